@@ -0,0 +1,20 @@
+use chip8_emu::bench::{self, BENCH_ROM, CONFIGURATIONS, DEFAULT_TICKS_PER_FRAME};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+// the same rom and configurations as `chip8-emu bench`, with criterion's statistics on top
+fn frames(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frame");
+    group.throughput(Throughput::Elements(DEFAULT_TICKS_PER_FRAME as u64));
+
+    for configuration in CONFIGURATIONS {
+        let mut chip8 = bench::prepare(BENCH_ROM, configuration).unwrap();
+
+        group.bench_function(configuration.name, |b| b.iter(|| chip8.advance_frame(DEFAULT_TICKS_PER_FRAME)));
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, frames);
+criterion_main!(benches);
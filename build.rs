@@ -0,0 +1,23 @@
+// with the `ffi` feature, regenerates include/chip8_emu.h when cbindgen is installed. the header
+// is checked in, so building without cbindgen only warns
+use std::env;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    if env::var_os("CARGO_FEATURE_FFI").is_none() {
+        return;
+    }
+
+    let status = Command::new("cbindgen")
+        .args(["--config", "cbindgen.toml", "--output", "include/chip8_emu.h", "src/ffi.rs"])
+        .status();
+
+    match status {
+        Ok(status) if status.success() => (),
+        Ok(status) => println!("cargo:warning=cbindgen failed ({}), include/chip8_emu.h may be out of date", status),
+        Err(_) => println!("cargo:warning=cbindgen isn't installed, include/chip8_emu.h wasn't regenerated"),
+    }
+}
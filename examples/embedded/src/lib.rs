@@ -0,0 +1,321 @@
+// the emulator as a handheld: a 128x64 SSD1306 over I2C shows the screen at 2x, a 4x4 button
+// matrix is the hex keypad and an optional buzzer pin beeps. a board's main only builds the HAL
+// pieces and hands them over, for example
+//
+//   let keypad = Keypad::new([row0, row1, row2, row3], [col0, col1, col2, col3]);
+//   let mut console = Console::new(i2c, keypad, buzzer, timer, ROM, seed)?;
+//   loop {
+//       console.step()?;
+//   }
+//
+// rows are outputs driven low one at a time, columns are inputs with pull-ups. the pacing only needs
+// timer ticks, no Duration, but the core still needs std and an allocator, so for now this runs on
+// std targets like embedded Linux boards
+
+use chip8_emu::{Chip8, Chip8Error, FrameClock, PackedScreen, SCREEN_WIDTH};
+
+use display_interface::DisplayError;
+use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal::i2c::I2c;
+use ssd1306::command::AddrMode;
+use ssd1306::mode::BasicMode;
+use ssd1306::prelude::*;
+use ssd1306::{I2CDisplayInterface, Ssd1306};
+
+pub const DISPLAY_WIDTH: usize = 128;
+pub const DISPLAY_HEIGHT: usize = 64;
+// the SSD1306 takes a byte for each column of 8 rows, a "page"
+pub const PAGE_BUFFER_SIZE: usize = DISPLAY_WIDTH * DISPLAY_HEIGHT / 8;
+
+const SCALE: usize = DISPLAY_WIDTH / SCREEN_WIDTH;
+const TICKS_PER_FRAME: usize = 10;
+// frames to catch up on at most after a stall, like the desktop
+const MAX_FRAMES: u32 = 4;
+
+// the COSMAC VIP keypad, row by row
+const MATRIX: [[usize; 4]; 4] = [[0x1, 0x2, 0x3, 0xC], [0x4, 0x5, 0x6, 0xD], [0x7, 0x8, 0x9, 0xE], [0xA, 0x0, 0xB, 0xF]];
+
+// a free-running counter, like a HAL's monotonic timer or a cycle counter. it may wrap
+pub trait Monotonic {
+    // not 0, FrameClock::advance_ticks panics on a timer without a rate
+    fn ticks_per_second(&self) -> u64;
+
+    fn now(&mut self) -> u64;
+}
+
+pub struct Keypad<R, C> {
+    rows: [R; 4],
+    columns: [C; 4],
+}
+
+impl<R: OutputPin, C: InputPin> Keypad<R, C> {
+    pub fn new(rows: [R; 4], columns: [C; 4]) -> Self {
+        Self { rows, columns }
+    }
+
+    // a pin that errors reads as not pressed
+    pub fn scan(&mut self) -> [bool; 16] {
+        let mut keys = [false; 16];
+
+        for (row, row_keys) in self.rows.iter_mut().zip(MATRIX) {
+            let _ = row.set_low();
+
+            for (column, key) in self.columns.iter_mut().zip(row_keys) {
+                keys[key] = column.is_low().unwrap_or(false);
+            }
+
+            let _ = row.set_high();
+        }
+
+        keys
+    }
+}
+
+// the screen at 2x in the SSD1306's page layout
+pub fn page_buffer(screen: &PackedScreen, out: &mut [u8; PAGE_BUFFER_SIZE]) {
+    let bytes = screen.bytes();
+
+    for (index, byte) in out.iter_mut().enumerate() {
+        let (page, x) = (index / DISPLAY_WIDTH, index % DISPLAY_WIDTH);
+        *byte = 0;
+
+        for bit in 0..8 {
+            let (source_x, source_y) = (x / SCALE, (page * 8 + bit) / SCALE);
+            let pixel = source_y * SCREEN_WIDTH + source_x;
+
+            if bytes[pixel / 8] & 0x80 >> (pixel % 8) != 0 {
+                *byte |= 1 << bit;
+            }
+        }
+    }
+}
+
+// why Console::new couldn't start
+#[derive(Debug)]
+pub enum StartError {
+    Display(DisplayError),
+    // the ROM is empty or too big
+    Rom(Chip8Error),
+}
+
+impl From<DisplayError> for StartError {
+    fn from(e: DisplayError) -> Self {
+        StartError::Display(e)
+    }
+}
+
+pub struct Console<I, R, C, B, T> {
+    chip8: Chip8,
+    display: Ssd1306<I2CInterface<I>, DisplaySize128x64, BasicMode>,
+    keypad: Keypad<R, C>,
+    buzzer: Option<B>,
+    timer: T,
+    clock: FrameClock,
+    last: u64,
+    buffer: [u8; PAGE_BUFFER_SIZE],
+}
+
+impl<I, R, C, B, T> Console<I, R, C, B, T>
+where
+    I: I2c,
+    R: OutputPin,
+    C: InputPin,
+    B: OutputPin,
+    T: Monotonic,
+{
+    // `seed` comes from wherever the board has entropy, an ADC's noise or the timer at a keypress
+    pub fn new(
+        i2c: I,
+        keypad: Keypad<R, C>,
+        buzzer: Option<B>,
+        mut timer: T,
+        rom: &[u8],
+        seed: u64,
+    ) -> Result<Self, StartError> {
+        let mut chip8 = Chip8::with_seed(seed);
+        chip8.load(rom).map_err(StartError::Rom)?;
+
+        let mut display = Ssd1306::new(I2CDisplayInterface::new(i2c), DisplaySize128x64, DisplayRotation::Rotate0);
+        display.init_with_addr_mode(AddrMode::Horizontal)?;
+
+        let last = timer.now();
+
+        Ok(Self {
+            chip8,
+            display,
+            keypad,
+            buzzer,
+            timer,
+            clock: FrameClock::new(MAX_FRAMES),
+            last,
+            buffer: [0; PAGE_BUFFER_SIZE],
+        })
+    }
+
+    // one pass of the main loop: read the keys, run the frames that are due and show the last
+    pub fn step(&mut self) -> Result<(), DisplayError> {
+        for (key, is_pressed) in self.keypad.scan().into_iter().enumerate() {
+            self.chip8.keypress(key, is_pressed);
+        }
+
+        let now = self.timer.now();
+        let frames = self.clock.advance_ticks(now.wrapping_sub(self.last), self.timer.ticks_per_second());
+        self.last = now;
+
+        if frames == 0 {
+            return Ok(());
+        }
+
+        for _ in 0..frames {
+            self.chip8.run_frame(TICKS_PER_FRAME);
+        }
+
+        if let Some(buzzer) = self.buzzer.as_mut() {
+            let _ = if self.chip8.is_beeping() { buzzer.set_high() } else { buzzer.set_low() };
+        }
+
+        page_buffer(&self.chip8.packed_display(), &mut self.buffer);
+        self.display.set_draw_area((0, 0), (DISPLAY_WIDTH as u8, DISPLAY_HEIGHT as u8))?;
+        self.display.draw(&self.buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chip8_emu::SCREEN_HEIGHT;
+    use embedded_hal::digital::{ErrorType, PinState};
+    use embedded_hal::i2c::{self, Operation, SevenBitAddress};
+
+    use std::cell::Cell;
+    use std::convert::Infallible;
+    use std::rc::Rc;
+
+    // which row is driven low, shared by the fake pins
+    type Driven = Rc<Cell<Option<usize>>>;
+
+    struct Row(usize, Driven);
+
+    impl ErrorType for Row {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for Row {
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.1.set(Some(self.0));
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.1.set(None);
+            Ok(())
+        }
+
+        fn set_state(&mut self, state: PinState) -> Result<(), Infallible> {
+            match state {
+                PinState::Low => self.set_low(),
+                PinState::High => self.set_high(),
+            }
+        }
+    }
+
+    // pressed at one row
+    struct Column(Option<usize>, Driven);
+
+    impl ErrorType for Column {
+        type Error = Infallible;
+    }
+
+    impl InputPin for Column {
+        fn is_high(&mut self) -> Result<bool, Infallible> {
+            Ok(!self.is_low()?)
+        }
+
+        fn is_low(&mut self) -> Result<bool, Infallible> {
+            Ok(self.0.is_some() && self.0 == self.1.get())
+        }
+    }
+
+    // counts the bytes sent to the display
+    struct Bus(Rc<Cell<usize>>);
+
+    impl i2c::ErrorType for Bus {
+        type Error = Infallible;
+    }
+
+    impl I2c for Bus {
+        fn transaction(&mut self, _: SevenBitAddress, operations: &mut [Operation<'_>]) -> Result<(), Infallible> {
+            for operation in operations {
+                if let Operation::Write(bytes) = operation {
+                    self.0.set(self.0.get() + bytes.len());
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    // a 1 kHz timer the test moves by hand
+    struct Timer(Rc<Cell<u64>>);
+
+    impl Monotonic for Timer {
+        fn ticks_per_second(&self) -> u64 {
+            1000
+        }
+
+        fn now(&mut self) -> u64 {
+            self.0.get()
+        }
+    }
+
+    #[test]
+    fn draws_once_a_frame_is_due() {
+        let (sent, time, driven) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(u64::MAX - 5)), Driven::default());
+        let keypad = Keypad::new([0, 1, 2, 3].map(|row| Row(row, driven.clone())), [0, 1, 2, 3].map(|_| Column(None, driven.clone())));
+        // draw "0" at (0, 0) then loop
+        let rom = [0xD0, 0x05, 0x12, 0x02];
+        let mut console = Console::new(Bus(sent.clone()), keypad, None::<Row>, Timer(time.clone()), &rom, 1).unwrap();
+
+        sent.set(0);
+        console.step().unwrap();
+        assert_eq!(sent.get(), 0);
+
+        // 17 ms is a frame, across the timer wrapping
+        time.set(time.get().wrapping_add(17));
+        console.step().unwrap();
+        assert!(sent.get() > PAGE_BUFFER_SIZE);
+        assert_eq!(console.chip8.frame_count(), 1);
+        // the left edge of "0" is 5 rows, 10 once doubled
+        assert_eq!((console.buffer[0], console.buffer[DISPLAY_WIDTH]), (0xFF, 0b11));
+    }
+
+    #[test]
+    fn scans_the_matrix_into_keys() {
+        let driven = Driven::default();
+        let rows = [0, 1, 2, 3].map(|row| Row(row, driven.clone()));
+        // row 1 column 1 is 5, row 3 column 3 is F
+        let columns = [None, Some(1), None, Some(3)].map(|row| Column(row, driven.clone()));
+        let keys = Keypad::new(rows, columns).scan();
+
+        let pressed: Vec<usize> = (0..16).filter(|&key| keys[key]).collect();
+        assert_eq!(pressed, vec![0x5, 0xF]);
+        assert_eq!(driven.get(), None);
+    }
+
+    #[test]
+    fn doubles_the_screen_into_pages() {
+        let mut screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        screen[0] = true;
+        screen[SCREEN_HEIGHT * SCREEN_WIDTH - 1] = true;
+        let mut buffer = [0; PAGE_BUFFER_SIZE];
+
+        page_buffer(&PackedScreen::pack(&screen), &mut buffer);
+
+        // (0, 0) is the top two rows of the first two columns
+        assert_eq!(buffer[..3], [0b11, 0b11, 0]);
+        // (63, 31) is the bottom two rows of the last two columns
+        assert_eq!(buffer[PAGE_BUFFER_SIZE - 2..], [0b1100_0000, 0b1100_0000]);
+        assert_eq!(buffer.iter().map(|byte| byte.count_ones()).sum::<u32>(), 8);
+    }
+}
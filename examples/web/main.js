@@ -0,0 +1,102 @@
+import init, { Emulator } from "./pkg/chip8_emu.js";
+
+// KeyboardEvent.code for each of the 16 keys, the same block as the desktop's default keypad
+const KEYPAD = [
+  "KeyX", "Digit1", "Digit2", "Digit3",
+  "KeyQ", "KeyW", "KeyE", "KeyA",
+  "KeyS", "KeyD", "KeyZ", "KeyC",
+  "Digit4", "KeyR", "KeyF", "KeyV",
+];
+
+// samples per audio callback, about three frames at 48 kHz
+const AUDIO_BUFFER = 2048;
+
+const canvas = document.getElementById("screen");
+const context = canvas.getContext("2d");
+const status = document.getElementById("status");
+
+let emulator = null;
+let audio = null;
+
+await init();
+
+document.getElementById("rom").addEventListener("change", async (event) => {
+  const file = event.target.files[0];
+  if (!file) {
+    return;
+  }
+
+  try {
+    emulator?.free();
+    emulator = new Emulator(new Uint8Array(await file.arrayBuffer()));
+    status.textContent = `${file.name}, seed ${emulator.seed()}`;
+    startAudio();
+  } catch (error) {
+    emulator = null;
+    status.textContent = error;
+  }
+});
+
+document.addEventListener("keydown", (event) => press(event, true));
+document.addEventListener("keyup", (event) => press(event, false));
+
+function press(event, isPressed) {
+  const key = KEYPAD.indexOf(event.code);
+  if (!emulator || key < 0) {
+    return;
+  }
+
+  event.preventDefault();
+  if (isPressed) {
+    emulator.keyDown(key);
+  } else {
+    emulator.keyUp(key);
+  }
+}
+
+// browsers only start audio after a user gesture, picking the file is one. a ScriptProcessor runs
+// on this thread, next to the emulator, where an AudioWorklet would need the samples posted over
+function startAudio() {
+  if (audio) {
+    return;
+  }
+
+  audio = new AudioContext();
+  const node = audio.createScriptProcessor(AUDIO_BUFFER, 0, 1);
+  node.onaudioprocess = (event) => {
+    const out = event.outputBuffer.getChannelData(0);
+    if (emulator) {
+      emulator.fillAudio(out, audio.sampleRate);
+    } else {
+      out.fill(0);
+    }
+  };
+  node.connect(audio.destination);
+}
+
+// requestAnimationFrame follows the display's refresh rate, so frames are run by the clock to keep
+// the game at 60 Hz on faster screens
+const FRAME_MS = 1000 / 60;
+let last = performance.now();
+let owed = 0;
+
+function loop(now) {
+  owed = Math.min(owed + (now - last) / FRAME_MS, 4);
+  last = now;
+
+  if (emulator) {
+    for (; owed >= 1; owed -= 1) {
+      emulator.runFrame();
+    }
+
+    if (canvas.width !== emulator.width || canvas.height !== emulator.height) {
+      canvas.width = emulator.width;
+      canvas.height = emulator.height;
+    }
+    context.putImageData(new ImageData(emulator.displayRgba(), emulator.width, emulator.height), 0, 0);
+  }
+
+  requestAnimationFrame(loop);
+}
+
+requestAnimationFrame(loop);
@@ -0,0 +1,26 @@
+// runs generated instructions straight through the interpreter, skipping the ones that would
+// crash it on purpose, and checks the invariants after each
+
+#![no_main]
+
+use chip8_emu::invariants::{can_execute, check};
+use chip8_emu::{Chip8, Instruction, Quirk};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: (u64, bool, Vec<Instruction>)| {
+    let (seed, is_shift_using_vy, instructions) = input;
+    let mut chip8 = Chip8::with_seed(seed);
+    chip8.set_quirk(Quirk::ShiftUsesVy, is_shift_using_vy);
+
+    for instruction in instructions {
+        if !can_execute(&chip8, instruction) {
+            continue;
+        }
+
+        chip8.execute_instruction(instruction);
+
+        if let Err(message) = check(&chip8, Some(instruction)) {
+            panic!("{}", message);
+        }
+    }
+});
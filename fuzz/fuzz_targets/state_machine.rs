@@ -0,0 +1,67 @@
+// a generated ROM driven the way a frontend would: ticks, key presses, timer ticks and save states
+// in any order. a save state has to come back as the same machine, FX0A waits included
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use chip8_emu::invariants::{can_tick, check};
+use chip8_emu::{Chip8, Instruction};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Clone, Copy, Debug)]
+enum Op {
+    Tick,
+    Key { key: u8, is_pressed: bool },
+    Timers,
+    SaveLoad,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    seed: u64,
+    rom: Vec<Instruction>,
+    ops: Vec<Op>,
+}
+
+fuzz_target!(|input: Input| {
+    let rom: Vec<u8> = input.rom.iter().flat_map(|instruction| instruction.encode().to_be_bytes()).collect();
+    let mut chip8 = Chip8::with_seed(input.seed);
+    if chip8.load(&rom).is_err() {
+        return;
+    }
+
+    for op in input.ops {
+        let mut executed = None;
+
+        match op {
+            Op::Tick => {
+                if !can_tick(&chip8) {
+                    continue;
+                }
+
+                let pc = chip8.program_counter() as usize;
+                let memory = chip8.memory();
+                executed = Instruction::decode(u16::from_be_bytes([memory[pc], memory[pc + 1]]));
+                chip8.tick().unwrap();
+            },
+            Op::Key { key, is_pressed } => chip8.keypress(key as usize % 16, is_pressed),
+            Op::Timers => {
+                chip8.tick_timers();
+            },
+            Op::SaveLoad => {
+                let state = chip8.save_state();
+                let mut loaded = Chip8::with_seed(input.seed);
+                loaded.load(&rom).unwrap();
+                loaded.load_state(&state).unwrap();
+
+                assert_eq!(loaded.save_state(), state, "the save state didn't round trip");
+                assert_eq!(loaded.program_counter(), chip8.program_counter());
+                chip8 = loaded;
+            },
+        }
+
+        if let Err(message) = check(&chip8, executed) {
+            panic!("{:?}: {}", op, message);
+        }
+    }
+});
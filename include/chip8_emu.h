@@ -0,0 +1,106 @@
+/* Generated by cbindgen from src/ffi.rs, don't edit by hand */
+
+#ifndef CHIP8_EMU_H
+#define CHIP8_EMU_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// What every call that can fail returns.
+typedef enum Chip8Status {
+  CHIP8_STATUS_OK = 0,
+  // A pointer argument was NULL.
+  CHIP8_STATUS_NULL_POINTER = 1,
+  // The ROM doesn't fit in the 3584 bytes of program memory.
+  CHIP8_STATUS_ROM_TOO_LARGE = 2,
+  // The key isn't 0-15.
+  CHIP8_STATUS_INVALID_KEY = 3,
+  // The buffer can't hold the save state, the size needed was written to `written`.
+  CHIP8_STATUS_BUFFER_TOO_SMALL = 4,
+  // The data isn't a save state this version can read.
+  CHIP8_STATUS_INVALID_STATE = 5,
+  // The save state was taken with a different ROM loaded.
+  CHIP8_STATUS_ROM_MISMATCH = 6,
+  // The emulator panicked.
+  CHIP8_STATUS_PANIC = 7,
+  // An instruction couldn't run, like an unknown opcode or a RET with nothing to return to. The
+  // machine is left paused just before it.
+  CHIP8_STATUS_FAULT = 8,
+  // The ROM is empty.
+  CHIP8_STATUS_EMPTY_ROM = 9,
+} Chip8Status;
+
+// A CHIP-8 machine. Only ever handled through the pointer chip8_new returns.
+typedef struct Chip8 Chip8;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// A new machine with nothing loaded, or NULL if it couldn't be made.
+// Free it with chip8_free.
+Chip8 *chip8_new(void);
+
+// Frees a machine from chip8_new. NULL is ignored. Any display pointer from chip8_get_display
+// is invalid afterwards.
+//
+// # Safety
+// `chip8` is NULL or from chip8_new, and isn't used again.
+void chip8_free(Chip8 *chip8);
+
+// Resets the machine and loads `len` bytes of ROM from `rom`. The bytes are copied, the caller
+// keeps ownership of `rom`. A ROM that's empty or too large leaves the machine as it was.
+//
+// # Safety
+// `chip8` is from chip8_new, `rom` points to `len` readable bytes.
+Chip8Status chip8_load(Chip8 *chip8, const uint8_t *rom, size_t len);
+
+// Runs one instruction.
+//
+// # Safety
+// `chip8` is from chip8_new.
+Chip8Status chip8_tick(Chip8 *chip8);
+
+// Runs one 60 Hz frame: `ticks_per_frame` instructions, then the timers count down once.
+//
+// # Safety
+// `chip8` is from chip8_new.
+Chip8Status chip8_run_frame(Chip8 *chip8, size_t ticks_per_frame);
+
+// Presses (`is_pressed` true) or releases key 0-15 of the hex keypad.
+//
+// # Safety
+// `chip8` is from chip8_new.
+Chip8Status chip8_keypress(Chip8 *chip8, size_t key, bool is_pressed);
+
+// Points `pixels` at the screen, `width` * `height` bools row by row with true for a lit pixel.
+// The machine owns the pixels. They change as it runs and are valid until chip8_free.
+//
+// # Safety
+// `chip8` is from chip8_new, the out pointers are writable.
+Chip8Status chip8_get_display(Chip8 *chip8, const bool **pixels, size_t *width, size_t *height);
+
+// Writes a save state into the caller's `buffer` of `capacity` bytes and its size to `written`.
+// With a NULL or too small buffer nothing is copied, it returns CHIP8_STATUS_BUFFER_TOO_SMALL and
+// `written` is the size needed, so a first call with a NULL buffer asks for the size.
+//
+// # Safety
+// `chip8` is from chip8_new, `buffer` is NULL or has `capacity` writable bytes, `written` is
+// writable.
+Chip8Status chip8_save_state(Chip8 *chip8, uint8_t *buffer, size_t capacity, size_t *written);
+
+// Restores a save state from chip8_save_state, taken with the same ROM loaded. The bytes are
+// copied, the caller keeps ownership of `state`.
+//
+// # Safety
+// `chip8` is from chip8_new, `state` points to `len` readable bytes.
+Chip8Status chip8_load_state(Chip8 *chip8, const uint8_t *state, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CHIP8_EMU_H */
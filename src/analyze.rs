@@ -0,0 +1,280 @@
+// what can be told about a ROM without running it. the code is whatever the start address reaches
+// by following jumps, calls and both sides of every skip, the rest is taken for data. a BNNN jump
+// depends on V0, so the analysis can't follow it
+//
+// the lints are for people writing ROMs, each one a pattern that runs but rarely does what was
+// meant. a finding is the address it's at and why it's there
+
+use crate::disasm::disassemble;
+use crate::START_ADDRESS;
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Analysis {
+    // the instructions the start address reaches, by address
+    pub code: BTreeSet<u16>,
+    pub rom_size: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Lint {
+    // VF set as a value, then overwritten with a flag by the next instruction
+    VfClobbered,
+    // FX33 writing its digits over the program
+    BcdIntoCode,
+    // DXY0, a 16x16 sprite on SUPER-CHIP but nothing at all here
+    ZeroHeightSprite,
+    // a skip over the first half of F000 NNNN, landing on its address word
+    SkipIntoLongLoad,
+    // instructions are two bytes from an even address, an odd one runs their halves mixed up
+    OddJump,
+}
+
+impl Lint {
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::VfClobbered => "vf_clobbered",
+            Lint::BcdIntoCode => "bcd_into_code",
+            Lint::ZeroHeightSprite => "zero_height_sprite",
+            Lint::SkipIntoLongLoad => "skip_into_long_load",
+            Lint::OddJump => "odd_jump",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub address: u16,
+    pub lint: Lint,
+    pub explanation: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:03X} {}: {}", self.address, self.lint.name(), self.explanation)
+    }
+}
+
+// the opcode at `address` of a ROM loaded at the start address, None outside it
+pub fn opcode_at(rom: &[u8], address: u16) -> Option<u16> {
+    let index = address.checked_sub(START_ADDRESS)? as usize;
+
+    match (rom.get(index), rom.get(index + 1)) {
+        (Some(&high), Some(&low)) => Some(u16::from_be_bytes([high, low])),
+        _ => None,
+    }
+}
+
+fn is_skip(opcode: u16) -> bool {
+    matches!(opcode >> 12, 0x3 | 0x4)
+        || matches!(opcode & 0xF00F, 0x5000 | 0x9000)
+        || matches!(opcode & 0xF0FF, 0xE09E | 0xE0A1)
+}
+
+// where running `opcode` at `address` can go next, as far as the ROM alone says
+fn successors(address: u16, opcode: u16) -> Vec<u16> {
+    let next = address.wrapping_add(2);
+    let nnn = opcode & 0x0FFF;
+    // odd targets are a lint, not somewhere to look for more code
+    let target = nnn.is_multiple_of(2).then_some(nnn);
+
+    if opcode == 0x00EE {
+        return Vec::new();
+    }
+
+    if opcode == 0xF000 {
+        return vec![address.wrapping_add(4)];
+    }
+
+    if is_skip(opcode) {
+        return vec![next, address.wrapping_add(4)];
+    }
+
+    match opcode >> 12 {
+        0x1 => target.into_iter().collect(),
+        0x2 => target.into_iter().chain([next]).collect(),
+        0xB => Vec::new(),
+        _ => vec![next],
+    }
+}
+
+pub fn analyze(rom: &[u8]) -> Analysis {
+    let mut code = BTreeSet::new();
+    let mut pending = vec![START_ADDRESS];
+
+    while let Some(address) = pending.pop() {
+        let Some(opcode) = opcode_at(rom, address) else {
+            continue;
+        };
+
+        if code.insert(address) {
+            pending.extend(successors(address, opcode));
+        }
+    }
+
+    Analysis { code, rom_size: rom.len() }
+}
+
+impl Analysis {
+    fn is_code_byte(&self, address: u16) -> bool {
+        self.code.contains(&address) || self.code.contains(&address.wrapping_sub(1))
+    }
+
+    // ROM bytes no instruction covers
+    pub fn data_bytes(&self) -> usize {
+        (0..self.rom_size as u16).filter(|&offset| !self.is_code_byte(START_ADDRESS + offset)).count()
+    }
+
+    // the code as the disassembler reads it, `PC OPCODE assembly` a line
+    pub fn listing(&self, rom: &[u8]) -> String {
+        self.code
+            .iter()
+            .filter_map(|&address| opcode_at(rom, address).map(|opcode| (address, opcode)))
+            .map(|(address, opcode)| format!("{:03X} {:04X} {}\n", address, opcode, disassemble(opcode)))
+            .collect()
+    }
+
+    // by address, then in the order of Lint
+    pub fn lints(&self, rom: &[u8]) -> Vec<Finding> {
+        let mut findings = Vec::new();
+
+        for &address in &self.code {
+            let Some(opcode) = opcode_at(rom, address) else {
+                continue;
+            };
+            let next = address.wrapping_add(2);
+            let next_opcode = opcode_at(rom, next);
+            let mut find = |lint, explanation: String| findings.push(Finding { address, lint, explanation });
+
+            if writes_vf(opcode) && self.code.contains(&next) {
+                if let Some(clobber) = next_opcode.filter(|&next_opcode| clobbers_vf(next_opcode)) {
+                    find(
+                        Lint::VfClobbered,
+                        format!(
+                            "VF is set here but {} at {:03X} overwrites it with a flag",
+                            disassemble(clobber),
+                            next
+                        ),
+                    );
+                }
+            }
+
+            if opcode & 0xF0FF == 0xF033 {
+                if let Some(i) = self.known_i(rom, address) {
+                    if (i..i.wrapping_add(3)).any(|byte| self.is_code_byte(byte)) {
+                        find(Lint::BcdIntoCode, format!("I is {:03X}, so the digits overwrite the program there", i));
+                    }
+                }
+            }
+
+            if opcode & 0xF00F == 0xD000 {
+                find(
+                    Lint::ZeroHeightSprite,
+                    "a height of 0 only draws on SUPER-CHIP, here it draws nothing".to_string(),
+                );
+            }
+
+            if is_skip(opcode) && next_opcode == Some(0xF000) {
+                find(
+                    Lint::SkipIntoLongLoad,
+                    format!("skipping jumps 2 bytes into the F000 NNNN at {:03X}, onto its address", next),
+                );
+            }
+
+            if matches!(opcode >> 12, 0x1 | 0x2 | 0xB) && opcode % 2 == 1 {
+                find(
+                    Lint::OddJump,
+                    format!("{:03X} is odd, so every instruction from there is misaligned", opcode & 0x0FFF),
+                );
+            }
+        }
+
+        findings
+    }
+
+    // I at `address` when an ANNN before it in the same run of instructions set it
+    fn known_i(&self, rom: &[u8], address: u16) -> Option<u16> {
+        let mut at = address;
+
+        loop {
+            at = at.checked_sub(2).filter(|at| self.code.contains(at))?;
+            let opcode = opcode_at(rom, at)?;
+
+            match opcode >> 12 {
+                0xA => return Some(opcode & 0x0FFF),
+                // I could have changed another way, or the run doesn't reach here by falling through
+                0x0..=0x2 | 0xB => return None,
+                0xF if matches!(opcode & 0xFF, 0x1E | 0x29) => return None,
+                _ => (),
+            }
+        }
+    }
+}
+
+// VF written as a value rather than as a flag
+fn writes_vf(opcode: u16) -> bool {
+    let x = (opcode >> 8) & 0xF;
+
+    x == 0xF
+        && match opcode >> 12 {
+            0x6 | 0x7 | 0xC => true,
+            0x8 => matches!(opcode & 0xF, 0x0..=0x3),
+            0xF => matches!(opcode & 0xFF, 0x07 | 0x0A | 0x65),
+            _ => false,
+        }
+}
+
+// sets VF as a flag without reading it first, so whatever was in it is gone
+fn clobbers_vf(opcode: u16) -> bool {
+    let (x, y) = ((opcode >> 8) & 0xF, (opcode >> 4) & 0xF);
+    let is_clobber = match opcode >> 12 {
+        0x8 => matches!(opcode & 0xF, 0x4 | 0x5 | 0x6 | 0x7 | 0xE),
+        0xD => true,
+        _ => false,
+    };
+
+    is_clobber && x != 0xF && y != 0xF
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lints(rom: &[u8]) -> Vec<Lint> {
+        analyze(rom).lints(rom).into_iter().map(|finding| finding.lint).collect()
+    }
+
+    #[test]
+    fn follows_jumps_calls_and_skips() {
+        // CALL 208, SE V0 0, JMP 20A twice, RET, JMP 20A, then data
+        let rom = [0x22, 0x08, 0x30, 0x00, 0x12, 0x0A, 0x12, 0x0A, 0x00, 0xEE, 0x12, 0x0A, 0xAB, 0xCD];
+        let analysis = analyze(&rom);
+
+        assert_eq!(analysis.code.iter().copied().collect::<Vec<_>>(), [0x200, 0x202, 0x204, 0x206, 0x208, 0x20A]);
+        assert_eq!(analysis.data_bytes(), 2);
+        assert!(analysis.listing(&rom).starts_with("200 2208 CALL 0x208\n202 3000 "));
+    }
+
+    #[test]
+    fn stops_at_computed_jumps_and_the_end_of_the_rom() {
+        assert_eq!(analyze(&[0xB3, 0x00, 0x00, 0xE0]).code.len(), 1);
+        assert_eq!(analyze(&[0x00, 0xE0, 0x00]).code.len(), 1);
+    }
+
+    #[test]
+    fn vf_used_as_a_flag_is_fine() {
+        // V0 += V1 then VF copied out, and VF read by the add that clobbers it
+        assert!(lints(&[0x80, 0x14, 0x82, 0xF0, 0x6F, 0x01, 0x80, 0xF4, 0x12, 0x06]).is_empty());
+    }
+
+    #[test]
+    fn knows_i_only_within_a_run_of_instructions() {
+        // I = 200 then FX1E before the BCD, so I isn't known there
+        assert!(lints(&[0xA2, 0x00, 0xF0, 0x1E, 0xF0, 0x33, 0x12, 0x06]).is_empty());
+        // I = 300 is past the code
+        assert!(lints(&[0xA3, 0x00, 0xF0, 0x33, 0x12, 0x04]).is_empty());
+        assert_eq!(lints(&[0xA2, 0x04, 0xF0, 0x33, 0x12, 0x04]), [Lint::BcdIntoCode]);
+    }
+}
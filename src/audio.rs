@@ -0,0 +1,368 @@
+use crate::Chip8;
+
+pub const DEFAULT_BEEP_FREQUENCY: f32 = 440.0;
+pub const DEFAULT_BEEP_VOLUME: f32 = 0.25;
+pub const AUDIO_PATTERN_SIZE: usize = 16;
+pub const DEFAULT_AUDIO_PITCH: u8 = 64;
+
+const PATTERN_BITS: f32 = (AUDIO_PATTERN_SIZE * 8) as f32;
+// long enough to hide the click of a square wave switching on, short enough not to be heard
+const RAMP_SECONDS: f32 = 0.002;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sine,
+}
+
+impl Waveform {
+    // one period maps phase 0.0..1.0 to -1.0..=1.0
+    fn sample(self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+            Waveform::Triangle => 4.0 * (phase - 0.5).abs() - 1.0,
+            Waveform::Sine => (phase * std::f32::consts::TAU).sin(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BeepConfig {
+    pub frequency_hz: f32,
+    pub waveform: Waveform,
+    // peak amplitude, 0.0..=1.0
+    pub volume: f32,
+    // beeps shorter than this are stretched so a 1 frame ST still makes a sound, 0.0 to disable
+    pub min_duration_ms: f32,
+}
+
+impl Default for BeepConfig {
+    fn default() -> Self {
+        Self {
+            frequency_hz: DEFAULT_BEEP_FREQUENCY,
+            waveform: Waveform::Square,
+            volume: DEFAULT_BEEP_VOLUME,
+            min_duration_ms: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Beeper {
+    config: BeepConfig,
+    // position within the current period, 0.0..1.0
+    phase: f32,
+    envelope: f32,
+    // a beep started since the last fill, it may already be over by the time audio is generated
+    is_triggered: bool,
+    hold_samples: usize,
+}
+
+impl Beeper {
+    pub(crate) fn new() -> Self {
+        Self {
+            config: BeepConfig::default(),
+            phase: 0.0,
+            envelope: 0.0,
+            is_triggered: false,
+            hold_samples: 0,
+        }
+    }
+
+    pub(crate) fn trigger(&mut self) {
+        self.is_triggered = true;
+    }
+
+    fn fill(&mut self, out: &mut [f32], sample_rate: u32, is_beeping: bool, pattern: Option<(&[u8], u8)>) {
+        let sample_rate = sample_rate as f32;
+        // with a pattern one phase is the whole 128 bit loop rather than one period of the tone
+        let phase_step = match pattern {
+            Some((_, pitch)) => pattern_bit_rate(pitch) / PATTERN_BITS / sample_rate,
+            None => self.config.frequency_hz / sample_rate,
+        };
+        let ramp_step = 1.0 / (RAMP_SECONDS * sample_rate).max(1.0);
+
+        if self.is_triggered {
+            self.hold_samples = (self.config.min_duration_ms / 1000.0 * sample_rate) as usize;
+            self.is_triggered = false;
+        }
+
+        for sample in out.iter_mut() {
+            let target = if is_beeping || self.hold_samples > 0 { 1.0 } else { 0.0 };
+            self.hold_samples = self.hold_samples.saturating_sub(1);
+
+            if self.envelope < target {
+                self.envelope = (self.envelope + ramp_step).min(target);
+            } else if self.envelope > target {
+                self.envelope = (self.envelope - ramp_step).max(target);
+            }
+
+            if self.envelope == 0.0 {
+                // restart silent beeps on a period boundary so every beep sounds the same
+                self.phase = 0.0;
+                *sample = 0.0;
+                continue;
+            }
+
+            let wave = match pattern {
+                Some((bytes, _)) => pattern_sample(bytes, self.phase),
+                None => self.config.waveform.sample(self.phase),
+            };
+            *sample = wave * self.envelope * self.config.volume;
+
+            self.phase = (self.phase + phase_step).fract();
+        }
+    }
+}
+
+// XO-CHIP plays 4000 bits per second at pitch 64, doubling every 48 steps
+fn pattern_bit_rate(pitch: u8) -> f32 {
+    4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0)
+}
+
+// nearest neighbour is plenty for a 1-bit source
+fn pattern_sample(bytes: &[u8], phase: f32) -> f32 {
+    let bit = ((phase * PATTERN_BITS) as usize).min(PATTERN_BITS as usize - 1);
+
+    if bytes[bit / 8] & (0b1000_0000 >> (bit % 8)) != 0 { 1.0 } else { -1.0 }
+}
+
+impl Chip8 {
+    // synthesize the beep for `out.len()` samples, call once per buffer with consecutive buffers.
+    // plays the XO-CHIP pattern buffer once a program has loaded one, the configured tone otherwise
+    pub fn fill_audio(&mut self, out: &mut [f32], sample_rate: u32) {
+        // the sound timer is frozen while paused, so a beep would otherwise drone on,
+        // and fast-forwarded beeps just screech
+        let is_beeping = self.is_beeping() && !self.is_paused() && self.speed_multiplier() <= 1.0;
+        let pattern = self
+            .audio_pattern
+            .as_ref()
+            .map(|bytes| (&bytes[..], self.audio_pitch));
+
+        self.beeper.fill(out, sample_rate, is_beeping, pattern);
+
+        if let Some(recorder) = self.audio_recorder.as_mut() {
+            recorder.record(out, sample_rate);
+        }
+    }
+
+    pub fn beep_config(&self) -> BeepConfig {
+        self.beeper.config
+    }
+
+    pub fn set_beep_config(&mut self, config: BeepConfig) {
+        self.beeper.config = config;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 44100;
+
+    fn beeping_chip8(frames: u8) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        // V0 = frames, ST = V0
+        chip8.load(&[0x60, frames, 0xF0, 0x18]).unwrap();
+        chip8.tick().unwrap();
+        chip8.tick().unwrap();
+
+        chip8
+    }
+
+    fn max_step(samples: &[f32]) -> f32 {
+        samples
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]).abs())
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn is_silent_without_beep() {
+        let mut chip8 = Chip8::new();
+        let mut out = [1.0; 512];
+        chip8.fill_audio(&mut out, SAMPLE_RATE);
+
+        assert!(out.iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn consecutive_buffers_join_seamlessly() {
+        let mut whole = beeping_chip8(10);
+        let mut split = beeping_chip8(10);
+
+        let mut expected = [0.0; 1024];
+        whole.fill_audio(&mut expected, SAMPLE_RATE);
+
+        let mut actual = [0.0; 1024];
+        for chunk in actual.chunks_mut(100) {
+            split.fill_audio(chunk, SAMPLE_RATE);
+        }
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn ramps_in_and_out() {
+        let mut chip8 = beeping_chip8(1);
+        let ramp = (RAMP_SECONDS * SAMPLE_RATE as f32) as usize;
+        let ramp_step = DEFAULT_BEEP_VOLUME / ramp as f32;
+
+        let mut start = vec![0.0; ramp];
+        chip8.fill_audio(&mut start, SAMPLE_RATE);
+
+        chip8.tick_timers();
+        assert!(!chip8.is_beeping());
+
+        let mut end = vec![0.0; ramp * 2];
+        chip8.fill_audio(&mut end, SAMPLE_RATE);
+
+        // the square wave flips sign within the ramp, so compare magnitudes
+        let magnitudes: Vec<f32> = start.iter().chain(&end).map(|sample| sample.abs()).collect();
+
+        assert!(magnitudes[0] <= ramp_step * 1.01);
+        assert!(max_step(&magnitudes) <= ramp_step * 1.01);
+        assert!(end[ramp..].iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn stretches_short_beeps_to_the_minimum_duration() {
+        let mut chip8 = beeping_chip8(1);
+        chip8.set_beep_config(BeepConfig { min_duration_ms: 50.0, ..BeepConfig::default() });
+
+        // the timer has already run out by the time the frontend asks for audio
+        chip8.tick_timers();
+        assert!(!chip8.is_beeping());
+
+        let mut out = vec![0.0; SAMPLE_RATE as usize / 10];
+        for chunk in out.chunks_mut(735) {
+            chip8.fill_audio(chunk, SAMPLE_RATE);
+        }
+
+        let min_samples = SAMPLE_RATE as usize * 50 / 1000;
+        let last_sound = out.iter().rposition(|&sample| sample != 0.0).unwrap();
+        assert!(last_sound >= min_samples);
+        assert!(out[0] != 0.0);
+
+        // the tail fades out rather than cutting off
+        let ramp = (RAMP_SECONDS * SAMPLE_RATE as f32) as usize;
+        let tail: Vec<f32> = out[last_sound + 1 - ramp / 2..=last_sound].iter().map(|s| s.abs()).collect();
+        assert!(tail.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert!(tail[tail.len() - 1] < DEFAULT_BEEP_VOLUME / 10.0);
+    }
+
+    fn dominant_period(samples: &[f32]) -> f32 {
+        let rising_edges: Vec<usize> = samples
+            .windows(2)
+            .enumerate()
+            .filter(|(_, pair)| pair[0] < 0.0 && pair[1] >= 0.0)
+            .map(|(i, _)| i)
+            .collect();
+
+        (rising_edges[rising_edges.len() - 1] - rising_edges[0]) as f32 / (rising_edges.len() - 1) as f32
+    }
+
+    fn pattern_chip8(pattern: u8, pitch: u8) -> Chip8 {
+        let mut rom = vec![
+            0xA2, 0x10, // I = pattern
+            0xF0, 0x02, // AUDIO = [I]
+            0x60, pitch, // V0 = pitch
+            0xF0, 0x3A, // PITCH = V0
+            0x60, 0x3C, // V0 = 60
+            0xF0, 0x18, // ST = V0
+            0x12, 0x0C, // spin
+            0x00, 0x00,
+        ];
+        rom.extend_from_slice(&[pattern; AUDIO_PATTERN_SIZE]);
+
+        let mut chip8 = Chip8::new();
+        chip8.load(&rom).unwrap();
+
+        for _ in 0..6 {
+            chip8.tick().unwrap();
+        }
+
+        chip8
+    }
+
+    #[test]
+    fn plays_the_pattern_at_the_default_pitch() {
+        // 4 bits high, 4 low: 4000 / 8 = 500Hz, 96 samples per period at 48kHz
+        let mut chip8 = pattern_chip8(0xF0, DEFAULT_AUDIO_PITCH);
+
+        let mut out = vec![0.0; 4800];
+        chip8.fill_audio(&mut out, 48000);
+
+        assert!((dominant_period(&out[200..]) - 96.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn pitch_changes_the_pattern_rate() {
+        // 48 steps above 64 doubles the rate: 1000Hz, 48 samples per period
+        let mut chip8 = pattern_chip8(0xF0, DEFAULT_AUDIO_PITCH + 48);
+
+        let mut out = vec![0.0; 4800];
+        chip8.fill_audio(&mut out, 48000);
+
+        assert!((dominant_period(&out[200..]) - 48.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn pattern_is_gated_by_the_sound_timer() {
+        let mut chip8 = pattern_chip8(0xAA, DEFAULT_AUDIO_PITCH);
+
+        for _ in 0..60 {
+            chip8.tick_timers();
+        }
+
+        let mut out = vec![1.0; 480];
+        chip8.fill_audio(&mut out, 48000);
+
+        assert!(out[200..].iter().all(|&sample| sample == 0.0));
+    }
+
+    fn assert_period_and_peak(waveform: Waveform) {
+        let mut chip8 = beeping_chip8(60);
+        chip8.set_beep_config(BeepConfig { frequency_hz: 441.0, waveform, volume: 0.5, ..BeepConfig::default() });
+
+        let mut out = vec![0.0; SAMPLE_RATE as usize / 10];
+        chip8.fill_audio(&mut out, SAMPLE_RATE);
+
+        // skip the attack ramp
+        let settled = &out[200..];
+
+        let rising_edges: Vec<usize> = settled
+            .windows(2)
+            .enumerate()
+            .filter(|(_, pair)| pair[0] < 0.0 && pair[1] >= 0.0)
+            .map(|(i, _)| i)
+            .collect();
+
+        // 44100 / 441 = 100 samples per period
+        for pair in rising_edges.windows(2) {
+            assert!((pair[1] - pair[0]).abs_diff(100) <= 1, "{:?}", waveform);
+        }
+
+        assert!(rising_edges.len() >= 40, "{:?}", waveform);
+
+        let peak = settled.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        assert!((peak - 0.5).abs() < 0.01, "{:?} peaked at {}", waveform, peak);
+    }
+
+    #[test]
+    fn square_wave_has_the_expected_period_and_peak() {
+        assert_period_and_peak(Waveform::Square);
+    }
+
+    #[test]
+    fn triangle_wave_has_the_expected_period_and_peak() {
+        assert_period_and_peak(Waveform::Triangle);
+    }
+
+    #[test]
+    fn sine_wave_has_the_expected_period_and_peak() {
+        assert_period_and_peak(Waveform::Sine);
+    }
+}
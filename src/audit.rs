@@ -0,0 +1,465 @@
+// checking the core is deterministic: the same ROM, seed and input run twice side by side should
+// be the same machine after every frame. the first frame they differ on is reported with what
+// differs, and the instruction that made them differ when stepping back can find it.
+//
+// a run can also be written as a log of per-frame state hashes and checked against later, on
+// another build or another machine:
+//
+//   # chip8-emu audit log
+//   0 3fa9c0d2e4b81765
+//   1 ...
+//
+// the seed itself isn't part of the state, only what RND has handed out so far, so two seeds only
+// differ once the ROM asks for a random number
+
+use crate::headless::{self, crash_message, Options};
+use crate::{Chip8, Chip8Error, Quirks, DEFAULT_STEP_BACK_INTERVAL, NUM_KEYS, SCREEN_WIDTH};
+
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fmt;
+
+// past this many differing bytes the rest are counted rather than listed
+const MAX_LISTED_BYTES: usize = 16;
+
+// everything a frame can change that shows or that later frames depend on. the counters aren't
+// part of it, save states leave them out too
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditState {
+    program_counter: u16,
+    register_v: Vec<u8>,
+    register_i: u16,
+    stack: Vec<u16>,
+    delay_timer: u8,
+    sound_timer: u8,
+    keys: Vec<bool>,
+    ram: Vec<u8>,
+    screen: Vec<bool>,
+    quirks: Quirks,
+    frame_count: u64,
+    // how many random words have been drawn
+    rng_position: u128,
+}
+
+impl AuditState {
+    pub fn of(chip8: &Chip8) -> Self {
+        Self {
+            program_counter: chip8.program_counter,
+            register_v: chip8.register_v.to_vec(),
+            register_i: chip8.register_i,
+            stack: chip8.stack[..chip8.stack_pointer as usize].to_vec(),
+            delay_timer: chip8.delay_timer,
+            sound_timer: chip8.sound_timer,
+            keys: chip8.keys.to_vec(),
+            ram: chip8.ram.clone(),
+            screen: chip8.screen.to_vec(),
+            quirks: chip8.quirks,
+            frame_count: chip8.frame_count,
+            rng_position: chip8.rng.get_word_pos(),
+        }
+    }
+
+    // for machines meant to differ in nothing but their quirks
+    pub fn without_quirks(mut self) -> Self {
+        self.quirks = Quirks::default();
+        self
+    }
+
+    // the first 8 bytes of a SHA-256 over every field
+    pub fn hash(&self) -> u64 {
+        let mut hasher = Sha256::new();
+        hasher.update(self.program_counter.to_le_bytes());
+        hasher.update(&self.register_v);
+        hasher.update(self.register_i.to_le_bytes());
+        hasher.update(self.stack.iter().flat_map(|address| address.to_le_bytes()).collect::<Vec<u8>>());
+        hasher.update([self.delay_timer, self.sound_timer]);
+        hasher.update(self.keys.iter().map(|&is_down| is_down as u8).collect::<Vec<u8>>());
+        hasher.update(&self.ram);
+        hasher.update(self.screen.iter().map(|&pixel| pixel as u8).collect::<Vec<u8>>());
+        hasher.update(format!("{:?}", self.quirks));
+        hasher.update(self.frame_count.to_le_bytes());
+        hasher.update(self.rng_position.to_le_bytes());
+
+        u64::from_be_bytes(hasher.finalize()[..8].try_into().unwrap())
+    }
+
+    // "V3: 2A vs 91" for each field that differs, this run first
+    pub fn differences(&self, other: &Self) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut differ = |name: &str, this: String, other: String| {
+            if this != other {
+                lines.push(format!("{}: {} vs {}", name, this, other));
+            }
+        };
+
+        differ("PC", format!("{:03X}", self.program_counter), format!("{:03X}", other.program_counter));
+        for (index, (this, other)) in self.register_v.iter().zip(&other.register_v).enumerate() {
+            differ(&format!("V{:X}", index), format!("{:02X}", this), format!("{:02X}", other));
+        }
+        differ("I", format!("{:03X}", self.register_i), format!("{:03X}", other.register_i));
+        differ("stack", format!("{:03X?}", self.stack), format!("{:03X?}", other.stack));
+        differ("delay timer", self.delay_timer.to_string(), other.delay_timer.to_string());
+        differ("sound timer", self.sound_timer.to_string(), other.sound_timer.to_string());
+        differ("keys", key_list(&self.keys), key_list(&other.keys));
+        differ("quirks", format!("{:?}", self.quirks), format!("{:?}", other.quirks));
+        differ("frames", self.frame_count.to_string(), other.frame_count.to_string());
+        differ("random numbers drawn", self.rng_position.to_string(), other.rng_position.to_string());
+
+        let bytes: Vec<usize> =
+            (0..self.ram.len().min(other.ram.len())).filter(|&a| self.ram[a] != other.ram[a]).collect();
+        for &address in bytes.iter().take(MAX_LISTED_BYTES) {
+            lines.push(format!("[{:03X}]: {:02X} vs {:02X}", address, self.ram[address], other.ram[address]));
+        }
+        if bytes.len() > MAX_LISTED_BYTES {
+            lines.push(format!("and {} more bytes of RAM", bytes.len() - MAX_LISTED_BYTES));
+        }
+
+        let pixels: Vec<usize> =
+            (0..self.screen.len()).filter(|&index| self.screen[index] != other.screen[index]).collect();
+        if let Some(&first) = pixels.first() {
+            let (x, y) = (first % SCREEN_WIDTH, first / SCREEN_WIDTH);
+            lines.push(format!("screen: {} pixels differ, the first at ({}, {})", pixels.len(), x, y));
+        }
+
+        lines
+    }
+}
+
+// "3 A", the keys held down
+fn key_list(keys: &[bool]) -> String {
+    let held: Vec<String> = (0..NUM_KEYS).filter(|&key| keys[key]).map(|key| format!("{:X}", key)).collect();
+
+    if held.is_empty() {
+        "none".to_string()
+    } else {
+        held.join(" ")
+    }
+}
+
+// how far two runs agreed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Agreement {
+    pub frames: usize,
+    // both runs crashed the same way on the last frame, which ended the audit
+    pub crash: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    // counted from 0, like input scripts
+    pub frame: usize,
+    // the program counter and opcode of the first instruction whose result differed, None when
+    // stepping back couldn't find it, or when comparing against a log
+    pub instruction: Option<(u16, u16)>,
+    // as AuditState::differences, empty against a log
+    pub differences: Vec<String>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the runs differ after frame {}", self.frame)?;
+
+        if let Some((pc, opcode)) = self.instruction {
+            write!(f, ", first at {:03X} running {:04X}", pc, opcode)?;
+        }
+
+        for line in &self.differences {
+            write!(f, "\n  {}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for Divergence {}
+
+// runs `play` on each machine for `frames` frames, comparing them after each one. both keep step
+// back history for finding the instruction, and are left stepped back to it on a divergence
+pub fn compare(
+    frames: usize,
+    first: &mut Chip8,
+    second: &mut Chip8,
+    play: impl FnMut(&mut Chip8, usize),
+) -> Result<Agreement, Divergence> {
+    compare_by(frames, first, second, AuditState::of, play)
+}
+
+// compare, with `state` as what has to match
+pub fn compare_by(
+    frames: usize,
+    first: &mut Chip8,
+    second: &mut Chip8,
+    state: impl Fn(&Chip8) -> AuditState,
+    mut play: impl FnMut(&mut Chip8, usize),
+) -> Result<Agreement, Divergence> {
+    for chip8 in [&mut *first, &mut *second] {
+        if chip8.step_back_interval() == 0 {
+            chip8.set_step_back_interval(DEFAULT_STEP_BACK_INTERVAL);
+        }
+    }
+
+    for frame in 0..frames {
+        let crashes = [&mut *first, &mut *second].map(|chip8| crash_message(chip8, |chip8| play(chip8, frame)));
+        let (this, other) = (state(first), state(second));
+
+        if crashes[0] != crashes[1] {
+            let describe = |crash: &Option<String>| crash.clone().unwrap_or_else(|| "ran".to_string());
+            let differences = vec![format!("crash: {} vs {}", describe(&crashes[0]), describe(&crashes[1]))];
+
+            return Err(Divergence { frame, instruction: None, differences });
+        }
+
+        if this != other {
+            let differences = this.differences(&other);
+            return Err(Divergence { frame, instruction: first_difference(first, second, &state), differences });
+        }
+
+        if let [Some(crash), _] = crashes {
+            return Ok(Agreement { frames: frame + 1, crash: Some(crash) });
+        }
+    }
+
+    Ok(Agreement { frames, crash: None })
+}
+
+// steps both machines back together until they agree, the instruction there is where they split
+fn first_difference(
+    first: &mut Chip8,
+    second: &mut Chip8,
+    state: impl Fn(&Chip8) -> AuditState,
+) -> Option<(u16, u16)> {
+    while first.step_back() && second.step_back() {
+        if state(first) == state(second) {
+            let pc = first.program_counter();
+            let memory = first.memory();
+            let opcode = u16::from_be_bytes([memory[pc as usize], *memory.get(pc as usize + 1)?]);
+
+            return Some((pc, opcode));
+        }
+    }
+
+    None
+}
+
+// both runs load the same ROM, so one that doesn't load agrees with itself without running
+pub(crate) fn unloadable(error: Chip8Error) -> Agreement {
+    Agreement { frames: 0, crash: Some(format!("unable to load the ROM: {}", error)) }
+}
+
+fn machine(rom: &[u8], options: &Options) -> Result<Chip8, Chip8Error> {
+    let mut chip8 = Chip8::new();
+    chip8.load(rom)?;
+    chip8.set_seed(options.seed);
+
+    Ok(chip8)
+}
+
+fn play_frame(options: &Options) -> impl FnMut(&mut Chip8, usize) + '_ {
+    move |chip8, frame| {
+        headless::play_frame(chip8, frame, options.ticks_per_frame, &options.script);
+    }
+}
+
+// the run twice, as a headless run would go
+pub fn audit(rom: &[u8], options: &Options) -> Result<Agreement, Divergence> {
+    let (mut first, mut second) = match (machine(rom, options), machine(rom, options)) {
+        (Ok(first), Ok(second)) => (first, second),
+        (Err(error), _) | (_, Err(error)) => return Ok(unloadable(error)),
+    };
+
+    compare(options.frames, &mut first, &mut second, play_frame(options))
+}
+
+// the state hash after each frame of a headless run, up to a crash
+pub fn hash_log(rom: &[u8], options: &Options) -> Vec<u64> {
+    let Ok(mut chip8) = machine(rom, options) else {
+        return Vec::new();
+    };
+    let mut play = play_frame(options);
+    let mut hashes = Vec::new();
+
+    for frame in 0..options.frames {
+        if crash_message(&mut chip8, |chip8| play(chip8, frame)).is_some() {
+            break;
+        }
+
+        hashes.push(AuditState::of(&chip8).hash());
+    }
+
+    hashes
+}
+
+// the run against a log from hash_log, to the end of the shorter of the two
+pub fn compare_log(rom: &[u8], options: &Options, log: &[u64]) -> Result<Agreement, Divergence> {
+    let hashes = hash_log(rom, options);
+
+    match hashes.iter().zip(log).position(|(hash, logged)| hash != logged) {
+        Some(frame) => Err(Divergence { frame, instruction: None, differences: Vec::new() }),
+        None if hashes.len() != log.len() => {
+            let frame = hashes.len().min(log.len());
+            let differences = vec![format!("frames: {} vs {} in the log", hashes.len(), log.len())];
+
+            Err(Divergence { frame, instruction: None, differences })
+        },
+        None => Ok(Agreement { frames: hashes.len(), crash: None }),
+    }
+}
+
+pub fn format_log(hashes: &[u64]) -> String {
+    let mut text = "# chip8-emu audit log\n".to_string();
+
+    for (frame, hash) in hashes.iter().enumerate() {
+        text += &format!("{} {:016x}\n", frame, hash);
+    }
+
+    text
+}
+
+// the hashes in frame order, "line N: ..." when a line isn't `FRAME HASH` with frames counting up
+// from 0
+pub fn parse_log(text: &str) -> Result<Vec<u64>, String> {
+    let mut hashes = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let entry = match fields[..] {
+            [frame, hash] => frame.parse::<usize>().ok().zip(u64::from_str_radix(hash, 16).ok()),
+            _ => None,
+        };
+
+        match entry {
+            Some((frame, hash)) if frame == hashes.len() => hashes.push(hash),
+            Some((frame, _)) => {
+                return Err(format!("line {}: expected frame {}, not {}", number + 1, hashes.len(), frame))
+            },
+            None => return Err(format!("line {}: expected `FRAME HASH`, not `{}`", number + 1, line)),
+        }
+    }
+
+    Ok(hashes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::headless::{Script, DEFAULT_SEED};
+
+    // counts in V1 for a frame and a bit, then draws a random digit at a random place forever
+    const ROM: [u8; 16] =
+        [0x71, 0x01, 0x31, 0x04, 0x12, 0x00, 0xC0, 0x0F, 0xC2, 0x1F, 0xF0, 0x29, 0xD2, 0x25, 0x12, 0x06];
+    const FIRST_RND: u16 = 0x206;
+
+    fn options(frames: usize) -> Options {
+        Options { frames, ticks_per_frame: 10, seed: DEFAULT_SEED, script: Script::default() }
+    }
+
+    // the digit the first RND picks with `seed`
+    fn first_digit(seed: u64) -> u8 {
+        let mut chip8 = Chip8::with_seed(seed);
+        chip8.load(&[0xC0, 0x0F]).unwrap();
+        chip8.tick().unwrap();
+
+        chip8.registers()[0]
+    }
+
+    #[test]
+    fn the_same_run_twice_agrees() {
+        assert_eq!(audit(&ROM, &options(120)), Ok(Agreement { frames: 120, crash: None }));
+    }
+
+    #[test]
+    fn finds_the_first_random_number_of_an_unseeded_run() {
+        let mut seeded = Chip8::with_seed(DEFAULT_SEED);
+        // the OS picks this seed. one that happens to draw the same first digit would differ an
+        // instruction later, so it's picked again
+        let mut unseeded = loop {
+            let chip8 = Chip8::new();
+            if first_digit(chip8.seed()) != first_digit(DEFAULT_SEED) {
+                break chip8;
+            }
+        };
+        seeded.load(&ROM).unwrap();
+        unseeded.load(&ROM).unwrap();
+
+        let divergence = compare(60, &mut seeded, &mut unseeded, |chip8, _| {
+            chip8.run_frame(10);
+        })
+        .unwrap_err();
+
+        // 11 instructions of counting, then the RND in the second frame
+        assert_eq!(divergence.frame, 1);
+        assert_eq!(divergence.instruction, Some((FIRST_RND, 0xC00F)));
+        // by the end of the frame it has drawn a few digits, V0 might have come out the same again
+        assert!(!divergence.differences.is_empty());
+        assert!(divergence.to_string().starts_with("the runs differ after frame 1, first at 206 running C00F\n  "));
+    }
+
+    #[test]
+    fn a_seed_only_counts_once_it_is_used() {
+        let (mut first, mut second) = (Chip8::with_seed(1), Chip8::with_seed(2));
+        // counting forever
+        first.load(&[0x71, 0x01, 0x12, 0x00]).unwrap();
+        second.load(&[0x71, 0x01, 0x12, 0x00]).unwrap();
+
+        assert!(compare(30, &mut first, &mut second, |chip8, _| {
+            chip8.run_frame(10);
+        })
+        .is_ok());
+    }
+
+    #[test]
+    fn crashing_the_same_way_agrees() {
+        // return with an empty stack
+        let agreement = audit(&[0x00, 0xEE], &options(10)).unwrap();
+
+        assert_eq!(agreement.frames, 1);
+        assert!(agreement.crash.is_some());
+        assert!(hash_log(&[0x00, 0xEE], &options(10)).is_empty());
+    }
+
+    #[test]
+    fn checks_a_run_against_its_log() {
+        let log = parse_log(&format_log(&hash_log(&ROM, &options(30)))).unwrap();
+        assert_eq!(log.len(), 30);
+        assert_eq!(compare_log(&ROM, &options(30), &log), Ok(Agreement { frames: 30, crash: None }));
+
+        // another seed draws other digits from the second frame on
+        let reseeded = Options { seed: DEFAULT_SEED + 1, ..options(30) };
+        assert_eq!(compare_log(&ROM, &reseeded, &log).unwrap_err().frame, 1);
+
+        let longer = compare_log(&ROM, &options(31), &log).unwrap_err();
+        assert_eq!((longer.frame, longer.differences), (30, vec!["frames: 31 vs 30 in the log".to_string()]));
+    }
+
+    #[test]
+    fn rejects_a_broken_log() {
+        assert_eq!(parse_log("# comment\n0 ff\n\n1 a0 # trailing\n"), Ok(vec![0xFF, 0xA0]));
+        assert_eq!(parse_log("0 ff\n2 a0\n"), Err("line 2: expected frame 1, not 2".to_string()));
+        assert_eq!(parse_log("0 xyz\n"), Err("line 1: expected `FRAME HASH`, not `0 xyz`".to_string()));
+    }
+
+    #[test]
+    fn lists_what_differs() {
+        let mut first = Chip8::with_seed(0);
+        let mut second = Chip8::with_seed(0);
+        second.set_register(0xA, 0x91);
+        second.keypress(3, true);
+        for address in 0x300..0x320 {
+            second.write_memory(address, 1);
+        }
+
+        let differences = AuditState::of(&first).differences(&AuditState::of(&second));
+        assert_eq!(differences[..2], ["VA: 00 vs 91", "keys: none vs 3"]);
+        assert_eq!(differences[2], "[300]: 00 vs 01");
+        assert_eq!(differences.last().unwrap(), "and 16 more bytes of RAM");
+
+        first.set_register(0xA, 0x91);
+        assert_ne!(AuditState::of(&first).hash(), AuditState::of(&second).hash());
+    }
+}
@@ -0,0 +1,283 @@
+use crate::{Chip8, Chip8Error};
+
+use std::env;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// a loop of arithmetic, calls, BCD, register stores and sprite drawing that never waits for input
+pub const BENCH_ROM: &[u8] = &[
+    0x60, 0x00, // V0 = 0
+    0x61, 0x00, // V1 = 0
+    0x70, 0x01, // loop: V0 += 1
+    0x81, 0x04, // V1 += V0
+    0x82, 0x16, // V2 = V1 >> 1
+    0x83, 0x10, // V3 = V1
+    0x83, 0x32, // V3 &= V3
+    0x22, 0x20, // call the store routine
+    0x64, 0x0F, // V4 = 0x0F
+    0x84, 0x02, // V4 &= V0
+    0xF4, 0x29, // I = font digit V4
+    0xD1, 0x25, // draw it at (V1, V2)
+    0x30, 0x00, // clear the screen each time V0 wraps
+    0x12, 0x04,
+    0x00, 0xE0,
+    0x12, 0x04,
+    0xA3, 0x00, // store routine: I = 0x300
+    0xF3, 0x33, // BCD of V3
+    0xA3, 0x10, // I = 0x310
+    0xF3, 0x55, // store V0 - V3
+    0xF3, 0x65, // and load them back
+    0x00, 0xEE,
+];
+
+pub const DEFAULT_TICKS_PER_FRAME: usize = 10;
+
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+// a way of setting up the interpreter whose speed is worth comparing. there's a single dispatch
+// and a single screen representation for now, alternatives get an entry here when they land
+#[derive(Clone, Copy)]
+pub struct Configuration {
+    pub name: &'static str,
+    pub setup: fn(&mut Chip8),
+}
+
+pub const CONFIGURATIONS: &[Configuration] = &[Configuration { name: "default", setup: |_| () }];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Settings {
+    pub warmup: Duration,
+    pub duration: Duration,
+    pub ticks_per_frame: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            warmup: Duration::from_millis(500),
+            duration: Duration::from_secs(3),
+            ticks_per_frame: DEFAULT_TICKS_PER_FRAME,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Measurement {
+    pub configuration: &'static str,
+    pub frames: u64,
+    pub instructions: u64,
+    pub elapsed: Duration,
+}
+
+impl Measurement {
+    pub fn frames_per_second(&self) -> f64 {
+        self.frames as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn instructions_per_second(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Machine {
+    pub os: &'static str,
+    pub arch: &'static str,
+    pub threads: usize,
+    pub version: &'static str,
+}
+
+impl Machine {
+    pub fn current() -> Self {
+        Self {
+            os: env::consts::OS,
+            arch: env::consts::ARCH,
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+            version: env!("CARGO_PKG_VERSION"),
+        }
+    }
+}
+
+// a fresh interpreter with `configuration` applied, ready to run `rom`
+pub fn prepare(rom: &[u8], configuration: &Configuration) -> Result<Chip8, Chip8Error> {
+    let mut chip8 = Chip8::new();
+    chip8.load(rom)?;
+    chip8.set_seed(0);
+    (configuration.setup)(&mut chip8);
+    Ok(chip8)
+}
+
+// runs whole frames through the warmup, then counts what gets done until `duration` has passed.
+// the clock is read once per frame, which is small next to ticks_per_frame instructions
+pub fn measure<C: Clock>(
+    clock: &C,
+    rom: &[u8],
+    configuration: &Configuration,
+    settings: &Settings,
+) -> Result<Measurement, Chip8Error> {
+    let mut chip8 = prepare(rom, configuration)?;
+
+    let warmup_end = clock.now() + settings.warmup;
+    while clock.now() < warmup_end {
+        chip8.advance_frame(settings.ticks_per_frame);
+    }
+
+    let (frames, instructions) = (chip8.frame_count(), chip8.instruction_count());
+    let start = clock.now();
+    let mut now = start;
+
+    while now - start < settings.duration {
+        chip8.advance_frame(settings.ticks_per_frame);
+        now = clock.now();
+    }
+
+    Ok(Measurement {
+        configuration: configuration.name,
+        frames: chip8.frame_count() - frames,
+        instructions: chip8.instruction_count() - instructions,
+        elapsed: now - start,
+    })
+}
+
+pub fn run<C: Clock>(clock: &C, rom: &[u8], settings: &Settings) -> Result<Vec<Measurement>, Chip8Error> {
+    CONFIGURATIONS.iter().map(|configuration| measure(clock, rom, configuration, settings)).collect()
+}
+
+pub fn format_table(machine: &Machine, measurements: &[Measurement]) -> String {
+    let mut out = format!(
+        "chip8-emu {} on {}/{}, {} threads\n\n{:<12} {:>16} {:>12}\n",
+        machine.version, machine.os, machine.arch, machine.threads, "config", "instructions/s", "frames/s"
+    );
+
+    for measurement in measurements {
+        out.push_str(&format!(
+            "{:<12} {:>16.0} {:>12.0}\n",
+            measurement.configuration,
+            measurement.instructions_per_second(),
+            measurement.frames_per_second()
+        ));
+    }
+
+    out
+}
+
+pub fn format_json(machine: &Machine, measurements: &[Measurement]) -> String {
+    let results: Vec<String> = measurements
+        .iter()
+        .map(|measurement| {
+            format!(
+                "    {{ \"config\": \"{}\", \"instructions_per_second\": {:.0}, \"frames_per_second\": {:.0}, \"instructions\": {}, \"frames\": {}, \"seconds\": {:.3} }}",
+                measurement.configuration,
+                measurement.instructions_per_second(),
+                measurement.frames_per_second(),
+                measurement.instructions,
+                measurement.frames,
+                measurement.elapsed.as_secs_f64()
+            )
+        })
+        .collect();
+
+    format!(
+        "{{\n  \"version\": \"{}\",\n  \"os\": \"{}\",\n  \"arch\": \"{}\",\n  \"threads\": {},\n  \"results\": [\n{}\n  ]\n}}\n",
+        machine.version,
+        machine.os,
+        machine.arch,
+        machine.threads,
+        results.join(",\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+
+    // every reading moves time on by a fixed step
+    struct FakeClock {
+        now: Cell<Instant>,
+        step: Duration,
+    }
+
+    impl FakeClock {
+        fn new(step: Duration) -> Self {
+            Self { now: Cell::new(Instant::now()), step }
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            let now = self.now.get();
+            self.now.set(now + self.step);
+            now
+        }
+    }
+
+    fn settings() -> Settings {
+        Settings { warmup: Duration::from_millis(50), duration: Duration::from_millis(100), ticks_per_frame: 10 }
+    }
+
+    #[test]
+    fn warmup_is_left_out() {
+        let clock = FakeClock::new(Duration::from_millis(10));
+        let measurement = measure(&clock, BENCH_ROM, &CONFIGURATIONS[0], &settings()).unwrap();
+
+        // one frame per reading, ten readings to cover the 100ms
+        assert_eq!(measurement.frames, 10);
+        assert_eq!(measurement.instructions, 100);
+        assert_eq!(measurement.elapsed, Duration::from_millis(100));
+        assert_eq!(measurement.frames_per_second(), 100.0);
+        assert_eq!(measurement.instructions_per_second(), 1000.0);
+    }
+
+    #[test]
+    fn runs_every_configuration() {
+        let clock = FakeClock::new(Duration::from_millis(30));
+        let measurements = run(&clock, BENCH_ROM, &settings()).unwrap();
+
+        assert_eq!(measurements.len(), CONFIGURATIONS.len());
+        // the last frame overshoots the duration, elapsed is what was actually spent
+        assert!(measurements.iter().all(|m| m.frames == 4 && m.elapsed == Duration::from_millis(120)));
+    }
+
+    #[test]
+    fn bench_rom_runs_without_waiting() {
+        let mut chip8 = prepare(BENCH_ROM, &CONFIGURATIONS[0]).unwrap();
+
+        for _ in 0..2000 {
+            chip8.advance_frame(DEFAULT_TICKS_PER_FRAME);
+        }
+
+        assert_eq!(chip8.instruction_count(), 20_000);
+        assert!(chip8.get_display().contains(&true));
+    }
+
+    #[test]
+    fn formats_reports() {
+        let machine = Machine { os: "linux", arch: "x86_64", threads: 8, version: "0.1.0" };
+        let measurements = [Measurement {
+            configuration: "default",
+            frames: 600,
+            instructions: 6000,
+            elapsed: Duration::from_secs(2),
+        }];
+
+        let table = format_table(&machine, &measurements);
+        assert!(table.starts_with("chip8-emu 0.1.0 on linux/x86_64, 8 threads"));
+        assert!(table.lines().last().unwrap().split_whitespace().eq(["default", "3000", "300"]));
+
+        let json = format_json(&machine, &measurements);
+        assert!(json.contains("\"threads\": 8,"), "{}", json);
+        assert!(json.contains("{ \"config\": \"default\", \"instructions_per_second\": 3000, \"frames_per_second\": 300,"), "{}", json);
+    }
+}
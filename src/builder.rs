@@ -0,0 +1,290 @@
+// everything a machine can be set up with before it runs, checked together so an impossible
+// combination is an error instead of a panic mid-program
+//
+//   let chip8 = Chip8::builder().seed(7).start_address(0x600).build()?;
+
+use crate::{
+    Chip8, Chip8Error, KeyWaitTimeout, OnTimeout, Quirk, Quirks, FONTSET, FONTSET_SIZE, NUM_KEYS, RAM_SIZE, STACK_SIZE,
+    START_ADDRESS,
+};
+
+#[derive(Clone, Debug)]
+pub struct Chip8Builder {
+    quirks: Quirks,
+    ram_size: usize,
+    stack_limit: usize,
+    start_address: u16,
+    seed: Option<u64>,
+    font: [u8; FONTSET_SIZE],
+    speed_multiplier: f64,
+    key_wait_timeout: Option<KeyWaitTimeout>,
+}
+
+impl Default for Chip8Builder {
+    fn default() -> Self {
+        Self {
+            quirks: Quirks::default(),
+            ram_size: RAM_SIZE,
+            stack_limit: STACK_SIZE,
+            start_address: START_ADDRESS,
+            seed: None,
+            font: FONTSET,
+            speed_multiplier: 1.0,
+            key_wait_timeout: None,
+        }
+    }
+}
+
+impl Chip8Builder {
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    pub fn quirk(mut self, quirk: Quirk, is_enabled: bool) -> Self {
+        self.quirks.set(quirk, is_enabled);
+        self
+    }
+
+    // at most RAM_SIZE, less for machines with less memory
+    pub fn ram_size(mut self, size: usize) -> Self {
+        self.ram_size = size;
+        self
+    }
+
+    // how deep CALL can nest, at most STACK_SIZE
+    pub fn stack_limit(mut self, limit: usize) -> Self {
+        self.stack_limit = limit;
+        self
+    }
+
+    // where ROMs load and the program counter starts, after the font
+    pub fn start_address(mut self, address: u16) -> Self {
+        self.start_address = address;
+        self
+    }
+
+    // without one the machine seeds RND randomly, like Chip8::new
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    // the 16 4x5 hex digit sprites FX29 points at, kept at the bottom of RAM
+    pub fn font(mut self, font: [u8; FONTSET_SIZE]) -> Self {
+        self.font = font;
+        self
+    }
+
+    pub fn speed_multiplier(mut self, multiplier: f64) -> Self {
+        self.speed_multiplier = multiplier;
+        self
+    }
+
+    // without one FX0A waits for a key forever
+    pub fn key_wait_timeout(mut self, timeout: KeyWaitTimeout) -> Self {
+        self.key_wait_timeout = Some(timeout);
+        self
+    }
+
+    pub fn build(self) -> Result<Chip8, Chip8Error> {
+        if self.ram_size > RAM_SIZE {
+            return Err(Chip8Error::RamSize(self.ram_size));
+        }
+
+        if (self.start_address as usize) < FONTSET_SIZE {
+            return Err(Chip8Error::FontOverlapsProgram(self.start_address));
+        }
+
+        // room for at least one instruction
+        if self.start_address as usize + 2 > self.ram_size {
+            return Err(Chip8Error::StartAddress { start_address: self.start_address, ram_size: self.ram_size });
+        }
+
+        if self.stack_limit == 0 || self.stack_limit > STACK_SIZE {
+            return Err(Chip8Error::StackLimit(self.stack_limit));
+        }
+
+        if !(self.speed_multiplier > 0.0 && self.speed_multiplier.is_finite()) {
+            return Err(Chip8Error::SpeedMultiplier(self.speed_multiplier));
+        }
+
+        if let Some(KeyWaitTimeout { on_timeout: OnTimeout::PressKey(key), .. }) = self.key_wait_timeout {
+            if key as usize >= NUM_KEYS {
+                return Err(Chip8Error::TimeoutKey(key));
+            }
+        }
+
+        let mut chip8 = match self.seed {
+            Some(seed) => Chip8::with_seed(seed),
+            None => Chip8::new(),
+        };
+
+        chip8.ram = vec![0; self.ram_size];
+        chip8.start_address = self.start_address;
+        chip8.font = self.font;
+        chip8.stack_limit = self.stack_limit;
+        chip8.reset();
+        chip8.set_quirks(self.quirks);
+        chip8.set_speed_multiplier(self.speed_multiplier);
+        chip8.set_key_wait_timeout(self.key_wait_timeout);
+
+        Ok(chip8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // V0 = RND, V1 = 0x81, V1 >>= 1 (or V0 >> 1 with the quirk), draw "0", jump back
+    const ROM: [u8; 10] = [0xC0, 0xFF, 0x61, 0x81, 0x81, 0x06, 0xD0, 0x15, 0x12, 0x00];
+
+    fn build(builder: Chip8Builder) -> Chip8 {
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn defaults_are_chip8_new() {
+        let mut built = build(Chip8::builder().seed(3));
+        let mut new = Chip8::with_seed(3);
+        built.load(&ROM).unwrap();
+        new.load(&ROM).unwrap();
+
+        assert_eq!(built.memory(), new.memory());
+        assert_eq!(built.program_counter(), new.program_counter());
+        assert_eq!(built.quirks(), new.quirks());
+        assert_eq!(built.speed_multiplier(), 1.0);
+        assert_eq!(built.save_state(), new.save_state());
+    }
+
+    #[test]
+    fn sets_the_seed() {
+        assert_eq!(build(Chip8::builder().seed(42)).seed(), 42);
+    }
+
+    #[test]
+    fn sets_quirks() {
+        assert!(build(Chip8::builder().quirk(Quirk::ShiftUsesVy, true)).quirks().shift_uses_vy);
+
+        let quirks = Quirks {
+            shift_uses_vy: true,
+            memory_increment_i: true,
+            jump_uses_vx: true,
+            clip_sprites: true,
+            vf_reset: true,
+            display_wait: true,
+        };
+        assert_eq!(build(Chip8::builder().quirks(quirks)).quirks(), quirks);
+    }
+
+    #[test]
+    fn sets_the_speed() {
+        assert_eq!(build(Chip8::builder().speed_multiplier(2.5)).speed_multiplier(), 2.5);
+    }
+
+    #[test]
+    fn sets_the_key_wait_timeout() {
+        let timeout = KeyWaitTimeout { frames: 60, on_timeout: OnTimeout::Stop };
+
+        assert_eq!(build(Chip8::builder().key_wait_timeout(timeout)).key_wait_timeout(), Some(timeout));
+        assert_eq!(build(Chip8::builder()).key_wait_timeout(), None);
+    }
+
+    #[test]
+    fn sets_the_ram_size() {
+        let mut chip8 = build(Chip8::builder().seed(1).ram_size(2048));
+        chip8.load(&ROM).unwrap();
+        chip8.run_frame(10);
+
+        assert_eq!(chip8.memory().len(), 2048);
+
+        // states stay full size and load back
+        let state = chip8.save_state();
+        chip8.run_frame(10);
+        chip8.load_state(&state).unwrap();
+        assert_eq!(chip8.save_state(), state);
+    }
+
+    #[test]
+    fn loads_and_starts_at_the_start_address() {
+        let mut chip8 = build(Chip8::builder().start_address(0x600));
+        chip8.load(&[0x60, 0x2A]).unwrap();
+
+        assert_eq!(chip8.program_counter(), 0x600);
+        assert_eq!(&chip8.memory()[0x600..0x602], &[0x60, 0x2A]);
+        assert_eq!(chip8.loaded_rom().unwrap().loaded_at_address, 0x600);
+
+        chip8.tick().unwrap();
+        chip8.soft_reset();
+        assert_eq!(chip8.program_counter(), 0x600);
+        assert_eq!(chip8.registers()[0], 0);
+    }
+
+    #[test]
+    fn keeps_the_font_across_resets() {
+        let font = [0xAA; FONTSET_SIZE];
+        let mut chip8 = build(Chip8::builder().font(font));
+        assert_eq!(&chip8.memory()[..FONTSET_SIZE], &font);
+
+        chip8.reset();
+        assert_eq!(&chip8.memory()[..FONTSET_SIZE], &font);
+    }
+
+    #[test]
+    fn overflows_past_the_stack_limit() {
+        // CALL 0x200, forever
+        let mut chip8 = build(Chip8::builder().stack_limit(2));
+        chip8.load(&[0x22, 0x00]).unwrap();
+
+        chip8.tick().unwrap();
+        chip8.tick().unwrap();
+        assert_eq!(chip8.stack_pointer(), 2);
+        assert_eq!(chip8.tick().err(), Some(Chip8Error::StackOverflow(0x200)));
+        assert_eq!((chip8.stack_pointer(), chip8.program_counter()), (2, 0x200));
+    }
+
+    #[test]
+    fn refuses_impossible_machines() {
+        let error = |builder: Chip8Builder| builder.build().err().unwrap();
+
+        assert_eq!(error(Chip8::builder().ram_size(8192)), Chip8Error::RamSize(8192));
+        assert_eq!(
+            error(Chip8::builder().ram_size(1024).start_address(0x400)),
+            Chip8Error::StartAddress { start_address: 0x400, ram_size: 1024 }
+        );
+        assert_eq!(error(Chip8::builder().start_address(0x040)), Chip8Error::FontOverlapsProgram(0x040));
+        assert_eq!(error(Chip8::builder().stack_limit(0)), Chip8Error::StackLimit(0));
+        assert_eq!(error(Chip8::builder().stack_limit(17)), Chip8Error::StackLimit(17));
+        assert_eq!(error(Chip8::builder().speed_multiplier(0.0)), Chip8Error::SpeedMultiplier(0.0));
+        assert_eq!(
+            error(
+                Chip8::builder().key_wait_timeout(KeyWaitTimeout { frames: 60, on_timeout: OnTimeout::PressKey(16) })
+            ),
+            Chip8Error::TimeoutKey(16)
+        );
+        assert!(error(Chip8::builder().ram_size(1024).start_address(0x400)).to_string().contains("0x400"));
+    }
+
+    #[test]
+    fn builds_the_same_machine_as_the_setters() {
+        let mut built = build(Chip8::builder().seed(9).quirk(Quirk::ShiftUsesVy, true).speed_multiplier(1.5));
+
+        let mut set = Chip8::new();
+        set.set_seed(9);
+        set.set_quirk(Quirk::ShiftUsesVy, true);
+        set.set_speed_multiplier(1.5);
+
+        built.load(&ROM).unwrap();
+        set.load(&ROM).unwrap();
+
+        for _ in 0..20 {
+            built.run_frame(7);
+            set.run_frame(7);
+        }
+
+        assert_eq!(built.get_display(), set.get_display());
+        assert_eq!(built.registers(), set.registers());
+        assert_eq!(built.save_state(), set.save_state());
+    }
+}
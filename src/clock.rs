@@ -0,0 +1,139 @@
+use std::time::Duration;
+
+const FRAMES_PER_SECOND: u64 = 60;
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+// turns wall-clock time into whole 60 Hz frames, so emulation speed doesn't depend on how often
+// the host renders. time is kept in nanoseconds times 60 so there's no rounding to drift
+#[derive(Clone, Debug)]
+pub struct FrameClock {
+    accumulator: u64,
+    max_frames: u32,
+    // what advance_ticks couldn't turn into whole nanoseconds yet, in nanoseconds times the tick rate
+    tick_remainder: u64,
+}
+
+impl FrameClock {
+    // `max_frames` caps how far one update catches up, anything beyond that is dropped
+    // so a long stall can't snowball into ever longer updates
+    pub fn new(max_frames: u32) -> Self {
+        assert!(max_frames > 0, "max_frames must be at least 1");
+
+        Self { accumulator: 0, max_frames, tick_remainder: 0 }
+    }
+
+    // how many frames to run for `elapsed` time since the last call
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.advance_nanos(elapsed.as_nanos().min(u64::MAX as u128) as u64)
+    }
+
+    // the same with the time in nanoseconds, for hosts without Duration
+    pub fn advance_nanos(&mut self, elapsed: u64) -> u32 {
+        let elapsed = elapsed.min(u64::MAX / FRAMES_PER_SECOND);
+        self.accumulator = self.accumulator.saturating_add(elapsed * FRAMES_PER_SECOND);
+
+        let frames = self.accumulator / NANOS_PER_SECOND;
+        self.accumulator %= NANOS_PER_SECOND;
+
+        if frames > self.max_frames as u64 {
+            self.max_frames
+        } else {
+            frames as u32
+        }
+    }
+
+    // the same for hosts whose timer counts ticks at `ticks_per_second`, like a microcontroller's
+    // monotonic counter. rates that don't divide a second evenly don't drift either
+    pub fn advance_ticks(&mut self, ticks: u64, ticks_per_second: u64) -> u32 {
+        assert!(ticks_per_second > 0, "ticks_per_second must be at least 1");
+
+        let scaled = ticks as u128 * NANOS_PER_SECOND as u128 + self.tick_remainder as u128;
+        self.tick_remainder = (scaled % ticks_per_second as u128) as u64;
+
+        self.advance_nanos((scaled / ticks_per_second as u128).min(u64::MAX as u128) as u64)
+    }
+
+    // how far into the next frame we are, 0.0 to 1.0
+    pub fn frame_progress(&self) -> f64 {
+        self.accumulator as f64 / NANOS_PER_SECOND as f64
+    }
+
+    pub fn reset(&mut self) {
+        self.accumulator = 0;
+        self.tick_remainder = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(clock: &mut FrameClock, refresh_hz: u64, seconds: u64) -> u32 {
+        let interval = Duration::from_nanos(NANOS_PER_SECOND / refresh_hz);
+
+        (0..refresh_hz * seconds).map(|_| clock.advance(interval)).sum()
+    }
+
+    #[test]
+    fn same_speed_at_any_refresh_rate() {
+        for refresh_hz in [30, 60, 75, 144, 240] {
+            let frames = run(&mut FrameClock::new(4), refresh_hz, 10);
+            // the integer interval loses up to a nanosecond per present
+            assert!((599..=600).contains(&frames), "{} Hz ran {} frames", refresh_hz, frames);
+        }
+    }
+
+    #[test]
+    fn exact_over_a_long_run() {
+        let mut clock = FrameClock::new(4);
+        let frames: u32 = (0..3000).map(|_| clock.advance(Duration::from_millis(20))).sum();
+
+        assert_eq!(frames, 3600);
+    }
+
+    #[test]
+    fn counts_timer_ticks_without_drift() {
+        // a 32.768 kHz RTC, a tick at a time for a minute
+        let mut clock = FrameClock::new(4);
+        let frames: u32 = (0..32768 * 60).map(|_| clock.advance_ticks(1, 32768)).sum();
+        assert_eq!(frames, 3600);
+
+        // a 1 MHz timer read at odd moments
+        let mut clock = FrameClock::new(4);
+        let frames: u32 = (0..10_000).map(|i| clock.advance_ticks(1000 + i % 7, 1_000_000)).sum();
+        assert_eq!(frames as u64, (0..10_000).map(|i| 1000 + i % 7).sum::<u64>() * 60 / 1_000_000);
+    }
+
+    #[test]
+    fn nanoseconds_pace_like_durations() {
+        let (mut nanos, mut duration) = (FrameClock::new(4), FrameClock::new(4));
+
+        for elapsed in [16_666_667, 5_000_000, 40_000_000, 1, 0, u64::MAX] {
+            assert_eq!(nanos.advance_nanos(elapsed), duration.advance(Duration::from_nanos(elapsed)));
+            assert_eq!(nanos.frame_progress(), duration.frame_progress());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "ticks_per_second must be at least 1")]
+    fn a_timer_needs_a_rate() {
+        FrameClock::new(4).advance_ticks(100, 0);
+    }
+
+    #[test]
+    fn catches_up_after_a_hitch() {
+        let mut clock = FrameClock::new(4);
+
+        assert_eq!(clock.advance(Duration::from_millis(50)), 3);
+        assert!(clock.frame_progress() < 1.0);
+    }
+
+    #[test]
+    fn caps_long_stalls() {
+        let mut clock = FrameClock::new(4);
+
+        assert_eq!(clock.advance(Duration::from_secs(2)), 4);
+        assert_eq!(clock.advance(Duration::from_millis(16)), 0);
+        assert_eq!(clock.advance(Duration::from_millis(1)), 1);
+    }
+}
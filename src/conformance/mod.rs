@@ -0,0 +1,379 @@
+use crate::headless;
+use crate::ocr::{self, Glyph, Region};
+use crate::{Chip8, Chip8Error, Quirks, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+pub const TICKS_PER_FRAME: usize = 10;
+
+const GOLDENS: &str = include_str!("goldens.txt");
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScriptedKey {
+    pub frame: usize,
+    pub key: usize,
+    pub is_pressed: bool,
+}
+
+pub struct ConformanceTest {
+    pub name: &'static str,
+    pub file: &'static str,
+    pub frames: usize,
+    pub keys: &'static [ScriptedKey],
+    // the quirk presets it runs under
+    pub presets: &'static [&'static str],
+}
+
+// the quirks test opens with a platform menu, key 1 picks plain CHIP-8
+const SELECT_CHIP8: &[ScriptedKey] = &[
+    ScriptedKey { frame: 30, key: 0x1, is_pressed: true },
+    ScriptedKey { frame: 40, key: 0x1, is_pressed: false },
+];
+
+// Quirks::preset names. the quirks test checks the quirks against the platform picked in its menu,
+// so it only runs under the one it picks
+pub const PRESETS: &[&str] = &["chip8", "schip", "xochip"];
+const CHIP8_PRESET: &[&str] = &["chip8"];
+
+// the keypad, beep and scrolling tests need a human or SUPER-CHIP, so they're left out
+pub const TESTS: &[ConformanceTest] = &[
+    ConformanceTest { name: "chip8-logo", file: "1-chip8-logo.ch8", frames: 60, keys: &[], presets: PRESETS },
+    ConformanceTest { name: "ibm-logo", file: "2-ibm-logo.ch8", frames: 60, keys: &[], presets: PRESETS },
+    ConformanceTest { name: "corax+", file: "3-corax+.ch8", frames: 120, keys: &[], presets: PRESETS },
+    ConformanceTest { name: "flags", file: "4-flags.ch8", frames: 120, keys: &[], presets: PRESETS },
+    ConformanceTest { name: "quirks", file: "5-quirks.ch8", frames: 600, keys: SELECT_CHIP8, presets: CHIP8_PRESET },
+];
+
+pub const PASS_MARK: char = '+';
+pub const FAIL_MARK: char = 'x';
+
+// the tick and cross the corax+ and flags tests draw next to each opcode group
+pub const MARK_GLYPHS: [Glyph; 2] = [
+    Glyph { ch: PASS_MARK, width: 8, rows: [0x01, 0x02, 0x04, 0xA8, 0x50] },
+    Glyph { ch: FAIL_MARK, width: 8, rows: [0x88, 0x50, 0x20, 0x50, 0x88] },
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    Fail { hash: String, screen: String, failed_groups: Vec<String> },
+    NoGolden { hash: String },
+    Crashed(String),
+    Missing,
+}
+
+#[derive(Debug, Clone)]
+pub struct TestResult {
+    pub test: &'static str,
+    pub preset: &'static str,
+    pub outcome: Outcome,
+}
+
+impl TestResult {
+    pub fn is_failure(&self) -> bool {
+        matches!(self.outcome, Outcome::Fail { .. } | Outcome::Crashed(_))
+    }
+}
+
+#[derive(Default)]
+pub struct Goldens {
+    hashes: HashMap<(String, String), String>,
+}
+
+impl Goldens {
+    pub fn builtin() -> Self {
+        Self::parse(GOLDENS)
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let hashes = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+
+                match (parts.next(), parts.next(), parts.next()) {
+                    (Some(test), Some(preset), Some(hash)) => {
+                        Some(((test.to_string(), preset.to_string()), hash.to_string()))
+                    },
+                    _ => None,
+                }
+            })
+            .collect();
+
+        Self { hashes }
+    }
+
+    pub fn get(&self, test: &str, preset: &str) -> Option<&str> {
+        self.hashes
+            .get(&(test.to_string(), preset.to_string()))
+            .map(String::as_str)
+    }
+}
+
+pub fn run_headless(rom: &[u8], frames: usize, keys: &[ScriptedKey], quirks: Quirks) -> Result<Chip8, Chip8Error> {
+    let mut chip8 = Chip8::with_quirks(quirks);
+    chip8.load(rom)?;
+    let script = headless::Script { keys: keys.to_vec(), ..headless::Script::default() };
+    headless::run_frames(&mut chip8, frames, TICKS_PER_FRAME, &script)?;
+    Ok(chip8)
+}
+
+// a test that draws ticks and crosses passes or fails on those without a golden. a cross fails it
+// even when the screen matches one, the golden was blessed from a broken run
+pub fn run_test(test: &ConformanceTest, preset: &'static str, rom: &[u8], goldens: &Goldens) -> TestResult {
+    let quirks = Quirks::preset(preset).expect("conformance runs under Quirks::preset names");
+    let run = panic::catch_unwind(AssertUnwindSafe(|| run_headless(rom, test.frames, test.keys, quirks)));
+
+    let outcome = match run {
+        Ok(Ok(chip8)) => {
+            let screen = chip8.get_display();
+            let hash = screen_hash(screen);
+            let (passed_groups, failed_groups) = marked_groups(screen);
+            let golden = goldens.get(test.name, preset);
+
+            if !failed_groups.is_empty() || golden.is_some_and(|golden| golden != hash) {
+                Outcome::Fail { hash, screen: ascii_screen(screen), failed_groups }
+            } else if golden.is_some() || !passed_groups.is_empty() {
+                Outcome::Pass
+            } else {
+                Outcome::NoGolden { hash }
+            }
+        },
+        Ok(Err(error)) => Outcome::Crashed(error.to_string()),
+        Err(cause) => {
+            let message = cause
+                .downcast_ref::<String>()
+                .cloned()
+                .or_else(|| cause.downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_default();
+
+            Outcome::Crashed(message)
+        },
+    };
+
+    TestResult { test: test.name, preset, outcome }
+}
+
+// runs every test under every preset, roms are looked up by their file names in `dir`
+pub fn run_suite(dir: &Path, goldens: &Goldens) -> Vec<TestResult> {
+    let mut results = Vec::new();
+
+    for test in TESTS {
+        let rom = fs::read(dir.join(test.file)).ok();
+
+        for &preset in test.presets {
+            let result = match &rom {
+                Some(rom) => run_test(test, preset, rom, goldens),
+                None => TestResult { test: test.name, preset, outcome: Outcome::Missing },
+            };
+
+            results.push(result);
+        }
+    }
+
+    results
+}
+
+// labels of the opcode groups marked with a tick and with a cross, e.g. "8 4" for 8xy4
+// (letters outside the hex font don't read and show up as gaps)
+pub fn marked_groups(screen: &[bool]) -> (Vec<String>, Vec<String>) {
+    let mut glyphs = ocr::font_glyphs();
+    glyphs.extend_from_slice(&MARK_GLYPHS);
+
+    let text = ocr::read_glyphs(screen, Region::SCREEN, &glyphs);
+    let (mut passed, mut failed) = (Vec::new(), Vec::new());
+
+    for line in text.lines() {
+        let mut label = String::new();
+
+        for ch in line.chars() {
+            let groups = match ch {
+                PASS_MARK => &mut passed,
+                FAIL_MARK => &mut failed,
+                _ => {
+                    label.push(ch);
+                    continue;
+                },
+            };
+
+            groups.push(label.trim().to_string());
+            label.clear();
+        }
+    }
+
+    (passed, failed)
+}
+
+pub fn failed_groups(screen: &[bool]) -> Vec<String> {
+    marked_groups(screen).1
+}
+
+pub fn screen_hash(screen: &[bool]) -> String {
+    let bytes: Vec<u8> = screen
+        .chunks(8)
+        .map(|pixels| pixels.iter().fold(0, |byte, &pixel| (byte << 1) | pixel as u8))
+        .collect();
+
+    Sha256::digest(&bytes)
+        .iter()
+        .take(8)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub fn ascii_screen(screen: &[bool]) -> String {
+    let mut output = String::with_capacity((SCREEN_WIDTH + 1) * SCREEN_HEIGHT);
+
+    for row in screen.chunks(SCREEN_WIDTH) {
+        output.extend(row.iter().map(|&pixel| if pixel { '#' } else { '.' }));
+        output.push('\n');
+    }
+
+    output
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Pass => write!(f, "pass"),
+            Outcome::Fail { hash, failed_groups, .. } if failed_groups.is_empty() => {
+                write!(f, "FAIL (screen hash mismatch, {})", hash)
+            },
+            Outcome::Fail { failed_groups, .. } => write!(f, "FAIL ({})", failed_groups.join(", ")),
+            Outcome::NoGolden { hash } => write!(f, "no golden ({})", hash),
+            Outcome::Crashed(message) => write!(f, "CRASH ({})", message),
+            Outcome::Missing => write!(f, "missing rom"),
+        }
+    }
+}
+
+pub fn format_table(results: &[TestResult]) -> String {
+    let mut output = format!("{:<12} {:<10} result\n", "test", "preset");
+
+    for result in results {
+        output += &format!("{:<12} {:<10} {}\n", result.test, result.preset, result.outcome);
+    }
+
+    for result in results {
+        if let Outcome::Fail { screen, .. } = &result.outcome {
+            output += &format!("\n{} ({}):\n{}", result.test, result.preset, screen);
+        }
+    }
+
+    output
+}
+
+// golden lines for the current screens, for pasting into goldens.txt
+pub fn bless(results: &[TestResult]) -> String {
+    results
+        .iter()
+        .filter_map(|result| match &result.outcome {
+            Outcome::Fail { hash, .. } | Outcome::NoGolden { hash } => Some((result, hash)),
+            _ => None,
+        })
+        .map(|(result, hash)| format!("{} {} {}\n", result.test, result.preset, hash))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // draw the "0" font sprite at (0, 0) and spin
+    const DRAW_ZERO: [u8; 6] = [0xA0, 0x00, 0xD0, 0x05, 0x12, 0x04];
+
+    #[test]
+    fn parses_goldens() {
+        let goldens = Goldens::parse("# comment\n\nflags chip8 0123abcd\nbroken line\n");
+
+        assert_eq!(goldens.get("flags", "chip8"), Some("0123abcd"));
+        assert_eq!(goldens.get("flags", "other"), None);
+        assert_eq!(goldens.get("broken", "line"), None);
+    }
+
+    #[test]
+    fn renders_ascii_screen() {
+        let chip8 = run_headless(&DRAW_ZERO, 1, &[], Quirks::default()).unwrap();
+        let screen = ascii_screen(chip8.get_display());
+        let rows: Vec<&str> = screen.lines().collect();
+
+        assert_eq!(rows.len(), SCREEN_HEIGHT);
+        assert!(rows[0].starts_with("####...."));
+        assert!(rows[1].starts_with("#..#...."));
+    }
+
+    #[test]
+    fn compares_against_goldens() {
+        let test = ConformanceTest { name: "zero", file: "zero.ch8", frames: 1, keys: &[], presets: PRESETS };
+        let hash = screen_hash(run_headless(&DRAW_ZERO, 1, &[], Quirks::default()).unwrap().get_display());
+
+        let goldens = Goldens::parse(&format!("zero chip8 {}", hash));
+        assert_eq!(run_test(&test, "chip8", &DRAW_ZERO, &goldens).outcome, Outcome::Pass);
+
+        let goldens = Goldens::parse("zero chip8 0000000000000000");
+        assert!(run_test(&test, "chip8", &DRAW_ZERO, &goldens).is_failure());
+        assert_eq!(run_test(&test, "chip8", &DRAW_ZERO, &Goldens::default()).outcome, Outcome::NoGolden { hash });
+    }
+
+    #[test]
+    fn marks_decide_without_a_golden() {
+        // "3" and a mark next to it, the mark's rows at 0x212
+        let rom = |mark: &Glyph| {
+            let mut rom = vec![0x60, 0x03, 0xF0, 0x29, 0x61, 0x00, 0xD1, 0x15];
+            rom.extend([0x61, 0x05, 0xA2, 0x12, 0x62, 0x00, 0xD1, 0x25, 0x12, 0x10]);
+            rom.extend(mark.rows);
+            rom
+        };
+        let test = ConformanceTest { name: "marks", file: "marks.ch8", frames: 3, keys: &[], presets: PRESETS };
+
+        for preset in PRESETS {
+            let passed = run_test(&test, preset, &rom(&MARK_GLYPHS[0]), &Goldens::default());
+            assert_eq!(passed.outcome, Outcome::Pass, "{}", preset);
+
+            let failed = run_test(&test, preset, &rom(&MARK_GLYPHS[1]), &Goldens::default());
+            assert!(
+                matches!(&failed.outcome, Outcome::Fail { failed_groups, .. } if failed_groups == &["3"]),
+                "{}",
+                preset
+            );
+        }
+    }
+
+    #[test]
+    fn reports_failed_groups() {
+        let mut screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+
+        // "3 +  4 x" on one line, "5 x" below
+        draw(&mut screen, &ocr::font_glyphs()[3], 0, 0);
+        draw(&mut screen, &MARK_GLYPHS[0], 5, 0);
+        draw(&mut screen, &ocr::font_glyphs()[4], 16, 0);
+        draw(&mut screen, &MARK_GLYPHS[1], 21, 0);
+        draw(&mut screen, &ocr::font_glyphs()[5], 0, 6);
+        draw(&mut screen, &MARK_GLYPHS[1], 5, 6);
+
+        assert_eq!(failed_groups(&screen), vec!["4", "5"]);
+    }
+
+    fn draw(screen: &mut [bool], glyph: &Glyph, x: usize, y: usize) {
+        for (row, bits) in glyph.rows.iter().enumerate() {
+            for column in 0..glyph.width {
+                screen[x + column + SCREEN_WIDTH * (y + row)] = bits & (0x80 >> column) != 0;
+            }
+        }
+    }
+
+    #[test]
+    fn reports_crashes() {
+        let test = ConformanceTest { name: "bad", file: "bad.ch8", frames: 1, keys: &[], presets: PRESETS };
+        let result = run_test(&test, "chip8", &[0xFF, 0xFF], &Goldens::default());
+
+        assert!(matches!(result.outcome, Outcome::Crashed(_)));
+    }
+}
@@ -0,0 +1,203 @@
+// what a debugger sets up on the machine, kept between runs: breakpoints, watchpoints, watch
+// expressions and how much to trace. expressions are kept as the text they print as, which parses
+// back to the same thing
+//
+// a session saved for one build of a ROM may be restored into another. a breakpoint past the end
+// of the ROM now can't be where it was meant to be, so it comes back disabled with a warning
+
+use crate::expr::Expr;
+use crate::Chip8;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SavedBreakpoint {
+    pub address: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+    #[serde(default = "enabled")]
+    pub is_enabled: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DebugSession {
+    #[serde(default)]
+    pub breakpoints: Vec<SavedBreakpoint>,
+    #[serde(default)]
+    pub watchpoints: Vec<u16>,
+    #[serde(default)]
+    pub watches: Vec<String>,
+    // instructions kept for the trace, 0 for none
+    #[serde(default)]
+    pub trace_capacity: usize,
+}
+
+impl DebugSession {
+    pub fn capture(chip8: &Chip8) -> Self {
+        let breakpoints = chip8
+            .breakpoints()
+            .map(|address| SavedBreakpoint {
+                address,
+                condition: chip8.breakpoint_condition(address).map(Expr::to_string),
+                is_enabled: chip8.is_breakpoint_enabled(address),
+            })
+            .collect();
+
+        Self {
+            breakpoints,
+            watchpoints: chip8.watchpoints().collect(),
+            watches: chip8.watches().iter().map(Expr::to_string).collect(),
+            trace_capacity: chip8.trace_capacity(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // adds to whatever the machine already has, returning what didn't come back as it was saved
+    pub fn restore(&self, chip8: &mut Chip8) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let rom_end = chip8.loaded_rom().map(|rom| rom.loaded_at_address as usize + rom.size);
+
+        for breakpoint in &self.breakpoints {
+            let address = breakpoint.address;
+
+            match &breakpoint.condition {
+                Some(text) => match Expr::parse(text) {
+                    Ok(condition) => chip8.add_conditional_breakpoint(address, condition),
+                    Err(e) => {
+                        warnings.push(format!(
+                            "dropped the breakpoint at {:03X}, `{}` isn't an expression, {}",
+                            address, text, e
+                        ));
+                        continue;
+                    },
+                },
+                None => chip8.add_breakpoint(address),
+            }
+
+            let is_stale = rom_end.is_some_and(|end| address as usize >= end);
+
+            if is_stale && breakpoint.is_enabled {
+                warnings.push(format!("the breakpoint at {:03X} is past the end of the ROM, it's disabled", address));
+            }
+
+            chip8.set_breakpoint_enabled(address, breakpoint.is_enabled && !is_stale);
+        }
+
+        for &address in &self.watchpoints {
+            chip8.add_watchpoint(address);
+        }
+
+        for text in &self.watches {
+            match Expr::parse(text) {
+                Ok(expr) => chip8.add_watch(expr),
+                Err(e) => warnings.push(format!("dropped the watch `{}`, {}", text, e)),
+            }
+        }
+
+        chip8.set_trace_capacity(self.trace_capacity);
+
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 200: V3 += 1, draw, loop
+    const ROM: [u8; 6] = [0x73, 0x01, 0xD0, 0x05, 0x12, 0x00];
+
+    fn machine() -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ROM).unwrap();
+        chip8
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut chip8 = machine();
+        chip8.add_breakpoint(0x200);
+        chip8.add_conditional_breakpoint(0x202, Expr::parse("v3 == 0x10 && [i+1]>2").unwrap());
+        chip8.set_breakpoint_enabled(0x200, false);
+        chip8.add_watchpoint(0xF00);
+        chip8.add_watch(Expr::parse("v3").unwrap());
+        chip8.add_watch(Expr::parse("[i] & 0xF0").unwrap());
+        chip8.set_trace_capacity(64);
+
+        let session = DebugSession::capture(&chip8);
+        assert_eq!(session.breakpoints[1].condition.as_deref(), Some("v3 == 0x10 && [i + 1] > 2"));
+
+        let json = serde_json::to_string(&session).unwrap();
+        let read: DebugSession = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, session);
+
+        let mut other = machine();
+        assert!(read.restore(&mut other).is_empty());
+        assert_eq!(DebugSession::capture(&other), session);
+        assert!(!other.is_breakpoint_enabled(0x200));
+        assert!(other.has_watchpoint(0xF00));
+        assert_eq!(other.trace_capacity(), 64);
+    }
+
+    #[test]
+    fn breakpoints_past_the_rom_come_back_disabled() {
+        let session = DebugSession {
+            breakpoints: vec![
+                SavedBreakpoint { address: 0x204, condition: None, is_enabled: true },
+                SavedBreakpoint { address: 0x206, condition: Some("v3 == 2".to_string()), is_enabled: true },
+                SavedBreakpoint { address: 0x300, condition: None, is_enabled: false },
+            ],
+            // data, not code, so the ROM's size says nothing about them
+            watchpoints: vec![0xF00],
+            ..DebugSession::default()
+        };
+
+        let mut chip8 = machine();
+        let warnings = session.restore(&mut chip8);
+
+        assert_eq!(warnings, ["the breakpoint at 206 is past the end of the ROM, it's disabled"]);
+        assert!(chip8.is_breakpoint_enabled(0x204));
+        assert!(chip8.has_breakpoint(0x206) && !chip8.is_breakpoint_enabled(0x206));
+        assert!(chip8.breakpoint_condition(0x206).is_some());
+        assert!(!chip8.is_breakpoint_enabled(0x300));
+        assert!(chip8.has_watchpoint(0xF00));
+    }
+
+    #[test]
+    fn drops_what_doesnt_parse() {
+        let session = DebugSession {
+            breakpoints: vec![SavedBreakpoint {
+                address: 0x200,
+                condition: Some("v3 ==".to_string()),
+                is_enabled: true,
+            }],
+            watches: vec!["v3 +* 1".to_string(), "v3".to_string()],
+            ..DebugSession::default()
+        };
+
+        let mut chip8 = machine();
+        let warnings = session.restore(&mut chip8);
+
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].starts_with("dropped the breakpoint at 200, `v3 ==` isn't an expression"));
+        assert!(warnings[1].starts_with("dropped the watch `v3 +* 1`"));
+        assert!(!chip8.has_breakpoint(0x200));
+        assert_eq!(chip8.watches(), [Expr::parse("v3").unwrap()]);
+    }
+
+    #[test]
+    fn missing_fields_are_defaults() {
+        let read: DebugSession = serde_json::from_str(r#"{ "breakpoints": [{ "address": 512 }] }"#).unwrap();
+
+        assert_eq!(read.breakpoints, [SavedBreakpoint { address: 0x200, condition: None, is_enabled: true }]);
+        assert!(read.watches.is_empty() && read.trace_capacity == 0);
+        assert!(DebugSession::default().is_empty());
+    }
+}
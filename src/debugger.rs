@@ -0,0 +1,454 @@
+use crate::expr::Expr;
+use crate::{Chip8, Chip8Error, NUM_REGISTER_V};
+
+use std::collections::{BTreeMap, BTreeSet};
+
+// how long step_over lets a subroutine run before giving up on it returning
+const STEP_OVER_LIMIT: usize = 1_000_000;
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Breakpoints {
+    addresses: BTreeSet<u16>,
+    // only stop at these addresses while the condition holds
+    conditions: BTreeMap<u16, Expr>,
+    // kept but not stopped at
+    disabled: BTreeSet<u16>,
+    // the breakpoint just stopped at, so resuming executes it instead of stopping again
+    resume_from: Option<u16>,
+    hit: Option<u16>,
+    watches: Vec<Expr>,
+    // addresses that stop the machine after an instruction writes them
+    watchpoints: BTreeSet<u16>,
+    // the watchpoint the instruction running now wrote
+    written: Option<u16>,
+    watchpoint_hit: Option<u16>,
+}
+
+impl Chip8 {
+    // checked before each instruction while running, true means stop here. a condition that can't
+    // be evaluated stops too, so the mistake shows
+    pub(crate) fn should_stop(&mut self) -> bool {
+        let pc = self.program_counter;
+        let resume_from = self.breakpoints.resume_from.take();
+
+        if self.breakpoints.addresses.is_empty()
+            || resume_from == Some(pc)
+            || !self.breakpoints.addresses.contains(&pc)
+            || self.breakpoints.disabled.contains(&pc)
+        {
+            return false;
+        }
+
+        let is_met = match self.breakpoints.conditions.get(&pc) {
+            Some(condition) => condition.is_true(self).unwrap_or(true),
+            None => true,
+        };
+
+        if is_met {
+            self.breakpoints.hit = Some(pc);
+            self.breakpoints.resume_from = Some(pc);
+        }
+
+        is_met
+    }
+
+    // checked after each instruction while running, true means stop after it
+    pub(crate) fn has_written_watchpoint(&mut self) -> bool {
+        let Some(address) = self.breakpoints.written.take() else {
+            return false;
+        };
+
+        self.breakpoints.watchpoint_hit = Some(address);
+        true
+    }
+
+    // called by write_ram
+    pub(crate) fn record_watchpoint_write(&mut self, address: usize) {
+        if !self.breakpoints.watchpoints.is_empty() && self.breakpoints.watchpoints.contains(&(address as u16)) {
+            self.breakpoints.written = Some(address as u16);
+        }
+    }
+
+    // a write outside of running, by a step or an edit, isn't a reason to stop the next frame
+    pub(crate) fn forget_watchpoint_write(&mut self) {
+        self.breakpoints.written = None;
+    }
+
+    // replaces any breakpoint already at the address, enabled
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.addresses.insert(address);
+        self.breakpoints.conditions.remove(&address);
+        self.breakpoints.disabled.remove(&address);
+    }
+
+    pub fn add_conditional_breakpoint(&mut self, address: u16, condition: Expr) {
+        self.breakpoints.addresses.insert(address);
+        self.breakpoints.conditions.insert(address, condition);
+        self.breakpoints.disabled.remove(&address);
+    }
+
+    // does nothing where there's no breakpoint
+    pub fn set_breakpoint_enabled(&mut self, address: u16, is_enabled: bool) {
+        if !self.breakpoints.addresses.contains(&address) {
+            return;
+        }
+
+        if is_enabled {
+            self.breakpoints.disabled.remove(&address);
+        } else {
+            self.breakpoints.disabled.insert(address);
+        }
+    }
+
+    pub fn is_breakpoint_enabled(&self, address: u16) -> bool {
+        self.has_breakpoint(address) && !self.breakpoints.disabled.contains(&address)
+    }
+
+    pub fn breakpoint_condition(&self, address: u16) -> Option<&Expr> {
+        self.breakpoints.conditions.get(&address)
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) {
+        self.breakpoints.addresses.remove(&address);
+        self.breakpoints.conditions.remove(&address);
+        self.breakpoints.disabled.remove(&address);
+    }
+
+    // true if the address now has a breakpoint
+    pub fn toggle_breakpoint(&mut self, address: u16) -> bool {
+        if !self.breakpoints.addresses.remove(&address) {
+            self.breakpoints.addresses.insert(address);
+            return true;
+        }
+
+        self.breakpoints.conditions.remove(&address);
+        self.breakpoints.disabled.remove(&address);
+        false
+    }
+
+    pub fn has_breakpoint(&self, address: u16) -> bool {
+        self.breakpoints.addresses.contains(&address)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.addresses.iter().copied()
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.addresses.clear();
+        self.breakpoints.conditions.clear();
+        self.breakpoints.disabled.clear();
+    }
+
+    // stop after any instruction that writes to `address`, even the value it already had
+    pub fn add_watchpoint(&mut self, address: u16) {
+        self.breakpoints.watchpoints.insert(address);
+    }
+
+    pub fn remove_watchpoint(&mut self, address: u16) {
+        self.breakpoints.watchpoints.remove(&address);
+    }
+
+    pub fn has_watchpoint(&self, address: u16) -> bool {
+        self.breakpoints.watchpoints.contains(&address)
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.watchpoints.iter().copied()
+    }
+
+    // the address whose write paused the machine since the last call, if any
+    pub fn take_watchpoint_hit(&mut self) -> Option<u16> {
+        self.breakpoints.watchpoint_hit.take()
+    }
+
+    // expressions a debugger shows the value of as the program runs
+    pub fn add_watch(&mut self, expr: Expr) {
+        self.breakpoints.watches.push(expr);
+    }
+
+    pub fn remove_watch(&mut self, index: usize) -> Option<Expr> {
+        (index < self.breakpoints.watches.len()).then(|| self.breakpoints.watches.remove(index))
+    }
+
+    pub fn watches(&self) -> &[Expr] {
+        &self.breakpoints.watches
+    }
+
+    // the address of the breakpoint that paused the machine since the last call, if any
+    pub fn take_breakpoint_hit(&mut self) -> Option<u16> {
+        self.breakpoints.hit.take()
+    }
+
+    // don't stop on the breakpoint at `address` when running from it next
+    pub(crate) fn resume_from(&mut self, address: u16) {
+        self.breakpoints.resume_from = Some(address);
+    }
+
+    pub fn set_register(&mut self, index: usize, value: u8) {
+        assert!(index < NUM_REGISTER_V, "there are only 16 V registers");
+        self.register_v[index] = value;
+        self.step_history.clear();
+    }
+
+    pub fn set_register_i(&mut self, value: u16) {
+        self.register_i = value;
+        self.step_history.clear();
+    }
+
+    pub fn set_program_counter(&mut self, address: u16) {
+        self.program_counter = address;
+        self.breakpoints.resume_from = None;
+        self.step_history.clear();
+    }
+
+    pub fn write_memory(&mut self, address: u16, value: u8) {
+        assert!((address as usize) < self.ram.len(), "address is outside of memory");
+        self.write_ram(address as usize, value);
+        self.step_history.clear();
+    }
+
+    // one instruction, but a CALL runs until its subroutine returns. stops early on a breakpoint
+    // inside the subroutine, returns false if it hit one or never came back within STEP_OVER_LIMIT.
+    // an instruction that can't run stops it with the error
+    pub fn step_over(&mut self) -> Result<bool, Chip8Error> {
+        let pc = self.program_counter;
+        let is_call = self.ram.get(pc as usize).is_some_and(|byte| byte >> 4 == 0x2);
+
+        // the instruction under the cursor always runs, even if it has a breakpoint
+        self.breakpoints.resume_from = None;
+        self.tick()?;
+
+        if !is_call {
+            return Ok(true);
+        }
+
+        let return_to = pc + 2;
+        let depth = self.stack_pointer - 1;
+
+        for _ in 0..STEP_OVER_LIMIT {
+            if self.program_counter == return_to && self.stack_pointer == depth {
+                return Ok(true);
+            }
+
+            if self.should_stop() {
+                self.is_paused = true;
+                return Ok(false);
+            }
+
+            self.tick()?;
+        }
+
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 200: V3 = 1, I = the top of the "0" glyph, draw it at (V3, V3), V3 += 1, loop
+    // 20A: call 20E, jump to 200 ... 20E: V5 = 7, return
+    const ROM: [u8; 18] = [
+        0x63, 0x01, 0xA0, 0x00, 0xD3, 0x31, 0x73, 0x01, 0x12, 0x04, 0x22, 0x0E, 0x12, 0x00, 0x65, 0x07, 0x00, 0xEE,
+    ];
+    const DRAW: u16 = 0x204;
+
+    fn machine() -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ROM).unwrap();
+        chip8
+    }
+
+    #[test]
+    fn pause_edit_break_and_continue() {
+        let mut chip8 = machine();
+        chip8.run_frame(1);
+        chip8.set_paused(true);
+
+        // edit V3 while paused, then break on the draw routine
+        chip8.set_register(3, 0x10);
+        chip8.add_breakpoint(DRAW);
+        chip8.set_paused(false);
+        chip8.run_frame(10);
+
+        assert!(chip8.is_paused());
+        assert_eq!(chip8.take_breakpoint_hit(), Some(DRAW));
+        assert_eq!(chip8.take_breakpoint_hit(), None);
+        assert_eq!(chip8.program_counter(), DRAW);
+        assert_eq!(chip8.registers()[3], 0x10);
+
+        // continuing runs the draw at the edited position and stops on the next pass
+        chip8.set_paused(false);
+        chip8.run_frame(10);
+
+        assert_eq!(chip8.take_breakpoint_hit(), Some(DRAW));
+        assert!(chip8.get_display()[0x10 + 0x10 * crate::SCREEN_WIDTH]);
+        assert_eq!(chip8.registers()[3], 0x11);
+    }
+
+    #[test]
+    fn breakpoints_stop_turbo_frames_too() {
+        let mut chip8 = machine();
+        chip8.set_speed_multiplier(8.0);
+        chip8.add_breakpoint(0x206);
+        chip8.run_frame(1);
+
+        assert!(chip8.is_paused());
+        assert_eq!(chip8.program_counter(), 0x206);
+    }
+
+    #[test]
+    fn toggles_and_lists_breakpoints() {
+        let mut chip8 = machine();
+
+        assert!(chip8.toggle_breakpoint(0x300));
+        chip8.add_breakpoint(0x200);
+        assert_eq!(chip8.breakpoints().collect::<Vec<_>>(), vec![0x200, 0x300]);
+        assert!(!chip8.toggle_breakpoint(0x300));
+        assert!(!chip8.has_breakpoint(0x300));
+
+        chip8.remove_breakpoint(0x200);
+        chip8.add_breakpoint(0x202);
+        chip8.clear_breakpoints();
+        assert_eq!(chip8.breakpoints().count(), 0);
+    }
+
+    #[test]
+    fn conditional_breakpoints_stop_when_the_condition_holds() {
+        let mut chip8 = machine();
+        chip8.add_conditional_breakpoint(DRAW, Expr::parse("v3 == 3").unwrap());
+        chip8.run_frame(20);
+
+        assert_eq!(chip8.take_breakpoint_hit(), Some(DRAW));
+        assert_eq!(chip8.registers()[3], 3);
+        assert_eq!(chip8.breakpoint_condition(DRAW).map(Expr::to_string), Some("v3 == 3".to_string()));
+
+        // a plain breakpoint at the same address drops the condition
+        chip8.add_breakpoint(DRAW);
+        assert_eq!(chip8.breakpoint_condition(DRAW), None);
+    }
+
+    #[test]
+    fn a_broken_condition_stops() {
+        let mut chip8 = machine();
+        chip8.add_conditional_breakpoint(DRAW, Expr::parse("[0x2000] == 1").unwrap());
+        chip8.run_frame(20);
+
+        assert_eq!(chip8.take_breakpoint_hit(), Some(DRAW));
+        assert_eq!(chip8.registers()[3], 1);
+
+        chip8.remove_breakpoint(DRAW);
+        assert_eq!(chip8.breakpoint_condition(DRAW), None);
+    }
+
+    #[test]
+    fn disabled_breakpoints_stay_but_dont_stop() {
+        let mut chip8 = machine();
+        chip8.add_conditional_breakpoint(DRAW, Expr::parse("v3 == 3").unwrap());
+        chip8.set_breakpoint_enabled(DRAW, false);
+        chip8.set_breakpoint_enabled(0x300, false);
+        chip8.run_frame(20);
+
+        assert!(!chip8.is_paused());
+        assert!(chip8.has_breakpoint(DRAW) && !chip8.is_breakpoint_enabled(DRAW));
+        assert!(chip8.breakpoint_condition(DRAW).is_some());
+        assert!(!chip8.has_breakpoint(0x300));
+
+        chip8.set_breakpoint_enabled(DRAW, true);
+        chip8.set_register(3, 3);
+        chip8.set_program_counter(DRAW);
+        chip8.run_frame(20);
+        assert_eq!(chip8.take_breakpoint_hit(), Some(DRAW));
+
+        // adding it again enables it
+        chip8.set_breakpoint_enabled(DRAW, false);
+        chip8.add_breakpoint(DRAW);
+        assert!(chip8.is_breakpoint_enabled(DRAW));
+    }
+
+    #[test]
+    fn watchpoints_stop_after_the_write() {
+        // V0 = 5, I = 300, store V0, V1 = 1, loop
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0x60, 0x05, 0xA3, 0x00, 0xF0, 0x55, 0x61, 0x01, 0x12, 0x00]).unwrap();
+        chip8.add_watchpoint(0x300);
+        chip8.add_watchpoint(0x301);
+        chip8.run_frame(20);
+
+        assert!(chip8.is_paused());
+        assert_eq!(chip8.take_watchpoint_hit(), Some(0x300));
+        assert_eq!(chip8.take_breakpoint_hit(), None);
+        assert_eq!(chip8.program_counter(), 0x206);
+        assert_eq!(chip8.registers()[1], 0);
+
+        // the same value again still counts
+        chip8.set_paused(false);
+        chip8.run_frame(20);
+        assert_eq!(chip8.take_watchpoint_hit(), Some(0x300));
+        assert_eq!(chip8.watchpoints().collect::<Vec<_>>(), [0x300, 0x301]);
+
+        // stepping over the write doesn't stop the next frame
+        chip8.set_program_counter(0x204);
+        chip8.tick().unwrap();
+        chip8.set_paused(false);
+        chip8.run_frame(3);
+        assert!(!chip8.is_paused());
+
+        chip8.remove_watchpoint(0x300);
+        assert!(!chip8.has_watchpoint(0x300));
+        chip8.run_frame(20);
+        assert!(!chip8.is_paused());
+    }
+
+    #[test]
+    fn keeps_watches() {
+        let mut chip8 = machine();
+        chip8.add_watch(Expr::parse("v3").unwrap());
+        chip8.add_watch(Expr::parse("[i]").unwrap());
+
+        assert_eq!(chip8.watches().len(), 2);
+        assert_eq!(chip8.remove_watch(0), Some(Expr::parse("v3").unwrap()));
+        assert_eq!(chip8.remove_watch(1), None);
+        assert_eq!(chip8.watches(), [Expr::parse("[i]").unwrap()]);
+    }
+
+    #[test]
+    fn edits_state() {
+        let mut chip8 = machine();
+
+        chip8.set_register_i(0x345);
+        chip8.set_program_counter(0x20A);
+        chip8.write_memory(0x345, 0xAB);
+
+        assert_eq!(chip8.register_i(), 0x345);
+        assert_eq!(chip8.program_counter(), 0x20A);
+        assert_eq!(chip8.memory()[0x345], 0xAB);
+    }
+
+    #[test]
+    fn steps_over_calls() {
+        let mut chip8 = machine();
+        chip8.set_program_counter(0x20A);
+
+        assert!(chip8.step_over().unwrap());
+        assert_eq!(chip8.program_counter(), 0x20C);
+        assert_eq!(chip8.registers()[5], 7);
+        assert_eq!(chip8.stack_pointer(), 0);
+
+        // a plain instruction is a single step
+        assert!(chip8.step_over().unwrap());
+        assert_eq!(chip8.program_counter(), 0x200);
+    }
+
+    #[test]
+    fn step_over_stops_at_a_breakpoint_inside_the_call() {
+        let mut chip8 = machine();
+        chip8.set_program_counter(0x20A);
+        chip8.add_breakpoint(0x210);
+
+        assert!(!chip8.step_over().unwrap());
+        assert_eq!(chip8.program_counter(), 0x210);
+        assert_eq!(chip8.take_breakpoint_hit(), Some(0x210));
+    }
+}
@@ -0,0 +1,250 @@
+// everything about a running machine, written out when something looks wrong. a dump bundle is
+// a directory holding
+//
+//   manifest.json  what the bundle is: BUNDLE_VERSION, why it was taken, the ROM, when in the
+//                  run, and the name of each file below
+//   state.json     registers, stack and timers, as `headless --dump-state` writes them
+//   screen.pbm     the screen, as `headless --dump-screen` writes it
+//   trace.txt      the last instructions run, oldest first, one `PC OPCODE disassembly` a line
+//
+// renaming or dropping a file or manifest field bumps BUNDLE_VERSION, adding one doesn't.
+// report_fault is the same for a person reading a crash: the fault, the calls that led to it, the
+// instructions before it and the registers, as plain text
+
+use crate::disasm::disassemble;
+use crate::headless::{pbm, state_json};
+use crate::report::{json_string, RunError};
+use crate::{Chip8, TraceEntry};
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub const BUNDLE_VERSION: u32 = 1;
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const STATE_FILE: &str = "state.json";
+pub const SCREEN_FILE: &str = "screen.pbm";
+pub const TRACE_FILE: &str = "trace.txt";
+
+// a few frames' worth at normal speed
+pub const DEFAULT_TRACE_LENGTH: usize = 1000;
+// how much of the trace a fault report shows
+pub const FAULT_TRACE_LENGTH: usize = 16;
+
+pub fn trace_text(trace: &[TraceEntry]) -> String {
+    trace
+        .iter()
+        .map(|entry| format!("{:03X} {:04X} {}\n", entry.pc, entry.opcode, disassemble(entry.opcode)))
+        .collect()
+}
+
+// the machine as the fault left it, just before the faulting instruction changed anything
+pub fn report_fault(chip8: &Chip8, error: &RunError) -> String {
+    let mut out = format!(
+        "the emulator crashed at {:03X} running {:04X} {}: {}\nin frame {} at instruction {}\n",
+        error.pc,
+        error.opcode,
+        disassemble(error.opcode),
+        error.message,
+        chip8.frame_count(),
+        chip8.instruction_count()
+    );
+
+    // each return address is just after the CALL that pushed it
+    out.push_str("\ncall stack, innermost first:\n");
+    out.push_str(&format!("  {:03X} {:04X} {}\n", error.pc, error.opcode, disassemble(error.opcode)));
+
+    for &return_address in chip8.stack()[..chip8.stack_pointer() as usize].iter().rev() {
+        let call = return_address.wrapping_sub(2);
+        let opcode = opcode_at(chip8, call);
+        out.push_str(&format!("  {:03X} {:04X} {}\n", call, opcode, disassemble(opcode)));
+    }
+
+    let trace = chip8.trace();
+    out.push_str("\nlast instructions, oldest first:\n");
+
+    if trace.is_empty() {
+        out.push_str("  none, the trace is off\n");
+    }
+
+    for line in trace_text(&trace[trace.len().saturating_sub(FAULT_TRACE_LENGTH)..]).lines() {
+        out.push_str(&format!("  {}\n", line));
+    }
+
+    out.push_str("\nregisters:\n");
+
+    for (row, values) in chip8.registers().chunks(8).enumerate() {
+        let registers: Vec<String> =
+            values.iter().enumerate().map(|(i, value)| format!("V{:X} {:02X}", row * 8 + i, value)).collect();
+        out.push_str(&format!("  {}\n", registers.join("  ")));
+    }
+
+    out.push_str(&format!(
+        "  I {:03X}  SP {}  DT {:02X}  ST {:02X}\n",
+        chip8.register_i(),
+        chip8.stack_pointer(),
+        chip8.delay_timer(),
+        chip8.sound_timer()
+    ));
+
+    out
+}
+
+pub(crate) fn opcode_at(chip8: &Chip8, address: u16) -> u16 {
+    let memory = chip8.memory();
+    let byte = |address: u16| memory.get(address as usize).copied().unwrap_or(0);
+
+    u16::from_be_bytes([byte(address), byte(address.wrapping_add(1))])
+}
+
+// `reason` says what took the dump, like "hotkey" or "signal"
+pub fn manifest_json(chip8: &Chip8, reason: &str) -> String {
+    let (rom, sha256) = match chip8.loaded_rom() {
+        Some(rom) => (rom.source_name.as_deref().map_or("null".to_string(), json_string), json_string(&rom.sha256_hex())),
+        None => ("null".to_string(), "null".to_string()),
+    };
+
+    format!(
+        "{{\n  \"version\": {},\n  \"reason\": {},\n  \"rom\": {},\n  \"rom_sha256\": {},\n  \"frames\": {},\n  \"instructions\": {},\n  \"files\": {{ \"state\": \"{}\", \"screen\": \"{}\", \"trace\": \"{}\" }}\n}}\n",
+        BUNDLE_VERSION,
+        json_string(reason),
+        rom,
+        sha256,
+        chip8.frame_count(),
+        chip8.instruction_count(),
+        STATE_FILE,
+        SCREEN_FILE,
+        TRACE_FILE
+    )
+}
+
+// creates `dir` if it's missing. the manifest goes last, so a bundle with one is complete
+pub fn write_bundle(chip8: &Chip8, dir: &Path, reason: &str) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join(STATE_FILE), state_json(chip8))?;
+    fs::write(dir.join(SCREEN_FILE), pbm(chip8.get_display()))?;
+    fs::write(dir.join(TRACE_FILE), trace_text(&chip8.trace()))?;
+    fs::write(dir.join(MANIFEST_FILE), manifest_json(chip8, reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::headless::{Options, Script, DEFAULT_SEED};
+    use crate::report::{self, Expectations};
+
+    use std::env;
+    use std::process;
+
+    // draw "0" at (0, 0) then loop
+    const ROM: [u8; 4] = [0xD0, 0x05, 0x12, 0x02];
+
+    fn options() -> Options {
+        Options { frames: 1, ticks_per_frame: 10, seed: DEFAULT_SEED, script: Script::default() }
+    }
+
+    fn running() -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load_named(&ROM, "zero.ch8").unwrap();
+        chip8.set_trace_capacity(4);
+        chip8.run_frame(4);
+
+        chip8
+    }
+
+    #[test]
+    fn writes_every_file_in_the_manifest() {
+        let dir = env::temp_dir().join(format!("chip8-emu-diagnostics-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let chip8 = running();
+
+        write_bundle(&chip8, &dir.join("bundle"), "hotkey").unwrap();
+
+        let bundle = dir.join("bundle");
+        let manifest = fs::read_to_string(bundle.join(MANIFEST_FILE)).unwrap();
+        for file in [STATE_FILE, SCREEN_FILE, TRACE_FILE] {
+            assert!(manifest.contains(&format!("\"{}\"", file)), "{} isn't in {}", file, manifest);
+            assert!(bundle.join(file).is_file());
+        }
+
+        assert_eq!(fs::read_to_string(bundle.join(STATE_FILE)).unwrap(), state_json(&chip8));
+        assert!(fs::read_to_string(bundle.join(SCREEN_FILE)).unwrap().starts_with("P1\n64 32\n1 1 1 1 0"));
+        assert_eq!(fs::read_dir(&bundle).unwrap().count(), 4);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn describes_the_bundle() {
+        let manifest = manifest_json(&running(), "signal");
+
+        assert!(manifest.contains(&format!("\"version\": {},", BUNDLE_VERSION)));
+        assert!(manifest.contains("\"reason\": \"signal\","));
+        assert!(manifest.contains("\"rom\": \"zero.ch8\","));
+        assert!(manifest.contains("\"frames\": 1,"));
+        assert!(manifest.contains("\"instructions\": 4,"));
+
+        let anonymous = manifest_json(&Chip8::new(), "exit");
+        assert!(anonymous.contains("\"rom\": null,") && anonymous.contains("\"rom_sha256\": null,"));
+    }
+
+    #[test]
+    fn reports_a_return_with_an_empty_stack() {
+        let (_, report) = report::run(&[0x00, 0xEE], &options(), &Expectations::default());
+
+        assert_eq!(
+            report.errors[0].report,
+            "the emulator crashed at 200 running 00EE RET: the RET at 0x200 has nothing to return to
+in frame 0 at instruction 1
+
+call stack, innermost first:
+  200 00EE RET
+
+last instructions, oldest first:
+  200 00EE RET
+
+registers:
+  V0 00  V1 00  V2 00  V3 00  V4 00  V5 00  V6 00  V7 00
+  V8 00  V9 00  VA 00  VB 00  VC 00  VD 00  VE 00  VF 00
+  I 000  SP 0  DT 00  ST 00
+"
+        );
+    }
+
+    #[test]
+    fn reports_the_calls_that_led_to_a_fault() {
+        // V0 = 7, CALL 206, then from there CALL 20A and CALL 20E, one more than the stack holds
+        let rom = [0x60, 0x07, 0x22, 0x06, 0x12, 0x04, 0x22, 0x0A, 0x00, 0xEE, 0x22, 0x0E, 0x00, 0xEE, 0x00, 0xE0];
+        let mut chip8 = Chip8::builder().seed(1).stack_limit(2).build().unwrap();
+        chip8.load(&rom).unwrap();
+        chip8.set_trace_capacity(2);
+
+        chip8.run_frame(10);
+        let fault = chip8.take_fault().unwrap();
+        let report = RunError::from_fault(&chip8, &fault).report;
+
+        assert!(
+            report.starts_with("the emulator crashed at 20A running 220E CALL 0x20E: the CALL at 0x20A overflows"),
+            "{}",
+            report
+        );
+        assert!(report.contains(
+            "call stack, innermost first:\n  20A 220E CALL 0x20E\n  206 220A CALL 0x20A\n  202 2206 CALL 0x206\n\n"
+        ));
+        assert!(report.contains("last instructions, oldest first:\n  206 220A CALL 0x20A\n  20A 220E CALL 0x20E\n\n"));
+        assert!(report.contains("  V0 07  V1 00"));
+        assert!(report.ends_with("  I 000  SP 2  DT 00  ST 00\n"));
+    }
+
+    #[test]
+    fn lists_the_trace_oldest_first() {
+        let text = trace_text(&running().trace());
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], format!("200 D005 {}", disassemble(0xD005)));
+        assert!(lines[1..].iter().all(|line| *line == format!("202 1202 {}", disassemble(0x1202))));
+        assert_eq!(trace_text(&[]), "");
+    }
+}
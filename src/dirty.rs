@@ -0,0 +1,113 @@
+use crate::{Chip8, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// the bounding box of every pixel that may have changed since a frontend last asked, so renderers
+// that pay for each update (terminals, the network) can leave the rest of the screen alone
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DirtyRegion {
+    pub left: usize,
+    pub top: usize,
+    // exclusive
+    pub right: usize,
+    pub bottom: usize,
+}
+
+impl DirtyRegion {
+    pub const FULL: Self = Self { left: 0, top: 0, right: SCREEN_WIDTH, bottom: SCREEN_HEIGHT };
+
+    pub fn pixel(x: usize, y: usize) -> Self {
+        Self { left: x, top: y, right: x + 1, bottom: y + 1 }
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            left: self.left.min(other.left),
+            top: self.top.min(other.top),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        (self.left..self.right).contains(&x) && (self.top..self.bottom).contains(&y)
+    }
+}
+
+impl Chip8 {
+    // where the screen changed since the last call, None if it didn't. a new or reset machine
+    // reports the whole screen so the first draw paints everything
+    pub fn take_dirty_region(&mut self) -> Option<DirtyRegion> {
+        self.dirty.take()
+    }
+
+    pub(crate) fn mark_dirty(&mut self, region: DirtyRegion) {
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => dirty.union(region),
+            None => region,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn machine(rom: &[u8]) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load(rom).unwrap();
+        chip8.take_dirty_region();
+        chip8
+    }
+
+    #[test]
+    fn starts_fully_dirty() {
+        let mut chip8 = Chip8::new();
+
+        assert_eq!(chip8.take_dirty_region(), Some(DirtyRegion::FULL));
+        assert_eq!(chip8.take_dirty_region(), None);
+    }
+
+    #[test]
+    fn covers_a_drawn_sprite() {
+        // V0 = 10, V1 = 4, I = the "0" glyph, draw it
+        let mut chip8 = machine(&[0x60, 0x0A, 0x61, 0x04, 0xA0, 0x00, 0xD0, 0x15]);
+
+        for _ in 0..3 {
+            chip8.tick().unwrap();
+        }
+
+        assert_eq!(chip8.take_dirty_region(), None);
+        chip8.tick().unwrap();
+        assert_eq!(chip8.take_dirty_region(), Some(DirtyRegion { left: 10, top: 4, right: 14, bottom: 9 }));
+    }
+
+    #[test]
+    fn grows_until_taken() {
+        // draw the "1" glyph at (0, 0), then at (60, 30) where it wraps to the top
+        let mut chip8 = machine(&[0xA0, 0x05, 0xD0, 0x05, 0x60, 0x3C, 0x61, 0x1E, 0xD0, 0x15]);
+
+        for _ in 0..5 {
+            chip8.tick().unwrap();
+        }
+
+        // the glyph's leftmost column is blank
+        assert_eq!(chip8.take_dirty_region(), Some(DirtyRegion { left: 1, top: 0, right: 64, bottom: 32 }));
+    }
+
+    #[test]
+    fn clearing_the_screen_dirties_all_of_it() {
+        let mut chip8 = machine(&[0x00, 0xE0]);
+        chip8.tick().unwrap();
+
+        assert_eq!(chip8.take_dirty_region(), Some(DirtyRegion::FULL));
+    }
+
+    #[test]
+    fn unions_and_contains() {
+        let region = DirtyRegion::pixel(3, 4).union(DirtyRegion::pixel(10, 2));
+
+        assert_eq!(region, DirtyRegion { left: 3, top: 2, right: 11, bottom: 5 });
+        assert!(region.contains(10, 4));
+        assert!(!region.contains(11, 4));
+        assert!(!region.contains(2, 3));
+    }
+}
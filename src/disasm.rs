@@ -0,0 +1,112 @@
+use crate::{Chip8Error, Instruction, Peek};
+
+// a decoded instruction to assembly text, including the long load one opcode can't hold
+pub fn disassemble_instruction(instruction: Instruction) -> String {
+    match instruction {
+        Instruction::LongLoad(address) => format!("LD I, 0x{:04X}", address),
+        _ => disassemble(instruction.encode()),
+    }
+}
+
+// the opcode and assembly for what a peek found, a word that isn't an instruction as data. None
+// past the end of memory
+pub fn disassemble_peek(peek: &Peek) -> Option<(u16, String)> {
+    match peek {
+        Ok((opcode, instruction)) => Some((*opcode, disassemble_instruction(*instruction))),
+        Err(Chip8Error::UnknownOpcode { opcode, .. }) => Some((*opcode, disassemble(*opcode))),
+        Err(_) => None,
+    }
+}
+
+// one opcode to assembly text, using the same mnemonics as the debug trace
+pub fn disassemble(opcode: u16) -> String {
+    let digit1 = (opcode & 0xF000) >> 12;
+    let digit2 = (opcode & 0x0F00) >> 8;
+    let digit3 = (opcode & 0x00F0) >> 4;
+    let digit4 = opcode & 0x000F;
+    let nnn = opcode & 0x0FFF;
+    let nn = opcode & 0x00FF;
+    let x = digit2;
+    let y = digit3;
+
+    match (digit1, digit2, digit3, digit4) {
+        (0, 0, 0, 0) => "NOP".to_string(),
+        (0, 0, 0xE, 0) => "CLS".to_string(),
+        (0, 0, 0xE, 0xE) => "RET".to_string(),
+        (1, _, _, _) => format!("JMP 0x{:03X}", nnn),
+        (2, _, _, _) => format!("CALL 0x{:03X}", nnn),
+        (3, _, _, _) => format!("SE V{:X}, 0x{:02X}", x, nn),
+        (4, _, _, _) => format!("SNE V{:X}, 0x{:02X}", x, nn),
+        (5, _, _, _) => format!("SE V{:X}, V{:X}", x, y),
+        (6, _, _, _) => format!("LD V{:X}, 0x{:02X}", x, nn),
+        (7, _, _, _) => format!("ADD V{:X}, 0x{:02X}", x, nn),
+        (8, _, _, 0) => format!("LD V{:X}, V{:X}", x, y),
+        (8, _, _, 1) => format!("OR V{:X}, V{:X}", x, y),
+        (8, _, _, 2) => format!("AND V{:X}, V{:X}", x, y),
+        (8, _, _, 3) => format!("XOR V{:X}, V{:X}", x, y),
+        (8, _, _, 4) => format!("ADD V{:X}, V{:X}", x, y),
+        (8, _, _, 5) => format!("SUB V{:X}, V{:X}", x, y),
+        (8, _, _, 6) => format!("SHR V{:X}", x),
+        (8, _, _, 7) => format!("SUBN V{:X}, V{:X}", x, y),
+        (8, _, _, 0xE) => format!("SHL V{:X}", x),
+        (9, _, _, 0) => format!("SNE V{:X}, V{:X}", x, y),
+        (0xA, _, _, _) => format!("LD I, 0x{:03X}", nnn),
+        (0xB, _, _, _) => format!("JMP V0, 0x{:03X}", nnn),
+        (0xC, _, _, _) => format!("RND V{:X}, 0x{:02X}", x, nn),
+        (0xD, _, _, _) => format!("DRW V{:X}, V{:X}, {:X}", x, y, digit4),
+        (0xE, _, 9, 0xE) => format!("SKP V{:X}", x),
+        (0xE, _, 0xA, 1) => format!("SKNP V{:X}", x),
+        (0xF, 0, 0, 2) => "AUDIO".to_string(),
+        (0xF, _, 0, 7) => format!("LD V{:X}, DT", x),
+        (0xF, _, 0, 0xA) => format!("LD V{:X}, K", x),
+        (0xF, _, 1, 5) => format!("LD DT, V{:X}", x),
+        (0xF, _, 1, 8) => format!("LD ST, V{:X}", x),
+        (0xF, _, 1, 0xE) => format!("ADD I, V{:X}", x),
+        (0xF, _, 2, 9) => format!("LD F, V{:X}", x),
+        (0xF, _, 3, 3) => format!("LD B, V{:X}", x),
+        (0xF, _, 3, 0xA) => format!("PITCH V{:X}", x),
+        (0xF, _, 5, 5) => format!("LD [I], V{:X}", x),
+        (0xF, _, 6, 5) => format!("LD V{:X}, [I]", x),
+        // anything the interpreter doesn't run is most likely sprite or other data
+        _ => format!("DW 0x{:04X}", opcode),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disassembles_each_form() {
+        assert_eq!(disassemble(0x00E0), "CLS");
+        assert_eq!(disassemble(0x00EE), "RET");
+        assert_eq!(disassemble(0x1234), "JMP 0x234");
+        assert_eq!(disassemble(0x6A2B), "LD VA, 0x2B");
+        assert_eq!(disassemble(0x8CD4), "ADD VC, VD");
+        assert_eq!(disassemble(0x8F0E), "SHL VF");
+        assert_eq!(disassemble(0xB300), "JMP V0, 0x300");
+        assert_eq!(disassemble(0xD125), "DRW V1, V2, 5");
+        assert_eq!(disassemble(0xE3A1), "SKNP V3");
+        assert_eq!(disassemble(0xF20A), "LD V2, K");
+        assert_eq!(disassemble(0xF002), "AUDIO");
+        assert_eq!(disassemble(0xF965), "LD V9, [I]");
+    }
+
+    #[test]
+    fn disassembles_decoded_instructions() {
+        assert_eq!(disassemble_instruction(Instruction::Draw(1, 2, 5)), "DRW V1, V2, 5");
+        assert_eq!(disassemble_instruction(Instruction::LongLoad(0x1234)), "LD I, 0x1234");
+        assert_eq!(
+            disassemble_peek(&Err(Chip8Error::UnknownOpcode { address: 0x200, opcode: 0xFFFF })).unwrap().1,
+            "DW 0xFFFF"
+        );
+        assert_eq!(disassemble_peek(&Err(Chip8Error::PcOutOfBounds(0xFFF))), None);
+    }
+
+    #[test]
+    fn unknown_opcodes_are_data() {
+        assert_eq!(disassemble(0x8128), "DW 0x8128");
+        assert_eq!(disassemble(0xFFFF), "DW 0xFFFF");
+        assert_eq!(disassemble(0x0123), "DW 0x0123");
+    }
+}
@@ -0,0 +1,255 @@
+// a gym-style wrapper for bots and reinforcement learning: reset, then step with the keys to hold
+// for a number of frames, and get back the screen and what happened meanwhile to shape rewards from
+//
+//   let mut env = Env::new(&rom, EnvConfig { seed: 7, ..EnvConfig::default() })?;
+//   let mut observation = env.reset();
+//
+//   loop {
+//       let (next, info) = env.step(policy(&observation), 4);
+//       ...
+//   }
+//
+// a reset builds the machine afresh from the ROM and config, so the same actions replay the same
+// episode, RND included. an observation is the screen packed into a fixed array, it only
+// allocates for the RAM window when there is one
+
+use crate::harness::DEFAULT_TICKS_PER_FRAME;
+use crate::headless::DEFAULT_SEED;
+use crate::report::HaltReason;
+use crate::{BeepEdge, Chip8, Chip8Error, PackedScreen, Quirks, NUM_KEYS};
+
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+
+// bit n held down is key n
+pub type KeyBitmask = u16;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnvConfig {
+    pub seed: u64,
+    pub quirks: Quirks,
+    pub ticks_per_frame: usize,
+    // memory copied into every observation, for agents that read a score instead of the screen
+    pub ram_window: Option<Range<usize>>,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        Self {
+            seed: DEFAULT_SEED,
+            quirks: Quirks::default(),
+            ticks_per_frame: DEFAULT_TICKS_PER_FRAME,
+            ram_window: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Observation {
+    pub screen: PackedScreen,
+    // empty without a ram_window
+    pub ram: Vec<u8>,
+}
+
+// what changed during one step
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StepInfo {
+    // as many as asked for, unless the program crashed partway
+    pub frames: u32,
+    pub instructions: u64,
+    pub draws: u64,
+    // draws that erased a lit pixel
+    pub collisions: u64,
+    // times the sound started
+    pub beeps: u32,
+    // stopped on FX0A, nothing happens until a key goes down and comes back up
+    pub is_waiting_for_key: bool,
+    // the program crashed the emulator, every step does nothing until a reset
+    pub is_crashed: bool,
+}
+
+pub struct Env {
+    rom: Vec<u8>,
+    config: EnvConfig,
+    chip8: Chip8,
+    is_crashed: bool,
+}
+
+impl Env {
+    pub fn new(rom: &[u8], config: EnvConfig) -> Result<Self, Chip8Error> {
+        let chip8 = machine(rom, &config)?;
+
+        if let Some(window) = &config.ram_window {
+            if window.start > window.end || window.end > chip8.ram.len() {
+                return Err(Chip8Error::RamWindow { start: window.start, end: window.end, ram_size: chip8.ram.len() });
+            }
+        }
+
+        Ok(Self { rom: rom.to_vec(), config, chip8, is_crashed: false })
+    }
+
+    pub fn reset(&mut self) -> Observation {
+        self.chip8 = machine(&self.rom, &self.config).expect("new checked the ROM and config");
+        self.is_crashed = false;
+
+        self.observe()
+    }
+
+    // holds the keys in `action` for `frames` frames, releasing the rest
+    pub fn step(&mut self, action: KeyBitmask, frames: u32) -> (Observation, StepInfo) {
+        for key in 0..NUM_KEYS {
+            self.chip8.keypress(key, action & 1 << key != 0);
+        }
+
+        let (instructions, draws, collisions) =
+            (self.chip8.instruction_count(), self.chip8.draw_count(), self.chip8.collision_count());
+        let mut info = StepInfo::default();
+
+        if !self.is_crashed {
+            let (chip8, ticks_per_frame) = (&mut self.chip8, self.config.ticks_per_frame);
+
+            // advance_frame, so every frame runs whatever the pause and speed multiplier say
+            let run = panic::catch_unwind(AssertUnwindSafe(|| {
+                for _ in 0..frames {
+                    let result = chip8.advance_frame(ticks_per_frame);
+
+                    // a frame an instruction couldn't finish doesn't count
+                    if chip8.take_fault().is_some() {
+                        return true;
+                    }
+
+                    if result.beep == Some(BeepEdge::Start) {
+                        info.beeps += 1;
+                    }
+
+                    info.frames += 1;
+                }
+
+                false
+            }));
+            self.is_crashed = run.unwrap_or(true);
+        }
+
+        info.instructions = self.chip8.instruction_count() - instructions;
+        info.draws = self.chip8.draw_count() - draws;
+        info.collisions = self.chip8.collision_count() - collisions;
+        info.is_waiting_for_key = !self.is_crashed && HaltReason::of(&self.chip8) == HaltReason::KeyWait;
+        info.is_crashed = self.is_crashed;
+
+        (self.observe(), info)
+    }
+
+    pub fn observe(&self) -> Observation {
+        let ram = self.config.ram_window.clone().map_or_else(Vec::new, |window| self.chip8.memory()[window].to_vec());
+
+        Observation { screen: PackedScreen::pack(self.chip8.get_display()), ram }
+    }
+
+    pub fn is_crashed(&self) -> bool {
+        self.is_crashed
+    }
+
+    // for anything an observation leaves out
+    pub fn chip8(&self) -> &Chip8 {
+        &self.chip8
+    }
+}
+
+fn machine(rom: &[u8], config: &EnvConfig) -> Result<Chip8, Chip8Error> {
+    let mut chip8 = Chip8::builder().seed(config.seed).quirks(config.quirks).build()?;
+    chip8.load(rom)?;
+
+    Ok(chip8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::SCREEN_WIDTH;
+
+    fn env(rom: &[u8]) -> Env {
+        Env::new(rom, EnvConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn reports_draws_and_collisions_per_step() {
+        // draw "0" at (0, 0) forever, every other draw erases it
+        let mut env = env(&[0xD0, 0x05, 0x12, 0x00]);
+        env.reset();
+
+        let (observation, info) = env.step(0, 1);
+        assert_eq!((info.frames, info.instructions, info.draws, info.collisions), (1, 10, 5, 2));
+        // five draws leave it drawn
+        assert!(observation.screen.unpack()[0]);
+
+        let (_, info) = env.step(0, 2);
+        assert_eq!((info.frames, info.draws, info.collisions), (2, 10, 5));
+    }
+
+    #[test]
+    fn holds_the_keys_in_the_action() {
+        // wait for a key into V0, copy it to 0x300, loop
+        let config = EnvConfig { ram_window: Some(0x300..0x302), ..EnvConfig::default() };
+        let mut env = Env::new(&[0xF0, 0x0A, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x00], config).unwrap();
+        assert_eq!(env.reset().ram, [0, 0]);
+
+        let (_, info) = env.step(0, 3);
+        assert!(info.is_waiting_for_key);
+
+        // key B down, the wait goes on until it's let go
+        let (observation, info) = env.step(1 << 0xB, 1);
+        assert_eq!(observation.ram, [0, 0]);
+        assert!(env.chip8().keys()[0xB] && !env.chip8().keys()[0xA]);
+        assert!(info.is_waiting_for_key);
+
+        // then it's stored and the loop is back waiting for the next one
+        let (observation, info) = env.step(0, 1);
+        assert_eq!(observation.ram, [0xB, 0]);
+        assert!(!env.chip8().keys()[0xB]);
+        assert!(info.is_waiting_for_key);
+    }
+
+    #[test]
+    fn a_crash_ends_the_episode_until_a_reset() {
+        // count to 3 in V0, then return with an empty stack
+        let mut env = env(&[0x70, 0x01, 0x30, 0x03, 0x12, 0x00, 0x00, 0xEE]);
+        env.reset();
+
+        let (_, info) = env.step(0, 5);
+        assert!(info.is_crashed && env.is_crashed());
+        assert_eq!(info.frames, 0);
+
+        let (_, info) = env.step(0, 5);
+        assert_eq!((info.frames, info.instructions, info.is_crashed), (0, 0, true));
+
+        env.reset();
+        assert!(!env.is_crashed());
+        assert_eq!(env.chip8().registers()[0], 0);
+    }
+
+    #[test]
+    fn resets_to_the_same_start() {
+        // random digits at random places
+        let rom = [0xC0, 0x3F, 0xC1, 0x1F, 0xC2, 0x0F, 0xF2, 0x29, 0xD0, 0x15, 0x12, 0x00];
+        let mut env = Env::new(&rom, EnvConfig { seed: 3, ..EnvConfig::default() }).unwrap();
+
+        let start = env.reset();
+        let (first, _) = env.step(0, 4);
+        assert_eq!(env.reset(), start);
+        assert_eq!(env.step(0, 4).0, first);
+        assert!(first.ram.is_empty());
+        assert!(first.screen.unpack().iter().take(SCREEN_WIDTH * 32).any(|&pixel| pixel));
+    }
+
+    #[test]
+    fn refuses_what_cant_run() {
+        assert_eq!(Env::new(&[], EnvConfig::default()).err(), Some(Chip8Error::EmptyRom));
+
+        let config = EnvConfig { ram_window: Some(0xF00..0x1001), ..EnvConfig::default() };
+        assert_eq!(
+            Env::new(&[0x12, 0x00], config).err(),
+            Some(Chip8Error::RamWindow { start: 0xF00, end: 0x1001, ram_size: 0x1000 })
+        );
+    }
+}
@@ -0,0 +1,79 @@
+// everything that can go wrong setting a machine up, loading a ROM into it or running it
+
+use crate::{FONTSET_SIZE, RAM_SIZE, STACK_SIZE};
+
+use std::error::Error;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Chip8Error {
+    RamSize(usize),
+    StartAddress { start_address: u16, ram_size: usize },
+    FontOverlapsProgram(u16),
+    StackLimit(usize),
+    SpeedMultiplier(f64),
+    EmptyRom,
+    RomTooLarge { size: usize, max: usize },
+    Io { path: String, message: String },
+    RamWindow { start: usize, end: usize, ram_size: usize },
+    TimeoutKey(u8),
+    // no whole instruction fits at the address before RAM ends: a ROM that ran off the end of its code
+    // or jumped past it, or a peek that looked there
+    PcOutOfBounds(u16),
+    UnknownOpcode { address: u16, opcode: u16 },
+    // the rest are running an instruction, at `address`, that can't run. tick leaves the machine
+    // just before it
+    StackOverflow(u16),
+    StackUnderflow(u16),
+    // it reads or writes `target`, past the end of RAM
+    MemoryOutOfBounds { address: u16, target: usize },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Chip8Error::RamSize(size) => {
+                write!(f, "{} bytes of RAM is more than 12-bit addresses reach ({})", size, RAM_SIZE)
+            },
+            Chip8Error::StartAddress { start_address, ram_size } => {
+                write!(
+                    f,
+                    "start address {:#05X} leaves no room for a program in {} bytes of RAM",
+                    start_address, ram_size
+                )
+            },
+            Chip8Error::FontOverlapsProgram(start_address) => {
+                write!(
+                    f,
+                    "the font at 0x000-{:#05X} overlaps programs loaded at {:#05X}",
+                    FONTSET_SIZE - 1,
+                    start_address
+                )
+            },
+            Chip8Error::StackLimit(limit) => write!(f, "stack limit {} isn't between 1 and {}", limit, STACK_SIZE),
+            Chip8Error::SpeedMultiplier(multiplier) => write!(f, "speed multiplier {} isn't positive", multiplier),
+            Chip8Error::EmptyRom => write!(f, "the ROM is empty"),
+            Chip8Error::RomTooLarge { size, max } => {
+                write!(f, "{} bytes is larger than the {} bytes of program memory", size, max)
+            },
+            Chip8Error::Io { path, message } => write!(f, "unable to read {}: {}", path, message),
+            Chip8Error::RamWindow { start, end, ram_size } => {
+                write!(f, "RAM window {:#05X}..{:#05X} isn't inside {} bytes of RAM", start, end, ram_size)
+            },
+            Chip8Error::TimeoutKey(key) => write!(f, "key wait timeout key {:#X} isn't one of the 16 keys", key),
+            Chip8Error::PcOutOfBounds(address) => {
+                write!(f, "no instruction fits at {:#05X}, RAM ends first", address)
+            },
+            Chip8Error::UnknownOpcode { address, opcode } => {
+                write!(f, "{:04X} at {:#05X} isn't an instruction", opcode, address)
+            },
+            Chip8Error::StackOverflow(address) => write!(f, "the CALL at {:#05X} overflows the stack", address),
+            Chip8Error::StackUnderflow(address) => write!(f, "the RET at {:#05X} has nothing to return to", address),
+            Chip8Error::MemoryOutOfBounds { address, target } => {
+                write!(f, "the instruction at {:#05X} reaches {:#05X}, past the end of RAM", address, target)
+            },
+        }
+    }
+}
+
+impl Error for Chip8Error {}
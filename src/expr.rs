@@ -0,0 +1,535 @@
+// the debugger's expressions, for breakpoint conditions and watches
+//
+//   v3 == 0x20 && [i + 1] != 0
+//
+// terms are v0-vF, i, pc, dt, st, sp, a byte of memory in brackets and numbers, decimal or 0x/$
+// hex. values are u32 and wrap, comparisons and ! give 0 or 1 and a condition holds when it isn't
+// 0. from loosest to tightest:
+//
+//   ||   &&   == != < <= > >= (these don't chain)   |   ^   &   << >>   + -   !
+
+use crate::Chip8;
+
+use std::error::Error;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Register {
+    V(u8),
+    I,
+    Pc,
+    Dt,
+    St,
+    Sp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Literal(u32),
+    Register(Register),
+    Memory(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExprError {
+    // columns count from 1
+    Syntax { column: usize, message: String },
+    OutOfMemory(u32),
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExprError::Syntax { column, message } => write!(f, "column {}: {}", column, message),
+            ExprError::OutOfMemory(address) => write!(f, "[{:#X}] is outside of memory", address),
+        }
+    }
+}
+
+impl Error for ExprError {}
+
+// the operators with their text, longest first so "<=" isn't read as "<"
+const OPERATORS: [(&str, BinaryOp); 15] = [
+    ("||", BinaryOp::Or),
+    ("&&", BinaryOp::And),
+    ("==", BinaryOp::Eq),
+    ("!=", BinaryOp::Ne),
+    ("<=", BinaryOp::Le),
+    (">=", BinaryOp::Ge),
+    ("<<", BinaryOp::Shl),
+    (">>", BinaryOp::Shr),
+    ("<", BinaryOp::Lt),
+    (">", BinaryOp::Gt),
+    ("|", BinaryOp::BitOr),
+    ("^", BinaryOp::BitXor),
+    ("&", BinaryOp::BitAnd),
+    ("+", BinaryOp::Add),
+    ("-", BinaryOp::Sub),
+];
+
+impl BinaryOp {
+    fn text(self) -> &'static str {
+        OPERATORS.iter().find(|&&(_, op)| op == self).map_or("?", |&(text, _)| text)
+    }
+
+    // higher binds tighter
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => 3,
+            BinaryOp::BitOr => 4,
+            BinaryOp::BitXor => 5,
+            BinaryOp::BitAnd => 6,
+            BinaryOp::Shl | BinaryOp::Shr => 7,
+            BinaryOp::Add | BinaryOp::Sub => 8,
+        }
+    }
+
+    fn is_comparison(self) -> bool {
+        self.precedence() == 3
+    }
+
+    fn apply(self, left: u32, right: u32) -> u32 {
+        match self {
+            BinaryOp::Or => (left != 0 || right != 0) as u32,
+            BinaryOp::And => (left != 0 && right != 0) as u32,
+            BinaryOp::Eq => (left == right) as u32,
+            BinaryOp::Ne => (left != right) as u32,
+            BinaryOp::Lt => (left < right) as u32,
+            BinaryOp::Le => (left <= right) as u32,
+            BinaryOp::Gt => (left > right) as u32,
+            BinaryOp::Ge => (left >= right) as u32,
+            BinaryOp::BitOr => left | right,
+            BinaryOp::BitXor => left ^ right,
+            BinaryOp::BitAnd => left & right,
+            BinaryOp::Shl => left.checked_shl(right).unwrap_or(0),
+            BinaryOp::Shr => left.checked_shr(right).unwrap_or(0),
+            BinaryOp::Add => left.wrapping_add(right),
+            BinaryOp::Sub => left.wrapping_sub(right),
+        }
+    }
+}
+
+impl Register {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "i" => Some(Register::I),
+            "pc" => Some(Register::Pc),
+            "dt" => Some(Register::Dt),
+            "st" => Some(Register::St),
+            "sp" => Some(Register::Sp),
+            _ => {
+                let digit = name.strip_prefix('v').filter(|digit| digit.len() == 1)?;
+                u8::from_str_radix(digit, 16).ok().map(Register::V)
+            },
+        }
+    }
+
+    fn read(self, chip8: &Chip8) -> u32 {
+        match self {
+            Register::V(index) => chip8.registers()[index as usize] as u32,
+            Register::I => chip8.register_i() as u32,
+            Register::Pc => chip8.program_counter() as u32,
+            Register::Dt => chip8.delay_timer() as u32,
+            Register::St => chip8.sound_timer() as u32,
+            Register::Sp => chip8.stack_pointer() as u32,
+        }
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Register::V(index) => write!(f, "v{:X}", index),
+            Register::I => write!(f, "i"),
+            Register::Pc => write!(f, "pc"),
+            Register::Dt => write!(f, "dt"),
+            Register::St => write!(f, "st"),
+            Register::Sp => write!(f, "sp"),
+        }
+    }
+}
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Self, ExprError> {
+        let mut parser = Parser { tokens: tokenize(text)?, next: 0, end: text.chars().count() + 1 };
+        let expr = parser.expression(1)?;
+
+        match parser.peek() {
+            None => Ok(expr),
+            Some(&(column, ref token)) => Err(syntax(column, format!("unexpected {}", token))),
+        }
+    }
+
+    pub fn evaluate(&self, chip8: &Chip8) -> Result<u32, ExprError> {
+        Ok(match self {
+            Expr::Literal(value) => *value,
+            Expr::Register(register) => register.read(chip8),
+            Expr::Memory(address) => {
+                let address = address.evaluate(chip8)?;
+                let byte = chip8.memory().get(address as usize).ok_or(ExprError::OutOfMemory(address))?;
+
+                *byte as u32
+            },
+            Expr::Not(operand) => (operand.evaluate(chip8)? == 0) as u32,
+            // the right side of || and && only runs when it matters, so `[i] == 1` can be guarded
+            Expr::Binary(BinaryOp::Or, left, right) => (left.is_true(chip8)? || right.is_true(chip8)?) as u32,
+            Expr::Binary(BinaryOp::And, left, right) => (left.is_true(chip8)? && right.is_true(chip8)?) as u32,
+            Expr::Binary(op, left, right) => op.apply(left.evaluate(chip8)?, right.evaluate(chip8)?),
+        })
+    }
+
+    pub fn is_true(&self, chip8: &Chip8) -> Result<bool, ExprError> {
+        Ok(self.evaluate(chip8)? != 0)
+    }
+}
+
+// the shortest form that parses back the same
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Literal(value) if *value > 9 => write!(f, "{:#X}", value),
+            Expr::Literal(value) => write!(f, "{}", value),
+            Expr::Register(register) => write!(f, "{}", register),
+            Expr::Memory(address) => write!(f, "[{}]", address),
+            Expr::Not(operand) if matches!(**operand, Expr::Binary(..)) => write!(f, "!({})", operand),
+            Expr::Not(operand) => write!(f, "!{}", operand),
+            Expr::Binary(op, left, right) => {
+                // the left side only needs parentheses when it binds looser, and comparisons never chain
+                let is_left_wrapped = matches!(**left, Expr::Binary(inner, ..)
+                    if inner.precedence() < op.precedence() || (op.is_comparison() && inner.is_comparison()));
+                let is_right_wrapped =
+                    matches!(**right, Expr::Binary(inner, ..) if inner.precedence() <= op.precedence());
+
+                let wrap = |expr: &Expr, is_wrapped: bool| if is_wrapped { format!("({})", expr) } else { expr.to_string() };
+
+                write!(f, "{} {} {}", wrap(left, is_left_wrapped), op.text(), wrap(right, is_right_wrapped))
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Number(u32),
+    Name(String),
+    Op(BinaryOp),
+    Not,
+    Open(char),
+    Close(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "number {}", value),
+            Token::Name(name) => write!(f, "`{}`", name),
+            Token::Op(op) => write!(f, "`{}`", op.text()),
+            Token::Not => write!(f, "`!`"),
+            Token::Open(c) | Token::Close(c) => write!(f, "`{}`", c),
+        }
+    }
+}
+
+fn syntax(column: usize, message: String) -> ExprError {
+    ExprError::Syntax { column, message }
+}
+
+// each token with the column it starts at
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>, ExprError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+
+    while index < chars.len() {
+        let c = chars[index];
+        let column = index + 1;
+        let rest: String = chars[index..].iter().take(2).collect();
+
+        if c.is_whitespace() {
+            index += 1;
+            continue;
+        }
+
+        if c.is_ascii_alphanumeric() || c == '$' {
+            let start = index;
+            index += 1;
+            while index < chars.len() && chars[index].is_ascii_alphanumeric() {
+                index += 1;
+            }
+
+            let word: String = chars[start..index].iter().collect::<String>().to_ascii_lowercase();
+            tokens.push((column, word_token(&word).ok_or_else(|| syntax(column, format!("`{}` isn't a number or register", word)))?));
+            continue;
+        }
+
+        if let Some(&(text, op)) = OPERATORS.iter().find(|(text, _)| rest.starts_with(text)) {
+            tokens.push((column, Token::Op(op)));
+            index += text.len();
+            continue;
+        }
+
+        let token = match c {
+            '!' => Token::Not,
+            '(' | '[' => Token::Open(c),
+            ')' | ']' => Token::Close(c),
+            _ => return Err(syntax(column, format!("unexpected `{}`", c))),
+        };
+
+        tokens.push((column, token));
+        index += 1;
+    }
+
+    Ok(tokens)
+}
+
+fn word_token(word: &str) -> Option<Token> {
+    if word.starts_with(|c: char| c.is_ascii_digit() || c == '$') {
+        let value = match word.strip_prefix("0x").or_else(|| word.strip_prefix('$')) {
+            Some(digits) => u32::from_str_radix(digits, 16),
+            None => word.parse(),
+        };
+
+        return value.ok().map(Token::Number);
+    }
+
+    Register::from_name(word).map(|_| Token::Name(word.to_string()))
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    // the column just past the text, where "expected ..." points when it runs out
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&(usize, Token)> {
+        self.tokens.get(self.next)
+    }
+
+    fn column(&self) -> usize {
+        self.peek().map_or(self.end, |&(column, _)| column)
+    }
+
+    fn take(&mut self) -> Option<(usize, Token)> {
+        let token = self.tokens.get(self.next).cloned();
+        self.next += 1;
+        token
+    }
+
+    // binary operators at `min_precedence` and tighter, left to right
+    fn expression(&mut self, min_precedence: u8) -> Result<Expr, ExprError> {
+        let mut left = self.unary()?;
+
+        while let Some(&(_, Token::Op(op))) = self.peek() {
+            if op.precedence() < min_precedence {
+                break;
+            }
+
+            self.next += 1;
+            let right = self.expression(op.precedence() + 1)?;
+
+            if let Some(&(next, Token::Op(following))) = self.peek() {
+                if op.is_comparison() && following.is_comparison() {
+                    return Err(syntax(next, format!("comparisons don't chain, put `{}` in parentheses", op.text())));
+                }
+            }
+
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ExprError> {
+        let column = self.column();
+
+        match self.take() {
+            Some((_, Token::Not)) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some((_, Token::Number(value))) => Ok(Expr::Literal(value)),
+            Some((_, Token::Name(name))) => Ok(Expr::Register(Register::from_name(&name).expect("tokenize checked it"))),
+            Some((_, Token::Open(open))) => {
+                let inner = self.expression(1)?;
+                let close = if open == '[' { ']' } else { ')' };
+
+                match self.take() {
+                    Some((_, Token::Close(c))) if c == close => {},
+                    _ => return Err(syntax(self.column_before(), format!("expected `{}` to close the `{}` at column {}", close, open, column))),
+                }
+
+                Ok(if open == '[' { Expr::Memory(Box::new(inner)) } else { inner })
+            },
+            Some((_, token)) => Err(syntax(column, format!("expected a value, found {}", token))),
+            None => Err(syntax(column, "expected a value".to_string())),
+        }
+    }
+
+    // where the token `take` just looked at was, or the end
+    fn column_before(&self) -> usize {
+        self.tokens.get(self.next - 1).map_or(self.end, |&(column, _)| column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn machine() -> Chip8 {
+        let mut chip8 = Chip8::with_seed(1);
+        // V0 = 5, V1 = 0x20, I = 0x300, [0x300] = 0xAB, [0x301] = 7
+        chip8.load(&[0x60, 0x05, 0x61, 0x20, 0xA3, 0x00]).unwrap();
+        chip8.run_frame(3);
+        chip8.write_memory(0x300, 0xAB);
+        chip8.write_memory(0x301, 7);
+        chip8
+    }
+
+    fn eval(text: &str) -> u32 {
+        Expr::parse(text).unwrap().evaluate(&machine()).unwrap()
+    }
+
+    fn syntax_error(text: &str) -> (usize, String) {
+        match Expr::parse(text) {
+            Err(ExprError::Syntax { column, message }) => (column, message),
+            other => panic!("{:?} gave {:?}", text, other),
+        }
+    }
+
+    #[test]
+    fn reads_registers_and_literals() {
+        assert_eq!(eval("v0"), 5);
+        assert_eq!(eval("V1"), 0x20);
+        assert_eq!(eval("i"), 0x300);
+        assert_eq!(eval("pc"), 0x206);
+        assert_eq!(eval("sp + dt + st"), 0);
+        assert_eq!(eval("42"), 42);
+        assert_eq!(eval("0x2A"), 42);
+        assert_eq!(eval("$2a"), 42);
+    }
+
+    #[test]
+    fn reads_memory() {
+        assert_eq!(eval("[0x300]"), 0xAB);
+        assert_eq!(eval("[i]"), 0xAB);
+        assert_eq!(eval("[i+1]"), 7);
+        assert_eq!(eval("[[0x301] + 0x2F9]"), 0xAB);
+    }
+
+    #[test]
+    fn memory_reads_stop_at_the_end_of_memory() {
+        let chip8 = machine();
+
+        assert_eq!(Expr::parse("[0xFFF]").unwrap().evaluate(&chip8), Ok(0));
+        assert_eq!(Expr::parse("[0x1000]").unwrap().evaluate(&chip8), Err(ExprError::OutOfMemory(0x1000)));
+        assert_eq!(Expr::parse("[i - 0x301]").unwrap().evaluate(&chip8), Err(ExprError::OutOfMemory(u32::MAX)));
+
+        // guarded by && the read never happens
+        assert_eq!(Expr::parse("v0 == 0 && [0x5000] == 1").unwrap().evaluate(&chip8), Ok(0));
+    }
+
+    #[test]
+    fn operators_bind_by_precedence() {
+        assert_eq!(eval("1 + 2 << 1"), 6);
+        // 1 | (2 ^ (7 & 6))
+        assert_eq!(eval("1 | 2 ^ 7 & 6"), 5);
+        assert_eq!(eval("v0 & 4 == 4"), 1);
+        assert_eq!(eval("v0 == 5 || v0 == 6 && v1 == 0"), 1);
+        assert_eq!(eval("(v0 == 5 || v0 == 6) && v1 == 0"), 0);
+        assert_eq!(eval("10 - 3 - 2"), 5);
+        assert_eq!(eval("!v0 + 1"), 1);
+        assert_eq!(eval("!(v0 - 5)"), 1);
+        assert_eq!(eval("0 - 1"), u32::MAX);
+        assert_eq!(eval("1 << 40"), 0);
+    }
+
+    #[test]
+    fn compares() {
+        assert_eq!(eval("v0 < 6"), 1);
+        assert_eq!(eval("v0 <= 4"), 0);
+        assert_eq!(eval("v1 > v0"), 1);
+        assert_eq!(eval("v1 >= 0x21"), 0);
+        assert_eq!(eval("[i] != 0xAB"), 0);
+    }
+
+    #[test]
+    fn parses_into_a_tree() {
+        assert_eq!(
+            Expr::parse("v3 == 2 + [i]"),
+            Ok(Expr::Binary(
+                BinaryOp::Eq,
+                Box::new(Expr::Register(Register::V(3))),
+                Box::new(Expr::Binary(
+                    BinaryOp::Add,
+                    Box::new(Expr::Literal(2)),
+                    Box::new(Expr::Memory(Box::new(Expr::Register(Register::I))))
+                ))
+            ))
+        );
+    }
+
+    #[test]
+    fn displays_what_parses_back() {
+        for (text, shown) in [
+            ("V3==0x20&&[I+1]!=0", "v3 == 0x20 && [i + 1] != 0"),
+            ("(1 + 2) << 1", "1 + 2 << 1"),
+            ("(1 | 2) & 3", "(1 | 2) & 3"),
+            ("9 - (3 - 2)", "9 - (3 - 2)"),
+            ("255", "0xFF"),
+            ("(v0 == 1) == 1", "(v0 == 1) == 1"),
+            ("!(v0 & 1)", "!(v0 & 1)"),
+        ] {
+            let expr = Expr::parse(text).unwrap();
+
+            assert_eq!(expr.to_string(), shown);
+            assert_eq!(Expr::parse(shown), Ok(expr));
+        }
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert_eq!(syntax_error(""), (1, "expected a value".to_string()));
+        assert_eq!(syntax_error("v0 +"), (5, "expected a value".to_string()));
+        assert_eq!(syntax_error("vg"), (1, "`vg` isn't a number or register".to_string()));
+        assert_eq!(syntax_error("0x"), (1, "`0x` isn't a number or register".to_string()));
+        assert_eq!(syntax_error("99999999999"), (1, "`99999999999` isn't a number or register".to_string()));
+        assert_eq!(syntax_error("v0 # 1"), (4, "unexpected `#`".to_string()));
+        assert_eq!(syntax_error("v0 v1"), (4, "unexpected `v1`".to_string()));
+        assert_eq!(syntax_error("* 2"), (1, "unexpected `*`".to_string()));
+        assert_eq!(syntax_error("== 2"), (1, "expected a value, found `==`".to_string()));
+        assert_eq!(syntax_error("v0)"), (3, "unexpected `)`".to_string()));
+
+        let (column, message) = syntax_error("[i + 1");
+        assert_eq!(column, 7);
+        assert!(message.contains("expected `]`"), "{}", message);
+
+        let (column, message) = syntax_error("(v0 == 1]");
+        assert_eq!(column, 9);
+        assert!(message.contains("expected `)`"), "{}", message);
+
+        let (_, message) = syntax_error("1 < v0 < 3");
+        assert!(message.contains("don't chain"), "{}", message);
+    }
+}
@@ -0,0 +1,393 @@
+// a flat C API over the core, for C and C++ frontends. include/chip8_emu.h is generated from this
+// file by cbindgen (see build.rs), so the `///` comments here are the header's documentation.
+//
+// every function catches panics at the boundary and reports them as CHIP8_STATUS_PANIC, nothing
+// unwinds into C. after a panic the machine is left as it was when it stopped, chip8_load gives
+// it a clean start
+
+use crate::{StateError, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+/// A CHIP-8 machine. Only ever handled through the pointer chip8_new returns.
+pub struct Chip8 {
+    machine: crate::Chip8,
+}
+
+/// What every call that can fail returns.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chip8Status {
+    Ok = 0,
+    /// A pointer argument was NULL.
+    NullPointer = 1,
+    /// The ROM doesn't fit in the 3584 bytes of program memory.
+    RomTooLarge = 2,
+    /// The key isn't 0-15.
+    InvalidKey = 3,
+    /// The buffer can't hold the save state, the size needed was written to `written`.
+    BufferTooSmall = 4,
+    /// The data isn't a save state this version can read.
+    InvalidState = 5,
+    /// The save state was taken with a different ROM loaded.
+    RomMismatch = 6,
+    /// The emulator panicked.
+    Panic = 7,
+    /// An instruction couldn't run, like an unknown opcode or a RET with nothing to return to. The
+    /// machine is left paused just before it.
+    Fault = 8,
+    /// The ROM is empty.
+    EmptyRom = 9,
+}
+
+fn guard(body: impl FnOnce() -> Chip8Status) -> Chip8Status {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(Chip8Status::Panic)
+}
+
+// the caller promises the pointer came from chip8_new and hasn't been freed
+unsafe fn machine<'a>(chip8: *mut Chip8) -> Result<&'a mut crate::Chip8, Chip8Status> {
+    chip8.as_mut().map(|chip8| &mut chip8.machine).ok_or(Chip8Status::NullPointer)
+}
+
+/// A new machine with nothing loaded, or NULL if it couldn't be made.
+/// Free it with chip8_free.
+#[no_mangle]
+pub extern "C" fn chip8_new() -> *mut Chip8 {
+    panic::catch_unwind(|| Box::into_raw(Box::new(Chip8 { machine: crate::Chip8::new() }))).unwrap_or(ptr::null_mut())
+}
+
+/// Frees a machine from chip8_new. NULL is ignored. Any display pointer from chip8_get_display
+/// is invalid afterwards.
+///
+/// # Safety
+/// `chip8` is NULL or from chip8_new, and isn't used again.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_free(chip8: *mut Chip8) {
+    if !chip8.is_null() {
+        // dropping can't panic, nothing in the machine has a Drop that does
+        drop(Box::from_raw(chip8));
+    }
+}
+
+/// Resets the machine and loads `len` bytes of ROM from `rom`. The bytes are copied, the caller
+/// keeps ownership of `rom`. A ROM that's empty or too large leaves the machine as it was.
+///
+/// # Safety
+/// `chip8` is from chip8_new, `rom` points to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load(chip8: *mut Chip8, rom: *const u8, len: usize) -> Chip8Status {
+    guard(|| {
+        let machine = match machine(chip8) {
+            Ok(machine) => machine,
+            Err(status) => return status,
+        };
+
+        if rom.is_null() {
+            return Chip8Status::NullPointer;
+        }
+
+        // checked before the reset, a ROM that doesn't load leaves the machine as it was
+        let rom = slice::from_raw_parts(rom, len);
+        let loaded = machine.check_rom(rom).and_then(|()| {
+            machine.reset();
+            machine.load(rom)
+        });
+
+        match loaded {
+            Ok(()) => Chip8Status::Ok,
+            Err(crate::Chip8Error::EmptyRom) => Chip8Status::EmptyRom,
+            Err(_) => Chip8Status::RomTooLarge,
+        }
+    })
+}
+
+/// Runs one instruction.
+///
+/// # Safety
+/// `chip8` is from chip8_new.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_tick(chip8: *mut Chip8) -> Chip8Status {
+    guard(|| match machine(chip8) {
+        Ok(machine) => match machine.tick() {
+            Ok(_) => Chip8Status::Ok,
+            Err(_) => Chip8Status::Fault,
+        },
+        Err(status) => status,
+    })
+}
+
+/// Runs one 60 Hz frame: `ticks_per_frame` instructions, then the timers count down once.
+///
+/// # Safety
+/// `chip8` is from chip8_new.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_run_frame(chip8: *mut Chip8, ticks_per_frame: usize) -> Chip8Status {
+    guard(|| match machine(chip8) {
+        Ok(machine) => {
+            machine.run_frame(ticks_per_frame);
+
+            match machine.take_fault() {
+                Some(_) => Chip8Status::Fault,
+                None => Chip8Status::Ok,
+            }
+        },
+        Err(status) => status,
+    })
+}
+
+/// Presses (`is_pressed` true) or releases key 0-15 of the hex keypad.
+///
+/// # Safety
+/// `chip8` is from chip8_new.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_keypress(chip8: *mut Chip8, key: usize, is_pressed: bool) -> Chip8Status {
+    guard(|| {
+        let machine = match machine(chip8) {
+            Ok(machine) => machine,
+            Err(status) => return status,
+        };
+
+        if key >= 16 {
+            return Chip8Status::InvalidKey;
+        }
+
+        machine.keypress(key, is_pressed);
+
+        Chip8Status::Ok
+    })
+}
+
+/// Points `pixels` at the screen, `width` * `height` bools row by row with true for a lit pixel.
+/// The machine owns the pixels. They change as it runs and are valid until chip8_free.
+///
+/// # Safety
+/// `chip8` is from chip8_new, the out pointers are writable.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_get_display(chip8: *mut Chip8, pixels: *mut *const bool, width: *mut usize, height: *mut usize) -> Chip8Status {
+    guard(|| {
+        let machine = match machine(chip8) {
+            Ok(machine) => machine,
+            Err(status) => return status,
+        };
+
+        if pixels.is_null() || width.is_null() || height.is_null() {
+            return Chip8Status::NullPointer;
+        }
+
+        *pixels = machine.get_display().as_ptr();
+        *width = SCREEN_WIDTH;
+        *height = SCREEN_HEIGHT;
+
+        Chip8Status::Ok
+    })
+}
+
+/// Writes a save state into the caller's `buffer` of `capacity` bytes and its size to `written`.
+/// With a NULL or too small buffer nothing is copied, it returns CHIP8_STATUS_BUFFER_TOO_SMALL and
+/// `written` is the size needed, so a first call with a NULL buffer asks for the size.
+///
+/// # Safety
+/// `chip8` is from chip8_new, `buffer` is NULL or has `capacity` writable bytes, `written` is
+/// writable.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_save_state(chip8: *mut Chip8, buffer: *mut u8, capacity: usize, written: *mut usize) -> Chip8Status {
+    guard(|| {
+        let machine = match machine(chip8) {
+            Ok(machine) => machine,
+            Err(status) => return status,
+        };
+
+        if written.is_null() {
+            return Chip8Status::NullPointer;
+        }
+
+        let state = machine.save_state();
+        *written = state.len();
+
+        if buffer.is_null() || capacity < state.len() {
+            return Chip8Status::BufferTooSmall;
+        }
+
+        ptr::copy_nonoverlapping(state.as_ptr(), buffer, state.len());
+
+        Chip8Status::Ok
+    })
+}
+
+/// Restores a save state from chip8_save_state, taken with the same ROM loaded. The bytes are
+/// copied, the caller keeps ownership of `state`.
+///
+/// # Safety
+/// `chip8` is from chip8_new, `state` points to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_state(chip8: *mut Chip8, state: *const u8, len: usize) -> Chip8Status {
+    guard(|| {
+        let machine = match machine(chip8) {
+            Ok(machine) => machine,
+            Err(status) => return status,
+        };
+
+        if state.is_null() {
+            return Chip8Status::NullPointer;
+        }
+
+        match machine.load_state(slice::from_raw_parts(state, len)) {
+            Ok(()) => Chip8Status::Ok,
+            Err(StateError::RomMismatch) => Chip8Status::RomMismatch,
+            Err(_) => Chip8Status::InvalidState,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::MAX_ROM_SIZE;
+
+    // draw "0" at (0, 0) then loop
+    const ROM: [u8; 4] = [0xD0, 0x05, 0x12, 0x02];
+
+    struct Machine(*mut Chip8);
+
+    impl Machine {
+        fn new() -> Self {
+            let chip8 = chip8_new();
+            assert!(!chip8.is_null());
+
+            Self(chip8)
+        }
+
+        fn loaded(rom: &[u8]) -> Self {
+            let machine = Self::new();
+            assert_eq!(unsafe { chip8_load(machine.0, rom.as_ptr(), rom.len()) }, Chip8Status::Ok);
+
+            machine
+        }
+
+        fn pixels(&self) -> Vec<bool> {
+            let (mut pixels, mut width, mut height) = (ptr::null(), 0, 0);
+
+            unsafe {
+                assert_eq!(chip8_get_display(self.0, &mut pixels, &mut width, &mut height), Chip8Status::Ok);
+                slice::from_raw_parts(pixels, width * height).to_vec()
+            }
+        }
+    }
+
+    impl Drop for Machine {
+        fn drop(&mut self) {
+            unsafe { chip8_free(self.0) };
+        }
+    }
+
+    #[test]
+    fn runs_a_rom_and_shows_the_screen() {
+        let machine = Machine::loaded(&ROM);
+
+        unsafe {
+            assert_eq!(chip8_tick(machine.0), Chip8Status::Ok);
+            assert_eq!(chip8_run_frame(machine.0, 10), Chip8Status::Ok);
+        }
+
+        let (mut pixels, mut width, mut height) = (ptr::null(), 0, 0);
+        assert_eq!(unsafe { chip8_get_display(machine.0, &mut pixels, &mut width, &mut height) }, Chip8Status::Ok);
+        assert_eq!((width, height), (SCREEN_WIDTH, SCREEN_HEIGHT));
+        // the top row of "0" is 1111
+        assert_eq!(machine.pixels()[..5], [true, true, true, true, false]);
+    }
+
+    #[test]
+    fn null_pointers_are_errors() {
+        let machine = Machine::new();
+        let mut written = 0;
+
+        unsafe {
+            chip8_free(ptr::null_mut());
+            assert_eq!(chip8_tick(ptr::null_mut()), Chip8Status::NullPointer);
+            assert_eq!(chip8_run_frame(ptr::null_mut(), 10), Chip8Status::NullPointer);
+            assert_eq!(chip8_keypress(ptr::null_mut(), 1, true), Chip8Status::NullPointer);
+            assert_eq!(chip8_load(machine.0, ptr::null(), 4), Chip8Status::NullPointer);
+            assert_eq!(chip8_get_display(machine.0, ptr::null_mut(), ptr::null_mut(), ptr::null_mut()), Chip8Status::NullPointer);
+            assert_eq!(chip8_save_state(machine.0, ptr::null_mut(), 0, ptr::null_mut()), Chip8Status::NullPointer);
+            assert_eq!(chip8_save_state(ptr::null_mut(), ptr::null_mut(), 0, &mut written), Chip8Status::NullPointer);
+            assert_eq!(chip8_load_state(machine.0, ptr::null(), 0), Chip8Status::NullPointer);
+        }
+    }
+
+    #[test]
+    fn checks_roms_and_keys() {
+        let machine = Machine::new();
+        let big = vec![0; MAX_ROM_SIZE + 1];
+
+        unsafe {
+            assert_eq!(chip8_load(machine.0, big.as_ptr(), big.len()), Chip8Status::RomTooLarge);
+            assert_eq!(chip8_load(machine.0, ROM.as_ptr(), 0), Chip8Status::EmptyRom);
+            assert_eq!(chip8_keypress(machine.0, 15, true), Chip8Status::Ok);
+            assert_eq!(chip8_keypress(machine.0, 16, true), Chip8Status::InvalidKey);
+            assert!((*machine.0).machine.keys()[15]);
+        }
+    }
+
+    #[test]
+    fn saves_into_the_callers_buffer() {
+        let machine = Machine::loaded(&ROM);
+        let mut written = 0;
+
+        // ask for the size first
+        let status = unsafe { chip8_save_state(machine.0, ptr::null_mut(), 0, &mut written) };
+        assert_eq!(status, Chip8Status::BufferTooSmall);
+        let mut small = vec![0; written - 1];
+        let status = unsafe { chip8_save_state(machine.0, small.as_mut_ptr(), small.len(), &mut written) };
+        assert_eq!(status, Chip8Status::BufferTooSmall);
+
+        let mut state = vec![0; written];
+        assert_eq!(unsafe { chip8_save_state(machine.0, state.as_mut_ptr(), state.len(), &mut written) }, Chip8Status::Ok);
+        assert_eq!(written, state.len());
+
+        unsafe { chip8_run_frame(machine.0, 10) };
+        assert!(machine.pixels()[0]);
+        assert_eq!(unsafe { chip8_load_state(machine.0, state.as_ptr(), state.len()) }, Chip8Status::Ok);
+        assert!(!machine.pixels()[0]);
+
+        let garbage = [1, 2, 3];
+        assert_eq!(unsafe { chip8_load_state(machine.0, garbage.as_ptr(), garbage.len()) }, Chip8Status::InvalidState);
+
+        let other = Machine::loaded(&[0x12, 0x00]);
+        assert_eq!(unsafe { chip8_load_state(other.0, state.as_ptr(), state.len()) }, Chip8Status::RomMismatch);
+    }
+
+    #[test]
+    fn faults_stop_at_the_boundary() {
+        // return with nothing on the stack
+        let machine = Machine::loaded(&[0x00, 0xEE]);
+
+        assert_eq!(unsafe { chip8_tick(machine.0) }, Chip8Status::Fault);
+        assert_eq!(unsafe { chip8_run_frame(machine.0, 10) }, Chip8Status::Fault);
+
+        // and a fresh load still works afterwards
+        unsafe {
+            assert_eq!(chip8_load(machine.0, ROM.as_ptr(), ROM.len()), Chip8Status::Ok);
+            assert_eq!(chip8_run_frame(machine.0, 10), Chip8Status::Ok);
+        }
+    }
+
+    #[test]
+    fn the_header_declares_every_function() {
+        let header = include_str!("../include/chip8_emu.h");
+        let source = include_str!("ffi.rs");
+        let functions: Vec<&str> = source
+            .lines()
+            .filter_map(|line| line.strip_prefix("pub unsafe extern \"C\" fn ").or_else(|| line.strip_prefix("pub extern \"C\" fn ")))
+            .map(|line| &line[..line.find('(').unwrap()])
+            .collect();
+
+        assert_eq!(functions.len(), 9);
+
+        for function in functions {
+            assert!(header.contains(&format!("{}(", function)), "{} isn't in include/chip8_emu.h, run cbindgen", function);
+        }
+    }
+}
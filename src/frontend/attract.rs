@@ -0,0 +1,264 @@
+// attract mode for a cabinet or kiosk: --playlist goes through a list of ROMs on its own, moving on
+// every --rotate or when a game ends in a jump to itself. a ROM with an input recording next to it
+// (game.keys beside game.ch8, as --record-input writes it) plays itself. any key hands the game
+// to whoever pressed it and rotation waits until they've left it alone for a whole --rotate
+
+use super::browser;
+
+use chip8_emu::headless::{self, Script};
+use chip8_emu::Chip8;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_ROTATE: Duration = Duration::from_secs(90);
+pub const RECORDING_EXTENSION: &str = "keys";
+
+// the ROMs in a directory as the browser lists them, or those a text file names one per line
+pub fn load_playlist(path: &Path) -> Result<Vec<PathBuf>, String> {
+    if path.is_dir() {
+        return Ok(browser::scan(path)?.into_iter().map(|entry| entry.path).collect());
+    }
+
+    let text = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+    let roms = parse_playlist(&text, path.parent().unwrap_or(Path::new("")));
+
+    if roms.is_empty() {
+        return Err(format!("No ROMs in {}", path.display()));
+    }
+
+    Ok(roms)
+}
+
+// paths relative to the playlist's directory, `#` starts a comment
+pub fn parse_playlist(text: &str, dir: &Path) -> Vec<PathBuf> {
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| dir.join(line))
+        .collect()
+}
+
+pub fn recording_path(rom: &Path) -> PathBuf {
+    rom.with_extension(RECORDING_EXTENSION)
+}
+
+// where the playlist is and whether it's playing itself, apart from loading and timing
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rotation {
+    len: usize,
+    index: usize,
+    interval: Duration,
+    started: Instant,
+    // the last key a player pressed, None while the demo plays
+    player_input: Option<Instant>,
+}
+
+impl Rotation {
+    pub fn new(len: usize, interval: Duration, now: Instant) -> Self {
+        assert!(len > 0, "a playlist needs at least one ROM");
+
+        Self { len, index: 0, interval, started: now, player_input: None }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn is_demo(&self) -> bool {
+        self.player_input.is_none()
+    }
+
+    // true when this is the key that took over from the demo
+    pub fn player_input(&mut self, now: Instant) -> bool {
+        let is_takeover = self.is_demo();
+        self.player_input = Some(now);
+
+        is_takeover
+    }
+
+    // the index to load next, when it's time. a player's game over stays up until they leave
+    pub fn poll(&mut self, now: Instant, is_halted: bool) -> Option<usize> {
+        let is_due = match self.player_input {
+            Some(last) => now - last >= self.interval,
+            None => is_halted || now - self.started >= self.interval,
+        };
+
+        if !is_due {
+            return None;
+        }
+
+        self.index = (self.index + 1) % self.len;
+        self.started = now;
+        self.player_input = None;
+
+        Some(self.index)
+    }
+}
+
+pub struct Attract {
+    roms: Vec<PathBuf>,
+    rotation: Rotation,
+    // the current ROM's, while it plays itself
+    recording: Option<Script>,
+}
+
+impl Attract {
+    pub fn new(roms: Vec<PathBuf>, interval: Duration, now: Instant) -> Self {
+        Self { rotation: Rotation::new(roms.len(), interval, now), roms, recording: None }
+    }
+
+    pub fn rom(&self) -> &Path {
+        &self.roms[self.rotation.index()]
+    }
+
+    // after the current ROM is loaded: its recording, seeded as it was recorded
+    pub fn start(&mut self, chip8: &mut Chip8) {
+        let path = recording_path(self.rom());
+        self.recording = None;
+
+        let Ok(text) = fs::read_to_string(&path) else {
+            return;
+        };
+
+        match headless::parse_script(&text) {
+            Ok(script) => {
+                if let Some(seed) = script.seed {
+                    chip8.set_seed(seed);
+                }
+
+                self.recording = Some(script);
+            },
+            Err(e) => eprintln!("{}: {}, the demo won't play itself", path.display(), e),
+        }
+    }
+
+    // a real key press. taking over lets go of the keys the recording was holding
+    pub fn player_input(&mut self, now: Instant, chip8: &mut Chip8, keypad: &[bool; 16]) {
+        if self.rotation.player_input(now) && self.recording.take().is_some() {
+            for key in (0..16).filter(|&key| !keypad[key]) {
+                chip8.keypress(key, false);
+            }
+        }
+    }
+
+    // the recording's input for the coming frame, and the speed to run it at
+    pub fn before_frame(&self, chip8: &mut Chip8, ticks_per_frame: usize) -> usize {
+        let Some(script) = &self.recording else {
+            return ticks_per_frame;
+        };
+
+        let frame = chip8.frame_count() as usize;
+
+        for change in script.quirks.iter().filter(|change| change.frame == frame) {
+            chip8.set_quirk(change.quirk, change.is_enabled);
+        }
+
+        for key in script.keys.iter().filter(|key| key.frame == frame) {
+            chip8.keypress(key.key, key.is_pressed);
+        }
+
+        script.speed_at(frame, ticks_per_frame)
+    }
+
+    // the ROM to load next, when it's time
+    pub fn poll(&mut self, now: Instant, is_halted: bool) -> Option<&Path> {
+        self.rotation.poll(now, is_halted).map(|index| self.roms[index].as_path())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROTATE: Duration = Duration::from_secs(90);
+
+    #[test]
+    fn reads_a_playlist_relative_to_itself() {
+        let roms =
+            parse_playlist("# the cabinet\npong.ch8\n\n  games/tetris.ch8  # new\n/roms/ufo.ch8\n", Path::new("kiosk"));
+
+        assert_eq!(
+            roms,
+            [PathBuf::from("kiosk/pong.ch8"), PathBuf::from("kiosk/games/tetris.ch8"), PathBuf::from("/roms/ufo.ch8")]
+        );
+        assert_eq!(recording_path(&roms[1]), PathBuf::from("kiosk/games/tetris.keys"));
+    }
+
+    #[test]
+    fn rotates_on_the_timer_and_wraps() {
+        let start = Instant::now();
+        let mut rotation = Rotation::new(2, ROTATE, start);
+
+        assert_eq!(rotation.poll(start + Duration::from_secs(89), false), None);
+        assert_eq!(rotation.poll(start + ROTATE, false), Some(1));
+        assert_eq!(rotation.poll(start + ROTATE * 2 - Duration::from_secs(1), false), None);
+        assert_eq!(rotation.poll(start + ROTATE * 2, false), Some(0));
+    }
+
+    #[test]
+    fn moves_on_when_the_game_halts() {
+        let start = Instant::now();
+        let mut rotation = Rotation::new(3, ROTATE, start);
+
+        assert_eq!(rotation.poll(start + Duration::from_secs(5), true), Some(1));
+        // the timer starts again for the next one
+        assert_eq!(rotation.poll(start + Duration::from_secs(90), false), None);
+        assert_eq!(rotation.poll(start + Duration::from_secs(95), false), Some(2));
+    }
+
+    #[test]
+    fn a_player_holds_the_game_until_they_leave_it() {
+        let start = Instant::now();
+        let mut rotation = Rotation::new(3, ROTATE, start);
+
+        assert!(rotation.player_input(start + Duration::from_secs(60)));
+        assert!(!rotation.player_input(start + Duration::from_secs(100)));
+        assert!(!rotation.is_demo());
+
+        // past the demo's time, and a game over, with the player still around
+        assert_eq!(rotation.poll(start + Duration::from_secs(120), true), None);
+        assert_eq!(rotation.poll(start + Duration::from_secs(189), false), None);
+
+        // then the demo picks up with the next ROM
+        assert_eq!(rotation.poll(start + Duration::from_secs(190), false), Some(1));
+        assert!(rotation.is_demo());
+        assert_eq!(rotation.poll(start + Duration::from_secs(279), false), None);
+        assert_eq!(rotation.poll(start + Duration::from_secs(280), false), Some(2));
+    }
+
+    #[test]
+    fn plays_the_recording_until_a_player_takes_over() {
+        // waits for a key and copies it to V1, then waits again
+        let rom = [0xF0, 0x0A, 0x81, 0x00, 0x12, 0x00];
+        let now = Instant::now();
+        let mut chip8 = Chip8::new();
+        chip8.load(&rom).unwrap();
+
+        let mut attract = Attract::new(vec![PathBuf::from("game.ch8")], ROTATE, now);
+        attract.recording = Some(headless::parse_script("0 speed 4\n2 7 down\n3 7 up\n5 9 down\n").unwrap());
+
+        for _ in 0..4 {
+            let ticks_per_frame = attract.before_frame(&mut chip8, 10);
+            assert_eq!(ticks_per_frame, 4);
+            chip8.run_frame(ticks_per_frame);
+        }
+        assert_eq!(chip8.registers()[1], 7);
+
+        // the player presses 3 and the recording lets go of everything else
+        let mut keypad = [false; 16];
+        keypad[3] = true;
+        chip8.keypress(3, true);
+        chip8.keypress(9, true);
+        attract.player_input(now, &mut chip8, &keypad);
+        assert!(chip8.keys()[3] && !chip8.keys()[9]);
+
+        for _ in 0..4 {
+            let ticks_per_frame = attract.before_frame(&mut chip8, 10);
+            assert_eq!(ticks_per_frame, 10);
+            chip8.run_frame(ticks_per_frame);
+        }
+        assert!(!chip8.keys()[9]);
+    }
+}
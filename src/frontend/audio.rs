@@ -0,0 +1,174 @@
+use super::drift::DriftController;
+
+use chip8_emu::Chip8;
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::Sdl;
+
+pub const SAMPLE_RATE: i32 = 44100;
+// ~12ms per callback, a bit under one 60Hz frame
+const DEVICE_BUFFER_SAMPLES: u16 = 512;
+// frames of audio queued ahead of the device, enough to survive a late frame
+const PREFILL_FRAMES: usize = 2;
+const MAX_QUEUED_FRAMES: usize = 4;
+const MAX_DRIFT_CORRECTION: f32 = 0.005;
+const FRAMES_PER_SECOND: usize = 60;
+
+pub fn samples_per_frame() -> usize {
+    SAMPLE_RATE as usize / FRAMES_PER_SECOND
+}
+
+// samples handed from the main loop to the audio callback, the callback never sees the emulator
+#[derive(Clone)]
+pub struct SampleRing {
+    samples: Arc<Mutex<VecDeque<f32>>>,
+    capacity: usize,
+}
+
+impl SampleRing {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    // when full the oldest samples are dropped so latency can't creep up
+    pub fn push(&self, data: &[f32]) {
+        let mut samples = self.samples.lock().unwrap();
+
+        samples.extend(data);
+
+        let overflow = samples.len().saturating_sub(self.capacity);
+        samples.drain(..overflow);
+    }
+
+    // fills `out` and pads with silence on underrun, returns how many real samples were read
+    pub fn pop_into(&self, out: &mut [f32]) -> usize {
+        let mut samples = self.samples.lock().unwrap();
+        let count = out.len().min(samples.len());
+
+        for (sample, queued) in out.iter_mut().zip(samples.drain(..count)) {
+            *sample = queued;
+        }
+
+        out[count..].fill(0.0);
+
+        count
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.lock().unwrap().len()
+    }
+}
+
+struct RingCallback {
+    ring: SampleRing,
+}
+
+impl AudioCallback for RingCallback {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.ring.pop_into(out);
+    }
+}
+
+pub struct AudioOutput {
+    _device: AudioDevice<RingCallback>,
+    ring: SampleRing,
+    frame: Vec<f32>,
+    samples_per_frame: usize,
+    sample_rate: u32,
+    drift: DriftController,
+}
+
+impl AudioOutput {
+    pub fn open(sdl_context: &Sdl) -> Result<Self, String> {
+        let audio_subsystem = sdl_context.audio()?;
+        let desired = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: Some(DEVICE_BUFFER_SAMPLES),
+        };
+
+        let ring = SampleRing::new(samples_per_frame() * MAX_QUEUED_FRAMES);
+        let callback_ring = ring.clone();
+
+        let device = audio_subsystem.open_playback(None, &desired, |_spec| RingCallback {
+            ring: callback_ring,
+        })?;
+
+        let sample_rate = device.spec().freq as u32;
+        let samples_per_frame = sample_rate as usize / FRAMES_PER_SECOND;
+        let target = samples_per_frame * PREFILL_FRAMES;
+
+        ring.push(&vec![0.0; target]);
+        device.resume();
+
+        Ok(Self {
+            _device: device,
+            ring,
+            frame: Vec::with_capacity(samples_per_frame * 2),
+            samples_per_frame,
+            sample_rate,
+            drift: DriftController::new(target, MAX_DRIFT_CORRECTION),
+        })
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    // generate one frame worth of audio, call once per emulated frame. returns what was queued
+    pub fn push_frame(&mut self, chip8: &mut Chip8) -> &[f32] {
+        let samples = self.drift.samples_for_frame(self.samples_per_frame, self.ring.len());
+
+        self.frame.resize(samples, 0.0);
+        chip8.fill_audio(&mut self.frame, self.sample_rate);
+
+        self.ring.push(&self.frame);
+        &self.frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pops_in_order() {
+        let ring = SampleRing::new(8);
+        ring.push(&[0.1, 0.2, 0.3]);
+
+        let mut out = [0.0; 2];
+        assert_eq!(ring.pop_into(&mut out), 2);
+        assert_eq!(out, [0.1, 0.2]);
+        assert_eq!(ring.pop_into(&mut out), 1);
+        assert_eq!(out, [0.3, 0.0]);
+    }
+
+    #[test]
+    fn pads_underruns_with_silence() {
+        let ring = SampleRing::new(8);
+        ring.push(&[0.5]);
+
+        let mut out = [1.0; 4];
+        assert_eq!(ring.pop_into(&mut out), 1);
+        assert_eq!(out, [0.5, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn drops_oldest_samples_on_overflow() {
+        let ring = SampleRing::new(3);
+        ring.push(&[0.1, 0.2]);
+        ring.push(&[0.3, 0.4]);
+
+        let mut out = [0.0; 3];
+        ring.pop_into(&mut out);
+        assert_eq!(out, [0.2, 0.3, 0.4]);
+    }
+}
@@ -0,0 +1,347 @@
+use std::collections::BTreeMap;
+
+use sdl2::keyboard::Keycode;
+
+// SDL's own key names, so they match what SDL_GetKeyName shows in other tools
+const KEY_NAMES: &[(&str, Keycode)] = &[
+    ("0", Keycode::Num0), ("1", Keycode::Num1), ("2", Keycode::Num2), ("3", Keycode::Num3),
+    ("4", Keycode::Num4), ("5", Keycode::Num5), ("6", Keycode::Num6), ("7", Keycode::Num7),
+    ("8", Keycode::Num8), ("9", Keycode::Num9),
+    ("A", Keycode::A), ("B", Keycode::B), ("C", Keycode::C), ("D", Keycode::D), ("E", Keycode::E),
+    ("F", Keycode::F), ("G", Keycode::G), ("H", Keycode::H), ("I", Keycode::I), ("J", Keycode::J),
+    ("K", Keycode::K), ("L", Keycode::L), ("M", Keycode::M), ("N", Keycode::N), ("O", Keycode::O),
+    ("P", Keycode::P), ("Q", Keycode::Q), ("R", Keycode::R), ("S", Keycode::S), ("T", Keycode::T),
+    ("U", Keycode::U), ("V", Keycode::V), ("W", Keycode::W), ("X", Keycode::X), ("Y", Keycode::Y),
+    ("Z", Keycode::Z),
+    ("F1", Keycode::F1), ("F2", Keycode::F2), ("F3", Keycode::F3), ("F4", Keycode::F4),
+    ("F5", Keycode::F5), ("F6", Keycode::F6), ("F7", Keycode::F7), ("F8", Keycode::F8),
+    ("F9", Keycode::F9), ("F10", Keycode::F10), ("F11", Keycode::F11), ("F12", Keycode::F12),
+    ("Keypad 0", Keycode::Kp0), ("Keypad 1", Keycode::Kp1), ("Keypad 2", Keycode::Kp2),
+    ("Keypad 3", Keycode::Kp3), ("Keypad 4", Keycode::Kp4), ("Keypad 5", Keycode::Kp5),
+    ("Keypad 6", Keycode::Kp6), ("Keypad 7", Keycode::Kp7), ("Keypad 8", Keycode::Kp8),
+    ("Keypad 9", Keycode::Kp9), ("Keypad +", Keycode::KpPlus), ("Keypad -", Keycode::KpMinus),
+    ("Keypad *", Keycode::KpMultiply), ("Keypad /", Keycode::KpDivide),
+    ("Keypad Enter", Keycode::KpEnter), ("Keypad .", Keycode::KpPeriod),
+    ("Space", Keycode::Space), ("Return", Keycode::Return), ("Tab", Keycode::Tab),
+    ("Backspace", Keycode::Backspace), ("Escape", Keycode::Escape), ("Delete", Keycode::Delete),
+    ("Insert", Keycode::Insert), ("Home", Keycode::Home), ("End", Keycode::End),
+    ("PageUp", Keycode::PageUp), ("PageDown", Keycode::PageDown),
+    ("Up", Keycode::Up), ("Down", Keycode::Down), ("Left", Keycode::Left), ("Right", Keycode::Right),
+    (",", Keycode::Comma), (".", Keycode::Period), ("/", Keycode::Slash), (";", Keycode::Semicolon),
+    ("'", Keycode::Quote), ("[", Keycode::LeftBracket), ("]", Keycode::RightBracket),
+    ("-", Keycode::Minus), ("=", Keycode::Equals), ("`", Keycode::Backquote),
+    ("\\", Keycode::Backslash),
+    ("Left Shift", Keycode::LShift), ("Right Shift", Keycode::RShift),
+    ("Left Ctrl", Keycode::LCtrl), ("Right Ctrl", Keycode::RCtrl),
+    ("Left Alt", Keycode::LAlt), ("Right Alt", Keycode::RAlt),
+];
+
+// the classic layout: the 4x4 block under 1234 stands in for the COSMAC VIP hex keypad
+const DEFAULT_KEYPAD: [Keycode; 16] = [
+    Keycode::X, Keycode::Num1, Keycode::Num2, Keycode::Num3,
+    Keycode::Q, Keycode::W, Keycode::E, Keycode::A,
+    Keycode::S, Keycode::D, Keycode::Z, Keycode::C,
+    Keycode::Num4, Keycode::R, Keycode::F, Keycode::V,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Action {
+    Quit,
+    Reset,
+    Pause,
+    FrameAdvance,
+    Step,
+    SaveState,
+    LoadState,
+    Turbo,
+    Rewind,
+    Screenshot,
+    RecordGif,
+    CyclePalette,
+    ToggleCrt,
+    ToggleOverlay,
+    ToggleGrid,
+    ToggleZoom,
+    ToggleKeypad,
+    ToggleHeatmap,
+    SpeedUp,
+    SpeedDown,
+    SpeedReset,
+    SaveReplay,
+    DumpState,
+    QuirkMenu,
+}
+
+impl Action {
+    // actions that change the emulation, during netplay they'd only happen on one side
+    pub fn is_emulation_changing(self) -> bool {
+        matches!(
+            self,
+            Action::Reset
+                | Action::Pause
+                | Action::FrameAdvance
+                | Action::Step
+                | Action::LoadState
+                | Action::Turbo
+                | Action::Rewind
+                | Action::SpeedUp
+                | Action::SpeedDown
+                | Action::SpeedReset
+                | Action::QuirkMenu
+        )
+    }
+}
+
+// an action listed twice gets both keys by default, rebinding it replaces both
+const ACTIONS: &[(&str, Action, Keycode)] = &[
+    ("quit", Action::Quit, Keycode::Escape),
+    ("reset", Action::Reset, Keycode::N),
+    ("pause", Action::Pause, Keycode::P),
+    ("frame_advance", Action::FrameAdvance, Keycode::Period),
+    ("frame_advance", Action::FrameAdvance, Keycode::F10),
+    ("step", Action::Step, Keycode::Comma),
+    ("save_state", Action::SaveState, Keycode::F5),
+    ("load_state", Action::LoadState, Keycode::F9),
+    ("turbo", Action::Turbo, Keycode::Tab),
+    ("rewind", Action::Rewind, Keycode::Backspace),
+    ("screenshot", Action::Screenshot, Keycode::F12),
+    ("record_gif", Action::RecordGif, Keycode::F11),
+    ("save_replay", Action::SaveReplay, Keycode::F7),
+    ("dump_state", Action::DumpState, Keycode::Insert),
+    ("quirk_menu", Action::QuirkMenu, Keycode::Home),
+    ("cycle_palette", Action::CyclePalette, Keycode::F6),
+    ("toggle_crt", Action::ToggleCrt, Keycode::F8),
+    ("toggle_overlay", Action::ToggleOverlay, Keycode::F1),
+    ("toggle_grid", Action::ToggleGrid, Keycode::F2),
+    ("toggle_zoom", Action::ToggleZoom, Keycode::F3),
+    ("toggle_keypad", Action::ToggleKeypad, Keycode::F4),
+    ("toggle_heatmap", Action::ToggleHeatmap, Keycode::End),
+    ("speed_up", Action::SpeedUp, Keycode::Equals),
+    ("speed_up", Action::SpeedUp, Keycode::KpPlus),
+    ("speed_down", Action::SpeedDown, Keycode::Minus),
+    ("speed_down", Action::SpeedDown, Keycode::KpMinus),
+    ("speed_reset", Action::SpeedReset, Keycode::Num0),
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyBindings {
+    keypad: [Keycode; 16],
+    actions: Vec<(Action, Keycode)>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            keypad: DEFAULT_KEYPAD,
+            actions: ACTIONS.iter().map(|&(_, action, key)| (action, key)).collect(),
+        }
+    }
+}
+
+pub fn key_from_name(name: &str) -> Option<Keycode> {
+    KEY_NAMES
+        .iter()
+        .find(|(key_name, _)| key_name.eq_ignore_ascii_case(name.trim()))
+        .map(|&(_, key)| key)
+}
+
+pub fn key_name(key: Keycode) -> &'static str {
+    KEY_NAMES
+        .iter()
+        .find(|&&(_, known)| known == key)
+        .map_or("?", |&(name, _)| name)
+}
+
+#[cfg(any(feature = "tui", feature = "terminal"))]
+fn key_char(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c.to_ascii_lowercase()),
+        _ if name == "Space" => Some(' '),
+        _ => None,
+    }
+}
+
+impl KeyBindings {
+    // `keys` maps hex digits 0-F to key names, `actions` maps action names to key names,
+    // anything left out keeps its default
+    pub fn from_config(
+        keys: &BTreeMap<String, String>,
+        actions: &BTreeMap<String, String>,
+    ) -> Result<Self, String> {
+        let mut bindings = Self::default();
+
+        for (digit, name) in keys {
+            let index = u8::from_str_radix(digit, 16)
+                .ok()
+                .filter(|&index| digit.len() == 1 && index < 16)
+                .ok_or_else(|| format!("`keys.{}` isn't a CHIP-8 key, use 0-F", digit))?;
+
+            bindings.keypad[index as usize] = parse_key(&format!("keys.{}", digit), name)?;
+        }
+
+        for (action_name, name) in actions {
+            let action = ACTIONS
+                .iter()
+                .find(|(known, _, _)| known == action_name)
+                .map(|&(_, action, _)| action)
+                .ok_or_else(|| {
+                    let mut known: Vec<&str> = ACTIONS.iter().map(|(name, _, _)| *name).collect();
+                    known.dedup();
+                    format!("`actions.{}` isn't an action, use one of {}", action_name, known.join(", "))
+                })?;
+
+            let key = parse_key(&format!("actions.{}", action_name), name)?;
+
+            bindings.actions.retain(|&(bound, _)| bound != action);
+            bindings.actions.push((action, key));
+        }
+
+        bindings.check_duplicates()?;
+
+        Ok(bindings)
+    }
+
+    // key names for 0-F, for the keypad overlay and frontends that don't speak SDL keycodes
+    pub fn keypad_names(&self) -> [&'static str; 16] {
+        self.keypad.map(key_name)
+    }
+
+    #[cfg(any(feature = "minifb", feature = "pixels"))]
+    pub fn action_names(&self) -> Vec<(Action, &'static str)> {
+        self.actions.iter().map(|&(action, key)| (action, key_name(key))).collect()
+    }
+
+    // the characters terminal frontends see for 0-F, keys that don't type one can't be used there
+    #[cfg(any(feature = "tui", feature = "terminal"))]
+    pub fn keypad_chars(&self) -> [Option<char>; 16] {
+        self.keypad.map(|key| key_char(key_name(key)))
+    }
+
+    pub fn button(&self, key: Keycode) -> Option<usize> {
+        self.keypad.iter().position(|&bound| bound == key)
+    }
+
+    pub fn action(&self, key: Keycode) -> Option<Action> {
+        self.actions
+            .iter()
+            .find(|&&(_, bound)| bound == key)
+            .map(|&(action, _)| action)
+    }
+
+    fn check_duplicates(&self) -> Result<(), String> {
+        let mut seen: BTreeMap<i32, String> = BTreeMap::new();
+        let keypad = self.keypad.iter().enumerate().map(|(i, &key)| (format!("key {:X}", i), key));
+        let actions = self.actions.iter().map(|&(action, key)| (action_name(action).to_string(), key));
+
+        for (target, key) in keypad.chain(actions) {
+            if let Some(other) = seen.insert(key as i32, target.clone()) {
+                return Err(format!(
+                    "{} is bound to both {} and {}, rebind one of them",
+                    key_name(key),
+                    other,
+                    target
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn action_name(action: Action) -> &'static str {
+    ACTIONS
+        .iter()
+        .find(|&&(_, known, _)| known == action)
+        .map_or("?", |&(name, _, _)| name)
+}
+
+fn parse_key(setting: &str, name: &str) -> Result<Keycode, String> {
+    key_from_name(name).ok_or_else(|| format!("`{}`: unknown key name `{}`", setting, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|&(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn defaults_match_the_classic_layout() {
+        let bindings = KeyBindings::default();
+
+        assert_eq!(bindings.button(Keycode::Num1), Some(0x1));
+        assert_eq!(bindings.button(Keycode::Num4), Some(0xC));
+        assert_eq!(bindings.button(Keycode::X), Some(0x0));
+        assert_eq!(bindings.button(Keycode::V), Some(0xF));
+        assert_eq!(bindings.action(Keycode::Escape), Some(Action::Quit));
+        assert_eq!(bindings.action(Keycode::N), Some(Action::Reset));
+        assert_eq!(bindings.action(Keycode::Period), Some(Action::FrameAdvance));
+        assert_eq!(bindings.action(Keycode::F10), Some(Action::FrameAdvance));
+        assert_eq!(bindings.action(Keycode::Equals), Some(Action::SpeedUp));
+        assert_eq!(bindings.action(Keycode::KpMinus), Some(Action::SpeedDown));
+        assert_eq!(bindings.action(Keycode::Num0), Some(Action::SpeedReset));
+        assert_eq!(bindings.action(Keycode::F2), Some(Action::ToggleGrid));
+        assert_eq!(bindings.action(Keycode::F3), Some(Action::ToggleZoom));
+        assert_eq!(bindings.action(Keycode::F4), Some(Action::ToggleKeypad));
+        assert_eq!(bindings.action(Keycode::End), Some(Action::ToggleHeatmap));
+        assert_eq!(bindings.action(Keycode::Insert), Some(Action::DumpState));
+        assert_eq!(bindings.action(Keycode::Home), Some(Action::QuirkMenu));
+    }
+
+    #[test]
+    fn remaps_keys_and_actions() {
+        let keys = section(&[("5", "Up"), ("8", "Keypad 5"), ("a", "space")]);
+        let actions = section(&[("pause", "Return"), ("frame_advance", "Right")]);
+        let bindings = KeyBindings::from_config(&keys, &actions).unwrap();
+
+        assert_eq!(bindings.button(Keycode::Up), Some(0x5));
+        assert_eq!(bindings.button(Keycode::Kp5), Some(0x8));
+        assert_eq!(bindings.button(Keycode::Space), Some(0xA));
+        assert_eq!(bindings.button(Keycode::W), None);
+        assert_eq!(bindings.button(Keycode::Q), Some(0x4));
+        assert_eq!(bindings.action(Keycode::Return), Some(Action::Pause));
+        assert_eq!(bindings.action(Keycode::P), None);
+        assert_eq!(bindings.action(Keycode::Right), Some(Action::FrameAdvance));
+        assert_eq!(bindings.action(Keycode::F10), None);
+    }
+
+    #[cfg(any(feature = "tui", feature = "terminal"))]
+    #[test]
+    fn terminals_only_see_character_keys() {
+        let keys = section(&[("0", "Space"), ("1", "Keypad 1"), ("2", "'")]);
+        let chars = KeyBindings::from_config(&keys, &section(&[])).unwrap().keypad_chars();
+
+        assert_eq!(chars[0], Some(' '));
+        assert_eq!(chars[1], None);
+        assert_eq!(chars[2], Some('\''));
+        assert_eq!(chars[0xF], Some('v'));
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        let error = KeyBindings::from_config(&section(&[("5", "Hyper")]), &section(&[])).unwrap_err();
+        assert!(error.contains("keys.5") && error.contains("Hyper"), "{}", error);
+
+        let error = KeyBindings::from_config(&section(&[("G", "Up")]), &section(&[])).unwrap_err();
+        assert!(error.contains("keys.G"), "{}", error);
+
+        let error = KeyBindings::from_config(&section(&[]), &section(&[("jump", "Up")])).unwrap_err();
+        assert!(error.contains("actions.jump"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_conflicting_bindings() {
+        let error = KeyBindings::from_config(&section(&[("1", "Q")]), &section(&[])).unwrap_err();
+        assert!(error.contains("key 1") && error.contains("key 4"), "{}", error);
+
+        let error = KeyBindings::from_config(&section(&[]), &section(&[("turbo", "P")])).unwrap_err();
+        assert!(error.contains("pause") && error.contains("turbo"), "{}", error);
+    }
+}
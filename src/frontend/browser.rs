@@ -0,0 +1,260 @@
+// picking a ROM from a directory: what's listed, what's selected, and a preview of each one
+
+use super::title;
+
+use chip8_emu::headless::{self, Options, Script};
+#[cfg(feature = "scripting")]
+use chip8_emu::report::Expectations;
+#[cfg(feature = "scripting")]
+use chip8_emu::scripting::{self, ScriptHost};
+
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+pub const ROM_EXTENSIONS: &[&str] = &["ch8"];
+// a second of play is enough for most ROMs to get past a blank screen
+pub const THUMBNAIL_FRAMES: usize = 60;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RomEntry {
+    pub path: PathBuf,
+    pub title: String,
+}
+
+pub fn is_rom_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| ROM_EXTENSIONS.iter().any(|known| extension.eq_ignore_ascii_case(known)))
+}
+
+// the ROMs directly inside `dir`, by title
+pub fn scan(dir: &Path) -> Result<Vec<RomEntry>, String> {
+    let read = fs::read_dir(dir).map_err(|e| format!("Unable to read {}: {}", dir.display(), e))?;
+    let mut entries: Vec<RomEntry> = read
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && is_rom_file(path))
+        .map(|path| RomEntry { title: title::display_name(&path.to_string_lossy()).to_string(), path })
+        .collect();
+
+    if entries.is_empty() {
+        return Err(format!("No ROMs in {}, looking for .{} files", dir.display(), ROM_EXTENSIONS.join(", .")));
+    }
+
+    entries.sort_by_cached_key(|entry| (entry.title.to_lowercase(), entry.path.clone()));
+    Ok(entries)
+}
+
+// the screen after THUMBNAIL_FRAMES frames, None if the ROM crashes before then
+pub fn thumbnail(rom: &[u8], ticks_per_frame: usize) -> Option<Vec<bool>> {
+    headless::run(rom, &thumbnail_options(ticks_per_frame)).ok().map(|chip8| chip8.get_display().to_vec())
+}
+
+// thumbnail with a script playing along, to get a ROM past a title screen. None if either fails
+#[cfg(feature = "scripting")]
+pub fn scripted_thumbnail(rom: &[u8], ticks_per_frame: usize, source: &str) -> Option<Vec<bool>> {
+    let mut host = ScriptHost::new(source).ok()?;
+    let (chip8, report) = scripting::run(rom, &thumbnail_options(ticks_per_frame), &Expectations::default(), &mut host);
+
+    report.is_pass().then(|| chip8.get_display().to_vec())
+}
+
+fn thumbnail_options(ticks_per_frame: usize) -> Options {
+    Options { frames: THUMBNAIL_FRAMES, ticks_per_frame, seed: headless::DEFAULT_SEED, script: Script::default() }
+}
+
+pub struct Browser {
+    entries: Vec<RomEntry>,
+    selected: usize,
+    // made when a ROM is first selected, None for ones that can't be read or crash
+    thumbnails: HashMap<PathBuf, Option<Vec<bool>>>,
+    // why the selected ROM couldn't be opened, until the selection moves
+    error: Option<String>,
+}
+
+impl Browser {
+    pub fn new(entries: Vec<RomEntry>) -> Self {
+        assert!(!entries.is_empty(), "a browser needs at least one ROM");
+
+        Self { entries, selected: 0, thumbnails: HashMap::new(), error: None }
+    }
+
+    pub fn entries(&self) -> &[RomEntry] {
+        &self.entries
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected(&self) -> &RomEntry {
+        &self.entries[self.selected]
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn set_error(&mut self, message: String) {
+        self.error = Some(message);
+    }
+
+    // stops at the ends of the list
+    pub fn move_by(&mut self, rows: isize) {
+        let last = self.entries.len() - 1;
+        self.selected = self.selected.saturating_add_signed(rows).min(last);
+        self.error = None;
+    }
+
+    // the entries to show in `rows` lines, scrolled to keep the selection in the middle
+    pub fn visible(&self, rows: usize) -> Range<usize> {
+        let rows = rows.max(1).min(self.entries.len());
+        let start = self.selected.saturating_sub(rows / 2).min(self.entries.len() - rows);
+
+        start..start + rows
+    }
+
+    pub fn thumbnail(&mut self, ticks_per_frame: usize) -> Option<&[bool]> {
+        let path = &self.entries[self.selected].path;
+
+        self.thumbnails
+            .entry(path.clone())
+            .or_insert_with(|| {
+                let rom = fs::read(path).ok()?;
+
+                // game.rhai next to game.ch8
+                #[cfg(feature = "scripting")]
+                if let Ok(source) = fs::read_to_string(path.with_extension("rhai")) {
+                    return scripted_thumbnail(&rom, ticks_per_frame, &source);
+                }
+
+                thumbnail(&rom, ticks_per_frame)
+            })
+            .as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+
+    fn entries(titles: &[&str]) -> Vec<RomEntry> {
+        titles
+            .iter()
+            .map(|title| RomEntry { path: PathBuf::from(format!("{}.ch8", title)), title: title.to_string() })
+            .collect()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("chip8-emu-browser-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn lists_roms_by_title() {
+        let dir = temp_dir("scan");
+        for name in ["pong.ch8", "Brix.CH8", "notes.txt", "airplane.ch8"] {
+            fs::write(dir.join(name), [0x12, 0x00]).unwrap();
+        }
+        fs::create_dir(dir.join("more.ch8")).unwrap();
+
+        let titles: Vec<String> = scan(&dir).unwrap().into_iter().map(|entry| entry.title).collect();
+
+        assert_eq!(titles, ["airplane", "Brix", "pong"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn an_empty_directory_is_an_error() {
+        let dir = temp_dir("empty");
+
+        assert!(scan(&dir).unwrap_err().starts_with("No ROMs in"));
+        assert!(scan(&dir.join("missing")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn selection_stops_at_the_ends() {
+        let mut browser = Browser::new(entries(&["a", "b", "c"]));
+
+        browser.move_by(-1);
+        assert_eq!(browser.selected_index(), 0);
+        browser.move_by(1);
+        assert_eq!(browser.selected().title, "b");
+        browser.move_by(10);
+        assert_eq!(browser.selected_index(), 2);
+    }
+
+    #[test]
+    fn an_error_lasts_until_the_selection_moves() {
+        let mut browser = Browser::new(entries(&["a", "b"]));
+
+        browser.set_error("Unable to load a.ch8: the ROM is empty".to_string());
+        assert_eq!(browser.error(), Some("Unable to load a.ch8: the ROM is empty"));
+        browser.move_by(1);
+        assert_eq!(browser.error(), None);
+    }
+
+    #[test]
+    fn scrolls_to_keep_the_selection_visible() {
+        let mut browser = Browser::new(entries(&["a", "b", "c", "d", "e", "f", "g", "h"]));
+
+        assert_eq!(browser.visible(4), 0..4);
+        browser.move_by(4);
+        assert_eq!(browser.visible(4), 2..6);
+        browser.move_by(10);
+        assert_eq!(browser.visible(4), 4..8);
+        assert_eq!(browser.visible(20), 0..8);
+    }
+
+    #[test]
+    fn previews_the_first_second() {
+        // draw "0" at (0, 0) then loop
+        let screen = thumbnail(&[0xD0, 0x05, 0x12, 0x02], 10).unwrap();
+        assert!(screen[0] && screen[3] && !screen[4]);
+
+        // return with an empty stack
+        assert_eq!(thumbnail(&[0x00, 0xEE], 10), None);
+    }
+
+    #[test]
+    fn caches_thumbnails() {
+        let dir = temp_dir("thumbnails");
+        fs::write(dir.join("draw.ch8"), [0xD0, 0x05, 0x12, 0x02]).unwrap();
+        fs::write(dir.join("empty.ch8"), []).unwrap();
+        let mut browser = Browser::new(scan(&dir).unwrap());
+
+        assert!(browser.thumbnail(10).is_some());
+        fs::remove_file(dir.join("draw.ch8")).unwrap();
+        assert!(browser.thumbnail(10).is_some());
+
+        browser.move_by(1);
+        assert!(browser.thumbnail(10).is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "scripting")]
+    #[test]
+    fn plays_a_script_next_to_the_rom() {
+        let dir = temp_dir("scripted");
+        // wait for a key, then draw it
+        fs::write(dir.join("key.ch8"), [0xF0, 0x0A, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x06]).unwrap();
+        fs::write(dir.join("key.rhai"), "fn on_frame(chip) { if chip.frame == 2 { chip.press(1) } else { chip.release(1) } }").unwrap();
+        let mut browser = Browser::new(scan(&dir).unwrap());
+
+        // "1" has its top row one pixel in
+        let screen = browser.thumbnail(10).unwrap();
+        assert!(!screen[0] && screen[2]);
+
+        assert_eq!(scripted_thumbnail(&[0x12, 0x00], 10, "throw 1"), None);
+        assert_eq!(scripted_thumbnail(&[0x12, 0x00], 10, "fn on_frame(chip) { throw 1 }"), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
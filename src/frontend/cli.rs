@@ -0,0 +1,1515 @@
+use super::attract;
+use super::bindings::KeyBindings;
+use super::crt;
+use super::screenshot;
+use super::video;
+
+use chip8_emu::lockstep;
+use chip8_emu::report::HaltReason;
+use chip8_emu::trace_log::{self, TraceFilter, TraceFormat};
+use chip8_emu::{headless, BeepConfig, Palette, Quirk, Quirks, Waveform};
+
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
+
+pub const DEFAULT_SCALE: u32 = 20;
+pub const DEFAULT_TICKS_PER_FRAME: usize = 10;
+pub const DEFAULT_TURBO_SPEED: u32 = 8;
+
+const FRAMES_PER_SECOND: usize = 60;
+// the 4 KB a breakpoint can be set in
+const MEMORY_SIZE: usize = 0x1000;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "chip8-emu",
+    version,
+    about = "A CHIP-8 emulator",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+
+    /// Write a commented config file template (to the default config path if none is given)
+    #[arg(long, value_name = "PATH", num_args = 0..=1, exclusive = true)]
+    write_default_config: Option<Option<PathBuf>>,
+
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum CliCommand {
+    /// Play a ROM (the default when no subcommand is given)
+    Run(Box<RunArgs>),
+    /// Play a ROM with the debugger panel beside it
+    #[cfg(feature = "egui")]
+    Debug(Box<RunArgs>),
+    /// Debug a ROM in the terminal
+    #[cfg(feature = "tui")]
+    Tui(Box<RunArgs>),
+    /// List the speed, palette and quirks saved for each ROM
+    Overrides,
+    /// Run a ROM without a window or audio, for scripted checks and CI
+    Headless(HeadlessArgs),
+    /// Measure emulation speed with the bundled benchmark ROM or your own
+    Bench {
+        /// ROM file to measure instead of the bundled benchmark
+        rom: Option<String>,
+        /// Seconds to measure each configuration for, after a short warmup
+        #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u64).range(1..))]
+        seconds: u64,
+        /// Print the results as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Run the Timendus test suite headlessly and print a pass/fail table
+    Conformance {
+        /// Directory containing the suite's .ch8 files
+        dir: PathBuf,
+        /// Print golden hashes for the current screens instead of checking them
+        #[arg(long)]
+        bless: bool,
+    },
+    /// Write the state before each instruction as JSON lines, to compare with other emulators
+    ExportVectors {
+        #[command(flatten)]
+        run: VectorArgs,
+        /// Instructions to record
+        #[arg(long, default_value_t = 1000)]
+        instructions: usize,
+        /// File to write instead of stdout
+        #[arg(long, short, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Replay a ROM against a test vector file and report the first line that differs
+    VerifyVectors {
+        #[command(flatten)]
+        run: VectorArgs,
+        /// JSON lines file from export-vectors or another emulator
+        vectors: PathBuf,
+    },
+    /// Run every ROM in a directory headlessly and compare the results with a saved baseline
+    Sweep(SweepArgs),
+    /// Run a ROM twice side by side and report the first frame where the two runs differ
+    Audit(AuditArgs),
+    /// Write the screen at chosen frames as golden PBM and PNG fixtures, with a manifest of the run
+    Snapshot(SnapshotArgs),
+    /// List the code a ROM reaches without running it, or with --lints the pitfalls in it
+    Analyze {
+        /// ROM file to analyze
+        rom: String,
+        /// Print common mistakes instead of the listing, one a line. Exits with 1 if there are any
+        #[arg(long)]
+        lints: bool,
+    },
+    /// Write every instruction a headless run executes to a file, filtered and capped in size
+    Trace(TraceArgs),
+    /// Run a ROM on two quirk configurations in lockstep and report the first instruction they differ at
+    Compare(CompareArgs),
+}
+
+// the run both sides of a test vector file have to agree on
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct VectorArgs {
+    /// ROM file to run
+    pub rom: String,
+
+    /// Seed for RND, 0 unless given
+    #[arg(long, value_parser = parse_seed, default_value_t = headless::DEFAULT_SEED)]
+    pub seed: u64,
+
+    /// Instructions between timer ticks
+    #[arg(long, value_name = "SPEED", value_parser = parse_speed, default_value_t = DEFAULT_TICKS_PER_FRAME)]
+    pub speed: usize,
+}
+
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct SweepArgs {
+    /// Directory of .ch8 files to run
+    pub dir: PathBuf,
+
+    /// Frames to run each ROM for, 60 per emulated second
+    #[arg(long, default_value_t = 1200)]
+    pub frames: usize,
+
+    /// Seed for RND, the same for every ROM
+    #[arg(long, value_parser = parse_seed, default_value_t = headless::DEFAULT_SEED)]
+    pub seed: u64,
+
+    /// Emulation speed, instructions per frame (e.g. 10) or per second (e.g. 700ips)
+    #[arg(long, value_name = "SPEED", value_parser = parse_speed, default_value_t = DEFAULT_TICKS_PER_FRAME)]
+    pub speed: usize,
+
+    /// Results to compare against, written instead when the file doesn't exist yet
+    #[arg(long, value_name = "PATH")]
+    pub baseline: Option<PathBuf>,
+
+    /// Overwrite the baseline with this sweep's results instead of comparing
+    #[arg(long, requires = "baseline")]
+    pub bless: bool,
+}
+
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct AuditArgs {
+    /// ROM file to run
+    pub rom: String,
+
+    /// Frames to compare, 60 per emulated second
+    #[arg(long, default_value_t = 600)]
+    pub frames: usize,
+
+    /// Seed for RND, e.g. 42 or 0xDEADBEEF. Defaults to the input script's, then 0
+    #[arg(long, value_parser = parse_seed)]
+    pub seed: Option<u64>,
+
+    /// Emulation speed, instructions per frame (e.g. 10) or per second (e.g. 700ips)
+    #[arg(long, value_name = "SPEED", value_parser = parse_speed, default_value_t = DEFAULT_TICKS_PER_FRAME)]
+    pub speed: usize,
+
+    /// Input to play back in both runs, as headless --input-script reads it
+    #[arg(long, value_name = "PATH")]
+    pub input_script: Option<PathBuf>,
+
+    /// Run once and write the state hash after every frame to PATH, for --against to check later
+    #[arg(long, value_name = "PATH")]
+    pub record: Option<PathBuf>,
+
+    /// Run once and compare with a log --record wrote, instead of running twice
+    #[arg(long, value_name = "PATH", conflicts_with = "record")]
+    pub against: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotArgs {
+    /// ROM file to run
+    pub rom: String,
+
+    /// Frames to write the screen after, e.g. 120,300,600. 0 is the screen before the first frame
+    #[arg(long, value_name = "FRAMES", value_delimiter = ',', required = true)]
+    pub at: Vec<usize>,
+
+    /// Directory to write the fixtures and manifest.json to, created when missing
+    #[arg(long, value_name = "DIR")]
+    pub out: PathBuf,
+
+    /// Seed for RND, e.g. 42 or 0xDEADBEEF. Defaults to the input script's, then 0
+    #[arg(long, value_parser = parse_seed)]
+    pub seed: Option<u64>,
+
+    /// Emulation speed, instructions per frame (e.g. 10) or per second (e.g. 700ips)
+    #[arg(long, value_name = "SPEED", value_parser = parse_speed, default_value_t = DEFAULT_TICKS_PER_FRAME)]
+    pub speed: usize,
+
+    /// Input to play back, as headless --input-script reads it
+    #[arg(long, value_name = "PATH")]
+    pub input_script: Option<PathBuf>,
+
+    /// Size of each CHIP-8 pixel in the PNGs
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..=64))]
+    pub png_scale: u32,
+
+    /// Replace fixtures already in the directory
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct TraceArgs {
+    /// ROM file to run
+    pub rom: String,
+
+    /// Frames to trace, 60 per emulated second
+    #[arg(long, default_value_t = 300)]
+    pub frames: usize,
+
+    /// Seed for RND, e.g. 42 or 0xDEADBEEF. Defaults to the input script's, then 0
+    #[arg(long, value_parser = parse_seed)]
+    pub seed: Option<u64>,
+
+    /// Emulation speed, instructions per frame (e.g. 10) or per second (e.g. 700ips)
+    #[arg(long, value_name = "SPEED", value_parser = parse_speed, default_value_t = DEFAULT_TICKS_PER_FRAME)]
+    pub speed: usize,
+
+    /// Input to play back, as headless --input-script reads it
+    #[arg(long, value_name = "PATH")]
+    pub input_script: Option<PathBuf>,
+
+    /// A line per instruction, or a JSON object per instruction
+    #[arg(long, value_enum, default_value_t = TraceFormatArg::Text)]
+    pub format: TraceFormatArg,
+
+    /// File to write the trace to
+    #[arg(long, value_name = "PATH")]
+    pub out: PathBuf,
+
+    /// Only trace pc=200-2FF, op=DXYN or frames=60-120. Filters of one kind are alternatives
+    #[arg(long, value_name = "FILTER", value_parser = parse_trace_filter)]
+    pub filter: Vec<TraceFilter>,
+
+    /// Stop writing once the trace reaches this size, e.g. 500K or 64M
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, default_value_t = trace_log::DEFAULT_MAX_BYTES)]
+    pub max_bytes: u64,
+}
+
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct CompareArgs {
+    /// ROM file to run
+    pub rom: String,
+
+    /// The first configuration, a preset and quirks on top, e.g. preset:chip8 or preset:schip,shift_uses_vy=on
+    #[arg(long, value_name = "QUIRKS", value_parser = parse_quirk_config)]
+    pub a: QuirkConfig,
+
+    /// The configuration to compare it with
+    #[arg(long, value_name = "QUIRKS", value_parser = parse_quirk_config)]
+    pub b: QuirkConfig,
+
+    /// Frames to compare, 60 per emulated second
+    #[arg(long, default_value_t = 600)]
+    pub frames: usize,
+
+    /// Seed for RND, e.g. 42 or 0xDEADBEEF. Defaults to the input script's, then 0
+    #[arg(long, value_parser = parse_seed)]
+    pub seed: Option<u64>,
+
+    /// Emulation speed, instructions per frame (e.g. 10) or per second (e.g. 700ips)
+    #[arg(long, value_name = "SPEED", value_parser = parse_speed, default_value_t = DEFAULT_TICKS_PER_FRAME)]
+    pub speed: usize,
+
+    /// Input to play back on both, as headless --input-script reads it. Its quirk changes are left out
+    #[arg(long, value_name = "PATH")]
+    pub input_script: Option<PathBuf>,
+}
+
+// a compare configuration, with the text it was given as to name it by
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuirkConfig {
+    pub name: String,
+    pub quirks: Quirks,
+}
+
+// every setting is optional here so later layers (config file, defaults) can tell what was given
+#[derive(Args, Debug, Default)]
+pub struct RunArgs {
+    /// ROM file to play, or an http(s) url when built with the `net` feature
+    // with the `dialog` feature a file picker asks for it instead
+    #[cfg_attr(not(feature = "dialog"), arg(required_unless_present_any = ["playlist", "latency_test"]))]
+    pub rom: Option<String>,
+
+    /// Config file to read instead of $XDG_CONFIG_HOME/chip8-emu/config.toml
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Initial window scale, pixels per CHIP-8 pixel
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=64))]
+    pub scale: Option<u32>,
+
+    /// Emulation speed, instructions per frame (e.g. 10) or per second (e.g. 700ips)
+    #[arg(long, value_name = "SPEED", value_parser = parse_speed)]
+    pub speed: Option<usize>,
+
+    /// Speed multiplier while the turbo key is held
+    #[arg(long, value_name = "FACTOR", value_parser = clap::value_parser!(u32).range(2..=64))]
+    pub turbo: Option<u32>,
+
+    /// Quirk preset for the platform the ROM was written for
+    #[arg(long, value_enum)]
+    pub preset: Option<Preset>,
+
+    /// 8XY6/8XYE shift VY into VX instead of shifting VX in place
+    #[arg(long, value_name = "on|off", value_parser = parse_switch)]
+    pub quirk_shift: Option<bool>,
+
+    /// FX55/FX65 leave I past the last register instead of unchanged
+    #[arg(long, value_name = "on|off", value_parser = parse_switch)]
+    pub quirk_load_store: Option<bool>,
+
+    /// BNNN jumps to XNN + VX instead of NNN + V0
+    #[arg(long, value_name = "on|off", value_parser = parse_switch)]
+    pub quirk_jump: Option<bool>,
+
+    /// Sprites are clipped at the screen edges instead of wrapping
+    #[arg(long, value_name = "on|off", value_parser = parse_switch)]
+    pub quirk_clip: Option<bool>,
+
+    /// 8XY1/8XY2/8XY3 reset VF to 0
+    #[arg(long, value_name = "on|off", value_parser = parse_switch)]
+    pub quirk_vf_reset: Option<bool>,
+
+    /// DXYN waits for the next frame before drawing
+    #[arg(long, value_name = "on|off", value_parser = parse_switch)]
+    pub quirk_display_wait: Option<bool>,
+
+    /// Screen colors: green, white, amber, gameboy, inverted, or two hex colors as background,foreground
+    #[arg(long, value_parser = str::parse::<Palette>)]
+    pub palette: Option<Palette>,
+
+    /// Don't open an audio device
+    #[arg(long, conflicts_with = "volume")]
+    pub mute: bool,
+
+    /// Beep volume, 0-100
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub volume: Option<u8>,
+
+    /// Beep frequency in Hz
+    #[arg(long, value_name = "HZ", value_parser = parse_frequency)]
+    pub beep_frequency: Option<f32>,
+
+    /// Beep waveform
+    #[arg(long, value_enum)]
+    pub waveform: Option<WaveformArg>,
+
+    /// Stretch shorter beeps to this many milliseconds
+    #[arg(long, value_name = "MS")]
+    pub min_beep_ms: Option<f32>,
+
+    /// Record the generated audio to a WAV file, written on exit
+    #[arg(long, value_name = "PATH")]
+    pub record_audio: Option<PathBuf>,
+
+    /// Record video and sound through ffmpeg until the emulator closes, e.g. capture.mkv
+    #[arg(long, value_name = "PATH")]
+    pub record_video: Option<PathBuf>,
+
+    /// The ffmpeg used by --record-video
+    #[arg(long, value_name = "PATH")]
+    pub ffmpeg: Option<PathBuf>,
+
+    /// Record key presses, speed and quirk changes, written on exit and replayed with `headless --input-script`
+    #[arg(long, value_name = "PATH")]
+    pub record_input: Option<PathBuf>,
+
+    /// Write the registers, stack and timers as JSON on exit
+    #[arg(long, value_name = "PATH")]
+    pub dump_state_on_exit: Option<PathBuf>,
+
+    /// Directory screenshots, recordings and state dumps are written to
+    #[arg(long, value_name = "DIR")]
+    pub screenshot_dir: Option<PathBuf>,
+
+    /// Screenshot scale, defaults to the window scale
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=64))]
+    pub screenshot_scale: Option<u32>,
+
+    /// Frame rate of F11 GIF recordings, lower drops frames for smaller files
+    #[arg(long, value_name = "FPS", value_parser = clap::value_parser!(u32).range(1..=60))]
+    pub gif_fps: Option<u32>,
+
+    /// Seconds of play kept for the save replay key (F7) to write as a GIF, 0 turns it off
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u32).range(0..=600))]
+    pub replay_seconds: Option<u32>,
+
+    /// Seed for the random number generator (CXNN), e.g. 42 or 0xDEADBEEF. Random and printed when unset
+    #[arg(long, value_parser = parse_seed)]
+    pub seed: Option<u64>,
+
+    /// Start with emulation paused
+    #[arg(long)]
+    pub start_paused: bool,
+
+    /// Pause before running these addresses, e.g. 0x200,0x2A4 (0x or $ for hex, decimal otherwise)
+    #[arg(long = "break", value_name = "ADDRESSES", value_delimiter = ',', value_parser = parse_breakpoint)]
+    pub breakpoints: Vec<u16>,
+
+    /// Start in fullscreen, Alt+Enter toggles it
+    #[arg(long)]
+    pub fullscreen: bool,
+
+    /// Reload the ROM whenever the file changes, for ROM development
+    #[arg(long)]
+    pub watch: bool,
+
+    /// Attract mode: play the ROMs in a directory, or listed in a file one per line, in turn. A
+    /// recording next to a ROM (game.keys for game.ch8, from --record-input) plays it by itself
+    #[arg(long, value_name = "DIR_OR_FILE", conflicts_with_all = ["rom", "watch", "netplay", "netplay_listen"])]
+    pub playlist: Option<PathBuf>,
+
+    /// How long each playlist ROM plays before the next, e.g. 90s or 2m. A key press hands the game
+    /// to the player until they've left it alone this long
+    #[arg(long, value_name = "TIME", value_parser = parse_rotate, requires = "playlist", conflicts_with = "rom")]
+    pub rotate: Option<Duration>,
+
+    /// Phosphor ghosting, scanlines and soft pixel edges
+    #[arg(long)]
+    pub crt: bool,
+
+    /// How much of the previous frame lingers with --crt, 0.0-1.0
+    #[arg(long, value_name = "FACTOR", value_parser = parse_persistence)]
+    pub crt_persistence: Option<f32>,
+
+    /// Leave out the scanlines with --crt
+    #[arg(long)]
+    pub no_scanlines: bool,
+
+    /// Time how long key presses take to show, with a built-in ROM that inverts the screen on any
+    /// key. Prints min, median and p95 latencies on exit
+    #[arg(long, conflicts_with_all = ["rom", "playlist", "watch", "netplay", "netplay_listen"])]
+    pub latency_test: bool,
+
+    /// Don't wait for vsync, a software limiter keeps presents at 60 per second
+    #[arg(long)]
+    pub no_vsync: bool,
+
+    /// Render as fast as possible, emulation still runs at normal speed
+    #[arg(long)]
+    pub unlock_fps: bool,
+
+    /// Forget the speed and palette saved for this ROM and start from the defaults
+    #[arg(long)]
+    pub forget_overrides: bool,
+
+    /// Play together with another chip8-emu listening at HOST:PORT (experimental)
+    #[arg(long, value_name = "HOST:PORT", conflicts_with = "netplay_listen")]
+    pub netplay: Option<String>,
+
+    /// Wait for another chip8-emu to connect with --netplay and play together (experimental)
+    #[arg(long, value_name = "PORT")]
+    pub netplay_listen: Option<u16>,
+
+    /// Answer JSON control commands on HOST:PORT while playing, see src/remote.rs
+    #[cfg(feature = "remote")]
+    #[arg(long, value_name = "HOST:PORT")]
+    pub remote: Option<String>,
+
+    /// Stream the screen over WebSocket on HOST:PORT and take keys from the viewers
+    #[cfg(feature = "viewer")]
+    #[arg(long, value_name = "HOST:PORT")]
+    pub viewer: Option<String>,
+
+    /// Window library to play in
+    #[cfg(any(feature = "minifb", feature = "pixels"))]
+    #[arg(long, value_enum)]
+    pub backend: Option<Backend>,
+
+    /// Play in this terminal instead of a window
+    #[cfg(feature = "terminal")]
+    #[arg(long, value_name = "STYLE", num_args = 0..=1, require_equals = true, default_missing_value = "blocks")]
+    pub terminal: Option<TerminalStyle>,
+}
+
+// no config file is read, so a run plays out the same on every machine
+#[derive(Args, Debug)]
+pub struct HeadlessArgs {
+    /// ROM file to run
+    pub rom: String,
+
+    /// Frames to run before stopping, 60 per emulated second
+    #[arg(long, default_value_t = 600)]
+    pub frames: usize,
+
+    /// Seed for the random number generator (CXNN), e.g. 42 or 0xDEADBEEF. Defaults to the input script's, then 0
+    #[arg(long, value_parser = parse_seed)]
+    pub seed: Option<u64>,
+
+    /// Emulation speed, instructions per frame (e.g. 10) or per second (e.g. 700ips)
+    #[arg(long, value_name = "SPEED", value_parser = parse_speed, default_value_t = DEFAULT_TICKS_PER_FRAME)]
+    pub speed: usize,
+
+    /// Quirk preset for the platform the ROM was written for
+    #[arg(long, value_enum)]
+    pub preset: Option<Preset>,
+
+    /// Input to play back as --record-input writes it, e.g. `FRAME KEY down|up` (key in hex) or `FRAME speed N` per line
+    #[arg(long, value_name = "PATH")]
+    pub input_script: Option<PathBuf>,
+
+    /// Rhai script with on_frame, on_draw and on_halt callbacks to run alongside the ROM. The run
+    /// fails if it throws or a callback takes too long, and what it prints goes to stderr
+    #[cfg(feature = "scripting")]
+    #[arg(long, value_name = "PATH")]
+    pub script: Option<PathBuf>,
+
+    /// Write the final screen as a PBM image
+    #[arg(long, value_name = "PATH")]
+    pub dump_screen: Option<PathBuf>,
+
+    /// Write the final registers, stack and timers as JSON
+    #[arg(long, value_name = "PATH")]
+    pub dump_state: Option<PathBuf>,
+
+    /// Print the SHA-256 of the final screen
+    #[arg(long)]
+    pub print_hash: bool,
+
+    /// Describe the run for CI: frames, how it ended, the screen hash and the verdict
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    pub report: Option<ReportFormat>,
+
+    /// Write the report here instead of to stdout
+    #[arg(long, value_name = "PATH", requires = "report")]
+    pub report_file: Option<PathBuf>,
+
+    /// Fail unless the final screen has this hash, as --print-hash shows it
+    #[arg(long, value_name = "HASH")]
+    pub expect_hash: Option<String>,
+
+    /// Fail unless the run ends this way: frames, self_jump, key_wait or crash
+    #[arg(long, value_name = "HALT", value_parser = parse_halt)]
+    pub expect_halt: Option<HaltReason>,
+
+    /// Load the ROM then answer JSON control commands on HOST:PORT until the client disconnects,
+    /// instead of running --frames
+    #[cfg(feature = "remote")]
+    #[arg(long, value_name = "HOST:PORT", conflicts_with_all = ["input_script", "report", "expect_hash", "expect_halt"])]
+    pub remote: Option<String>,
+
+    /// Run --frames in real time, streaming the screen over WebSocket on HOST:PORT and taking keys
+    /// from the viewers
+    #[cfg(feature = "viewer")]
+    #[arg(long, value_name = "HOST:PORT", conflicts_with_all = ["input_script", "report", "expect_hash", "expect_halt"])]
+    pub viewer: Option<String>,
+}
+
+impl HeadlessArgs {
+    pub fn quirks(&self) -> Quirks {
+        self.preset.map_or_else(Quirks::default, Preset::quirks)
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    Json,
+}
+
+#[cfg(any(feature = "minifb", feature = "pixels"))]
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// SDL2, with audio, save states, recording and every hotkey
+    #[default]
+    Sdl,
+    /// minifb, a lighter window with the basic hotkeys and no audio
+    #[cfg(feature = "minifb")]
+    Minifb,
+    /// pixels on winit, drawn with wgpu, the basic hotkeys and no audio
+    #[cfg(feature = "pixels")]
+    Pixels,
+}
+
+#[cfg(feature = "terminal")]
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TerminalStyle {
+    /// Two pixels per character with Unicode half blocks
+    Blocks,
+    /// Two pixels per character with plain ASCII, for fonts without block characters
+    Ascii,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Netplay {
+    Connect(String),
+    Listen(u16),
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    Chip8,
+    Schip,
+    Xochip,
+}
+
+impl Preset {
+    pub fn quirks(self) -> Quirks {
+        let name = match self {
+            Preset::Chip8 => "chip8",
+            Preset::Schip => "schip",
+            Preset::Xochip => "xochip",
+        };
+
+        Quirks::preset(name).expect("the library has a preset for each of these")
+    }
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WaveformArg {
+    Square,
+    Triangle,
+    Sine,
+}
+
+impl From<WaveformArg> for Waveform {
+    fn from(waveform: WaveformArg) -> Self {
+        match waveform {
+            WaveformArg::Square => Waveform::Square,
+            WaveformArg::Triangle => Waveform::Triangle,
+            WaveformArg::Sine => Waveform::Sine,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceFormatArg {
+    Text,
+    Json,
+}
+
+impl From<TraceFormatArg> for TraceFormat {
+    fn from(format: TraceFormatArg) -> Self {
+        match format {
+            TraceFormatArg::Text => TraceFormat::Text,
+            TraceFormatArg::Json => TraceFormat::Json,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QuirkOverrides {
+    pub shift: Option<bool>,
+    pub load_store: Option<bool>,
+    pub jump: Option<bool>,
+    pub clip: Option<bool>,
+    pub vf_reset: Option<bool>,
+    pub display_wait: Option<bool>,
+}
+
+impl QuirkOverrides {
+    pub fn set(&mut self, quirk: Quirk, is_enabled: bool) {
+        match quirk {
+            Quirk::ShiftUsesVy => self.shift = Some(is_enabled),
+            Quirk::MemoryIncrementI => self.load_store = Some(is_enabled),
+            Quirk::JumpUsesVx => self.jump = Some(is_enabled),
+            Quirk::ClipSprites => self.clip = Some(is_enabled),
+            Quirk::VfReset => self.vf_reset = Some(is_enabled),
+            Quirk::DisplayWait => self.display_wait = Some(is_enabled),
+        }
+    }
+
+    // `quirks` with whatever's set changed
+    pub fn on(&self, mut quirks: Quirks) -> Quirks {
+        if let Some(shift) = self.shift {
+            quirks.set(Quirk::ShiftUsesVy, shift);
+        }
+
+        if let Some(load_store) = self.load_store {
+            quirks.set(Quirk::MemoryIncrementI, load_store);
+        }
+
+        if let Some(jump) = self.jump {
+            quirks.set(Quirk::JumpUsesVx, jump);
+        }
+
+        if let Some(clip) = self.clip {
+            quirks.set(Quirk::ClipSprites, clip);
+        }
+
+        if let Some(vf_reset) = self.vf_reset {
+            quirks.set(Quirk::VfReset, vf_reset);
+        }
+
+        if let Some(display_wait) = self.display_wait {
+            quirks.set(Quirk::DisplayWait, display_wait);
+        }
+
+        quirks
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    pub rom: String,
+    pub scale: u32,
+    pub ticks_per_frame: usize,
+    // the speed before any saved per-ROM override, what the speed reset key goes back to
+    pub default_ticks_per_frame: usize,
+    pub turbo_speed: u32,
+    pub preset: Option<Preset>,
+    pub quirks: QuirkOverrides,
+    pub palette: Palette,
+    pub is_mute: bool,
+    pub beep: BeepConfig,
+    pub record_audio: Option<PathBuf>,
+    pub record_input: Option<PathBuf>,
+    pub dump_state_on_exit: Option<PathBuf>,
+    pub record_video: Option<PathBuf>,
+    pub ffmpeg: PathBuf,
+    pub screenshot_dir: PathBuf,
+    pub screenshot_scale: Option<u32>,
+    pub gif_fps: u32,
+    pub replay_seconds: u32,
+    pub seed: Option<u64>,
+    pub is_start_paused: bool,
+    pub breakpoints: Vec<u16>,
+    pub is_fullscreen: bool,
+    pub is_watch: bool,
+    pub playlist: Option<PathBuf>,
+    pub rotate: Duration,
+    pub is_crt: bool,
+    pub crt_persistence: f32,
+    pub is_scanlines: bool,
+    pub is_vsync: bool,
+    pub is_unlock_fps: bool,
+    pub is_latency_test: bool,
+    pub netplay: Option<Netplay>,
+    #[cfg(feature = "remote")]
+    pub remote: Option<String>,
+    #[cfg(feature = "viewer")]
+    pub viewer: Option<String>,
+    #[cfg(any(feature = "minifb", feature = "pixels"))]
+    pub backend: Backend,
+    #[cfg(feature = "terminal")]
+    pub terminal: Option<TerminalStyle>,
+    pub bindings: KeyBindings,
+}
+
+impl Config {
+    pub fn new(rom: String) -> Self {
+        Self {
+            rom,
+            scale: DEFAULT_SCALE,
+            ticks_per_frame: DEFAULT_TICKS_PER_FRAME,
+            default_ticks_per_frame: DEFAULT_TICKS_PER_FRAME,
+            turbo_speed: DEFAULT_TURBO_SPEED,
+            preset: None,
+            quirks: QuirkOverrides::default(),
+            palette: Palette::default(),
+            is_mute: false,
+            beep: BeepConfig::default(),
+            record_audio: None,
+            record_input: None,
+            dump_state_on_exit: None,
+            record_video: None,
+            ffmpeg: PathBuf::from(video::DEFAULT_FFMPEG),
+            screenshot_dir: PathBuf::from(screenshot::DEFAULT_DIR),
+            screenshot_scale: None,
+            gif_fps: screenshot::DEFAULT_GIF_FPS,
+            replay_seconds: screenshot::DEFAULT_REPLAY_SECONDS,
+            seed: None,
+            is_start_paused: false,
+            breakpoints: Vec::new(),
+            is_fullscreen: false,
+            is_watch: false,
+            playlist: None,
+            rotate: attract::DEFAULT_ROTATE,
+            is_crt: false,
+            crt_persistence: crt::DEFAULT_PERSISTENCE,
+            is_scanlines: true,
+            is_vsync: true,
+            is_unlock_fps: false,
+            is_latency_test: false,
+            netplay: None,
+            #[cfg(feature = "remote")]
+            remote: None,
+            #[cfg(feature = "viewer")]
+            viewer: None,
+            #[cfg(any(feature = "minifb", feature = "pixels"))]
+            backend: Backend::Sdl,
+            #[cfg(feature = "terminal")]
+            terminal: None,
+            bindings: KeyBindings::default(),
+        }
+    }
+
+    // the preset, or the library's defaults, with the quirk overrides on top
+    pub fn initial_quirks(&self) -> Quirks {
+        self.quirks.on(self.preset.map_or_else(Quirks::default, Preset::quirks))
+    }
+
+    // layer the command line over whatever the config already holds
+    pub fn apply_args(&mut self, args: &RunArgs) {
+        if let Some(rom) = &args.rom {
+            self.rom = rom.clone();
+        }
+
+        self.scale = args.scale.unwrap_or(self.scale);
+        self.ticks_per_frame = args.speed.unwrap_or(self.ticks_per_frame);
+        self.default_ticks_per_frame = args.speed.unwrap_or(self.default_ticks_per_frame);
+        self.turbo_speed = args.turbo.unwrap_or(self.turbo_speed);
+        self.preset = args.preset.or(self.preset);
+        self.palette = args.palette.unwrap_or(self.palette);
+        self.is_mute |= args.mute;
+        self.record_audio = args.record_audio.clone().or(self.record_audio.take());
+        self.record_input = args.record_input.clone().or(self.record_input.take());
+        self.dump_state_on_exit = args.dump_state_on_exit.clone().or(self.dump_state_on_exit.take());
+        self.record_video = args.record_video.clone().or(self.record_video.take());
+        self.ffmpeg = args.ffmpeg.clone().unwrap_or(self.ffmpeg.clone());
+        self.screenshot_dir = args.screenshot_dir.clone().unwrap_or(self.screenshot_dir.clone());
+        self.screenshot_scale = args.screenshot_scale.or(self.screenshot_scale);
+        self.gif_fps = args.gif_fps.unwrap_or(self.gif_fps);
+        self.replay_seconds = args.replay_seconds.unwrap_or(self.replay_seconds);
+        self.seed = args.seed.or(self.seed);
+        self.is_start_paused |= args.start_paused;
+
+        if !args.breakpoints.is_empty() {
+            self.breakpoints = args.breakpoints.clone();
+        }
+
+        self.is_fullscreen |= args.fullscreen;
+        self.is_watch |= args.watch;
+        self.playlist = args.playlist.clone().or(self.playlist.take());
+        self.rotate = args.rotate.unwrap_or(self.rotate);
+        self.is_crt |= args.crt;
+        self.crt_persistence = args.crt_persistence.unwrap_or(self.crt_persistence);
+        self.is_scanlines &= !args.no_scanlines;
+        self.is_vsync &= !args.no_vsync;
+        self.is_unlock_fps |= args.unlock_fps;
+        self.is_latency_test |= args.latency_test;
+
+        if let Some(address) = &args.netplay {
+            self.netplay = Some(Netplay::Connect(address.clone()));
+        } else if let Some(port) = args.netplay_listen {
+            self.netplay = Some(Netplay::Listen(port));
+        }
+
+        #[cfg(feature = "remote")]
+        if let Some(address) = &args.remote {
+            self.remote = Some(address.clone());
+        }
+
+        #[cfg(feature = "viewer")]
+        if let Some(address) = &args.viewer {
+            self.viewer = Some(address.clone());
+        }
+
+        #[cfg(any(feature = "minifb", feature = "pixels"))]
+        {
+            self.backend = args.backend.unwrap_or(self.backend);
+        }
+
+        #[cfg(feature = "terminal")]
+        {
+            self.terminal = args.terminal.or(self.terminal);
+        }
+
+        let quirks = &mut self.quirks;
+        quirks.shift = args.quirk_shift.or(quirks.shift);
+        quirks.load_store = args.quirk_load_store.or(quirks.load_store);
+        quirks.jump = args.quirk_jump.or(quirks.jump);
+        quirks.clip = args.quirk_clip.or(quirks.clip);
+        quirks.vf_reset = args.quirk_vf_reset.or(quirks.vf_reset);
+        quirks.display_wait = args.quirk_display_wait.or(quirks.display_wait);
+
+        let beep = &mut self.beep;
+        beep.volume = args.volume.map_or(beep.volume, |volume| volume as f32 / 100.0);
+        beep.frequency_hz = args.beep_frequency.unwrap_or(beep.frequency_hz);
+        beep.waveform = args.waveform.map_or(beep.waveform, Waveform::from);
+        beep.min_duration_ms = args.min_beep_ms.unwrap_or(beep.min_duration_ms);
+    }
+}
+
+#[derive(Debug)]
+pub enum Command {
+    Run(Box<RunArgs>),
+    #[cfg(feature = "egui")]
+    Debug(Box<RunArgs>),
+    #[cfg(feature = "tui")]
+    Tui(Box<RunArgs>),
+    Headless(Box<HeadlessArgs>),
+    Bench { rom: Option<String>, seconds: u64, is_json: bool },
+    Overrides,
+    Conformance { dir: PathBuf, is_bless: bool },
+    ExportVectors { run: VectorArgs, instructions: usize, output: Option<PathBuf> },
+    VerifyVectors { run: VectorArgs, vectors: PathBuf },
+    Sweep(SweepArgs),
+    Audit(AuditArgs),
+    Snapshot(SnapshotArgs),
+    Analyze { rom: String, is_lints: bool },
+    Trace(TraceArgs),
+    Compare(CompareArgs),
+    WriteDefaultConfig(Option<PathBuf>),
+}
+
+pub fn parse<I, T>(args: I) -> Result<Command, clap::Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let cli = Cli::try_parse_from(args)?;
+
+    if let Some(path) = cli.write_default_config {
+        return Ok(Command::WriteDefaultConfig(path));
+    }
+
+    Ok(match cli.command {
+        Some(CliCommand::Run(run)) => Command::Run(run),
+        #[cfg(feature = "egui")]
+        Some(CliCommand::Debug(run)) => Command::Debug(run),
+        #[cfg(feature = "tui")]
+        Some(CliCommand::Tui(run)) => Command::Tui(run),
+        Some(CliCommand::Headless(headless)) => Command::Headless(Box::new(headless)),
+        Some(CliCommand::Bench { rom, seconds, json }) => Command::Bench { rom, seconds, is_json: json },
+        Some(CliCommand::Overrides) => Command::Overrides,
+        Some(CliCommand::Conformance { dir, bless }) => Command::Conformance { dir, is_bless: bless },
+        Some(CliCommand::ExportVectors { run, instructions, output }) => Command::ExportVectors { run, instructions, output },
+        Some(CliCommand::VerifyVectors { run, vectors }) => Command::VerifyVectors { run, vectors },
+        Some(CliCommand::Sweep(sweep)) => Command::Sweep(sweep),
+        Some(CliCommand::Audit(audit)) => Command::Audit(audit),
+        Some(CliCommand::Snapshot(snapshot)) => Command::Snapshot(snapshot),
+        Some(CliCommand::Analyze { rom, lints }) => Command::Analyze { rom, is_lints: lints },
+        Some(CliCommand::Trace(trace)) => Command::Trace(trace),
+        Some(CliCommand::Compare(compare)) => Command::Compare(compare),
+        None => Command::Run(Box::new(cli.run)),
+    })
+}
+
+pub fn parse_speed(value: &str) -> Result<usize, String> {
+    let (number, per_second) = match value.strip_suffix("ips") {
+        Some(number) => (number, true),
+        None => (value, false),
+    };
+
+    let number: usize = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("`{}` isn't a number of instructions, try 10 or 700ips", value))?;
+
+    let ticks_per_frame = if per_second { number / FRAMES_PER_SECOND } else { number };
+
+    if ticks_per_frame == 0 {
+        return Err(format!("`{}` is slower than one instruction per frame", value));
+    }
+
+    Ok(ticks_per_frame)
+}
+
+// "0x2A4" or "$2A4" in hex, "676" in decimal
+pub fn parse_breakpoint(value: &str) -> Result<u16, String> {
+    let text = value.trim();
+    let address = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")).or_else(|| text.strip_prefix('$')) {
+        Some(digits) => u16::from_str_radix(digits, 16).ok(),
+        None => text.parse().ok(),
+    };
+
+    address
+        .filter(|&address| (address as usize) < MEMORY_SIZE)
+        .ok_or_else(|| format!("`{}` isn't an address in memory, try 0x200 or 512", value))
+}
+
+fn parse_seed(value: &str) -> Result<u64, String> {
+    headless::parse_seed(value).ok_or_else(|| format!("`{}` isn't a seed, try 42 or 0xDEADBEEF", value))
+}
+
+fn parse_trace_filter(value: &str) -> Result<TraceFilter, String> {
+    TraceFilter::parse(value)
+        .ok_or_else(|| format!("`{}` isn't a trace filter, try pc=200-2FF, op=DXYN or frames=60-120", value))
+}
+
+fn parse_quirk_config(value: &str) -> Result<QuirkConfig, String> {
+    let quirks = lockstep::parse_quirks(value).ok_or_else(|| {
+        let names: Vec<&str> = Quirk::all().map(Quirk::name).collect();
+        format!(
+            "`{}` isn't a quirk configuration, try preset:chip8, preset:schip, preset:xochip or {}=on",
+            value,
+            names.join("=on, ")
+        )
+    })?;
+
+    Ok(QuirkConfig { name: value.to_string(), quirks })
+}
+
+// "4096" in bytes, or with a K, M or G suffix
+fn parse_size(value: &str) -> Result<u64, String> {
+    let text = value.trim().to_ascii_uppercase();
+    let (number, unit) = match text.char_indices().last() {
+        Some((at, 'K')) => (&text[..at], 1 << 10),
+        Some((at, 'M')) => (&text[..at], 1 << 20),
+        Some((at, 'G')) => (&text[..at], 1 << 30),
+        _ => (text.as_str(), 1),
+    };
+
+    match number.trim().parse::<u64>().ok().and_then(|count| count.checked_mul(unit)) {
+        Some(bytes) if bytes > 0 => Ok(bytes),
+        _ => Err(format!("`{}` isn't a size, try 4096, 500K or 64M", value)),
+    }
+}
+
+fn parse_halt(value: &str) -> Result<HaltReason, String> {
+    HaltReason::from_name(value).ok_or_else(|| {
+        format!("`{}` isn't a way a run ends, try one of {}", value, HaltReason::names().collect::<Vec<_>>().join(", "))
+    })
+}
+
+fn parse_switch(value: &str) -> Result<bool, String> {
+    match value {
+        "on" | "true" | "1" => Ok(true),
+        "off" | "false" | "0" => Ok(false),
+        _ => Err(format!("expected on or off, got `{}`", value)),
+    }
+}
+
+// "90s", "2m" or a number of seconds
+fn parse_rotate(value: &str) -> Result<Duration, String> {
+    let text = value.trim();
+    let (number, unit) = match text.strip_suffix('m') {
+        Some(minutes) => (minutes, 60),
+        None => (text.strip_suffix('s').unwrap_or(text), 1),
+    };
+
+    match number.trim().parse::<u64>() {
+        Ok(count) if count > 0 => Ok(Duration::from_secs(count * unit)),
+        _ => Err(format!("`{}` isn't a time to play each ROM, try 90s or 2m", value)),
+    }
+}
+
+pub fn parse_frequency(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(frequency) if frequency > 0.0 && frequency <= 20000.0 => Ok(frequency),
+        _ => Err(format!("`{}` isn't an audible frequency, try 440", value)),
+    }
+}
+
+pub fn parse_persistence(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(persistence) if (0.0..=1.0).contains(&persistence) => Ok(persistence),
+        _ => Err(format!("`{}` isn't between 0.0 and 1.0", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_config(args: &[&str]) -> Config {
+        match parse(args.iter().copied()).unwrap() {
+            Command::Run(args) => {
+                let mut config = Config::new(String::new());
+                config.apply_args(&args);
+                config
+            },
+            command => panic!("expected run, got {:?}", command),
+        }
+    }
+
+    #[test]
+    fn rom_only_uses_defaults() {
+        let config = run_config(&["chip8-emu", "pong.ch8"]);
+
+        assert_eq!(config, Config::new("pong.ch8".to_string()));
+    }
+
+    #[test]
+    fn run_subcommand_is_the_same_as_no_subcommand() {
+        assert_eq!(
+            run_config(&["chip8-emu", "run", "--scale", "8", "pong.ch8"]),
+            run_config(&["chip8-emu", "--scale", "8", "pong.ch8"])
+        );
+    }
+
+    #[test]
+    fn maps_flags_to_config() {
+        let config = run_config(&[
+            "chip8-emu", "pong.ch8", "--scale", "10", "--speed", "600ips", "--preset", "schip",
+            "--quirk-shift", "off", "--quirk-clip", "on", "--palette", "amber", "--seed", "42",
+            "--start-paused", "--fullscreen", "--watch", "--volume", "50", "--waveform", "sine",
+        ]);
+
+        assert_eq!(config.scale, 10);
+        assert_eq!(config.ticks_per_frame, 10);
+        assert_eq!(config.preset, Some(Preset::Schip));
+        assert_eq!(config.quirks.shift, Some(false));
+        assert_eq!(config.quirks.clip, Some(true));
+        assert_eq!(config.quirks.jump, None);
+        assert_eq!(config.palette, Palette::named("amber").unwrap());
+        assert_eq!(config.seed, Some(42));
+        assert!(config.is_start_paused && config.is_fullscreen && config.is_watch && !config.is_mute);
+        assert_eq!(config.beep.volume, 0.5);
+        assert_eq!(config.beep.waveform, Waveform::Sine);
+    }
+
+    #[test]
+    fn maps_the_playlist() {
+        let config = run_config(&["chip8-emu", "--playlist", "kiosk", "--rotate", "2m"]);
+        assert_eq!((config.playlist, config.rotate), (Some(PathBuf::from("kiosk")), Duration::from_secs(120)));
+        assert_eq!(run_config(&["chip8-emu", "--playlist", "kiosk"]).rotate, attract::DEFAULT_ROTATE);
+        assert_eq!(run_config(&["chip8-emu", "--playlist", "kiosk", "--rotate", "45"]).rotate, Duration::from_secs(45));
+
+        assert!(parse(["chip8-emu", "--playlist", "kiosk", "--rotate", "0s"]).is_err());
+        assert!(parse(["chip8-emu", "pong.ch8", "--playlist", "kiosk"]).is_err());
+        assert!(parse(["chip8-emu", "pong.ch8", "--rotate", "90s"]).is_err());
+    }
+
+    #[test]
+    fn the_latency_test_needs_no_rom() {
+        assert!(run_config(&["chip8-emu", "--latency-test"]).is_latency_test);
+        assert!(!run_config(&["chip8-emu", "pong.ch8"]).is_latency_test);
+        assert!(parse(["chip8-emu", "pong.ch8", "--latency-test"]).is_err());
+        assert!(parse(["chip8-emu", "--latency-test", "--netplay", "host:7000"]).is_err());
+    }
+
+    #[test]
+    fn maps_quirk_flags_to_the_library() {
+        assert!(run_config(&["chip8-emu", "pong.ch8", "--quirk-shift", "on"]).initial_quirks().shift_uses_vy);
+        assert!(!run_config(&["chip8-emu", "pong.ch8", "--quirk-shift", "off"]).initial_quirks().shift_uses_vy);
+        assert!(run_config(&["chip8-emu", "pong.ch8", "--quirk-load-store", "on"]).initial_quirks().memory_increment_i);
+        assert!(run_config(&["chip8-emu", "pong.ch8", "--quirk-jump", "on"]).initial_quirks().jump_uses_vx);
+        assert_eq!(run_config(&["chip8-emu", "pong.ch8"]).initial_quirks(), Quirks::default());
+
+        let mut overrides = QuirkOverrides::default();
+        overrides.set(Quirk::ShiftUsesVy, true);
+        overrides.set(Quirk::MemoryIncrementI, false);
+        assert_eq!((overrides.shift, overrides.load_store), (Some(true), Some(false)));
+        assert!(run_config(&["chip8-emu", "pong.ch8", "--quirk-clip", "on"]).initial_quirks().clip_sprites);
+        assert!(run_config(&["chip8-emu", "pong.ch8", "--quirk-vf-reset", "on"]).initial_quirks().vf_reset);
+        assert!(run_config(&["chip8-emu", "pong.ch8", "--quirk-display-wait", "on"]).initial_quirks().display_wait);
+    }
+
+    #[test]
+    fn quirk_flags_go_on_top_of_the_preset() {
+        let chip8 = Quirks::preset("chip8").unwrap();
+
+        assert_eq!(run_config(&["chip8-emu", "pong.ch8", "--preset", "chip8"]).initial_quirks(), chip8);
+        assert_eq!(
+            run_config(&["chip8-emu", "pong.ch8", "--preset", "chip8", "--quirk-clip", "off"]).initial_quirks(),
+            Quirks { clip_sprites: false, ..chip8 }
+        );
+        let schip = Quirks::preset("schip").unwrap();
+        assert_eq!(run_config(&["chip8-emu", "pong.ch8", "--preset", "schip"]).initial_quirks(), schip);
+    }
+
+    #[test]
+    fn the_headless_preset_changes_the_run() {
+        // V1 = 0x81, V0 = 0x10, V0 = V1 >> 1 on the VIP or V0 >> 1 without the preset, then stop
+        let rom = [0x61, 0x81, 0x60, 0x10, 0x80, 0x16, 0x12, 0x06];
+        let shifted = |args: &[&str]| {
+            let Command::Headless(args) = parse(args.iter().copied()).unwrap() else {
+                panic!("expected headless");
+            };
+            let mut script = headless::Script::default();
+            script.start_with_quirks(args.quirks());
+            let options = headless::Options { frames: 1, ticks_per_frame: 10, seed: 0, script };
+
+            headless::run(&rom, &options).unwrap().registers()[0]
+        };
+
+        assert_eq!(shifted(&["chip8-emu", "headless", "shift.ch8"]), 0x08);
+        assert_eq!(shifted(&["chip8-emu", "headless", "shift.ch8", "--preset", "chip8"]), 0x40);
+    }
+
+    #[test]
+    fn seeds_are_hex_or_decimal() {
+        assert_eq!(run_config(&["chip8-emu", "pong.ch8", "--seed", "0xDEADBEEF"]).seed, Some(0xDEADBEEF));
+        assert_eq!(run_config(&["chip8-emu", "pong.ch8", "--seed", "1234"]).seed, Some(1234));
+        assert_eq!(run_config(&["chip8-emu", "pong.ch8"]).seed, None);
+    }
+
+    #[test]
+    fn speed_accepts_ticks_per_frame() {
+        assert_eq!(run_config(&["chip8-emu", "--speed", "15", "pong.ch8"]).ticks_per_frame, 15);
+    }
+
+    #[test]
+    fn subcommands_have_their_own_help() {
+        let cli = <Cli as clap::CommandFactory>::command();
+        let about = |name| cli.find_subcommand(name).and_then(|command| command.get_about()).map(ToString::to_string);
+
+        let headless = "Run a ROM without a window or audio, for scripted checks and CI";
+        assert_eq!(about("headless").as_deref(), Some(headless));
+        let conformance = "Run the Timendus test suite headlessly and print a pass/fail table";
+        assert_eq!(about("conformance").as_deref(), Some(conformance));
+    }
+
+    #[test]
+    fn parses_conformance() {
+        match parse(["chip8-emu", "conformance", "suite/bin", "--bless"]).unwrap() {
+            Command::Conformance { dir, is_bless } => {
+                assert_eq!(dir, PathBuf::from("suite/bin"));
+                assert!(is_bless);
+            },
+            command => panic!("expected conformance, got {:?}", command),
+        }
+    }
+
+    #[test]
+    fn parses_headless() {
+        let args = ["chip8-emu", "headless", "pong.ch8", "--seed", "42", "--speed", "600ips", "--print-hash"];
+
+        match parse(args).unwrap() {
+            Command::Headless(args) => {
+                assert_eq!(args.rom, "pong.ch8");
+                assert_eq!((args.frames, args.seed, args.speed), (600, Some(42), 10));
+                assert!(args.print_hash && args.dump_screen.is_none());
+            },
+            command => panic!("expected headless, got {:?}", command),
+        }
+
+        assert!(parse(["chip8-emu", "headless"]).is_err());
+
+        let args = ["chip8-emu", "headless", "pong.ch8", "--report", "json", "--expect-halt", "self_jump", "--expect-hash", "ab"];
+        match parse(args).unwrap() {
+            Command::Headless(args) => {
+                assert_eq!(args.report, Some(ReportFormat::Json));
+                assert_eq!(args.expect_halt, Some(HaltReason::SelfJump));
+                assert_eq!(args.expect_hash.as_deref(), Some("ab"));
+            },
+            command => panic!("expected headless, got {:?}", command),
+        }
+
+        assert!(parse(["chip8-emu", "headless", "pong.ch8", "--expect-halt", "done"]).is_err());
+        assert!(parse(["chip8-emu", "headless", "pong.ch8", "--report-file", "out.json"]).is_err());
+    }
+
+    #[test]
+    fn parses_bench() {
+        assert!(matches!(
+            parse(["chip8-emu", "bench"]).unwrap(),
+            Command::Bench { rom: None, seconds: 3, is_json: false }
+        ));
+
+        match parse(["chip8-emu", "bench", "pong.ch8", "--seconds", "10", "--json"]).unwrap() {
+            Command::Bench { rom, seconds, is_json } => {
+                assert_eq!(rom.as_deref(), Some("pong.ch8"));
+                assert_eq!(seconds, 10);
+                assert!(is_json);
+            },
+            command => panic!("expected bench, got {:?}", command),
+        }
+
+        assert!(parse(["chip8-emu", "bench", "--seconds", "0"]).is_err());
+    }
+
+    #[test]
+    fn parses_test_vector_commands() {
+        match parse(["chip8-emu", "export-vectors", "pong.ch8", "--instructions", "50", "-o", "pong.jsonl"]).unwrap() {
+            Command::ExportVectors { run, instructions, output } => {
+                assert_eq!(run, VectorArgs { rom: "pong.ch8".to_string(), seed: 0, speed: 10 });
+                assert_eq!(instructions, 50);
+                assert_eq!(output, Some(PathBuf::from("pong.jsonl")));
+            },
+            command => panic!("expected export-vectors, got {:?}", command),
+        }
+
+        match parse(["chip8-emu", "verify-vectors", "pong.ch8", "pong.jsonl", "--seed", "0x2A"]).unwrap() {
+            Command::VerifyVectors { run, vectors } => {
+                assert_eq!(run.seed, 42);
+                assert_eq!(vectors, PathBuf::from("pong.jsonl"));
+            },
+            command => panic!("expected verify-vectors, got {:?}", command),
+        }
+
+        assert!(parse(["chip8-emu", "verify-vectors", "pong.ch8"]).is_err());
+    }
+
+    #[test]
+    fn parses_sweep() {
+        let args = ["chip8-emu", "sweep", "roms", "--frames", "1200", "--seed", "1", "--baseline", "results.json"];
+
+        match parse(args).unwrap() {
+            Command::Sweep(args) => {
+                assert_eq!(args.dir, PathBuf::from("roms"));
+                assert_eq!((args.frames, args.seed, args.speed), (1200, 1, DEFAULT_TICKS_PER_FRAME));
+                assert_eq!(args.baseline, Some(PathBuf::from("results.json")));
+                assert!(!args.bless);
+            },
+            command => panic!("expected sweep, got {:?}", command),
+        }
+
+        assert!(matches!(parse(["chip8-emu", "sweep", "roms"]).unwrap(), Command::Sweep(args) if args.baseline.is_none()));
+        assert!(parse(["chip8-emu", "sweep", "roms", "--bless"]).is_err());
+        assert!(parse(["chip8-emu", "sweep"]).is_err());
+    }
+
+    #[test]
+    fn parses_audit() {
+        match parse(["chip8-emu", "audit", "pong.ch8", "--frames", "120", "--against", "pong.log"]).unwrap() {
+            Command::Audit(args) => {
+                assert_eq!(args.rom, "pong.ch8");
+                assert_eq!((args.frames, args.seed, args.speed), (120, None, DEFAULT_TICKS_PER_FRAME));
+                assert_eq!((args.record, args.against), (None, Some(PathBuf::from("pong.log"))));
+            },
+            command => panic!("expected audit, got {:?}", command),
+        }
+
+        assert!(matches!(parse(["chip8-emu", "audit", "pong.ch8"]).unwrap(), Command::Audit(args) if args.frames == 600));
+        assert!(parse(["chip8-emu", "audit", "pong.ch8", "--record", "a.log", "--against", "b.log"]).is_err());
+        assert!(parse(["chip8-emu", "audit"]).is_err());
+    }
+
+    #[test]
+    fn parses_snapshot() {
+        match parse(["chip8-emu", "snapshot", "game.ch8", "--at", "120,300,600", "--out", "fixtures/"]).unwrap() {
+            Command::Snapshot(args) => {
+                assert_eq!((args.rom.as_str(), args.at), ("game.ch8", vec![120, 300, 600]));
+                assert_eq!(args.out, PathBuf::from("fixtures/"));
+                assert_eq!((args.seed, args.png_scale, args.force), (None, 8, false));
+            },
+            command => panic!("expected snapshot, got {:?}", command),
+        }
+
+        assert!(parse(["chip8-emu", "snapshot", "game.ch8", "--out", "fixtures"]).is_err());
+        assert!(parse(["chip8-emu", "snapshot", "game.ch8", "--at", "1,x", "--out", "fixtures"]).is_err());
+        assert!(parse(["chip8-emu", "snapshot", "game.ch8", "--at", "60"]).is_err());
+    }
+
+    #[test]
+    fn parses_analyze() {
+        assert!(matches!(
+            parse(["chip8-emu", "analyze", "game.ch8", "--lints"]).unwrap(),
+            Command::Analyze { rom, is_lints: true } if rom == "game.ch8"
+        ));
+        assert!(matches!(parse(["chip8-emu", "analyze", "game.ch8"]).unwrap(), Command::Analyze { is_lints: false, .. }));
+        assert!(parse(["chip8-emu", "analyze"]).is_err());
+    }
+
+    #[test]
+    fn parses_trace() {
+        match parse(["chip8-emu", "trace", "game.ch8", "--out", "trace.log"]).unwrap() {
+            Command::Trace(args) => {
+                assert_eq!((args.rom.as_str(), args.frames, args.format), ("game.ch8", 300, TraceFormatArg::Text));
+                assert_eq!((args.out, args.max_bytes), (PathBuf::from("trace.log"), trace_log::DEFAULT_MAX_BYTES));
+                assert!(args.filter.is_empty());
+            },
+            command => panic!("expected trace, got {:?}", command),
+        }
+
+        let args = ["chip8-emu", "trace", "game.ch8", "--out", "t.jsonl", "--format", "json", "--max-bytes", "2k"];
+        match parse(args.into_iter().chain(["--filter", "op=DXYN", "--filter", "pc=200-2FF"])).unwrap() {
+            Command::Trace(args) => {
+                assert_eq!((args.format, args.max_bytes), (TraceFormatArg::Json, 2048));
+                assert_eq!(args.filter, [TraceFilter::Opcode { value: 0xD000, mask: 0xF000 }, TraceFilter::Pc(0x200..=0x2FF)]);
+            },
+            command => panic!("expected trace, got {:?}", command),
+        }
+
+        assert!(parse(["chip8-emu", "trace", "game.ch8"]).is_err());
+        assert!(parse(["chip8-emu", "trace", "game.ch8", "--out", "t", "--filter", "op=D"]).is_err());
+        assert!(parse(["chip8-emu", "trace", "game.ch8", "--out", "t", "--max-bytes", "0"]).is_err());
+        assert!(parse(["chip8-emu", "trace", "game.ch8", "--out", "t", "--max-bytes", "12Q"]).is_err());
+    }
+
+    #[test]
+    fn parses_compare() {
+        match parse(["chip8-emu", "compare", "game.ch8", "--a", "preset:chip8", "--b", "preset:schip"]).unwrap() {
+            Command::Compare(args) => {
+                assert_eq!((args.a.name.as_str(), args.a.quirks.shift_uses_vy), ("preset:chip8", true));
+                assert_eq!((args.b.name.as_str(), args.b.quirks.jump_uses_vx), ("preset:schip", true));
+                assert_eq!((args.frames, args.seed), (600, None));
+            },
+            command => panic!("expected compare, got {:?}", command),
+        }
+
+        assert!(parse(["chip8-emu", "compare", "game.ch8", "--a", "preset:chip8"]).is_err());
+        assert!(parse(["chip8-emu", "compare", "game.ch8", "--a", "preset:vip", "--b", "preset:schip"]).is_err());
+    }
+
+    #[test]
+    fn parses_breakpoint_lists() {
+        let config = run_config(&["chip8-emu", "pong.ch8", "--break", "0x200,0x2A4", "--break", "$2a6, 1024"]);
+        assert_eq!(config.breakpoints, [0x200, 0x2A4, 0x2A6, 1024]);
+        assert!(run_config(&["chip8-emu", "pong.ch8"]).breakpoints.is_empty());
+
+        assert_eq!(parse_breakpoint("0XFFF"), Ok(0xFFF));
+        assert_eq!(parse_breakpoint("4095"), Ok(0xFFF));
+
+        for bad in ["0x1000", "4096", "2A4", "0x", "", "-1"] {
+            assert!(parse_breakpoint(bad).is_err(), "{:?} should be rejected", bad);
+        }
+
+        assert!(parse(["chip8-emu", "pong.ch8", "--break", "0x200,,0x202"]).is_err());
+    }
+
+    #[test]
+    fn parses_netplay() {
+        let config = run_config(&["chip8-emu", "pong.ch8", "--netplay", "192.168.1.5:7878"]);
+        assert_eq!(config.netplay, Some(Netplay::Connect("192.168.1.5:7878".to_string())));
+
+        let config = run_config(&["chip8-emu", "pong.ch8", "--netplay-listen", "7878"]);
+        assert_eq!(config.netplay, Some(Netplay::Listen(7878)));
+
+        assert!(parse(["chip8-emu", "pong.ch8", "--netplay", "a:1", "--netplay-listen", "1"]).is_err());
+    }
+
+    #[test]
+    fn parses_write_default_config() {
+        assert!(matches!(
+            parse(["chip8-emu", "--write-default-config"]).unwrap(),
+            Command::WriteDefaultConfig(None)
+        ));
+        assert!(matches!(
+            parse(["chip8-emu", "--write-default-config", "my.toml"]).unwrap(),
+            Command::WriteDefaultConfig(Some(_))
+        ));
+        assert!(parse(["chip8-emu", "--write-default-config", "my.toml", "--scale", "2"]).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        let invalid: &[&[&str]] = &[
+            &["chip8-emu", "pong.ch8", "--mute", "--volume", "20"],
+            &["chip8-emu", "pong.ch8", "--speed", "0"],
+            &["chip8-emu", "pong.ch8", "--speed", "fast"],
+            &["chip8-emu", "pong.ch8", "--scale", "0"],
+            &["chip8-emu", "pong.ch8", "--quirk-jump", "maybe"],
+            &["chip8-emu", "pong.ch8", "--preset", "megachip"],
+            &["chip8-emu", "pong.ch8", "--beep-frequency", "-5"],
+            &["chip8-emu", "pong.ch8", "--volume", "101"],
+            &["chip8-emu", "pong.ch8", "--crt-persistence", "1.5"],
+            &["chip8-emu", "pong.ch8", "--seed", "0xbeans"],
+        ];
+
+        for args in invalid {
+            assert!(parse(args.iter().copied()).is_err(), "{:?} should be rejected", args);
+        }
+    }
+
+    #[cfg(feature = "egui")]
+    #[test]
+    fn parses_debug() {
+        match parse(["chip8-emu", "debug", "--speed", "12", "pong.ch8"]).unwrap() {
+            Command::Debug(args) => {
+                assert_eq!(args.rom.as_deref(), Some("pong.ch8"));
+                assert_eq!(args.speed, Some(12));
+            },
+            command => panic!("expected debug, got {:?}", command),
+        }
+    }
+
+    #[cfg(feature = "minifb")]
+    #[test]
+    fn picks_a_backend() {
+        assert_eq!(run_config(&["chip8-emu", "pong.ch8"]).backend, Backend::Sdl);
+        assert_eq!(run_config(&["chip8-emu", "--backend", "minifb", "pong.ch8"]).backend, Backend::Minifb);
+        assert!(parse(["chip8-emu", "--backend", "gtk", "pong.ch8"]).is_err());
+    }
+
+    #[cfg(feature = "pixels")]
+    #[test]
+    fn picks_the_pixels_backend() {
+        assert_eq!(run_config(&["chip8-emu", "--backend", "pixels", "pong.ch8"]).backend, Backend::Pixels);
+    }
+
+    #[cfg(feature = "terminal")]
+    #[test]
+    fn parses_terminal_styles() {
+        assert_eq!(run_config(&["chip8-emu", "--terminal", "pong.ch8"]).terminal, Some(TerminalStyle::Blocks));
+        assert_eq!(run_config(&["chip8-emu", "--terminal=ascii", "pong.ch8"]).terminal, Some(TerminalStyle::Ascii));
+        assert_eq!(run_config(&["chip8-emu", "pong.ch8"]).terminal, None);
+        assert!(parse(["chip8-emu", "--terminal=braille", "pong.ch8"]).is_err());
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn parses_tui() {
+        match parse(["chip8-emu", "tui", "pong.ch8"]).unwrap() {
+            Command::Tui(args) => assert_eq!(args.rom.as_deref(), Some("pong.ch8")),
+            command => panic!("expected tui, got {:?}", command),
+        }
+    }
+
+    #[test]
+    fn rom_is_required_without_a_file_picker() {
+        let parsed = parse(["chip8-emu", "--scale", "4"]);
+
+        if cfg!(feature = "dialog") {
+            assert!(matches!(parsed.unwrap(), Command::Run(args) if args.rom.is_none()));
+        } else {
+            assert!(parsed.is_err());
+        }
+    }
+
+    #[test]
+    fn help_documents_every_flag() {
+        let help = parse(["chip8-emu", "--help"]).unwrap_err().to_string();
+
+        for flag in ["--config", "--write-default-config", "--scale", "--speed", "--preset", "--quirk-shift", "--palette", "--mute", "--seed", "--start-paused", "--fullscreen"] {
+            assert!(help.contains(flag), "--help is missing {}", flag);
+        }
+    }
+}
@@ -0,0 +1,425 @@
+use super::bindings::KeyBindings;
+use super::cli::{self, Config, Preset, WaveformArg};
+
+use chip8_emu::Waveform;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+pub const TEMPLATE: &str = r#"# chip8-emu configuration
+# command line flags take precedence over anything set here
+
+# pixels per CHIP-8 pixel
+# scale = 20
+
+# instructions per frame (10) or per second ("700ips")
+# speed = 10
+
+# speed multiplier while the turbo key is held
+# turbo = 8
+
+# chip8, schip or xochip
+# preset = "chip8"
+
+# green, white, amber, gameboy, inverted, or two hex colors as 'background,foreground'
+# palette = "green"
+# palette = '#000000,#32a956'
+
+# fullscreen = false
+# start_paused = false
+
+# phosphor ghosting, scanlines and soft pixel edges, F8 toggles it
+# crt = false
+# how much of the previous frame lingers, 0.0-1.0
+# crt_persistence = 0.6
+# scanlines = true
+
+# a software limiter takes over when vsync is off or unavailable
+# vsync = true
+# render as fast as possible, for benchmarking
+# unlock_fps = false
+
+# where F12 screenshots and F11 GIF recordings go, and their scale (the window scale when unset)
+# screenshot_dir = "screenshots"
+# screenshot_scale = 20
+# frame rate of GIF recordings, lower drops frames for smaller files
+# gif_fps = 30
+# seconds of play F7 saves as a GIF, 0 turns it off
+# replay_seconds = 30
+# the ffmpeg that --record-video pipes to
+# ffmpeg = "ffmpeg"
+
+# seed for CXNN, random and printed at startup when unset
+# seed = 1234
+
+[quirks]
+# shift = false
+# load_store = false
+# jump = false
+# clip = false
+# vf_reset = false
+# display_wait = false
+
+[audio]
+# mute = false
+# volume = 25
+# beep_frequency = 440.0
+# square, triangle or sine
+# waveform = "square"
+# min_beep_ms = 0.0
+
+# CHIP-8 key (0-F) = key name, unlisted keys keep the default layout:
+#   1 2 3 C      1 2 3 4
+#   4 5 6 D  ->  Q W E R
+#   7 8 9 E      A S D F
+#   A 0 B F      Z X C V
+[keys]
+# 5 = "Up"
+# 8 = "Down"
+# 7 = "Left"
+# 9 = "Right"
+
+# emulator actions
+[actions]
+# quit = "Escape"
+# reset = "N"
+# pause = "P"
+# frame_advance = "."
+# step = ","
+# save_state = "F5"
+# load_state = "F9"
+# turbo = "Tab"
+# rewind = "Backspace"
+# screenshot = "F12"
+# record_gif = "F11"
+# save_replay = "F7"
+# dump_state = "Insert"
+# quirk_menu = "Home"
+# cycle_palette = "F6"
+# toggle_crt = "F8"
+# toggle_overlay = "F1"
+# toggle_grid = "F2"
+# toggle_zoom = "F3"
+# toggle_keypad = "F4"
+# toggle_heatmap = "End"
+# speed_up = "="
+# speed_down = "-"
+# speed_reset = "0"
+"#;
+
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    scale: Option<u32>,
+    speed: Option<Speed>,
+    turbo: Option<u32>,
+    preset: Option<Preset>,
+    palette: Option<String>,
+    fullscreen: Option<bool>,
+    crt: Option<bool>,
+    crt_persistence: Option<f32>,
+    scanlines: Option<bool>,
+    vsync: Option<bool>,
+    unlock_fps: Option<bool>,
+    start_paused: Option<bool>,
+    screenshot_dir: Option<PathBuf>,
+    screenshot_scale: Option<u32>,
+    gif_fps: Option<u32>,
+    replay_seconds: Option<u32>,
+    ffmpeg: Option<PathBuf>,
+    seed: Option<u64>,
+    #[serde(default)]
+    quirks: QuirksSection,
+    #[serde(default)]
+    audio: AudioSection,
+    #[serde(default)]
+    keys: BTreeMap<String, String>,
+    #[serde(default)]
+    actions: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(untagged)]
+enum Speed {
+    TicksPerFrame(usize),
+    Text(String),
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct QuirksSection {
+    shift: Option<bool>,
+    load_store: Option<bool>,
+    jump: Option<bool>,
+    clip: Option<bool>,
+    vf_reset: Option<bool>,
+    display_wait: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct AudioSection {
+    mute: Option<bool>,
+    volume: Option<u8>,
+    beep_frequency: Option<f32>,
+    waveform: Option<WaveformArg>,
+    min_beep_ms: Option<f32>,
+}
+
+// $XDG_CONFIG_HOME/chip8-emu/config.toml, falling back to ~/.config
+pub fn default_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_home.join("chip8-emu").join("config.toml"))
+}
+
+// a missing file is only an error when the path was asked for explicitly
+pub fn load(path: &Path, is_explicit: bool) -> Result<FileConfig, String> {
+    match fs::read_to_string(path) {
+        Ok(text) => parse(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !is_explicit => Ok(FileConfig::default()),
+        Err(e) => Err(format!("Unable to read {}: {}", path.display(), e)),
+    }
+}
+
+pub fn parse(text: &str) -> Result<FileConfig, String> {
+    toml::from_str(text).map_err(|e| e.to_string())
+}
+
+pub fn write_template(path: &Path) -> Result<(), String> {
+    if path.exists() {
+        return Err(format!("{} already exists, not overwriting it", path.display()));
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Unable to create {}: {}", dir.display(), e))?;
+    }
+
+    fs::write(path, TEMPLATE).map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+}
+
+impl FileConfig {
+    pub fn apply(&self, config: &mut Config) -> Result<(), String> {
+        if let Some(speed) = &self.speed {
+            config.ticks_per_frame = match speed {
+                Speed::TicksPerFrame(ticks) => cli::parse_speed(&ticks.to_string()),
+                Speed::Text(text) => cli::parse_speed(text),
+            }
+            .map_err(|e| format!("invalid value for `speed`: {}", e))?;
+            config.default_ticks_per_frame = config.ticks_per_frame;
+        }
+
+        if let Some(scale) = self.scale {
+            if !(1..=64).contains(&scale) {
+                return Err(format!("invalid value for `scale`: {} isn't in 1..=64", scale));
+            }
+
+            config.scale = scale;
+        }
+
+        if let Some(turbo) = self.turbo {
+            if !(2..=64).contains(&turbo) {
+                return Err(format!("invalid value for `turbo`: {} isn't in 2..=64", turbo));
+            }
+
+            config.turbo_speed = turbo;
+        }
+
+        if let Some(scale) = self.screenshot_scale {
+            if !(1..=64).contains(&scale) {
+                return Err(format!("invalid value for `screenshot_scale`: {} isn't in 1..=64", scale));
+            }
+
+            config.screenshot_scale = Some(scale);
+        }
+
+        if let Some(fps) = self.gif_fps {
+            if !(1..=60).contains(&fps) {
+                return Err(format!("invalid value for `gif_fps`: {} isn't in 1..=60", fps));
+            }
+
+            config.gif_fps = fps;
+        }
+
+        if let Some(seconds) = self.replay_seconds {
+            if seconds > 600 {
+                return Err(format!("invalid value for `replay_seconds`: {} isn't in 0..=600", seconds));
+            }
+
+            config.replay_seconds = seconds;
+        }
+
+        if let Some(persistence) = self.crt_persistence {
+            if !(0.0..=1.0).contains(&persistence) {
+                return Err(format!("invalid value for `crt_persistence`: {} isn't in 0.0..=1.0", persistence));
+            }
+
+            config.crt_persistence = persistence;
+        }
+
+        config.screenshot_dir = self.screenshot_dir.clone().unwrap_or(config.screenshot_dir.clone());
+        config.ffmpeg = self.ffmpeg.clone().unwrap_or(config.ffmpeg.clone());
+        config.preset = self.preset.or(config.preset);
+        if let Some(palette) = &self.palette {
+            config.palette = palette.parse().map_err(|e| format!("invalid value for `palette`: {}", e))?;
+        }
+
+        config.is_fullscreen = self.fullscreen.unwrap_or(config.is_fullscreen);
+        config.is_crt = self.crt.unwrap_or(config.is_crt);
+        config.is_scanlines = self.scanlines.unwrap_or(config.is_scanlines);
+        config.is_vsync = self.vsync.unwrap_or(config.is_vsync);
+        config.is_unlock_fps = self.unlock_fps.unwrap_or(config.is_unlock_fps);
+        config.is_start_paused = self.start_paused.unwrap_or(config.is_start_paused);
+        config.seed = self.seed.or(config.seed);
+
+        let quirks = &mut config.quirks;
+        quirks.shift = self.quirks.shift.or(quirks.shift);
+        quirks.load_store = self.quirks.load_store.or(quirks.load_store);
+        quirks.jump = self.quirks.jump.or(quirks.jump);
+        quirks.clip = self.quirks.clip.or(quirks.clip);
+        quirks.vf_reset = self.quirks.vf_reset.or(quirks.vf_reset);
+        quirks.display_wait = self.quirks.display_wait.or(quirks.display_wait);
+
+        let audio = &self.audio;
+        config.is_mute = audio.mute.unwrap_or(config.is_mute);
+
+        if let Some(volume) = audio.volume {
+            if volume > 100 {
+                return Err(format!("invalid value for `audio.volume`: {} isn't in 0..=100", volume));
+            }
+
+            config.beep.volume = volume as f32 / 100.0;
+        }
+
+        if let Some(frequency) = audio.beep_frequency {
+            config.beep.frequency_hz = cli::parse_frequency(&frequency.to_string())
+                .map_err(|e| format!("invalid value for `audio.beep_frequency`: {}", e))?;
+        }
+
+        config.beep.waveform = audio.waveform.map_or(config.beep.waveform, Waveform::from);
+        config.beep.min_duration_ms = audio.min_beep_ms.unwrap_or(config.beep.min_duration_ms);
+
+        config.bindings = KeyBindings::from_config(&self.keys, &self.actions)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::cli::Command;
+
+    use chip8_emu::Palette;
+
+    fn layered(file: &str, args: &[&str]) -> Result<Config, String> {
+        let args = match cli::parse(args.iter().copied()).unwrap() {
+            Command::Run(args) => args,
+            command => panic!("expected run, got {:?}", command),
+        };
+
+        let mut config = Config::new(String::new());
+        parse(file)?.apply(&mut config)?;
+        config.apply_args(&args);
+
+        Ok(config)
+    }
+
+    #[test]
+    fn reads_every_section() {
+        let config = layered(
+            r#"
+            scale = 12
+            speed = "900ips"
+            preset = "xochip"
+            palette = "white"
+            fullscreen = true
+
+            [quirks]
+            jump = true
+
+            [audio]
+            volume = 40
+            waveform = "triangle"
+            "#,
+            &["chip8-emu", "pong.ch8"],
+        )
+        .unwrap();
+
+        assert_eq!(config.scale, 12);
+        assert_eq!(config.ticks_per_frame, 15);
+        assert_eq!(config.preset, Some(Preset::Xochip));
+        assert_eq!(config.palette, Palette::named("white").unwrap());
+        assert!(config.is_fullscreen);
+        assert_eq!(config.quirks.jump, Some(true));
+        assert_eq!(config.beep.volume, 0.4);
+        assert_eq!(config.beep.waveform, Waveform::Triangle);
+    }
+
+    #[test]
+    fn command_line_beats_file_beats_defaults() {
+        let config = layered(
+            "scale = 12\npalette = \"amber\"\n[quirks]\nclip = true\n",
+            &["chip8-emu", "pong.ch8", "--scale", "4", "--quirk-clip", "off"],
+        )
+        .unwrap();
+
+        assert_eq!(config.scale, 4);
+        assert_eq!(config.palette, Palette::named("amber").unwrap());
+        assert_eq!(config.quirks.clip, Some(false));
+        assert_eq!(config.ticks_per_frame, cli::DEFAULT_TICKS_PER_FRAME);
+    }
+
+    #[test]
+    fn errors_name_the_offending_key() {
+        let unknown = layered("scael = 12\n", &["chip8-emu", "pong.ch8"]).unwrap_err();
+        assert!(unknown.contains("scael"), "{}", unknown);
+
+        let wrong_type = layered("[audio]\nvolume = \"loud\"\n", &["chip8-emu", "pong.ch8"]).unwrap_err();
+        assert!(wrong_type.contains("volume"), "{}", wrong_type);
+
+        let bad_speed = layered("speed = \"warp\"\n", &["chip8-emu", "pong.ch8"]).unwrap_err();
+        assert!(bad_speed.contains("`speed`"), "{}", bad_speed);
+
+        let bad_scale = layered("scale = 0\n", &["chip8-emu", "pong.ch8"]).unwrap_err();
+        assert!(bad_scale.contains("`scale`"), "{}", bad_scale);
+
+        let bad_palette = layered("palette = \"#00,#ff\"\n", &["chip8-emu", "pong.ch8"]).unwrap_err();
+        assert!(bad_palette.contains("`palette`"), "{}", bad_palette);
+    }
+
+    #[test]
+    fn reads_key_bindings() {
+        let config = layered(
+            "[keys]\n5 = \"Up\"\n[actions]\npause = \"Space\"\n",
+            &["chip8-emu", "pong.ch8"],
+        )
+        .unwrap();
+
+        assert_eq!(config.bindings.button(sdl2::keyboard::Keycode::Up), Some(0x5));
+
+        let error = layered("[keys]\n5 = \"Q\"\n", &["chip8-emu", "pong.ch8"]).unwrap_err();
+        assert!(error.contains("bound to both"), "{}", error);
+    }
+
+    #[test]
+    fn template_parses_to_defaults() {
+        assert_eq!(parse(TEMPLATE).unwrap(), FileConfig::default());
+    }
+
+    #[test]
+    fn missing_default_file_is_fine() {
+        let path = env::temp_dir().join("chip8-emu-missing-config.toml");
+
+        assert_eq!(load(&path, false).unwrap(), FileConfig::default());
+        assert!(load(&path, true).is_err());
+    }
+}
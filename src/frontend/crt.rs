@@ -0,0 +1,98 @@
+use chip8_emu::{Palette, Phosphor, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+pub const DEFAULT_PERSISTENCE: f32 = 0.6;
+// the texture is upscaled this much before the linear-filtered blit, so only pixel edges blur
+pub const SOFTEN_SCALE: usize = 4;
+pub const TEXTURE_WIDTH: u32 = (SCREEN_WIDTH * SOFTEN_SCALE) as u32;
+pub const TEXTURE_HEIGHT: u32 = (SCREEN_HEIGHT * SOFTEN_SCALE) as u32;
+// alpha of the black line drawn over every other output row
+pub const SCANLINE_ALPHA: u8 = 70;
+
+pub struct Crt {
+    phosphor: Phosphor,
+    rgba: Vec<u8>,
+    texture_pixels: Vec<u8>,
+}
+
+impl Crt {
+    pub fn new(persistence: f32) -> Self {
+        Self {
+            phosphor: Phosphor::new(SCREEN_WIDTH * SCREEN_HEIGHT, persistence),
+            rgba: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT * 4],
+            texture_pixels: vec![0; TEXTURE_WIDTH as usize * TEXTURE_HEIGHT as usize * 4],
+        }
+    }
+
+    // blends `screen` into the phosphor and returns the texture contents
+    pub fn frame(&mut self, screen: &[bool], palette: Palette) -> &[u8] {
+        self.phosphor.update(screen);
+        self.phosphor.render_rgba(palette, &mut self.rgba);
+        upscale_rgba(&self.rgba, SCREEN_WIDTH, SOFTEN_SCALE, &mut self.texture_pixels);
+
+        &self.texture_pixels
+    }
+
+    pub fn clear(&mut self) {
+        self.phosphor.clear();
+    }
+}
+
+// nearest-neighbour upscale of an RGBA image `width` pixels wide
+pub fn upscale_rgba(source: &[u8], width: usize, factor: usize, out: &mut [u8]) {
+    let out_width = width * factor;
+
+    for (y, row) in out.chunks_exact_mut(out_width * 4).enumerate() {
+        let source_row = &source[(y / factor) * width * 4..][..width * 4];
+
+        for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+            let offset = (x / factor) * 4;
+            pixel.copy_from_slice(&source_row[offset..offset + 4]);
+        }
+    }
+}
+
+// window rows that get darkened, every other row inside the image
+pub fn scanline_rows(top: i32, height: u32) -> impl Iterator<Item = i32> {
+    (top..top + height as i32).skip(1).step_by(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upscales_small_buffers() {
+        // 2x1: red, blue
+        let source = [255, 0, 0, 255, 0, 0, 255, 255];
+        let mut out = [0; 2 * 2 * 2 * 4];
+        upscale_rgba(&source, 2, 2, &mut out);
+
+        let red = [255, 0, 0, 255];
+        let blue = [0, 0, 255, 255];
+        let row = [red, red, blue, blue].concat();
+        assert_eq!(&out[..16], &row[..]);
+        assert_eq!(&out[16..], &row[..]);
+    }
+
+    #[test]
+    fn darkens_every_other_row() {
+        assert_eq!(scanline_rows(10, 6).collect::<Vec<_>>(), vec![11, 13, 15]);
+        assert_eq!(scanline_rows(-3, 3).collect::<Vec<_>>(), vec![-2]);
+    }
+
+    #[test]
+    fn ghosts_the_previous_frame() {
+        let palette = Palette::new((0, 0, 0), (200, 200, 200));
+        let mut crt = Crt::new(0.5);
+        let mut screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+
+        screen[0] = true;
+        crt.frame(&screen, palette);
+        screen[0] = false;
+        let pixels = crt.frame(&screen, palette);
+
+        assert_eq!(&pixels[..4], &[100, 100, 100, 255]);
+        // still the same pixel one texel over, inside the upscaled block
+        assert_eq!(&pixels[4..8], &[100, 100, 100, 255]);
+    }
+}
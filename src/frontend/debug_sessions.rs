@@ -0,0 +1,218 @@
+// debugger sessions stored by ROM hash, restored when a debugger opens the ROM again and saved
+// when it closes. a ROM that was rebuilt since has a new hash, so the last session under the same
+// name stands in for it, that's where a stale breakpoint comes from
+
+use super::config_file;
+use super::title;
+
+use chip8_emu::debug_session::DebugSession;
+use chip8_emu::Chip8;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+struct StoredSession {
+    // the name it was last debugged under, to find it again after a rebuild
+    #[serde(default)]
+    name: String,
+    session: DebugSession,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct StoreFile {
+    #[serde(default)]
+    roms: BTreeMap<String, StoredSession>,
+}
+
+#[derive(Debug)]
+pub struct SessionStore {
+    path: PathBuf,
+    roms: BTreeMap<String, StoredSession>,
+}
+
+impl SessionStore {
+    // next to config.toml
+    pub fn default_path() -> Option<PathBuf> {
+        config_file::default_path().and_then(|path| Some(path.parent()?.join("debug_sessions.toml")))
+    }
+
+    // nothing stored yet is an empty store
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let roms = match fs::read_to_string(path) {
+            Ok(text) => toml::from_str::<StoreFile>(&text).map_err(|e| format!("{}: {}", path.display(), e))?.roms,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(format!("Unable to read {}: {}", path.display(), e)),
+        };
+
+        Ok(Self { path: path.to_path_buf(), roms })
+    }
+
+    // the session for this exact ROM, or else one for a ROM of the same name
+    pub fn find(&self, hash: &str, name: &str) -> Option<&DebugSession> {
+        let stored =
+            self.roms.get(hash).or_else(|| self.roms.values().find(|stored| !name.is_empty() && stored.name == name));
+
+        stored.map(|stored| &stored.session)
+    }
+
+    pub fn update(&mut self, hash: &str, name: &str, session: DebugSession) {
+        self.roms.insert(hash.to_string(), StoredSession { name: name.to_string(), session });
+    }
+
+    pub fn save(&mut self) -> Result<(), String> {
+        self.roms.retain(|_, stored| !stored.session.is_empty());
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("Unable to create {}: {}", dir.display(), e))?;
+        }
+
+        let file = StoreFile { roms: self.roms.clone() };
+        let text = toml::to_string(&file).map_err(|e| format!("Unable to save the debug session: {}", e))?;
+
+        fs::write(&self.path, text).map_err(|e| format!("Unable to write {}: {}", self.path.display(), e))
+    }
+}
+
+fn rom_key(chip8: &Chip8) -> Option<(String, String)> {
+    let rom = chip8.loaded_rom()?;
+
+    let name = rom.source_name.as_deref().map_or("", title::display_name);
+
+    Some((rom.sha256_hex(), name.to_string()))
+}
+
+// the stored session for the ROM `chip8` has loaded, with anything that didn't come back as saved
+pub fn restore_from(path: &Path, chip8: &mut Chip8) -> Vec<String> {
+    let Some((hash, name)) = rom_key(chip8) else {
+        return Vec::new();
+    };
+
+    match SessionStore::load(path) {
+        Ok(store) => store.find(&hash, &name).map_or_else(Vec::new, |session| session.restore(chip8)),
+        Err(message) => vec![message],
+    }
+}
+
+pub fn remember_in(path: &Path, chip8: &Chip8) -> Result<(), String> {
+    let Some((hash, name)) = rom_key(chip8) else {
+        return Ok(());
+    };
+
+    let mut store = SessionStore::load(path)?;
+    store.update(&hash, &name, DebugSession::capture(chip8));
+    store.save()
+}
+
+// restore_from the default store, each warning on the console
+pub fn restore(chip8: &mut Chip8) {
+    if let Some(path) = SessionStore::default_path() {
+        for warning in restore_from(&path, chip8) {
+            eprintln!("Debug session: {}", warning);
+        }
+    }
+}
+
+pub fn remember(chip8: &Chip8) {
+    if let Some(path) = SessionStore::default_path() {
+        if let Err(message) = remember_in(&path, chip8) {
+            eprintln!("{}", message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chip8_emu::expr::Expr;
+
+    use std::env;
+
+    // V3 += 1, loop
+    const ROM: [u8; 4] = [0x73, 0x01, 0x12, 0x00];
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("chip8-emu-debug-sessions-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        dir.join("debug_sessions.toml")
+    }
+
+    fn machine(rom: &[u8]) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load_named(rom, "roms/counter.ch8").unwrap();
+        chip8
+    }
+
+    #[test]
+    fn saves_and_restores_by_hash() {
+        let path = temp_path("round-trip");
+        let mut chip8 = machine(&ROM);
+        chip8.add_conditional_breakpoint(0x202, Expr::parse("v3 == 4").unwrap());
+        chip8.add_breakpoint(0x200);
+        chip8.set_breakpoint_enabled(0x200, false);
+        chip8.add_watchpoint(0x300);
+        chip8.add_watch(Expr::parse("[i + 1]").unwrap());
+        chip8.set_trace_capacity(32);
+        remember_in(&path, &chip8).unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.contains("name = \"counter\""), "{}", text);
+
+        let mut restored = machine(&ROM);
+        assert_eq!(restore_from(&path, &mut restored), Vec::<String>::new());
+        assert_eq!(DebugSession::capture(&restored), DebugSession::capture(&chip8));
+
+        // a ROM nothing was saved for gets nothing
+        let mut other = Chip8::new();
+        other.load_named(&[0x73, 0x02, 0x12, 0x00], "other.ch8").unwrap();
+        assert!(restore_from(&path, &mut other).is_empty());
+        assert_eq!(other.breakpoints().count(), 0);
+    }
+
+    #[test]
+    fn a_rebuilt_rom_gets_the_old_session_with_stale_breakpoints_disabled() {
+        let path = temp_path("rebuilt");
+        let mut chip8 = machine(&[0x73, 0x01, 0x00, 0xE0, 0x00, 0xE0, 0x12, 0x00]);
+        chip8.add_breakpoint(0x202);
+        chip8.add_breakpoint(0x206);
+        remember_in(&path, &chip8).unwrap();
+
+        let mut rebuilt = machine(&ROM);
+        let warnings = restore_from(&path, &mut rebuilt);
+
+        assert_eq!(warnings, ["the breakpoint at 206 is past the end of the ROM, it's disabled"]);
+        assert!(rebuilt.is_breakpoint_enabled(0x202));
+        assert!(!rebuilt.is_breakpoint_enabled(0x206));
+    }
+
+    #[test]
+    fn forgets_emptied_sessions() {
+        let path = temp_path("empty");
+        let mut chip8 = machine(&ROM);
+        chip8.add_breakpoint(0x200);
+        remember_in(&path, &chip8).unwrap();
+
+        chip8.clear_breakpoints();
+        remember_in(&path, &chip8).unwrap();
+        assert_eq!(SessionStore::load(&path).unwrap().roms.len(), 0);
+    }
+
+    #[test]
+    fn a_damaged_store_is_a_warning() {
+        let path = temp_path("damaged");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "roms = 3\n").unwrap();
+
+        let warnings = restore_from(&path, &mut machine(&ROM));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("debug_sessions.toml"));
+    }
+}
@@ -0,0 +1,502 @@
+// pieces shared by the debugger frontends: addresses, the command language, the disassembly listing
+
+use chip8_emu::expr::Expr;
+use chip8_emu::{disasm, Chip8};
+
+use std::ops::Range;
+
+pub const MEMORY_SIZE: u16 = 0x1000;
+
+pub const HELP: &str = "break ADDR [if EXPR], delete ADDR, enable|disable ADDR, wp|unwp ADDR, watch [EXPR], unwatch N, step, rs (step back), next, continue, pause, set vX|i|pc VALUE, write ADDR BYTE, reset, quit";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    Break(u16),
+    BreakIf(u16, Expr),
+    Delete(u16),
+    Enable(u16),
+    Disable(u16),
+    // stop after a write to the address
+    Watchpoint(u16),
+    Unwatchpoint(u16),
+    Watch(Expr),
+    // list the watches
+    Watches,
+    // numbered from 1, as listed
+    Unwatch(usize),
+    Step,
+    // undo the last instruction
+    StepBack,
+    // step over a CALL
+    Next,
+    Continue,
+    Pause,
+    SetRegister(usize, u8),
+    SetI(u16),
+    SetPc(u16),
+    Write(u16, u8),
+    Reset,
+    Help,
+    Quit,
+}
+
+// "204", "0x204" or "$204", always hex like everywhere else in the debugger
+pub fn parse_address(text: &str) -> Option<u16> {
+    parse_hex(text).filter(|&address| address < MEMORY_SIZE)
+}
+
+fn parse_hex(text: &str) -> Option<u16> {
+    let text = text.trim();
+    let digits = text.strip_prefix("0x").or_else(|| text.strip_prefix('$')).unwrap_or(text);
+
+    u16::from_str_radix(digits, 16).ok()
+}
+
+fn parse_byte(text: &str) -> Result<u8, String> {
+    parse_hex(text)
+        .and_then(|value| u8::try_from(value).ok())
+        .ok_or_else(|| format!("`{}` isn't a byte, use 00-FF", text))
+}
+
+fn address_arg(arg: Option<&str>) -> Result<u16, String> {
+    let arg = arg.ok_or("expected an address")?;
+    parse_address(arg).ok_or_else(|| format!("`{}` isn't an address, use 000-FFF", arg))
+}
+
+fn expression_arg(text: &str) -> Result<Expr, String> {
+    Expr::parse(text).map_err(|e| format!("`{}` isn't an expression, {}", text.trim(), e))
+}
+
+// "v3 = 2A (42)", or what went wrong reading it
+pub fn watch_value(expr: &Expr, chip8: &Chip8) -> String {
+    match expr.evaluate(chip8) {
+        Ok(value) => format!("{} = {:X} ({})", expr, value, value),
+        Err(e) => format!("{}: {}", expr, e),
+    }
+}
+
+// "1  v3 = 2A (42)" for each watch
+pub fn watch_lines(chip8: &Chip8) -> Vec<String> {
+    chip8
+        .watches()
+        .iter()
+        .enumerate()
+        .map(|(index, expr)| format!("{}  {}", index + 1, watch_value(expr, chip8)))
+        .collect()
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        // the commands that take an expression, which has spaces of its own
+        let trimmed = line.trim();
+        let (head, rest) = trimmed.split_once(char::is_whitespace).unwrap_or((trimmed, ""));
+
+        match head.to_ascii_lowercase().as_str() {
+            "b" | "break" => {
+                if let Some((address, condition)) = rest.trim().split_once(char::is_whitespace) {
+                    let condition = match condition.trim_start().split_once(char::is_whitespace) {
+                        Some((keyword, condition)) if keyword.eq_ignore_ascii_case("if") => condition,
+                        _ => return Err("expected `if` and a condition after the address".to_string()),
+                    };
+
+                    return Ok(Command::BreakIf(address_arg(Some(address))?, expression_arg(condition)?));
+                }
+            },
+            "watch" if rest.trim().is_empty() => return Ok(Command::Watches),
+            "watch" => return Ok(Command::Watch(expression_arg(rest)?)),
+            _ => {},
+        }
+
+        let mut words = line.split_whitespace();
+        let name = words.next().ok_or("type a command, or help")?.to_ascii_lowercase();
+        let args: Vec<&str> = words.collect();
+
+        let arity = match name.as_str() {
+            "b" | "break" | "d" | "delete" | "enable" | "disable" | "wp" | "unwp" | "unwatch" => 1,
+            "w" | "write" | "set" => 2,
+            "s" | "step" | "rs" | "n" | "next" | "c" | "continue" | "p" | "pause" | "reset" | "h" | "help" | "q" | "quit" => 0,
+            _ => return Err(format!("unknown command `{}`, try help", name)),
+        };
+
+        if args.len() > arity {
+            return Err(format!("too many arguments to `{}`", name));
+        }
+
+        let first = args.first().copied();
+        let second = args.get(1).copied();
+
+        Ok(match name.as_str() {
+            "b" | "break" => Command::Break(address_arg(first)?),
+            "d" | "delete" => Command::Delete(address_arg(first)?),
+            "enable" => Command::Enable(address_arg(first)?),
+            "disable" => Command::Disable(address_arg(first)?),
+            "wp" => Command::Watchpoint(address_arg(first)?),
+            "unwp" => Command::Unwatchpoint(address_arg(first)?),
+            "unwatch" => {
+                let arg = first.ok_or("expected a watch number")?;
+                let number = arg.parse().ok().filter(|&number| number > 0);
+
+                Command::Unwatch(number.ok_or_else(|| format!("`{}` isn't a watch number, see watch", arg))?)
+            },
+            "s" | "step" => Command::Step,
+            "rs" => Command::StepBack,
+            "n" | "next" => Command::Next,
+            "c" | "continue" => Command::Continue,
+            "p" | "pause" => Command::Pause,
+            "reset" => Command::Reset,
+            "h" | "help" => Command::Help,
+            "q" | "quit" => Command::Quit,
+            "w" | "write" => {
+                let address = address_arg(first)?;
+                Command::Write(address, parse_byte(second.ok_or("expected a byte to write")?)?)
+            },
+            "set" => {
+                let target = first.ok_or("expected vX, i or pc")?.to_ascii_lowercase();
+                let value = second.ok_or("expected a value")?;
+
+                match target.as_str() {
+                    "i" => Command::SetI(parse_hex(value).filter(|&i| i <= 0xFFF).ok_or("I is 000-FFF")?),
+                    "pc" => Command::SetPc(address_arg(Some(value))?),
+                    _ => {
+                        let index = target
+                            .strip_prefix('v')
+                            .filter(|digit| digit.len() == 1)
+                            .and_then(|digit| usize::from_str_radix(digit, 16).ok())
+                            .ok_or_else(|| format!("`{}` isn't a register, use v0-vf, i or pc", target))?;
+
+                        Command::SetRegister(index, parse_byte(value)?)
+                    },
+                }
+            },
+            _ => unreachable!("arity already rejected `{}`", name),
+        })
+    }
+
+    // applies the command and describes what happened, Quit is left to the frontend
+    pub fn execute(self, chip8: &mut Chip8) -> String {
+        match self {
+            Command::Break(address) => {
+                chip8.add_breakpoint(address);
+                format!("Breakpoint at {:03X}", address)
+            },
+            Command::BreakIf(address, condition) => {
+                let text = format!("Breakpoint at {:03X} if {}", address, condition);
+                chip8.add_conditional_breakpoint(address, condition);
+                text
+            },
+            Command::Delete(address) => {
+                chip8.remove_breakpoint(address);
+                format!("Removed breakpoint at {:03X}", address)
+            },
+            Command::Enable(address) | Command::Disable(address) if !chip8.has_breakpoint(address) => {
+                format!("There's no breakpoint at {:03X}", address)
+            },
+            Command::Enable(address) => {
+                chip8.set_breakpoint_enabled(address, true);
+                format!("Enabled breakpoint at {:03X}", address)
+            },
+            Command::Disable(address) => {
+                chip8.set_breakpoint_enabled(address, false);
+                format!("Disabled breakpoint at {:03X}", address)
+            },
+            Command::Watchpoint(address) => {
+                chip8.add_watchpoint(address);
+                format!("Watchpoint at {:03X}", address)
+            },
+            Command::Unwatchpoint(address) => {
+                chip8.remove_watchpoint(address);
+                format!("Removed watchpoint at {:03X}", address)
+            },
+            Command::Watch(expr) => {
+                let text = format!("Watch {}: {}", chip8.watches().len() + 1, watch_value(&expr, chip8));
+                chip8.add_watch(expr);
+                text
+            },
+            Command::Watches => match watch_lines(chip8) {
+                lines if lines.is_empty() => "No watches, add one with watch EXPR".to_string(),
+                lines => lines.join(", "),
+            },
+            Command::Unwatch(number) => match chip8.remove_watch(number - 1) {
+                Some(expr) => format!("Removed watch {}: {}", number, expr),
+                None => format!("There's no watch {}", number),
+            },
+            Command::Step => {
+                chip8.set_paused(true);
+
+                match chip8.tick() {
+                    Ok(_) => format!("PC {:03X}", chip8.program_counter()),
+                    Err(error) => format!("Can't run that: {}", error),
+                }
+            },
+            Command::StepBack => {
+                chip8.set_paused(true);
+
+                if chip8.step_back() {
+                    format!("PC {:03X}", chip8.program_counter())
+                } else {
+                    "Nothing to step back to".to_string()
+                }
+            },
+            Command::Next => {
+                chip8.set_paused(true);
+
+                match chip8.step_over() {
+                    Ok(true) => format!("PC {:03X}", chip8.program_counter()),
+                    Ok(false) => match chip8.take_breakpoint_hit() {
+                        Some(address) => format!("Breakpoint at {:03X}", address),
+                        None => "The subroutine didn't return".to_string(),
+                    },
+                    Err(error) => format!("Can't run that: {}", error),
+                }
+            },
+            Command::Continue => {
+                chip8.set_paused(false);
+                "Running".to_string()
+            },
+            Command::Pause => {
+                chip8.set_paused(true);
+                format!("Paused at {:03X}", chip8.program_counter())
+            },
+            Command::SetRegister(index, value) => {
+                chip8.set_register(index, value);
+                format!("V{:X} = {:02X}", index, value)
+            },
+            Command::SetI(value) => {
+                chip8.set_register_i(value);
+                format!("I = {:03X}", value)
+            },
+            Command::SetPc(address) => {
+                chip8.set_program_counter(address);
+                format!("PC = {:03X}", address)
+            },
+            Command::Write(address, value) => {
+                chip8.write_memory(address, value);
+                format!("[{:03X}] = {:02X}", address, value)
+            },
+            Command::Reset => {
+                chip8.soft_reset();
+                "Reset".to_string()
+            },
+            Command::Help => HELP.to_string(),
+            Command::Quit => String::new(),
+        }
+    }
+}
+
+// even addresses around `pc`, clamped to memory, `rows` instructions long
+pub fn disassembly_window(pc: u16, rows: u16) -> Range<u16> {
+    let length = rows * 2;
+    let start = pc.saturating_sub(rows / 2 * 2).min(MEMORY_SIZE - length);
+
+    start..start + length
+}
+
+// "●> 204  D125  DRW V1, V2, 5" for each instruction in the window
+pub fn disassembly_lines(chip8: &Chip8, rows: u16) -> Vec<(u16, String)> {
+    let pc = chip8.program_counter();
+    let window = disassembly_window(pc, rows);
+
+    // there's no telling where instructions start going backwards, so before PC it's a row a word.
+    // from PC on a long load takes its address word with it
+    let before =
+        (window.start..pc.max(window.start)).step_by(2).map(|address| (address, chip8.peek_instruction_at(address)));
+    let after = chip8.peek_next((window.end.saturating_sub(pc) / 2) as usize);
+
+    before
+        .chain(after)
+        .filter_map(|(address, peek)| {
+            let (opcode, text) = disasm::disassemble_peek(&peek)?;
+            // a disabled breakpoint is hollow
+            let breakpoint = match (chip8.has_breakpoint(address), chip8.is_breakpoint_enabled(address)) {
+                (true, true) => "●",
+                (true, false) => "○",
+                _ => " ",
+            };
+            let marker = format!("{}{}", breakpoint, if address == pc { ">" } else { " " });
+
+            Some((address, format!("{} {:03X}  {:04X}  {}", marker, address, opcode, text)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chip8_emu::DEFAULT_STEP_BACK_INTERVAL;
+
+    #[test]
+    fn parses_hex_addresses() {
+        assert_eq!(parse_address("204"), Some(0x204));
+        assert_eq!(parse_address(" 0x2A0 "), Some(0x2A0));
+        assert_eq!(parse_address("$fff"), Some(0xFFF));
+        assert_eq!(parse_address("1000"), None);
+        assert_eq!(parse_address("draw"), None);
+        assert_eq!(parse_address(""), None);
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(Command::parse("b 204"), Ok(Command::Break(0x204)));
+        assert_eq!(Command::parse("  BREAK 0x2a0 "), Ok(Command::Break(0x2A0)));
+        assert_eq!(Command::parse("n"), Ok(Command::Next));
+        assert_eq!(Command::parse("RS"), Ok(Command::StepBack));
+        assert_eq!(Command::parse("set v3 2a"), Ok(Command::SetRegister(3, 0x2A)));
+        assert_eq!(Command::parse("set VF ff"), Ok(Command::SetRegister(0xF, 0xFF)));
+        assert_eq!(Command::parse("set i 300"), Ok(Command::SetI(0x300)));
+        assert_eq!(Command::parse("set pc $20a"), Ok(Command::SetPc(0x20A)));
+        assert_eq!(Command::parse("write 300 7"), Ok(Command::Write(0x300, 7)));
+        assert_eq!(Command::parse("disable 204"), Ok(Command::Disable(0x204)));
+        assert_eq!(Command::parse("enable 0x204"), Ok(Command::Enable(0x204)));
+        assert_eq!(Command::parse("wp f00"), Ok(Command::Watchpoint(0xF00)));
+        assert_eq!(Command::parse("UNWP f00"), Ok(Command::Unwatchpoint(0xF00)));
+    }
+
+    #[test]
+    fn rejects_bad_commands() {
+        for (line, expected) in [
+            ("", "type a command"),
+            ("jump 200", "unknown command `jump`"),
+            ("b", "expected an address"),
+            ("b 2000", "`2000` isn't an address"),
+            ("set v10 1", "`v10` isn't a register"),
+            ("set v1 100", "`100` isn't a byte"),
+            ("step 2", "too many arguments"),
+            ("write 300", "expected a byte"),
+        ] {
+            let error = Command::parse(line).unwrap_err();
+            assert!(error.contains(expected), "{:?} gave {:?}", line, error);
+        }
+    }
+
+    #[test]
+    fn parses_expression_commands() {
+        let condition = Expr::parse("v3 == 2").unwrap();
+
+        assert_eq!(Command::parse("b 204 if v3 == 2"), Ok(Command::BreakIf(0x204, condition.clone())));
+        assert_eq!(Command::parse("BREAK 0x204 IF v3==2"), Ok(Command::BreakIf(0x204, condition)));
+        assert_eq!(Command::parse("watch [i + 1]"), Ok(Command::Watch(Expr::parse("[i+1]").unwrap())));
+        assert_eq!(Command::parse("watch"), Ok(Command::Watches));
+        assert_eq!(Command::parse("unwatch 2"), Ok(Command::Unwatch(2)));
+
+        for (line, expected) in [
+            ("b 204 when v3", "expected `if`"),
+            ("b 204 if", "expected `if`"),
+            ("b 204 if v3 ==", "isn't an expression, column 6: expected a value"),
+            ("b 2000 if v3", "`2000` isn't an address"),
+            ("watch v3 +* 1", "unexpected `*`"),
+            ("unwatch 0", "isn't a watch number"),
+            ("unwatch", "expected a watch number"),
+        ] {
+            let error = Command::parse(line).unwrap_err();
+            assert!(error.contains(expected), "{:?} gave {:?}", line, error);
+        }
+    }
+
+    #[test]
+    fn watches_and_conditional_breaks() {
+        let mut chip8 = Chip8::new();
+        // V3 = 1, V3 += 1, loop
+        chip8.load(&[0x63, 0x01, 0x73, 0x01, 0x12, 0x02]).unwrap();
+
+        assert_eq!(Command::parse("watch").unwrap().execute(&mut chip8), "No watches, add one with watch EXPR");
+        assert_eq!(Command::parse("watch v3").unwrap().execute(&mut chip8), "Watch 1: v3 = 0 (0)");
+        assert_eq!(Command::parse("watch [0x1000]").unwrap().execute(&mut chip8), "Watch 2: [0x1000]: [0x1000] is outside of memory");
+        assert_eq!(Command::parse("b 202 if v3 == 0x20").unwrap().execute(&mut chip8), "Breakpoint at 202 if v3 == 0x20");
+
+        chip8.run_frame(100);
+        assert_eq!(chip8.take_breakpoint_hit(), Some(0x202));
+        assert_eq!(watch_lines(&chip8)[0], "1  v3 = 20 (32)");
+
+        assert_eq!(Command::Unwatch(2).execute(&mut chip8), "Removed watch 2: [0x1000]");
+        assert_eq!(Command::Unwatch(2).execute(&mut chip8), "There's no watch 2");
+        assert_eq!(Command::Watches.execute(&mut chip8), "1  v3 = 20 (32)");
+    }
+
+    #[test]
+    fn executes_against_the_machine() {
+        let mut chip8 = Chip8::new();
+        // V3 = 1, V3 += 1, loop
+        chip8.load(&[0x63, 0x01, 0x73, 0x01, 0x12, 0x02]).unwrap();
+
+        assert_eq!(Command::Break(0x204).execute(&mut chip8), "Breakpoint at 204");
+        assert_eq!(Command::Step.execute(&mut chip8), "PC 202");
+        assert!(chip8.is_paused());
+        assert_eq!(Command::SetRegister(3, 0x40).execute(&mut chip8), "V3 = 40");
+
+        Command::Continue.execute(&mut chip8);
+        chip8.run_frame(10);
+
+        assert_eq!(chip8.take_breakpoint_hit(), Some(0x204));
+        assert_eq!(chip8.registers()[3], 0x41);
+    }
+
+    #[test]
+    fn disables_breakpoints_and_sets_watchpoints() {
+        let mut chip8 = Chip8::new();
+        // V3 = 1, I = 300, store V0-V3, loop
+        chip8.load(&[0x63, 0x01, 0xA3, 0x00, 0xF3, 0x55, 0x12, 0x00]).unwrap();
+
+        assert_eq!(Command::Disable(0x202).execute(&mut chip8), "There's no breakpoint at 202");
+        Command::Break(0x202).execute(&mut chip8);
+        assert_eq!(Command::Disable(0x202).execute(&mut chip8), "Disabled breakpoint at 202");
+        assert_eq!(disassembly_lines(&chip8, 4)[3].1, "○  202  A300  LD I, 0x300");
+
+        assert_eq!(Command::Watchpoint(0x303).execute(&mut chip8), "Watchpoint at 303");
+        chip8.run_frame(10);
+        assert_eq!(chip8.take_breakpoint_hit(), None);
+        assert_eq!(chip8.take_watchpoint_hit(), Some(0x303));
+        assert_eq!(chip8.program_counter(), 0x206);
+
+        assert_eq!(Command::Enable(0x202).execute(&mut chip8), "Enabled breakpoint at 202");
+        assert_eq!(Command::Unwatchpoint(0x303).execute(&mut chip8), "Removed watchpoint at 303");
+        Command::Continue.execute(&mut chip8);
+        chip8.run_frame(10);
+        assert_eq!(chip8.take_breakpoint_hit(), Some(0x202));
+    }
+
+    #[test]
+    fn steps_back() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0x63, 0x01, 0x73, 0x01, 0x12, 0x02]).unwrap();
+        chip8.set_step_back_interval(DEFAULT_STEP_BACK_INTERVAL);
+
+        assert_eq!(Command::StepBack.execute(&mut chip8), "Nothing to step back to");
+        Command::Step.execute(&mut chip8);
+        Command::Step.execute(&mut chip8);
+        assert_eq!(Command::StepBack.execute(&mut chip8), "PC 202");
+        assert_eq!(chip8.registers()[3], 1);
+    }
+
+    #[test]
+    fn centers_the_disassembly_on_pc() {
+        assert_eq!(disassembly_window(0x300, 24), 0x2E8..0x318);
+        assert_eq!(disassembly_window(0x204, 24), 0x1EC..0x21C);
+        assert_eq!(disassembly_window(0x004, 24), 0x000..0x030);
+        assert_eq!(disassembly_window(0xFFE, 24), 0xFD0..0x1000);
+    }
+
+    #[test]
+    fn marks_pc_and_breakpoints() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0x63, 0x01, 0x73, 0x01, 0x12, 0x02]).unwrap();
+        chip8.add_breakpoint(0x202);
+
+        let lines = disassembly_lines(&chip8, 4);
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[1], (0x1FE, "   1FE  0000  NOP".to_string()));
+        assert_eq!(lines[2], (0x200, " > 200  6301  LD V3, 0x01".to_string()));
+        assert_eq!(lines[3], (0x202, "●  202  7301  ADD V3, 0x01".to_string()));
+    }
+
+    #[test]
+    fn lists_a_long_load_as_one_row() {
+        let mut chip8 = Chip8::new();
+        // I = 0x0300 as a long load, then a jump to the start
+        chip8.load(&[0xF0, 0x00, 0x03, 0x00, 0x12, 0x00]).unwrap();
+
+        let lines = disassembly_lines(&chip8, 4);
+        assert_eq!(lines[2], (0x200, " > 200  F000  LD I, 0x0300".to_string()));
+        assert_eq!(lines[3], (0x204, "   204  1200  JMP 0x200".to_string()));
+    }
+}
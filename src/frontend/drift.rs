@@ -0,0 +1,95 @@
+// keeps the audio queue near a target depth by nudging how many samples are generated per frame.
+// the emulator's frame clock and the sound card's sample clock never agree exactly, left alone the
+// queue slowly fills up (latency creep) or runs dry (crackle)
+pub struct DriftController {
+    target: usize,
+    // largest fraction a single frame may be stretched or squeezed by, small enough to be inaudible
+    max_correction: f32,
+    // carries rounding leftovers so corrections average out exactly
+    remainder: f32,
+}
+
+// how much of the queue error to correct per second of frames
+const GAIN: f32 = 0.05;
+
+impl DriftController {
+    pub fn new(target: usize, max_correction: f32) -> Self {
+        Self {
+            target,
+            max_correction,
+            remainder: 0.0,
+        }
+    }
+
+    // number of samples to generate this frame given the samples still queued for the device
+    pub fn samples_for_frame(&mut self, nominal: usize, queued: usize) -> usize {
+        let error = (self.target as f32 - queued as f32) / self.target.max(1) as f32;
+        let correction = (error * GAIN).clamp(-self.max_correction, self.max_correction);
+        let exact = nominal as f32 * (1.0 + correction) + self.remainder;
+        let samples = exact.round().max(0.0);
+
+        self.remainder = exact - samples;
+
+        samples as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOMINAL: usize = 735;
+    const TARGET: usize = NOMINAL * 2;
+
+    // feed the controller a device that consumes `consumed_per_frame` samples every frame
+    fn simulate(consumed_per_frame: f32, frames: usize) -> Vec<usize> {
+        let mut controller = DriftController::new(TARGET, 0.005);
+        let mut queued = TARGET as f32;
+        let mut history = Vec::new();
+
+        for _ in 0..frames {
+            queued += controller.samples_for_frame(NOMINAL, queued as usize) as f32;
+            queued = (queued - consumed_per_frame).max(0.0);
+            history.push(queued as usize);
+        }
+
+        history
+    }
+
+    #[test]
+    fn leaves_a_balanced_queue_alone() {
+        let mut controller = DriftController::new(TARGET, 0.005);
+
+        for _ in 0..100 {
+            assert_eq!(controller.samples_for_frame(NOMINAL, TARGET), NOMINAL);
+        }
+    }
+
+    #[test]
+    fn stops_latency_creep_from_a_slow_device() {
+        // device clock 0.2% slow, uncorrected this adds ~1.5 samples every frame
+        let history = simulate(NOMINAL as f32 * 0.998, 60 * 60 * 10);
+        let settled = &history[history.len() - 600..];
+
+        assert!(settled.iter().all(|&queued| queued.abs_diff(TARGET) < NOMINAL / 2));
+    }
+
+    #[test]
+    fn stops_starvation_from_a_fast_device() {
+        let history = simulate(NOMINAL as f32 * 1.002, 60 * 60 * 10);
+
+        assert!(history.iter().all(|&queued| queued > 0));
+        assert!(history[history.len() - 600..].iter().all(|&queued| queued.abs_diff(TARGET) < NOMINAL / 2));
+    }
+
+    #[test]
+    fn corrections_are_bounded() {
+        let mut controller = DriftController::new(TARGET, 0.005);
+
+        let empty = controller.samples_for_frame(NOMINAL, 0);
+        let full = controller.samples_for_frame(NOMINAL, TARGET * 10);
+
+        assert!(empty <= NOMINAL + 4);
+        assert!(full >= NOMINAL - 4);
+    }
+}
@@ -0,0 +1,412 @@
+use super::cli::Config;
+use super::debug_sessions;
+use super::debugger::{self, Command, MEMORY_SIZE};
+use super::timestep::Timestep;
+use super::title;
+
+use chip8_emu::expr::Expr;
+use chip8_emu::{Chip8, DEFAULT_STEP_BACK_INTERVAL, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use std::ops::Range;
+use std::time::Instant;
+
+use eframe::egui;
+
+// instructions listed in the disassembly, PC sits in the middle
+const DISASSEMBLY_ROWS: u16 = 24;
+const MEMORY_ROWS: u16 = 16;
+const MEMORY_COLUMNS: u16 = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Follow {
+    Pc,
+    I,
+}
+
+struct DebuggerApp {
+    chip8: Chip8,
+    config: Config,
+    keypad: Vec<Option<egui::Key>>,
+    timestep: Timestep,
+    screen: Option<egui::TextureHandle>,
+    rgba: Vec<u8>,
+    follow: Follow,
+    new_breakpoint: String,
+    new_watch: String,
+    command: String,
+    status: String,
+}
+
+pub fn run(config: Config, buffer: Vec<u8>) -> Result<(), String> {
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title(format!("{} - {}", title::display_name(&config.rom), title::APP_NAME))
+            .with_inner_size([1200.0, 640.0]),
+        ..Default::default()
+    };
+    let app = DebuggerApp::new(config, &buffer)?;
+
+    eframe::run_native(title::APP_NAME, options, Box::new(move |_| Ok(Box::new(app))))
+    .map_err(|e| format!("Unable to open the debugger window: {}", e))
+}
+
+impl DebuggerApp {
+    fn new(config: Config, buffer: &[u8]) -> Result<Self, String> {
+        let mut chip8 = Chip8::new();
+        chip8.load_named(buffer, &config.rom).map_err(|e| format!("Unable to load {}: {}", config.rom, e))?;
+        chip8.set_quirks(config.initial_quirks());
+
+        if let Some(seed) = config.seed {
+            chip8.set_seed(seed);
+        }
+
+        chip8.set_beep_config(config.beep);
+        chip8.set_paused(config.is_start_paused);
+        chip8.set_step_back_interval(DEFAULT_STEP_BACK_INTERVAL);
+        debug_sessions::restore(&mut chip8);
+
+        for &address in &config.breakpoints {
+            chip8.add_breakpoint(address);
+        }
+
+        Ok(Self {
+            chip8,
+            keypad: config.bindings.keypad_names().iter().map(|name| egui::Key::from_name(name)).collect(),
+            config,
+            timestep: Timestep::new(Instant::now()),
+            screen: None,
+            rgba: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT * 4],
+            follow: Follow::Pc,
+            new_breakpoint: String::new(),
+            new_watch: String::new(),
+            command: String::new(),
+            status: String::new(),
+        })
+    }
+
+    fn emulate(&mut self, ctx: &egui::Context) {
+        // typing an address shouldn't press keypad keys
+        let is_typing = ctx.wants_keyboard_input();
+
+        for (index, key) in self.keypad.iter().enumerate() {
+            let is_down = !is_typing && key.is_some_and(|key| ctx.input(|input| input.key_down(key)));
+            self.chip8.keypress(index, is_down);
+        }
+
+        let frames = self.timestep.frames(Instant::now());
+
+        for _ in 0..frames {
+            self.chip8.run_frame(self.config.ticks_per_frame);
+
+            if let Some(address) = self.chip8.take_breakpoint_hit() {
+                self.status = format!("Breakpoint at {:03X}", address);
+            }
+
+            if let Some(address) = self.chip8.take_watchpoint_hit() {
+                self.status = format!("Watchpoint at {:03X} written", address);
+            }
+        }
+    }
+
+    fn controls(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let run_label = if self.chip8.is_paused() { "Run" } else { "Pause" };
+
+            if ui.button(run_label).clicked() {
+                self.chip8.set_paused(!self.chip8.is_paused());
+                self.status.clear();
+            }
+
+            ui.add_enabled_ui(self.chip8.is_paused(), |ui| {
+                if ui.button("Step back").clicked() && !self.chip8.step_back() {
+                    self.status = "Nothing to step back to".to_string();
+                }
+
+                if ui.button("Step").clicked() {
+                    if let Err(error) = self.chip8.tick() {
+                        self.status = format!("Can't run that: {}", error);
+                    }
+                }
+
+                if ui.button("Step over").clicked() {
+                    match self.chip8.step_over() {
+                        Ok(true) => {},
+                        Ok(false) => {
+                            self.status = match self.chip8.take_breakpoint_hit() {
+                                Some(address) => format!("Breakpoint at {:03X}", address),
+                                None => "The subroutine didn't return".to_string(),
+                            };
+                        },
+                        Err(error) => self.status = format!("Can't run that: {}", error),
+                    }
+                }
+            });
+
+            if ui.button("Reset").clicked() {
+                self.chip8.soft_reset();
+            }
+        });
+
+        // the same commands as the terminal debugger
+        let field = ui.add(egui::TextEdit::singleline(&mut self.command).hint_text("command, e.g. b 204"));
+
+        if field.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+            self.status = match Command::parse(&self.command) {
+                Ok(Command::Quit) => {
+                    ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close);
+                    String::new()
+                },
+                Ok(command) => command.execute(&mut self.chip8),
+                Err(message) => message,
+            };
+            self.command.clear();
+        }
+
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+    }
+
+    fn registers(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("registers").num_columns(8).striped(true).show(ui, |ui| {
+            for index in 0..16 {
+                let mut value = self.chip8.registers()[index];
+                ui.monospace(format!("V{:X}", index));
+
+                if ui.add(egui::DragValue::new(&mut value).hexadecimal(2, false, true)).changed() {
+                    self.chip8.set_register(index, value);
+                }
+
+                if index % 4 == 3 {
+                    ui.end_row();
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let mut i = self.chip8.register_i();
+            ui.monospace("I");
+
+            if ui.add(egui::DragValue::new(&mut i).hexadecimal(3, false, true).range(0..=0xFFF)).changed() {
+                self.chip8.set_register_i(i);
+            }
+
+            let mut pc = self.chip8.program_counter();
+            ui.monospace("PC");
+
+            if ui.add(egui::DragValue::new(&mut pc).hexadecimal(3, false, true).range(0..=0xFFE)).changed() {
+                self.chip8.set_program_counter(pc);
+            }
+        });
+
+        ui.monospace(format!(
+            "SP {:X}  DT {:02X}  ST {:02X}",
+            self.chip8.stack_pointer(),
+            self.chip8.delay_timer(),
+            self.chip8.sound_timer()
+        ));
+    }
+
+    fn breakpoints(&mut self, ui: &mut egui::Ui) {
+        let mut removed = None;
+        let mut toggled = None;
+        let mut removed_watchpoint = None;
+
+        for address in self.chip8.breakpoints() {
+            ui.horizontal(|ui| {
+                let mut is_enabled = self.chip8.is_breakpoint_enabled(address);
+
+                if ui.checkbox(&mut is_enabled, "").changed() {
+                    toggled = Some((address, is_enabled));
+                }
+
+                match self.chip8.breakpoint_condition(address) {
+                    Some(condition) => ui.monospace(format!("{:03X} if {}", address, condition)),
+                    None => ui.monospace(format!("{:03X}", address)),
+                };
+
+                if ui.small_button("x").clicked() {
+                    removed = Some(address);
+                }
+            });
+        }
+
+        for address in self.chip8.watchpoints() {
+            ui.horizontal(|ui| {
+                ui.monospace(format!("{:03X} written", address));
+
+                if ui.small_button("x").clicked() {
+                    removed_watchpoint = Some(address);
+                }
+            });
+        }
+
+        if let Some(address) = removed {
+            self.chip8.remove_breakpoint(address);
+        }
+
+        if let Some((address, is_enabled)) = toggled {
+            self.chip8.set_breakpoint_enabled(address, is_enabled);
+        }
+
+        if let Some(address) = removed_watchpoint {
+            self.chip8.remove_watchpoint(address);
+        }
+
+        ui.horizontal(|ui| {
+            let field = ui.add(egui::TextEdit::singleline(&mut self.new_breakpoint).desired_width(60.0).hint_text("addr"));
+            let is_submitted = field.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+
+            if ui.button("Add").clicked() || is_submitted {
+                match debugger::parse_address(&self.new_breakpoint) {
+                    Some(address) => {
+                        self.chip8.add_breakpoint(address);
+                        self.new_breakpoint.clear();
+                    },
+                    None => self.status = format!("`{}` isn't an address", self.new_breakpoint),
+                }
+            }
+        });
+    }
+
+    fn watches(&mut self, ui: &mut egui::Ui) {
+        let mut removed = None;
+
+        for (index, expr) in self.chip8.watches().iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.monospace(debugger::watch_value(expr, &self.chip8));
+
+                if ui.small_button("x").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+
+        if let Some(index) = removed {
+            self.chip8.remove_watch(index);
+        }
+
+        ui.horizontal(|ui| {
+            let field = ui.add(egui::TextEdit::singleline(&mut self.new_watch).desired_width(160.0).hint_text("e.g. [i + 1]"));
+            let is_submitted = field.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+
+            if ui.button("Add").clicked() || is_submitted {
+                match Expr::parse(&self.new_watch) {
+                    Ok(expr) => {
+                        self.chip8.add_watch(expr);
+                        self.new_watch.clear();
+                    },
+                    Err(e) => self.status = format!("`{}` isn't an expression, {}", self.new_watch, e),
+                }
+            }
+        });
+    }
+
+    fn disassembly(&mut self, ui: &mut egui::Ui) {
+        let pc = self.chip8.program_counter();
+
+        for (address, text) in debugger::disassembly_lines(&self.chip8, DISASSEMBLY_ROWS) {
+            // clicking a line toggles its breakpoint
+            if ui.selectable_label(address == pc, egui::RichText::new(text).monospace()).clicked() {
+                self.chip8.toggle_breakpoint(address);
+            }
+        }
+    }
+
+    fn memory(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Follow");
+            ui.radio_value(&mut self.follow, Follow::Pc, "PC");
+            ui.radio_value(&mut self.follow, Follow::I, "I");
+        });
+
+        let target = match self.follow {
+            Follow::Pc => self.chip8.program_counter(),
+            Follow::I => self.chip8.register_i(),
+        };
+        let memory = self.chip8.memory();
+
+        for start in memory_window(target).step_by(MEMORY_COLUMNS as usize) {
+            let bytes: Vec<String> = (start..start + MEMORY_COLUMNS)
+                .map(|address| {
+                    let byte = memory[address as usize];
+                    if address == target { format!("[{:02X}]", byte) } else { format!(" {:02X} ", byte) }
+                })
+                .collect();
+
+            ui.monospace(format!("{:03X} {}", start, bytes.concat()));
+        }
+    }
+
+    fn game(&mut self, ui: &mut egui::Ui) {
+        self.chip8.render_rgba(self.config.palette, &mut self.rgba);
+        let image = egui::ColorImage::from_rgba_unmultiplied([SCREEN_WIDTH, SCREEN_HEIGHT], &self.rgba);
+
+        let texture = match self.screen.as_mut() {
+            Some(texture) => {
+                texture.set(image, egui::TextureOptions::NEAREST);
+                texture
+            },
+            None => self.screen.insert(ui.ctx().load_texture("screen", image, egui::TextureOptions::NEAREST)),
+        };
+
+        // the same whole-number scaling as the SDL window
+        let available = ui.available_size();
+        let scale = (available.x / SCREEN_WIDTH as f32).min(available.y / SCREEN_HEIGHT as f32).floor().max(1.0);
+        let size = egui::vec2(SCREEN_WIDTH as f32 * scale, SCREEN_HEIGHT as f32 * scale);
+
+        ui.centered_and_justified(|ui| ui.image((texture.id(), size)));
+    }
+}
+
+impl eframe::App for DebuggerApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.emulate(ctx);
+
+        egui::SidePanel::right("debugger").min_width(360.0).show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                self.controls(ui);
+                ui.separator();
+                self.registers(ui);
+                ui.separator();
+                ui.collapsing("Breakpoints", |ui| self.breakpoints(ui));
+                ui.collapsing("Watches", |ui| self.watches(ui));
+                egui::CollapsingHeader::new("Disassembly")
+                    .default_open(true)
+                    .show(ui, |ui| self.disassembly(ui));
+                ui.collapsing("Memory", |ui| self.memory(ui));
+            });
+        });
+
+        egui::CentralPanel::default()
+            .frame(egui::Frame::none().fill(egui::Color32::BLACK))
+            .show(ctx, |ui| self.game(ui));
+
+        ctx.request_repaint();
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        debug_sessions::remember(&self.chip8);
+    }
+}
+
+// the 8-byte-aligned rows of the hex view with `target` in the first half
+pub fn memory_window(target: u16) -> Range<u16> {
+    let length = MEMORY_ROWS * MEMORY_COLUMNS;
+    let row = target - target % MEMORY_COLUMNS;
+    let start = row.saturating_sub(MEMORY_COLUMNS * 4).min(MEMORY_SIZE - length);
+
+    start..start + length
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligns_the_memory_view() {
+        assert_eq!(memory_window(0x345), 0x320..0x3A0);
+        assert_eq!(memory_window(0x010), 0x000..0x080);
+        assert_eq!(memory_window(0xFFF), 0xF80..0x1000);
+    }
+}
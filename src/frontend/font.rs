@@ -0,0 +1,168 @@
+// a 3x5 bitmap font for debug text, drawn as rectangles so no font library is needed
+
+pub const GLYPH_WIDTH: u32 = 3;
+pub const GLYPH_HEIGHT: u32 = 5;
+// one blank column and row between glyphs
+pub const ADVANCE: u32 = GLYPH_WIDTH + 1;
+pub const LINE_HEIGHT: u32 = GLYPH_HEIGHT + 1;
+
+// each row is 3 bits, 0b100 is the leftmost pixel
+const GLYPHS: &[(char, [u8; 5])] = &[
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b111, 0b001, 0b111, 0b100, 0b111]),
+    ('3', [0b111, 0b001, 0b111, 0b001, 0b111]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b111, 0b001, 0b111]),
+    ('6', [0b111, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b010, 0b010, 0b010]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b111]),
+    ('A', [0b010, 0b101, 0b111, 0b101, 0b101]),
+    ('B', [0b110, 0b101, 0b110, 0b101, 0b110]),
+    ('C', [0b011, 0b100, 0b100, 0b100, 0b011]),
+    ('D', [0b110, 0b101, 0b101, 0b101, 0b110]),
+    ('E', [0b111, 0b100, 0b110, 0b100, 0b111]),
+    ('F', [0b111, 0b100, 0b110, 0b100, 0b100]),
+    ('G', [0b011, 0b100, 0b101, 0b101, 0b011]),
+    ('H', [0b101, 0b101, 0b111, 0b101, 0b101]),
+    ('I', [0b111, 0b010, 0b010, 0b010, 0b111]),
+    ('J', [0b001, 0b001, 0b001, 0b101, 0b010]),
+    ('K', [0b101, 0b101, 0b110, 0b101, 0b101]),
+    ('L', [0b100, 0b100, 0b100, 0b100, 0b111]),
+    ('M', [0b101, 0b111, 0b111, 0b101, 0b101]),
+    ('N', [0b110, 0b101, 0b101, 0b101, 0b101]),
+    ('O', [0b010, 0b101, 0b101, 0b101, 0b010]),
+    ('P', [0b110, 0b101, 0b110, 0b100, 0b100]),
+    ('Q', [0b010, 0b101, 0b101, 0b110, 0b011]),
+    ('R', [0b110, 0b101, 0b110, 0b101, 0b101]),
+    ('S', [0b011, 0b100, 0b010, 0b001, 0b110]),
+    ('T', [0b111, 0b010, 0b010, 0b010, 0b010]),
+    ('U', [0b101, 0b101, 0b101, 0b101, 0b111]),
+    ('V', [0b101, 0b101, 0b101, 0b101, 0b010]),
+    ('W', [0b101, 0b101, 0b111, 0b111, 0b101]),
+    ('X', [0b101, 0b101, 0b010, 0b101, 0b101]),
+    ('Y', [0b101, 0b101, 0b010, 0b010, 0b010]),
+    ('Z', [0b111, 0b001, 0b010, 0b100, 0b111]),
+    (' ', [0b000, 0b000, 0b000, 0b000, 0b000]),
+    (':', [0b000, 0b010, 0b000, 0b010, 0b000]),
+    (',', [0b000, 0b000, 0b000, 0b010, 0b100]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+    ('+', [0b000, 0b010, 0b111, 0b010, 0b000]),
+    ('=', [0b000, 0b111, 0b000, 0b111, 0b000]),
+    ('<', [0b001, 0b010, 0b100, 0b010, 0b001]),
+    ('>', [0b100, 0b010, 0b001, 0b010, 0b100]),
+    ('[', [0b110, 0b100, 0b100, 0b100, 0b110]),
+    (']', [0b011, 0b001, 0b001, 0b001, 0b011]),
+    ('/', [0b001, 0b001, 0b010, 0b100, 0b100]),
+    ('?', [0b111, 0b001, 0b010, 0b000, 0b010]),
+];
+
+// lowercase is drawn as uppercase, anything else unknown as '?'
+pub fn glyph(c: char) -> [u8; 5] {
+    let c = c.to_ascii_uppercase();
+
+    let find = |c: char| GLYPHS.iter().find(|(known, _)| *known == c).map(|&(_, rows)| rows);
+
+    find(c).or_else(|| find('?')).unwrap()
+}
+
+pub fn text_width(text: &str) -> u32 {
+    match text.chars().count() as u32 {
+        0 => 0,
+        count => count * ADVANCE - 1,
+    }
+}
+
+// `text` split into lines of at most `max_chars`, at spaces where it can be and mid-word where it can't
+pub fn wrap(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        let chars: Vec<char> = word.chars().collect();
+
+        for piece in chars.chunks(max_chars) {
+            if !line.is_empty() && line.chars().count() + 1 + piece.len() > max_chars {
+                lines.push(std::mem::take(&mut line));
+            }
+
+            if !line.is_empty() {
+                line.push(' ');
+            }
+
+            line.extend(piece);
+        }
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+// font pixels lit by one line of `text`, relative to its top left corner
+pub fn text_pixels(text: &str) -> impl Iterator<Item = (u32, u32)> + '_ {
+    text.chars().enumerate().flat_map(|(column, c)| {
+        let rows = glyph(c);
+
+        (0..GLYPH_HEIGHT).flat_map(move |y| {
+            (0..GLYPH_WIDTH)
+                .filter(move |&x| rows[y as usize] & (0b100 >> x) != 0)
+                .map(move |x| (column as u32 * ADVANCE + x, y))
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // renders to ascii art so the expected patterns can be read
+    fn render(text: &str) -> Vec<String> {
+        let mut rows = vec![vec!['.'; text_width(text) as usize]; GLYPH_HEIGHT as usize];
+
+        for (x, y) in text_pixels(text) {
+            rows[y as usize][x as usize] = '#';
+        }
+
+        rows.into_iter().map(|row| row.into_iter().collect()).collect()
+    }
+
+    #[test]
+    fn draws_known_patterns() {
+        assert_eq!(render("1"), [".#.", "##.", ".#.", ".#.", "###"]);
+        assert_eq!(render("PC"), ["##...##", "#.#.#..", "##..#..", "#...#..", "#....##"]);
+    }
+
+    #[test]
+    fn spaces_glyphs_one_column_apart() {
+        assert_eq!(render("-="), [".......", "....###", "###....", "....###", "......."]);
+        assert_eq!(text_width(""), 0);
+        assert_eq!(text_width("V0"), 7);
+    }
+
+    #[test]
+    fn folds_case_and_marks_unknown_characters() {
+        assert_eq!(glyph('a'), glyph('A'));
+        assert_eq!(glyph('~'), glyph('?'));
+        assert_eq!(render(" ").concat(), "...............");
+    }
+
+    #[test]
+    fn wraps_at_spaces_and_splits_long_words() {
+        assert_eq!(wrap("the ROM is empty", 10), ["the ROM is", "empty"]);
+        assert_eq!(wrap("roms/games/pong.ch8: gone", 8), ["roms/gam", "es/pong.", "ch8:", "gone"]);
+        assert!(wrap("", 8).is_empty());
+    }
+
+    #[test]
+    fn every_glyph_fits_in_three_columns() {
+        for (c, rows) in GLYPHS {
+            assert!(rows.iter().all(|row| *row < 0b1000), "{} is too wide", c);
+        }
+    }
+}
@@ -0,0 +1,99 @@
+// the screen as text: each character cell holds two pixels stacked with half blocks
+
+use chip8_emu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+pub const ROWS: usize = SCREEN_HEIGHT / 2;
+
+pub fn cell(top: bool, bottom: bool) -> char {
+    match (top, bottom) {
+        (true, true) => '█',
+        (true, false) => '▀',
+        (false, true) => '▄',
+        (false, false) => ' ',
+    }
+}
+
+// for fonts without block characters
+#[cfg(feature = "terminal")]
+pub fn ascii_cell(top: bool, bottom: bool) -> char {
+    match (top, bottom) {
+        (true, true) => '#',
+        (true, false) => '"',
+        (false, true) => ',',
+        (false, false) => ' ',
+    }
+}
+
+// the top and bottom pixel shown in a character cell
+pub fn pixels(screen: &[bool], column: usize, row: usize) -> (bool, bool) {
+    let top = row * 2 * SCREEN_WIDTH + column;
+    (screen[top], screen[top + SCREEN_WIDTH])
+}
+
+// 64x16 characters for the 64x32 display
+#[cfg(feature = "tui")]
+pub fn rows(screen: &[bool]) -> Vec<String> {
+    (0..ROWS)
+        .map(|row| {
+            (0..SCREEN_WIDTH)
+                .map(|column| {
+                    let (top, bottom) = pixels(screen, column, row);
+                    cell(top, bottom)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stacks_two_pixels_per_cell() {
+        assert_eq!(cell(true, true), '█');
+        assert_eq!(cell(true, false), '▀');
+        assert_eq!(cell(false, true), '▄');
+        assert_eq!(cell(false, false), ' ');
+    }
+
+    #[cfg(feature = "terminal")]
+    #[test]
+    fn has_an_ascii_fallback() {
+        assert_eq!(ascii_cell(true, true), '#');
+        assert_eq!(ascii_cell(true, false), '"');
+        assert_eq!(ascii_cell(false, true), ',');
+        assert_eq!(ascii_cell(false, false), ' ');
+    }
+
+    #[test]
+    fn pairs_pixels_by_cell() {
+        let mut screen = vec![false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        screen[3 * SCREEN_WIDTH + 5] = true;
+
+        assert_eq!(pixels(&screen, 5, 1), (false, true));
+        assert_eq!(pixels(&screen, 5, 0), (false, false));
+        assert_eq!(pixels(&screen, 4, 1), (false, false));
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn renders_the_whole_screen() {
+        let mut screen = vec![false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        // top left pixel, the pixel under it one column over, and a full column at the right edge
+        screen[0] = true;
+        screen[SCREEN_WIDTH + 1] = true;
+
+        for y in 0..SCREEN_HEIGHT {
+            screen[y * SCREEN_WIDTH + SCREEN_WIDTH - 1] = true;
+        }
+
+        let rows = rows(&screen);
+
+        assert_eq!(rows.len(), ROWS);
+        assert!(rows.iter().all(|row| row.chars().count() == SCREEN_WIDTH));
+        assert!(rows[0].starts_with("▀▄ "));
+        assert!(rows.iter().all(|row| row.ends_with(" █")));
+        assert_eq!(rows[1].trim_end_matches('█').trim(), "");
+    }
+}
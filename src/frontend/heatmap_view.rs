@@ -0,0 +1,138 @@
+// the heatmap overlay: all of memory as a 64x64 grid, a cell a byte, lit by how much it's been
+// written lately. each frame's writes add to a cell's heat and the heat halves every HALF_LIFE
+// frames, so a buffer rewritten every frame glows steadily and a score flashes when it changes
+
+use chip8_emu::{Chip8, Heatmap};
+
+// cells a row, and rows
+pub const GRID_SIZE: u32 = 64;
+// frames for a cell's heat to halve
+const HALF_LIFE: f32 = 15.0;
+// the heat that shows as halfway up the colors, a byte written once a frame settles around 22
+const WARM: f32 = 4.0;
+
+// `heat` after `frames` more frames that wrote the byte `writes` times
+pub fn decay(heat: f32, frames: u32, writes: u32) -> f32 {
+    heat * 0.5f32.powf(frames as f32 / HALF_LIFE) + writes as f32
+}
+
+// black through red and yellow to white, None for a cell too cold to show
+pub fn color(heat: f32) -> Option<(u8, u8, u8)> {
+    // 0 to 1, never quite reaching 1 however hot
+    let t = 1.0 - (-heat / (WARM / std::f32::consts::LN_2)).exp();
+    let channel = |from: f32| ((t * 3.0 - from).clamp(0.0, 1.0) * 255.0).round() as u8;
+
+    let rgb = (channel(0.0), channel(1.0), channel(2.0));
+    (rgb != (0, 0, 0)).then_some(rgb)
+}
+
+// the address of the cell at (column, row)
+pub fn address(column: u32, row: u32) -> u16 {
+    (row * GRID_SIZE + column) as u16
+}
+
+// what hovering a cell shows
+pub fn describe(chip8: &Chip8, address: u16) -> String {
+    let value = chip8.memory().get(address as usize).copied().unwrap_or(0);
+
+    format!("{:03X} = {:02X}, written {} times", address, value, chip8.heatmap().writes(address))
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemoryHeat {
+    heat: Vec<f32>,
+    // the write counts at the last update, to tell what's new
+    seen: Vec<u32>,
+}
+
+impl MemoryHeat {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // after `frames` frames ran, 0 while paused holds the picture still
+    pub fn update(&mut self, heatmap: &Heatmap, frames: u32) {
+        let size = (GRID_SIZE * GRID_SIZE) as usize;
+        self.heat.resize(size, 0.0);
+        self.seen.resize(size, 0);
+
+        for (address, (heat, seen)) in self.heat.iter_mut().zip(&mut self.seen).enumerate() {
+            let writes = heatmap.writes(address as u16);
+            // counts only go down when they were cleared, all of them are new since
+            let new = if writes >= *seen { writes - *seen } else { writes };
+
+            *heat = decay(*heat, frames, new);
+            *seen = writes;
+        }
+    }
+
+    pub fn heat(&self, address: u16) -> f32 {
+        self.heat.get(address as usize).copied().unwrap_or(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn halves_every_half_life() {
+        assert_eq!(decay(8.0, 15, 0), 4.0);
+        assert_eq!(decay(8.0, 30, 0), 2.0);
+        assert_eq!(decay(8.0, 0, 3), 11.0);
+
+        // written once a frame, it settles where the fading matches the writes
+        let settled = (0..1000).fold(0.0, |heat, _| decay(heat, 1, 1));
+        assert!((decay(settled, 1, 1) - settled).abs() < 0.001);
+        assert!((21.0..23.0).contains(&settled));
+    }
+
+    #[test]
+    fn colors_go_from_nothing_to_white() {
+        assert_eq!(color(0.0), None);
+        assert_eq!(color(0.001), None);
+        // one write is a dim red
+        assert!(matches!(color(1.0), Some((r, 0, 0)) if r > 100 && r < 200));
+        assert!(matches!(color(WARM), Some((255, g, 0)) if (120..136).contains(&g)));
+        assert!(matches!(color(22.0), Some((255, 255, b)) if b > 200));
+
+        let reds: Vec<u8> = [0.2, 0.5, 1.0, 1.5].iter().map(|&heat| color(heat).unwrap().0).collect();
+        assert!(reds.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn heats_up_where_the_writes_are() {
+        // I = 300, store V0 there, loop
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0xA3, 0x00, 0xF0, 0x55, 0x12, 0x02]).unwrap();
+        chip8.set_heatmap_enabled(true);
+        let mut heat = MemoryHeat::new();
+
+        chip8.run_frame(5);
+        heat.update(chip8.heatmap(), 1);
+        assert_eq!(heat.heat(0x300), 2.0);
+        assert_eq!(heat.heat(0x301), 0.0);
+
+        // nothing new while paused
+        heat.update(chip8.heatmap(), 0);
+        assert_eq!(heat.heat(0x300), 2.0);
+
+        // and only what was written since, after the counts start over
+        chip8.clear_heatmap();
+        chip8.run_frame(2);
+        heat.update(chip8.heatmap(), 15);
+        assert_eq!(heat.heat(0x300), 2.0);
+    }
+
+    #[test]
+    fn cells_go_across_then_down() {
+        assert_eq!(address(0, 0), 0);
+        assert_eq!(address(63, 0), 0x3F);
+        assert_eq!(address(0, 8), 0x200);
+        assert_eq!(address(63, 63), 0xFFF);
+
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0x12, 0x00]).unwrap();
+        assert_eq!(describe(&chip8, 0x200), "200 = 12, written 0 times");
+    }
+}
@@ -0,0 +1,104 @@
+// terminals report key presses but usually not releases, so a key counts as held for a while after
+// each press. the first press waits out the auto-repeat delay, repeats then keep it alive. once the
+// terminal sends a real release (the kitty keyboard protocol) the timeouts are no longer used
+
+use std::time::{Duration, Instant};
+
+// longer than the usual 200-500 ms delay before a held key starts repeating would be laggy, so a
+// tap is short and a held key may flicker off once before its repeats arrive
+pub const FIRST_PRESS_HOLD: Duration = Duration::from_millis(200);
+// repeats come every 30-50 ms, this covers a couple of missed ones
+pub const REPEAT_HOLD: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug, Default)]
+pub struct HeldKeys {
+    // when each key lets go if no repeat or release arrives
+    until: [Option<Instant>; 16],
+    has_releases: bool,
+}
+
+impl HeldKeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn press(&mut self, key: usize, now: Instant) {
+        let hold = match self.until[key] {
+            Some(until) if until > now => REPEAT_HOLD,
+            _ => FIRST_PRESS_HOLD,
+        };
+
+        self.until[key] = Some(now + hold);
+    }
+
+    pub fn release(&mut self, key: usize) {
+        self.has_releases = true;
+        self.until[key] = None;
+    }
+
+    pub fn held(&self, now: Instant) -> [bool; 16] {
+        self.until.map(|until| until.is_some_and(|until| self.has_releases || until > now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(start: Instant, millis: u64) -> Instant {
+        start + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn a_tap_lets_go_after_the_first_press_hold() {
+        let start = Instant::now();
+        let mut keys = HeldKeys::new();
+        keys.press(5, start);
+
+        assert!(keys.held(ms(start, 199))[5]);
+        assert!(!keys.held(ms(start, 200))[5]);
+        assert_eq!(keys.held(ms(start, 100)).iter().filter(|&&held| held).count(), 1);
+    }
+
+    #[test]
+    fn repeats_keep_a_key_held() {
+        let start = Instant::now();
+        let mut keys = HeldKeys::new();
+        keys.press(0xA, start);
+
+        // auto-repeat kicks in at 150 ms, then every 40 ms
+        for millis in (150..600).step_by(40) {
+            keys.press(0xA, ms(start, millis));
+            assert!(keys.held(ms(start, millis + 39))[0xA]);
+        }
+
+        // the last repeat was at 590, so the key lets go 100 ms later
+        assert!(keys.held(ms(start, 689))[0xA]);
+        assert!(!keys.held(ms(start, 690))[0xA]);
+    }
+
+    #[test]
+    fn a_press_after_letting_go_starts_over() {
+        let start = Instant::now();
+        let mut keys = HeldKeys::new();
+        keys.press(1, start);
+        keys.press(1, ms(start, 500));
+
+        assert!(keys.held(ms(start, 650))[1]);
+    }
+
+    #[test]
+    fn real_releases_turn_the_timeouts_off() {
+        let start = Instant::now();
+        let mut keys = HeldKeys::new();
+        keys.press(2, start);
+        keys.release(2);
+        keys.press(3, start);
+
+        assert!(!keys.held(start)[2]);
+        assert!(keys.held(ms(start, 10_000))[3]);
+
+        keys.release(3);
+        assert!(!keys.held(ms(start, 10_000))[3]);
+    }
+}
@@ -0,0 +1,94 @@
+// looking at single pixels: the magnified region under the mouse and what a click reports
+
+use chip8_emu::{Chip8, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// the zoom shows this many pixels each way
+pub const ZOOM_SIZE: usize = 8;
+// the grid only goes in once there's room between the lines for the pixels themselves
+pub const MIN_GRID_SCALE: u32 = 4;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Zoom {
+    // top left of the region on the emulated screen
+    pub left: usize,
+    pub top: usize,
+    // the pixel under the mouse, in screen coordinates
+    pub hovered: (usize, usize),
+    pub pixels: [[bool; ZOOM_SIZE]; ZOOM_SIZE],
+}
+
+// the region around (x, y), moved in at the edges so it's always a full ZOOM_SIZE square
+pub fn zoom(chip8: &Chip8, x: usize, y: usize) -> Zoom {
+    let left = x.saturating_sub(ZOOM_SIZE / 2).min(SCREEN_WIDTH - ZOOM_SIZE);
+    let top = y.saturating_sub(ZOOM_SIZE / 2).min(SCREEN_HEIGHT - ZOOM_SIZE);
+    let mut pixels = [[false; ZOOM_SIZE]; ZOOM_SIZE];
+
+    for (row, line) in pixels.iter_mut().enumerate() {
+        for (column, pixel) in line.iter_mut().enumerate() {
+            *pixel = chip8.pixel(left + column, top + row).unwrap_or(false);
+        }
+    }
+
+    Zoom { left, top, hovered: (x, y), pixels }
+}
+
+pub fn screen_index(x: usize, y: usize) -> usize {
+    y * SCREEN_WIDTH + x
+}
+
+// what a click on a paused screen reports, in the coordinates DXYN uses
+pub fn describe(chip8: &Chip8, x: usize, y: usize) -> String {
+    let state = match chip8.pixel(x, y) {
+        Some(true) => "on",
+        _ => "off",
+    };
+
+    format!("x {}, y {}, index {}, {}", x, y, screen_index(x, y), state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // draw "0" at (0, 0)
+    const ROM: [u8; 2] = [0xD0, 0x05];
+
+    fn drawn() -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ROM).unwrap();
+        chip8.tick().unwrap();
+
+        chip8
+    }
+
+    #[test]
+    fn centers_the_zoom_on_the_mouse() {
+        let zoom = zoom(&drawn(), 20, 10);
+
+        assert_eq!((zoom.left, zoom.top), (16, 6));
+        assert_eq!(zoom.hovered, (20, 10));
+    }
+
+    #[test]
+    fn keeps_the_zoom_on_the_screen() {
+        let chip8 = drawn();
+
+        let corner = zoom(&chip8, 1, 2);
+        assert_eq!((corner.left, corner.top), (0, 0));
+        // the top row of "0" is 4 pixels wide
+        assert_eq!(corner.pixels[0][..5], [true, true, true, true, false]);
+        assert_eq!(corner.pixels[1][..5], [true, false, false, true, false]);
+
+        let corner = zoom(&chip8, SCREEN_WIDTH - 1, SCREEN_HEIGHT - 1);
+        assert_eq!((corner.left, corner.top), (SCREEN_WIDTH - ZOOM_SIZE, SCREEN_HEIGHT - ZOOM_SIZE));
+        assert!(corner.pixels.iter().flatten().all(|&pixel| !pixel));
+    }
+
+    #[test]
+    fn describes_a_pixel() {
+        let chip8 = drawn();
+
+        assert_eq!(describe(&chip8, 3, 4), "x 3, y 4, index 259, on");
+        assert_eq!(describe(&chip8, 63, 31), "x 63, y 31, index 2047, off");
+    }
+}
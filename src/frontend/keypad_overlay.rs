@@ -0,0 +1,171 @@
+// the hex keypad over the top right of the game, lit for each key the emulator sees as held,
+// whichever way it got there, with the host key bound to it under the digit
+
+use super::font;
+use super::paint::{self, Painter, Rgba};
+
+// the COSMAC VIP keypad as it sits on the device
+pub const LAYOUT: [[usize; 4]; 4] = [[0x1, 0x2, 0x3, 0xC], [0x4, 0x5, 0x6, 0xD], [0x7, 0x8, 0x9, 0xE], [0xA, 0x0, 0xB, 0xF]];
+
+const BACKGROUND: Rgba = (0, 0, 0, 190);
+const IDLE: Rgba = (60, 60, 60, 255);
+const PRESSED: Rgba = (255, 190, 0, 255);
+const DIGIT: Rgba = (255, 255, 255, 255);
+const PRESSED_DIGIT: Rgba = (0, 0, 0, 255);
+const LABEL: Rgba = (140, 140, 140, 255);
+const PRESSED_LABEL: Rgba = (110, 80, 0, 255);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub key: usize,
+    pub left: i32,
+    pub top: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Layout {
+    pub width: u32,
+    pub height: u32,
+    pub cells: Vec<Cell>,
+}
+
+// relative to the overlay's top left, `size` pixels per font pixel. cells are as wide as the
+// longest host key name so none gets cut off
+pub fn layout(labels: &[&str; 16], size: u32) -> Layout {
+    let margin = size * 2;
+    let gap = size;
+    let text_width = labels.iter().map(|label| font::text_width(label)).max().unwrap_or(0).max(font::GLYPH_WIDTH);
+    let width = text_width * size + size * 2;
+    let height = (font::LINE_HEIGHT + font::GLYPH_HEIGHT) * size + size * 2;
+
+    let cells = LAYOUT
+        .iter()
+        .enumerate()
+        .flat_map(|(row, keys)| {
+            keys.iter().enumerate().map(move |(column, &key)| Cell {
+                key,
+                left: (margin + column as u32 * (width + gap)) as i32,
+                top: (margin + row as u32 * (height + gap)) as i32,
+                width,
+                height,
+            })
+        })
+        .collect();
+
+    Layout { width: width * 4 + gap * 3 + margin * 2, height: height * 4 + gap * 3 + margin * 2, cells }
+}
+
+// `keys` is what the emulator has as held, `labels` the host key for each of them
+pub fn draw<P: Painter + ?Sized>(painter: &mut P, keys: &[bool], labels: &[&str; 16], left: i32, top: i32, size: u32) {
+    let layout = layout(labels, size);
+    painter.fill_rect(left, top, layout.width, layout.height, BACKGROUND);
+
+    for cell in &layout.cells {
+        let (fill, digit, label) = if keys[cell.key] { (PRESSED, PRESSED_DIGIT, PRESSED_LABEL) } else { (IDLE, DIGIT, LABEL) };
+        let (cell_left, cell_top) = (left + cell.left, top + cell.top);
+        painter.fill_rect(cell_left, cell_top, cell.width, cell.height, fill);
+
+        let inset = size as i32;
+        paint::text(painter, &format!("{:X}", cell.key), cell_left + inset, cell_top + inset, size, digit);
+        let label_top = cell_top + inset + (font::LINE_HEIGHT * size) as i32;
+        paint::text(painter, labels[cell.key], cell_left + inset, label_top, size, label);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::frontend::bindings::KeyBindings;
+    use crate::frontend::paint::RgbaPainter;
+
+    struct Frame {
+        rgba: Vec<u8>,
+        width: usize,
+    }
+
+    impl Frame {
+        fn pixel(&self, x: i32, y: i32) -> Rgba {
+            let at = (y as usize * self.width + x as usize) * 4;
+            (self.rgba[at], self.rgba[at + 1], self.rgba[at + 2], self.rgba[at + 3])
+        }
+    }
+
+    fn render(keys: &[bool], labels: &[&str; 16], size: u32) -> Frame {
+        let layout = layout(labels, size);
+        let (width, height) = (layout.width as usize + 10, layout.height as usize + 10);
+        let mut rgba = vec![0; width * height * 4];
+        draw(&mut RgbaPainter::new(&mut rgba, width, height), keys, labels, 10, 10, size);
+
+        Frame { rgba, width }
+    }
+
+    fn cell(labels: &[&str; 16], size: u32, key: usize) -> Cell {
+        *layout(labels, size).cells.iter().find(|cell| cell.key == key).unwrap()
+    }
+
+    #[test]
+    fn lays_out_the_vip_keypad() {
+        let labels = KeyBindings::default().keypad_names();
+        let layout = layout(&labels, 2);
+
+        let keys: Vec<usize> = layout.cells.iter().map(|cell| cell.key).collect();
+        assert_eq!(keys, [0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF]);
+
+        // one character names: 3 font pixels plus an inset each side, two lines tall
+        assert_eq!((layout.cells[0].width, layout.cells[0].height), (10, 26));
+        assert_eq!((layout.cells[0].left, layout.cells[0].top), (4, 4));
+        assert_eq!((layout.cells[5].left, layout.cells[5].top), (16, 32));
+        assert_eq!((layout.width, layout.height), (54, 118));
+    }
+
+    #[test]
+    fn widens_cells_for_long_key_names() {
+        let mut labels = KeyBindings::default().keypad_names();
+        labels[0x5] = "Keypad 5";
+
+        assert_eq!(layout(&labels, 1).cells[0].width, font::text_width("Keypad 5") + 2);
+    }
+
+    #[test]
+    fn lights_held_keys() {
+        let labels = KeyBindings::default().keypad_names();
+        let mut keys = [false; 16];
+        keys[0xA] = true;
+        let frame = render(&keys, &labels, 2);
+
+        let held = cell(&labels, 2, 0xA);
+        let idle = cell(&labels, 2, 0x0);
+        // the bottom right corner of a cell is never under text
+        let corner = |cell: Cell| (10 + cell.left + cell.width as i32 - 1, 10 + cell.top + cell.height as i32 - 1);
+
+        assert_eq!(frame.pixel(corner(held).0, corner(held).1), PRESSED);
+        assert_eq!(frame.pixel(corner(idle).0, corner(idle).1), IDLE);
+        // outside the overlay is left alone, the margin is the translucent background
+        assert_eq!(frame.pixel(0, 0), (0, 0, 0, 0));
+        assert_eq!(frame.pixel(10, 10), (0, 0, 0, 255));
+    }
+
+    #[test]
+    fn draws_the_digit_and_host_key() {
+        let labels = KeyBindings::default().keypad_names();
+        let mut keys = [false; 16];
+        keys[0x1] = true;
+        let frame = render(&keys, &labels, 1);
+
+        // key 1 is bound to "1": .#. on the top row of both the digit and the label
+        let one = cell(&labels, 1, 0x1);
+        let (left, top) = (10 + one.left + 1, 10 + one.top + 1);
+        assert_eq!(frame.pixel(left, top), PRESSED);
+        assert_eq!(frame.pixel(left + 1, top), PRESSED_DIGIT);
+        assert_eq!(frame.pixel(left + 1, top + font::LINE_HEIGHT as i32), PRESSED_LABEL);
+
+        // key C is bound to "4": #.# on its top row
+        let c = cell(&labels, 1, 0xC);
+        let (left, top) = (10 + c.left + 1, 10 + c.top + 1);
+        assert_eq!(frame.pixel(left, top + font::LINE_HEIGHT as i32), LABEL);
+        assert_eq!(frame.pixel(left + 1, top + font::LINE_HEIGHT as i32), IDLE);
+    }
+}
@@ -0,0 +1,313 @@
+// --latency-test: how long a key press takes to show. a built-in ROM inverts the screen as soon
+// as any key goes down, and each press is timed in two parts:
+//
+//   emulation     from the key event to the end of the emulated frame that first changed the screen
+//   presentation  from there to present() returning, drawing and waiting for the display
+//
+// a press is only timed once the screen has settled from the last one, and one nothing answers
+// within MAX_LATENCY is dropped, so a tap too quick for the ROM to see doesn't sit waiting
+
+use std::time::{Duration, Instant};
+
+pub const ROM_NAME: &str = "latency-test";
+
+// looks at every key in turn, inverts the screen 8x8 pixels at a time when one is down, then waits
+// for all of them to be let go. FX0A would only answer once the key comes back up
+pub const ROM: &[u8] = &[
+    0xA2, 0xA8, // LD I, 0x2A8        the key numbers
+    0xFF, 0x65, // LD VF, [I]         Vk = k
+    // 204: jump to the inversion if a key is down
+    0xE0, 0xA1, 0x12, 0x46, // SKNP V0, JMP 0x246
+    0xE1, 0xA1, 0x12, 0x46, // SKNP V1, JMP 0x246
+    0xE2, 0xA1, 0x12, 0x46, // SKNP V2, JMP 0x246
+    0xE3, 0xA1, 0x12, 0x46, // SKNP V3, JMP 0x246
+    0xE4, 0xA1, 0x12, 0x46, // SKNP V4, JMP 0x246
+    0xE5, 0xA1, 0x12, 0x46, // SKNP V5, JMP 0x246
+    0xE6, 0xA1, 0x12, 0x46, // SKNP V6, JMP 0x246
+    0xE7, 0xA1, 0x12, 0x46, // SKNP V7, JMP 0x246
+    0xE8, 0xA1, 0x12, 0x46, // SKNP V8, JMP 0x246
+    0xE9, 0xA1, 0x12, 0x46, // SKNP V9, JMP 0x246
+    0xEA, 0xA1, 0x12, 0x46, // SKNP VA, JMP 0x246
+    0xEB, 0xA1, 0x12, 0x46, // SKNP VB, JMP 0x246
+    0xEC, 0xA1, 0x12, 0x46, // SKNP VC, JMP 0x246
+    0xED, 0xA1, 0x12, 0x46, // SKNP VD, JMP 0x246
+    0xEE, 0xA1, 0x12, 0x46, // SKNP VE, JMP 0x246
+    0xEF, 0xA1, 0x12, 0x46, // SKNP VF, JMP 0x246
+    0x12, 0x04, // JMP 0x204
+    0xA2, 0xA0, // LD I, 0x2A0        the solid block
+    0x61, 0x00, // LD V1, 0x00        y
+    0x62, 0x00, // LD V2, 0x00        row: x
+    0xD2, 0x18, // DRW V2, V1, 8      block: invert it
+    0x72, 0x08, // ADD V2, 0x08
+    0x32, 0x40, // SE V2, 0x40        the row ends at x = 64
+    0x12, 0x4C, // JMP 0x24C
+    0x71, 0x08, // ADD V1, 0x08
+    0x31, 0x20, // SE V1, 0x20        the screen ends at y = 32
+    0x12, 0x4A, // JMP 0x24A
+    0xA2, 0xA8, // LD I, 0x2A8
+    0xFF, 0x65, // LD VF, [I]
+    // 25E: start over while a key is down
+    0xE0, 0xA1, 0x12, 0x5E, // SKNP V0, JMP 0x25E
+    0xE1, 0xA1, 0x12, 0x5E, // SKNP V1, JMP 0x25E
+    0xE2, 0xA1, 0x12, 0x5E, // SKNP V2, JMP 0x25E
+    0xE3, 0xA1, 0x12, 0x5E, // SKNP V3, JMP 0x25E
+    0xE4, 0xA1, 0x12, 0x5E, // SKNP V4, JMP 0x25E
+    0xE5, 0xA1, 0x12, 0x5E, // SKNP V5, JMP 0x25E
+    0xE6, 0xA1, 0x12, 0x5E, // SKNP V6, JMP 0x25E
+    0xE7, 0xA1, 0x12, 0x5E, // SKNP V7, JMP 0x25E
+    0xE8, 0xA1, 0x12, 0x5E, // SKNP V8, JMP 0x25E
+    0xE9, 0xA1, 0x12, 0x5E, // SKNP V9, JMP 0x25E
+    0xEA, 0xA1, 0x12, 0x5E, // SKNP VA, JMP 0x25E
+    0xEB, 0xA1, 0x12, 0x5E, // SKNP VB, JMP 0x25E
+    0xEC, 0xA1, 0x12, 0x5E, // SKNP VC, JMP 0x25E
+    0xED, 0xA1, 0x12, 0x5E, // SKNP VD, JMP 0x25E
+    0xEE, 0xA1, 0x12, 0x5E, // SKNP VE, JMP 0x25E
+    0xEF, 0xA1, 0x12, 0x5E, // SKNP VF, JMP 0x25E
+    0x12, 0x04, // JMP 0x204
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, // 2A0: the block
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, // 2A8: the key numbers
+    0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F,
+];
+
+// a press that takes longer than this to show wasn't seen by the ROM
+const MAX_LATENCY: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sample {
+    pub emulation: Duration,
+    pub presentation: Duration,
+}
+
+impl Sample {
+    pub fn total(&self) -> Duration {
+        self.emulation + self.presentation
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Distribution {
+    pub min: Duration,
+    pub median: Duration,
+    pub p95: Duration,
+}
+
+// nearest rank, None for no values
+pub fn distribution(values: &[Duration]) -> Option<Distribution> {
+    let mut sorted = values.to_vec();
+    sorted.sort();
+
+    let rank = |percent: usize| sorted[(sorted.len() * percent).div_ceil(100).max(1) - 1];
+
+    Some(Distribution { min: *sorted.first()?, median: rank(50), p95: rank(95) })
+}
+
+#[derive(Debug, Default)]
+pub struct LatencyProbe {
+    screen: Vec<bool>,
+    // whether the last frame left the screen as it was
+    is_settled: bool,
+    pressed_at: Option<Instant>,
+    changed_at: Option<Instant>,
+    samples: Vec<Sample>,
+    missed: usize,
+}
+
+impl LatencyProbe {
+    pub fn new() -> Self {
+        Self { is_settled: true, ..Self::default() }
+    }
+
+    pub fn key_pressed(&mut self, at: Instant) {
+        if let Some(pressed_at) = self.pressed_at {
+            if self.changed_at.is_some() || at.saturating_duration_since(pressed_at) < MAX_LATENCY {
+                return;
+            }
+
+            self.missed += 1;
+            self.pressed_at = None;
+        }
+
+        if self.is_settled {
+            self.pressed_at = Some(at);
+        }
+    }
+
+    // after each emulated frame
+    pub fn frame_ran(&mut self, screen: &[bool], at: Instant) {
+        if screen == self.screen.as_slice() {
+            self.is_settled = true;
+            return;
+        }
+
+        // the first frame is only what later ones are compared with
+        let is_first = self.screen.is_empty();
+        self.screen = screen.to_vec();
+        self.is_settled = is_first;
+
+        if self.changed_at.is_none() && self.pressed_at.is_some() {
+            self.changed_at = Some(at);
+        }
+    }
+
+    // after each present(), which finishes the press the last frames answered
+    pub fn presented(&mut self, at: Instant) {
+        if let (Some(pressed_at), Some(changed_at)) = (self.pressed_at, self.changed_at) {
+            self.samples.push(Sample {
+                emulation: changed_at.saturating_duration_since(pressed_at),
+                presentation: at.saturating_duration_since(changed_at),
+            });
+            self.pressed_at = None;
+            self.changed_at = None;
+        }
+    }
+
+    // min, median and 95th percentile of each part, None before the first press
+    pub fn summary(&self) -> Option<String> {
+        let part = |name: &str, part: fn(&Sample) -> Duration| {
+            let values: Vec<Duration> = self.samples.iter().map(part).collect();
+            let distribution = distribution(&values)?;
+            let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+
+            Some(format!(
+                "  {:<12} {:>6.1} {:>6.1} {:>6.1}\n",
+                name,
+                ms(distribution.min),
+                ms(distribution.median),
+                ms(distribution.p95)
+            ))
+        };
+
+        let mut text = format!("Input latency over {} presses, in ms\n", self.samples.len());
+        text += &format!("  {:<12} {:>6} {:>6} {:>6}\n", "", "min", "median", "p95");
+        text += &part("emulation", |sample| sample.emulation)?;
+        text += &part("presentation", |sample| sample.presentation)?;
+        text += &part("total", Sample::total)?;
+
+        if self.missed > 0 {
+            text += &format!("{} presses went unanswered and were left out\n", self.missed);
+        }
+
+        Some(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chip8_emu::Chip8;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn the_rom_inverts_the_screen_on_a_press() {
+        let mut chip8 = Chip8::new();
+        chip8.load(ROM).unwrap();
+        chip8.run_frame(10);
+        assert!(chip8.get_display().iter().all(|&pixel| !pixel));
+
+        // a sweep of the keys takes under two frames, the first block shows by the end of them
+        chip8.keypress(5, true);
+        chip8.run_frame(10);
+        chip8.run_frame(10);
+        assert!(chip8.get_display()[0]);
+
+        for _ in 0..20 {
+            chip8.run_frame(10);
+        }
+        assert!(chip8.get_display().iter().all(|&pixel| pixel));
+
+        // held, nothing more happens. let go and pressed again, it goes back
+        chip8.run_frame(10);
+        assert!(chip8.get_display().iter().all(|&pixel| pixel));
+        chip8.keypress(5, false);
+        chip8.run_frame(10);
+        chip8.keypress(0xA, true);
+        for _ in 0..20 {
+            chip8.run_frame(10);
+        }
+        assert!(chip8.get_display().iter().all(|&pixel| !pixel));
+    }
+
+    #[test]
+    fn pairs_presses_with_the_change_and_the_present_after_it() {
+        let start = Instant::now();
+        let (blank, lit) = ([false; 4], [true; 4]);
+        let mut probe = LatencyProbe::new();
+        probe.frame_ran(&blank, start);
+
+        probe.key_pressed(start + ms(2));
+        // a frame that doesn't answer yet, and a present of it
+        probe.frame_ran(&blank, start + ms(10));
+        probe.presented(start + ms(12));
+        // a second press before the first shows doesn't count
+        probe.key_pressed(start + ms(14));
+        probe.frame_ran(&lit, start + ms(26));
+        probe.presented(start + ms(30));
+
+        assert_eq!(probe.samples, [Sample { emulation: ms(24), presentation: ms(4) }]);
+        assert_eq!(probe.samples[0].total(), ms(28));
+    }
+
+    #[test]
+    fn waits_for_the_screen_to_settle() {
+        let start = Instant::now();
+        let mut probe = LatencyProbe::new();
+        probe.frame_ran(&[false, false], start);
+        probe.key_pressed(start);
+        probe.frame_ran(&[true, false], start + ms(16));
+        probe.presented(start + ms(20));
+
+        // still drawing the last inversion, so this press can't be told apart from it
+        probe.key_pressed(start + ms(21));
+        probe.frame_ran(&[true, true], start + ms(32));
+        probe.presented(start + ms(36));
+        assert_eq!(probe.samples.len(), 1);
+
+        probe.frame_ran(&[true, true], start + ms(48));
+        probe.key_pressed(start + ms(50));
+        probe.frame_ran(&[false, true], start + ms(64));
+        probe.presented(start + ms(70));
+        assert_eq!(probe.samples[1], Sample { emulation: ms(14), presentation: ms(6) });
+    }
+
+    #[test]
+    fn drops_presses_nothing_answers() {
+        let start = Instant::now();
+        let mut probe = LatencyProbe::new();
+        probe.frame_ran(&[false], start);
+
+        probe.key_pressed(start);
+        probe.key_pressed(start + ms(1500));
+        probe.frame_ran(&[true], start + ms(1510));
+        probe.presented(start + ms(1512));
+
+        assert_eq!(probe.samples, [Sample { emulation: ms(10), presentation: ms(2) }]);
+        assert!(probe.summary().unwrap().ends_with("1 presses went unanswered and were left out\n"));
+    }
+
+    #[test]
+    fn summarizes_by_nearest_rank() {
+        let values: Vec<Duration> = (1..=20).map(ms).collect();
+        assert_eq!(distribution(&values), Some(Distribution { min: ms(1), median: ms(10), p95: ms(19) }));
+        assert_eq!(distribution(&[ms(7)]), Some(Distribution { min: ms(7), median: ms(7), p95: ms(7) }));
+        assert_eq!(distribution(&[]), None);
+
+        assert_eq!(LatencyProbe::new().summary(), None);
+
+        let mut probe = LatencyProbe::new();
+        probe.frame_ran(&[false], Instant::now());
+        let start = Instant::now();
+        probe.key_pressed(start);
+        probe.frame_ran(&[true], start + ms(5));
+        probe.presented(start + ms(8));
+
+        let summary = probe.summary().unwrap();
+        let lines: Vec<&str> = summary.lines().collect();
+        assert_eq!(lines[0], "Input latency over 1 presses, in ms");
+        assert_eq!(lines[2], "  emulation       5.0    5.0    5.0");
+        assert_eq!(lines[4], "  total           8.0    8.0    8.0");
+    }
+}
@@ -0,0 +1,154 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+// sleep overshoots by up to a scheduler tick, so the last stretch is spent spinning
+const SPIN_MARGIN: Duration = Duration::from_millis(2);
+
+pub trait Clock {
+    fn now(&self) -> Instant;
+
+    fn sleep(&self, duration: Duration);
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+// caps presents per second when vsync isn't there to do it
+pub struct FrameLimiter {
+    interval: Duration,
+    deadline: Option<Instant>,
+}
+
+impl FrameLimiter {
+    pub fn new(fps: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / fps,
+            deadline: None,
+        }
+    }
+
+    // how long to sleep before spinning out the rest of the wait
+    pub fn sleep_time(deadline: Instant, now: Instant) -> Duration {
+        deadline.saturating_duration_since(now).saturating_sub(SPIN_MARGIN)
+    }
+
+    // call once per present, returns once the next present is due
+    pub fn wait<C: Clock>(&mut self, clock: &C) {
+        let now = clock.now();
+        let deadline = match self.deadline {
+            Some(deadline) => deadline,
+            None => {
+                self.deadline = Some(now + self.interval);
+                return;
+            }
+        };
+
+        let sleep = Self::sleep_time(deadline, now);
+
+        if !sleep.is_zero() {
+            clock.sleep(sleep);
+        }
+
+        while clock.now() < deadline {
+            std::hint::spin_loop();
+        }
+
+        // after a long hitch start counting from now instead of rushing to catch up
+        let next = deadline + self.interval;
+        let now = clock.now();
+        self.deadline = Some(if next < now { now + self.interval } else { next });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+
+    // time only moves when slept, plus a little per reading so spinning ends
+    struct MockClock {
+        now: Cell<Instant>,
+        slept: Cell<Duration>,
+    }
+
+    impl MockClock {
+        fn new() -> Self {
+            Self {
+                now: Cell::new(Instant::now()),
+                slept: Cell::new(Duration::ZERO),
+            }
+        }
+
+        fn advance(&self, duration: Duration) {
+            self.now.set(self.now.get() + duration);
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Instant {
+            let now = self.now.get();
+            self.advance(Duration::from_micros(50));
+            now
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.slept.set(self.slept.get() + duration);
+            self.advance(duration);
+        }
+    }
+
+    #[test]
+    fn sleeps_short_of_the_deadline() {
+        let now = Instant::now();
+
+        assert_eq!(FrameLimiter::sleep_time(now + Duration::from_millis(10), now), Duration::from_millis(8));
+        assert_eq!(FrameLimiter::sleep_time(now + Duration::from_millis(1), now), Duration::ZERO);
+        assert_eq!(FrameLimiter::sleep_time(now, now + Duration::from_millis(5)), Duration::ZERO);
+    }
+
+    #[test]
+    fn paces_presents_to_the_target_rate() {
+        let clock = MockClock::new();
+        let mut limiter = FrameLimiter::new(60);
+        let start = clock.now.get();
+
+        for _ in 0..61 {
+            limiter.wait(&clock);
+        }
+
+        let elapsed = clock.now.get() - start;
+        assert!(elapsed >= Duration::from_millis(999), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(1010), "{:?}", elapsed);
+        // most of the wait is sleeping, not spinning
+        assert!(clock.slept.get() > Duration::from_millis(850), "{:?}", clock.slept.get());
+    }
+
+    #[test]
+    fn does_not_rush_after_a_hitch() {
+        let clock = MockClock::new();
+        let mut limiter = FrameLimiter::new(60);
+
+        limiter.wait(&clock);
+        limiter.wait(&clock);
+        clock.advance(Duration::from_millis(500));
+
+        // the frame that took too long returns straight away, the next waits a full interval
+        let before = clock.now.get();
+        limiter.wait(&clock);
+        assert!(clock.now.get() - before < Duration::from_millis(1));
+
+        let before = clock.now.get();
+        limiter.wait(&clock);
+        assert!(clock.now.get() - before >= Duration::from_millis(16));
+    }
+}
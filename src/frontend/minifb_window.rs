@@ -0,0 +1,129 @@
+use super::bindings::Action;
+use super::cli::Config;
+use super::session::{Input, Session, Window};
+use super::title;
+
+use std::time::Instant;
+
+use minifb::{Key, KeyRepeat, ScaleMode, WindowOptions};
+
+// the SDL key names from the config, for the keys minifb knows
+const KEYS: &[(&str, Key)] = &[
+    ("0", Key::Key0), ("1", Key::Key1), ("2", Key::Key2), ("3", Key::Key3), ("4", Key::Key4),
+    ("5", Key::Key5), ("6", Key::Key6), ("7", Key::Key7), ("8", Key::Key8), ("9", Key::Key9),
+    ("A", Key::A), ("B", Key::B), ("C", Key::C), ("D", Key::D), ("E", Key::E), ("F", Key::F),
+    ("G", Key::G), ("H", Key::H), ("I", Key::I), ("J", Key::J), ("K", Key::K), ("L", Key::L),
+    ("M", Key::M), ("N", Key::N), ("O", Key::O), ("P", Key::P), ("Q", Key::Q), ("R", Key::R),
+    ("S", Key::S), ("T", Key::T), ("U", Key::U), ("V", Key::V), ("W", Key::W), ("X", Key::X),
+    ("Y", Key::Y), ("Z", Key::Z),
+    ("F1", Key::F1), ("F2", Key::F2), ("F3", Key::F3), ("F4", Key::F4), ("F5", Key::F5),
+    ("F6", Key::F6), ("F7", Key::F7), ("F8", Key::F8), ("F9", Key::F9), ("F10", Key::F10),
+    ("F11", Key::F11), ("F12", Key::F12),
+    ("Keypad 0", Key::NumPad0), ("Keypad 1", Key::NumPad1), ("Keypad 2", Key::NumPad2),
+    ("Keypad 3", Key::NumPad3), ("Keypad 4", Key::NumPad4), ("Keypad 5", Key::NumPad5),
+    ("Keypad 6", Key::NumPad6), ("Keypad 7", Key::NumPad7), ("Keypad 8", Key::NumPad8),
+    ("Keypad 9", Key::NumPad9), ("Keypad +", Key::NumPadPlus), ("Keypad -", Key::NumPadMinus),
+    ("Keypad *", Key::NumPadAsterisk), ("Keypad /", Key::NumPadSlash),
+    ("Keypad Enter", Key::NumPadEnter), ("Keypad .", Key::NumPadDot),
+    ("Space", Key::Space), ("Return", Key::Enter), ("Tab", Key::Tab), ("Backspace", Key::Backspace),
+    ("Escape", Key::Escape), ("Delete", Key::Delete), ("Insert", Key::Insert), ("Home", Key::Home),
+    ("End", Key::End), ("PageUp", Key::PageUp), ("PageDown", Key::PageDown),
+    ("Up", Key::Up), ("Down", Key::Down), ("Left", Key::Left), ("Right", Key::Right),
+    (",", Key::Comma), (".", Key::Period), ("/", Key::Slash), (";", Key::Semicolon),
+    ("'", Key::Apostrophe), ("[", Key::LeftBracket), ("]", Key::RightBracket), ("-", Key::Minus),
+    ("=", Key::Equal), ("`", Key::Backquote), ("\\", Key::Backslash),
+    ("Left Shift", Key::LeftShift), ("Right Shift", Key::RightShift),
+    ("Left Ctrl", Key::LeftCtrl), ("Right Ctrl", Key::RightCtrl),
+    ("Left Alt", Key::LeftAlt), ("Right Alt", Key::RightAlt),
+];
+
+struct MinifbWindow {
+    window: minifb::Window,
+    keypad: [Option<Key>; 16],
+    actions: Vec<(Action, Key)>,
+    // minifb takes 0RGB words
+    buffer: Vec<u32>,
+}
+
+pub fn run(config: Config, buffer: Vec<u8>) -> Result<(), String> {
+    let scale = config.scale as usize;
+    let (width, height) = (chip8_emu::SCREEN_WIDTH * scale, chip8_emu::SCREEN_HEIGHT * scale);
+    let options = WindowOptions {
+        resize: true,
+        scale_mode: ScaleMode::Center,
+        ..WindowOptions::default()
+    };
+    let name = format!("{} - {}", title::display_name(&config.rom), title::APP_NAME);
+
+    let mut window = minifb::Window::new(&name, width, height, options)
+        .map_err(|e| format!("Unable to open a window: {}", e))?;
+    window.set_target_fps(60);
+
+    let mut window = MinifbWindow {
+        window,
+        keypad: config.bindings.keypad_names().map(key),
+        actions: config
+            .bindings
+            .action_names()
+            .into_iter()
+            .filter_map(|(action, name)| Some((action, key(name)?)))
+            .collect(),
+        buffer: Vec::new(),
+    };
+
+    Session::new(config, &buffer, Instant::now())?.run(&mut window)
+}
+
+fn key(name: &str) -> Option<Key> {
+    KEYS.iter().find(|&&(known, _)| known == name).map(|&(_, key)| key)
+}
+
+impl MinifbWindow {
+    fn actions_for(&self, keys: Vec<Key>) -> Vec<Action> {
+        keys.into_iter()
+            .filter_map(|key| self.actions.iter().find(|&&(_, bound)| bound == key).map(|&(action, _)| action))
+            .collect()
+    }
+}
+
+impl Window for MinifbWindow {
+    fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+
+    fn size(&self) -> (usize, usize) {
+        self.window.get_size()
+    }
+
+    fn poll(&mut self, input: &mut Input) {
+        input.keypad = self.keypad.map(|key| key.is_some_and(|key| self.window.is_key_down(key)));
+        input.pressed = self.actions_for(self.window.get_keys_pressed(KeyRepeat::No));
+        input.released = self.actions_for(self.window.get_keys_released());
+    }
+
+    fn present(&mut self, rgba: &[u8], width: usize, height: usize) -> Result<(), String> {
+        self.buffer.clear();
+        self.buffer.extend(rgba.chunks_exact(4).map(|pixel| u32::from_be_bytes([0, pixel[0], pixel[1], pixel[2]])));
+
+        self.window
+            .update_with_buffer(&self.buffer, width, height)
+            .map_err(|e| format!("Unable to draw the window: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::frontend::bindings::KeyBindings;
+
+    #[test]
+    fn knows_every_default_key() {
+        let bindings = KeyBindings::default();
+
+        assert!(bindings.keypad_names().iter().all(|name| key(name).is_some()));
+        assert!(bindings.action_names().iter().all(|(_, name)| key(name).is_some()));
+        assert_eq!(key("Return"), Some(Key::Enter));
+        assert_eq!(key("Hyper"), None);
+    }
+}
@@ -0,0 +1,122 @@
+pub mod attract;
+pub mod audio;
+pub mod bindings;
+pub mod browser;
+pub mod cli;
+pub mod config_file;
+pub mod crt;
+#[cfg(any(feature = "egui", feature = "tui"))]
+pub mod debug_sessions;
+#[cfg(any(feature = "egui", feature = "tui"))]
+pub mod debugger;
+pub mod drift;
+#[cfg(feature = "egui")]
+pub mod egui_debugger;
+pub mod font;
+#[cfg(any(feature = "tui", feature = "terminal"))]
+pub mod half_block;
+#[cfg(any(feature = "tui", feature = "terminal"))]
+pub mod held_keys;
+pub mod heatmap_view;
+pub mod inspect;
+pub mod keypad_overlay;
+pub mod latency;
+pub mod limiter;
+#[cfg(feature = "minifb")]
+pub mod minifb_window;
+#[cfg(feature = "net")]
+pub mod net;
+pub mod netplay;
+pub mod osd;
+pub mod overlay;
+pub mod paint;
+pub mod pacing;
+#[cfg(feature = "pixels")]
+pub mod pixels_window;
+pub mod quirk_menu;
+pub mod rom_overrides;
+pub mod save_slots;
+pub mod screenshot;
+#[cfg(any(feature = "minifb", feature = "pixels"))]
+pub mod session;
+pub mod signals;
+pub mod speed;
+#[cfg(feature = "terminal")]
+pub mod terminal;
+pub mod timestep;
+pub mod title;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod video;
+pub mod viewport;
+pub mod watch;
+pub mod window_mode;
+
+use std::fs::File;
+use std::io::Read;
+
+pub fn is_url(arg: &str) -> bool {
+    arg.starts_with("http://") || arg.starts_with("https://")
+}
+
+pub fn read_rom(source: &str) -> Result<Vec<u8>, String> {
+    if is_url(source) {
+        return read_remote_rom(source);
+    }
+
+    let mut rom = File::open(source).map_err(|e| format!("Unable to open {}: {}", source, e))?;
+    let mut buffer = Vec::new();
+
+    rom.read_to_end(&mut buffer)
+        .map_err(|e| format!("Unable to read {}: {}", source, e))?;
+
+    Ok(buffer)
+}
+
+// asked for when no rom was given, e.g. launched by double-clicking, None if the user cancels
+#[cfg(feature = "dialog")]
+pub fn pick_rom() -> Option<String> {
+    let path = rfd::FileDialog::new()
+        .set_title("Open a CHIP-8 ROM")
+        .add_filter("CHIP-8 ROMs", &["ch8", "c8"])
+        .add_filter("All files", &["*"])
+        .pick_file()?;
+
+    Some(path.to_string_lossy().into_owned())
+}
+
+#[cfg(feature = "net")]
+fn read_remote_rom(url: &str) -> Result<Vec<u8>, String> {
+    net::RomDownloader::from_env(net::HttpFetcher)
+        .load(url)
+        .map_err(|e| format!("Unable to download {}: {}", url, e))
+}
+
+#[cfg(not(feature = "net"))]
+fn read_remote_rom(url: &str) -> Result<Vec<u8>, String> {
+    Err(format!("Unable to load {}: built without the `net` feature", url))
+}
+
+// port 0 picks a free port, so the one actually bound is printed for the client
+#[cfg(feature = "remote")]
+pub fn listen_remote(address: &str) -> Result<chip8_emu::remote::Server, String> {
+    let server = chip8_emu::remote::Server::bind(address).map_err(|e| format!("Unable to listen on {}: {}", address, e))?;
+    eprintln!("Listening for remote commands on {}", server.local_addr());
+
+    Ok(server)
+}
+
+#[cfg(feature = "scripting")]
+pub fn load_script(path: &std::path::Path) -> Result<chip8_emu::scripting::ScriptHost, String> {
+    let source = std::fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+
+    chip8_emu::scripting::ScriptHost::new(&source).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(feature = "viewer")]
+pub fn listen_viewer(address: &str) -> Result<chip8_emu::viewer::Server, String> {
+    let server = chip8_emu::viewer::Server::bind(address).map_err(|e| format!("Unable to listen on {}: {}", address, e))?;
+    eprintln!("Streaming to viewers on ws://{}", server.local_addr());
+
+    Ok(server)
+}
@@ -0,0 +1,254 @@
+use chip8_emu::MAX_ROM_SIZE;
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const CACHE_DIR_VAR: &str = "CHIP8_EMU_CACHE_DIR";
+
+#[derive(Debug)]
+pub enum NetError {
+    NotFound,
+    Status(u16),
+    TooLarge(usize),
+    Timeout,
+    NotARom(&'static str),
+    Transport(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetError::NotFound => write!(f, "not found (404)"),
+            NetError::Status(code) => write!(f, "server responded with status {}", code),
+            NetError::TooLarge(size) => {
+                write!(f, "response is too large ({} bytes, max {})", size, MAX_ROM_SIZE)
+            },
+            NetError::Timeout => write!(f, "request timed out"),
+            NetError::NotARom(reason) => write!(f, "response doesn't look like a ROM: {}", reason),
+            NetError::Transport(message) => write!(f, "{}", message),
+            NetError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<io::Error> for NetError {
+    fn from(e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::TimedOut || e.kind() == io::ErrorKind::WouldBlock {
+            NetError::Timeout
+        } else {
+            NetError::Io(e)
+        }
+    }
+}
+
+// anything that can turn a url into at most `max_size` bytes, so the downloader
+// can be tested without a network
+pub trait Fetcher {
+    fn fetch(&self, url: &str, max_size: usize, timeout: Duration) -> Result<Vec<u8>, NetError>;
+}
+
+pub struct HttpFetcher;
+
+impl Fetcher for HttpFetcher {
+    fn fetch(&self, url: &str, max_size: usize, timeout: Duration) -> Result<Vec<u8>, NetError> {
+        let agent = ureq::AgentBuilder::new().timeout(timeout).build();
+
+        let response = match agent.get(url).call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(404, _)) => return Err(NetError::NotFound),
+            Err(ureq::Error::Status(code, _)) => return Err(NetError::Status(code)),
+            Err(ureq::Error::Transport(transport)) => return Err(transport_error(transport)),
+        };
+
+        // bail out early when the server tells us the size up front
+        if let Some(length) = response.header("Content-Length").and_then(|l| l.parse().ok()) {
+            if length > max_size {
+                return Err(NetError::TooLarge(length));
+            }
+        }
+
+        // read one byte past the cap so an oversized body can be detected without a header
+        let mut buffer = Vec::new();
+        response.into_reader().take(max_size as u64 + 1).read_to_end(&mut buffer)?;
+
+        if buffer.len() > max_size {
+            return Err(NetError::TooLarge(buffer.len()));
+        }
+
+        Ok(buffer)
+    }
+}
+
+fn transport_error(transport: ureq::Transport) -> NetError {
+    let source = std::error::Error::source(&transport)
+        .and_then(|source| source.downcast_ref::<io::Error>());
+
+    match source {
+        Some(e) if e.kind() == io::ErrorKind::TimedOut || e.kind() == io::ErrorKind::WouldBlock => {
+            NetError::Timeout
+        },
+        _ => NetError::Transport(transport.to_string()),
+    }
+}
+
+pub struct RomDownloader<F: Fetcher> {
+    fetcher: F,
+    cache_dir: Option<PathBuf>,
+}
+
+impl<F: Fetcher> RomDownloader<F> {
+    pub fn new(fetcher: F, cache_dir: Option<PathBuf>) -> Self {
+        Self { fetcher, cache_dir }
+    }
+
+    // caching is opt-in through CHIP8_EMU_CACHE_DIR
+    pub fn from_env(fetcher: F) -> Self {
+        Self::new(fetcher, env::var_os(CACHE_DIR_VAR).map(PathBuf::from))
+    }
+
+    pub fn load(&self, url: &str) -> Result<Vec<u8>, NetError> {
+        let cache_path = self.cache_path(url);
+
+        if let Some(path) = &cache_path {
+            if let Ok(data) = fs::read(path) {
+                if validate_rom(&data).is_ok() {
+                    return Ok(data);
+                }
+            }
+        }
+
+        let data = self.fetcher.fetch(url, MAX_ROM_SIZE, TIMEOUT)?;
+        validate_rom(&data)?;
+
+        if let Some(path) = &cache_path {
+            // a failed cache write shouldn't stop the game from running
+            let _ = fs::create_dir_all(self.cache_dir.as_ref().unwrap())
+                .and_then(|_| fs::write(path, &data));
+        }
+
+        Ok(data)
+    }
+
+    fn cache_path(&self, url: &str) -> Option<PathBuf> {
+        self.cache_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{:016x}.ch8", url_hash(url))))
+    }
+}
+
+pub fn validate_rom(data: &[u8]) -> Result<(), NetError> {
+    if data.is_empty() {
+        return Err(NetError::NotARom("empty response"));
+    }
+
+    if data.len() > MAX_ROM_SIZE {
+        return Err(NetError::TooLarge(data.len()));
+    }
+
+    // error pages and redirects to landing pages come back as html
+    let head: Vec<u8> = data
+        .iter()
+        .skip_while(|b| b.is_ascii_whitespace())
+        .take(16)
+        .map(|b| b.to_ascii_lowercase())
+        .collect();
+
+    if head.starts_with(b"<!doctype") || head.starts_with(b"<html") || head.starts_with(b"<?xml") {
+        return Err(NetError::NotARom("got an html page"));
+    }
+
+    Ok(())
+}
+
+// FNV-1a, stable across builds so cache entries survive upgrades
+fn url_hash(url: &str) -> u64 {
+    url.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    struct FakeFetcher {
+        response: fn() -> Result<Vec<u8>, NetError>,
+        calls: Cell<usize>,
+    }
+
+    impl FakeFetcher {
+        fn new(response: fn() -> Result<Vec<u8>, NetError>) -> Self {
+            Self { response, calls: Cell::new(0) }
+        }
+    }
+
+    impl Fetcher for &FakeFetcher {
+        fn fetch(&self, _url: &str, _max_size: usize, _timeout: Duration) -> Result<Vec<u8>, NetError> {
+            self.calls.set(self.calls.get() + 1);
+            (self.response)()
+        }
+    }
+
+    fn temp_cache_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("chip8-emu-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn detects_urls() {
+        assert!(crate::frontend::is_url("https://example.com/games/pong.ch8"));
+        assert!(crate::frontend::is_url("http://localhost:8000/pong.ch8"));
+        assert!(!crate::frontend::is_url("roms/pong.ch8"));
+    }
+
+    #[test]
+    fn rejects_html_and_oversized_responses() {
+        assert!(matches!(validate_rom(b""), Err(NetError::NotARom(_))));
+        assert!(matches!(validate_rom(b"\n  <!DOCTYPE html><html>"), Err(NetError::NotARom(_))));
+        assert!(matches!(validate_rom(&[0; MAX_ROM_SIZE + 1]), Err(NetError::TooLarge(_))));
+        assert!(validate_rom(&[0x00, 0xE0, 0x12, 0x00]).is_ok());
+    }
+
+    #[test]
+    fn reports_fetch_errors() {
+        let fetcher = FakeFetcher::new(|| Err(NetError::NotFound));
+        let result = RomDownloader::new(&fetcher, None).load("https://example.com/missing.ch8");
+
+        assert!(matches!(result, Err(NetError::NotFound)));
+    }
+
+    #[test]
+    fn caches_downloads_by_url() {
+        let dir = temp_cache_dir("cache");
+        let fetcher = FakeFetcher::new(|| Ok(vec![0x00, 0xE0, 0x12, 0x00]));
+        let downloader = RomDownloader::new(&fetcher, Some(dir.clone()));
+
+        let first = downloader.load("https://example.com/pong.ch8").unwrap();
+        let second = downloader.load("https://example.com/pong.ch8").unwrap();
+        downloader.load("https://example.com/tetris.ch8").unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(fetcher.calls.get(), 2);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn does_not_cache_invalid_responses() {
+        let dir = temp_cache_dir("invalid");
+        let fetcher = FakeFetcher::new(|| Ok(b"<html>not found</html>".to_vec()));
+        let downloader = RomDownloader::new(&fetcher, Some(dir.clone()));
+
+        assert!(downloader.load("https://example.com/pong.ch8").is_err());
+        assert!(!dir.exists());
+    }
+}
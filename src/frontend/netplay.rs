@@ -0,0 +1,35 @@
+use super::cli::Netplay;
+
+use chip8_emu::netplay::Lockstep;
+
+use std::net::{TcpListener, TcpStream};
+
+// blocks until the other player is there and both agree on the ROM. the host's --seed, or a
+// random one, is what both sides play with
+pub fn connect(netplay: &Netplay, rom: &[u8], seed: Option<u64>) -> Result<Lockstep<TcpStream>, String> {
+    let stream = match netplay {
+        Netplay::Connect(address) => {
+            println!("Connecting to {}", address);
+            TcpStream::connect(address).map_err(|e| format!("Unable to connect to {}: {}", address, e))?
+        },
+        Netplay::Listen(port) => {
+            let listener =
+                TcpListener::bind(("0.0.0.0", *port)).map_err(|e| format!("Unable to listen on port {}: {}", port, e))?;
+            println!("Waiting for the other player on port {}", port);
+
+            let (stream, address) = listener.accept().map_err(|e| format!("Netplay connection failed: {}", e))?;
+            println!("{} joined", address);
+            stream
+        },
+    };
+
+    // inputs are tiny and every frame waits on them
+    stream.set_nodelay(true).map_err(|e| format!("Netplay connection failed: {}", e))?;
+
+    let lockstep = match netplay {
+        Netplay::Connect(_) => Lockstep::join(stream, rom),
+        Netplay::Listen(_) => Lockstep::host(stream, rom, seed.unwrap_or_else(rand::random)),
+    };
+
+    lockstep.map_err(|e| format!("Netplay failed: {}", e))
+}
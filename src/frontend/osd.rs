@@ -0,0 +1,54 @@
+use std::time::{Duration, Instant};
+
+// how long a message stays up
+pub const DURATION: Duration = Duration::from_secs(2);
+
+// a short message over the game, like the new speed after pressing a speed key
+#[derive(Clone, Debug, Default)]
+pub struct Osd {
+    message: Option<(String, Instant)>,
+}
+
+impl Osd {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // replaces whatever is showing
+    pub fn show(&mut self, message: String, now: Instant) {
+        self.message = Some((message, now));
+    }
+
+    pub fn message(&self, now: Instant) -> Option<&str> {
+        self.message
+            .as_ref()
+            .filter(|(_, shown)| now.saturating_duration_since(*shown) < DURATION)
+            .map(|(message, _)| message.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shows_a_message_for_a_while() {
+        let start = Instant::now();
+        let mut osd = Osd::new();
+        assert_eq!(osd.message(start), None);
+
+        osd.show("Speed 12 (720 ips)".to_string(), start);
+        assert_eq!(osd.message(start + DURATION / 2), Some("Speed 12 (720 ips)"));
+        assert_eq!(osd.message(start + DURATION), None);
+    }
+
+    #[test]
+    fn a_new_message_starts_over() {
+        let start = Instant::now();
+        let mut osd = Osd::new();
+
+        osd.show("Speed 12 (720 ips)".to_string(), start);
+        osd.show("Speed 15 (900 ips)".to_string(), start + DURATION / 2);
+        assert_eq!(osd.message(start + DURATION), Some("Speed 15 (900 ips)"));
+    }
+}
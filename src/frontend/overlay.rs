@@ -0,0 +1,98 @@
+use chip8_emu::disasm;
+
+use chip8_emu::Chip8;
+
+// instructions listed from PC onwards
+pub const INSTRUCTIONS_SHOWN: usize = 4;
+
+// the debug overlay's text, built only from the library's public accessors
+pub fn lines(chip8: &Chip8) -> Vec<String> {
+    let mut lines: Vec<String> = chip8
+        .registers()
+        .chunks(4)
+        .enumerate()
+        .map(|(row, registers)| {
+            let cells: Vec<String> = registers
+                .iter()
+                .enumerate()
+                .map(|(column, value)| format!("V{:X} {:02X}", row * 4 + column, value))
+                .collect();
+
+            cells.join("  ")
+        })
+        .collect();
+
+    lines.push(format!(
+        "I {:03X}  PC {:03X}  SP {:X}",
+        chip8.register_i(),
+        chip8.program_counter(),
+        chip8.stack_pointer()
+    ));
+    lines.push(format!("DT {:02X}  ST {:02X}  KEYS {:04X}", chip8.delay_timer(), chip8.sound_timer(), key_mask(chip8.keys())));
+
+    for (i, (address, peek)) in chip8.peek_next(INSTRUCTIONS_SHOWN).iter().enumerate() {
+        let marker = if i == 0 { ">" } else { " " };
+
+        if let Some((_, text)) = disasm::disassemble_peek(peek) {
+            lines.push(format!("{} {:03X} {}", marker, address, text));
+        }
+    }
+
+    lines
+}
+
+// bit n is set while key n is held
+pub fn key_mask(keys: &[bool]) -> u16 {
+    keys.iter()
+        .enumerate()
+        .filter(|(_, is_pressed)| **is_pressed)
+        .fold(0, |mask, (key, _)| mask | 1 << key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shows_registers_and_upcoming_instructions() {
+        let mut chip8 = Chip8::new();
+        // V3 = 0x2A, I = 0x300, jump to self
+        chip8.load(&[0x63, 0x2A, 0xA3, 0x00, 0x12, 0x04]).unwrap();
+        chip8.tick().unwrap();
+        chip8.tick().unwrap();
+        chip8.keypress(0xA, true);
+        chip8.keypress(1, true);
+
+        let lines = lines(&chip8);
+
+        assert_eq!(lines[0], "V0 00  V1 00  V2 00  V3 2A");
+        assert_eq!(lines[3], "VC 00  VD 00  VE 00  VF 00");
+        assert_eq!(lines[4], "I 300  PC 204  SP 0");
+        assert_eq!(lines[5], "DT 00  ST 00  KEYS 0402");
+        assert_eq!(lines[6], "> 204 JMP 0x204");
+        assert_eq!(lines[7], "  206 NOP");
+        assert_eq!(lines.len(), 6 + INSTRUCTIONS_SHOWN);
+    }
+
+    #[test]
+    fn stops_listing_at_the_end_of_memory() {
+        let mut chip8 = Chip8::new();
+        // jump to the last full opcode
+        chip8.load(&[0x1F, 0xFC]).unwrap();
+        chip8.tick().unwrap();
+
+        let lines = lines(&chip8);
+        assert_eq!(lines.last().unwrap(), "  FFE NOP");
+        assert_eq!(lines.len(), 6 + 2);
+    }
+
+    #[test]
+    fn masks_pressed_keys() {
+        let mut keys = [false; 16];
+        assert_eq!(key_mask(&keys), 0);
+
+        keys[0] = true;
+        keys[0xF] = true;
+        assert_eq!(key_mask(&keys), 0x8001);
+    }
+}
@@ -0,0 +1,190 @@
+// how the SDL loop keeps time with the display. the timestep always decides how many 60 Hz frames
+// have passed, this decides what paces the presents: vsync on a 60 Hz display, vsync with each
+// frame shown for a steady 2 or 3 presents on a fast one, and the software limiter on anything else
+// where vsync would judder
+
+use std::fmt;
+
+pub const EMULATOR_FPS: u32 = 60;
+// a few frames of disagreement with the clock before it takes over again
+const MAX_DRIFT: i64 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pacing {
+    // one emulator frame per present
+    Vsync,
+    // a display this many Hz, fast enough to show every frame more than once
+    Repeat(u32),
+    // vsync on a display the limiter would have suited better, presents follow it unevenly
+    Uneven,
+    // no vsync to lean on, the limiter holds presents to 60 a second
+    Limiter,
+}
+
+impl Pacing {
+    // for a display refreshing at `refresh_rate` Hz, None when SDL couldn't say
+    pub fn choose(refresh_rate: Option<u32>) -> Self {
+        match refresh_rate {
+            // 59.94 Hz modes report as 59
+            Some(59..=61) => Pacing::Vsync,
+            Some(rate) if rate >= EMULATOR_FPS * 2 => Pacing::Repeat(rate),
+            _ => Pacing::Limiter,
+        }
+    }
+
+    // a renderer's vsync can't change once it's made. without it there's only the limiter, with it
+    // presents follow the display even where the limiter would have been better
+    pub fn with_renderer(self, has_vsync: bool) -> Self {
+        match (self, has_vsync) {
+            (_, false) => Pacing::Limiter,
+            (Pacing::Limiter, true) => Pacing::Uneven,
+            (pacing, true) => pacing,
+        }
+    }
+
+    pub fn is_vsync(&self) -> bool {
+        *self != Pacing::Limiter
+    }
+
+    // presents a second, as far as is known
+    fn refresh_rate(&self) -> u32 {
+        match self {
+            Pacing::Vsync | Pacing::Uneven | Pacing::Limiter => EMULATOR_FPS,
+            Pacing::Repeat(rate) => *rate,
+        }
+    }
+}
+
+impl fmt::Display for Pacing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pacing::Vsync => write!(f, "vsync"),
+            Pacing::Repeat(rate) => {
+                write!(f, "vsync, showing each frame {:.1} times", *rate as f64 / EMULATOR_FPS as f64)
+            },
+            Pacing::Uneven => write!(f, "vsync at a rate that doesn't suit it, some frames will judder"),
+            Pacing::Limiter => write!(f, "the frame limiter"),
+        }
+    }
+}
+
+// for the console, at startup and when the window moves to another display
+pub fn describe(refresh_rate: Option<u32>, pacing: Pacing) -> String {
+    match refresh_rate {
+        Some(rate) => format!("Display refreshes at {} Hz, pacing with {}", rate, pacing),
+        None => format!("Display refresh rate unknown, pacing with {}", pacing),
+    }
+}
+
+// which presents get a new emulator frame: 60 frames spread evenly over a second of presents, so at
+// 144 Hz frames take turns being shown 2 and 3 times. the timestep's count is still the truth, the
+// cadence only wins while it keeps up with it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameRepeat {
+    refresh_rate: u32,
+    phase: u32,
+    // frames the clock has counted that the cadence hasn't run, negative when it ran ahead
+    drift: i64,
+}
+
+impl FrameRepeat {
+    // None when presents have no steady rate, the timestep's count is run as it comes
+    pub fn new(pacing: Pacing) -> Option<Self> {
+        match pacing {
+            Pacing::Uneven | Pacing::Limiter => None,
+            pacing => Some(Self { refresh_rate: pacing.refresh_rate(), phase: 0, drift: 0 }),
+        }
+    }
+
+    // emulator frames to run before this present, `due` being what the timestep counted since the
+    // last. a display that skips presents, or runs a little off its rate, falls behind the clock
+    // and catches up in one go
+    pub fn frames(&mut self, due: u32) -> u32 {
+        self.phase += EMULATOR_FPS;
+        let cadence = self.phase / self.refresh_rate;
+        self.phase %= self.refresh_rate;
+
+        self.drift += due as i64 - cadence as i64;
+
+        if self.drift.abs() <= MAX_DRIFT {
+            return cadence;
+        }
+
+        let frames = (cadence as i64 + self.drift).max(0) as u32;
+        self.drift = 0;
+
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `presents` presents with the clock counting frames exactly in step
+    fn cadence(refresh_rate: u32, presents: usize) -> Vec<u32> {
+        let mut repeat = FrameRepeat::new(Pacing::choose(Some(refresh_rate))).unwrap();
+        let mut clock = 0;
+
+        (1..=presents)
+            .map(|present| {
+                let due = (present as u32 * EMULATOR_FPS) / refresh_rate - clock;
+                clock += due;
+                repeat.frames(due)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chooses_by_refresh_rate() {
+        assert_eq!(Pacing::choose(Some(60)), Pacing::Vsync);
+        assert_eq!(Pacing::choose(Some(59)), Pacing::Vsync);
+        assert_eq!(Pacing::choose(Some(75)), Pacing::Limiter);
+        assert_eq!(Pacing::choose(Some(120)), Pacing::Repeat(120));
+        assert_eq!(Pacing::choose(Some(144)), Pacing::Repeat(144));
+        assert_eq!(Pacing::choose(Some(240)), Pacing::Repeat(240));
+        assert_eq!(Pacing::choose(None), Pacing::Limiter);
+    }
+
+    #[test]
+    fn settles_for_the_renderer_it_got() {
+        assert_eq!(Pacing::Vsync.with_renderer(false), Pacing::Limiter);
+        assert_eq!(Pacing::Repeat(144).with_renderer(true), Pacing::Repeat(144));
+        // moved to a 75 Hz display with vsync already on
+        assert_eq!(Pacing::choose(Some(75)).with_renderer(true), Pacing::Uneven);
+        assert_eq!(FrameRepeat::new(Pacing::Uneven), None);
+        assert_eq!(FrameRepeat::new(Pacing::Limiter), None);
+    }
+
+    #[test]
+    fn describes_the_choice() {
+        assert_eq!(
+            describe(Some(144), Pacing::Repeat(144)),
+            "Display refreshes at 144 Hz, pacing with vsync, showing each frame 2.4 times"
+        );
+        assert_eq!(describe(None, Pacing::Limiter), "Display refresh rate unknown, pacing with the frame limiter");
+    }
+
+    #[test]
+    fn shows_each_frame_for_a_steady_number_of_presents() {
+        assert_eq!(cadence(60, 4), [1, 1, 1, 1]);
+        assert_eq!(cadence(120, 6), [0, 1, 0, 1, 0, 1]);
+        assert_eq!(cadence(240, 8), [0, 0, 0, 1, 0, 0, 0, 1]);
+        // 2.4 presents a frame, as 2 and 3 in turn
+        assert_eq!(cadence(144, 12), [0, 0, 1, 0, 1, 0, 0, 1, 0, 1, 0, 1]);
+        assert_eq!(cadence(144, 144).iter().sum::<u32>(), 60);
+    }
+
+    #[test]
+    fn catches_up_when_presents_are_skipped() {
+        let mut repeat = FrameRepeat::new(Pacing::Repeat(120)).unwrap();
+        assert_eq!(repeat.frames(0), 0);
+        assert_eq!(repeat.frames(1), 1);
+
+        // the window was hidden and vsync stopped blocking for a while, or the clock jumped
+        assert_eq!(repeat.frames(5), 5);
+        assert_eq!(repeat.frames(0), 1);
+        assert_eq!(repeat.frames(0), 0);
+        assert_eq!(repeat.frames(1), 1);
+    }
+}
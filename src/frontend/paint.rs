@@ -0,0 +1,107 @@
+// rectangles and text for the overlays, on the SDL canvas or straight into an RGBA buffer for the
+// frontends that only take finished frames
+
+use super::font;
+
+pub type Rgba = (u8, u8, u8, u8);
+
+pub trait Painter {
+    // alpha below 255 blends over what's already there
+    fn fill_rect(&mut self, left: i32, top: i32, width: u32, height: u32, color: Rgba);
+}
+
+// `size` surface pixels per font pixel
+pub fn text<P: Painter + ?Sized>(painter: &mut P, text: &str, left: i32, top: i32, size: u32, color: Rgba) {
+    for (x, y) in font::text_pixels(text) {
+        painter.fill_rect(left + (x * size) as i32, top + (y * size) as i32, size, size, color);
+    }
+}
+
+#[cfg(any(test, feature = "minifb", feature = "pixels"))]
+pub struct RgbaPainter<'a> {
+    rgba: &'a mut [u8],
+    width: usize,
+    height: usize,
+}
+
+#[cfg(any(test, feature = "minifb", feature = "pixels"))]
+impl<'a> RgbaPainter<'a> {
+    pub fn new(rgba: &'a mut [u8], width: usize, height: usize) -> Self {
+        assert_eq!(rgba.len(), width * height * 4, "the buffer doesn't match its size");
+
+        Self { rgba, width, height }
+    }
+}
+
+#[cfg(any(test, feature = "minifb", feature = "pixels"))]
+impl Painter for RgbaPainter<'_> {
+    fn fill_rect(&mut self, left: i32, top: i32, width: u32, height: u32, color: Rgba) {
+        // whatever hangs off the edges is dropped
+        let clip = |start: i32, length: u32, limit: usize| {
+            let end = (start as i64 + length as i64).clamp(0, limit as i64) as usize;
+            (start.max(0) as usize).min(end)..end
+        };
+        let (columns, rows) = (clip(left, width, self.width), clip(top, height, self.height));
+        let (r, g, b, a) = color;
+        let alpha = a as u32;
+
+        for y in rows {
+            for x in columns.clone() {
+                let pixel = &mut self.rgba[(y * self.width + x) * 4..][..4];
+
+                for (channel, source) in pixel.iter_mut().zip([r, g, b]) {
+                    *channel = ((source as u32 * alpha + *channel as u32 * (255 - alpha)) / 255) as u8;
+                }
+                pixel[3] = 255;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(rgba: &[u8], width: usize, x: usize, y: usize) -> [u8; 4] {
+        rgba[(y * width + x) * 4..][..4].try_into().unwrap()
+    }
+
+    #[test]
+    fn fills_inside_the_buffer_only() {
+        let mut rgba = vec![0; 4 * 3 * 4];
+        let mut painter = RgbaPainter::new(&mut rgba, 4, 3);
+
+        painter.fill_rect(-1, 1, 3, 5, (10, 20, 30, 255));
+
+        assert_eq!(pixel(&rgba, 4, 0, 1), [10, 20, 30, 255]);
+        assert_eq!(pixel(&rgba, 4, 1, 2), [10, 20, 30, 255]);
+        assert_eq!(pixel(&rgba, 4, 2, 1), [0; 4]);
+        assert_eq!(pixel(&rgba, 4, 0, 0), [0; 4]);
+
+        // entirely off the buffer
+        let mut painter = RgbaPainter::new(&mut rgba, 4, 3);
+        painter.fill_rect(10, -10, 2, 2, (255, 255, 255, 255));
+        painter.fill_rect(-5, 0, 2, 2, (255, 255, 255, 255));
+        assert_eq!(rgba.iter().filter(|&&byte| byte == 255).count(), 4);
+    }
+
+    #[test]
+    fn blends_translucent_colors() {
+        let mut rgba = vec![200; 4];
+        RgbaPainter::new(&mut rgba, 1, 1).fill_rect(0, 0, 1, 1, (0, 100, 255, 51));
+
+        assert_eq!(rgba, [160, 180, 211, 255]);
+    }
+
+    #[test]
+    fn draws_text_at_a_size() {
+        let mut rgba = vec![0; 8 * 10 * 4];
+        // "1" is .#. on its top row
+        text(&mut RgbaPainter::new(&mut rgba, 8, 10), "1", 0, 0, 2, (255, 255, 255, 255));
+
+        assert_eq!(pixel(&rgba, 8, 1, 1), [0; 4]);
+        assert_eq!(pixel(&rgba, 8, 2, 0), [255; 4]);
+        assert_eq!(pixel(&rgba, 8, 3, 1), [255; 4]);
+        assert_eq!(pixel(&rgba, 8, 4, 0), [0; 4]);
+    }
+}
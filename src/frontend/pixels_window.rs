@@ -0,0 +1,181 @@
+use super::bindings::Action;
+use super::cli::Config;
+use super::session::{Input, Session, Window};
+use super::title;
+
+use chip8_emu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use std::time::Instant;
+
+use pixels::{Pixels, SurfaceTexture};
+use winit::dpi::PhysicalSize;
+use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode as Key, WindowEvent};
+use winit::event_loop::EventLoop;
+use winit::platform::run_return::EventLoopExtRunReturn;
+use winit::window::WindowBuilder;
+
+// the SDL key names from the config, for the keys winit knows
+const KEYS: &[(&str, Key)] = &[
+    ("0", Key::Key0), ("1", Key::Key1), ("2", Key::Key2), ("3", Key::Key3), ("4", Key::Key4),
+    ("5", Key::Key5), ("6", Key::Key6), ("7", Key::Key7), ("8", Key::Key8), ("9", Key::Key9),
+    ("A", Key::A), ("B", Key::B), ("C", Key::C), ("D", Key::D), ("E", Key::E), ("F", Key::F),
+    ("G", Key::G), ("H", Key::H), ("I", Key::I), ("J", Key::J), ("K", Key::K), ("L", Key::L),
+    ("M", Key::M), ("N", Key::N), ("O", Key::O), ("P", Key::P), ("Q", Key::Q), ("R", Key::R),
+    ("S", Key::S), ("T", Key::T), ("U", Key::U), ("V", Key::V), ("W", Key::W), ("X", Key::X),
+    ("Y", Key::Y), ("Z", Key::Z),
+    ("F1", Key::F1), ("F2", Key::F2), ("F3", Key::F3), ("F4", Key::F4), ("F5", Key::F5),
+    ("F6", Key::F6), ("F7", Key::F7), ("F8", Key::F8), ("F9", Key::F9), ("F10", Key::F10),
+    ("F11", Key::F11), ("F12", Key::F12),
+    ("Keypad 0", Key::Numpad0), ("Keypad 1", Key::Numpad1), ("Keypad 2", Key::Numpad2),
+    ("Keypad 3", Key::Numpad3), ("Keypad 4", Key::Numpad4), ("Keypad 5", Key::Numpad5),
+    ("Keypad 6", Key::Numpad6), ("Keypad 7", Key::Numpad7), ("Keypad 8", Key::Numpad8),
+    ("Keypad 9", Key::Numpad9), ("Keypad +", Key::NumpadAdd), ("Keypad -", Key::NumpadSubtract),
+    ("Keypad *", Key::NumpadMultiply), ("Keypad /", Key::NumpadDivide),
+    ("Keypad Enter", Key::NumpadEnter), ("Keypad .", Key::NumpadDecimal),
+    ("Space", Key::Space), ("Return", Key::Return), ("Tab", Key::Tab), ("Backspace", Key::Back),
+    ("Escape", Key::Escape), ("Delete", Key::Delete), ("Insert", Key::Insert), ("Home", Key::Home),
+    ("End", Key::End), ("PageUp", Key::PageUp), ("PageDown", Key::PageDown),
+    ("Up", Key::Up), ("Down", Key::Down), ("Left", Key::Left), ("Right", Key::Right),
+    (",", Key::Comma), (".", Key::Period), ("/", Key::Slash), (";", Key::Semicolon),
+    ("'", Key::Apostrophe), ("[", Key::LBracket), ("]", Key::RBracket), ("-", Key::Minus),
+    ("=", Key::Equals), ("`", Key::Grave), ("\\", Key::Backslash),
+    ("Left Shift", Key::LShift), ("Right Shift", Key::RShift),
+    ("Left Ctrl", Key::LControl), ("Right Ctrl", Key::RControl),
+    ("Left Alt", Key::LAlt), ("Right Alt", Key::RAlt),
+];
+
+struct PixelsWindow {
+    event_loop: EventLoop<()>,
+    window: winit::window::Window,
+    pixels: Pixels,
+    buffer_size: (usize, usize),
+    keypad: [Option<Key>; 16],
+    actions: Vec<(Action, Key)>,
+    // keys down right now, winit repeats presses while a key is held
+    held: Vec<Key>,
+    is_open: bool,
+}
+
+pub fn run(config: Config, buffer: Vec<u8>) -> Result<(), String> {
+    let scale = config.scale;
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title(format!("{} - {}", title::display_name(&config.rom), title::APP_NAME))
+        .with_inner_size(PhysicalSize::new(SCREEN_WIDTH as u32 * scale, SCREEN_HEIGHT as u32 * scale))
+        .with_min_inner_size(PhysicalSize::new(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32))
+        .build(&event_loop)
+        .map_err(|e| format!("Unable to open a window: {}", e))?;
+
+    let size = window.inner_size();
+    let surface = SurfaceTexture::new(size.width, size.height, &window);
+    let pixels = Pixels::new(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32, surface)
+        .map_err(|e| format!("Unable to set up rendering: {}", e))?;
+
+    let mut window = PixelsWindow {
+        event_loop,
+        window,
+        pixels,
+        buffer_size: (SCREEN_WIDTH, SCREEN_HEIGHT),
+        keypad: config.bindings.keypad_names().map(key),
+        actions: config
+            .bindings
+            .action_names()
+            .into_iter()
+            .filter_map(|(action, name)| Some((action, key(name)?)))
+            .collect(),
+        held: Vec::new(),
+        is_open: true,
+    };
+
+    Session::new(config, &buffer, Instant::now())?.run(&mut window)
+}
+
+fn key(name: &str) -> Option<Key> {
+    KEYS.iter().find(|&&(known, _)| known == name).map(|&(_, key)| key)
+}
+
+impl Window for PixelsWindow {
+    fn is_open(&self) -> bool {
+        self.is_open
+    }
+
+    fn size(&self) -> (usize, usize) {
+        let size = self.window.inner_size();
+        (size.width as usize, size.height as usize)
+    }
+
+    // runs the event loop until it has nothing queued, so the session can drive it like the others
+    fn poll(&mut self, input: &mut Input) {
+        let Self { event_loop, pixels, keypad, actions, held, is_open, .. } = self;
+
+        event_loop.run_return(|event, _, control_flow| {
+            control_flow.set_poll();
+
+            match event {
+                Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => *is_open = false,
+                Event::WindowEvent { event: WindowEvent::Resized(size), .. } => {
+                    if let Err(e) = pixels.resize_surface(size.width.max(1), size.height.max(1)) {
+                        eprintln!("Unable to resize the window: {}", e);
+                    }
+                },
+                Event::WindowEvent {
+                    event: WindowEvent::KeyboardInput {
+                        input: KeyboardInput { virtual_keycode: Some(key), state, .. },
+                        ..
+                    },
+                    ..
+                } => {
+                    let is_down = state == ElementState::Pressed;
+                    let is_repeat = is_down && held.contains(&key);
+                    held.retain(|&held| held != key);
+
+                    if is_down {
+                        held.push(key);
+                    }
+
+                    if let Some(index) = keypad.iter().position(|&bound| bound == Some(key)) {
+                        input.keypad[index] = is_down;
+                    } else if let Some(&(action, _)) = actions.iter().find(|&&(_, bound)| bound == key) {
+                        match (is_down, is_repeat) {
+                            (true, false) => input.pressed.push(action),
+                            (false, _) => input.released.push(action),
+                            (true, true) => (),
+                        }
+                    }
+                },
+                Event::MainEventsCleared => control_flow.set_exit(),
+                _ => (),
+            }
+        });
+    }
+
+    fn present(&mut self, rgba: &[u8], width: usize, height: usize) -> Result<(), String> {
+        // the session already scaled the screen, pixels only centers it
+        if self.buffer_size != (width, height) {
+            self.pixels
+                .resize_buffer(width as u32, height as u32)
+                .map_err(|e| format!("Unable to resize the window: {}", e))?;
+            self.buffer_size = (width, height);
+        }
+
+        self.pixels.frame_mut().copy_from_slice(rgba);
+        self.pixels.render().map_err(|e| format!("Unable to draw the window: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::frontend::bindings::KeyBindings;
+
+    #[test]
+    fn knows_every_default_key() {
+        let bindings = KeyBindings::default();
+
+        assert!(bindings.keypad_names().iter().all(|name| key(name).is_some()));
+        assert!(bindings.action_names().iter().all(|(_, name)| key(name).is_some()));
+        assert_eq!(key("Backspace"), Some(Key::Back));
+        assert_eq!(key("Hyper"), None);
+    }
+}
@@ -0,0 +1,113 @@
+// a small list of the library's quirks over the game, moved through with the arrow keys and
+// flipped with Enter. the change itself is up to the frontend, which makes it between frames
+
+use chip8_emu::{Quirk, Quirks};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QuirkMenu {
+    is_open: bool,
+    selected: usize,
+}
+
+impl QuirkMenu {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.is_open
+    }
+
+    // opens where it was left
+    pub fn toggle(&mut self) {
+        self.is_open = !self.is_open;
+    }
+
+    pub fn close(&mut self) {
+        self.is_open = false;
+    }
+
+    // both ends wrap around
+    pub fn up(&mut self) {
+        let count = Quirk::all().count();
+        self.selected = (self.selected + count - 1) % count;
+    }
+
+    pub fn down(&mut self) {
+        self.selected = (self.selected + 1) % Quirk::all().count();
+    }
+
+    pub fn selected(&self) -> Quirk {
+        Quirk::all().nth(self.selected).unwrap()
+    }
+
+    // a title, then one line per quirk with the selected one marked
+    pub fn lines(&self, quirks: Quirks) -> Vec<String> {
+        let mut lines = vec!["QUIRKS  ENTER TOGGLES".to_string()];
+
+        for (index, quirk) in Quirk::all().enumerate() {
+            let marker = if index == self.selected { ">" } else { " " };
+            lines.push(format!("{} {} {}", marker, switch(quirks.get(quirk)), quirk.description()));
+        }
+
+        lines
+    }
+}
+
+// for the OSD and the console once a quirk is flipped
+pub fn describe(quirk: Quirk, is_enabled: bool) -> String {
+    format!("Quirk {} {}", quirk.name(), switch(is_enabled).trim_end())
+}
+
+fn switch(is_enabled: bool) -> &'static str {
+    if is_enabled {
+        "on "
+    } else {
+        "off"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_every_quirk_with_its_state() {
+        let menu = QuirkMenu::new();
+        let quirks = Quirks { shift_uses_vy: true, ..Quirks::default() };
+        let lines = menu.lines(quirks);
+
+        assert_eq!(lines.len(), Quirk::all().count() + 1);
+        assert_eq!(lines[1], "> on  8XY6/8XYE shift VY into VX");
+        assert_eq!(lines[2], "  off FX55/FX65 move I past the registers");
+        assert_eq!(QuirkMenu::new().lines(Quirks::default())[1], "> off 8XY6/8XYE shift VY into VX");
+    }
+
+    #[test]
+    fn moves_around_the_list() {
+        let mut menu = QuirkMenu::new();
+        let first = menu.selected();
+
+        menu.up();
+        assert_eq!(menu.selected(), Quirk::all().last().unwrap());
+        menu.down();
+        assert_eq!(menu.selected(), first);
+    }
+
+    #[test]
+    fn opens_and_closes() {
+        let mut menu = QuirkMenu::new();
+        assert!(!menu.is_open());
+
+        menu.toggle();
+        assert!(menu.is_open());
+        menu.close();
+        assert!(!menu.is_open());
+    }
+
+    #[test]
+    fn describes_a_change() {
+        assert_eq!(describe(Quirk::ShiftUsesVy, true), "Quirk shift_uses_vy on");
+        assert_eq!(describe(Quirk::ShiftUsesVy, false), "Quirk shift_uses_vy off");
+    }
+}
@@ -0,0 +1,384 @@
+// settings changed while playing a ROM, stored by ROM hash and restored the next time it's loaded.
+// they layer as defaults < config file < stored overrides < command line
+
+use super::cli::{Config, RunArgs};
+use super::config_file;
+use super::title;
+
+use chip8_emu::{Chip8, Palette, Quirk};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RomOverrides {
+    // the name it was last played under, only so the list is readable
+    #[serde(default)]
+    pub name: String,
+    // instructions per frame
+    pub speed: Option<usize>,
+    pub palette: Option<String>,
+    // by the library's quirk names, on or off
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub quirks: BTreeMap<String, bool>,
+}
+
+impl RomOverrides {
+    pub fn is_empty(&self) -> bool {
+        self.speed.is_none() && self.palette.is_none() && self.quirks.is_empty()
+    }
+
+    // checks everything before changing anything, so a bad entry leaves the config alone
+    pub fn apply(&self, config: &mut Config) -> Result<(), String> {
+        let palette = match &self.palette {
+            Some(palette) => Some(palette.parse::<Palette>().map_err(|e| format!("invalid saved palette: {}", e))?),
+            None => None,
+        };
+
+        if self.speed == Some(0) {
+            return Err("invalid saved speed: 0 instructions per frame".to_string());
+        }
+
+        let mut quirks = Vec::new();
+
+        for (name, &is_enabled) in &self.quirks {
+            let quirk = Quirk::from_name(name).ok_or_else(|| format!("invalid saved quirk: {}", name))?;
+            quirks.push((quirk, is_enabled));
+        }
+
+        config.ticks_per_frame = self.speed.unwrap_or(config.ticks_per_frame);
+        config.palette = palette.unwrap_or(config.palette);
+
+        for (quirk, is_enabled) in quirks {
+            config.quirks.set(quirk, is_enabled);
+        }
+
+        Ok(())
+    }
+}
+
+// `config` already holds the defaults and the config file. the command line goes on again last
+// so an explicit flag always beats a stored override
+pub fn resolve(config: &mut Config, overrides: Option<&RomOverrides>, args: &RunArgs) -> Result<(), String> {
+    let result = overrides.map_or(Ok(()), |overrides| overrides.apply(config));
+
+    // the ROM isn't a setting, and the browser may have picked a different one than the command line
+    let rom = std::mem::take(&mut config.rom);
+    config.apply_args(args);
+    config.rom = rom;
+
+    result
+}
+
+// stores one change for the ROM `chip8` is running
+pub fn remember(chip8: &Chip8, change: impl FnOnce(&mut RomOverrides)) -> Result<(), String> {
+    let (Some(path), Some(rom)) = (OverrideStore::default_path(), chip8.loaded_rom()) else {
+        return Ok(());
+    };
+
+    let mut store = OverrideStore::load(&path)?;
+    store.update(&rom.sha256_hex(), rom.source_name.as_deref().map_or("", title::display_name), change);
+    store.save()
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct StoreFile {
+    #[serde(default)]
+    roms: BTreeMap<String, RomOverrides>,
+}
+
+#[derive(Debug)]
+pub struct OverrideStore {
+    path: PathBuf,
+    roms: BTreeMap<String, RomOverrides>,
+}
+
+impl OverrideStore {
+    // next to config.toml
+    pub fn default_path() -> Option<PathBuf> {
+        config_file::default_path().and_then(|path| Some(path.parent()?.join("overrides.toml")))
+    }
+
+    // nothing stored yet is an empty store
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let roms = match fs::read_to_string(path) {
+            Ok(text) => toml::from_str::<StoreFile>(&text).map_err(|e| format!("{}: {}", path.display(), e))?.roms,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(format!("Unable to read {}: {}", path.display(), e)),
+        };
+
+        Ok(Self { path: path.to_path_buf(), roms })
+    }
+
+    pub fn get(&self, hash: &str) -> Option<&RomOverrides> {
+        self.roms.get(hash)
+    }
+
+    pub fn update(&mut self, hash: &str, name: &str, change: impl FnOnce(&mut RomOverrides)) {
+        let overrides = self.roms.entry(hash.to_string()).or_default();
+        overrides.name = name.to_string();
+        change(overrides);
+    }
+
+    // whether there was anything to forget
+    pub fn forget(&mut self, hash: &str) -> bool {
+        self.roms.remove(hash).is_some()
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &RomOverrides)> {
+        self.roms.iter().map(|(hash, overrides)| (hash.as_str(), overrides))
+    }
+
+    pub fn save(&mut self) -> Result<(), String> {
+        self.roms.retain(|_, overrides| !overrides.is_empty());
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("Unable to create {}: {}", dir.display(), e))?;
+        }
+
+        let file = StoreFile { roms: self.roms.clone() };
+        let text = toml::to_string(&file).map_err(|e| format!("Unable to save overrides: {}", e))?;
+
+        fs::write(&self.path, text).map_err(|e| format!("Unable to write {}: {}", self.path.display(), e))
+    }
+}
+
+// one line per ROM for `chip8-emu overrides`
+pub fn format_list(store: &OverrideStore) -> String {
+    let mut out = String::new();
+
+    for (hash, overrides) in store.entries() {
+        let mut settings = Vec::new();
+
+        if let Some(speed) = overrides.speed {
+            settings.push(format!("speed {}", speed));
+        }
+
+        if let Some(palette) = &overrides.palette {
+            settings.push(format!("palette {}", palette));
+        }
+
+        for (name, &is_enabled) in &overrides.quirks {
+            settings.push(format!("{} {}", name, if is_enabled { "on" } else { "off" }));
+        }
+
+        out.push_str(&format!("{}  {:<24} {}\n", &hash[..hash.len().min(12)], overrides.name, settings.join(", ")));
+    }
+
+    if out.is_empty() {
+        out.push_str("No saved per-ROM settings\n");
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::cli::{self, Command};
+
+    use std::env;
+
+    const HASH: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    fn args(args: &[&str]) -> RunArgs {
+        match cli::parse(args.iter().copied()).unwrap() {
+            Command::Run(args) => *args,
+            command => panic!("expected run, got {:?}", command),
+        }
+    }
+
+    // defaults, then `file`, then the stored overrides and the command line
+    fn layered(file: &str, overrides: Option<&RomOverrides>, cli: &[&str]) -> Result<Config, String> {
+        let args = args(cli);
+        let mut config = Config::new(String::new());
+        config_file::parse(file)?.apply(&mut config)?;
+        config.apply_args(&args);
+        resolve(&mut config, overrides, &args)?;
+
+        Ok(config)
+    }
+
+    fn overrides(speed: Option<usize>, palette: Option<&str>) -> RomOverrides {
+        RomOverrides { name: "pong".to_string(), speed, palette: palette.map(String::from), quirks: BTreeMap::new() }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("chip8-emu-overrides-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        dir.join("overrides.toml")
+    }
+
+    #[test]
+    fn overrides_beat_the_config_file() {
+        let saved = overrides(Some(15), Some("amber"));
+        let config = layered("speed = 8\npalette = \"white\"", Some(&saved), &["chip8-emu", "pong.ch8"]).unwrap();
+
+        assert_eq!(config.ticks_per_frame, 15);
+        assert_eq!(config.palette, Palette::named("amber").unwrap());
+    }
+
+    #[test]
+    fn command_line_beats_overrides() {
+        let saved = overrides(Some(15), Some("amber"));
+        let cli = ["chip8-emu", "pong.ch8", "--speed", "20", "--palette", "gameboy"];
+        let config = layered("speed = 8", Some(&saved), &cli).unwrap();
+
+        assert_eq!(config.ticks_per_frame, 20);
+        assert_eq!(config.palette, Palette::named("gameboy").unwrap());
+    }
+
+    #[test]
+    fn each_setting_is_layered_on_its_own() {
+        // the override only has a speed, the command line only a palette
+        let saved = overrides(Some(15), None);
+        let config = layered("palette = \"white\"", Some(&saved), &["chip8-emu", "pong.ch8", "--palette", "amber"]).unwrap();
+        assert_eq!(config.ticks_per_frame, 15);
+        assert_eq!(config.palette, Palette::named("amber").unwrap());
+
+        let saved = overrides(None, Some("gameboy"));
+        let config = layered("speed = 8", Some(&saved), &["chip8-emu", "pong.ch8"]).unwrap();
+        assert_eq!(config.ticks_per_frame, 8);
+        assert_eq!(config.palette, Palette::named("gameboy").unwrap());
+    }
+
+    #[test]
+    fn the_default_speed_leaves_out_the_override() {
+        let saved = overrides(Some(15), None);
+
+        let config = layered("speed = 8", Some(&saved), &["chip8-emu", "pong.ch8"]).unwrap();
+        assert_eq!((config.ticks_per_frame, config.default_ticks_per_frame), (15, 8));
+
+        let config = layered("speed = 8", Some(&saved), &["chip8-emu", "pong.ch8", "--speed", "20"]).unwrap();
+        assert_eq!((config.ticks_per_frame, config.default_ticks_per_frame), (20, 20));
+    }
+
+    #[test]
+    fn restores_quirks_under_the_command_line() {
+        let mut saved = overrides(None, None);
+        saved.quirks.insert("shift_uses_vy".to_string(), true);
+
+        let config = layered("[quirks]\nshift = false", Some(&saved), &["chip8-emu", "pong.ch8"]).unwrap();
+        assert_eq!(config.quirks.shift, Some(true));
+
+        let config = layered("", Some(&saved), &["chip8-emu", "pong.ch8", "--quirk-shift", "off"]).unwrap();
+        assert_eq!(config.quirks.shift, Some(false));
+    }
+
+    #[test]
+    fn keeps_the_rom_the_browser_picked() {
+        let args = args(&["chip8-emu", "roms/", "--speed", "20"]);
+        let mut config = Config::new("roms/pong.ch8".to_string());
+
+        resolve(&mut config, Some(&overrides(Some(15), None)), &args).unwrap();
+
+        assert_eq!(config.rom, "roms/pong.ch8");
+        assert_eq!(config.ticks_per_frame, 20);
+    }
+
+    #[test]
+    fn no_overrides_keeps_the_config_file() {
+        let config = layered("speed = 8\npalette = \"white\"", None, &["chip8-emu", "pong.ch8"]).unwrap();
+
+        assert_eq!(config.ticks_per_frame, 8);
+        assert_eq!(config.palette, Palette::named("white").unwrap());
+    }
+
+    #[test]
+    fn bad_overrides_change_nothing_but_are_reported() {
+        let cli = ["chip8-emu", "pong.ch8", "--scale", "3"];
+
+        let mut unknown_quirk = overrides(Some(15), Some("amber"));
+        unknown_quirk.quirks.insert("warp".to_string(), true);
+
+        for saved in [overrides(Some(15), Some("plaid")), overrides(Some(0), Some("amber")), unknown_quirk] {
+            let args = args(&cli);
+            let mut config = Config::new(String::new());
+            config_file::parse("speed = 8").unwrap().apply(&mut config).unwrap();
+
+            assert!(resolve(&mut config, Some(&saved), &args).is_err());
+            assert_eq!(config.ticks_per_frame, 8);
+            assert_eq!(config.palette, Palette::default());
+            // the command line still applies
+            assert_eq!(config.scale, 3);
+        }
+    }
+
+    #[test]
+    fn saves_and_restores_by_hash() {
+        let path = temp_path("round-trip");
+        let mut store = OverrideStore::load(&path).unwrap();
+        assert!(store.get(HASH).is_none());
+
+        store.update(HASH, "pong", |overrides| overrides.speed = Some(15));
+        store.update(HASH, "Pong (1990)", |overrides| overrides.palette = Some("#000000,#ffffff".to_string()));
+        store.update(HASH, "Pong (1990)", |overrides| {
+            overrides.quirks.insert("shift_uses_vy".to_string(), true);
+        });
+        store.save().unwrap();
+
+        let store = OverrideStore::load(&path).unwrap();
+        let saved = store.get(HASH).unwrap();
+        assert_eq!(saved.name, "Pong (1990)");
+        assert_eq!(saved.speed, Some(15));
+        assert_eq!(saved.palette.as_deref(), Some("#000000,#ffffff"));
+        assert_eq!(saved.quirks.get("shift_uses_vy"), Some(&true));
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn forgets_and_drops_empty_entries() {
+        let path = temp_path("forget");
+        let mut store = OverrideStore::load(&path).unwrap();
+        store.update(HASH, "pong", |overrides| overrides.speed = Some(15));
+        store.update("ab", "brix", |overrides| overrides.speed = Some(12));
+        store.update("cd", "tetris", |_| ());
+        store.save().unwrap();
+
+        let mut store = OverrideStore::load(&path).unwrap();
+        assert_eq!(store.entries().count(), 2);
+        assert!(store.forget(HASH));
+        assert!(!store.forget(HASH));
+        store.save().unwrap();
+
+        let store = OverrideStore::load(&path).unwrap();
+        assert!(store.entries().map(|(hash, _)| hash).eq(["ab"]));
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn a_damaged_store_is_an_error() {
+        let path = temp_path("damaged");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "[roms.ab]\nspeed = \"fast\"\n").unwrap();
+
+        assert!(OverrideStore::load(&path).unwrap_err().starts_with(&path.display().to_string()));
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn lists_what_is_stored() {
+        let path = temp_path("list");
+        let mut store = OverrideStore::load(&path).unwrap();
+        assert_eq!(format_list(&store), "No saved per-ROM settings\n");
+
+        store.update(HASH, "pong", |overrides| {
+            overrides.speed = Some(15);
+            overrides.palette = Some("amber".to_string());
+            overrides.quirks.insert("shift_uses_vy".to_string(), false);
+        });
+
+        let line = format!("9f86d081884c  {:<24} speed 15, palette amber, shift_uses_vy off\n", "pong");
+        assert_eq!(format_list(&store), line);
+    }
+}
@@ -0,0 +1,146 @@
+use chip8_emu::Chip8;
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+pub const SLOTS: u8 = 9;
+
+// $XDG_DATA_HOME/chip8-emu/states, falling back to ~/.local/share
+pub fn default_dir() -> Option<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")))?;
+
+    Some(data_home.join("chip8-emu").join("states"))
+}
+
+// numbered save slots for one ROM, files are keyed by the ROM's hash so renaming it keeps its saves
+pub struct SaveSlots {
+    dir: PathBuf,
+    rom_hash: String,
+    slot: u8,
+}
+
+impl SaveSlots {
+    pub fn new(dir: PathBuf, rom_hash: String) -> Self {
+        Self { dir, rom_hash, slot: 1 }
+    }
+
+    pub fn slot(&self) -> u8 {
+        self.slot
+    }
+
+    pub fn select(&mut self, slot: u8) -> Result<(), String> {
+        if !(1..=SLOTS).contains(&slot) {
+            return Err(format!("there's no slot {}, use 1-{}", slot, SLOTS));
+        }
+
+        self.slot = slot;
+
+        Ok(())
+    }
+
+    pub fn path(&self, slot: u8) -> PathBuf {
+        self.dir.join(format!("{}.slot{}.state", self.rom_hash, slot))
+    }
+
+    pub fn save(&self, chip8: &Chip8) -> Result<PathBuf, String> {
+        let path = self.path(self.slot);
+
+        fs::create_dir_all(&self.dir).map_err(|e| format!("Unable to create {}: {}", self.dir.display(), e))?;
+        fs::write(&path, chip8.save_state()).map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+
+        Ok(path)
+    }
+
+    // an empty slot is an error the caller can show, not a crash
+    pub fn load(&self, chip8: &mut Chip8) -> Result<PathBuf, String> {
+        let path = self.path(self.slot);
+
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(format!("Slot {} is empty", self.slot)),
+            Err(e) => return Err(format!("Unable to read {}: {}", path.display(), e)),
+        };
+
+        chip8
+            .load_state(&data)
+            .map_err(|e| format!("Unable to load slot {}: {}", self.slot, e))?;
+
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROM: [u8; 6] = [0x60, 0x2A, 0x70, 0x01, 0x12, 0x02];
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("chip8-emu-slots-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        dir
+    }
+
+    fn running_chip8(rom: &[u8]) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load(rom).unwrap();
+        chip8.run_frame(3);
+
+        chip8
+    }
+
+    #[test]
+    fn names_files_by_hash_and_slot() {
+        let mut slots = SaveSlots::new(PathBuf::from("states"), "abc123".to_string());
+
+        assert_eq!(slots.path(slots.slot()), PathBuf::from("states/abc123.slot1.state"));
+
+        slots.select(9).unwrap();
+        assert_eq!(slots.slot(), 9);
+        assert_eq!(slots.path(slots.slot()), PathBuf::from("states/abc123.slot9.state"));
+
+        assert!(slots.select(0).is_err());
+        assert!(slots.select(10).is_err());
+        assert_eq!(slots.slot(), 9);
+    }
+
+    #[test]
+    fn saves_and_loads_a_slot() {
+        let dir = temp_dir("round-trip");
+        let mut chip8 = running_chip8(&ROM);
+        let hash = chip8.loaded_rom().unwrap().sha256_hex();
+        let mut slots = SaveSlots::new(dir.clone(), hash);
+        slots.select(3).unwrap();
+
+        assert!(slots.load(&mut chip8).unwrap_err().contains("Slot 3 is empty"));
+
+        slots.save(&chip8).unwrap();
+        let saved_frame = chip8.frame_count();
+        chip8.run_frame(3);
+
+        slots.load(&mut chip8).unwrap();
+        assert_eq!(chip8.frame_count(), saved_frame);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_slots_from_another_rom() {
+        let dir = temp_dir("other-rom");
+        let chip8 = running_chip8(&ROM);
+        let slots = SaveSlots::new(dir.clone(), "shared".to_string());
+        slots.save(&chip8).unwrap();
+
+        let mut other = running_chip8(&[0x12, 0x00]);
+        let error = slots.load(&mut other).unwrap_err();
+        assert!(error.contains("different ROM"), "{}", error);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
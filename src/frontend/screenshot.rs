@@ -0,0 +1,211 @@
+use chip8_emu::{diagnostics, Chip8, GifRecorder, PackedScreen, Palette};
+
+use chrono::{Local, NaiveDateTime};
+
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_DIR: &str = "screenshots";
+pub const DEFAULT_GIF_FPS: u32 = 30;
+pub const DEFAULT_REPLAY_SECONDS: u32 = 30;
+
+// the ROM's file name without extension, safe to use in another file name
+pub fn rom_stem(rom: &str) -> String {
+    let file_name = rom.rsplit(['/', '\\']).next().unwrap_or(rom);
+    let stem = Path::new(file_name)
+        .file_stem()
+        .map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+
+    let stem: String = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+
+    if stem.is_empty() {
+        String::from("chip8")
+    } else {
+        stem
+    }
+}
+
+// romname-YYYYMMDD-HHMMSS.ext, with -2, -3... for several captures in the same second
+pub fn capture_path<F>(dir: &Path, rom: &str, time: NaiveDateTime, extension: &str, exists: F) -> PathBuf
+where
+    F: Fn(&Path) -> bool,
+{
+    let base = format!("{}-{}", rom_stem(rom), time.format("%Y%m%d-%H%M%S"));
+    let mut path = dir.join(format!("{}.{}", base, extension));
+    let mut count = 1;
+
+    while exists(&path) {
+        count += 1;
+        path = dir.join(format!("{}-{}.{}", base, count, extension));
+    }
+
+    path
+}
+
+fn new_capture_path(dir: &Path, rom: &str, extension: &str) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Unable to create {}: {}", dir.display(), e))?;
+
+    Ok(capture_path(dir, rom, Local::now().naive_local(), extension, |path| path.exists()))
+}
+
+pub fn save(chip8: &Chip8, dir: &Path, rom: &str, scale: u32, palette: Palette) -> Result<PathBuf, String> {
+    let path = new_capture_path(dir, rom, "png")?;
+
+    chip8
+        .save_png(&path, scale, palette)
+        .map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+
+    Ok(path)
+}
+
+pub struct GifCapture {
+    path: PathBuf,
+    recorder: GifRecorder<BufWriter<File>>,
+}
+
+// frames go straight to the file, so long recordings don't pile up in memory
+pub fn start_gif(dir: &Path, rom: &str, scale: u32, palette: Palette, fps: u32) -> Result<GifCapture, String> {
+    let path = new_capture_path(dir, rom, "gif")?;
+    let file = File::create(&path).map_err(|e| format!("Unable to create {}: {}", path.display(), e))?;
+    let recorder = GifRecorder::new(BufWriter::new(file), scale, palette, fps)
+        .map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+
+    Ok(GifCapture { path, recorder })
+}
+
+impl GifCapture {
+    pub fn push_frame(&mut self, chip8: &Chip8) -> Result<(), String> {
+        self.push_screen(chip8.get_display())
+    }
+
+    fn push_screen(&mut self, screen: &[bool]) -> Result<(), String> {
+        self.recorder
+            .push_frame(screen)
+            .map_err(|e| format!("Unable to write {}: {}", self.path.display(), e))
+    }
+
+    pub fn finish(self) -> Result<PathBuf, String> {
+        match self.recorder.finish() {
+            Ok(_) => Ok(self.path),
+            Err(e) => Err(format!("Unable to write {}: {}", self.path.display(), e)),
+        }
+    }
+}
+
+// a diagnostics bundle in its own romname-YYYYMMDD-HHMMSS.dump directory
+pub fn save_dump(chip8: &Chip8, dir: &Path, rom: &str, reason: &str) -> Result<PathBuf, String> {
+    let path = new_capture_path(dir, rom, "dump")?;
+
+    diagnostics::write_bundle(chip8, &path, reason).map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+
+    Ok(path)
+}
+
+// the replay buffer written out like an F11 recording that started that long ago
+pub fn save_replay(screens: &[PackedScreen], dir: &Path, rom: &str, scale: u32, palette: Palette, fps: u32) -> Result<PathBuf, String> {
+    if screens.is_empty() {
+        return Err("Nothing to save yet, the replay buffer is empty".to_string());
+    }
+
+    let mut capture = start_gif(dir, rom, scale, palette, fps)?;
+
+    for screen in screens {
+        capture.push_screen(&screen.unpack())?;
+    }
+
+    capture.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::NaiveDate;
+
+    fn time() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 3, 9).unwrap().and_hms_opt(7, 5, 42).unwrap()
+    }
+
+    #[test]
+    fn names_shots_after_the_rom_and_time() {
+        let path = capture_path(Path::new("shots"), "roms/Space Invaders.ch8", time(), "png", |_| false);
+        assert_eq!(path, PathBuf::from("shots/Space_Invaders-20240309-070542.png"));
+
+        let path = capture_path(Path::new("shots"), "https://example.com/pong.ch8", time(), "gif", |_| false);
+        assert_eq!(path, PathBuf::from("shots/pong-20240309-070542.gif"));
+    }
+
+    #[test]
+    fn numbers_shots_taken_in_the_same_second() {
+        let taken = [
+            PathBuf::from("shots/pong-20240309-070542.png"),
+            PathBuf::from("shots/pong-20240309-070542-2.png"),
+        ];
+
+        let is_taken = |path: &Path| taken.iter().any(|p| p == path);
+        let path = capture_path(Path::new("shots"), "pong.ch8", time(), "png", is_taken);
+        assert_eq!(path, PathBuf::from("shots/pong-20240309-070542-3.png"));
+    }
+
+    #[test]
+    fn falls_back_to_a_generic_name() {
+        assert_eq!(rom_stem(""), "chip8");
+        assert_eq!(rom_stem("dir/"), "chip8");
+    }
+
+    #[test]
+    fn creates_the_directory() {
+        let dir = std::env::temp_dir().join(format!("chip8-emu-shots-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let path = save(&Chip8::new(), &dir.join("nested"), "pong.ch8", 2, Palette::default()).unwrap();
+        assert!(path.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn saves_dumps_side_by_side() {
+        let dir = std::env::temp_dir().join(format!("chip8-emu-dumps-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let first = save_dump(&Chip8::new(), &dir, "pong.ch8", "hotkey").unwrap();
+        let second = save_dump(&Chip8::new(), &dir, "pong.ch8", "signal").unwrap();
+
+        assert_ne!(first, second);
+        assert!(first.file_name().unwrap().to_string_lossy().starts_with("pong-"));
+        assert!(first.join(diagnostics::MANIFEST_FILE).is_file() && second.join(diagnostics::MANIFEST_FILE).is_file());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn saves_the_replay_buffer() {
+        let dir = std::env::temp_dir().join(format!("chip8-emu-replay-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut chip8 = Chip8::new();
+        // draw "0" at (0, 0) over and over, so it blinks
+        chip8.load(&[0xD0, 0x05, 0x12, 0x00]).unwrap();
+        chip8.set_replay_capacity(10);
+
+        assert!(save_replay(&chip8.replay_screens(), &dir, "pong.ch8", 1, Palette::default(), 60).is_err());
+
+        // one draw a frame
+        for _ in 0..20 {
+            chip8.run_frame(2);
+        }
+
+        let path = save_replay(&chip8.replay_screens(), &dir, "pong.ch8", 1, Palette::default(), 60).unwrap();
+        let gif = fs::read(&path).unwrap();
+        assert!(gif.starts_with(b"GIF89a"));
+        // a graphic control extension for each of the 10 frames
+        assert_eq!(gif.windows(3).filter(|bytes| *bytes == [0x21, 0xF9, 0x04]).count(), 10);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
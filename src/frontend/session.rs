@@ -0,0 +1,305 @@
+// the emulation loop for frontends that only bring a window: config, the fixed timestep, palette
+// and hotkeys are handled here so the backends can't drift apart
+
+use super::bindings::Action;
+use super::cli::Config;
+use super::keypad_overlay;
+use super::paint::RgbaPainter;
+use super::rom_overrides;
+use super::speed;
+use super::timestep::Timestep;
+use super::viewport::Viewport;
+
+use chip8_emu::report::RunError;
+use chip8_emu::{Chip8, Palette, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use std::time::Instant;
+
+pub trait Window {
+    fn is_open(&self) -> bool;
+
+    // the drawable area in pixels, the screen is drawn at the largest whole scale that fits
+    fn size(&self) -> (usize, usize);
+
+    // the keypad as it is now, and the actions whose keys went down or up since the last call
+    fn poll(&mut self, input: &mut Input);
+
+    // `rgba` is the screen scaled to `width` x `height`
+    fn present(&mut self, rgba: &[u8], width: usize, height: usize) -> Result<(), String>;
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Input {
+    pub keypad: [bool; 16],
+    pub pressed: Vec<Action>,
+    pub released: Vec<Action>,
+}
+
+pub struct Session {
+    chip8: Chip8,
+    config: Config,
+    palette: Palette,
+    timestep: Timestep,
+    rgba: Vec<u8>,
+    is_keypad: bool,
+    is_quitting: bool,
+}
+
+impl Session {
+    pub fn new(config: Config, buffer: &[u8], now: Instant) -> Result<Self, String> {
+        let mut chip8 = Chip8::new();
+        chip8.load_named(buffer, &config.rom).map_err(|e| format!("Unable to load {}: {}", config.rom, e))?;
+        chip8.set_quirks(config.initial_quirks());
+
+        if let Some(seed) = config.seed {
+            chip8.set_seed(seed);
+        }
+
+        chip8.set_beep_config(config.beep);
+        chip8.set_paused(config.is_start_paused);
+
+        for &address in &config.breakpoints {
+            chip8.add_breakpoint(address);
+        }
+
+        Ok(Self {
+            chip8,
+            palette: config.palette,
+            config,
+            timestep: Timestep::new(now),
+            rgba: Vec::new(),
+            is_keypad: false,
+            is_quitting: false,
+        })
+    }
+
+    // the hotkeys every backend supports, the rest are SDL-only for now
+    pub fn handle(&mut self, input: &Input) {
+        for (index, &is_down) in input.keypad.iter().enumerate() {
+            self.chip8.keypress(index, is_down);
+        }
+
+        for &action in &input.pressed {
+            match action {
+                Action::Quit => self.is_quitting = true,
+                Action::Reset => self.chip8.soft_reset(),
+                Action::Pause => self.chip8.set_paused(!self.chip8.is_paused()),
+                Action::FrameAdvance if self.chip8.is_paused() => {
+                    self.chip8.advance_frame(self.config.ticks_per_frame);
+                    self.show_fault();
+                },
+                Action::Step if self.chip8.is_paused() => {
+                    if let Err(fault) = self.chip8.tick() {
+                        eprintln!("{}", RunError::from_fault(&self.chip8, &fault).report);
+                    }
+                },
+                Action::Turbo => self.chip8.set_speed_multiplier(self.config.turbo_speed as f64),
+                Action::SpeedUp => self.change_speed(speed::faster(self.config.ticks_per_frame)),
+                Action::SpeedDown => self.change_speed(speed::slower(self.config.ticks_per_frame)),
+                Action::SpeedReset => self.change_speed(self.config.default_ticks_per_frame),
+                Action::ToggleKeypad => self.is_keypad = !self.is_keypad,
+                Action::CyclePalette => {
+                    self.palette = self.palette.next();
+                    println!("Palette: {}", self.palette);
+
+                    let palette = self.palette.to_string();
+                    if let Err(message) = rom_overrides::remember(&self.chip8, |saved| saved.palette = Some(palette)) {
+                        eprintln!("{}", message);
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        if input.released.contains(&Action::Turbo) {
+            self.chip8.set_speed_multiplier(1.0);
+        }
+    }
+
+    fn change_speed(&mut self, ticks_per_frame: usize) {
+        self.config.ticks_per_frame = ticks_per_frame;
+        println!("{}", speed::describe(ticks_per_frame));
+
+        let saved = (ticks_per_frame != self.config.default_ticks_per_frame).then_some(ticks_per_frame);
+        if let Err(message) = rom_overrides::remember(&self.chip8, |overrides| overrides.speed = saved) {
+            eprintln!("{}", message);
+        }
+    }
+
+    pub fn update(&mut self, now: Instant) {
+        for _ in 0..self.timestep.frames(now) {
+            self.chip8.run_frame(self.config.ticks_per_frame);
+        }
+
+        if let Some(address) = self.chip8.take_breakpoint_hit() {
+            println!("Breakpoint at {:03X}", address);
+        }
+
+        self.show_fault();
+    }
+
+    // a fault pauses the machine, the report goes to the console
+    fn show_fault(&mut self) {
+        if let Some(fault) = self.chip8.take_fault() {
+            eprintln!("{}", RunError::from_fault(&self.chip8, &fault).report);
+        }
+    }
+
+    pub fn frame(&mut self, drawable: (usize, usize)) -> (&[u8], usize, usize) {
+        let viewport = Viewport::fit(
+            (drawable.0 as u32, drawable.1 as u32),
+            (SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32),
+        );
+        let (width, height) = (viewport.width as usize, viewport.height as usize);
+
+        self.rgba.resize(width * height * 4, 0);
+        self.chip8.render_rgba_scaled(self.palette, viewport.scale as usize, &mut self.rgba);
+
+        if self.is_keypad {
+            let labels = self.config.bindings.keypad_names();
+            let size = (viewport.scale / 6).max(2);
+            let left = width as i32 - keypad_overlay::layout(&labels, size).width as i32;
+            let mut painter = RgbaPainter::new(&mut self.rgba, width, height);
+            keypad_overlay::draw(&mut painter, self.chip8.keys(), &labels, left, 0, size);
+        }
+
+        (&self.rgba, width, height)
+    }
+
+    pub fn run<W: Window>(&mut self, window: &mut W) -> Result<(), String> {
+        let mut input = Input::default();
+
+        while window.is_open() && !self.is_quitting {
+            input.pressed.clear();
+            input.released.clear();
+            window.poll(&mut input);
+
+            self.handle(&input);
+            self.update(Instant::now());
+
+            let (rgba, width, height) = self.frame(window.size());
+            window.present(rgba, width, height)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60 + 1);
+
+    // plays back scripted input and keeps what it was shown
+    struct NullWindow {
+        script: Vec<Input>,
+        frames: Vec<Vec<u8>>,
+        size: (usize, usize),
+    }
+
+    impl Window for NullWindow {
+        fn is_open(&self) -> bool {
+            !self.script.is_empty()
+        }
+
+        // a bit over 2x, the leftover becomes bars
+        fn size(&self) -> (usize, usize) {
+            (150, 70)
+        }
+
+        fn poll(&mut self, input: &mut Input) {
+            *input = self.script.remove(0);
+        }
+
+        fn present(&mut self, rgba: &[u8], width: usize, height: usize) -> Result<(), String> {
+            self.frames.push(rgba.to_vec());
+            self.size = (width, height);
+            Ok(())
+        }
+    }
+
+    fn session(rom: &[u8]) -> Session {
+        let mut config = Config::new("test.ch8".to_string());
+        config.palette = Palette::named("white").unwrap();
+        Session::new(config, rom, Instant::now()).unwrap()
+    }
+
+    #[test]
+    fn runs_until_the_window_closes() {
+        // draw the "0" glyph at (0, 0), then loop
+        let mut session = session(&[0xD0, 0x05, 0x12, 0x02]);
+        let mut window = NullWindow { script: vec![Input::default(); 3], frames: Vec::new(), size: (0, 0) };
+
+        session.chip8.tick().unwrap();
+        session.run(&mut window).unwrap();
+
+        assert_eq!(window.frames.len(), 3);
+        assert_eq!(window.size, (128, 64));
+        // the glyph's top left pixel fills a 2x2 block
+        assert_eq!(window.frames[2][..4], [255, 255, 255, 255]);
+        assert_eq!(window.frames[2][128 * 4..128 * 4 + 4], [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn handles_the_shared_hotkeys() {
+        let mut session = session(&[0x12, 0x00]);
+        let press = |action| Input { pressed: vec![action], ..Input::default() };
+
+        session.handle(&press(Action::Pause));
+        assert!(session.chip8.is_paused());
+
+        session.handle(&press(Action::Turbo));
+        assert_eq!(session.chip8.speed_multiplier(), session.config.turbo_speed as f64);
+        session.handle(&Input { released: vec![Action::Turbo], ..Input::default() });
+        assert_eq!(session.chip8.speed_multiplier(), 1.0);
+
+        let mut keypad = [false; 16];
+        keypad[0xA] = true;
+        session.handle(&Input { keypad, ..Input::default() });
+        assert!(session.chip8.keys()[0xA]);
+
+        let mut window = NullWindow { script: vec![press(Action::Quit), Input::default()], frames: Vec::new(), size: (0, 0) };
+        session.run(&mut window).unwrap();
+
+        assert!(session.is_quitting);
+        assert_eq!(window.frames.len(), 1);
+    }
+
+    #[test]
+    fn starts_paused_and_stops_at_breakpoints() {
+        let start = Instant::now();
+        let mut config = Config::new("test.ch8".to_string());
+        config.is_start_paused = true;
+        config.breakpoints = vec![0x204];
+        // V0 += 1 twice, then loop
+        let mut session = Session::new(config, &[0x70, 0x01, 0x70, 0x01, 0x12, 0x04], start).unwrap();
+
+        // a long wait while paused builds up nothing to catch up on
+        session.update(start + Duration::from_secs(10));
+        assert_eq!(session.chip8.frame_count(), 0);
+        assert_eq!(session.chip8.program_counter(), 0x200);
+
+        session.handle(&Input { pressed: vec![Action::Pause], ..Input::default() });
+        session.update(start + Duration::from_secs(10) + FRAME);
+        assert!(session.chip8.is_paused());
+        assert_eq!(session.chip8.program_counter(), 0x204);
+        assert_eq!(session.chip8.registers()[0], 2);
+    }
+
+    #[test]
+    fn draws_the_keypad_over_the_frame() {
+        let mut session = session(&[0x12, 0x00]);
+        let top_right = |session: &mut Session| session.frame((128, 64)).0[(128 - 1) * 4..128 * 4].to_vec();
+
+        assert_eq!(top_right(&mut session), [0, 0, 0, 255]);
+
+        // the overlay's translucent margin darkens the white background
+        session.palette = Palette { background: (255, 255, 255), ..session.palette };
+        assert_eq!(top_right(&mut session), [255, 255, 255, 255]);
+        session.handle(&Input { pressed: vec![Action::ToggleKeypad], ..Input::default() });
+        assert_eq!(top_right(&mut session), [65, 65, 65, 255]);
+    }
+}
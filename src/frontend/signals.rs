@@ -0,0 +1,48 @@
+// `kill -USR1` asks a running emulator for a dump bundle. the handler only sets a flag, the main
+// loop picks it up between frames so emulation carries on
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static IS_DUMP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn request_dump(_: libc::c_int) {
+    IS_DUMP_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+#[cfg(unix)]
+pub fn install() {
+    let handler = request_dump as extern "C" fn(libc::c_int);
+
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+    unsafe {
+        libc::signal(libc::SIGUSR1, handler as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn install() {}
+
+// true once for each request
+pub fn take_dump_request() -> bool {
+    IS_DUMP_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_signal_requests_one_dump() {
+        install();
+        take_dump_request();
+
+        // SAFETY: raising a signal this process has a handler for
+        unsafe {
+            libc::raise(libc::SIGUSR1);
+        }
+
+        assert!(take_dump_request());
+        assert!(!take_dump_request());
+    }
+}
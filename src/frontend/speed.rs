@@ -0,0 +1,51 @@
+// the steps the speed hotkeys move through, in instructions per frame
+pub const STEPS: &[usize] = &[1, 2, 3, 5, 7, 10, 12, 15, 20, 30, 50, 75, 100, 150, 200, 300, 500, 1000];
+
+const FRAMES_PER_SECOND: usize = 60;
+
+// a speed between steps goes to the nearest step in that direction
+pub fn faster(ticks_per_frame: usize) -> usize {
+    STEPS.iter().copied().find(|&step| step > ticks_per_frame).unwrap_or(ticks_per_frame)
+}
+
+pub fn slower(ticks_per_frame: usize) -> usize {
+    STEPS.iter().rev().copied().find(|&step| step < ticks_per_frame).unwrap_or(ticks_per_frame)
+}
+
+// "Speed 15 (900 ips)"
+pub fn describe(ticks_per_frame: usize) -> String {
+    format!("Speed {} ({} ips)", ticks_per_frame, ticks_per_frame * FRAMES_PER_SECOND)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_up_and_down() {
+        assert_eq!(faster(10), 12);
+        assert_eq!(slower(10), 7);
+        assert_eq!(faster(1), 2);
+        assert_eq!(slower(2), 1);
+    }
+
+    #[test]
+    fn speeds_between_steps_snap_to_the_next_one() {
+        assert_eq!(faster(11), 12);
+        assert_eq!(slower(11), 10);
+    }
+
+    #[test]
+    fn stops_at_the_ends() {
+        assert_eq!(slower(1), 1);
+        assert_eq!(faster(1000), 1000);
+        // faster than the last step already, only going down does anything
+        assert_eq!(faster(4000), 4000);
+        assert_eq!(slower(4000), 1000);
+    }
+
+    #[test]
+    fn describes_both_units() {
+        assert_eq!(describe(15), "Speed 15 (900 ips)");
+    }
+}
@@ -0,0 +1,291 @@
+use super::cli::{Config, TerminalStyle};
+use super::half_block;
+use super::held_keys::HeldKeys;
+use super::timestep::Timestep;
+use super::title;
+
+use chip8_emu::{Chip8, DirtyRegion, SCREEN_WIDTH};
+
+use std::io::{self, stdout, Write};
+use std::panic;
+use std::time::{Duration, Instant};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+
+// consecutive changed cells on one row, written with a single cursor move
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Run {
+    pub column: u16,
+    pub row: u16,
+    pub text: String,
+}
+
+// what the terminal is showing, so each frame only rewrites the cells that changed.
+// over ssh a full redraw every frame is slow enough to flicker
+pub struct ScreenDiff {
+    cells: Vec<char>,
+    cell: fn(bool, bool) -> char,
+}
+
+impl ScreenDiff {
+    pub fn new(cell: fn(bool, bool) -> char) -> Self {
+        let mut diff = Self { cells: Vec::new(), cell };
+        diff.invalidate();
+        diff
+    }
+
+    // forget what's on the terminal, e.g. after a resize cleared it
+    pub fn invalidate(&mut self) {
+        // never drawn, so every cell counts as changed
+        self.cells = vec!['\0'; SCREEN_WIDTH * half_block::ROWS];
+    }
+
+    // the runs to write for `screen`, only looking inside `region`, the library's record of
+    // where pixels changed. after invalidate the region must be the whole screen
+    pub fn changes(&mut self, screen: &[bool], region: DirtyRegion) -> Vec<Run> {
+        let mut runs = Vec::new();
+
+        for row in region.top / 2..region.bottom.div_ceil(2) {
+            let mut run: Option<Run> = None;
+
+            for column in region.left..region.right {
+                let (top, bottom) = half_block::pixels(screen, column, row);
+                let c = (self.cell)(top, bottom);
+                let shown = &mut self.cells[row * SCREEN_WIDTH + column];
+
+                if *shown == c {
+                    runs.extend(run.take());
+                    continue;
+                }
+
+                *shown = c;
+                run.get_or_insert_with(|| Run { column: column as u16, row: row as u16, text: String::new() })
+                    .text
+                    .push(c);
+            }
+
+            runs.extend(run);
+        }
+
+        runs
+    }
+}
+
+pub fn run(config: Config, buffer: Vec<u8>, style: TerminalStyle) -> Result<(), String> {
+    let chip8 = machine(&config, &buffer)?;
+    terminal::enable_raw_mode().map_err(|e| format!("Unable to set up the terminal: {}", e))?;
+
+    // without this the terminal never says when a key goes up, see held_keys
+    let has_releases = terminal::supports_keyboard_enhancement().unwrap_or(false)
+        && execute!(stdout(), PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)).is_ok();
+
+    // a panic would otherwise leave the shell in raw mode on the alternate screen
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        restore(has_releases);
+        default_hook(info);
+    }));
+
+    let result = execute!(stdout(), EnterAlternateScreen, Hide).and_then(|_| play(&config, chip8, style));
+
+    restore(has_releases);
+    let _ = panic::take_hook();
+    result.map_err(|e| format!("Terminal error: {}", e))
+}
+
+fn restore(has_releases: bool) {
+    let mut out = stdout();
+
+    if has_releases {
+        let _ = execute!(out, PopKeyboardEnhancementFlags);
+    }
+
+    let _ = execute!(out, ResetColor, Show, LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+}
+
+fn machine(config: &Config, buffer: &[u8]) -> Result<Chip8, String> {
+    let mut chip8 = Chip8::new();
+    chip8.load_named(buffer, &config.rom).map_err(|e| format!("Unable to load {}: {}", config.rom, e))?;
+    chip8.set_quirks(config.initial_quirks());
+
+    if let Some(seed) = config.seed {
+        chip8.set_seed(seed);
+    }
+
+    chip8.set_paused(config.is_start_paused);
+
+    for &address in &config.breakpoints {
+        chip8.add_breakpoint(address);
+    }
+
+    Ok(chip8)
+}
+
+fn play(config: &Config, mut chip8: Chip8, style: TerminalStyle) -> io::Result<()> {
+
+    let keypad = config.bindings.keypad_chars();
+    let mut keys = HeldKeys::new();
+    let mut timestep = Timestep::new(Instant::now());
+    let mut diff = ScreenDiff::new(match style {
+        TerminalStyle::Blocks => half_block::cell,
+        TerminalStyle::Ascii => half_block::ascii_cell,
+    });
+    let mut is_redraw = true;
+    let mut out = stdout();
+
+    loop {
+        if is_redraw {
+            diff.invalidate();
+            queue!(
+                out,
+                ResetColor,
+                Clear(ClearType::All),
+                MoveTo(0, half_block::ROWS as u16),
+                Print(format!("{} - Esc quits", title::display_name(&config.rom)))
+            )?;
+        }
+
+        let region = if is_redraw { Some(DirtyRegion::FULL) } else { chip8.take_dirty_region() };
+        is_redraw = false;
+
+        if let Some(region) = region {
+            let (r, g, b) = config.palette.foreground;
+            queue!(out, SetForegroundColor(Color::Rgb { r, g, b }))?;
+            let (r, g, b) = config.palette.background;
+            queue!(out, SetBackgroundColor(Color::Rgb { r, g, b }))?;
+
+            for run in diff.changes(chip8.get_display(), region) {
+                queue!(out, MoveTo(run.column, run.row), Print(run.text))?;
+            }
+
+            out.flush()?;
+        }
+
+        // wait for input until the next frame is due, then take everything that queued up
+        let mut timeout = timestep.until_next_frame(Instant::now());
+
+        while event::poll(timeout)? {
+            match event::read()? {
+                Event::Key(key) if is_quit(&key) => return Ok(()),
+                Event::Key(KeyEvent { code: KeyCode::Char(c), kind, .. }) => {
+                    let index = keypad.iter().position(|&key| key == Some(c.to_ascii_lowercase()));
+
+                    match (index, kind) {
+                        (Some(index), KeyEventKind::Release) => keys.release(index),
+                        (Some(index), _) => keys.press(index, Instant::now()),
+                        (None, _) => {},
+                    }
+                },
+                Event::Resize(..) => is_redraw = true,
+                _ => {},
+            }
+
+            timeout = Duration::ZERO;
+        }
+
+        let now = Instant::now();
+
+        for (index, is_down) in keys.held(now).into_iter().enumerate() {
+            chip8.keypress(index, is_down);
+        }
+
+        for _ in 0..timestep.frames(now) {
+            chip8.run_frame(config.ticks_per_frame);
+        }
+    }
+}
+
+fn is_quit(key: &KeyEvent) -> bool {
+    key.kind != KeyEventKind::Release
+        && (key.code == KeyCode::Esc
+            || key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chip8_emu::SCREEN_HEIGHT;
+
+    fn blank() -> Vec<bool> {
+        vec![false; SCREEN_WIDTH * SCREEN_HEIGHT]
+    }
+
+    #[test]
+    fn first_frame_draws_everything() {
+        let mut diff = ScreenDiff::new(half_block::cell);
+        let runs = diff.changes(&blank(), DirtyRegion::FULL);
+
+        assert_eq!(runs.len(), half_block::ROWS);
+        assert!(runs.iter().all(|run| run.column == 0 && run.text == " ".repeat(SCREEN_WIDTH)));
+        assert!(diff.changes(&blank(), DirtyRegion::FULL).is_empty());
+    }
+
+    #[test]
+    fn groups_changed_cells_into_runs() {
+        let mut diff = ScreenDiff::new(half_block::ascii_cell);
+        diff.changes(&blank(), DirtyRegion::FULL);
+
+        let mut screen = blank();
+        // a top pixel and its neighbour's bottom pixel, then a gap, then one more
+        screen[2 * SCREEN_WIDTH + 10] = true;
+        screen[3 * SCREEN_WIDTH + 11] = true;
+        screen[2 * SCREEN_WIDTH + 13] = true;
+
+        let runs = diff.changes(&screen, DirtyRegion::FULL);
+        assert_eq!(
+            runs,
+            [
+                Run { column: 10, row: 1, text: "\",".to_string() },
+                Run { column: 13, row: 1, text: "\"".to_string() },
+            ]
+        );
+
+        diff.invalidate();
+        assert_eq!(diff.changes(&screen, DirtyRegion::FULL).len(), half_block::ROWS);
+    }
+
+    #[test]
+    fn dirty_regions_find_every_change() {
+        // draw digits across the screen, some wrapping, erase one and clear the screen halfway
+        let mut rom = Vec::new();
+        for (digit, x, y) in [(0, 0, 0), (1, 10, 3), (8, 62, 5), (3, 30, 29), (1, 10, 3), (5, 61, 30)] {
+            rom.extend([0x60, digit, 0xF0, 0x29, 0x60, x, 0x61, y, 0xD0, 0x15]);
+        }
+        rom.extend([0x00, 0xE0]);
+        for (digit, x, y) in [(7, 20, 9), (2, 40, 18)] {
+            rom.extend([0x60, digit, 0xF0, 0x29, 0x60, x, 0x61, y, 0xD0, 0x15]);
+        }
+        rom.extend([0x12, rom.len() as u8]);
+
+        let mut chip8 = Chip8::new();
+        chip8.load(&rom).unwrap();
+
+        let mut diff = ScreenDiff::new(half_block::cell);
+        // diffs the whole screen every time, what the region-limited diff has to match
+        let mut reference = ScreenDiff::new(half_block::cell);
+
+        for _ in 0..rom.len() / 2 {
+            chip8.tick().unwrap();
+
+            let region = chip8.take_dirty_region().unwrap_or(DirtyRegion { left: 0, top: 0, right: 0, bottom: 0 });
+            let runs = diff.changes(chip8.get_display(), region);
+
+            assert_eq!(runs, reference.changes(chip8.get_display(), DirtyRegion::FULL));
+
+            for run in &runs {
+                let columns = run.column as usize..run.column as usize + run.text.chars().count();
+                assert!(columns.clone().all(|column| region.contains(column, run.row as usize * 2)
+                    || region.contains(column, run.row as usize * 2 + 1)));
+            }
+        }
+    }
+}
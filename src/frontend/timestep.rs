@@ -0,0 +1,98 @@
+use chip8_emu::FrameClock;
+
+#[cfg(any(feature = "tui", feature = "terminal"))]
+use std::time::Duration;
+use std::time::Instant;
+
+// most frames run to catch up after a hitch, longer stalls just lose time
+pub const MAX_CATCH_UP_FRAMES: u32 = 5;
+#[cfg(any(feature = "tui", feature = "terminal"))]
+pub const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+// the fixed 60 Hz step every frontend emulates in, whatever rate it draws at
+pub struct Timestep {
+    clock: FrameClock,
+    last_update: Instant,
+}
+
+impl Timestep {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            clock: FrameClock::new(MAX_CATCH_UP_FRAMES),
+            last_update: now,
+        }
+    }
+
+    // whole frames to emulate for the time since the last call
+    pub fn frames(&mut self, now: Instant) -> u32 {
+        let frames = self.clock.advance(now.saturating_duration_since(self.last_update));
+        self.last_update = now;
+        frames
+    }
+
+    // how long until another frame is due, for frontends that sleep while waiting for input
+    #[cfg(any(feature = "tui", feature = "terminal"))]
+    pub fn until_next_frame(&self, now: Instant) -> Duration {
+        let due = self.last_update + FRAME_TIME.mul_f64(1.0 - self.clock.frame_progress());
+        due.saturating_duration_since(now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chip8_emu::Chip8;
+
+    use std::time::Duration;
+
+    #[test]
+    fn counts_whole_frames() {
+        let start = Instant::now();
+        let mut timestep = Timestep::new(start);
+
+        let ms = |millis| start + Duration::from_millis(millis);
+
+        assert_eq!(timestep.frames(ms(8)), 0);
+        // the 8 ms left over count towards these
+        assert_eq!(timestep.frames(ms(34)), 2);
+        assert_eq!(timestep.frames(ms(2000)), MAX_CATCH_UP_FRAMES);
+    }
+
+    #[test]
+    fn a_paused_machine_has_nothing_to_catch_up_on() {
+        let start = Instant::now();
+        let mut timestep = Timestep::new(start);
+        let mut chip8 = Chip8::new();
+        // V0 += 1, jump back
+        chip8.load(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        chip8.set_paused(true);
+
+        // the loop keeps turning while paused, every frame it's due is a frame that does nothing
+        for second in 1..=10 {
+            for _ in 0..timestep.frames(start + Duration::from_secs(second)) {
+                chip8.run_frame(10);
+            }
+        }
+
+        assert_eq!((chip8.frame_count(), chip8.instruction_count()), (0, 0));
+
+        chip8.set_paused(false);
+        let frames = timestep.frames(start + Duration::from_secs(10) + Duration::from_millis(17));
+        assert_eq!(frames, 1);
+    }
+
+    #[cfg(any(feature = "tui", feature = "terminal"))]
+    #[test]
+    fn waits_out_the_rest_of_the_frame() {
+        let start = Instant::now();
+        let mut timestep = Timestep::new(start);
+        assert_eq!(timestep.until_next_frame(start), FRAME_TIME);
+
+        timestep.frames(start + FRAME_TIME / 4);
+        let wait = timestep.until_next_frame(start + FRAME_TIME / 2);
+        assert!(wait.abs_diff(FRAME_TIME / 2) < Duration::from_micros(1), "{:?}", wait);
+
+        assert_eq!(timestep.until_next_frame(start + FRAME_TIME * 2), Duration::ZERO);
+    }
+}
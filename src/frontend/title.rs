@@ -0,0 +1,225 @@
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+pub const APP_NAME: &str = "Chip-8 Emulator";
+// rewriting the title every frame makes some window managers flicker
+pub const UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+// rates are averaged over this much recent history
+const AVERAGE_WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Status<'a> {
+    pub name: Option<&'a str>,
+    pub fps: f64,
+    pub ips: f64,
+    pub is_paused: bool,
+    pub is_turbo: bool,
+    pub is_rewinding: bool,
+    pub is_recording: bool,
+}
+
+// "pong - 60 fps, 600 ips [PAUSED] - Chip-8 Emulator"
+pub fn format(status: &Status) -> String {
+    let mut title = String::new();
+
+    if let Some(name) = status.name {
+        title.push_str(name);
+        title.push_str(" - ");
+    }
+
+    title.push_str(&format!("{:.0} fps, {:.0} ips", status.fps, status.ips));
+
+    let tags = [
+        (status.is_paused, "[PAUSED]"),
+        (status.is_turbo, "[TURBO]"),
+        (status.is_rewinding, "[REWIND]"),
+        (status.is_recording, "[REC]"),
+    ];
+
+    for (_, tag) in tags.iter().filter(|(is_on, _)| *is_on) {
+        title.push(' ');
+        title.push_str(tag);
+    }
+
+    title.push_str(" - ");
+    title.push_str(APP_NAME);
+    title
+}
+
+// the file name without directories or extension, as typed, for display only
+pub fn display_name(rom: &str) -> &str {
+    let file_name = rom.rsplit(['/', '\\']).next().unwrap_or(rom);
+
+    Path::new(file_name)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .filter(|stem| !stem.is_empty())
+        .unwrap_or(file_name)
+}
+
+// presented frames and executed instructions over the last second
+#[derive(Clone, Debug, Default)]
+pub struct RateMeter {
+    samples: VecDeque<(Instant, u64)>,
+    next_update: Option<Instant>,
+}
+
+impl RateMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // called once per presented frame with the machine's running instruction count
+    pub fn record(&mut self, now: Instant, instruction_count: u64) {
+        self.samples.push_back((now, instruction_count));
+
+        while let Some(&(oldest, _)) = self.samples.front() {
+            if now - oldest <= AVERAGE_WINDOW {
+                break;
+            }
+
+            self.samples.pop_front();
+        }
+    }
+
+    // (frames per second, instructions per second), zero until two frames have been seen
+    pub fn rates(&self) -> (f64, f64) {
+        let ((first_time, first_count), (last_time, last_count)) = match (self.samples.front(), self.samples.back()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => return (0.0, 0.0),
+        };
+
+        let elapsed = (last_time - first_time).as_secs_f64();
+
+        if elapsed == 0.0 {
+            return (0.0, 0.0);
+        }
+
+        let frames = (self.samples.len() - 1) as f64;
+        // a reset or rewind can move the count backwards
+        let instructions = last_count.saturating_sub(first_count) as f64;
+
+        (frames / elapsed, instructions / elapsed)
+    }
+
+    // true at most once per UPDATE_INTERVAL
+    pub fn is_due(&mut self, now: Instant) -> bool {
+        match self.next_update {
+            Some(next_update) if now < next_update => false,
+            _ => {
+                self.next_update = Some(now + UPDATE_INTERVAL);
+                true
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `frames` evenly spaced frames at `fps`, each running `per_frame` instructions
+    fn meter_at(fps: u32, per_frame: u64, frames: u32) -> (RateMeter, Instant) {
+        let start = Instant::now();
+        let mut meter = RateMeter::new();
+        let mut now = start;
+
+        for frame in 0..frames {
+            now = start + Duration::from_secs(1) * frame / fps;
+            meter.record(now, frame as u64 * per_frame);
+        }
+
+        (meter, now)
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 0.01, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn measures_steady_rates() {
+        let (meter, _) = meter_at(60, 10, 120);
+        let (fps, ips) = meter.rates();
+
+        assert_close(fps, 60.0);
+        assert_close(ips, 600.0);
+    }
+
+    #[test]
+    fn follows_a_change_of_pace_within_a_second() {
+        let (mut meter, mut now) = meter_at(60, 10, 120);
+        let mut count = 119 * 10;
+
+        // the display drops to 30 Hz and turbo runs 80 instructions a frame
+        for _ in 0..60 {
+            now += Duration::from_secs(1) / 30;
+            count += 80;
+            meter.record(now, count);
+        }
+
+        let (fps, ips) = meter.rates();
+        assert_close(fps, 30.0);
+        assert_close(ips, 2400.0);
+    }
+
+    #[test]
+    fn needs_two_frames() {
+        assert_eq!(RateMeter::new().rates(), (0.0, 0.0));
+        assert_eq!(meter_at(60, 10, 1).0.rates(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn a_count_going_backwards_reads_as_zero() {
+        let start = Instant::now();
+        let mut meter = RateMeter::new();
+        meter.record(start, 5000);
+        meter.record(start + Duration::from_millis(500), 100);
+
+        assert_eq!(meter.rates(), (2.0, 0.0));
+    }
+
+    #[test]
+    fn throttles_updates() {
+        let start = Instant::now();
+        let mut meter = RateMeter::new();
+
+        assert!(meter.is_due(start));
+        assert!(!meter.is_due(start + Duration::from_millis(100)));
+        assert!(meter.is_due(start + UPDATE_INTERVAL));
+        assert!(!meter.is_due(start + UPDATE_INTERVAL + Duration::from_millis(249)));
+    }
+
+    #[test]
+    fn formats_the_title() {
+        let status = Status {
+            name: Some("pong"),
+            fps: 59.8,
+            ips: 600.4,
+            ..Status::default()
+        };
+        assert_eq!(format(&status), "pong - 60 fps, 600 ips - Chip-8 Emulator");
+
+        let status = Status {
+            is_paused: true,
+            is_recording: true,
+            ..Status::default()
+        };
+        assert_eq!(format(&status), "0 fps, 0 ips [PAUSED] [REC] - Chip-8 Emulator");
+
+        let status = Status {
+            is_turbo: true,
+            is_rewinding: true,
+            ..status
+        };
+        assert!(format(&status).contains("[PAUSED] [TURBO] [REWIND] [REC]"));
+    }
+
+    #[test]
+    fn names_roms_by_file_stem() {
+        assert_eq!(display_name("roms/Space Invaders [David Winter].ch8"), "Space Invaders [David Winter]");
+        assert_eq!(display_name("C:\\games\\pong.ch8"), "pong");
+        assert_eq!(display_name("https://example.com/roms/tetris.ch8"), "tetris");
+        assert_eq!(display_name("noext"), "noext");
+    }
+}
@@ -0,0 +1,267 @@
+use super::cli::Config;
+use super::debug_sessions;
+use super::debugger::{self, Command};
+use super::half_block;
+use super::held_keys::HeldKeys;
+use super::overlay;
+use super::timestep::Timestep;
+use super::title;
+
+use chip8_emu::{Chip8, DEFAULT_STEP_BACK_INTERVAL, SCREEN_WIDTH};
+
+use std::io::{self, stdout};
+use std::time::{Duration, Instant};
+
+use ratatui::crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use ratatui::crossterm::{execute, terminal};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+// the screen pane: 64x16 cells plus its border
+const SCREEN_PANE_WIDTH: u16 = SCREEN_WIDTH as u16 + 2;
+const SCREEN_PANE_HEIGHT: u16 = half_block::ROWS as u16 + 2;
+// the register rows of the overlay, without its short instruction list
+const REGISTER_LINES: usize = 6;
+
+struct TuiDebugger {
+    chip8: Chip8,
+    config: Config,
+    keypad: [Option<char>; 16],
+    keys: HeldKeys,
+    timestep: Timestep,
+    // Some while typing after ':'
+    command: Option<String>,
+    last_command: Option<Command>,
+    message: String,
+    is_quitting: bool,
+}
+
+pub fn run(config: Config, buffer: Vec<u8>) -> Result<(), String> {
+    let mut debugger = TuiDebugger::new(config, &buffer)?;
+    let mut terminal = ratatui::try_init().map_err(|e| format!("Unable to set up the terminal: {}", e))?;
+
+    // without this the terminal never says when a key goes up, see held_keys
+    let has_releases = terminal::supports_keyboard_enhancement().unwrap_or(false)
+        && execute!(stdout(), PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)).is_ok();
+
+    let result = debugger.run(&mut terminal);
+
+    if has_releases {
+        let _ = execute!(stdout(), PopKeyboardEnhancementFlags);
+    }
+
+    ratatui::restore();
+    debug_sessions::remember(&debugger.chip8);
+    result.map_err(|e| format!("Terminal error: {}", e))
+}
+
+impl TuiDebugger {
+    fn new(config: Config, buffer: &[u8]) -> Result<Self, String> {
+        let mut chip8 = Chip8::new();
+        chip8.load_named(buffer, &config.rom).map_err(|e| format!("Unable to load {}: {}", config.rom, e))?;
+        chip8.set_quirks(config.initial_quirks());
+
+        if let Some(seed) = config.seed {
+            chip8.set_seed(seed);
+        }
+
+        chip8.set_paused(config.is_start_paused);
+        chip8.set_step_back_interval(DEFAULT_STEP_BACK_INTERVAL);
+        debug_sessions::restore(&mut chip8);
+
+        for &address in &config.breakpoints {
+            chip8.add_breakpoint(address);
+        }
+
+        Ok(Self {
+            chip8,
+            keypad: config.bindings.keypad_chars(),
+            config,
+            keys: HeldKeys::new(),
+            timestep: Timestep::new(Instant::now()),
+            command: None,
+            last_command: None,
+            message: format!("{}, type : for commands, Esc quits", debugger::HELP),
+            is_quitting: false,
+        })
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        while !self.is_quitting {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            // wait for input until the next frame is due, then take everything that queued up
+            let mut timeout = self.timestep.until_next_frame(Instant::now());
+
+            while event::poll(timeout)? {
+                if let Event::Key(key) = event::read()? {
+                    self.handle_key(key);
+                }
+
+                timeout = Duration::ZERO;
+            }
+
+            self.emulate();
+        }
+
+        Ok(())
+    }
+
+    fn emulate(&mut self) {
+        let now = Instant::now();
+        let held = self.keys.held(now);
+
+        for (index, is_down) in held.into_iter().enumerate() {
+            self.chip8.keypress(index, is_down && self.command.is_none());
+        }
+
+        let frames = self.timestep.frames(now);
+
+        for _ in 0..frames {
+            self.chip8.run_frame(self.config.ticks_per_frame);
+
+            if let Some(address) = self.chip8.take_breakpoint_hit() {
+                self.message = format!("Breakpoint at {:03X}", address);
+            }
+
+            if let Some(address) = self.chip8.take_watchpoint_hit() {
+                self.message = format!("Watchpoint at {:03X} written", address);
+            }
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            self.is_quitting = true;
+            return;
+        }
+
+        if key.kind == KeyEventKind::Release {
+            if let Some(index) = self.keypad_index(key.code) {
+                self.keys.release(index);
+            }
+            return;
+        }
+
+        match (&mut self.command, key.code) {
+            (Some(_), KeyCode::Esc) => self.command = None,
+            (Some(line), KeyCode::Backspace) => {
+                line.pop();
+            },
+            (Some(line), KeyCode::Char(c)) => line.push(c),
+            (Some(_), KeyCode::Enter) => {
+                let line = self.command.take().unwrap_or_default();
+                self.execute(&line);
+            },
+            (Some(_), _) => {},
+            (None, KeyCode::Esc) => self.is_quitting = true,
+            (None, KeyCode::Char(':')) if key.kind == KeyEventKind::Press => self.command = Some(String::new()),
+            (None, code) => {
+                if let Some(index) = self.keypad_index(code) {
+                    self.keys.press(index, Instant::now());
+                }
+            },
+        }
+    }
+
+    fn keypad_index(&self, code: KeyCode) -> Option<usize> {
+        match code {
+            KeyCode::Char(c) => self.keypad.iter().position(|&key| key == Some(c.to_ascii_lowercase())),
+            _ => None,
+        }
+    }
+
+    // an empty line repeats the last command, handy for stepping
+    fn execute(&mut self, line: &str) {
+        let parsed = match line.trim() {
+            "" => self.last_command.clone().ok_or_else(|| "type a command, or help".to_string()),
+            line => Command::parse(line),
+        };
+
+        match parsed {
+            Ok(Command::Quit) => self.is_quitting = true,
+            Ok(command) => {
+                self.last_command = Some(command.clone());
+                self.message = command.execute(&mut self.chip8);
+            },
+            Err(message) => self.message = message,
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main, status] = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [left, right] =
+            Layout::horizontal([Constraint::Length(SCREEN_PANE_WIDTH), Constraint::Min(0)]).areas(main);
+        let [screen, _] = Layout::vertical([Constraint::Length(SCREEN_PANE_HEIGHT), Constraint::Min(0)]).areas(left);
+        let watch_lines = debugger::watch_lines(&self.chip8);
+        // the pane only shows up once there's something to watch
+        let watch_height = if watch_lines.is_empty() { 0 } else { watch_lines.len() as u16 + 2 };
+        let [registers, watches, disassembly] = Layout::vertical([
+            Constraint::Length(REGISTER_LINES as u16 + 2),
+            Constraint::Length(watch_height),
+            Constraint::Min(0),
+        ])
+        .areas(right);
+
+        self.draw_screen(frame, screen);
+
+        let register_lines: Vec<Line> = overlay::lines(&self.chip8)
+            .into_iter()
+            .take(REGISTER_LINES)
+            .map(Line::from)
+            .collect();
+        frame.render_widget(Paragraph::new(register_lines).block(Block::bordered().title(" Registers ")), registers);
+
+        if !watch_lines.is_empty() {
+            let lines: Vec<Line> = watch_lines.into_iter().map(Line::from).collect();
+            frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(" Watches ")), watches);
+        }
+
+        self.draw_disassembly(frame, disassembly);
+
+        let status_line = match &self.command {
+            Some(line) => Line::from(format!(":{}", line)),
+            None => Line::from(self.message.as_str()).dim(),
+        };
+        frame.render_widget(Paragraph::new(status_line), status);
+
+        if let Some(line) = &self.command {
+            frame.set_cursor_position((status.x + 1 + line.chars().count() as u16, status.y));
+        }
+    }
+
+    fn draw_screen(&self, frame: &mut Frame, area: Rect) {
+        let (r, g, b) = self.config.palette.foreground;
+        let foreground = Color::Rgb(r, g, b);
+        let (r, g, b) = self.config.palette.background;
+        let background = Color::Rgb(r, g, b);
+
+        let state = if self.chip8.is_paused() { " [PAUSED] " } else { "" };
+        let block = Block::bordered().title(format!(" {} {}", title::display_name(&self.config.rom), state));
+        let rows: Vec<Line> = half_block::rows(self.chip8.get_display()).into_iter().map(Line::from).collect();
+
+        frame.render_widget(
+            Paragraph::new(rows).style(Style::new().fg(foreground).bg(background)).block(block),
+            area,
+        );
+    }
+
+    fn draw_disassembly(&self, frame: &mut Frame, area: Rect) {
+        // an even number of rows keeps PC in the middle
+        let rows = area.height.saturating_sub(2).max(2) / 2 * 2;
+        let pc = self.chip8.program_counter();
+
+        let lines: Vec<Line> = debugger::disassembly_lines(&self.chip8, rows)
+            .into_iter()
+            .map(|(address, text)| if address == pc { Line::from(text).reversed() } else { Line::from(text) })
+            .collect();
+
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(" Disassembly ")), area);
+    }
+}
@@ -0,0 +1,278 @@
+// long captures go to ffmpeg: raw RGBA frames on its stdin and, with sound, 16-bit PCM on a
+// second pipe. ffmpeg logs to stderr, so the second pipe takes the place of its stdout and is
+// read as `pipe:1`
+
+use chip8_emu::{Chip8, Palette, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::io::{self, PipeWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+pub const DEFAULT_FFMPEG: &str = "ffmpeg";
+
+const FRAMES_PER_SECOND: u64 = 60;
+
+// the audio output makes a few samples more or less per frame to stay in step with the sound
+// card, while a video needs exactly sample_rate / 60 per frame. samples that don't fit wait
+// for the next frame and a short frame is padded with its last sample
+#[derive(Clone, Debug)]
+pub struct AudioPacing {
+    sample_rate: u64,
+    frames: u64,
+    samples: u64,
+    pending: VecDeque<f32>,
+}
+
+impl AudioPacing {
+    pub fn new(sample_rate: u32) -> Self {
+        Self { sample_rate: sample_rate as u64, frames: 0, samples: 0, pending: VecDeque::new() }
+    }
+
+    // 44100 Hz is always 735 but 22050 Hz alternates 367 and 368, either way after n frames
+    // there are exactly n * sample_rate / 60 samples
+    pub fn samples_for_frame(&mut self) -> usize {
+        self.frames += 1;
+        let total = self.frames * self.sample_rate / FRAMES_PER_SECOND;
+        let samples = total - self.samples;
+        self.samples = total;
+
+        samples as usize
+    }
+
+    // the next frame of audio as 16-bit little-endian samples
+    pub fn pace(&mut self, generated: &[f32], out: &mut Vec<u8>) {
+        let samples = self.samples_for_frame();
+        self.pending.extend(generated);
+
+        let taken = samples.min(self.pending.len());
+        let last = taken.checked_sub(1).map_or(0.0, |index| self.pending[index]);
+        let frame = self.pending.drain(..taken).chain(std::iter::repeat(last)).take(samples);
+
+        out.clear();
+        for sample in frame {
+            out.extend_from_slice(&((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes());
+        }
+
+        // never let the audio fall more than a frame behind the picture
+        let excess = self.pending.len().saturating_sub(samples);
+        self.pending.drain(..excess);
+    }
+}
+
+pub fn arguments(output: &Path, scale: u32, sample_rate: Option<u32>) -> Vec<OsString> {
+    let mut args: Vec<OsString> = ["-hide_banner", "-loglevel", "error", "-nostdin", "-y"]
+        .into_iter()
+        .map(OsString::from)
+        .collect();
+
+    let video_size = format!("{}x{}", SCREEN_WIDTH, SCREEN_HEIGHT);
+    let framerate = FRAMES_PER_SECOND.to_string();
+    args.extend(
+        ["-f", "rawvideo", "-pixel_format", "rgba", "-video_size", &video_size, "-framerate", &framerate, "-i", "pipe:0"]
+            .map(OsString::from),
+    );
+
+    if let Some(rate) = sample_rate {
+        let rate = rate.to_string();
+        args.extend(["-f", "s16le", "-sample_rate", &rate, "-channels", "1", "-i", "pipe:1"].map(OsString::from));
+    }
+
+    // scaled up by ffmpeg rather than piping 100 times the pixels
+    let filter = format!("scale=iw*{}:ih*{}:flags=neighbor", scale, scale);
+    args.extend(["-vf", &filter, "-pix_fmt", "yuv420p"].map(OsString::from));
+    args.push(output.as_os_str().to_os_string());
+
+    args
+}
+
+// run before the window opens, so a missing ffmpeg doesn't cost a game
+pub fn check_ffmpeg(ffmpeg: &Path) -> Result<(), String> {
+    let status = Command::new(ffmpeg)
+        .arg("-version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("{} -version failed ({}), unable to record video", ffmpeg.display(), status)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(format!(
+            "Recording video needs ffmpeg, `{}` wasn't found. Install it or point --ffmpeg at it",
+            ffmpeg.display()
+        )),
+        Err(e) => Err(format!("Unable to run {}: {}", ffmpeg.display(), e)),
+    }
+}
+
+pub struct VideoCapture {
+    path: PathBuf,
+    child: Child,
+    video: ChildStdin,
+    audio: Option<(PipeWriter, AudioPacing)>,
+    rgba: Vec<u8>,
+    pcm: Vec<u8>,
+}
+
+// `sample_rate` None records without sound
+pub fn start_video(ffmpeg: &Path, path: &Path, scale: u32, sample_rate: Option<u32>) -> Result<VideoCapture, String> {
+    let error = |e: io::Error| format!("Unable to start {}: {}", ffmpeg.display(), e);
+
+    let mut command = Command::new(ffmpeg);
+    command.args(arguments(path, scale, sample_rate)).stdin(Stdio::piped()).stdout(Stdio::null());
+
+    let audio = match sample_rate {
+        Some(rate) => {
+            let (reader, writer) = io::pipe().map_err(error)?;
+            command.stdout(reader);
+            Some((writer, AudioPacing::new(rate)))
+        },
+        None => None,
+    };
+
+    let mut child = command.spawn().map_err(error)?;
+    // the reader end went to ffmpeg with the command, dropping it here means a dead ffmpeg
+    // shows up as a broken pipe rather than a hang
+    drop(command);
+    let video = child.stdin.take().expect("stdin is piped");
+
+    Ok(VideoCapture {
+        path: path.to_path_buf(),
+        child,
+        video,
+        audio,
+        rgba: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT * 4],
+        pcm: Vec::new(),
+    })
+}
+
+impl VideoCapture {
+    // `samples` is the audio made during the frame, whatever the sample count
+    pub fn push_frame(&mut self, chip8: &Chip8, palette: Palette, samples: &[f32]) -> Result<(), String> {
+        chip8.render_rgba_scaled(palette, 1, &mut self.rgba);
+        self.video.write_all(&self.rgba).map_err(|e| self.write_error(e))?;
+
+        if let Some((pipe, pacing)) = self.audio.as_mut() {
+            pacing.pace(samples, &mut self.pcm);
+            let written = pipe.write_all(&self.pcm);
+            written.map_err(|e| self.write_error(e))?;
+        }
+
+        Ok(())
+    }
+
+    fn write_error(&self, e: io::Error) -> String {
+        format!("Unable to write {}: ffmpeg stopped ({})", self.path.display(), e)
+    }
+
+    // closing both pipes tells ffmpeg the capture is over, then it finishes the file
+    pub fn finish(self) -> Result<PathBuf, String> {
+        let Self { path, mut child, video, audio, .. } = self;
+        drop(video);
+        drop(audio);
+
+        match child.wait() {
+            Ok(status) if status.success() => Ok(path),
+            Ok(status) => Err(format!("ffmpeg failed writing {} ({})", path.display(), status)),
+            Err(e) => Err(format!("Unable to wait for ffmpeg: {}", e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+
+    fn paced(pacing: &mut AudioPacing, generated: &[f32]) -> Vec<i16> {
+        let mut out = Vec::new();
+        pacing.pace(generated, &mut out);
+
+        out.chunks(2).map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]])).collect()
+    }
+
+    #[test]
+    fn frames_add_up_to_the_sample_rate() {
+        let mut pacing = AudioPacing::new(44100);
+        let frames: Vec<usize> = (0..60).map(|_| pacing.samples_for_frame()).collect();
+
+        assert_eq!(&frames[..4], [735, 735, 735, 735]);
+        assert_eq!(frames.iter().sum::<usize>(), 44100);
+
+        let mut pacing = AudioPacing::new(22050);
+        let frames: Vec<usize> = (0..4).map(|_| pacing.samples_for_frame()).collect();
+        assert_eq!(frames, [367, 368, 367, 368]);
+    }
+
+    #[test]
+    fn long_frames_carry_over_and_short_ones_are_padded() {
+        // 6 samples a frame
+        let mut pacing = AudioPacing::new(360);
+
+        assert_eq!(paced(&mut pacing, &[1.0; 8]), [i16::MAX; 6]);
+        // the 2 left over come first
+        assert_eq!(paced(&mut pacing, &[0.0; 3]), [i16::MAX, i16::MAX, 0, 0, 0, 0]);
+        assert_eq!(paced(&mut pacing, &[]), [0; 6]);
+    }
+
+    #[test]
+    fn never_falls_more_than_a_frame_behind() {
+        let mut pacing = AudioPacing::new(360);
+
+        paced(&mut pacing, &[0.5; 30]);
+        assert_eq!(pacing.pending.len(), 6);
+    }
+
+    #[test]
+    fn asks_for_audio_only_when_there_is_some() {
+        let args = arguments(Path::new("out.mkv"), 10, None);
+        assert!(!args.contains(&OsString::from("pipe:1")));
+        assert!(args.contains(&OsString::from("scale=iw*10:ih*10:flags=neighbor")));
+        assert_eq!(args.last().unwrap(), "out.mkv");
+
+        let args = arguments(Path::new("out.mkv"), 10, Some(44100));
+        let audio = args.iter().position(|arg| arg == "s16le").unwrap();
+        assert_eq!(args[audio + 2], "44100");
+        assert_eq!(args[audio + 6], "pipe:1");
+    }
+
+    #[test]
+    fn a_missing_ffmpeg_is_a_clear_error() {
+        let message = check_ffmpeg(Path::new("/nonexistent/ffmpeg")).unwrap_err();
+
+        assert!(message.contains("needs ffmpeg"), "{}", message);
+    }
+
+    #[test]
+    fn records_through_ffmpeg() {
+        let ffmpeg = Path::new(DEFAULT_FFMPEG);
+
+        if check_ffmpeg(ffmpeg).is_err() {
+            eprintln!("ffmpeg isn't installed, skipping");
+            return;
+        }
+
+        let dir = env::temp_dir().join(format!("chip8-emu-video-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("capture.nut");
+
+        let mut chip8 = Chip8::new();
+        // draw "0" at (0, 0) then loop
+        chip8.load(&[0xD0, 0x05, 0x12, 0x02]).unwrap();
+        let mut capture = start_video(ffmpeg, &path, 2, Some(44100)).unwrap();
+
+        for _ in 0..30 {
+            chip8.run_frame(10);
+            capture.push_frame(&chip8, Palette::default(), &[0.25; 735]).unwrap();
+        }
+
+        assert_eq!(capture.finish().unwrap(), path);
+        assert!(fs::metadata(&path).unwrap().len() > 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,141 @@
+// where the emulator image goes inside the window: the largest whole-number scale that fits,
+// centered with bars on whatever space is left
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Viewport {
+    pub scale: u32,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    // `screen` is the emulated resolution, so a mode switch only needs another call
+    pub fn fit(drawable: (u32, u32), screen: (u32, u32)) -> Self {
+        let (drawable_width, drawable_height) = drawable;
+        let (screen_width, screen_height) = screen;
+
+        // never below 1, a tiny window crops instead of vanishing
+        let scale = (drawable_width / screen_width).min(drawable_height / screen_height).max(1);
+        let width = screen_width * scale;
+        let height = screen_height * scale;
+
+        Self {
+            scale,
+            x: (drawable_width as i32 - width as i32) / 2,
+            y: (drawable_height as i32 - height as i32) / 2,
+            width,
+            height,
+        }
+    }
+
+    // top left corner of an emulated pixel in window coordinates
+    pub fn pixel_origin(&self, x: u32, y: u32) -> (i32, i32) {
+        (self.x + (x * self.scale) as i32, self.y + (y * self.scale) as i32)
+    }
+
+    // the emulated pixel under a point in drawable coordinates, None over the bars
+    pub fn pixel_at(&self, point: (i32, i32)) -> Option<(u32, u32)> {
+        let x = point.0 - self.x;
+        let y = point.1 - self.y;
+
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return None;
+        }
+
+        Some((x as u32 / self.scale, y as u32 / self.scale))
+    }
+}
+
+// mouse events come in window coordinates, which on a high DPI display are fewer than the
+// drawable's pixels
+pub fn drawable_point(point: (i32, i32), window: (u32, u32), drawable: (u32, u32)) -> (i32, i32) {
+    let scale = |value: i32, from: u32, to: u32| {
+        if from == 0 {
+            value
+        } else {
+            (value as i64 * to as i64 / from as i64) as i32
+        }
+    };
+
+    (scale(point.0, window.0, drawable.0), scale(point.1, window.1, drawable.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: (u32, u32) = (64, 32);
+
+    #[test]
+    fn fills_an_exact_multiple() {
+        assert_eq!(
+            Viewport::fit((1280, 640), SCREEN),
+            Viewport { scale: 20, x: 0, y: 0, width: 1280, height: 640 }
+        );
+    }
+
+    #[test]
+    fn letterboxes_odd_sizes() {
+        // 1366x768 laptop: width allows 21, height allows 24
+        let viewport = Viewport::fit((1366, 768), SCREEN);
+        assert_eq!(viewport, Viewport { scale: 21, x: 11, y: 48, width: 1344, height: 672 });
+
+        // tall and narrow
+        let viewport = Viewport::fit((300, 900), SCREEN);
+        assert_eq!(viewport, Viewport { scale: 4, x: 22, y: 386, width: 256, height: 128 });
+        assert_eq!(viewport.pixel_origin(1, 1), (26, 390));
+    }
+
+    #[test]
+    fn scales_up_on_4k() {
+        assert_eq!(Viewport::fit((3840, 2160), SCREEN).scale, 60);
+    }
+
+    #[test]
+    fn crops_when_smaller_than_the_screen() {
+        let viewport = Viewport::fit((50, 20), SCREEN);
+        assert_eq!(viewport.scale, 1);
+        assert_eq!((viewport.x, viewport.y), (-7, -6));
+    }
+
+    #[test]
+    fn maps_window_points_back_to_pixels() {
+        let viewport = Viewport::fit((1366, 768), SCREEN);
+
+        assert_eq!(viewport.pixel_at((11, 48)), Some((0, 0)));
+        assert_eq!(viewport.pixel_at((11 + 20, 48 + 20)), Some((0, 0)));
+        assert_eq!(viewport.pixel_at((11 + 21, 48 + 21)), Some((1, 1)));
+        assert_eq!(viewport.pixel_at((11 + 1343, 48 + 671)), Some((63, 31)));
+
+        // the bars on every side
+        assert_eq!(viewport.pixel_at((10, 100)), None);
+        assert_eq!(viewport.pixel_at((100, 47)), None);
+        assert_eq!(viewport.pixel_at((11 + 1344, 100)), None);
+        assert_eq!(viewport.pixel_at((100, 48 + 672)), None);
+
+        for (x, y) in [(0, 0), (17, 9), (63, 31)] {
+            assert_eq!(viewport.pixel_at(viewport.pixel_origin(x, y)), Some((x, y)));
+        }
+    }
+
+    #[test]
+    fn maps_a_cropped_screen() {
+        let viewport = Viewport::fit((50, 20), SCREEN);
+
+        assert_eq!(viewport.pixel_at((0, 0)), Some((7, 6)));
+        assert_eq!(viewport.pixel_at((49, 19)), Some((56, 25)));
+    }
+
+    #[test]
+    fn scales_points_for_high_dpi() {
+        assert_eq!(drawable_point((100, 50), (640, 320), (1280, 640)), (200, 100));
+        assert_eq!(drawable_point((100, 50), (640, 320), (640, 320)), (100, 50));
+        assert_eq!(drawable_point((100, 50), (0, 0), (640, 320)), (100, 50));
+    }
+
+    #[test]
+    fn refits_for_a_different_resolution() {
+        assert_eq!(Viewport::fit((1280, 720), (128, 64)).scale, 10);
+    }
+}
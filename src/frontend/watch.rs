@@ -0,0 +1,162 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+// editors write in several steps (truncate, write, rename), wait for the file to sit still
+pub const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stamp {
+    pub modified: SystemTime,
+    pub len: u64,
+}
+
+impl Stamp {
+    // None while the file is missing, e.g. between an editor's delete and rename
+    pub fn read(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+
+        Some(Self {
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Debounce {
+    last: Option<Stamp>,
+    changed_at: Option<Instant>,
+}
+
+impl Debounce {
+    pub fn new(initial: Option<Stamp>) -> Self {
+        Self { last: initial, changed_at: None }
+    }
+
+    // true once per burst of changes, after the file has been left alone for SETTLE_TIME
+    pub fn observe(&mut self, stamp: Option<Stamp>, now: Instant) -> bool {
+        if stamp != self.last {
+            self.last = stamp;
+            self.changed_at = Some(now);
+            return false;
+        }
+
+        match self.changed_at {
+            Some(changed_at) if stamp.is_some() && now - changed_at >= SETTLE_TIME => {
+                self.changed_at = None;
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+// polls a rom file's modification time for --watch
+pub struct RomWatcher {
+    path: PathBuf,
+    debounce: Debounce,
+    next_poll: Instant,
+}
+
+impl RomWatcher {
+    pub fn new(path: PathBuf, now: Instant) -> Self {
+        Self {
+            debounce: Debounce::new(Stamp::read(&path)),
+            path,
+            next_poll: now + POLL_INTERVAL,
+        }
+    }
+
+    // true when the rom should be reloaded
+    pub fn poll(&mut self, now: Instant) -> bool {
+        if now < self.next_poll {
+            return false;
+        }
+
+        self.next_poll = now + POLL_INTERVAL;
+        self.debounce.observe(Stamp::read(&self.path), now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stamp(seconds: u64, len: u64) -> Option<Stamp> {
+        Some(Stamp {
+            modified: SystemTime::UNIX_EPOCH + Duration::from_secs(seconds),
+            len,
+        })
+    }
+
+    // feeds (milliseconds since start, file state) and returns when a reload fired
+    fn reloads(initial: Option<Stamp>, polls: &[(u64, Option<Stamp>)]) -> Vec<u64> {
+        let start = Instant::now();
+        let mut debounce = Debounce::new(initial);
+
+        polls
+            .iter()
+            .filter(|(ms, stamp)| debounce.observe(*stamp, start + Duration::from_millis(*ms)))
+            .map(|(ms, _)| *ms)
+            .collect()
+    }
+
+    #[test]
+    fn unchanged_files_never_reload() {
+        assert!(reloads(stamp(1, 10), &[(250, stamp(1, 10)), (500, stamp(1, 10)), (5000, stamp(1, 10))]).is_empty());
+    }
+
+    #[test]
+    fn reloads_once_the_file_settles() {
+        let polls = [(250, stamp(2, 10)), (500, stamp(2, 10)), (750, stamp(2, 10)), (1000, stamp(2, 10))];
+        assert_eq!(reloads(stamp(1, 10), &polls), vec![750]);
+    }
+
+    #[test]
+    fn a_burst_of_writes_reloads_once() {
+        // truncate, partial write, delete, final rename, then quiet
+        let polls = [
+            (250, stamp(2, 0)),
+            (500, stamp(2, 4)),
+            (750, None),
+            (1000, stamp(3, 10)),
+            (1250, stamp(3, 10)),
+            (1500, stamp(3, 10)),
+            (1750, stamp(3, 10)),
+            (4000, stamp(3, 10)),
+        ];
+        assert_eq!(reloads(stamp(1, 10), &polls), vec![1500]);
+    }
+
+    #[test]
+    fn waits_while_the_file_is_missing() {
+        let polls = [(250, None), (1000, None), (2000, None), (2250, stamp(2, 10)), (3000, stamp(2, 10))];
+        assert_eq!(reloads(stamp(1, 10), &polls), vec![3000]);
+    }
+
+    #[test]
+    fn a_size_change_counts_even_within_the_same_second() {
+        assert_eq!(reloads(stamp(1, 10), &[(250, stamp(1, 12)), (800, stamp(1, 12))]), vec![800]);
+    }
+
+    #[test]
+    fn watcher_only_polls_every_interval() {
+        let start = Instant::now();
+        let path = std::env::temp_dir().join(format!("chip8-emu-watch-{}.ch8", std::process::id()));
+        fs::write(&path, [0x12, 0x00]).unwrap();
+
+        let mut watcher = RomWatcher::new(path.clone(), start);
+        fs::write(&path, [0x12, 0x00, 0x00, 0xE0]).unwrap();
+
+        // too early to look, then the change is seen, then it has to settle
+        assert!(!watcher.poll(start + Duration::from_millis(100)));
+        assert!(!watcher.poll(start + POLL_INTERVAL));
+        assert!(!watcher.poll(start + POLL_INTERVAL * 2));
+        assert!(watcher.poll(start + POLL_INTERVAL + SETTLE_TIME));
+        assert!(!watcher.poll(start + POLL_INTERVAL * 10));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,73 @@
+// windowed <-> desktop fullscreen, remembering where the window was so leaving fullscreen puts it back
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Geometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transition {
+    EnterFullscreen,
+    // None when the window started out fullscreen and has nothing to go back to
+    LeaveFullscreen(Option<Geometry>),
+}
+
+#[derive(Clone, Debug)]
+pub struct WindowMode {
+    is_fullscreen: bool,
+    windowed: Option<Geometry>,
+}
+
+impl WindowMode {
+    pub fn new(is_fullscreen: bool) -> Self {
+        Self { is_fullscreen, windowed: None }
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        self.is_fullscreen
+    }
+
+    // `current` is the window as it is now, only kept when going fullscreen
+    pub fn toggle(&mut self, current: Geometry) -> Transition {
+        self.is_fullscreen = !self.is_fullscreen;
+
+        if self.is_fullscreen {
+            self.windowed = Some(current);
+            Transition::EnterFullscreen
+        } else {
+            Transition::LeaveFullscreen(self.windowed.take())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMALL: Geometry = Geometry { x: 100, y: 50, width: 640, height: 320 };
+    const DESKTOP: Geometry = Geometry { x: 0, y: 0, width: 1920, height: 1080 };
+
+    #[test]
+    fn restores_the_window_after_fullscreen() {
+        let mut mode = WindowMode::new(false);
+
+        assert_eq!(mode.toggle(SMALL), Transition::EnterFullscreen);
+        assert!(mode.is_fullscreen());
+
+        // the fullscreen size must not overwrite what was saved
+        assert_eq!(mode.toggle(DESKTOP), Transition::LeaveFullscreen(Some(SMALL)));
+        assert!(!mode.is_fullscreen());
+    }
+
+    #[test]
+    fn starting_fullscreen_has_nothing_to_restore() {
+        let mut mode = WindowMode::new(true);
+
+        assert_eq!(mode.toggle(DESKTOP), Transition::LeaveFullscreen(None));
+        assert_eq!(mode.toggle(SMALL), Transition::EnterFullscreen);
+        assert_eq!(mode.toggle(DESKTOP), Transition::LeaveFullscreen(Some(SMALL)));
+    }
+}
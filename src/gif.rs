@@ -0,0 +1,378 @@
+use crate::{Palette, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+const FRAMES_PER_SECOND: u32 = 60;
+// two colors still need the minimum code size GIF allows
+const MIN_CODE_SIZE: u8 = 2;
+const MAX_CODES: u16 = 4096;
+
+// streams an animated GIF of the screen to `writer` as frames come in, so only the frame being
+// held back for its delay stays in memory. runs of identical frames become one longer frame
+pub struct GifRecorder<W: Write> {
+    writer: W,
+    scale: usize,
+    fps: u32,
+    skip_carry: u32,
+    pending: Option<([bool; SCREEN_WIDTH * SCREEN_HEIGHT], u32)>,
+    elapsed_frames: u64,
+    written_centiseconds: u64,
+    frame_count: usize,
+}
+
+impl<W: Write> GifRecorder<W> {
+    // `fps` below 60 drops frames to keep files small, the timing stays at real speed
+    pub fn new(mut writer: W, scale: u32, palette: Palette, fps: u32) -> io::Result<Self> {
+        assert!(scale > 0, "scale must be at least 1");
+        assert!((1..=FRAMES_PER_SECOND).contains(&fps), "fps must be in 1..=60");
+
+        let (background, foreground) = (palette.background, palette.foreground);
+        let width = (SCREEN_WIDTH as u32 * scale) as u16;
+        let height = (SCREEN_HEIGHT as u32 * scale) as u16;
+
+        writer.write_all(b"GIF89a")?;
+        writer.write_all(&width.to_le_bytes())?;
+        writer.write_all(&height.to_le_bytes())?;
+        // global color table of 2 entries, background index 0, square pixels
+        writer.write_all(&[0x80, 0, 0])?;
+        writer.write_all(&[background.0, background.1, background.2, foreground.0, foreground.1, foreground.2])?;
+
+        // loop forever
+        writer.write_all(&[0x21, 0xFF, 0x0B])?;
+        writer.write_all(b"NETSCAPE2.0")?;
+        writer.write_all(&[0x03, 0x01, 0x00, 0x00, 0x00])?;
+
+        Ok(Self {
+            writer,
+            scale: scale as usize,
+            fps,
+            // so the very first frame is kept
+            skip_carry: FRAMES_PER_SECOND - fps,
+            pending: None,
+            elapsed_frames: 0,
+            written_centiseconds: 0,
+            frame_count: 0,
+        })
+    }
+
+    // call once per emulated frame
+    pub fn push_frame(&mut self, screen: &[bool]) -> io::Result<()> {
+        self.skip_carry += self.fps;
+        let is_kept = self.skip_carry >= FRAMES_PER_SECOND;
+
+        if is_kept {
+            self.skip_carry -= FRAMES_PER_SECOND;
+        }
+
+        if let Some((pending, frames)) = self.pending.as_mut() {
+            if !is_kept || pending[..] == *screen {
+                *frames += 1;
+                return Ok(());
+            }
+        }
+
+        self.flush_pending()?;
+
+        let mut frame = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        frame.copy_from_slice(screen);
+        self.pending = Some((frame, 1));
+
+        Ok(())
+    }
+
+    // frames written so far, not counting the one still waiting for its delay
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush_pending()?;
+        self.writer.write_all(&[0x3B])?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    fn flush_pending(&mut self) -> io::Result<()> {
+        let (frame, frames) = match self.pending.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+
+        // delays are in centiseconds, so 60 fps is kept on average by carrying the rounding
+        self.elapsed_frames += frames as u64;
+        let end = self.elapsed_frames * 100 / FRAMES_PER_SECOND as u64;
+        let delay = (end - self.written_centiseconds).min(u16::MAX as u64) as u16;
+        self.written_centiseconds = end;
+
+        let width = (SCREEN_WIDTH * self.scale) as u16;
+        let height = (SCREEN_HEIGHT * self.scale) as u16;
+
+        // graphic control extension: no transparency, just the delay
+        self.writer.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+        self.writer.write_all(&delay.to_le_bytes())?;
+        self.writer.write_all(&[0x00, 0x00])?;
+
+        self.writer.write_all(&[0x2C, 0, 0, 0, 0])?;
+        self.writer.write_all(&width.to_le_bytes())?;
+        self.writer.write_all(&height.to_le_bytes())?;
+        self.writer.write_all(&[0x00, MIN_CODE_SIZE])?;
+
+        let data = lzw_encode(&self.indices(&frame));
+
+        for block in data.chunks(255) {
+            self.writer.write_all(&[block.len() as u8])?;
+            self.writer.write_all(block)?;
+        }
+
+        self.writer.write_all(&[0x00])?;
+        self.frame_count += 1;
+
+        Ok(())
+    }
+
+    fn indices(&self, frame: &[bool]) -> Vec<u8> {
+        let width = SCREEN_WIDTH * self.scale;
+        let mut indices = Vec::with_capacity(width * SCREEN_HEIGHT * self.scale);
+
+        for y in 0..SCREEN_HEIGHT * self.scale {
+            let row = (y / self.scale) * SCREEN_WIDTH;
+            indices.extend((0..width).map(|x| frame[row + x / self.scale] as u8));
+        }
+
+        indices
+    }
+}
+
+fn lzw_encode(indices: &[u8]) -> Vec<u8> {
+    let clear = 1u16 << MIN_CODE_SIZE;
+    let end = clear + 1;
+
+    let mut out = BitWriter::default();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut code_size = MIN_CODE_SIZE + 1;
+    let mut next_code = end + 1;
+
+    out.write(clear, code_size);
+
+    let mut pixels = indices.iter();
+    let mut prefix = match pixels.next() {
+        Some(&first) => first as u16,
+        None => {
+            out.write(end, code_size);
+            return out.finish();
+        }
+    };
+
+    for &pixel in pixels {
+        if let Some(&code) = table.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+
+        out.write(prefix, code_size);
+
+        if next_code < MAX_CODES {
+            table.insert((prefix, pixel), next_code);
+            next_code += 1;
+
+            // the decoder adds each entry one code later, so widen once it could need the new code
+            if next_code > 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+        } else {
+            out.write(clear, code_size);
+            table.clear();
+            code_size = MIN_CODE_SIZE + 1;
+            next_code = end + 1;
+        }
+
+        prefix = pixel as u16;
+    }
+
+    out.write(prefix, code_size);
+    out.write(end, code_size);
+
+    out.finish()
+}
+
+// GIF packs codes least significant bit first
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLORS: Palette = Palette::new((0, 0, 0), (255, 255, 255));
+
+    // the matching decoder, only here to check the encoder round trips
+    fn lzw_decode(data: &[u8]) -> Vec<u8> {
+        let clear = 1u16 << MIN_CODE_SIZE;
+        let end = clear + 1;
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut code_size = MIN_CODE_SIZE + 1;
+        let mut previous: Option<Vec<u8>> = None;
+        let mut out = Vec::new();
+        let (mut buffer, mut bits, mut position) = (0u32, 0u8, 0);
+
+        loop {
+            while bits < code_size {
+                buffer |= (data[position] as u32) << bits;
+                position += 1;
+                bits += 8;
+            }
+
+            let code = (buffer & ((1 << code_size) - 1)) as u16;
+            buffer >>= code_size;
+            bits -= code_size;
+
+            if code == clear {
+                table = (0..clear).map(|i| vec![i as u8]).collect();
+                table.push(Vec::new());
+                table.push(Vec::new());
+                code_size = MIN_CODE_SIZE + 1;
+                previous = None;
+                continue;
+            }
+
+            if code == end {
+                return out;
+            }
+
+            let entry = match (table.get(code as usize), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => [&previous[..], &previous[..1]].concat(),
+                (None, None) => panic!("bad first code"),
+            };
+
+            if let Some(previous) = previous {
+                if table.len() < MAX_CODES as usize {
+                    table.push([&previous[..], &entry[..1]].concat());
+
+                    if table.len() == 1 << code_size && code_size < 12 {
+                        code_size += 1;
+                    }
+                }
+            }
+
+            out.extend_from_slice(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    // (delay, lzw data) for every image in the file
+    fn frames(gif: &[u8]) -> Vec<(u16, Vec<u8>)> {
+        // header, screen descriptor, color table, loop extension
+        let mut position = 6 + 7 + 6 + 19;
+        let mut frames = Vec::new();
+        let mut delay = 0;
+
+        loop {
+            match gif[position] {
+                0x21 => {
+                    delay = u16::from_le_bytes([gif[position + 4], gif[position + 5]]);
+                    position += 8;
+                },
+                0x2C => {
+                    position += 11;
+                    let mut data = Vec::new();
+
+                    while gif[position] != 0 {
+                        let len = gif[position] as usize;
+                        data.extend_from_slice(&gif[position + 1..position + 1 + len]);
+                        position += 1 + len;
+                    }
+
+                    frames.push((delay, data));
+                    position += 1;
+                },
+                0x3B => return frames,
+                other => panic!("unexpected block {:#x}", other),
+            }
+        }
+    }
+
+    fn screen_with(pixel: usize) -> [bool; SCREEN_WIDTH * SCREEN_HEIGHT] {
+        let mut screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        screen[pixel] = true;
+        screen
+    }
+
+    #[test]
+    fn round_trips_lzw() {
+        let noisy: Vec<u8> = (0..20_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8 & 1).collect();
+        assert_eq!(lzw_decode(&lzw_encode(&noisy)), noisy);
+
+        let flat = vec![0; 50_000];
+        assert_eq!(lzw_decode(&lzw_encode(&flat)), flat);
+    }
+
+    #[test]
+    fn writes_one_image_per_change_with_60fps_delays() {
+        let mut recorder = GifRecorder::new(Vec::new(), 2, COLORS, 60).unwrap();
+
+        for pixel in 0..3 {
+            recorder.push_frame(&screen_with(pixel)).unwrap();
+        }
+
+        // a still screen is one long frame
+        for _ in 0..3 {
+            recorder.push_frame(&screen_with(3)).unwrap();
+        }
+
+        let gif = recorder.finish().unwrap();
+        assert_eq!(&gif[..6], b"GIF89a");
+        assert_eq!(u16::from_le_bytes([gif[6], gif[7]]), 128);
+        assert_eq!(u16::from_le_bytes([gif[8], gif[9]]), 64);
+
+        let frames = frames(&gif);
+        let delays: Vec<u16> = frames.iter().map(|(delay, _)| *delay).collect();
+        assert_eq!(delays, vec![1, 2, 2, 5]);
+        assert_eq!(delays.iter().sum::<u16>(), 10);
+
+        let pixels = lzw_decode(&frames[1].1);
+        assert_eq!(pixels.len(), 128 * 64);
+        assert_eq!(&pixels[..4], &[0, 0, 1, 1]);
+        assert_eq!(&pixels[128..132], &[0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn drops_frames_to_reach_the_target_fps() {
+        let mut recorder = GifRecorder::new(Vec::new(), 1, COLORS, 20).unwrap();
+
+        for pixel in 0..60 {
+            recorder.push_frame(&screen_with(pixel)).unwrap();
+        }
+
+        let frames = frames(&recorder.finish().unwrap());
+        assert_eq!(frames.len(), 20);
+        assert_eq!(frames.iter().map(|(delay, _)| *delay as u32).sum::<u32>(), 100);
+    }
+}
@@ -0,0 +1,290 @@
+// making the golden screens harness::Scenario checks against: a headless run that keeps the screen
+// at each frame it was asked for, and a manifest of how they were made:
+//
+// {
+//   "emulator_version": "0.1.0",
+//   "rom": "game.ch8", "rom_sha256": "...",
+//   "seed": 0, "ticks_per_frame": 10, "input_script": null,
+//   "quirks": { "shift_uses_vy": false },
+//   "frames": [
+//     { "frame": 120, "screen_hash": "...", "pbm": "frame-000120.pbm", "png": "frame-000120.png" },
+//     ...
+//   ]
+// }
+//
+// frame N is the screen after N frames, what Scenario::new(rom).frames(N).expect_display(pbm)
+// expects with the same seed, speed and input. the version is the build that made them, a fixture
+// from another one may be stale
+
+use crate::conformance::screen_hash;
+use crate::headless::{self, crash_message, HeadlessError, Script};
+use crate::report::json_string;
+use crate::{Chip8, Quirk, Quirks};
+
+use serde::{Deserialize, Deserializer};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const EMULATOR_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// how a run plays out, everything about it the manifest records
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Setup {
+    pub seed: u64,
+    pub ticks_per_frame: usize,
+    pub script: Script,
+    // the file the script came from, for the manifest
+    pub input_script: Option<String>,
+}
+
+impl Setup {
+    // the quirks the first frame runs with, which the script sets at frame 0
+    pub fn quirks(&self) -> Quirks {
+        let mut quirks = Quirks::default();
+
+        for change in self.script.quirks.iter().filter(|change| change.frame == 0) {
+            quirks.set(change.quirk, change.is_enabled);
+        }
+
+        quirks
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Capture {
+    pub frame: usize,
+    pub screen: Vec<bool>,
+}
+
+// the frames `at` asks for in the order the run reaches them, each once
+pub fn schedule(at: &[usize]) -> Vec<usize> {
+    let mut frames = at.to_vec();
+    frames.sort_unstable();
+    frames.dedup();
+
+    frames
+}
+
+pub fn pbm_file(frame: usize) -> String {
+    format!("frame-{:06}.pbm", frame)
+}
+
+pub fn png_file(frame: usize) -> String {
+    format!("frame-{:06}.png", frame)
+}
+
+// everything a run capturing `frames` writes, the manifest last
+pub fn file_names(frames: &[usize]) -> Vec<String> {
+    let mut names: Vec<String> = frames.iter().flat_map(|&frame| [pbm_file(frame), png_file(frame)]).collect();
+    names.push(MANIFEST_FILE.to_string());
+
+    names
+}
+
+// runs until the last frame of `at`, a crash before it is Crashed with the frame it happened in
+pub fn capture(rom: &[u8], setup: &Setup, at: &[usize]) -> Result<Vec<Capture>, HeadlessError> {
+    let frames = schedule(at);
+    let mut chip8 = Chip8::new();
+    chip8.load(rom).map_err(|error| HeadlessError::Rom(error.to_string()))?;
+    chip8.set_seed(setup.seed);
+
+    let mut captures = Vec::with_capacity(frames.len());
+    let mut frame = 0;
+
+    for &capture_at in &frames {
+        while frame < capture_at {
+            let crash = crash_message(&mut chip8, |chip8| {
+                headless::play_frame(chip8, frame, setup.ticks_per_frame, &setup.script);
+            });
+
+            if let Some(message) = crash {
+                return Err(HeadlessError::Crashed(format!("in frame {}: {}", frame, message)));
+            }
+
+            frame += 1;
+        }
+
+        captures.push(Capture { frame, screen: chip8.get_display().to_vec() });
+    }
+
+    Ok(captures)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct ManifestFrame {
+    pub frame: usize,
+    pub screen_hash: String,
+    pub pbm: String,
+    pub png: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct Manifest {
+    pub emulator_version: String,
+    pub rom: String,
+    pub rom_sha256: String,
+    pub seed: u64,
+    pub ticks_per_frame: usize,
+    pub input_script: Option<String>,
+    #[serde(deserialize_with = "quirks_from_names")]
+    pub quirks: Quirks,
+    pub frames: Vec<ManifestFrame>,
+}
+
+fn quirks_from_names<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Quirks, D::Error> {
+    let mut quirks = Quirks::default();
+
+    for (name, is_enabled) in BTreeMap::<String, bool>::deserialize(deserializer)? {
+        let quirk =
+            Quirk::from_name(&name).ok_or_else(|| serde::de::Error::custom(format!("unknown quirk `{}`", name)))?;
+        quirks.set(quirk, is_enabled);
+    }
+
+    Ok(quirks)
+}
+
+impl Manifest {
+    pub fn new(rom_name: &str, rom: &[u8], setup: &Setup, captures: &[Capture]) -> Self {
+        let frames = captures
+            .iter()
+            .map(|capture| ManifestFrame {
+                frame: capture.frame,
+                screen_hash: screen_hash(&capture.screen),
+                pbm: pbm_file(capture.frame),
+                png: png_file(capture.frame),
+            })
+            .collect();
+
+        Self {
+            emulator_version: EMULATOR_VERSION.to_string(),
+            rom: rom_name.to_string(),
+            rom_sha256: Sha256::digest(rom).iter().map(|b| format!("{:02x}", b)).collect(),
+            seed: setup.seed,
+            ticks_per_frame: setup.ticks_per_frame,
+            input_script: setup.input_script.clone(),
+            quirks: setup.quirks(),
+            frames,
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        serde_json::from_str(text).map_err(|e| format!("not a golden frame manifest: {}", e))
+    }
+
+    // made by another build, whose screens may not be this one's
+    pub fn is_stale(&self) -> bool {
+        self.emulator_version != EMULATOR_VERSION
+    }
+
+    // one frame per line so a manifest kept in git diffs by frame
+    pub fn to_json(&self) -> String {
+        let quirks: Vec<String> =
+            Quirk::all().map(|quirk| format!("\"{}\": {}", quirk.name(), self.quirks.get(quirk))).collect();
+        let frames: Vec<String> = self
+            .frames
+            .iter()
+            .map(|frame| {
+                format!(
+                    "    {{ \"frame\": {}, \"screen_hash\": \"{}\", \"pbm\": {}, \"png\": {} }}",
+                    frame.frame,
+                    frame.screen_hash,
+                    json_string(&frame.pbm),
+                    json_string(&frame.png)
+                )
+            })
+            .collect();
+
+        format!(
+            "{{\n  \"emulator_version\": {},\n  \"rom\": {}, \"rom_sha256\": \"{}\",\n  \"seed\": {}, \"ticks_per_frame\": {}, \"input_script\": {},\n  \"quirks\": {{ {} }},\n  \"frames\": [\n{}\n  ]\n}}\n",
+            json_string(&self.emulator_version),
+            json_string(&self.rom),
+            self.rom_sha256,
+            self.seed,
+            self.ticks_per_frame,
+            self.input_script.as_deref().map_or("null".to_string(), json_string),
+            quirks.join(", "),
+            frames.join(",\n")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::harness::Scenario;
+    use crate::headless::parse_script;
+
+    // counts frames in V0 with the delay timer, drawing the count's low digit each time
+    const ROM: [u8; 20] = [
+        0x00, 0xE0, 0xF0, 0x29, 0xD0, 0x05, 0x61, 0x01, 0xF1, 0x15, 0xF1, 0x07, 0x31, 0x00, 0x12, 0x0A, 0x70, 0x01,
+        0x12, 0x00,
+    ];
+
+    fn setup() -> Setup {
+        Setup { seed: 3, ticks_per_frame: 10, ..Setup::default() }
+    }
+
+    #[test]
+    fn schedules_each_frame_once_in_order() {
+        assert_eq!(schedule(&[600, 120, 300, 120]), [120, 300, 600]);
+        assert_eq!(schedule(&[0]), [0]);
+        assert!(schedule(&[]).is_empty());
+    }
+
+    #[test]
+    fn names_every_file_it_writes() {
+        assert_eq!(
+            file_names(&[5, 120]),
+            ["frame-000005.pbm", "frame-000005.png", "frame-000120.pbm", "frame-000120.png", "manifest.json"]
+        );
+    }
+
+    #[test]
+    fn captures_the_screens_scenarios_expect() {
+        let captures = capture(&ROM, &setup(), &[40, 0, 7, 40]).unwrap();
+        assert_eq!(captures.iter().map(|capture| capture.frame).collect::<Vec<_>>(), [0, 7, 40]);
+        assert!(captures[0].screen.iter().all(|&pixel| !pixel));
+        assert_ne!(captures[1].screen, captures[2].screen);
+
+        for capture in &captures {
+            let hash = screen_hash(&capture.screen);
+            Scenario::new(&ROM).seed(3).frames(capture.frame).expect_screen_hash(&hash).run().unwrap();
+        }
+    }
+
+    #[test]
+    fn reports_the_frame_it_crashed_in() {
+        // return with an empty stack
+        let crash = capture(&[0x00, 0xEE], &setup(), &[10]).unwrap_err();
+
+        assert!(matches!(crash, HeadlessError::Crashed(message) if message.starts_with("in frame 0: ")));
+    }
+
+    #[test]
+    fn writes_a_manifest_it_reads_back() {
+        let setup = Setup {
+            script: parse_script("0 quirk shift_uses_vy on\n").unwrap(),
+            input_script: Some("input.txt".to_string()),
+            ..setup()
+        };
+        let captures = capture(&ROM, &setup, &[7, 40]).unwrap();
+        let manifest = Manifest::new("count.ch8", &ROM, &setup, &captures);
+
+        assert_eq!(manifest.seed, 3);
+        assert!(manifest.quirks.shift_uses_vy);
+        assert_eq!(manifest.frames[1].pbm, "frame-000040.pbm");
+        assert_eq!(manifest.frames[1].screen_hash, screen_hash(&captures[1].screen));
+        assert_eq!(manifest.rom_sha256.len(), 64);
+        assert!(!manifest.is_stale());
+
+        let json = manifest.to_json();
+        assert!(json.contains("\"emulator_version\": \"") && json.contains("\"input_script\": \"input.txt\""));
+        assert_eq!(Manifest::parse(&json), Ok(manifest.clone()));
+
+        let older = json.replace(&format!("\"{}\"", EMULATOR_VERSION), "\"0.0.0-old\"");
+        assert!(Manifest::parse(&older).unwrap().is_stale());
+        assert!(Manifest::parse(&json.replace("shift_uses_vy", "no_such_quirk")).is_err());
+    }
+}
@@ -0,0 +1,395 @@
+// golden-frame tests for ROM authors, so a game's CI can check what it draws with this crate as a
+// dev-dependency:
+//
+//   #[test]
+//   fn title_screen() {
+//       Scenario::new(include_bytes!("../game.ch8")).frames(600).expect_display("tests/title.pbm").run().unwrap();
+//   }
+//
+// a scenario runs headlessly, seeded so it plays out the same every time, and checks the run
+// against everything it was told to expect. a failure lists every expectation that didn't hold
+// with the stats of the run, and the screen drawn over the expected one so the difference shows.
+// golden screens are plain PBMs like `chip8-emu headless --dump-screen` and `chip8-emu snapshot`
+// write, read relative to the working directory, which for `cargo test` is the crate being tested
+
+use crate::headless::{self, Options, Script, DEFAULT_SEED};
+use crate::report::{self, Expectations, HaltReason, Report};
+use crate::{Chip8, Quirk, Quirks, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// the speed the frontends run at
+pub const DEFAULT_TICKS_PER_FRAME: usize = 10;
+pub const DEFAULT_FRAMES: usize = 60;
+
+// numbers about a finished run a scenario can expect
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stat {
+    Frames,
+    Instructions,
+    // times the sound started
+    Beeps,
+    Register(usize),
+    RegisterI,
+    ProgramCounter,
+    DelayTimer,
+    SoundTimer,
+}
+
+impl Stat {
+    pub fn of(self, report: &Report, chip8: &Chip8) -> u64 {
+        match self {
+            Stat::Frames => report.frames,
+            Stat::Instructions => report.instructions,
+            Stat::Beeps => report.beeps,
+            Stat::Register(register) => chip8.registers()[register] as u64,
+            Stat::RegisterI => chip8.register_i() as u64,
+            Stat::ProgramCounter => chip8.program_counter() as u64,
+            Stat::DelayTimer => chip8.delay_timer() as u64,
+            Stat::SoundTimer => chip8.sound_timer() as u64,
+        }
+    }
+}
+
+impl fmt::Display for Stat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stat::Frames => write!(f, "frames"),
+            Stat::Instructions => write!(f, "instructions"),
+            Stat::Beeps => write!(f, "beeps"),
+            Stat::Register(register) => write!(f, "V{:X}", register),
+            Stat::RegisterI => write!(f, "I"),
+            Stat::ProgramCounter => write!(f, "PC"),
+            Stat::DelayTimer => write!(f, "the delay timer"),
+            Stat::SoundTimer => write!(f, "the sound timer"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    rom: Vec<u8>,
+    seed: Option<u64>,
+    quirks: Quirks,
+    frames: usize,
+    ticks_per_frame: usize,
+    // kept as text so a bad script fails the run like any other problem
+    input_script: Option<String>,
+    display: Option<PathBuf>,
+    screen_hash: Option<String>,
+    halt: Option<HaltReason>,
+    stats: Vec<(Stat, u64)>,
+}
+
+impl Scenario {
+    pub fn new(rom: &[u8]) -> Self {
+        Self {
+            rom: rom.to_vec(),
+            seed: None,
+            quirks: Quirks::default(),
+            frames: DEFAULT_FRAMES,
+            ticks_per_frame: DEFAULT_TICKS_PER_FRAME,
+            input_script: None,
+            display: None,
+            screen_hash: None,
+            halt: None,
+            stats: Vec::new(),
+        }
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    pub fn quirk(mut self, quirk: Quirk, is_enabled: bool) -> Self {
+        self.quirks.set(quirk, is_enabled);
+        self
+    }
+
+    pub fn frames(mut self, frames: usize) -> Self {
+        self.frames = frames;
+        self
+    }
+
+    pub fn ticks_per_frame(mut self, ticks_per_frame: usize) -> Self {
+        self.ticks_per_frame = ticks_per_frame;
+        self
+    }
+
+    // in the format headless::parse_script reads. a `seed` line in it seeds the run unless `seed` did
+    pub fn input_script(mut self, script: &str) -> Self {
+        self.input_script = Some(script.to_string());
+        self
+    }
+
+    // a PBM of the screen after the last frame
+    pub fn expect_display<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.display = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    // the hash --print-hash and the conformance goldens use
+    pub fn expect_screen_hash(mut self, hash: &str) -> Self {
+        self.screen_hash = Some(hash.to_string());
+        self
+    }
+
+    pub fn expect_halt(mut self, halt: HaltReason) -> Self {
+        self.halt = Some(halt);
+        self
+    }
+
+    pub fn expect_stat(mut self, stat: Stat, value: u64) -> Self {
+        self.stats.push((stat, value));
+        self
+    }
+
+    // the report of a run that met every expectation, or why it didn't
+    pub fn run(&self) -> Result<Report, Failure> {
+        let mut script = match &self.input_script {
+            Some(text) => headless::parse_script(text).map_err(|e| Failure::before_running(e.to_string()))?,
+            None => Script::default(),
+        };
+
+        // the quirks hold from the first frame, unless the script changes one there itself
+        for quirk in Quirk::all() {
+            if !script.quirks.iter().any(|change| change.frame == 0 && change.quirk == quirk) {
+                script.set_quirk(0, quirk, self.quirks.get(quirk));
+            }
+        }
+
+        let expected_screen = match &self.display {
+            Some(path) => Some(read_pbm(path).map_err(Failure::before_running)?),
+            None => None,
+        };
+
+        let seed = self.seed.or(script.seed).unwrap_or(DEFAULT_SEED);
+        let options = Options { frames: self.frames, ticks_per_frame: self.ticks_per_frame, seed, script };
+        let expectations = Expectations { screen_hash: self.screen_hash.clone(), halt: self.halt };
+        let (chip8, mut report) = report::run(&self.rom, &options, &expectations);
+
+        if let (Some(path), Some(expected)) = (&self.display, &expected_screen) {
+            let differing =
+                expected.iter().zip(chip8.get_display()).filter(|(expected, actual)| expected != actual).count();
+
+            if differing > 0 {
+                report.failures.push(format!("{} pixels differ from {}", differing, path.display()));
+            }
+        }
+
+        for &(stat, expected) in &self.stats {
+            let actual = stat.of(&report, &chip8);
+
+            if actual != expected {
+                report.failures.push(format!("expected {} to be {}, it was {}", stat, expected, actual));
+            }
+        }
+
+        if report.is_pass() {
+            return Ok(report);
+        }
+
+        let screen = screen_diff(chip8.get_display(), expected_screen.as_deref());
+        Err(Failure { report: Some(Box::new(report)), problems: Vec::new(), screen })
+    }
+}
+
+fn read_pbm(path: &Path) -> Result<Vec<bool>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("unable to read {}: {}", path.display(), e))?;
+
+    headless::parse_pbm(&text)
+        .ok_or_else(|| format!("{} isn't a {}x{} plain PBM", path.display(), SCREEN_WIDTH, SCREEN_HEIGHT))
+}
+
+// the screen a row of characters per row of pixels, `#` lit and `.` dark. against an expected
+// screen, `+` is lit but shouldn't be and `-` should be lit but isn't
+fn screen_diff(actual: &[bool], expected: Option<&[bool]>) -> String {
+    let mut out = String::with_capacity((SCREEN_WIDTH + 1) * SCREEN_HEIGHT);
+
+    for (index, &pixel) in actual.iter().enumerate() {
+        let should_be = expected.map_or(pixel, |expected| expected[index]);
+
+        out.push(match (pixel, should_be) {
+            (true, true) => '#',
+            (false, false) => '.',
+            (true, false) => '+',
+            (false, true) => '-',
+        });
+
+        if index % SCREEN_WIDTH == SCREEN_WIDTH - 1 {
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+// Debug prints the same report as Display, so a failing `scenario.run().unwrap()` reads well
+#[derive(Clone, PartialEq, Eq)]
+pub struct Failure {
+    // None when the scenario couldn't start, a bad input script or golden screen
+    pub report: Option<Box<Report>>,
+    // what stopped it starting, the failed expectations are in the report
+    pub problems: Vec<String>,
+    screen: String,
+}
+
+impl Failure {
+    fn before_running(problem: String) -> Self {
+        Self { report: None, problems: vec![problem], screen: String::new() }
+    }
+
+    // every way the run went wrong, one line each
+    pub fn reasons(&self) -> Vec<String> {
+        let failures = self.report.iter().flat_map(|report| report.failures.iter().cloned());
+        self.problems.iter().cloned().chain(failures).collect()
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Some(report) = &self.report else {
+            return write!(f, "the scenario didn't run: {}", self.problems.join(", "));
+        };
+
+        writeln!(f, "the scenario failed after {} frames:", report.frames)?;
+
+        for reason in self.reasons() {
+            writeln!(f, "  {}", reason)?;
+        }
+
+        writeln!(f, "halt: {}", report.halt)?;
+        writeln!(f, "screen hash: {}", report.screen_hash)?;
+        writeln!(f, "seed: {}", report.seed)?;
+        writeln!(f, "instructions: {}, beeps: {}", report.instructions, report.beeps)?;
+        writeln!(f, "screen (# lit, . dark, + lit but shouldn't be, - should be lit):")?;
+        write!(f, "{}", self.screen)
+    }
+}
+
+impl fmt::Debug for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\n{}", self)
+    }
+}
+
+impl Error for Failure {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+
+    // V0 = 1, draw "1" at (1, 1), beep for 4 frames, stop
+    const ROM: [u8; 12] = [0x60, 0x01, 0xF0, 0x29, 0xD0, 0x05, 0x61, 0x04, 0xF1, 0x18, 0x12, 0x0A];
+
+    fn golden(name: &str, screen: &[bool]) -> PathBuf {
+        let path = env::temp_dir().join(format!("chip8-harness-{}-{}.pbm", std::process::id(), name));
+        fs::write(&path, headless::pbm(screen)).unwrap();
+        path
+    }
+
+    fn screen_of(rom: &[u8]) -> Vec<bool> {
+        let options = Options { frames: 2, ticks_per_frame: 10, seed: 0, script: Script::default() };
+        headless::run(rom, &options).unwrap().get_display().to_vec()
+    }
+
+    #[test]
+    fn passes_when_everything_holds() {
+        let path = golden("pass", &screen_of(&ROM));
+
+        let report = Scenario::new(&ROM)
+            .frames(2)
+            .expect_display(&path)
+            .expect_halt(HaltReason::SelfJump)
+            .expect_stat(Stat::Beeps, 1)
+            .expect_stat(Stat::Register(0), 1)
+            .expect_stat(Stat::ProgramCounter, 0x20A)
+            .run()
+            .unwrap();
+
+        assert_eq!(report.frames, 2);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn shows_where_the_screen_differs() {
+        let mut expected = screen_of(&ROM);
+        // lit in the golden only, and the top of the "1" missing from it
+        expected[0] = true;
+        assert!(expected[SCREEN_WIDTH + 3]);
+        expected[SCREEN_WIDTH + 3] = false;
+        let path = golden("diff", &expected);
+
+        let failure =
+            Scenario::new(&ROM).frames(2).expect_display(&path).expect_stat(Stat::Beeps, 3).run().unwrap_err();
+        let text = failure.to_string();
+        let rows: Vec<&str> = text.lines().skip_while(|line| !line.starts_with("screen (")).skip(1).collect();
+
+        assert_eq!(
+            failure.reasons(),
+            [format!("2 pixels differ from {}", path.display()), "expected beeps to be 3, it was 1".to_string()]
+        );
+        assert_eq!(rows.len(), SCREEN_HEIGHT);
+        assert!(rows[0].starts_with("-......."), "{}", text);
+        assert!(rows[1].starts_with("...+...."), "{}", text);
+        assert!(rows[2].starts_with("..##...."), "{}", text);
+        assert!(text.contains("instructions: "), "{}", text);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn quirks_and_scripts_apply_from_the_first_frame() {
+        // V1 = 0x81, V0 = V1 >> 1 (V0 >> 1 without the quirk), wait for a key into V2, stop
+        let rom = [0x61, 0x81, 0x80, 0x16, 0xF2, 0x0A, 0x12, 0x06];
+
+        Scenario::new(&rom)
+            .quirk(Quirk::ShiftUsesVy, true)
+            .input_script("1 5 down\n2 5 up\n")
+            .frames(4)
+            .expect_stat(Stat::Register(0), 0x40)
+            .expect_stat(Stat::Register(2), 5)
+            .expect_halt(HaltReason::SelfJump)
+            .run()
+            .unwrap();
+
+        // the script can still turn it back off at frame 0
+        Scenario::new(&rom)
+            .quirk(Quirk::ShiftUsesVy, true)
+            .input_script("0 quirk shift_uses_vy off\n")
+            .frames(1)
+            .expect_stat(Stat::Register(0), 0)
+            .run()
+            .unwrap();
+    }
+
+    #[test]
+    fn fails_before_running_on_bad_inputs() {
+        let failure = Scenario::new(&ROM).input_script("30 A sideways").run().unwrap_err();
+        assert_eq!(failure.report, None);
+        assert!(failure.to_string().contains("input script line 1"), "{}", failure);
+
+        let failure = Scenario::new(&ROM).expect_display("no/such/golden.pbm").run().unwrap_err();
+        assert!(failure.reasons()[0].starts_with("unable to read no/such/golden.pbm"), "{:?}", failure);
+    }
+
+    #[test]
+    fn a_crash_is_a_failure() {
+        // return with an empty stack
+        let failure = Scenario::new(&[0x00, 0xEE]).run().unwrap_err();
+
+        assert_eq!(failure.report.as_ref().unwrap().halt, HaltReason::Crash);
+        assert!(failure.reasons()[0].contains("crashed at 200 running 00EE"), "{:?}", failure);
+        assert!(Scenario::new(&[0x00, 0xEE]).expect_halt(HaltReason::Crash).run().is_ok());
+    }
+}
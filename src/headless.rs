@@ -0,0 +1,513 @@
+use crate::conformance::{screen_hash, ScriptedKey};
+use crate::{Chip8, Chip8Error, FrameResult, Quirk, Quirks, NUM_KEYS, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use std::any::Any;
+
+use std::error::Error;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+// runs without a seed still play out the same every time
+pub const DEFAULT_SEED: u64 = 0;
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub frames: usize,
+    pub ticks_per_frame: usize,
+    pub seed: u64,
+    pub script: Script,
+}
+
+// from `frame` on, each frame runs `ticks_per_frame` instructions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScriptedSpeed {
+    pub frame: usize,
+    pub ticks_per_frame: usize,
+}
+
+// from `frame` on, `quirk` is on or off
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScriptedQuirk {
+    pub frame: usize,
+    pub quirk: Quirk,
+    pub is_enabled: bool,
+}
+
+// everything that happened at a frame boundary during a run, so it can be played back
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Script {
+    // the seed the run was recorded with, --seed still wins
+    pub seed: Option<u64>,
+    pub keys: Vec<ScriptedKey>,
+    pub speeds: Vec<ScriptedSpeed>,
+    pub quirks: Vec<ScriptedQuirk>,
+}
+
+impl Script {
+    pub fn press(&mut self, frame: usize, key: usize, is_pressed: bool) {
+        self.keys.push(ScriptedKey { frame, key, is_pressed });
+    }
+
+    // only the last change before a frame matters, so it replaces any earlier one at the same frame
+    pub fn set_speed(&mut self, frame: usize, ticks_per_frame: usize) {
+        self.speeds.retain(|speed| speed.frame != frame);
+        self.speeds.push(ScriptedSpeed { frame, ticks_per_frame });
+    }
+
+    // like speeds, the last change to a quirk before a frame is the one that counts
+    pub fn set_quirk(&mut self, frame: usize, quirk: Quirk, is_enabled: bool) {
+        self.quirks.retain(|change| change.frame != frame || change.quirk != quirk);
+        self.quirks.push(ScriptedQuirk { frame, quirk, is_enabled });
+    }
+
+    // `quirks` from the first frame, under any change the script makes to them there itself
+    pub fn start_with_quirks(&mut self, quirks: Quirks) {
+        for quirk in Quirk::all() {
+            if !self.quirks.iter().any(|change| change.frame == 0 && change.quirk == quirk) {
+                self.quirks.push(ScriptedQuirk { frame: 0, quirk, is_enabled: quirks.get(quirk) });
+            }
+        }
+    }
+
+    // the speed the run is going at after `frames` frames
+    pub fn speed_at(&self, frame: usize, ticks_per_frame: usize) -> usize {
+        self.speeds
+            .iter()
+            .filter(|speed| speed.frame <= frame)
+            .max_by_key(|speed| speed.frame)
+            .map_or(ticks_per_frame, |speed| speed.ticks_per_frame)
+    }
+}
+
+// in the format parse_script reads, the seed then by frame with speed and quirk changes first
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(seed) = self.seed {
+            writeln!(f, "seed {:#X}", seed)?;
+        }
+
+        let speeds = self.speeds.iter().map(|speed| (speed.frame, 0, format!("speed {}", speed.ticks_per_frame)));
+        let quirks = self.quirks.iter().map(|change| {
+            (change.frame, 1, format!("quirk {} {}", change.quirk.name(), if change.is_enabled { "on" } else { "off" }))
+        });
+        let keys = self.keys.iter().map(|key| {
+            (key.frame, 2, format!("{:X} {}", key.key, if key.is_pressed { "down" } else { "up" }))
+        });
+        let mut lines: Vec<_> = speeds.chain(quirks).chain(keys).collect();
+        // stable, so presses at the same frame keep their order
+        lines.sort_by_key(|&(frame, order, _)| (frame, order));
+
+        for (frame, _, line) in lines {
+            writeln!(f, "{} {}", frame, line)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HeadlessError {
+    Script { line: usize, text: String },
+    // the ROM is empty or too big to load
+    Rom(String),
+    Crashed(String),
+}
+
+impl fmt::Display for HeadlessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeadlessError::Script { line, text } => {
+                write!(f, "input script line {}: `{}` isn't `FRAME KEY down|up`, `FRAME speed N`, `FRAME quirk NAME on|off` or `seed N`", line, text)
+            },
+            HeadlessError::Rom(message) => write!(f, "unable to load the ROM: {}", message),
+            HeadlessError::Crashed(message) => write!(f, "the emulator crashed: {}", message),
+        }
+    }
+}
+
+impl Error for HeadlessError {}
+
+// one event per line, `FRAME KEY down|up` with the key in hex, `FRAME speed N` with N
+// instructions per frame or `FRAME quirk NAME on|off`, and a `seed N` line for the whole run.
+// `#` starts a comment
+pub fn parse_script(text: &str) -> Result<Script, HeadlessError> {
+    let mut script = Script::default();
+
+    for (index, line) in text.lines().enumerate() {
+        let content = line.split('#').next().unwrap_or_default().trim();
+
+        if content.is_empty() {
+            continue;
+        }
+
+        let error = || HeadlessError::Script { line: index + 1, text: content.to_string() };
+        let mut parts = content.split_whitespace().peekable();
+
+        if parts.next_if_eq(&"seed").is_some() {
+            script.seed = Some(parts.next().and_then(parse_seed).ok_or_else(error)?);
+
+            if parts.next().is_some() {
+                return Err(error());
+            }
+
+            continue;
+        }
+
+        let frame = parts.next().and_then(|frame| frame.parse().ok()).ok_or_else(error)?;
+
+        if parts.next_if_eq(&"speed").is_some() {
+            let ticks_per_frame = parts
+                .next()
+                .and_then(|ticks| ticks.parse().ok())
+                .filter(|&ticks| ticks > 0)
+                .ok_or_else(error)?;
+
+            if parts.next().is_some() {
+                return Err(error());
+            }
+
+            script.set_speed(frame, ticks_per_frame);
+            continue;
+        }
+
+        if parts.next_if_eq(&"quirk").is_some() {
+            let quirk = parts.next().and_then(Quirk::from_name).ok_or_else(error)?;
+            let is_enabled = match parts.next() {
+                Some("on") => true,
+                Some("off") => false,
+                _ => return Err(error()),
+            };
+
+            if parts.next().is_some() {
+                return Err(error());
+            }
+
+            script.set_quirk(frame, quirk, is_enabled);
+            continue;
+        }
+
+        let key = parts
+            .next()
+            .and_then(|key| usize::from_str_radix(key, 16).ok())
+            .filter(|&key| key < NUM_KEYS)
+            .ok_or_else(error)?;
+        let is_pressed = match parts.next() {
+            Some("down") => true,
+            Some("up") => false,
+            _ => return Err(error()),
+        };
+
+        if parts.next().is_some() {
+            return Err(error());
+        }
+
+        script.press(frame, key, is_pressed);
+    }
+
+    Ok(script)
+}
+
+// "0xDEADBEEF" in hex, "42" in decimal
+pub fn parse_seed(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(digits) => u64::from_str_radix(digits, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+// runs `options.frames` frames, an instruction that can't run or a panic inside the emulator comes
+// back as Crashed
+pub fn run(rom: &[u8], options: &Options) -> Result<Chip8, HeadlessError> {
+    let mut chip8 = Chip8::new();
+    chip8.load(rom).map_err(|error| HeadlessError::Rom(error.to_string()))?;
+    chip8.set_seed(options.seed);
+
+    let run = panic::catch_unwind(AssertUnwindSafe(|| {
+        run_frames(&mut chip8, options.frames, options.ticks_per_frame, &options.script)
+    }));
+
+    match run {
+        Ok(Ok(())) => Ok(chip8),
+        Ok(Err(error)) => Err(HeadlessError::Crashed(error.to_string())),
+        Err(cause) => Err(HeadlessError::Crashed(panic_message(cause))),
+    }
+}
+
+pub(crate) fn panic_message(cause: Box<dyn Any + Send>) -> String {
+    cause
+        .downcast_ref::<String>()
+        .cloned()
+        .or_else(|| cause.downcast_ref::<&str>().map(|s| s.to_string()))
+        .unwrap_or_default()
+}
+
+// what stopped `play` short, an instruction that couldn't run or a panic
+pub(crate) fn crash_message(chip8: &mut Chip8, play: impl FnOnce(&mut Chip8)) -> Option<String> {
+    match panic::catch_unwind(AssertUnwindSafe(|| play(chip8))) {
+        Ok(()) => chip8.take_fault().map(|error| error.to_string()),
+        Err(cause) => Some(panic_message(cause)),
+    }
+}
+
+// speed changes land between frames, like in the frontends, so the timers still tick once a frame
+pub(crate) fn run_frames(
+    chip8: &mut Chip8,
+    frames: usize,
+    ticks_per_frame: usize,
+    script: &Script,
+) -> Result<(), Chip8Error> {
+    for frame in 0..frames {
+        play_frame(chip8, frame, ticks_per_frame, script);
+
+        if let Some(error) = chip8.take_fault() {
+            return Err(error);
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn play_frame(chip8: &mut Chip8, frame: usize, ticks_per_frame: usize, script: &Script) -> FrameResult {
+    for change in script.quirks.iter().filter(|change| change.frame == frame) {
+        chip8.set_quirk(change.quirk, change.is_enabled);
+    }
+
+    for key in script.keys.iter().filter(|key| key.frame == frame) {
+        chip8.keypress(key.key, key.is_pressed);
+    }
+
+    chip8.run_frame(script.speed_at(frame, ticks_per_frame))
+}
+
+// plain (P1) PBM, 1 is a lit pixel
+pub fn pbm(screen: &[bool]) -> String {
+    let mut out = format!("P1\n{} {}\n", SCREEN_WIDTH, SCREEN_HEIGHT);
+
+    for row in screen.chunks(SCREEN_WIDTH) {
+        let pixels: Vec<&str> = row.iter().map(|&pixel| if pixel { "1" } else { "0" }).collect();
+        out.push_str(&pixels.join(" "));
+        out.push('\n');
+    }
+
+    out
+}
+
+// what pbm writes back into a screen. any plain PBM of the screen's size reads, whitespace and
+// `#` comments included
+pub fn parse_pbm(text: &str) -> Option<Vec<bool>> {
+    let mut tokens = text.lines().map(|line| line.split('#').next().unwrap_or_default()).flat_map(str::split_whitespace);
+
+    if tokens.next()? != "P1" {
+        return None;
+    }
+
+    let width: usize = tokens.next()?.parse().ok()?;
+    let height: usize = tokens.next()?.parse().ok()?;
+
+    if (width, height) != (SCREEN_WIDTH, SCREEN_HEIGHT) {
+        return None;
+    }
+
+    // pixels may run together without spaces
+    let pixels: Vec<char> = tokens.flat_map(str::chars).collect();
+
+    if pixels.len() != width * height {
+        return None;
+    }
+
+    pixels
+        .into_iter()
+        .map(|pixel| match pixel {
+            '0' => Some(false),
+            '1' => Some(true),
+            _ => None,
+        })
+        .collect()
+}
+
+// the machine state as JSON, the screen as its hash and the seed so the run can be repeated
+pub fn state_json(chip8: &Chip8) -> String {
+    let list = |values: Vec<String>| values.join(", ");
+
+    format!(
+        "{{\n  \"pc\": {},\n  \"i\": {},\n  \"v\": [{}],\n  \"sp\": {},\n  \"stack\": [{}],\n  \"delay_timer\": {},\n  \"sound_timer\": {},\n  \"frames\": {},\n  \"instructions\": {},\n  \"seed\": {},\n  \"screen_hash\": \"{}\"\n}}\n",
+        chip8.program_counter(),
+        chip8.register_i(),
+        list(chip8.registers().iter().map(u8::to_string).collect()),
+        chip8.stack_pointer(),
+        list(chip8.stack()[..chip8.stack_pointer() as usize].iter().map(u16::to_string).collect()),
+        chip8.delay_timer(),
+        chip8.sound_timer(),
+        chip8.frame_count(),
+        chip8.instruction_count(),
+        chip8.seed(),
+        screen_hash(chip8.get_display()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(frames: usize) -> Options {
+        Options { frames, ticks_per_frame: 10, seed: DEFAULT_SEED, script: Script::default() }
+    }
+
+    #[test]
+    fn parses_scripts() {
+        let script = parse_script("# pick an option\n30 A down\n\n 40 a up # and let go\n").unwrap();
+
+        let keys = &script.keys;
+        assert_eq!(keys.len(), 2);
+        assert_eq!((keys[0].frame, keys[0].key, keys[0].is_pressed), (30, 0xA, true));
+        assert_eq!((keys[1].frame, keys[1].key, keys[1].is_pressed), (40, 0xA, false));
+        assert!(script.speeds.is_empty());
+        assert!(script.quirks.is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_script_lines() {
+        let bad = [("30 A", 1), ("1 2 up\nx 1 down", 2), ("5 10 down", 1), ("5 1 down now", 1), ("5 speed 0", 1), ("5 speed", 1), ("5 speed 8 now", 1), ("5 quirk shift on", 1), ("5 quirk shift_uses_vy", 1), ("5 quirk shift_uses_vy on now", 1), ("seed", 1), ("seed 0xZZ", 1), ("seed 1 2", 1)];
+
+        for (text, line) in bad {
+            match parse_script(text) {
+                Err(HeadlessError::Script { line: bad, .. }) => assert_eq!(bad, line, "{:?}", text),
+                result => panic!("{:?} gave {:?}", text, result),
+            }
+        }
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_run() {
+        // draw a random digit at a random spot every frame
+        let rom = [0xC0, 0x3F, 0xC1, 0x1F, 0xC2, 0x0F, 0xF2, 0x29, 0xD0, 0x15, 0x12, 0x00];
+        let mut seeded = options(30);
+        let first = run(&rom, &seeded).unwrap();
+        let second = run(&rom, &seeded).unwrap();
+        seeded.seed = 7;
+        let other = run(&rom, &seeded).unwrap();
+
+        assert_eq!(first.get_display(), second.get_display());
+        assert_ne!(first.get_display(), other.get_display());
+    }
+
+    #[test]
+    fn recordings_round_trip() {
+        let mut script = Script { seed: Some(0xDEADBEEF), ..Script::default() };
+        script.set_speed(0, 10);
+        script.press(30, 0xA, true);
+        script.set_speed(30, 20);
+        script.press(30, 0xB, true);
+        script.set_speed(45, 12);
+        script.set_speed(45, 15);
+        script.press(40, 0xA, false);
+        script.set_quirk(30, Quirk::ShiftUsesVy, true);
+        script.set_quirk(50, Quirk::ShiftUsesVy, true);
+        script.set_quirk(50, Quirk::ShiftUsesVy, false);
+
+        let text = script.to_string();
+        assert_eq!(
+            text,
+            "seed 0xDEADBEEF\n0 speed 10\n30 speed 20\n30 quirk shift_uses_vy on\n30 A down\n30 B down\n40 A up\n45 speed 15\n50 quirk shift_uses_vy off\n"
+        );
+
+        let parsed = parse_script(&text).unwrap();
+        assert_eq!(parsed.keys, script.keys);
+        assert_eq!(parsed.speeds, script.speeds);
+        assert_eq!(parsed.quirks, script.quirks);
+        assert_eq!(parsed.seed, script.seed);
+        assert_eq!(parse_script("seed 42").unwrap().seed, Some(42));
+    }
+
+    #[test]
+    fn quirk_changes_are_replayed_at_their_frame() {
+        // V1 = 0x81, then forever: V0 = V1 >> 1, V2 += V0
+        let rom = [0x61, 0x81, 0x80, 0x16, 0x82, 0x04, 0x12, 0x02];
+        let mut options = options(4);
+        options.ticks_per_frame = 4;
+        options.script = parse_script("2 quirk shift_uses_vy on\n").unwrap();
+
+        // shifting V0 in place keeps it at 0 until the quirk flips, then each shift gives 0x40
+        let chip8 = run(&rom, &options).unwrap();
+        assert_eq!(chip8.registers()[0], 0x40);
+        assert!(chip8.quirks().shift_uses_vy);
+        // two adds after shifts in frames 0 and 1, then two after the shifts in frames 2 and 3
+        assert_eq!(chip8.registers()[2], 0x80);
+    }
+
+    #[test]
+    fn starting_quirks_give_way_to_the_script() {
+        let mut script = parse_script("0 quirk clip_sprites off\n3 quirk vf_reset off\n").unwrap();
+        script.start_with_quirks(Quirks::preset("chip8").unwrap());
+
+        let at_start = |quirk| script.quirks.iter().find(|change| change.frame == 0 && change.quirk == quirk);
+        assert_eq!(at_start(Quirk::ShiftUsesVy).map(|change| change.is_enabled), Some(true));
+        assert_eq!(at_start(Quirk::ClipSprites).map(|change| change.is_enabled), Some(false));
+        assert_eq!(at_start(Quirk::JumpUsesVx).map(|change| change.is_enabled), Some(false));
+        assert_eq!(script.quirks.len(), Quirk::all().count() + 1);
+    }
+
+    #[test]
+    fn speed_changes_are_replayed_at_their_frame() {
+        // count up in V0 forever
+        let rom = [0x70, 0x01, 0x12, 0x00];
+        let mut options = options(20);
+        options.script = parse_script("5 speed 4\n12 speed 30\n").unwrap();
+
+        let first = run(&rom, &options).unwrap();
+        let second = run(&rom, &options).unwrap();
+
+        // 5 frames at the default 10, 7 at 4, 8 at 30
+        assert_eq!(first.instruction_count(), 50 + 28 + 240);
+        assert_eq!(first.frame_count(), 20);
+        assert_eq!(first.registers(), second.registers());
+        assert_eq!(first.registers()[0], ((50 + 28 + 240) / 2) as u8);
+
+        // a change after the last frame doesn't count
+        options.script = parse_script("20 speed 1").unwrap();
+        assert_eq!(run(&rom, &options).unwrap().instruction_count(), 200);
+    }
+
+    #[test]
+    fn reports_crashes() {
+        // return with an empty stack
+        let result = run(&[0x00, 0xEE], &options(1));
+
+        assert!(matches!(result, Err(HeadlessError::Crashed(_))), "{:?}", result.err());
+    }
+
+    #[test]
+    fn dumps_pbm_and_json() {
+        let mut chip8 = Chip8::new();
+        // V5 = 0x2A, draw the top row of "0" at (0, 0)
+        chip8.load(&[0x65, 0x2A, 0xD0, 0x01]).unwrap();
+        chip8.tick().unwrap();
+        chip8.tick().unwrap();
+
+        let pbm = pbm(chip8.get_display());
+        let mut lines = pbm.lines();
+        assert_eq!(lines.next(), Some("P1"));
+        assert_eq!(lines.next(), Some("64 32"));
+        assert!(lines.next().unwrap().starts_with("1 1 1 1 0 0"));
+        assert_eq!(lines.count(), SCREEN_HEIGHT - 1);
+
+        let json = state_json(&chip8);
+        assert!(json.contains("\"pc\": 516,"), "{}", json);
+        assert!(json.contains("\"v\": [0, 0, 0, 0, 0, 42, "), "{}", json);
+        assert!(json.contains("\"stack\": [],"), "{}", json);
+    }
+
+    #[test]
+    fn reads_back_its_pbm() {
+        let mut screen = vec![false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        screen[3] = true;
+        screen[SCREEN_WIDTH * 31 + 63] = true;
+
+        assert_eq!(parse_pbm(&pbm(&screen)), Some(screen.clone()));
+        assert_eq!(parse_pbm(&pbm(&screen).replace("P1\n", "P1 # a comment\n")), Some(screen.clone()));
+        assert_eq!(parse_pbm(&pbm(&screen).replace("0 ", "0").replace("1 ", "1")), Some(screen.clone()));
+        assert_eq!(parse_pbm("P1\n32 16\n"), None);
+        assert_eq!(parse_pbm(&pbm(&screen).replace("1 0", "2 0")), None);
+        assert_eq!(parse_pbm(&pbm(&screen)[..100]), None);
+    }
+}
@@ -0,0 +1,213 @@
+// how often each byte of memory is read and written, for finding what a ROM leans on. reads are
+// what instructions read as data: sprites, FX65, F002 patterns. fetching the instructions
+// themselves isn't counted, or the main loop would top every ranking
+//
+// off by default, the counters are only allocated once it's turned on
+
+use crate::Chip8;
+
+use std::cmp::Reverse;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Heatmap {
+    // a counter per byte of memory each, empty while off. they stop at u32::MAX
+    reads: Vec<u32>,
+    writes: Vec<u32>,
+}
+
+// one address's counts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Heat {
+    pub address: u16,
+    pub reads: u32,
+    pub writes: u32,
+}
+
+impl Heat {
+    pub fn total(&self) -> u64 {
+        self.reads as u64 + self.writes as u64
+    }
+}
+
+impl Heatmap {
+    fn new(ram_size: usize) -> Self {
+        Self { reads: vec![0; ram_size], writes: vec![0; ram_size] }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.reads.is_empty()
+    }
+
+    // 0 outside memory, or while off
+    pub fn reads(&self, address: u16) -> u32 {
+        self.reads.get(address as usize).copied().unwrap_or(0)
+    }
+
+    pub fn writes(&self, address: u16) -> u32 {
+        self.writes.get(address as usize).copied().unwrap_or(0)
+    }
+
+    pub fn get(&self, address: u16) -> Heat {
+        Heat { address, reads: self.reads(address), writes: self.writes(address) }
+    }
+
+    // every address that was touched
+    pub fn iter(&self) -> impl Iterator<Item = Heat> + '_ {
+        (0..self.reads.len() as u16).map(|address| self.get(address)).filter(|heat| heat.total() > 0)
+    }
+
+    // the `n` addresses with the most reads and writes together, ties going to the lower address
+    pub fn hottest(&self, n: usize) -> Vec<Heat> {
+        let mut heats: Vec<Heat> = self.iter().collect();
+        heats.sort_by_key(|heat| (Reverse(heat.total()), heat.address));
+        heats.truncate(n);
+
+        heats
+    }
+
+    // a row for every address, touched or not, so it lines up with a memory dump
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("address,reads,writes\n");
+
+        for (address, (reads, writes)) in self.reads.iter().zip(&self.writes).enumerate() {
+            csv.push_str(&format!("{:03X},{},{}\n", address, reads, writes));
+        }
+
+        csv
+    }
+
+    // the two counters as arrays indexed by address
+    pub fn to_json(&self) -> String {
+        let join = |counts: &[u32]| counts.iter().map(|count| count.to_string()).collect::<Vec<_>>().join(", ");
+
+        format!("{{\n  \"reads\": [{}],\n  \"writes\": [{}]\n}}\n", join(&self.reads), join(&self.writes))
+    }
+
+    fn clear(&mut self) {
+        self.reads.fill(0);
+        self.writes.fill(0);
+    }
+
+    fn record_read(&mut self, address: usize) {
+        if let Some(count) = self.reads.get_mut(address) {
+            *count = count.saturating_add(1);
+        }
+    }
+
+    fn record_write(&mut self, address: usize) {
+        if let Some(count) = self.writes.get_mut(address) {
+            *count = count.saturating_add(1);
+        }
+    }
+}
+
+impl Chip8 {
+    // off by default, turning it off or on again starts the counts over
+    pub fn set_heatmap_enabled(&mut self, is_enabled: bool) {
+        self.heatmap = if is_enabled { Heatmap::new(self.ram.len()) } else { Heatmap::default() };
+    }
+
+    // empty while off
+    pub fn heatmap(&self) -> &Heatmap {
+        &self.heatmap
+    }
+
+    pub fn clear_heatmap(&mut self) {
+        self.heatmap.clear();
+    }
+
+    // a data read by an instruction, counted when the heatmap is on
+    pub(crate) fn read_ram(&mut self, address: usize) -> u8 {
+        self.heatmap.record_read(address);
+
+        self.ram[address]
+    }
+
+    pub(crate) fn record_heatmap_write(&mut self, address: usize) {
+        self.heatmap.record_write(address);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn machine(rom: &[u8]) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load(rom).unwrap();
+        chip8.set_heatmap_enabled(true);
+        chip8
+    }
+
+    #[test]
+    fn costs_nothing_while_off() {
+        let mut chip8 = Chip8::new();
+        // I = 300, store V0, loop
+        chip8.load(&[0xA3, 0x00, 0xF0, 0x55, 0x12, 0x02]).unwrap();
+        chip8.run_frame(10);
+
+        assert!(!chip8.heatmap().is_enabled());
+        assert_eq!(chip8.heatmap().writes(0x300), 0);
+        assert!(chip8.heatmap().hottest(5).is_empty());
+        assert_eq!(chip8.heatmap().to_csv(), "address,reads,writes\n");
+    }
+
+    #[test]
+    fn a_hammered_address_tops_the_ranking() {
+        // I = 300, then store V0 and load V0-V1 from there in a loop: 300 is written once and read
+        // twice a pass, 301 read once
+        let mut chip8 = machine(&[0xA3, 0x00, 0xF0, 0x55, 0xF1, 0x65, 0xF1, 0x65, 0x12, 0x02]);
+        // the ANNN, then 25 passes of four instructions
+        chip8.run_frame(101);
+
+        let hottest = chip8.heatmap().hottest(3);
+        assert_eq!(
+            hottest,
+            [Heat { address: 0x300, reads: 50, writes: 25 }, Heat { address: 0x301, reads: 50, writes: 0 }]
+        );
+        assert_eq!(hottest[0].total(), 75);
+        // fetches aren't reads
+        assert_eq!(chip8.heatmap().reads(0x202), 0);
+
+        chip8.clear_heatmap();
+        assert!(chip8.heatmap().hottest(1).is_empty());
+        assert!(chip8.heatmap().is_enabled());
+    }
+
+    #[test]
+    fn counts_sprite_rows() {
+        // I = font 0, draw it twice
+        let mut chip8 = machine(&[0xA0, 0x00, 0xD0, 0x05, 0xD0, 0x05]);
+        chip8.run_frame(3);
+
+        assert_eq!((0..5).map(|address| chip8.heatmap().reads(address)).collect::<Vec<_>>(), [2; 5]);
+        assert_eq!(chip8.heatmap().reads(5), 0);
+    }
+
+    #[test]
+    fn counters_saturate() {
+        let mut heatmap = Heatmap::new(16);
+        heatmap.reads[3] = u32::MAX - 1;
+
+        for _ in 0..3 {
+            heatmap.record_read(3);
+        }
+
+        assert_eq!(heatmap.reads(3), u32::MAX);
+        assert_eq!(heatmap.get(3).total(), u32::MAX as u64);
+    }
+
+    #[test]
+    fn exports_every_address() {
+        let mut heatmap = Heatmap::new(3);
+        heatmap.record_read(1);
+        heatmap.record_write(1);
+        heatmap.record_write(2);
+
+        assert_eq!(heatmap.to_csv(), "address,reads,writes\n000,0,0\n001,1,1\n002,0,1\n");
+        assert_eq!(heatmap.to_json(), "{\n  \"reads\": [0, 1, 0],\n  \"writes\": [0, 1, 1]\n}\n");
+
+        let parsed: serde_json::Value = serde_json::from_str(&heatmap.to_json()).unwrap();
+        assert_eq!(parsed["writes"][2], 1);
+    }
+}
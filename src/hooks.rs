@@ -0,0 +1,33 @@
+// callbacks for frontends and tools that want to react to the machine as it runs,
+// every method has an empty default so implementors only pick what they need
+pub trait Hooks: Send {
+    fn on_beep_start(&mut self) {}
+
+    fn on_beep_end(&mut self) {}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BeepEdge {
+    Start,
+    End,
+}
+
+impl BeepEdge {
+    pub(crate) fn between(was_beeping: bool, is_beeping: bool) -> Option<Self> {
+        match (was_beeping, is_beeping) {
+            (false, true) => Some(BeepEdge::Start),
+            (true, false) => Some(BeepEdge::End),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TickResult {
+    pub beep: Option<BeepEdge>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameResult {
+    pub beep: Option<BeepEdge>,
+}
@@ -0,0 +1,379 @@
+// the instructions the interpreter runs, decoded. registers are 0 to F, addresses 12 bits, so
+// every value here encodes to an opcode that decodes back to it. with the `arbitrary` feature fuzzers
+// can generate them directly instead of hoping random bytes decode
+//
+// the exception is XO-CHIP's F000 NNNN, two words long. peeking reads it so a listing doesn't take
+// its address for an instruction, but the interpreter doesn't run it
+
+use crate::{Chip8, Chip8Error};
+
+#[cfg(feature = "arbitrary")]
+use arbitrary::{Arbitrary, Unstructured};
+
+// the first word of F000 NNNN
+const LONG_LOAD: u16 = 0xF000;
+
+// what's at an address: its first opcode word and the instruction
+pub type Peek = Result<(u16, Instruction), Chip8Error>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    Nop,
+    Cls,
+    Ret,
+    Jump(u16),
+    Call(u16),
+    SkipEqByte(u8, u8),
+    SkipNeByte(u8, u8),
+    SkipEq(u8, u8),
+    SetByte(u8, u8),
+    AddByte(u8, u8),
+    Set(u8, u8),
+    Or(u8, u8),
+    And(u8, u8),
+    Xor(u8, u8),
+    Add(u8, u8),
+    Sub(u8, u8),
+    ShiftRight(u8, u8),
+    SubN(u8, u8),
+    ShiftLeft(u8, u8),
+    SkipNe(u8, u8),
+    SetI(u16),
+    JumpV0(u16),
+    Random(u8, u8),
+    Draw(u8, u8, u8),
+    SkipKey(u8),
+    SkipNotKey(u8),
+    AudioPattern,
+    GetDelay(u8),
+    WaitKey(u8),
+    SetDelay(u8),
+    SetSound(u8),
+    AddI(u8),
+    Font(u8),
+    Bcd(u8),
+    Pitch(u8),
+    Store(u8),
+    Load(u8),
+    // I = NNNN, from the word after F000
+    LongLoad(u16),
+}
+
+// the fixed bits of each form, as (mask, bits). the rest are operands
+#[cfg(any(test, feature = "arbitrary"))]
+const FORMS: [(u16, u16); 37] = [
+    (0xFFFF, 0x0000),
+    (0xFFFF, 0x00E0),
+    (0xFFFF, 0x00EE),
+    (0xF000, 0x1000),
+    (0xF000, 0x2000),
+    (0xF000, 0x3000),
+    (0xF000, 0x4000),
+    (0xF000, 0x5000),
+    (0xF000, 0x6000),
+    (0xF000, 0x7000),
+    (0xF00F, 0x8000),
+    (0xF00F, 0x8001),
+    (0xF00F, 0x8002),
+    (0xF00F, 0x8003),
+    (0xF00F, 0x8004),
+    (0xF00F, 0x8005),
+    (0xF00F, 0x8006),
+    (0xF00F, 0x8007),
+    (0xF00F, 0x800E),
+    (0xF00F, 0x9000),
+    (0xF000, 0xA000),
+    (0xF000, 0xB000),
+    (0xF000, 0xC000),
+    (0xF000, 0xD000),
+    (0xF0FF, 0xE09E),
+    (0xF0FF, 0xE0A1),
+    (0xFFFF, 0xF002),
+    (0xF0FF, 0xF007),
+    (0xF0FF, 0xF00A),
+    (0xF0FF, 0xF015),
+    (0xF0FF, 0xF018),
+    (0xF0FF, 0xF01E),
+    (0xF0FF, 0xF029),
+    (0xF0FF, 0xF033),
+    (0xF0FF, 0xF03A),
+    (0xF0FF, 0xF055),
+    (0xF0FF, 0xF065),
+];
+
+impl Instruction {
+    // None for opcodes the interpreter doesn't run
+    pub fn decode(opcode: u16) -> Option<Self> {
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+        let n = (opcode & 0x000F) as u8;
+        let nn = (opcode & 0x00FF) as u8;
+        let nnn = opcode & 0x0FFF;
+
+        let instruction = match (opcode >> 12, x, y, n) {
+            (0, 0, 0, 0) => Instruction::Nop,
+            (0, 0, 0xE, 0) => Instruction::Cls,
+            (0, 0, 0xE, 0xE) => Instruction::Ret,
+            (1, _, _, _) => Instruction::Jump(nnn),
+            (2, _, _, _) => Instruction::Call(nnn),
+            (3, _, _, _) => Instruction::SkipEqByte(x, nn),
+            (4, _, _, _) => Instruction::SkipNeByte(x, nn),
+            (5, _, _, _) => Instruction::SkipEq(x, y),
+            (6, _, _, _) => Instruction::SetByte(x, nn),
+            (7, _, _, _) => Instruction::AddByte(x, nn),
+            (8, _, _, 0) => Instruction::Set(x, y),
+            (8, _, _, 1) => Instruction::Or(x, y),
+            (8, _, _, 2) => Instruction::And(x, y),
+            (8, _, _, 3) => Instruction::Xor(x, y),
+            (8, _, _, 4) => Instruction::Add(x, y),
+            (8, _, _, 5) => Instruction::Sub(x, y),
+            (8, _, _, 6) => Instruction::ShiftRight(x, y),
+            (8, _, _, 7) => Instruction::SubN(x, y),
+            (8, _, _, 0xE) => Instruction::ShiftLeft(x, y),
+            (9, _, _, 0) => Instruction::SkipNe(x, y),
+            (0xA, _, _, _) => Instruction::SetI(nnn),
+            (0xB, _, _, _) => Instruction::JumpV0(nnn),
+            (0xC, _, _, _) => Instruction::Random(x, nn),
+            (0xD, _, _, _) => Instruction::Draw(x, y, n),
+            (0xE, _, 9, 0xE) => Instruction::SkipKey(x),
+            (0xE, _, 0xA, 1) => Instruction::SkipNotKey(x),
+            (0xF, 0, 0, 2) => Instruction::AudioPattern,
+            (0xF, _, 0, 7) => Instruction::GetDelay(x),
+            (0xF, _, 0, 0xA) => Instruction::WaitKey(x),
+            (0xF, _, 1, 5) => Instruction::SetDelay(x),
+            (0xF, _, 1, 8) => Instruction::SetSound(x),
+            (0xF, _, 1, 0xE) => Instruction::AddI(x),
+            (0xF, _, 2, 9) => Instruction::Font(x),
+            (0xF, _, 3, 3) => Instruction::Bcd(x),
+            (0xF, _, 3, 0xA) => Instruction::Pitch(x),
+            (0xF, _, 5, 5) => Instruction::Store(x),
+            (0xF, _, 6, 5) => Instruction::Load(x),
+            _ => return None,
+        };
+
+        Some(instruction)
+    }
+
+    pub fn encode(self) -> u16 {
+        let xy = |base: u16, x: u8, y: u8| base | (x as u16) << 8 | (y as u16) << 4;
+        let xnn = |base: u16, x: u8, nn: u8| base | (x as u16) << 8 | nn as u16;
+        let x = |base: u16, x: u8| base | (x as u16) << 8;
+
+        match self {
+            Instruction::Nop => 0x0000,
+            Instruction::Cls => 0x00E0,
+            Instruction::Ret => 0x00EE,
+            Instruction::Jump(nnn) => 0x1000 | nnn,
+            Instruction::Call(nnn) => 0x2000 | nnn,
+            Instruction::SkipEqByte(vx, nn) => xnn(0x3000, vx, nn),
+            Instruction::SkipNeByte(vx, nn) => xnn(0x4000, vx, nn),
+            Instruction::SkipEq(vx, vy) => xy(0x5000, vx, vy),
+            Instruction::SetByte(vx, nn) => xnn(0x6000, vx, nn),
+            Instruction::AddByte(vx, nn) => xnn(0x7000, vx, nn),
+            Instruction::Set(vx, vy) => xy(0x8000, vx, vy),
+            Instruction::Or(vx, vy) => xy(0x8001, vx, vy),
+            Instruction::And(vx, vy) => xy(0x8002, vx, vy),
+            Instruction::Xor(vx, vy) => xy(0x8003, vx, vy),
+            Instruction::Add(vx, vy) => xy(0x8004, vx, vy),
+            Instruction::Sub(vx, vy) => xy(0x8005, vx, vy),
+            Instruction::ShiftRight(vx, vy) => xy(0x8006, vx, vy),
+            Instruction::SubN(vx, vy) => xy(0x8007, vx, vy),
+            Instruction::ShiftLeft(vx, vy) => xy(0x800E, vx, vy),
+            Instruction::SkipNe(vx, vy) => xy(0x9000, vx, vy),
+            Instruction::SetI(nnn) => 0xA000 | nnn,
+            Instruction::JumpV0(nnn) => 0xB000 | nnn,
+            Instruction::Random(vx, nn) => xnn(0xC000, vx, nn),
+            Instruction::Draw(vx, vy, n) => xy(0xD000, vx, vy) | n as u16,
+            Instruction::SkipKey(vx) => x(0xE09E, vx),
+            Instruction::SkipNotKey(vx) => x(0xE0A1, vx),
+            Instruction::AudioPattern => 0xF002,
+            Instruction::GetDelay(vx) => x(0xF007, vx),
+            Instruction::WaitKey(vx) => x(0xF00A, vx),
+            Instruction::SetDelay(vx) => x(0xF015, vx),
+            Instruction::SetSound(vx) => x(0xF018, vx),
+            Instruction::AddI(vx) => x(0xF01E, vx),
+            Instruction::Font(vx) => x(0xF029, vx),
+            Instruction::Bcd(vx) => x(0xF033, vx),
+            Instruction::Pitch(vx) => x(0xF03A, vx),
+            Instruction::Store(vx) => x(0xF055, vx),
+            Instruction::Load(vx) => x(0xF065, vx),
+            // only the first word, the address follows it
+            Instruction::LongLoad(_) => LONG_LOAD,
+        }
+    }
+
+    // the instructions that write a flag to VF, 0 or 1
+    pub fn sets_flag(self) -> bool {
+        matches!(
+            self,
+            Instruction::Add(..)
+                | Instruction::Sub(..)
+                | Instruction::ShiftRight(..)
+                | Instruction::SubN(..)
+                | Instruction::ShiftLeft(..)
+                | Instruction::Draw(..)
+        )
+    }
+}
+
+impl Instruction {
+    // bytes from this instruction to the next
+    pub fn size(self) -> u16 {
+        match self {
+            Instruction::LongLoad(_) => 4,
+            _ => 2,
+        }
+    }
+}
+
+impl Chip8 {
+    // the opcode at `address` and what it decodes to, without running anything or moving the
+    // program counter. F000 takes the word after it as its address
+    pub fn peek_instruction_at(&self, address: u16) -> Peek {
+        let word = |address: u16| {
+            let index = address as usize;
+            Some(u16::from_be_bytes([*self.ram.get(index)?, *self.ram.get(index + 1)?]))
+        };
+
+        let opcode = word(address).ok_or(Chip8Error::PcOutOfBounds(address))?;
+
+        if opcode == LONG_LOAD {
+            let target = address.checked_add(2).and_then(word).ok_or(Chip8Error::PcOutOfBounds(address))?;
+            return Ok((opcode, Instruction::LongLoad(target)));
+        }
+
+        let instruction = Instruction::decode(opcode).ok_or(Chip8Error::UnknownOpcode { address, opcode })?;
+
+        Ok((opcode, instruction))
+    }
+
+    // up to `count` instructions from the program counter on, each with its address. a word that
+    // isn't an instruction is stepped over on its own, and the list ends early where memory does
+    pub fn peek_next(&self, count: usize) -> Vec<(u16, Peek)> {
+        let mut peeked = Vec::new();
+        let mut address = Some(self.program_counter);
+
+        while let Some(at) = address.filter(|_| peeked.len() < count) {
+            let peek = self.peek_instruction_at(at);
+            let size = match &peek {
+                Ok((_, instruction)) => instruction.size(),
+                Err(Chip8Error::PcOutOfBounds(_)) => break,
+                Err(_) => 2,
+            };
+
+            peeked.push((at, peek));
+            address = at.checked_add(size);
+        }
+
+        peeked
+    }
+}
+
+// a form, then random operands in its free bits, so every input is an instruction
+#[cfg(feature = "arbitrary")]
+impl<'a> Arbitrary<'a> for Instruction {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let &(mask, bits) = u.choose(&FORMS)?;
+        let operands: u16 = u.arbitrary()?;
+
+        Ok(Self::decode(bits | (operands & !mask)).expect("every form decodes"))
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (3, Some(3))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_every_instruction() {
+        for instruction in (0..=u16::MAX).filter_map(Instruction::decode) {
+            assert_eq!(Instruction::decode(instruction.encode()), Some(instruction));
+        }
+
+        // the interpreter ignores the low nibble of 5XY0
+        assert_eq!(Instruction::decode(0x5121).map(Instruction::encode), Some(0x5120));
+    }
+
+    #[test]
+    fn every_form_decodes_whatever_its_operands() {
+        for (mask, bits) in FORMS {
+            for operands in [0x0000, 0xFFFF, 0x1234, 0xABCD] {
+                let opcode = bits | (operands & !mask);
+                assert!(Instruction::decode(opcode).is_some(), "{:04X}", opcode);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_what_the_interpreter_doesnt_run() {
+        for opcode in [0x0123, 0x8008, 0x9001, 0xE000, 0xF0FF, 0xF102] {
+            assert_eq!(Instruction::decode(opcode), None, "{:04X}", opcode);
+        }
+    }
+
+    // V1 = 2, I = 0x0300 as a long load, then data
+    const LONG: [u8; 8] = [0x61, 0x02, 0xF0, 0x00, 0x03, 0x00, 0xFF, 0xFF];
+
+    #[test]
+    fn peeking_changes_nothing() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&LONG).unwrap();
+        chip8.set_trace_capacity(8);
+        chip8.tick().unwrap();
+        let (state, count, trace) = (chip8.save_state(), chip8.instruction_count(), chip8.trace().len());
+
+        assert_eq!(chip8.peek_instruction_at(0x200), Ok((0x6102, Instruction::SetByte(1, 2))));
+        assert_eq!(chip8.peek_next(3).len(), 3);
+
+        assert_eq!(chip8.save_state(), state);
+        assert_eq!((chip8.instruction_count(), chip8.trace().len()), (count, trace));
+        assert_eq!(chip8.program_counter(), 0x202);
+    }
+
+    #[test]
+    fn reads_a_long_load_across_the_end_of_the_window() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&LONG).unwrap();
+
+        // the long load is the last instruction asked for, its address word still belongs to it
+        let peeked = chip8.peek_next(2);
+        assert_eq!(peeked[1], (0x202, Ok((0xF000, Instruction::LongLoad(0x0300)))));
+
+        // and the next one is past it
+        let peeked = chip8.peek_next(3);
+        assert_eq!(peeked[2], (0x206, Err(Chip8Error::UnknownOpcode { address: 0x206, opcode: 0xFFFF })));
+
+        // the address word read on its own isn't an instruction
+        assert_eq!(chip8.peek_instruction_at(0x204), Err(Chip8Error::UnknownOpcode { address: 0x204, opcode: 0x0300 }));
+    }
+
+    #[test]
+    fn stops_at_the_end_of_memory() {
+        let mut chip8 = Chip8::new();
+        let end = chip8.memory().len() as u16;
+        chip8.write_memory(end - 2, 0x12);
+        chip8.write_memory(end - 1, 0x00);
+        chip8.set_program_counter(end - 4);
+
+        let addresses: Vec<u16> = chip8.peek_next(10).iter().map(|(address, _)| *address).collect();
+        assert_eq!(addresses, [end - 4, end - 2]);
+        assert_eq!(chip8.peek_instruction_at(end - 1), Err(Chip8Error::PcOutOfBounds(end - 1)));
+
+        // a long load whose address would be past the end doesn't fit either
+        chip8.write_memory(end - 2, 0xF0);
+        assert_eq!(chip8.peek_instruction_at(end - 2), Err(Chip8Error::PcOutOfBounds(end - 2)));
+        assert_eq!(chip8.peek_next(10).len(), 1);
+    }
+
+    #[test]
+    fn agrees_with_the_disassembler() {
+        for opcode in (0..=u16::MAX).step_by(7) {
+            let is_data = crate::disasm::disassemble(opcode).starts_with("DW ");
+            assert_eq!(Instruction::decode(opcode).is_none(), is_data, "{:04X}", opcode);
+        }
+    }
+}
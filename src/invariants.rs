@@ -0,0 +1,169 @@
+// what must hold for the machine whatever program it runs, for fuzzing and for tests.
+//
+// the interpreter panics on programs that leave memory or the stack, that's a crash report, not a
+// bug. can_execute and can_tick say when that would happen so a fuzzer can steer around it, and
+// anything that still breaks `check` is a real bug
+
+use crate::{Chip8, Instruction, NUM_KEYS};
+
+// the last address a whole instruction fits at
+fn last_pc(chip8: &Chip8) -> usize {
+    chip8.ram.len() - 2
+}
+
+// whether running `instruction` right now, with the program counter already past it, stays inside
+// memory and the stack
+pub fn can_execute(chip8: &Chip8, instruction: Instruction) -> bool {
+    can_execute_at(chip8, instruction, chip8.program_counter() as usize)
+}
+
+fn can_execute_at(chip8: &Chip8, instruction: Instruction, pc: usize) -> bool {
+    let i = chip8.register_i() as usize;
+    let v = |register: u8| chip8.registers()[register as usize] as usize;
+    let fits = |len: usize| i + len <= chip8.ram.len();
+    let last_pc = last_pc(chip8);
+
+    let is_in_bounds = match instruction {
+        Instruction::Ret => chip8.stack_pointer() > 0,
+        Instruction::Jump(address) => address as usize <= last_pc,
+        Instruction::Call(address) => (chip8.stack_pointer() as usize) < chip8.stack_limit && address as usize <= last_pc,
+        Instruction::SkipEqByte(..)
+        | Instruction::SkipNeByte(..)
+        | Instruction::SkipEq(..)
+        | Instruction::SkipNe(..) => pc + 2 <= last_pc,
+        Instruction::SkipKey(x) | Instruction::SkipNotKey(x) => v(x) < NUM_KEYS && pc + 2 <= last_pc,
+        Instruction::JumpV0(address) => {
+            let register = if chip8.quirks().jump_uses_vx { (address >> 8) as u8 } else { 0 };
+            v(register) + address as usize <= last_pc
+        },
+        Instruction::Draw(_, _, rows) => fits(rows as usize),
+        Instruction::WaitKey(_) => pc >= 2,
+        Instruction::AudioPattern => fits(16),
+        Instruction::Bcd(_) => fits(3),
+        Instruction::Store(x) | Instruction::Load(x) => fits(x as usize + 1),
+        _ => true,
+    };
+
+    // anything that doesn't set the program counter carries on from it, and past the last
+    // instruction is off the end of memory. CALL pushes it too
+    let sets_pc = matches!(instruction, Instruction::Ret | Instruction::Jump(_) | Instruction::JumpV0(_));
+
+    is_in_bounds && (sets_pc || pc <= last_pc)
+}
+
+// whether a tick at the program counter would fetch an instruction the interpreter runs and run
+// it without leaving memory or the stack
+pub fn can_tick(chip8: &Chip8) -> bool {
+    let pc = chip8.program_counter() as usize;
+
+    if pc > last_pc(chip8) {
+        return false;
+    }
+
+    let memory = chip8.memory();
+    let Some(instruction) = Instruction::decode(u16::from_be_bytes([memory[pc], memory[pc + 1]])) else {
+        return false;
+    };
+
+    // execute sees the program counter past the instruction
+    can_execute_at(chip8, instruction, pc + 2)
+}
+
+// `executed` is the instruction that just ran, if one did, for the checks that depend on it
+pub fn check(chip8: &Chip8, executed: Option<Instruction>) -> Result<(), String> {
+    if chip8.stack_pointer() as usize > chip8.stack_limit {
+        return Err(format!("the stack pointer is {}, past the {} entry stack", chip8.stack_pointer(), chip8.stack_limit));
+    }
+
+    if chip8.program_counter() as usize > last_pc(chip8) {
+        return Err(format!("the program counter is {:#05X}, past the end of memory", chip8.program_counter()));
+    }
+
+    let vf = chip8.registers()[0xF];
+    if executed.is_some_and(Instruction::sets_flag) && vf > 1 {
+        return Err(format!("VF is {} after {:?}, flags are 0 or 1", vf, executed.unwrap()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha12Rng;
+
+    #[test]
+    fn refuses_what_would_crash() {
+        let mut chip8 = Chip8::with_seed(1);
+
+        assert!(!can_execute(&chip8, Instruction::Ret));
+        assert!(!can_execute(&chip8, Instruction::Jump(0xFFF)));
+        assert!(can_execute(&chip8, Instruction::Call(0x300)));
+
+        // past an instruction at 0xFFE
+        chip8.program_counter = 0x1000;
+        assert!(!can_execute(&chip8, Instruction::Cls));
+        assert!(!can_execute(&chip8, Instruction::Call(0x300)));
+        assert!(can_execute(&chip8, Instruction::Jump(0x200)));
+        chip8.program_counter = 0x200;
+
+        chip8.set_register(0, 16);
+        assert!(!can_execute(&chip8, Instruction::SkipKey(0)));
+        assert!(!can_execute(&chip8, Instruction::JumpV0(0xFF0)));
+
+        chip8.set_register_i(0xFFE);
+        assert!(!can_execute(&chip8, Instruction::Bcd(0)));
+        assert!(can_execute(&chip8, Instruction::Store(1)));
+        assert!(!can_execute(&chip8, Instruction::Store(2)));
+    }
+
+    #[test]
+    fn ticks_only_what_decodes() {
+        let mut chip8 = Chip8::with_seed(1);
+
+        chip8.load(&[0x00, 0xE0, 0x01, 0x23, 0x00, 0xEE]).unwrap();
+        assert!(can_tick(&chip8));
+        chip8.tick().unwrap();
+        assert!(!can_tick(&chip8));
+        chip8.tick_timers();
+        chip8.program_counter = 0x204;
+        assert!(!can_tick(&chip8));
+    }
+
+    #[test]
+    fn catches_a_broken_machine() {
+        let mut chip8 = Chip8::with_seed(1);
+        assert_eq!(check(&chip8, None), Ok(()));
+
+        chip8.set_register(0xF, 2);
+        assert_eq!(check(&chip8, Some(Instruction::SetByte(0xF, 2))), Ok(()));
+        assert!(check(&chip8, Some(Instruction::Add(0, 1))).unwrap_err().contains("VF is 2"));
+
+        chip8.program_counter = 0xFFF;
+        assert!(check(&chip8, None).unwrap_err().contains("program counter"));
+    }
+
+    // the fuzz targets in miniature, so plain `cargo test` runs a few thousand random instructions
+    #[test]
+    fn random_instructions_keep_the_invariants() {
+        let mut rng = ChaCha12Rng::seed_from_u64(0x5EED);
+
+        for seed in 0..20 {
+            let mut chip8 = Chip8::with_seed(seed);
+            chip8.set_quirk(crate::Quirk::ShiftUsesVy, seed % 2 == 0);
+
+            for _ in 0..500 {
+                let Some(instruction) = Instruction::decode(rng.gen()) else {
+                    continue;
+                };
+
+                if can_execute(&chip8, instruction) {
+                    chip8.execute_instruction(instruction).unwrap();
+                    check(&chip8, Some(instruction)).unwrap();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,367 @@
+// FX0A, and a limit on how long it waits. like on the COSMAC VIP it takes a key that goes down while
+// it waits and finishes once that key comes back up, so a key still held from earlier doesn't
+// answer it and the program doesn't carry on with the key down
+//
+// the limit is so a headless or scripted run that reaches one nobody will answer doesn't spin
+// forever. off by default, like on the real machine
+
+use crate::{Chip8, Chip8Error, NUM_KEYS};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum KeyWait {
+    // no FX0A running
+    #[default]
+    Idle,
+    // an FX0A is running and no key has gone down since it started
+    Waiting,
+    // this key went down, the FX0A finishes when it comes up
+    Pressed(u8),
+}
+
+impl KeyWait {
+    // a byte for save states
+    pub(crate) fn encode(self) -> u8 {
+        match self {
+            KeyWait::Idle => 0,
+            KeyWait::Waiting => 1,
+            KeyWait::Pressed(key) => 2 + key,
+        }
+    }
+
+    pub(crate) fn decode(byte: u8) -> Self {
+        match byte {
+            0 => KeyWait::Idle,
+            1 => KeyWait::Waiting,
+            _ => KeyWait::Pressed((byte - 2) % NUM_KEYS as u8),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnTimeout {
+    // finish the wait as if this key had been pressed and let go
+    PressKey(u8),
+    // pause with StopReason::KeyWaitTimeout, the wait carries on when the machine does
+    Stop,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyWaitTimeout {
+    // whole frames spent waiting for a key to go down, the wait times out at the end of the last
+    // one. a key that's down is only waiting on the player to let go, so it isn't timed
+    pub frames: u32,
+    pub on_timeout: OnTimeout,
+}
+
+// why the machine paused itself, other than a breakpoint
+#[derive(Clone, Debug, PartialEq)]
+pub enum StopReason {
+    // the FX0A at this address waited out the timeout
+    KeyWaitTimeout(u16),
+    // an instruction in run_frame or advance_frame couldn't run
+    Fault(Chip8Error),
+}
+
+impl Chip8 {
+    // None (the default) lets FX0A wait forever
+    pub fn set_key_wait_timeout(&mut self, timeout: Option<KeyWaitTimeout>) {
+        if let Some(KeyWaitTimeout { on_timeout: OnTimeout::PressKey(key), .. }) = timeout {
+            assert!((key as usize) < NUM_KEYS, "there are only 16 keys");
+        }
+
+        self.key_wait_timeout = timeout;
+    }
+
+    pub fn key_wait_timeout(&self) -> Option<KeyWaitTimeout> {
+        self.key_wait_timeout
+    }
+
+    // frames the current FX0A has been waiting, 0 when nothing is
+    pub fn key_wait_frames(&self) -> u32 {
+        self.key_wait_frames
+    }
+
+    // what paused the machine since the last call, if it paused itself
+    pub fn take_stop_reason(&mut self) -> Option<StopReason> {
+        self.stop_reason.take()
+    }
+
+    // like take_stop_reason, for callers that only look for faults. any other reason stays
+    pub fn take_fault(&mut self) -> Option<Chip8Error> {
+        match self.stop_reason.take() {
+            Some(StopReason::Fault(error)) => Some(error),
+            other => {
+                self.stop_reason = other;
+                None
+            },
+        }
+    }
+
+    // the key the running FX0A is waiting to come up, if one went down
+    pub fn key_wait_key(&self) -> Option<u8> {
+        match self.key_wait {
+            KeyWait::Pressed(key) => Some(key),
+            _ => None,
+        }
+    }
+
+    // FX0A, run again and again until the key it took comes up
+    pub(crate) fn wait_for_key(&mut self, x: usize) {
+        if self.key_wait == KeyWait::Idle {
+            self.key_wait = KeyWait::Waiting;
+        }
+
+        let released = match self.key_wait {
+            KeyWait::Pressed(key) if !self.keys[key as usize] => Some(key),
+            _ => None,
+        };
+
+        match released {
+            Some(key) => {
+                self.register_v[x] = key;
+                self.key_wait = KeyWait::Idle;
+            },
+            None => self.program_counter = self.instruction_address(),
+        }
+
+        self.record_key_wait(released.is_some());
+    }
+
+    // called by keypress before it stores the key, so a press and release between two instructions
+    // still counts
+    pub(crate) fn see_key_for_wait(&mut self, key: usize, is_pressed: bool) {
+        if is_pressed && !self.keys[key] && self.key_wait == KeyWait::Waiting {
+            self.key_wait = KeyWait::Pressed(key as u8);
+            self.key_wait_frames = 0;
+        }
+    }
+
+    // called by FX0A each time it runs
+    pub(crate) fn record_key_wait(&mut self, is_done: bool) {
+        self.is_waiting_for_key = !is_done;
+
+        if is_done {
+            self.key_wait_frames = 0;
+        }
+    }
+
+    // called by advance_frame at the end of each frame
+    pub(crate) fn check_key_wait(&mut self) {
+        if !std::mem::take(&mut self.is_waiting_for_key) || self.key_wait != KeyWait::Waiting {
+            return;
+        }
+
+        self.key_wait_frames += 1;
+
+        let Some(timeout) = self.key_wait_timeout else {
+            return;
+        };
+
+        if self.key_wait_frames < timeout.frames {
+            return;
+        }
+
+        let pc = self.program_counter;
+
+        match timeout.on_timeout {
+            OnTimeout::PressKey(key) => {
+                let x = (self.ram[pc as usize] & 0x0F) as usize;
+                self.register_v[x] = key;
+                self.program_counter += 2;
+                self.key_wait_frames = 0;
+                self.key_wait = KeyWait::Idle;
+            },
+            OnTimeout::Stop => {
+                self.stop_reason = Some(StopReason::KeyWaitTimeout(pc));
+                self.is_paused = true;
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Instruction;
+
+    // waits for a key into V3, then jumps to itself
+    const ROM: [u8; 4] = [0xF3, 0x0A, 0x12, 0x02];
+
+    fn machine(on_timeout: OnTimeout) -> Chip8 {
+        let mut chip8 = Chip8::with_seed(1);
+        chip8.load(&ROM).unwrap();
+        chip8.set_key_wait_timeout(Some(KeyWaitTimeout { frames: 5, on_timeout }));
+
+        chip8
+    }
+
+    #[test]
+    fn waits_forever_without_a_timeout() {
+        let mut chip8 = Chip8::with_seed(1);
+        chip8.load(&ROM).unwrap();
+
+        for _ in 0..100 {
+            chip8.run_frame(10);
+        }
+
+        assert_eq!(chip8.program_counter(), 0x200);
+        assert_eq!(chip8.key_wait_frames(), 100);
+        assert!(!chip8.is_paused());
+    }
+
+    // execute_instruction doesn't fetch, so at pc 0 the wait runs again "at" 0xFFFE
+    #[test]
+    fn waiting_at_the_bottom_of_memory_wraps() {
+        let mut chip8 = Chip8::with_seed(1);
+        chip8.program_counter = 0;
+
+        chip8.execute_instruction(Instruction::WaitKey(3)).unwrap();
+        assert_eq!(chip8.program_counter(), 0xFFFE);
+    }
+
+    #[test]
+    fn presses_the_default_key_at_the_deadline() {
+        let mut chip8 = machine(OnTimeout::PressKey(0xB));
+
+        for _ in 0..4 {
+            chip8.run_frame(10);
+        }
+        assert_eq!(chip8.program_counter(), 0x200);
+
+        chip8.run_frame(10);
+        assert_eq!(chip8.program_counter(), 0x202);
+        assert_eq!(chip8.registers()[3], 0xB);
+        assert_eq!(chip8.key_wait_frames(), 0);
+        assert!(!chip8.keys()[0xB]);
+        assert_eq!(chip8.take_stop_reason(), None);
+    }
+
+    #[test]
+    fn stops_at_the_deadline() {
+        let mut chip8 = machine(OnTimeout::Stop);
+
+        for _ in 0..10 {
+            chip8.run_frame(10);
+        }
+
+        assert!(chip8.is_paused());
+        assert_eq!(chip8.frame_count(), 5);
+        assert_eq!(chip8.take_stop_reason(), Some(StopReason::KeyWaitTimeout(0x200)));
+        assert_eq!(chip8.take_stop_reason(), None);
+
+        // a key answers it once the machine carries on
+        chip8.keypress(7, true);
+        chip8.set_paused(false);
+        chip8.run_frame(10);
+        chip8.keypress(7, false);
+        chip8.run_frame(10);
+        assert_eq!(chip8.program_counter(), 0x202);
+        assert_eq!(chip8.registers()[3], 7);
+        assert_eq!(chip8.key_wait_frames(), 0);
+    }
+
+    #[test]
+    fn a_key_just_before_the_deadline_resets_the_count() {
+        for on_timeout in [OnTimeout::PressKey(0xB), OnTimeout::Stop] {
+            let mut chip8 = machine(on_timeout);
+
+            for _ in 0..4 {
+                chip8.run_frame(10);
+            }
+            assert_eq!(chip8.key_wait_frames(), 4);
+
+            // tapped between two frames
+            chip8.keypress(2, true);
+            chip8.keypress(2, false);
+            chip8.run_frame(10);
+            assert_eq!(chip8.registers()[3], 2);
+            assert_eq!(chip8.key_wait_frames(), 0);
+
+            // and the next wait gets the whole timeout
+            chip8.set_program_counter(0x200);
+            for _ in 0..4 {
+                chip8.run_frame(10);
+            }
+            assert_eq!(chip8.program_counter(), 0x200);
+            assert!(!chip8.is_paused());
+        }
+    }
+
+    #[test]
+    fn takes_the_key_on_press_and_carries_on_at_release() {
+        let mut chip8 = Chip8::with_seed(1);
+        chip8.load(&ROM).unwrap();
+        chip8.tick().unwrap();
+
+        chip8.keypress(5, true);
+        for _ in 0..3 {
+            chip8.tick().unwrap();
+            assert_eq!(chip8.program_counter(), 0x200);
+        }
+        assert_eq!(chip8.key_wait_key(), Some(5));
+
+        // another key going down and up in the meantime doesn't change which one it takes
+        chip8.keypress(9, true);
+        chip8.keypress(9, false);
+        chip8.tick().unwrap();
+        assert_eq!(chip8.program_counter(), 0x200);
+
+        chip8.keypress(5, false);
+        chip8.tick().unwrap();
+        assert_eq!(chip8.program_counter(), 0x202);
+        assert_eq!(chip8.registers()[3], 5);
+        assert_eq!(chip8.key_wait_key(), None);
+    }
+
+    #[test]
+    fn a_key_held_from_before_doesnt_answer() {
+        let mut chip8 = Chip8::with_seed(1);
+        chip8.load(&ROM).unwrap();
+        chip8.keypress(1, true);
+
+        for _ in 0..5 {
+            chip8.run_frame(10);
+        }
+        assert_eq!(chip8.program_counter(), 0x200);
+
+        // letting it go and pressing it again does
+        chip8.keypress(1, false);
+        chip8.run_frame(10);
+        chip8.keypress(1, true);
+        chip8.run_frame(10);
+        chip8.keypress(1, false);
+        chip8.run_frame(10);
+        assert_eq!((chip8.program_counter(), chip8.registers()[3]), (0x202, 1));
+    }
+
+    #[test]
+    fn leaves_vx_alone_while_waiting() {
+        // V3 = 0x42, DT = V3, then wait for a key into V3
+        let mut chip8 = Chip8::with_seed(1);
+        chip8.load(&[0x63, 0x42, 0xF3, 0x15, 0xF3, 0x0A]).unwrap();
+
+        for _ in 0..3 {
+            chip8.run_frame(10);
+        }
+        assert_eq!(chip8.registers()[3], 0x42);
+        assert_ne!(chip8.delay_timer(), 0x42);
+    }
+
+    #[test]
+    fn save_states_keep_the_count() {
+        let mut chip8 = machine(OnTimeout::Stop);
+        for _ in 0..3 {
+            chip8.run_frame(10);
+        }
+        let state = chip8.save_state();
+
+        let mut other = machine(OnTimeout::Stop);
+        other.load_state(&state).unwrap();
+        assert_eq!(other.key_wait_frames(), 3);
+
+        other.run_frame(10);
+        other.run_frame(10);
+        assert_eq!(other.take_stop_reason(), Some(StopReason::KeyWaitTimeout(0x200)));
+    }
+}
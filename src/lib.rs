@@ -1,7 +1,101 @@
-use rand::Rng;
+pub mod analyze;
+pub mod audit;
+mod audio;
+pub mod bench;
+mod builder;
+mod clock;
+pub mod conformance;
+pub mod debug_session;
+mod debugger;
+pub mod diagnostics;
+mod dirty;
+pub mod disasm;
+pub mod env;
+mod error;
+pub mod expr;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod gif;
+pub mod golden;
+pub mod harness;
+pub mod headless;
+mod heatmap;
+#[cfg(feature = "libretro")]
+pub mod libretro;
+mod hooks;
+mod instruction;
+pub mod invariants;
+mod key_wait;
+pub mod lockstep;
+mod memory_changes;
+pub mod netplay;
+pub mod ocr;
+mod palette;
+mod phosphor;
+mod png;
+#[cfg(feature = "python")]
+mod python;
+mod quirks;
+mod recorder;
+#[cfg(feature = "remote")]
+pub mod remote;
+pub mod report;
+mod replay_buffer;
+mod rewind;
+mod rom;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+mod state;
+mod step_back;
+pub mod sweep;
+mod trace;
+pub mod trace_log;
+pub mod vectors;
+#[cfg(feature = "viewer")]
+pub mod viewer;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use audio::{BeepConfig, Waveform, DEFAULT_BEEP_FREQUENCY, DEFAULT_BEEP_VOLUME};
+pub use builder::Chip8Builder;
+pub use clock::FrameClock;
+pub use dirty::DirtyRegion;
+pub use error::Chip8Error;
+pub use gif::GifRecorder;
+pub use heatmap::{Heat, Heatmap};
+
+pub use hooks::{BeepEdge, FrameResult, Hooks, TickResult};
+pub use instruction::{Instruction, Peek};
+pub use key_wait::{KeyWaitTimeout, OnTimeout, StopReason};
+pub use memory_changes::MemoryChange;
+pub use palette::{Palette, PALETTES};
+pub use phosphor::Phosphor;
+pub use png::{export_apng, export_png};
+pub use quirks::{Quirk, Quirks};
+pub use recorder::AudioRecorder;
+pub use replay_buffer::{PackedScreen, PACKED_SCREEN_BYTES};
+pub use rom::LoadedRom;
+pub use state::{StateError, MAX_STATE_SIZE};
+pub use step_back::{DEFAULT_STEP_BACK_INTERVAL, STEP_BACK_CHECKPOINTS};
+pub use trace::TraceEntry;
+
+use audio::{Beeper, AUDIO_PATTERN_SIZE, DEFAULT_AUDIO_PITCH};
+use debugger::Breakpoints;
+use key_wait::KeyWait;
+use replay_buffer::ReplayBuffer;
+use rewind::RewindBuffer;
+use step_back::StepHistory;
+use trace::TraceBuffer;
+
+use rand::{Rng, SeedableRng};
+use std::fs;
+use std::path::Path;
+// StdRng's generator, named so a save state can seek back to where it was
+use rand_chacha::ChaCha12Rng;
 
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
+pub const MAX_ROM_SIZE: usize = RAM_SIZE - START_ADDRESS as usize;
 
 const START_ADDRESS: u16 = 0x200;
 const RAM_SIZE: usize = 4096;
@@ -31,7 +125,10 @@ const FONTSET: [u8; FONTSET_SIZE] = [
 
 pub struct Chip8 {
     screen: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
-    ram: [u8; RAM_SIZE],
+    // RAM_SIZE unless the builder made it smaller
+    ram: Vec<u8>,
+    start_address: u16,
+    font: [u8; FONTSET_SIZE],
     program_counter: u16,
     register_v: [u8; NUM_REGISTER_V],
     register_i: u16,
@@ -39,15 +136,88 @@ pub struct Chip8 {
     sound_timer: u8,
     stack_pointer: u16,
     stack: [u16; STACK_SIZE],
+    stack_limit: usize,
     keys: [bool; NUM_KEYS],
-    is_debug: bool
+    is_debug: bool,
+    loaded_rom: Option<LoadedRom>,
+    beeper: Beeper,
+    audio_pattern: Option<[u8; AUDIO_PATTERN_SIZE]>,
+    audio_pitch: u8,
+    audio_recorder: Option<AudioRecorder>,
+    hooks: Option<Box<dyn Hooks>>,
+    is_paused: bool,
+    frame_count: u64,
+    instruction_count: u64,
+    // DXYN run, and how many of them erased a pixel
+    draw_count: u64,
+    collision_count: u64,
+    // a DXYN ran since the last timer tick, the display_wait quirk holds back the next one
+    has_drawn_this_frame: bool,
+    speed_multiplier: f64,
+    speed_carry: f64,
+    rewind: RewindBuffer,
+    step_history: StepHistory,
+    replay: ReplayBuffer,
+    trace: TraceBuffer,
+    quirks: Quirks,
+    breakpoints: Breakpoints,
+    dirty: Option<DirtyRegion>,
+    // None unless a frontend asked for them
+    memory_changes: Option<Vec<MemoryChange>>,
+    heatmap: Heatmap,
+    key_wait_timeout: Option<KeyWaitTimeout>,
+    key_wait_frames: u32,
+    // FX0A found no key this frame
+    is_waiting_for_key: bool,
+    // how far the FX0A running now has got
+    key_wait: KeyWait,
+    stop_reason: Option<StopReason>,
+    rng: ChaCha12Rng,
+    seed: u64
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Chip8 {
     pub fn new() -> Self {
+        Self::with_seed(rand::random())
+    }
+
+    // for anything past the defaults and a seed
+    pub fn builder() -> Chip8Builder {
+        Chip8Builder::default()
+    }
+
+    // a new machine with `rom` loaded, if it fits
+    pub fn new_with_rom(rom: &[u8]) -> Result<Self, Chip8Error> {
+        let mut chip8 = Self::new();
+        chip8.load(rom)?;
+
+        Ok(chip8)
+    }
+
+    // new_with_rom from a file, named after its path
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Chip8Error> {
+        let path = path.as_ref();
+        let rom = fs::read(path).map_err(|e| Chip8Error::Io { path: path.display().to_string(), message: e.to_string() })?;
+
+        let mut chip8 = Self::new();
+        chip8.load_named(&rom, &path.display().to_string())?;
+
+        Ok(chip8)
+    }
+
+    // for hosts without an OS random source to seed from
+    pub fn with_seed(seed: u64) -> Self {
         let mut chip = Self {
             screen: [false; SCREEN_WIDTH * SCREEN_HEIGHT],
-            ram: [0; RAM_SIZE],
+            ram: vec![0; RAM_SIZE],
+            start_address: START_ADDRESS,
+            font: FONTSET,
             program_counter: START_ADDRESS,
             register_v: [0; NUM_REGISTER_V],
             register_i: 0,
@@ -55,8 +225,39 @@ impl Chip8 {
             delay_timer: 0,
             sound_timer: 0,
             stack: [0; STACK_SIZE],
+            stack_limit: STACK_SIZE,
             keys: [false; NUM_KEYS],
-            is_debug: false
+            is_debug: false,
+            loaded_rom: None,
+            beeper: Beeper::new(),
+            audio_pattern: None,
+            audio_pitch: DEFAULT_AUDIO_PITCH,
+            audio_recorder: None,
+            hooks: None,
+            is_paused: false,
+            frame_count: 0,
+            instruction_count: 0,
+            draw_count: 0,
+            collision_count: 0,
+            has_drawn_this_frame: false,
+            speed_multiplier: 1.0,
+            speed_carry: 0.0,
+            rewind: RewindBuffer::default(),
+            step_history: StepHistory::default(),
+            replay: ReplayBuffer::default(),
+            trace: TraceBuffer::default(),
+            quirks: Quirks::default(),
+            breakpoints: Breakpoints::default(),
+            dirty: Some(DirtyRegion::FULL),
+            memory_changes: None,
+            heatmap: Heatmap::default(),
+            key_wait_timeout: None,
+            key_wait_frames: 0,
+            is_waiting_for_key: false,
+            key_wait: KeyWait::Idle,
+            stop_reason: None,
+            rng: ChaCha12Rng::seed_from_u64(seed),
+            seed
         };
 
         chip.ram[..FONTSET_SIZE].copy_from_slice(&FONTSET);
@@ -66,8 +267,9 @@ impl Chip8 {
 
     pub fn reset(&mut self) {
         self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
-        self.ram = [0; RAM_SIZE];
-        self.program_counter = START_ADDRESS;
+        self.mark_dirty(DirtyRegion::FULL);
+        self.ram.fill(0);
+        self.program_counter = self.start_address;
         self.register_v = [0; NUM_REGISTER_V];
         self.register_i = 0;
         self.delay_timer = 0;
@@ -75,26 +277,119 @@ impl Chip8 {
         self.stack_pointer = 0;
         self.stack = [0; STACK_SIZE];
         self.keys = [false; NUM_KEYS];
-        self.ram[..FONTSET_SIZE].copy_from_slice(&FONTSET);
+        self.ram[..FONTSET_SIZE].copy_from_slice(&self.font);
+        self.mark_all_memory_changed();
         self.is_debug = false;
+        self.loaded_rom = None;
+        self.audio_pattern = None;
+        self.audio_pitch = DEFAULT_AUDIO_PITCH;
+        self.frame_count = 0;
+        self.key_wait_frames = 0;
+        self.is_waiting_for_key = false;
+        self.key_wait = KeyWait::Idle;
+        self.has_drawn_this_frame = false;
+        self.step_history.clear();
     }
 
-    pub fn load(&mut self, data: &[u8]) {
-        let start = START_ADDRESS as usize;
+    // reset the machine but keep the cartridge in, like pressing the reset button
+    pub fn soft_reset(&mut self) {
+        let loaded_rom = self.loaded_rom.take();
+
+        self.reset();
+
+        if let Some(rom) = loaded_rom {
+            self.copy_to_ram(&rom.bytes);
+            self.loaded_rom = Some(rom);
+        }
+    }
+
+    // a ROM that's empty or doesn't fit after the start address leaves the machine as it was
+    pub fn load(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        self.check_rom(data)?;
+        self.copy_to_ram(data);
+        self.loaded_rom = Some(LoadedRom::new(data, None, self.start_address));
+
+        Ok(())
+    }
+
+    pub fn load_named(&mut self, data: &[u8], source_name: &str) -> Result<(), Chip8Error> {
+        self.check_rom(data)?;
+        self.copy_to_ram(data);
+        self.loaded_rom = Some(LoadedRom::new(data, Some(source_name), self.start_address));
+
+        Ok(())
+    }
+
+    pub fn loaded_rom(&self) -> Option<&LoadedRom> {
+        self.loaded_rom.as_ref()
+    }
+
+    // the error load would give for `rom`, for checking a ROM before there's a machine to load it into
+    pub fn check_rom(&self, rom: &[u8]) -> Result<(), Chip8Error> {
+        let max = self.ram.len() - self.start_address as usize;
+
+        if rom.is_empty() {
+            Err(Chip8Error::EmptyRom)
+        } else if rom.len() > max {
+            Err(Chip8Error::RomTooLarge { size: rom.len(), max })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn copy_to_ram(&mut self, data: &[u8]) {
+        let start = self.start_address as usize;
         let end = start + data.len();
 
         self.ram[start..end].copy_from_slice(data);
+        self.mark_all_memory_changed();
+        self.step_history.clear();
     }
 
     pub fn get_display(&self) -> &[bool] {
         &self.screen
     }
 
+    // None off the screen, coordinates don't wrap here the way DXYN wraps them
+    pub fn pixel(&self, x: usize, y: usize) -> Option<bool> {
+        if x < SCREEN_WIDTH && y < SCREEN_HEIGHT {
+            Some(self.screen[y * SCREEN_WIDTH + x])
+        } else {
+            None
+        }
+    }
+
+    // makes RND repeatable, the same seed and input always play out the same way
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = ChaCha12Rng::seed_from_u64(seed);
+        self.seed = seed;
+        self.step_history.clear();
+    }
+
+    // the last seed given to set_seed, or the random one a new machine starts with
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
     pub fn keypress(&mut self, key_index: usize, is_pressed: bool) {
+        self.record_key(key_index, is_pressed);
+        self.see_key_for_wait(key_index, is_pressed);
         self.keys[key_index] = is_pressed;
     }
 
-    pub fn tick_timers(&mut self) {
+    pub fn set_hooks(&mut self, hooks: Box<dyn Hooks>) {
+        self.hooks = Some(hooks);
+    }
+
+    pub fn take_hooks(&mut self) -> Option<Box<dyn Hooks>> {
+        self.hooks.take()
+    }
+
+    pub fn tick_timers(&mut self) -> FrameResult {
+        self.record_timers();
+        let was_beeping = self.is_beeping();
+        self.has_drawn_this_frame = false;
+
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
@@ -102,31 +397,221 @@ impl Chip8 {
         if self.sound_timer > 0 {
             self.sound_timer -= 1;
         }
+
+        FrameResult { beep: self.beep_edge(was_beeping) }
     }
 
     pub fn is_beeping(&self) -> bool {
         self.sound_timer > 0
     }
 
-    pub fn tick(&mut self) {
-        let opcode = self.fetch();
+    pub fn sound_timer_remaining_frames(&self) -> u8 {
+        self.sound_timer
+    }
 
-        if self.is_debug {
-            self.execute_with_debug(opcode);
-        } else {
-            self.execute(opcode);
+    // one instruction. one that can't run is an error that leaves the machine just before it, with
+    // the program counter on it, so running on hits it again
+    pub fn tick(&mut self) -> Result<TickResult, Chip8Error> {
+        if self.step_history.is_enabled() {
+            self.record_instruction();
+        }
+
+        let was_beeping = self.is_beeping();
+        let pc = self.program_counter;
+        let opcode = self.fetch()?;
+        self.instruction_count += 1;
+
+        if self.trace.is_enabled() {
+            self.trace.push(TraceEntry { pc, opcode });
+        }
+
+        let executed = if self.is_debug { self.execute_with_debug(opcode) } else { self.execute(opcode) };
+
+        if let Err(error) = executed {
+            self.program_counter = pc;
+            return Err(error);
         }
+
+        Ok(TickResult { beep: self.beep_edge(was_beeping) })
+    }
+
+    // runs `instruction` as if it were just fetched, without touching the program counter first
+    pub fn execute_instruction(&mut self, instruction: Instruction) -> Result<(), Chip8Error> {
+        self.step_history.clear();
+        self.execute(instruction.encode())
     }
 
-    fn fetch(&mut self) -> u16 {
-        let higher_byte = self.ram[self.program_counter as usize] as u16;
-        let lower_byte = self.ram[(self.program_counter + 1) as usize] as u16;
+    // one 60 Hz frame: `ticks_per_frame` instructions then a timer tick, does nothing while paused.
+    // with a speed multiplier it runs as many whole frames as the multiplier has built up
+    pub fn run_frame(&mut self, ticks_per_frame: usize) -> FrameResult {
+        if self.is_paused {
+            return FrameResult::default();
+        }
+
+        self.speed_carry += self.speed_multiplier;
+        let frames = self.speed_carry.floor();
+        self.speed_carry -= frames;
+
+        let mut beep = None;
+
+        for _ in 0..frames as usize {
+            beep = self.advance_frame(ticks_per_frame).beep.or(beep);
+
+            // a breakpoint pauses mid-frame
+            if self.is_paused {
+                break;
+            }
+        }
+
+        FrameResult { beep }
+    }
+
+    // like run_frame but ignores pause, for frame-by-frame stepping. an instruction that can't run
+    // pauses the machine with StopReason::Fault
+    pub fn advance_frame(&mut self, ticks_per_frame: usize) -> FrameResult {
+        if self.rewind.is_enabled() {
+            let snapshot = self.snapshot();
+            self.rewind.push(snapshot);
+        }
+
+        let mut beep = None;
+        self.forget_watchpoint_write();
+
+        for _ in 0..ticks_per_frame {
+            if self.should_stop() {
+                self.is_paused = true;
+                break;
+            }
+
+            match self.tick() {
+                Ok(result) => beep = result.beep.or(beep),
+                // unlike a breakpoint the frame doesn't finish, the timers and frame count stay
+                // where the fault left them
+                Err(error) => {
+                    self.stop_reason = Some(StopReason::Fault(error));
+                    self.is_paused = true;
+                    return FrameResult { beep };
+                },
+            }
+
+            if self.has_written_watchpoint() {
+                self.is_paused = true;
+                break;
+            }
+        }
+
+        beep = self.tick_timers().beep.or(beep);
+        self.check_key_wait();
+        self.frame_count += 1;
+        self.record_next_frame();
+        self.record_replay_frame();
+
+        FrameResult { beep }
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    pub fn instruction_count(&self) -> u64 {
+        self.instruction_count
+    }
+
+    pub fn draw_count(&self) -> u64 {
+        self.draw_count
+    }
+
+    // draws that set VF, erasing at least one lit pixel
+    pub fn collision_count(&self) -> u64 {
+        self.collision_count
+    }
+
+    pub fn speed_multiplier(&self) -> f64 {
+        self.speed_multiplier
+    }
+
+    // fast-forward (above 1) or slow motion (below 1), timers speed up with the instructions.
+    // any partial frame built up at the old speed is dropped so 1x is exactly 1x again
+    pub fn set_speed_multiplier(&mut self, multiplier: f64) {
+        assert!(multiplier > 0.0 && multiplier.is_finite(), "speed multiplier must be positive");
+
+        self.speed_multiplier = multiplier;
+        self.speed_carry = 0.0;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
+    pub fn set_paused(&mut self, is_paused: bool) {
+        self.is_paused = is_paused;
+    }
+
+    // read-only views of the machine for debuggers and overlays
+    pub fn registers(&self) -> &[u8] {
+        &self.register_v
+    }
+
+    pub fn register_i(&self) -> u16 {
+        self.register_i
+    }
+
+    pub fn program_counter(&self) -> u16 {
+        self.program_counter
+    }
+
+    pub fn stack_pointer(&self) -> u16 {
+        self.stack_pointer
+    }
+
+    // only the first stack_pointer entries are live
+    pub fn stack(&self) -> &[u16] {
+        &self.stack
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn keys(&self) -> &[bool] {
+        &self.keys
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn beep_edge(&mut self, was_beeping: bool) -> Option<BeepEdge> {
+        let edge = BeepEdge::between(was_beeping, self.is_beeping());
+
+        if edge == Some(BeepEdge::Start) {
+            self.beeper.trigger();
+        }
+
+        if let (Some(edge), Some(hooks)) = (edge, self.hooks.as_mut()) {
+            match edge {
+                BeepEdge::Start => hooks.on_beep_start(),
+                BeepEdge::End => hooks.on_beep_end(),
+            }
+        }
+
+        edge
+    }
+
+    fn fetch(&mut self) -> Result<u16, Chip8Error> {
+        let pc = self.program_counter as usize;
+        let word = self.ram.get(pc..pc + 2).ok_or(Chip8Error::PcOutOfBounds(self.program_counter))?;
+        let opcode = u16::from_be_bytes([word[0], word[1]]);
         self.program_counter += 2;
 
-        (higher_byte << 8) | lower_byte
+        Ok(opcode)
     }
 
-    fn execute(&mut self, opcode: u16) {
+    fn execute(&mut self, opcode: u16) -> Result<(), Chip8Error> {
         let digit1 = (opcode & 0xF000) >> 12;
         let digit2 = (opcode & 0x0F00) >> 8;
         let digit3 = (opcode & 0x00F0) >> 4;
@@ -138,16 +623,15 @@ impl Chip8 {
 
         match (digit1, digit2, digit3, digit4) {
             // NOP
-            (0, 0, 0, 0) => {
-                return
-            },
+            (0, 0, 0, 0) => {},
             // CLS
             (0, 0, 0xE, 0) => {
                 self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+                self.mark_dirty(DirtyRegion::FULL);
             },
             // RET
             (0, 0, 0xE, 0xE) => {
-                let return_address = self.stack_pop();
+                let return_address = self.stack_pop()?;
                 self.program_counter = return_address;
             },
             // JMP NNN
@@ -156,7 +640,7 @@ impl Chip8 {
             },
             // CALL NNN
             (2, _, _, _) => {
-                self.stack_push(self.program_counter);
+                self.stack_push(self.program_counter)?;
                 self.program_counter = nnn;
             },
             // SKIP IF VX == NN
@@ -192,14 +676,17 @@ impl Chip8 {
             // VX |= VY
             (8, _, _, 1) => {
                 self.register_v[x] |= self.register_v[y];
+                self.reset_vf();
             },
             // VX &= VY
             (8, _, _, 2) => {
                 self.register_v[x] &= self.register_v[y];
+                self.reset_vf();
             },
             // VX ^= VY
             (8, _, _, 3) => {
                 self.register_v[x] ^= self.register_v[y];
+                self.reset_vf();
             },
             // VX += VY
             (8, _, _, 4) => {
@@ -217,8 +704,14 @@ impl Chip8 {
             },
             // VX >>= 1
             (8, _, _, 6) => {
-                self.register_v[0xF] = self.register_v[x] & 0x0001;
+                if self.quirks.shift_uses_vy {
+                    self.register_v[x] = self.register_v[y];
+                }
+
+                let flag = self.register_v[x] & 0x01;
+
                 self.register_v[x] >>= 1;
+                self.register_v[0xF] = flag;
             },
             // VX = VY - VX
             (8, _, _, 7) => {
@@ -229,8 +722,14 @@ impl Chip8 {
             },
             // VX <<= 1
             (8, _, _, 0x0E) => {
-                self.register_v[0xF] = (self.register_v[x] >> 7) & 0x01;
+                if self.quirks.shift_uses_vy {
+                    self.register_v[x] = self.register_v[y];
+                }
+
+                let flag = (self.register_v[x] >> 7) & 0x01;
+
                 self.register_v[x] <<= 1;
+                self.register_v[0xF] = flag;
             },
             // SKIP IF VX != VY
             (9, _, _, 0) => {
@@ -244,15 +743,22 @@ impl Chip8 {
             },
             // JMP V0 + NNN
             (0xB, _, _, _) => {
-                self.program_counter = (self.register_v[0] as u16) + nnn;
+                let offset = if self.quirks.jump_uses_vx { self.register_v[x] } else { self.register_v[0] };
+                self.program_counter = offset as u16 + nnn;
             },
             // VX = rand() & NN
             (0xC, _, _, _) => {
-                let rng: u8 = rand::thread_rng().gen();
+                let rng: u8 = self.rng.gen();
                 self.register_v[x] = rng & nn;
             },
             // DRAW
             (0xD, _, _, _) => {
+                self.check_memory(digit4 as usize)?;
+
+                if self.wait_for_display() {
+                    return Ok(());
+                }
+
                 // get the (x, y) coordinates from the sprite
                 let x_coordinate = self.register_v[x] as u16;
                 let y_coordinate = self.register_v[y] as u16;
@@ -266,33 +772,36 @@ impl Chip8 {
                 // interate over each row of the sprite
                 for y_line in 0..num_rows {
                     // determine which memory address the row's data is stored
-                    let address = self.register_i + y_line as u16;
-                    let pixels = self.ram[address as usize];
+                    let address = self.register_i + y_line;
+                    let pixels = self.read_ram(address as usize);
 
 
                     // iterate over each column in the row
                     for x_line in 0..8 {
                         // use a mask to fetch current pixel's bit. Only flip if a 1
                         if (pixels & (0b1000_0000 >> x_line)) != 0 {
-                            // sprites should wrap around screen, so apply modulo
-                            let x = (x_coordinate + x_line) as usize % SCREEN_WIDTH;
-                            let y = (y_coordinate + y_line) as usize % SCREEN_HEIGHT;
+                            let Some((x, y)) = self.sprite_pixel(x_coordinate, y_coordinate, x_line, y_line) else {
+                                continue;
+                            };
 
                             // get the pixel's index in the 1D screen array
                             let index = x + SCREEN_WIDTH * y;
                             // check if we're about to flip the pixel and set
                             flipped |= self.screen[index];
                             self.screen[index] ^= true;
+                            self.mark_dirty(DirtyRegion::pixel(x, y));
                         }
                     }
                 }
 
                 // populate VF register
                 self.register_v[0xF] = flipped as u8;
+                self.draw_count += 1;
+                self.collision_count += flipped as u64;
             },
             // SKIP KEY PRESS
             (0xE, _, 9, 0xE) => {
-                let key = self.keys[self.register_v[x] as usize];
+                let key = self.key(x);
 
                 if key {
                     self.program_counter += 2;
@@ -300,7 +809,7 @@ impl Chip8 {
             },
             // SKIP KEY RELEASE
             (0xE, _, 0xA, 1) => {
-                let key = self.keys[self.register_v[x] as usize];
+                let key = self.key(x);
 
                 if !key {
                     self.program_counter += 2;
@@ -312,20 +821,7 @@ impl Chip8 {
             },
             // WAIT KEY
             (0xF, _, 0, 0xA) => {
-                self.register_v[x] = self.delay_timer;
-                let mut is_pressed = false;
-
-                for i in 0..NUM_KEYS {
-                    if self.keys[i] {
-                        self.register_v[x] = i as u8;
-                        is_pressed = true;
-                        break;
-                    }
-                }
-
-                if !is_pressed {
-                    self.program_counter -= 2;
-                }
+                self.wait_for_key(x);
             },
             // DT = VX
             (0xF, _, 1, 5) => {
@@ -343,8 +839,24 @@ impl Chip8 {
             (0xF, _, 2, 9) => {
                 self.register_i = self.register_v[x] as u16 * 5;
             },
+            // AUDIO PATTERN = [I] (XO-CHIP)
+            (0xF, 0, 0, 2) => {
+                self.check_memory(AUDIO_PATTERN_SIZE)?;
+                let i = self.register_i as usize;
+                let mut pattern = [0; AUDIO_PATTERN_SIZE];
+
+                for (offset, byte) in pattern.iter_mut().enumerate() {
+                    *byte = self.read_ram(i + offset);
+                }
+                self.audio_pattern = Some(pattern);
+            },
+            // PITCH = VX (XO-CHIP)
+            (0xF, _, 3, 0xA) => {
+                self.audio_pitch = self.register_v[x];
+            },
             // BCD
             (0xF, _, 3, 3) => {
+                self.check_memory(3)?;
                 let vx = self.register_v[x] as f32;
 
                 // fetch the hundreds digit by dividing by 100 and tossing the decimal
@@ -355,31 +867,43 @@ impl Chip8 {
                 let ones = (vx % 10.0) as u8;
 
                 let i = self.register_i as usize;
-                self.ram[i] = hundreds;
-                self.ram[i + 1] = tens;
-                self.ram[i + 2] = ones;
+                self.write_ram(i, hundreds);
+                self.write_ram(i + 1, tens);
+                self.write_ram(i + 2, ones);
             },
             // STORE V0 - VX
             (0xF, _, 5, 5) => {
+                self.check_memory(x + 1)?;
                 let i = self.register_i as usize;
 
                 for index in 0..=x {
-                    self.ram[i + index] = self.register_v[index];
+                    self.write_ram(i + index, self.register_v[index]);
+                }
+
+                if self.quirks.memory_increment_i {
+                    self.register_i = self.register_i.wrapping_add(x as u16 + 1);
                 }
             },
             // LOAD V0 - VX
             (0xF, _, 6, 5) => {
+                self.check_memory(x + 1)?;
                 let i = self.register_i as usize;
 
                 for index in 0..=x {
-                    self.register_v[index] = self.ram[i + index];
+                    self.register_v[index] = self.read_ram(i + index);
+                }
+
+                if self.quirks.memory_increment_i {
+                    self.register_i = self.register_i.wrapping_add(x as u16 + 1);
                 }
             },
-            _ => unimplemented!("Unimplemented opcode: {:#04x}", opcode)
+            _ => return Err(Chip8Error::UnknownOpcode { address: self.instruction_address(), opcode }),
         }
+
+        Ok(())
     }
 
-    fn execute_with_debug(&mut self, opcode: u16) {
+    fn execute_with_debug(&mut self, opcode: u16) -> Result<(), Chip8Error> {
         let digit1 = (opcode & 0xF000) >> 12;
         let digit2 = (opcode & 0x0F00) >> 8;
         let digit3 = (opcode & 0x00F0) >> 4;
@@ -393,17 +917,17 @@ impl Chip8 {
             // NOP
             (0, 0, 0, 0) => {
                 println!("{:#04x} NOP", opcode);
-                return
             },
             // CLS
             (0, 0, 0xE, 0) => {
                 println!("{:#04x} CLS", opcode);
                 self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+                self.mark_dirty(DirtyRegion::FULL);
             },
             // RET
             (0, 0, 0xE, 0xE) => {
                 println!("{:#04x} RET", opcode);
-                let return_address = self.stack_pop();
+                let return_address = self.stack_pop()?;
                 self.program_counter = return_address;
             },
             // JMP NNN
@@ -414,7 +938,7 @@ impl Chip8 {
             // CALL NNN
             (2, _, _, _) => {
                 println!("{:#04x} CALL {:#04x}", opcode, nnn);
-                self.stack_push(self.program_counter);
+                self.stack_push(self.program_counter)?;
                 self.program_counter = nnn;
             },
             // SKIP IF VX == NN
@@ -455,18 +979,21 @@ impl Chip8 {
             },
             // VX |= VY
             (8, _, _, 1) => {
-                println!("{:#04x} OR V{}, V{}", opcode, x, y);
+                println!("{:#04x} OR V{}, V{}{}", opcode, x, y, self.vf_reset_note());
                 self.register_v[x] |= self.register_v[y];
+                self.reset_vf();
             },
             // VX &= VY
             (8, _, _, 2) => {
-                println!("{:#04x} AND V{}, V{}", opcode, x, y);
+                println!("{:#04x} AND V{}, V{}{}", opcode, x, y, self.vf_reset_note());
                 self.register_v[x] &= self.register_v[y];
+                self.reset_vf();
             },
             // VX ^= VY
             (8, _, _, 3) => {
-                println!("{:#04x} XOR V{}, V{}", opcode, x, y);
+                println!("{:#04x} XOR V{}, V{}{}", opcode, x, y, self.vf_reset_note());
                 self.register_v[x] ^= self.register_v[y];
+                self.reset_vf();
             },
             // VX += VY
             (8, _, _, 4) => {
@@ -486,9 +1013,17 @@ impl Chip8 {
             },
             // VX >>= 1
             (8, _, _, 6) => {
-                println!("{:#04x} SHR V{}", opcode, x);
-                self.register_v[0xF] = self.register_v[x] & 0x0001;
+                if self.quirks.shift_uses_vy {
+                    println!("{:#04x} SHR V{}, V{} (shift_uses_vy: VY shifted into VX)", opcode, x, y);
+                    self.register_v[x] = self.register_v[y];
+                } else {
+                    println!("{:#04x} SHR V{} (VX shifted in place)", opcode, x);
+                }
+
+                let flag = self.register_v[x] & 0x01;
+
                 self.register_v[x] >>= 1;
+                self.register_v[0xF] = flag;
             },
             // VX = VY - VX
             (8, _, _, 7) => {
@@ -500,9 +1035,17 @@ impl Chip8 {
             },
             // VX <<= 1
             (8, _, _, 0x0E) => {
-                println!("{:#04x} SHL V{}", opcode, x);
-                self.register_v[0xF] = (self.register_v[x] >> 7) & 0x01;
+                if self.quirks.shift_uses_vy {
+                    println!("{:#04x} SHL V{}, V{} (shift_uses_vy: VY shifted into VX)", opcode, x, y);
+                    self.register_v[x] = self.register_v[y];
+                } else {
+                    println!("{:#04x} SHL V{} (VX shifted in place)", opcode, x);
+                }
+
+                let flag = (self.register_v[x] >> 7) & 0x01;
+
                 self.register_v[x] <<= 1;
+                self.register_v[0xF] = flag;
             },
             // SKIP IF VX != VY
             (9, _, _, 0) => {
@@ -518,18 +1061,31 @@ impl Chip8 {
             },
             // JMP V0 + NNN
             (0xB, _, _, _) => {
-                println!("{:#04x} JMP V0, {:#04x}", opcode, nnn);
-                self.program_counter = (self.register_v[0] as u16) + nnn;
+                let offset = if self.quirks.jump_uses_vx {
+                    println!("{:#04x} JMP V{:X}, {:#04x} (jump_uses_vx: VX + XNN)", opcode, x, nnn);
+                    self.register_v[x]
+                } else {
+                    println!("{:#04x} JMP V0, {:#04x}", opcode, nnn);
+                    self.register_v[0]
+                };
+
+                self.program_counter = offset as u16 + nnn;
             },
             // VX = rand() & NN
             (0xC, _, _, _) => {
                 println!("{:#04x} RND V{}, {:#02x}", opcode, x, nn);
-                let rng: u8 = rand::thread_rng().gen();
+                let rng: u8 = self.rng.gen();
                 self.register_v[x] = rng & nn;
             },
             // DRAW
             (0xD, _, _, _) => {
-                println!("{:#04x} DRW V{}, V{}, {:#01x}", opcode, x, y, digit4);
+                println!("{:#04x} DRW V{}, V{}, {:#01x}{}", opcode, x, y, digit4, self.display_wait_note());
+                self.check_memory(digit4 as usize)?;
+
+                if self.wait_for_display() {
+                    return Ok(());
+                }
+
                 // get the (x, y) coordinates from the sprite
                 let x_coordinate = self.register_v[x] as u16;
                 let y_coordinate = self.register_v[y] as u16;
@@ -543,34 +1099,37 @@ impl Chip8 {
                 // interate over each row of the sprite
                 for y_line in 0..num_rows {
                     // determine which memory address the row's data is stored
-                    let address = self.register_i + y_line as u16;
-                    let pixels = self.ram[address as usize];
+                    let address = self.register_i + y_line;
+                    let pixels = self.read_ram(address as usize);
 
 
                     // iterate over each column in the row
                     for x_line in 0..8 {
                         // use a mask to fetch current pixel's bit. Only flip if a 1
                         if (pixels & (0b1000_0000 >> x_line)) != 0 {
-                            // sprites should wrap around screen, so apply modulo
-                            let x = (x_coordinate + x_line) as usize % SCREEN_WIDTH;
-                            let y = (y_coordinate + y_line) as usize % SCREEN_HEIGHT;
+                            let Some((x, y)) = self.sprite_pixel(x_coordinate, y_coordinate, x_line, y_line) else {
+                                continue;
+                            };
 
                             // get the pixel's index in the 1D screen array
                             let index = x + SCREEN_WIDTH * y;
                             // check if we're about to flip the pixel and set
                             flipped |= self.screen[index];
                             self.screen[index] ^= true;
+                            self.mark_dirty(DirtyRegion::pixel(x, y));
                         }
                     }
                 }
 
                 // populate VF register
                 self.register_v[0xF] = flipped as u8;
+                self.draw_count += 1;
+                self.collision_count += flipped as u64;
             },
             // SKIP KEY PRESS
             (0xE, _, 9, 0xE) => {
                 println!("{:#04x} SKP V{}", opcode, x);
-                let key = self.keys[self.register_v[x] as usize];
+                let key = self.key(x);
 
                 if key {
                     self.program_counter += 2;
@@ -579,7 +1138,7 @@ impl Chip8 {
             // SKIP KEY RELEASE
             (0xE, _, 0xA, 1) => {
                 println!("{:#04x} SKNP V{}", opcode, x);
-                let key = self.keys[self.register_v[x] as usize];
+                let key = self.key(x);
 
                 if !key {
                     self.program_counter += 2;
@@ -593,20 +1152,7 @@ impl Chip8 {
             // WAIT KEY
             (0xF, _, 0, 0xA) => {
                 println!("{:#04x} LD V{}, K", opcode, x);
-                self.register_v[x] = self.delay_timer;
-                let mut is_pressed = false;
-
-                for i in 0..NUM_KEYS {
-                    if self.keys[i] {
-                        self.register_v[x] = i as u8;
-                        is_pressed = true;
-                        break;
-                    }
-                }
-
-                if !is_pressed {
-                    self.program_counter -= 2;
-                }
+                self.wait_for_key(x);
             },
             // DT = VX
             (0xF, _, 1, 5) => {
@@ -628,9 +1174,27 @@ impl Chip8 {
                 println!("{:#04x} LD F, V{}", opcode, x);
                 self.register_i = self.register_v[x] as u16 * 5;
             },
+            // AUDIO PATTERN = [I] (XO-CHIP)
+            (0xF, 0, 0, 2) => {
+                println!("{:#04x} AUDIO", opcode);
+                self.check_memory(AUDIO_PATTERN_SIZE)?;
+                let i = self.register_i as usize;
+                let mut pattern = [0; AUDIO_PATTERN_SIZE];
+
+                for (offset, byte) in pattern.iter_mut().enumerate() {
+                    *byte = self.read_ram(i + offset);
+                }
+                self.audio_pattern = Some(pattern);
+            },
+            // PITCH = VX (XO-CHIP)
+            (0xF, _, 3, 0xA) => {
+                println!("{:#04x} PITCH V{}", opcode, x);
+                self.audio_pitch = self.register_v[x];
+            },
             // BCD
             (0xF, _, 3, 3) => {
                 println!("{:#04x} LD B, V{}", opcode, x);
+                self.check_memory(3)?;
                 let vx = self.register_v[x] as f32;
 
                 // fetch the hundreds digit by dividing by 100 and tossing the decimal
@@ -641,40 +1205,572 @@ impl Chip8 {
                 let ones = (vx % 10.0) as u8;
 
                 let i = self.register_i as usize;
-                self.ram[i] = hundreds;
-                self.ram[i + 1] = tens;
-                self.ram[i + 2] = ones;
+                self.write_ram(i, hundreds);
+                self.write_ram(i + 1, tens);
+                self.write_ram(i + 2, ones);
             },
             // STORE V0 - VX
             (0xF, _, 5, 5) => {
                 println!("{:#04x} LD [I], V{}", opcode, x);
+                self.check_memory(x + 1)?;
                 let i = self.register_i as usize;
 
                 for index in 0..=x {
-                    self.ram[i + index] = self.register_v[index];
+                    self.write_ram(i + index, self.register_v[index]);
+                }
+
+                if self.quirks.memory_increment_i {
+                    self.register_i = self.register_i.wrapping_add(x as u16 + 1);
                 }
             },
             // LOAD V0 - VX
             (0xF, _, 6, 5) => {
                 println!("{:#04x} LD V{}, [I]", opcode, x);
+                self.check_memory(x + 1)?;
                 let i = self.register_i as usize;
 
                 for index in 0..=x {
-                    self.register_v[index] = self.ram[i + index];
+                    self.register_v[index] = self.read_ram(i + index);
+                }
+
+                if self.quirks.memory_increment_i {
+                    self.register_i = self.register_i.wrapping_add(x as u16 + 1);
                 }
             },
-            _ => unimplemented!("Unimplemented opcode: {:#04x}", opcode)
+            _ => return Err(Chip8Error::UnknownOpcode { address: self.instruction_address(), opcode }),
+        }
+
+        Ok(())
+    }
+
+    // after 8XY1/8XY2/8XY3, which clear VF on the COSMAC VIP
+    fn reset_vf(&mut self) {
+        if self.quirks.vf_reset {
+            self.register_v[0xF] = 0;
         }
     }
 
-    fn stack_push(&mut self, data: u16) {
+    // the debug output's mention of it
+    fn vf_reset_note(&self) -> &'static str {
+        if self.quirks.vf_reset { " (vf_reset: VF = 0)" } else { "" }
+    }
+
+    // before DXYN draws. with display_wait only the first one in a frame does, like on the COSMAC
+    // VIP where drawing waited for the vertical blank, and a second one runs again until the timers
+    // tick. true when it has to wait
+    fn wait_for_display(&mut self) -> bool {
+        let is_waiting = self.quirks.display_wait && self.has_drawn_this_frame;
+
+        if is_waiting {
+            self.program_counter = self.instruction_address();
+        } else {
+            self.has_drawn_this_frame = true;
+        }
+
+        is_waiting
+    }
+
+    fn display_wait_note(&self) -> &'static str {
+        if self.quirks.display_wait && self.has_drawn_this_frame {
+            " (display_wait: waits for the next frame)"
+        } else {
+            ""
+        }
+    }
+
+    // where the sprite pixel `column`, `row` from (x, y) lands. sprites wrap around the screen, or
+    // with clip_sprites only their start wraps and a pixel past an edge is None
+    fn sprite_pixel(&self, x: u16, y: u16, column: u16, row: u16) -> Option<(usize, usize)> {
+        if !self.quirks.clip_sprites {
+            return Some(((x + column) as usize % SCREEN_WIDTH, (y + row) as usize % SCREEN_HEIGHT));
+        }
+
+        let x = x as usize % SCREEN_WIDTH + column as usize;
+        let y = y as usize % SCREEN_HEIGHT + row as usize;
+
+        (x < SCREEN_WIDTH && y < SCREEN_HEIGHT).then_some((x, y))
+    }
+
+    // where the instruction running was fetched from, execute_instruction's pretends it was
+    fn instruction_address(&self) -> u16 {
+        self.program_counter.wrapping_sub(2)
+    }
+
+    // the `len` bytes from I the instruction running reads or writes, checked before it touches any
+    fn check_memory(&self, len: usize) -> Result<(), Chip8Error> {
+        let end = self.register_i as usize + len;
+
+        if end > self.ram.len() {
+            return Err(Chip8Error::MemoryOutOfBounds { address: self.instruction_address(), target: end - 1 });
+        }
+
+        Ok(())
+    }
+
+    // whether the key in VX is down. only the low nibble picks the key, like the COSMAC VIP
+    fn key(&self, x: usize) -> bool {
+        self.keys[(self.register_v[x] & 0x0F) as usize]
+    }
+
+    fn stack_push(&mut self, data: u16) -> Result<(), Chip8Error> {
+        if self.stack_pointer as usize >= self.stack_limit {
+            return Err(Chip8Error::StackOverflow(self.instruction_address()));
+        }
+
         self.stack[self.stack_pointer as usize] = data;
         self.stack_pointer += 1;
+
+        Ok(())
     }
 
-    fn stack_pop(&mut self) -> u16 {
+    fn stack_pop(&mut self) -> Result<u16, Chip8Error> {
+        if self.stack_pointer == 0 {
+            return Err(Chip8Error::StackUnderflow(self.instruction_address()));
+        }
+
         self.stack_pointer -= 1;
 
-        self.stack[self.stack_pointer as usize]
+        Ok(self.stack[self.stack_pointer as usize])
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROM: [u8; 6] = [0x60, 0x2A, 0x70, 0x01, 0x12, 0x02];
+
+    #[test]
+    fn load_captures_rom_metadata() {
+        let mut chip8 = Chip8::new();
+        chip8.load_named(&ROM, "count.ch8").unwrap();
+
+        let rom = chip8.loaded_rom().unwrap();
+        assert_eq!(rom.bytes, ROM);
+        assert_eq!(rom.size, ROM.len());
+        assert_eq!(rom.source_name.as_deref(), Some("count.ch8"));
+        assert_eq!(rom.loaded_at_address, START_ADDRESS);
+        assert_eq!(rom.sha256_hex().len(), 64);
+        assert_eq!(rom.sha256, LoadedRom::new(&ROM, None, START_ADDRESS).sha256);
+    }
+
+    #[test]
+    fn reads_single_pixels() {
+        let mut chip8 = Chip8::new();
+        // draw "0" at (0, 0)
+        chip8.load(&[0xD0, 0x05]).unwrap();
+        chip8.tick().unwrap();
+
+        assert_eq!(chip8.pixel(0, 0), Some(true));
+        assert_eq!(chip8.pixel(1, 1), Some(false));
+        assert_eq!(chip8.pixel(3, 4), Some(true));
+        assert_eq!(chip8.pixel(SCREEN_WIDTH - 1, SCREEN_HEIGHT - 1), Some(false));
+        assert_eq!(chip8.pixel(SCREEN_WIDTH, 0), None);
+        assert_eq!(chip8.pixel(0, SCREEN_HEIGHT), None);
+    }
+
+    #[test]
+    fn new_with_rom_loads_what_fits() {
+        let chip8 = Chip8::new_with_rom(&ROM).unwrap();
+        assert_eq!(chip8.loaded_rom().unwrap().bytes, ROM);
+        assert_eq!(&chip8.memory()[START_ADDRESS as usize..][..ROM.len()], &ROM);
+
+        assert_eq!(Chip8::new_with_rom(&[]).err(), Some(Chip8Error::EmptyRom));
+        assert_eq!(
+            Chip8::new_with_rom(&[0; MAX_ROM_SIZE + 1]).err(),
+            Some(Chip8Error::RomTooLarge { size: MAX_ROM_SIZE + 1, max: MAX_ROM_SIZE })
+        );
+        assert!(Chip8::new_with_rom(&[0; MAX_ROM_SIZE]).is_ok());
+    }
+
+    #[test]
+    fn load_checks_the_size() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ROM).unwrap();
+
+        assert_eq!(MAX_ROM_SIZE, 3584);
+        assert_eq!(chip8.load(&[]), Err(Chip8Error::EmptyRom));
+        assert_eq!(
+            chip8.load_named(&[0x12; MAX_ROM_SIZE + 1], "game.zip"),
+            Err(Chip8Error::RomTooLarge { size: MAX_ROM_SIZE + 1, max: MAX_ROM_SIZE })
+        );
+        // what was loaded stays
+        assert_eq!(chip8.loaded_rom().unwrap().bytes, ROM);
+        assert_eq!(chip8.memory()[START_ADDRESS as usize + ROM.len()], 0);
+
+        chip8.load(&[0x12; MAX_ROM_SIZE]).unwrap();
+        assert_eq!(chip8.memory()[RAM_SIZE - 1], 0x12);
+    }
+
+    #[test]
+    fn from_path_reads_the_rom() {
+        let dir = std::env::temp_dir().join(format!("chip8-emu-from-path-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("count.ch8");
+        fs::write(&path, ROM).unwrap();
+
+        let chip8 = Chip8::from_path(&path).unwrap();
+        assert_eq!(chip8.loaded_rom().unwrap().bytes, ROM);
+        assert_eq!(chip8.loaded_rom().unwrap().source_name, Some(path.display().to_string()));
+
+        fs::write(&path, [0; MAX_ROM_SIZE + 1]).unwrap();
+        assert!(matches!(Chip8::from_path(&path), Err(Chip8Error::RomTooLarge { .. })));
+
+        let missing = dir.join("missing.ch8");
+        let error = Chip8::from_path(&missing).err().unwrap();
+        assert!(matches!(error, Chip8Error::Io { .. }));
+        assert!(error.to_string().contains("missing.ch8"), "{}", error);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // found by fuzz/, the shifted VF used to overwrite its own flag
+    #[test]
+    fn shifting_vf_leaves_the_flag() {
+        let mut chip8 = Chip8::new();
+
+        chip8.set_register(0xF, 0xC0);
+        chip8.execute_instruction(Instruction::ShiftLeft(0xF, 0)).unwrap();
+        assert_eq!(chip8.registers()[0xF], 1);
+
+        chip8.set_register(0xF, 0x03);
+        chip8.execute_instruction(Instruction::ShiftRight(0xF, 0)).unwrap();
+        assert_eq!(chip8.registers()[0xF], 1);
+    }
+
+    #[test]
+    fn instructions_that_cant_run_are_errors() {
+        let fault = |rom: &[u8], ticks| {
+            let mut chip8 = Chip8::new();
+            chip8.load(rom).unwrap();
+            for _ in 0..ticks {
+                chip8.tick().unwrap();
+            }
+
+            let error = chip8.tick().err();
+            // left just before the instruction, running it again fails the same way
+            assert_eq!(chip8.program_counter(), 0x200 + 2 * ticks as u16);
+            assert_eq!(chip8.tick().err(), error);
+            error.unwrap()
+        };
+
+        // I = 0xFFE, store V0 to V3
+        assert_eq!(
+            fault(&[0xAF, 0xFE, 0xF3, 0x55], 1),
+            Chip8Error::MemoryOutOfBounds { address: 0x202, target: 0x1001 }
+        );
+        assert_eq!(fault(&[0xF0, 0xFF], 0), Chip8Error::UnknownOpcode { address: 0x200, opcode: 0xF0FF });
+
+        let mut chip8 = Chip8::new();
+        chip8.set_program_counter(0xFFF);
+        assert_eq!(chip8.tick().err(), Some(Chip8Error::PcOutOfBounds(0xFFF)));
+    }
+
+    #[test]
+    fn bcd_store_and_load_stop_at_the_end_of_ram() {
+        for is_debug in [false, true] {
+            for opcode in [0x33, 0x55, 0x65] {
+                for i in [0xFFDu16, 0xFFE, 0xFFF] {
+                    // V0 to V2 = 0xFF, 0x01, 0x02, I = i, then BCD of V2 or a store or load of V0 to V2
+                    let [high, low] = (0xA000 | i).to_be_bytes();
+                    let rom = [0x60, 0xFF, 0x61, 0x01, 0x62, 0x02, high, low, 0xF2, opcode];
+                    let mut chip8 = Chip8::new();
+                    chip8.load(&rom).unwrap();
+                    chip8.is_debug = is_debug;
+                    chip8.set_trace_capacity(10);
+                    chip8.ram[0xFFD..].copy_from_slice(&[0xAA, 0xBB, 0xCC]);
+                    for _ in 0..4 {
+                        chip8.tick().unwrap();
+                    }
+
+                    let ran = chip8.tick();
+                    // the instruction is traced whether it runs or not
+                    assert_eq!(chip8.trace().last(), Some(&TraceEntry { pc: 0x208, opcode: 0xF200 | opcode as u16 }));
+
+                    if i == 0xFFD {
+                        assert!(ran.is_ok());
+                        continue;
+                    }
+
+                    // three bytes from I reach past 0xFFF, nothing is read or written
+                    let target = i as usize + 2;
+                    assert_eq!(ran.err(), Some(Chip8Error::MemoryOutOfBounds { address: 0x208, target }));
+                    assert_eq!(chip8.ram[0xFFD..], [0xAA, 0xBB, 0xCC]);
+                    assert_eq!(chip8.registers()[..3], [0xFF, 0x01, 0x02]);
+                    assert_eq!((chip8.program_counter(), chip8.register_i), (0x208, i));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn key_skips_only_look_at_the_low_nibble() {
+        for is_debug in [false, true] {
+            // V4 = 0x4A, skip if key V4 is down, skip if it's up
+            let mut chip8 = Chip8::new();
+            chip8.load(&[0x64, 0x4A, 0xE4, 0x9E, 0x00, 0x00, 0xE4, 0xA1]).unwrap();
+            chip8.is_debug = is_debug;
+            chip8.keypress(0xA, true);
+
+            chip8.tick().unwrap();
+            chip8.tick().unwrap();
+            assert_eq!(chip8.program_counter(), 0x206);
+
+            chip8.tick().unwrap();
+            assert_eq!(chip8.program_counter(), 0x208);
+
+            chip8.keypress(0xA, false);
+            chip8.set_program_counter(0x202);
+            chip8.tick().unwrap();
+            assert_eq!(chip8.program_counter(), 0x204);
+        }
+    }
+
+    #[test]
+    fn running_off_the_end_of_ram_is_an_error() {
+        // a full-size ROM of jumps, each to the next, with the last one at 0xFFE
+        let jumps = |last: u16| -> Vec<u8> {
+            let mut rom: Vec<u8> = (0x202..=0xFFE).step_by(2).flat_map(|to: u16| (0x1000 | to).to_be_bytes()).collect();
+            rom.extend((0x1000 | last).to_be_bytes());
+            rom
+        };
+        let run = |rom: &[u8]| {
+            let mut chip8 = Chip8::new();
+            chip8.load(rom).unwrap();
+            for _ in 0..10 {
+                chip8.run_frame(200);
+            }
+            (chip8.program_counter(), chip8.take_fault())
+        };
+
+        // into the last byte, where half an instruction fits
+        assert_eq!(run(&jumps(0xFFF)), (0xFFF, Some(Chip8Error::PcOutOfBounds(0xFFF))));
+        // onto itself, which never leaves RAM
+        assert_eq!(run(&jumps(0xFFE)), (0xFFE, None));
+
+        // past the last instruction, a skip at 0xFFE and a NOP there
+        let mut rom = jumps(0xFFE);
+        rom[MAX_ROM_SIZE - 2..].copy_from_slice(&[0x30, 0x00]);
+        assert_eq!(run(&rom), (0x1002, Some(Chip8Error::PcOutOfBounds(0x1002))));
+        rom[MAX_ROM_SIZE - 2..].copy_from_slice(&[0x00, 0x00]);
+        assert_eq!(run(&rom), (0x1000, Some(Chip8Error::PcOutOfBounds(0x1000))));
+
+        // and BNNN jumps past RAM altogether
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0x60, 0xFF, 0xBF, 0xFF]).unwrap();
+        chip8.tick().unwrap();
+        chip8.tick().unwrap();
+        assert_eq!(chip8.tick().err(), Some(Chip8Error::PcOutOfBounds(0x10FE)));
+    }
+
+    #[test]
+    fn seventeen_nested_calls_overflow_the_stack() {
+        // each CALL goes to the next one, the 17th has no room left
+        let rom: Vec<u8> = (1..=17u16).flat_map(|call| (0x2200 + 2 * call).to_be_bytes()).collect();
+        let mut chip8 = Chip8::new();
+        chip8.load(&rom).unwrap();
+
+        for _ in 0..16 {
+            chip8.tick().unwrap();
+        }
+
+        assert_eq!(chip8.tick().err(), Some(Chip8Error::StackOverflow(0x220)));
+        // the 16 return addresses are all still there
+        let returns: Vec<u16> = (1..=16).map(|call| 0x200 + 2 * call).collect();
+        assert_eq!((chip8.program_counter(), chip8.stack_pointer()), (0x220, 16));
+        assert_eq!(chip8.stack().to_vec(), returns);
+    }
+
+    #[test]
+    fn a_bare_return_underflows_the_stack() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0x00, 0xEE]).unwrap();
+
+        assert_eq!(chip8.tick().err(), Some(Chip8Error::StackUnderflow(0x200)));
+        assert_eq!((chip8.program_counter(), chip8.stack_pointer()), (0x200, 0));
+        assert_eq!(chip8.stack(), &[0; STACK_SIZE]);
+    }
+
+    #[test]
+    fn a_fault_pauses_the_frame() {
+        // V0 = 5, DT = V0, then return with an empty stack
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0x60, 0x05, 0xF0, 0x15, 0x00, 0xEE]).unwrap();
+
+        chip8.run_frame(10);
+        assert!(chip8.is_paused());
+        assert_eq!(chip8.program_counter(), 0x204);
+        assert_eq!((chip8.delay_timer(), chip8.frame_count()), (5, 0));
+        assert_eq!(chip8.take_fault(), Some(Chip8Error::StackUnderflow(0x204)));
+        assert_eq!(chip8.take_stop_reason(), None);
+
+        // paused, so the next frame doesn't run it again
+        chip8.run_frame(10);
+        assert_eq!(chip8.take_fault(), None);
+    }
+
+    #[test]
+    fn soft_reset_reloads_the_rom() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ROM).unwrap();
+
+        for _ in 0..10 {
+            chip8.tick().unwrap();
+        }
+
+        // clobber the program so the reload is observable
+        chip8.ram[START_ADDRESS as usize] = 0xFF;
+        chip8.soft_reset();
+
+        let start = START_ADDRESS as usize;
+        assert_eq!(&chip8.ram[start..start + ROM.len()], &ROM);
+        assert_eq!(chip8.program_counter, START_ADDRESS);
+        assert_eq!(chip8.register_v, [0; NUM_REGISTER_V]);
+        assert!(chip8.loaded_rom().is_some());
+    }
+
+    #[derive(Clone, Default)]
+    struct BeepLog(std::sync::Arc<std::sync::Mutex<Vec<BeepEdge>>>);
+
+    impl Hooks for BeepLog {
+        fn on_beep_start(&mut self) {
+            self.0.lock().unwrap().push(BeepEdge::Start);
+        }
+
+        fn on_beep_end(&mut self) {
+            self.0.lock().unwrap().push(BeepEdge::End);
+        }
+    }
+
+    #[test]
+    fn reports_beep_edges() {
+        let log = BeepLog::default();
+        let mut chip8 = Chip8::new();
+        chip8.set_hooks(Box::new(log.clone()));
+        // V0 = 3, ST = V0, spin
+        chip8.load(&[0x60, 0x03, 0xF0, 0x18, 0x12, 0x04]).unwrap();
+
+        assert_eq!(chip8.tick().unwrap().beep, None);
+        assert_eq!(chip8.tick().unwrap().beep, Some(BeepEdge::Start));
+        assert_eq!(chip8.sound_timer_remaining_frames(), 3);
+        assert_eq!(chip8.tick().unwrap().beep, None);
+
+        assert_eq!(chip8.tick_timers().beep, None);
+        assert_eq!(chip8.tick_timers().beep, None);
+        assert_eq!(chip8.tick_timers().beep, Some(BeepEdge::End));
+        assert_eq!(chip8.tick_timers().beep, None);
+
+        assert_eq!(*log.0.lock().unwrap(), vec![BeepEdge::Start, BeepEdge::End]);
+    }
+
+    #[test]
+    fn pause_freezes_run_frame() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ROM).unwrap();
+
+        chip8.run_frame(2);
+        assert_eq!(chip8.frame_count(), 1);
+        assert_eq!(chip8.register_v[0], 0x2B);
+
+        chip8.set_paused(true);
+        chip8.run_frame(2);
+        assert_eq!(chip8.frame_count(), 1);
+        assert_eq!(chip8.register_v[0], 0x2B);
+
+        chip8.advance_frame(2);
+        assert_eq!(chip8.frame_count(), 2);
+        assert_eq!(chip8.register_v[0], 0x2C);
+        assert!(chip8.is_paused());
+    }
+
+    #[test]
+    fn counts_draws_and_collisions() {
+        // draw "0" three times at (0, 0), the second erases the first
+        let mut chip8 = Chip8::new();
+        chip8.load(&[0xD0, 0x05, 0xD0, 0x05, 0xD0, 0x05]).unwrap();
+
+        chip8.tick().unwrap();
+        assert_eq!((chip8.draw_count(), chip8.collision_count()), (1, 0));
+        chip8.tick().unwrap();
+        chip8.tick().unwrap();
+        assert_eq!((chip8.draw_count(), chip8.collision_count()), (3, 1));
+    }
+
+    #[test]
+    fn speed_multiplier_scales_instructions_and_timers() {
+        let mut normal = Chip8::new();
+        normal.load(&ROM).unwrap();
+
+        let mut turbo = Chip8::new();
+        turbo.load(&ROM).unwrap();
+        turbo.set_speed_multiplier(8.0);
+
+        for _ in 0..30 {
+            normal.run_frame(10);
+            turbo.run_frame(10);
+        }
+
+        assert_eq!(turbo.frame_count(), 8 * normal.frame_count());
+        assert_eq!(turbo.instruction_count(), 8 * normal.instruction_count());
+
+        // back to 1x without drift
+        turbo.set_speed_multiplier(1.0);
+        let frames = turbo.frame_count();
+        turbo.run_frame(10);
+        assert_eq!(turbo.frame_count(), frames + 1);
+    }
+
+    #[test]
+    fn fractional_speeds_carry_between_frames() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ROM).unwrap();
+        chip8.set_speed_multiplier(0.25);
+
+        for _ in 0..8 {
+            chip8.run_frame(10);
+        }
+
+        assert_eq!(chip8.frame_count(), 2);
+    }
+
+    #[test]
+    fn run_frame_reports_beeps_from_any_tick() {
+        let mut chip8 = Chip8::new();
+        // V0 = 3, ST = V0, spin
+        chip8.load(&[0x60, 0x03, 0xF0, 0x18, 0x12, 0x04]).unwrap();
+
+        assert_eq!(chip8.run_frame(3).beep, Some(BeepEdge::Start));
+        assert_eq!(chip8.run_frame(3).beep, None);
+        assert_eq!(chip8.run_frame(3).beep, Some(BeepEdge::End));
+    }
+
+    #[test]
+    fn reset_ejects_the_rom() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ROM).unwrap();
+        chip8.reset();
+
+        assert!(chip8.loaded_rom().is_none());
+    }
+
+    #[test]
+    fn seeds_make_random_numbers_repeatable() {
+        // V0..V7 = random bytes
+        let rom: Vec<u8> = (0..8).flat_map(|x| [0xC0 | x, 0xFF]).collect();
+        let run = |seed| {
+            let mut chip8 = Chip8::new();
+            chip8.load(&rom).unwrap();
+            chip8.set_seed(seed);
+            chip8.run_frame(8);
+            chip8.registers()[..8].to_vec()
+        };
+
+        assert_eq!(run(42), run(42));
+        assert_ne!(run(42), run(43));
+
+        let mut chip8 = Chip8::new();
+        chip8.set_seed(42);
+        assert_eq!(chip8.seed(), 42);
+        assert_ne!(Chip8::new().seed(), Chip8::new().seed());
+    }
+}
@@ -0,0 +1,745 @@
+// a libretro core, so RetroArch and friends can run the emulator with their shaders, remapping
+// and save states. the cdylib built with `--no-default-features --features libretro` is the core,
+// install it as chip8_emu_libretro.so.
+//
+// the C side only moves data between the frontend's callbacks and Core, which sees the frontend
+// through the Frontend trait. that keeps the core testable without a frontend. only the part of
+// libretro.h the core uses is declared here
+
+use crate::{Chip8, Palette, Quirks, MAX_STATE_SIZE, PALETTES, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use std::ffi::{c_char, c_uint, c_void, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+use std::sync::Mutex;
+
+const RETRO_API_VERSION: c_uint = 1;
+const RETRO_REGION_NTSC: c_uint = 0;
+
+const RETRO_ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+const RETRO_ENVIRONMENT_GET_VARIABLE: c_uint = 15;
+const RETRO_ENVIRONMENT_SET_VARIABLES: c_uint = 16;
+const RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE: c_uint = 17;
+const RETRO_ENVIRONMENT_SET_GEOMETRY: c_uint = 37;
+
+const RETRO_PIXEL_FORMAT_XRGB8888: c_uint = 1;
+
+const RETRO_DEVICE_JOYPAD: c_uint = 1;
+const RETRO_DEVICE_KEYBOARD: c_uint = 3;
+
+const RETRO_DEVICE_ID_JOYPAD_B: c_uint = 0;
+const RETRO_DEVICE_ID_JOYPAD_Y: c_uint = 1;
+const RETRO_DEVICE_ID_JOYPAD_SELECT: c_uint = 2;
+const RETRO_DEVICE_ID_JOYPAD_START: c_uint = 3;
+const RETRO_DEVICE_ID_JOYPAD_UP: c_uint = 4;
+const RETRO_DEVICE_ID_JOYPAD_DOWN: c_uint = 5;
+const RETRO_DEVICE_ID_JOYPAD_LEFT: c_uint = 6;
+const RETRO_DEVICE_ID_JOYPAD_RIGHT: c_uint = 7;
+const RETRO_DEVICE_ID_JOYPAD_A: c_uint = 8;
+const RETRO_DEVICE_ID_JOYPAD_X: c_uint = 9;
+
+const FPS: f64 = 60.0;
+const SAMPLE_RATE: u32 = 44100;
+const SAMPLES_PER_FRAME: usize = SAMPLE_RATE as usize / FPS as usize;
+
+// RetroPad to keypad, the d-pad is the 2/4/6/8 cross most games steer with. the rest is left to
+// the frontend's remapping
+const JOYPAD: [(c_uint, usize); 10] = [
+    (RETRO_DEVICE_ID_JOYPAD_UP, 0x2),
+    (RETRO_DEVICE_ID_JOYPAD_DOWN, 0x8),
+    (RETRO_DEVICE_ID_JOYPAD_LEFT, 0x4),
+    (RETRO_DEVICE_ID_JOYPAD_RIGHT, 0x6),
+    (RETRO_DEVICE_ID_JOYPAD_A, 0x5),
+    (RETRO_DEVICE_ID_JOYPAD_B, 0x0),
+    (RETRO_DEVICE_ID_JOYPAD_X, 0xE),
+    (RETRO_DEVICE_ID_JOYPAD_Y, 0xF),
+    (RETRO_DEVICE_ID_JOYPAD_SELECT, 0xA),
+    (RETRO_DEVICE_ID_JOYPAD_START, 0xB),
+];
+
+// the desktop's keypad block, RETROK codes are ASCII for letters and digits
+const KEYBOARD: &[u8; 16] = b"x123qweasdzc4rfv";
+
+const SPEED: &str = "chip8_emu_speed";
+const QUIRKS: &str = "chip8_emu_quirks";
+const PALETTE: &str = "chip8_emu_palette";
+
+// key, then "Description; default|other|values" the way SET_VARIABLES wants them
+fn variables() -> Vec<(&'static str, String)> {
+    let palettes: Vec<&str> = PALETTES.iter().map(|&(name, _)| name).collect();
+
+    vec![
+        (SPEED, "Instructions per frame; 10|5|15|20|30|50|100|200".to_string()),
+        (QUIRKS, "Quirk preset; modern|cosmac_vip".to_string()),
+        (PALETTE, format!("Palette; {}", palettes.join("|"))),
+    ]
+}
+
+fn quirk_preset(name: &str) -> Quirks {
+    match name {
+        "cosmac_vip" => Quirks::preset("chip8").unwrap_or_default(),
+        _ => Quirks::default(),
+    }
+}
+
+trait Frontend {
+    // the current value of a core option
+    fn variable(&mut self, key: &str) -> Option<String>;
+
+    fn have_variables_changed(&mut self) -> bool;
+
+    fn set_geometry(&mut self, width: usize, height: usize);
+
+    // XRGB8888, `width` pixels a row with no padding
+    fn video(&mut self, pixels: &[u32], width: usize, height: usize);
+
+    // interleaved stereo
+    fn audio(&mut self, samples: &[i16]);
+
+    fn poll_input(&mut self);
+
+    fn is_pressed(&mut self, device: c_uint, id: c_uint) -> bool;
+}
+
+struct Core {
+    chip8: Chip8,
+    ticks_per_frame: usize,
+    palette: Palette,
+    // what the frontend was last told, by av info or SET_GEOMETRY
+    geometry: (usize, usize),
+    has_crashed: bool,
+    // reused every frame
+    pixels: Vec<u32>,
+    mono: Vec<f32>,
+    stereo: Vec<i16>,
+}
+
+impl Core {
+    fn new(rom: &[u8], frontend: &mut impl Frontend) -> Result<Self, String> {
+        let mut chip8 = Chip8::new();
+        chip8.load(rom).map_err(|error| format!("Unable to load the ROM: {}", error))?;
+
+        let mut core = Self {
+            chip8,
+            ticks_per_frame: 10,
+            palette: Palette::default(),
+            geometry: (SCREEN_WIDTH, SCREEN_HEIGHT),
+            has_crashed: false,
+            pixels: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            mono: vec![0.0; SAMPLES_PER_FRAME],
+            stereo: vec![0; SAMPLES_PER_FRAME * 2],
+        };
+        core.read_variables(frontend);
+
+        Ok(core)
+    }
+
+    // unknown values keep what was there
+    fn read_variables(&mut self, frontend: &mut impl Frontend) {
+        if let Some(speed) = frontend.variable(SPEED).and_then(|speed| speed.parse().ok()) {
+            self.ticks_per_frame = speed;
+        }
+
+        if let Some(preset) = frontend.variable(QUIRKS) {
+            self.chip8.set_quirks(quirk_preset(&preset));
+        }
+
+        if let Some(palette) = frontend.variable(PALETTE).and_then(|name| Palette::named(&name)) {
+            self.palette = palette;
+        }
+    }
+
+    // there's no hi-res mode yet, so this only ever matches what av info said
+    fn resolution(&self) -> (usize, usize) {
+        (SCREEN_WIDTH, SCREEN_HEIGHT)
+    }
+
+    fn run(&mut self, frontend: &mut impl Frontend) {
+        if frontend.have_variables_changed() {
+            self.read_variables(frontend);
+        }
+
+        frontend.poll_input();
+        for (key, &letter) in KEYBOARD.iter().enumerate() {
+            let is_joypad = JOYPAD.iter().any(|&(id, mapped)| mapped == key && frontend.is_pressed(RETRO_DEVICE_JOYPAD, id));
+            let is_keyboard = frontend.is_pressed(RETRO_DEVICE_KEYBOARD, letter as c_uint);
+            self.chip8.keypress(key, is_joypad || is_keyboard);
+        }
+
+        // a crashed program keeps showing its last frame, reset or a new game gets going again
+        if !self.has_crashed {
+            let chip8 = &mut self.chip8;
+            let ticks_per_frame = self.ticks_per_frame;
+            let run = panic::catch_unwind(AssertUnwindSafe(|| chip8.run_frame(ticks_per_frame)));
+            self.has_crashed = run.is_err() || chip8.take_fault().is_some();
+        }
+
+        let resolution = self.resolution();
+        if resolution != self.geometry {
+            frontend.set_geometry(resolution.0, resolution.1);
+            self.geometry = resolution;
+        }
+
+        let colour = |(r, g, b): (u8, u8, u8)| u32::from_be_bytes([0, r, g, b]);
+        let (background, foreground) = (colour(self.palette.background), colour(self.palette.foreground));
+        for (pixel, &is_on) in self.pixels.iter_mut().zip(self.chip8.get_display()) {
+            *pixel = if is_on { foreground } else { background };
+        }
+        frontend.video(&self.pixels, resolution.0, resolution.1);
+
+        self.chip8.fill_audio(&mut self.mono, SAMPLE_RATE);
+        for (pair, &sample) in self.stereo.chunks_exact_mut(2).zip(&self.mono) {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            pair.copy_from_slice(&[sample, sample]);
+        }
+        frontend.audio(&self.stereo);
+    }
+
+    fn reset(&mut self) {
+        self.chip8.soft_reset();
+        self.has_crashed = false;
+    }
+
+    // frontends want the same size every time, states are padded out to the biggest
+    fn serialize_size(&self) -> usize {
+        MAX_STATE_SIZE
+    }
+
+    fn serialize(&self, out: &mut [u8]) -> bool {
+        let state = self.chip8.save_state();
+
+        if out.len() < state.len() {
+            return false;
+        }
+
+        out[..state.len()].copy_from_slice(&state);
+        out[state.len()..].fill(0);
+
+        true
+    }
+
+    fn unserialize(&mut self, data: &[u8]) -> bool {
+        let is_loaded = self.chip8.load_state(data).is_ok();
+        if is_loaded {
+            self.has_crashed = false;
+        }
+
+        is_loaded
+    }
+}
+
+#[repr(C)]
+pub struct RetroSystemInfo {
+    library_name: *const c_char,
+    library_version: *const c_char,
+    valid_extensions: *const c_char,
+    need_fullpath: bool,
+    block_extract: bool,
+}
+
+#[repr(C)]
+pub struct RetroGameGeometry {
+    base_width: c_uint,
+    base_height: c_uint,
+    max_width: c_uint,
+    max_height: c_uint,
+    aspect_ratio: f32,
+}
+
+#[repr(C)]
+pub struct RetroSystemTiming {
+    fps: f64,
+    sample_rate: f64,
+}
+
+#[repr(C)]
+pub struct RetroSystemAvInfo {
+    geometry: RetroGameGeometry,
+    timing: RetroSystemTiming,
+}
+
+#[repr(C)]
+pub struct RetroVariable {
+    key: *const c_char,
+    value: *const c_char,
+}
+
+#[repr(C)]
+pub struct RetroGameInfo {
+    path: *const c_char,
+    data: *const c_void,
+    size: usize,
+    meta: *const c_char,
+}
+
+pub type RetroEnvironment = unsafe extern "C" fn(cmd: c_uint, data: *mut c_void) -> bool;
+pub type RetroVideoRefresh = unsafe extern "C" fn(data: *const c_void, width: c_uint, height: c_uint, pitch: usize);
+pub type RetroAudioSample = unsafe extern "C" fn(left: i16, right: i16);
+pub type RetroAudioSampleBatch = unsafe extern "C" fn(data: *const i16, frames: usize) -> usize;
+pub type RetroInputPoll = unsafe extern "C" fn();
+pub type RetroInputState = unsafe extern "C" fn(port: c_uint, device: c_uint, index: c_uint, id: c_uint) -> i16;
+
+// the frontend's callbacks, any of them can be missing until it sets them
+#[derive(Clone, Copy)]
+struct Callbacks {
+    environment: Option<RetroEnvironment>,
+    video_refresh: Option<RetroVideoRefresh>,
+    audio_sample_batch: Option<RetroAudioSampleBatch>,
+    input_poll: Option<RetroInputPoll>,
+    input_state: Option<RetroInputState>,
+}
+
+impl Frontend for Callbacks {
+    fn variable(&mut self, key: &str) -> Option<String> {
+        let environment = self.environment?;
+        let key = CString::new(key).ok()?;
+        let mut variable = RetroVariable { key: key.as_ptr(), value: ptr::null() };
+
+        unsafe {
+            if !environment(RETRO_ENVIRONMENT_GET_VARIABLE, &mut variable as *mut _ as *mut c_void) || variable.value.is_null() {
+                return None;
+            }
+
+            Some(CStr::from_ptr(variable.value).to_string_lossy().into_owned())
+        }
+    }
+
+    fn have_variables_changed(&mut self) -> bool {
+        let mut has_changed = false;
+
+        match self.environment {
+            Some(environment) => unsafe {
+                environment(RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE, &mut has_changed as *mut _ as *mut c_void) && has_changed
+            },
+            None => false,
+        }
+    }
+
+    fn set_geometry(&mut self, width: usize, height: usize) {
+        let mut geometry = geometry(width, height);
+
+        if let Some(environment) = self.environment {
+            unsafe { environment(RETRO_ENVIRONMENT_SET_GEOMETRY, &mut geometry as *mut _ as *mut c_void) };
+        }
+    }
+
+    fn video(&mut self, pixels: &[u32], width: usize, height: usize) {
+        if let Some(video_refresh) = self.video_refresh {
+            unsafe { video_refresh(pixels.as_ptr() as *const c_void, width as c_uint, height as c_uint, width * 4) };
+        }
+    }
+
+    fn audio(&mut self, samples: &[i16]) {
+        if let Some(audio_sample_batch) = self.audio_sample_batch {
+            unsafe { audio_sample_batch(samples.as_ptr(), samples.len() / 2) };
+        }
+    }
+
+    fn poll_input(&mut self) {
+        if let Some(input_poll) = self.input_poll {
+            unsafe { input_poll() };
+        }
+    }
+
+    fn is_pressed(&mut self, device: c_uint, id: c_uint) -> bool {
+        match self.input_state {
+            Some(input_state) => unsafe { input_state(0, device, 0, id) != 0 },
+            None => false,
+        }
+    }
+}
+
+struct Glue {
+    callbacks: Callbacks,
+    core: Option<Core>,
+}
+
+static GLUE: Mutex<Glue> = Mutex::new(Glue {
+    callbacks: Callbacks {
+        environment: None,
+        video_refresh: None,
+        audio_sample_batch: None,
+        input_poll: None,
+        input_state: None,
+    },
+    core: None,
+});
+
+fn with_glue<T>(body: impl FnOnce(&mut Glue) -> T) -> T {
+    // nothing panics with the lock held, Core::run catches the emulator's own
+    let mut glue = GLUE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    body(&mut glue)
+}
+
+fn geometry(width: usize, height: usize) -> RetroGameGeometry {
+    RetroGameGeometry {
+        base_width: width as c_uint,
+        base_height: height as c_uint,
+        max_width: SCREEN_WIDTH as c_uint,
+        max_height: SCREEN_HEIGHT as c_uint,
+        aspect_ratio: width as f32 / height as f32,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+/// # Safety
+/// `environment` is the frontend's environment callback.
+#[no_mangle]
+pub unsafe extern "C" fn retro_set_environment(environment: RetroEnvironment) {
+    with_glue(|glue| glue.callbacks.environment = Some(environment));
+
+    let variables = variables();
+    let strings: Vec<(CString, CString)> = variables
+        .iter()
+        .map(|(key, value)| (CString::new(*key).unwrap(), CString::new(value.as_str()).unwrap()))
+        .collect();
+    let mut table: Vec<RetroVariable> = strings
+        .iter()
+        .map(|(key, value)| RetroVariable { key: key.as_ptr(), value: value.as_ptr() })
+        .collect();
+    table.push(RetroVariable { key: ptr::null(), value: ptr::null() });
+
+    environment(RETRO_ENVIRONMENT_SET_VARIABLES, table.as_mut_ptr() as *mut c_void);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(video_refresh: RetroVideoRefresh) {
+    with_glue(|glue| glue.callbacks.video_refresh = Some(video_refresh));
+}
+
+// all audio goes through the batch callback
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_: RetroAudioSample) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(audio_sample_batch: RetroAudioSampleBatch) {
+    with_glue(|glue| glue.callbacks.audio_sample_batch = Some(audio_sample_batch));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(input_poll: RetroInputPoll) {
+    with_glue(|glue| glue.callbacks.input_poll = Some(input_poll));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(input_state: RetroInputState) {
+    with_glue(|glue| glue.callbacks.input_state = Some(input_state));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    with_glue(|glue| glue.core = None);
+}
+
+/// # Safety
+/// `info` is writable.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut RetroSystemInfo) {
+    *info = RetroSystemInfo {
+        library_name: c"chip8-emu".as_ptr(),
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+        valid_extensions: c"ch8|c8|rom".as_ptr(),
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+/// # Safety
+/// `info` is writable.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut RetroSystemAvInfo) {
+    let (width, height) = with_glue(|glue| glue.core.as_ref().map_or((SCREEN_WIDTH, SCREEN_HEIGHT), |core| core.geometry));
+
+    *info = RetroSystemAvInfo {
+        geometry: geometry(width, height),
+        timing: RetroSystemTiming { fps: FPS, sample_rate: SAMPLE_RATE as f64 },
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_: c_uint, _: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    with_glue(|glue| {
+        if let Some(core) = glue.core.as_mut() {
+            core.reset();
+        }
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    with_glue(|glue| {
+        if let Some(core) = glue.core.as_mut() {
+            core.run(&mut glue.callbacks);
+        }
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    with_glue(|glue| glue.core.as_ref().map_or(MAX_STATE_SIZE, Core::serialize_size))
+}
+
+/// # Safety
+/// `data` has `size` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    if data.is_null() {
+        return false;
+    }
+
+    let out = slice::from_raw_parts_mut(data as *mut u8, size);
+    with_glue(|glue| glue.core.as_ref().is_some_and(|core| core.serialize(out)))
+}
+
+/// # Safety
+/// `data` has `size` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    if data.is_null() {
+        return false;
+    }
+
+    let state = slice::from_raw_parts(data as *const u8, size);
+    with_glue(|glue| glue.core.as_mut().is_some_and(|core| core.unserialize(state)))
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_: c_uint, _: bool, _: *const c_char) {}
+
+/// # Safety
+/// `game` is NULL or a game whose `data` has `size` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const RetroGameInfo) -> bool {
+    let Some(game) = game.as_ref() else {
+        return false;
+    };
+
+    if game.data.is_null() {
+        return false;
+    }
+
+    with_glue(|glue| {
+        if let Some(environment) = glue.callbacks.environment {
+            let mut format = RETRO_PIXEL_FORMAT_XRGB8888;
+            if !environment(RETRO_ENVIRONMENT_SET_PIXEL_FORMAT, &mut format as *mut _ as *mut c_void) {
+                return false;
+            }
+        }
+
+        let rom = slice::from_raw_parts(game.data as *const u8, game.size);
+        glue.core = Core::new(rom, &mut glue.callbacks).ok();
+
+        glue.core.is_some()
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(_: c_uint, _: *const RetroGameInfo, _: usize) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    with_glue(|glue| glue.core = None);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    RETRO_REGION_NTSC
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(_: c_uint) -> *mut c_void {
+    ptr::null_mut()
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(_: c_uint) -> usize {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::MAX_ROM_SIZE;
+    use std::collections::{BTreeMap, HashSet};
+
+    // draw "0" at (0, 0) then loop
+    const ROM: [u8; 4] = [0xD0, 0x05, 0x12, 0x02];
+
+    // V0 = 0xF0 when key 5 is down, then loop
+    const KEY_ROM: [u8; 10] = [0x61, 0x05, 0xE1, 0xA1, 0x60, 0xF0, 0x12, 0x02, 0x00, 0x00];
+
+    #[derive(Default)]
+    struct MockFrontend {
+        variables: BTreeMap<&'static str, String>,
+        has_changed: bool,
+        pressed: HashSet<(c_uint, c_uint)>,
+        geometries: Vec<(usize, usize)>,
+        frames: Vec<(Vec<u32>, usize, usize)>,
+        audio: Vec<usize>,
+        polls: usize,
+    }
+
+    impl Frontend for MockFrontend {
+        fn variable(&mut self, key: &str) -> Option<String> {
+            self.variables.get(key).cloned()
+        }
+
+        fn have_variables_changed(&mut self) -> bool {
+            std::mem::take(&mut self.has_changed)
+        }
+
+        fn set_geometry(&mut self, width: usize, height: usize) {
+            self.geometries.push((width, height));
+        }
+
+        fn video(&mut self, pixels: &[u32], width: usize, height: usize) {
+            self.frames.push((pixels.to_vec(), width, height));
+        }
+
+        fn audio(&mut self, samples: &[i16]) {
+            self.audio.push(samples.len());
+        }
+
+        fn poll_input(&mut self) {
+            self.polls += 1;
+        }
+
+        fn is_pressed(&mut self, device: c_uint, id: c_uint) -> bool {
+            self.pressed.contains(&(device, id))
+        }
+    }
+
+    #[test]
+    fn runs_a_frame_of_video_and_audio() {
+        let mut frontend = MockFrontend::default();
+        let mut core = Core::new(&ROM, &mut frontend).unwrap();
+
+        core.run(&mut frontend);
+
+        assert_eq!(frontend.polls, 1);
+        let (pixels, width, height) = &frontend.frames[0];
+        assert_eq!((*width, *height), (SCREEN_WIDTH, SCREEN_HEIGHT));
+        assert_eq!(pixels.len(), width * height);
+        assert_eq!(pixels[..5], [0x32A956, 0x32A956, 0x32A956, 0x32A956, 0]);
+        assert_eq!(frontend.audio, vec![SAMPLES_PER_FRAME * 2]);
+    }
+
+    #[test]
+    fn only_reports_geometry_changes() {
+        let mut frontend = MockFrontend::default();
+        let mut core = Core::new(&ROM, &mut frontend).unwrap();
+
+        for _ in 0..3 {
+            core.run(&mut frontend);
+        }
+        assert!(frontend.geometries.is_empty());
+
+        // as if av info had said something else
+        core.geometry = (128, 64);
+        core.run(&mut frontend);
+        core.run(&mut frontend);
+        assert_eq!(frontend.geometries, vec![(SCREEN_WIDTH, SCREEN_HEIGHT)]);
+    }
+
+    #[test]
+    fn maps_the_joypad_and_keyboard_to_the_keypad() {
+        let mut frontend = MockFrontend::default();
+        let mut core = Core::new(&KEY_ROM, &mut frontend).unwrap();
+
+        frontend.pressed.insert((RETRO_DEVICE_JOYPAD, RETRO_DEVICE_ID_JOYPAD_A));
+        core.run(&mut frontend);
+        assert!(core.chip8.keys()[0x5]);
+        assert_eq!(core.chip8.registers()[0], 0xF0);
+
+        frontend.pressed.clear();
+        frontend.pressed.insert((RETRO_DEVICE_KEYBOARD, 'v' as c_uint));
+        core.run(&mut frontend);
+        assert!(!core.chip8.keys()[0x5]);
+        assert!(core.chip8.keys()[0xF]);
+    }
+
+    #[test]
+    fn serializes_to_a_constant_size() {
+        let mut frontend = MockFrontend::default();
+        let mut core = Core::new(&ROM, &mut frontend).unwrap();
+        let size = core.serialize_size();
+        let mut state = vec![0xFF; size];
+
+        assert!(core.serialize(&mut state));
+        core.run(&mut frontend);
+        assert_eq!(core.serialize_size(), size);
+        assert!(!core.serialize(&mut [0; 10]));
+
+        assert!(core.unserialize(&state));
+        assert_eq!(core.chip8.frame_count(), 0);
+        assert!(!core.unserialize(&[1, 2, 3]));
+    }
+
+    #[test]
+    fn applies_core_options() {
+        let mut frontend = MockFrontend::default();
+        frontend.variables.insert(SPEED, "20".to_string());
+        frontend.variables.insert(PALETTE, "white".to_string());
+        let mut core = Core::new(&ROM, &mut frontend).unwrap();
+
+        assert_eq!(core.ticks_per_frame, 20);
+        assert_eq!(core.palette, Palette::named("white").unwrap());
+        assert!(!core.chip8.quirks().shift_uses_vy);
+
+        // read again once the frontend says they changed
+        frontend.variables.insert(QUIRKS, "cosmac_vip".to_string());
+        frontend.variables.insert(SPEED, "nonsense".to_string());
+        core.run(&mut frontend);
+        assert!(!core.chip8.quirks().shift_uses_vy);
+        frontend.has_changed = true;
+        core.run(&mut frontend);
+        assert!(core.chip8.quirks().shift_uses_vy);
+        assert!(core.chip8.quirks().memory_increment_i);
+        assert_eq!(core.ticks_per_frame, 20);
+    }
+
+    #[test]
+    fn a_crash_holds_the_last_frame_until_reset() {
+        // return with nothing on the stack
+        let mut frontend = MockFrontend::default();
+        let mut core = Core::new(&[0x00, 0xEE], &mut frontend).unwrap();
+
+        core.run(&mut frontend);
+        assert!(core.has_crashed);
+        core.run(&mut frontend);
+        assert_eq!(frontend.frames.len(), 2);
+
+        core.reset();
+        assert!(!core.has_crashed);
+    }
+
+    #[test]
+    fn lists_every_option_for_the_frontend() {
+        for (key, value) in variables() {
+            let (_, values) = value.split_once("; ").unwrap();
+            assert!(key.starts_with("chip8_emu_"));
+            assert!(!values.is_empty());
+        }
+
+        assert_eq!(quirk_preset("modern"), Quirks::default());
+        assert!(Core::new(&vec![0; MAX_ROM_SIZE + 1], &mut MockFrontend::default()).is_err());
+    }
+}
@@ -0,0 +1,240 @@
+// one ROM on two quirk configurations side by side, for "works in Octo, breaks here": both get the
+// same seed and input, and the first instruction that leaves them in different states is where a
+// quirk mattered. everything but the quirks is compared, the same way an audit compares two runs
+//
+// a configuration is a preset and single quirks on top, applied left to right:
+//
+//   preset:schip
+//   preset:chip8,shift_uses_vy=off
+//
+// the input script's keys and speeds play on both sides, its quirk changes don't, the
+// configurations decide those
+
+use crate::audit::{self, Agreement, AuditState};
+use crate::disasm::disassemble;
+use crate::headless::{panic_message, Options};
+use crate::vectors::Vector;
+use crate::{Chip8, Chip8Error, Quirk, Quirks, DEFAULT_STEP_BACK_INTERVAL, SCREEN_WIDTH};
+
+use std::error::Error;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+pub fn parse_quirks(text: &str) -> Option<Quirks> {
+    let mut quirks = Quirks::default();
+
+    for item in text.split(',').map(str::trim) {
+        if let Some(name) = item.strip_prefix("preset:") {
+            quirks = Quirks::preset(name)?;
+            continue;
+        }
+
+        let (name, value) = item.split_once('=')?;
+        let is_enabled = match value {
+            "on" => true,
+            "off" => false,
+            _ => return None,
+        };
+
+        quirks.set(Quirk::from_name(name)?, is_enabled);
+    }
+
+    Some(quirks)
+}
+
+// one side where the runs split, right after the instruction that split them
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Side {
+    pub registers: Vector,
+    pub screen: Vec<bool>,
+}
+
+impl Side {
+    fn of(chip8: &Chip8) -> Self {
+        Self { registers: Vector::of(chip8), screen: chip8.get_display().to_vec() }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    // counted from 0, like input scripts
+    pub frame: usize,
+    // the program counter and opcode of the instruction, None when stepping back couldn't find it.
+    // the sides are then wherever stepping back left them
+    pub instruction: Option<(u16, u16)>,
+    // as AuditState::differences, first side first
+    pub differences: Vec<String>,
+    // boxed to keep the Result small
+    pub sides: Box<[Side; 2]>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the runs differ in frame {}", self.frame)?;
+
+        if let Some((pc, opcode)) = self.instruction {
+            write!(f, ", first at {:03X} running {:04X} {}", pc, opcode, disassemble(opcode))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for Divergence {}
+
+// the divergence with what differs, and the screens next to each other under the configurations'
+// names
+pub fn report(divergence: &Divergence, names: [&str; 2]) -> String {
+    let mut text = format!("{}\n", divergence);
+
+    for line in &divergence.differences {
+        text += &format!("  {}\n", line);
+    }
+
+    text += "\n";
+    for (side, name) in divergence.sides.iter().zip(names) {
+        let registers = &side.registers;
+        let v: Vec<String> = registers.v.iter().map(|value| format!("{:02X}", value)).collect();
+
+        text += &format!("{}: PC {:03X}, I {:03X}, V {}\n", name, registers.pc, registers.i, v.join(" "));
+    }
+
+    text += &format!("\n{:<width$}  {}\n", names[0], names[1], width = SCREEN_WIDTH);
+    let [first, second] = &*divergence.sides;
+    for (left, right) in first.screen.chunks(SCREEN_WIDTH).zip(second.screen.chunks(SCREEN_WIDTH)) {
+        text += &format!("{}  {}\n", ascii_row(left), ascii_row(right));
+    }
+
+    text
+}
+
+fn ascii_row(pixels: &[bool]) -> String {
+    pixels.iter().map(|&pixel| if pixel { '#' } else { '.' }).collect()
+}
+
+fn machine(rom: &[u8], options: &Options, quirks: Quirks) -> Result<Chip8, Chip8Error> {
+    let mut chip8 = Chip8::new();
+    chip8.load(rom)?;
+    chip8.set_seed(options.seed);
+    chip8.set_quirks(quirks);
+    chip8.set_step_back_interval(DEFAULT_STEP_BACK_INTERVAL);
+
+    Ok(chip8)
+}
+
+fn state(chip8: &Chip8) -> AuditState {
+    AuditState::of(chip8).without_quirks()
+}
+
+// the ROM on both configurations for `options.frames` frames, compared after every frame and
+// stepped back to the instruction when they differ
+pub fn compare(rom: &[u8], options: &Options, quirks: [Quirks; 2]) -> Result<Agreement, Divergence> {
+    let (mut first, mut second) = match quirks.map(|quirks| machine(rom, options, quirks)) {
+        [Ok(first), Ok(second)] => (first, second),
+        [Err(error), _] | [_, Err(error)] => return Ok(audit::unloadable(error)),
+    };
+    let play = |chip8: &mut Chip8, frame| {
+        for key in options.script.keys.iter().filter(|key| key.frame == frame) {
+            chip8.keypress(key.key, key.is_pressed);
+        }
+
+        chip8.run_frame(options.script.speed_at(frame, options.ticks_per_frame));
+    };
+
+    let divergence = match audit::compare_by(options.frames, &mut first, &mut second, state, play) {
+        Ok(agreement) => return Ok(agreement),
+        Err(divergence) => divergence,
+    };
+
+    let mut differences = divergence.differences;
+
+    // both are just before the instruction, running it shows what it did on each
+    if divergence.instruction.is_some() {
+        let crashes =
+            [&mut first, &mut second].map(|chip8| match panic::catch_unwind(AssertUnwindSafe(|| chip8.tick())) {
+                Ok(ticked) => ticked.err().map(|error| error.to_string()),
+                Err(cause) => Some(panic_message(cause)),
+            });
+
+        differences = match crashes {
+            [None, None] => state(&first).differences(&state(&second)),
+            _ => {
+                let describe = |crash: &Option<String>| crash.clone().unwrap_or_else(|| "ran".to_string());
+                vec![format!("crash: {} vs {}", describe(&crashes[0]), describe(&crashes[1]))]
+            },
+        };
+    }
+
+    Err(Divergence {
+        frame: divergence.frame,
+        instruction: divergence.instruction,
+        differences,
+        sides: Box::new([Side::of(&first), Side::of(&second)]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::headless::{Script, DEFAULT_SEED};
+
+    // V1 = 0x81, V0 = 0x10, V0 = V1 >> 1, draw the font's 0 at (V0, V0) and stop
+    const SHIFT: [u8; 10] = [0x61, 0x81, 0x60, 0x10, 0x80, 0x16, 0xD0, 0x05, 0x12, 0x08];
+
+    fn options(frames: usize) -> Options {
+        Options { frames, ticks_per_frame: 10, seed: DEFAULT_SEED, script: Script::default() }
+    }
+
+    fn presets(first: &str, second: &str) -> [Quirks; 2] {
+        [parse_quirks(first).unwrap(), parse_quirks(second).unwrap()]
+    }
+
+    #[test]
+    fn parses_configurations() {
+        assert_eq!(parse_quirks("preset:chip8"), Quirks::preset("chip8"));
+        let chip8 = Quirks::preset("chip8").unwrap();
+        assert_eq!(parse_quirks("preset:chip8, shift_uses_vy=off"), Some(Quirks { shift_uses_vy: false, ..chip8 }));
+        assert_eq!(parse_quirks("shift_uses_vy=on"), Some(Quirks { shift_uses_vy: true, ..Quirks::default() }));
+
+        for bad in ["", "preset:megachip", "shift_uses_vy", "shift_uses_vy=yes", "shift=on"] {
+            assert_eq!(parse_quirks(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn the_shift_quirk_splits_at_the_shift() {
+        let divergence = compare(&SHIFT, &options(10), presets("preset:chip8", "preset:schip")).unwrap_err();
+
+        assert_eq!((divergence.frame, divergence.instruction), (0, Some((0x204, 0x8016))));
+        // V1 >> 1 against V0 >> 1, with the bit shifted out in VF
+        assert_eq!(divergence.differences, ["V0: 40 vs 08", "VF: 01 vs 00"]);
+        assert_eq!(divergence.sides[0].registers.pc, 0x206);
+        assert_eq!(divergence.to_string(), "the runs differ in frame 0, first at 204 running 8016 SHR V0");
+    }
+
+    #[test]
+    fn reports_registers_and_both_screens() {
+        let mut divergence = compare(&SHIFT, &options(10), presets("preset:chip8", "preset:schip")).unwrap_err();
+        // nothing is drawn before the shift
+        assert!(divergence.sides.iter().all(|side| side.screen.iter().all(|&pixel| !pixel)));
+        divergence.sides[0].screen[1] = true;
+
+        let report = report(&divergence, ["preset:chip8", "preset:schip"]);
+        let lines: Vec<&str> = report.lines().collect();
+
+        assert_eq!(lines[1..4], ["  V0: 40 vs 08", "  VF: 01 vs 00", ""]);
+        assert_eq!(lines[4], "preset:chip8: PC 206, I 000, V 40 81 00 00 00 00 00 00 00 00 00 00 00 00 00 01");
+        assert_eq!(lines[5], "preset:schip: PC 206, I 000, V 08 81 00 00 00 00 00 00 00 00 00 00 00 00 00 00");
+        assert_eq!(lines[7], format!("{:<64}  preset:schip", "preset:chip8"));
+        assert_eq!(lines[8], format!(".#{}  {}", ".".repeat(62), ".".repeat(64)));
+        assert_eq!(lines.len(), 8 + 32);
+    }
+
+    #[test]
+    fn the_same_configuration_agrees() {
+        let agreement = compare(&SHIFT, &options(30), presets("preset:schip", "shift_uses_vy=off")).unwrap();
+
+        assert_eq!(agreement, Agreement { frames: 30, crash: None });
+    }
+}
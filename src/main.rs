@@ -1,132 +1,2000 @@
-use chip8_emu::{Chip8, SCREEN_HEIGHT, SCREEN_WIDTH};
+mod frontend;
+
+use chip8_emu::analyze;
+use chip8_emu::audit;
+use chip8_emu::bench::{self, Machine, Settings};
+use chip8_emu::conformance::{self, Goldens};
+use chip8_emu::diagnostics;
+use chip8_emu::golden::{self, Manifest, Setup};
+use chip8_emu::headless::{self, Options, Script};
+use chip8_emu::lockstep;
+use chip8_emu::report::{self, Expectations, HaltReason, RunError};
+use chip8_emu::sweep::{self, Sweep};
+use chip8_emu::trace_log::{self, TraceError, TraceLog};
+use chip8_emu::vectors;
+use chip8_emu::{export_png, AudioRecorder, Chip8, LoadedRom, Palette, Quirk, SCREEN_HEIGHT, SCREEN_WIDTH};
+use frontend::attract::{self, Attract};
+use frontend::audio::AudioOutput;
+use frontend::bindings::Action;
+use frontend::browser::{self, Browser};
+use frontend::cli::{
+    self, AuditArgs, Command, CompareArgs, Config, HeadlessArgs, ReportFormat, RunArgs, SnapshotArgs, SweepArgs,
+    TraceArgs, VectorArgs,
+};
+use frontend::config_file;
+use frontend::crt::{self, Crt};
+use frontend::font;
+use frontend::heatmap_view::{self, MemoryHeat};
+use frontend::inspect::{self, Zoom};
+use frontend::keypad_overlay;
+use frontend::latency::{self, LatencyProbe};
+use frontend::limiter::{FrameLimiter, SystemClock};
+use frontend::osd::Osd;
+use frontend::overlay;
+use frontend::pacing::{self, FrameRepeat, Pacing};
+use frontend::paint::{Painter, Rgba};
+use frontend::quirk_menu::{self, QuirkMenu};
+use frontend::rom_overrides::{self, OverrideStore};
+use frontend::save_slots::{self, SaveSlots};
+use frontend::screenshot::{self, GifCapture};
+use frontend::speed;
+use frontend::timestep::Timestep;
+use frontend::title::{self, RateMeter};
+use frontend::video::{self, VideoCapture};
+use frontend::viewport::{drawable_point, Viewport};
+use frontend::watch::RomWatcher;
+use frontend::window_mode::{Geometry, Transition, WindowMode};
 
 use std::env;
-use std::fs::File;
-use std::io::Read;
+use std::fs;
+use std::io;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
 
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::rect::Rect;
-use sdl2::render::Canvas;
-use sdl2::video::Window;
+use sdl2::render::{BlendMode, Canvas, Texture};
+use sdl2::sys::SDL_RendererFlags;
+use sdl2::video::{FullscreenType, Window, WindowPos};
 
-const SCALE: u32 = 20;
-const WINDOW_WIDTH: u32 = (SCREEN_WIDTH as u32) * SCALE;
-const WINDOW_HEIGHT: u32 = (SCREEN_HEIGHT as u32) * SCALE;
-const TICKS_PER_FRAME: usize = 10;
+// seconds of history kept for hold-to-rewind
+const REWIND_SECONDS: usize = 10;
+// how dark the box behind the debug overlay is
+const OVERLAY_ALPHA: u8 = 190;
+// how strongly the pixel grid shows over the game
+const GRID_ALPHA: u8 = 70;
 
 fn main() {
-    let args: Vec<_> = env::args().collect();
+    let command = cli::parse(env::args_os()).unwrap_or_else(|e| e.exit());
+
+    match command {
+        Command::Conformance { dir, is_bless } => run_conformance(&dir, is_bless),
+        Command::Bench { rom, seconds, is_json } => run_bench(rom.as_deref(), seconds, is_json),
+        Command::ExportVectors { run, instructions, output } => {
+            if let Err(message) = export_vectors(&run, instructions, output.as_deref()) {
+                eprintln!("{}", message);
+                process::exit(1);
+            }
+        },
+        Command::VerifyVectors { run, vectors } => match verify_vectors(&run, &vectors) {
+            Ok(count) => println!("{} test vectors match", count),
+            Err(message) => {
+                eprintln!("{}", message);
+                process::exit(1);
+            },
+        },
+        Command::Headless(args) => match run_headless(&args) {
+            Ok(code) => process::exit(code),
+            Err(message) => {
+                eprintln!("{}", message);
+                process::exit(report::EXIT_ERROR);
+            },
+        },
+        Command::Sweep(args) => match run_sweep(&args) {
+            Ok(is_pass) => process::exit(if is_pass { 0 } else { 1 }),
+            Err(message) => {
+                eprintln!("{}", message);
+                process::exit(2);
+            },
+        },
+        Command::Audit(args) => match run_audit(&args) {
+            Ok(code) => process::exit(code),
+            Err(message) => {
+                eprintln!("{}", message);
+                process::exit(report::EXIT_ERROR);
+            },
+        },
+        Command::Snapshot(args) => {
+            if let Err(message) = run_snapshot(&args) {
+                eprintln!("{}", message);
+                process::exit(1);
+            }
+        },
+        Command::Analyze { rom, is_lints } => match analyze(&rom, is_lints) {
+            Ok(code) => process::exit(code),
+            Err(message) => {
+                eprintln!("{}", message);
+                process::exit(report::EXIT_ERROR);
+            },
+        },
+        Command::Trace(args) => {
+            if let Err(message) = run_trace(&args) {
+                eprintln!("{}", message);
+                process::exit(1);
+            }
+        },
+        Command::Compare(args) => match run_compare(&args) {
+            Ok(code) => process::exit(code),
+            Err(message) => {
+                eprintln!("{}", message);
+                process::exit(report::EXIT_ERROR);
+            },
+        },
+        Command::WriteDefaultConfig(path) => write_default_config(path),
+        Command::Overrides => list_overrides(),
+        Command::Run(args) => {
+            if let Some(mut config) = load_config(&args) {
+                if config.is_latency_test {
+                    config.rom = latency::ROM_NAME.to_string();
+                    run(config, latency::ROM.to_vec(), false, None);
+                    return;
+                }
+
+                #[cfg(feature = "terminal")]
+                if let Some(style) = config.terminal {
+                    let buffer = load_rom(&mut config, &args);
+                    warn_sdl_only(&config);
+
+                    if let Err(message) = frontend::terminal::run(config, buffer, style) {
+                        eprintln!("{}", message);
+                        process::exit(1);
+                    }
+                    return;
+                }
+
+                #[cfg(any(feature = "minifb", feature = "pixels"))]
+                if config.backend != cli::Backend::Sdl {
+                    let buffer = load_rom(&mut config, &args);
+                    warn_sdl_only(&config);
+
+                    let result = match config.backend {
+                        #[cfg(feature = "minifb")]
+                        cli::Backend::Minifb => frontend::minifb_window::run(config, buffer),
+                        #[cfg(feature = "pixels")]
+                        cli::Backend::Pixels => frontend::pixels_window::run(config, buffer),
+                        cli::Backend::Sdl => unreachable!(),
+                    };
+
+                    if let Err(message) = result {
+                        eprintln!("{}", message);
+                        process::exit(1);
+                    }
+                    return;
+                }
+
+                if let Some(path) = &config.playlist {
+                    let roms = attract::load_playlist(path).unwrap_or_else(|message| {
+                        eprintln!("{}", message);
+                        process::exit(1);
+                    });
+                    let attract = Attract::new(roms, config.rotate, Instant::now());
+                    let buffer = load_rom(&mut config, &args);
+                    run(config, buffer, false, Some(attract));
+                } else if Path::new(&config.rom).is_dir() {
+                    browse(config, &args);
+                } else {
+                    let buffer = load_rom(&mut config, &args);
+                    run(config, buffer, false, None);
+                }
+            }
+        },
+        #[cfg(feature = "egui")]
+        Command::Debug(args) => {
+            if let Some(mut config) = load_config(&args) {
+                let buffer = load_rom(&mut config, &args);
+                warn_sdl_only(&config);
+
+                if let Err(message) = frontend::egui_debugger::run(config, buffer) {
+                    eprintln!("{}", message);
+                    process::exit(1);
+                }
+            }
+        },
+        #[cfg(feature = "tui")]
+        Command::Tui(args) => {
+            if let Some(mut config) = load_config(&args) {
+                let buffer = load_rom(&mut config, &args);
+                warn_sdl_only(&config);
+
+                if let Err(message) = frontend::tui::run(config, buffer) {
+                    eprintln!("{}", message);
+                    process::exit(1);
+                }
+            }
+        },
+    }
+}
+
+// defaults, then the config file, then the command line. None if the user backed out of picking a rom
+fn load_config(args: &RunArgs) -> Option<Config> {
+    let mut config = Config::new(String::new());
+    let config_path = args.config.clone().or_else(config_file::default_path);
+
+    if let Some(path) = config_path {
+        let applied = config_file::load(&path, args.config.is_some())
+            .and_then(|file| file.apply(&mut config).map_err(|e| format!("{}: {}", path.display(), e)));
+
+        if let Err(message) = applied {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+    }
+
+    config.apply_args(args);
 
-    if args.len() != 2 {
-        println!("Usage: cargo run path/to/game");
+    // the other frontends only play the first ROM of a playlist, the SDL window goes on from there
+    if let Some(path) = &config.playlist {
+        match attract::load_playlist(path) {
+            Ok(roms) => config.rom = roms[0].display().to_string(),
+            Err(message) => {
+                eprintln!("{}", message);
+                process::exit(1);
+            },
+        }
+    }
+
+    #[cfg(feature = "dialog")]
+    if config.rom.is_empty() && !config.is_latency_test {
+        config.rom = frontend::pick_rom()?;
+    }
+
+    // a run without a seed still gets one, printed so a bug report can say which run it was
+    if config.seed.is_none() {
+        let seed = rand::random();
+        println!("Random seed {:#X}, pass --seed {:#X} to repeat the run", seed, seed);
+        config.seed = Some(seed);
+    }
+
+    Some(config)
+}
+
+// the library's own check, so a ROM that can't load is reported before anything else is set up
+fn read_loadable_rom(source: &str) -> Result<Vec<u8>, String> {
+    let rom = frontend::read_rom(source)?;
+    Chip8::new().check_rom(&rom).map_err(|e| format!("Unable to load {}: {}", source, e))?;
+
+    Ok(rom)
+}
+
+fn read_rom_or_exit(rom: &str) -> Vec<u8> {
+    read_loadable_rom(rom).unwrap_or_else(|message| {
+        eprintln!("{}", message);
+        process::exit(1);
+    })
+}
+
+// reads the ROM and layers the settings saved for it under the command line
+fn load_rom(config: &mut Config, args: &RunArgs) -> Vec<u8> {
+    let buffer = read_rom_or_exit(&config.rom);
+    apply_saved_overrides(config, args, &buffer);
+
+    buffer
+}
+
+fn apply_saved_overrides(config: &mut Config, args: &RunArgs, buffer: &[u8]) {
+    let hash = LoadedRom::new(buffer, None, 0).sha256_hex();
+
+    let Some(path) = OverrideStore::default_path() else {
         return;
+    };
+
+    let mut store = match OverrideStore::load(&path) {
+        Ok(store) => store,
+        Err(message) => {
+            eprintln!("{}, ignoring saved per-ROM settings", message);
+            return;
+        }
+    };
+
+    if args.forget_overrides {
+        if store.forget(&hash) {
+            match store.save() {
+                Ok(()) => println!("Forgot the saved settings for {}", title::display_name(&config.rom)),
+                Err(message) => eprintln!("{}", message),
+            }
+        }
+    } else if let Err(message) = rom_overrides::resolve(config, store.get(&hash), args) {
+        eprintln!("{}, ignoring saved per-ROM settings", message);
+    }
+}
+
+fn list_overrides() {
+    let Some(path) = OverrideStore::default_path() else {
+        eprintln!("Unable to find a config directory");
+        process::exit(1);
+    };
+
+    match OverrideStore::load(&path) {
+        Ok(store) => print!("{}", rom_overrides::format_list(&store)),
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+    }
+}
+
+fn write_default_config(path: Option<PathBuf>) {
+    let path = match path.or_else(config_file::default_path) {
+        Some(path) => path,
+        None => {
+            eprintln!("Unable to find a config directory, pass a path to --write-default-config");
+            process::exit(1);
+        }
+    };
+
+    match config_file::write_template(&path) {
+        Ok(()) => println!("Wrote {}", path.display()),
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+    }
+}
+
+// netplay, --remote and --viewer need the frame loop in run, the other frontends don't have it yet
+#[cfg(any(feature = "egui", feature = "tui", feature = "terminal", feature = "minifb", feature = "pixels"))]
+fn warn_sdl_only(config: &Config) {
+    if config.netplay.is_some() {
+        eprintln!("Netplay only works in the SDL window, ignoring");
+    }
+
+    if config.playlist.is_some() {
+        eprintln!("--playlist only rotates in the SDL window, playing its first ROM");
+    }
+
+    if config.is_latency_test {
+        eprintln!("--latency-test only works in the SDL window, ignoring");
+    }
+
+    #[cfg(feature = "remote")]
+    if config.remote.is_some() {
+        eprintln!("--remote only works in the SDL window and headless, ignoring");
+    }
+
+    #[cfg(feature = "viewer")]
+    if config.viewer.is_some() {
+        eprintln!("--viewer only works in the SDL window and headless, ignoring");
+    }
+}
+
+// a directory opens the browser, leaving a game with the quit key comes back to it
+fn browse(config: Config, args: &RunArgs) {
+    let dir = PathBuf::from(&config.rom);
+    let mut browser = match browser::scan(&dir) {
+        Ok(entries) => Browser::new(entries),
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+    };
+
+    while let Some((rom, buffer)) = run_browser(&mut browser, &config, &dir) {
+        let mut game = Config { rom, ..config.clone() };
+        apply_saved_overrides(&mut game, args, &buffer);
+
+        if !run(game, buffer, true, None) {
+            break;
+        }
+    }
+}
+
+// the picked ROM and its contents, None when the window is closed. one that can't be loaded says why
+// in the browser and stays in the list
+fn run_browser(browser: &mut Browser, config: &Config, dir: &Path) -> Option<(String, Vec<u8>)> {
+    let sdl_context = sdl2::init().unwrap();
+    let video_subsystem = sdl_context.video().unwrap();
+    let window_title = format!("{} - {}", title::APP_NAME, dir.display());
+    let window_width = SCREEN_WIDTH as u32 * config.scale;
+    let window_height = SCREEN_HEIGHT as u32 * config.scale;
+    let mut window_builder = video_subsystem.window(&window_title, window_width, window_height);
+    window_builder.position_centered().resizable();
+
+    if config.is_fullscreen {
+        window_builder.fullscreen_desktop();
+    }
+
+    let mut canvas = window_builder.build().unwrap().into_canvas().present_vsync().build().unwrap();
+    let mut event_pump = sdl_context.event_pump().unwrap();
+
+    loop {
+        let rows = draw_browser(&mut canvas, browser, config);
+        canvas.present();
+
+        // nothing moves on its own, so sleep until there's input
+        match event_pump.wait_event() {
+            Event::Quit { .. } => return None,
+            Event::KeyDown { keycode: Some(key), .. } => match key {
+                Keycode::Up => browser.move_by(-1),
+                Keycode::Down => browser.move_by(1),
+                Keycode::PageUp => browser.move_by(-(rows as isize)),
+                Keycode::PageDown => browser.move_by(rows as isize),
+                Keycode::Home => browser.move_by(isize::MIN),
+                Keycode::End => browser.move_by(isize::MAX),
+                Keycode::Return | Keycode::KpEnter => {
+                    let rom = browser.selected().path.display().to_string();
+
+                    match read_loadable_rom(&rom) {
+                        Ok(buffer) => return Some((rom, buffer)),
+                        Err(message) => browser.set_error(message),
+                    }
+                },
+                Keycode::Escape => return None,
+                _ => (),
+            },
+            _ => (),
+        }
+    }
+}
+
+// the list on the left, the selected ROM's first second on the right. returns how many rows fit
+fn draw_browser(canvas: &mut Canvas<Window>, browser: &mut Browser, config: &Config) -> usize {
+    let (width, height) = canvas.output_size().unwrap();
+    let (background, foreground) = (config.palette.background, config.palette.foreground);
+    let size = (height / 128).max(1);
+    let margin = size * 4;
+    let row_height = (font::LINE_HEIGHT + 2) * size;
+    let list_width = (width / 2).saturating_sub(margin * 2);
+    let rows = (height.saturating_sub(margin * 2) / row_height).max(1) as usize;
+    let max_chars = (list_width / (font::ADVANCE * size)).max(1) as usize;
+
+    canvas.set_draw_color(Color::RGB(background.0, background.1, background.2));
+    canvas.clear();
+
+    for (row, index) in browser.visible(rows).enumerate() {
+        let top = (margin + row as u32 * row_height) as i32;
+        let text: String = browser.entries()[index].title.chars().take(max_chars).collect();
+        let mut color = foreground;
+
+        if index == browser.selected_index() {
+            canvas.set_draw_color(Color::RGB(foreground.0, foreground.1, foreground.2));
+            canvas.fill_rect(Rect::new(margin as i32 - size as i32, top - size as i32, list_width, row_height)).unwrap();
+            color = background;
+        }
+
+        canvas.set_draw_color(Color::RGB(color.0, color.1, color.2));
+        draw_text(canvas, &text, margin as i32, top, size);
+    }
+
+    let preview = Viewport::fit(
+        ((width / 2).saturating_sub(margin), height.saturating_sub(margin * 2)),
+        (SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32),
+    );
+    let (left, top) = ((width / 2) as i32 + preview.x, margin as i32 + preview.y);
+    canvas.set_draw_color(Color::RGB(foreground.0, foreground.1, foreground.2));
+
+    if let Some(error) = browser.error() {
+        let max_chars = ((width / 2).saturating_sub(margin) / (font::ADVANCE * size)).max(1) as usize;
+
+        for (line, text) in font::wrap(error, max_chars).iter().enumerate() {
+            draw_text(canvas, text, left, top + (line as u32 * row_height) as i32, size);
+        }
+
+        return rows;
+    }
+
+    match browser.thumbnail(config.ticks_per_frame) {
+        Some(screen) => {
+            // a frame around the preview so a blank screen still shows where it is
+            canvas.draw_rect(Rect::new(left - 1, top - 1, preview.width + 2, preview.height + 2)).unwrap();
+
+            for (i, _) in screen.iter().enumerate().filter(|(_, &pixel)| pixel) {
+                let (x, y) = ((i % SCREEN_WIDTH) as u32, (i / SCREEN_WIDTH) as u32);
+                let rect = Rect::new(left + (x * preview.scale) as i32, top + (y * preview.scale) as i32, preview.scale, preview.scale);
+                canvas.fill_rect(rect).unwrap();
+            }
+        },
+        None => draw_text(canvas, "no preview", left, top, size),
     }
 
+    rows
+}
+
+fn run(mut config: Config, buffer: Vec<u8>, is_browsing: bool, mut attract: Option<Attract>) -> bool {
+    if config.record_video.is_some() {
+        if let Err(message) = video::check_ffmpeg(&config.ffmpeg) {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+    }
+
+    // connect before opening the window, waiting for the other player can take a while
+    let mut netplay = config.netplay.as_ref().map(|netplay| {
+        frontend::netplay::connect(netplay, &buffer, config.seed).unwrap_or_else(|message| {
+            eprintln!("{}", message);
+            process::exit(1);
+        })
+    });
+
+    #[cfg(feature = "remote")]
+    let remote = config.remote.as_deref().map(|address| {
+        frontend::listen_remote(address).unwrap_or_else(|message| {
+            eprintln!("{}", message);
+            process::exit(1);
+        })
+    });
+
+    #[cfg(feature = "viewer")]
+    let mut viewer = config.viewer.as_deref().map(|address| {
+        frontend::listen_viewer(address).unwrap_or_else(|message| {
+            eprintln!("{}", message);
+            process::exit(1);
+        })
+    });
+
     // setup sdl
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
-    let window = video_subsystem
-        .window("Chip-8 Emulator", WINDOW_WIDTH, WINDOW_HEIGHT)
-        .position_centered()
-        .opengl()
-        .build()
-        .unwrap();
+    let window_width = SCREEN_WIDTH as u32 * config.scale;
+    let window_height = SCREEN_HEIGHT as u32 * config.scale;
+    let mut window_builder = video_subsystem.window(title::APP_NAME, window_width, window_height);
+    window_builder.position_centered().resizable().opengl();
+
+    if config.is_fullscreen {
+        window_builder.fullscreen_desktop();
+    }
+
+    let mut window = window_builder.build().unwrap();
+    window.set_minimum_size(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32).unwrap();
+
+    // vsync only on a display it paces well, the limiter on the rest
+    let mut display = window.display_index().ok();
+    let refresh_rate = display_refresh_rate(&window);
+    let mut canvas_builder = window.into_canvas();
+
+    if config.is_vsync && !config.is_unlock_fps && Pacing::choose(refresh_rate).is_vsync() {
+        canvas_builder = canvas_builder.present_vsync();
+    }
+
+    let mut canvas = canvas_builder.build().unwrap();
 
-    let mut canvas = window.into_canvas().present_vsync().build().unwrap();
+    // the driver can refuse vsync, then the limiter stops the loop from spinning a core
+    let has_vsync = canvas.info().flags & SDL_RendererFlags::SDL_RENDERER_PRESENTVSYNC as u32 != 0;
+    let mut limiter = (!has_vsync && !config.is_unlock_fps).then(|| FrameLimiter::new(60));
+    let pacing = Pacing::choose(refresh_rate).with_renderer(has_vsync);
+    let mut frame_repeat = FrameRepeat::new(pacing);
+    println!("{}", pacing::describe(refresh_rate, pacing));
     canvas.clear();
     canvas.present();
 
+    let mut audio = if config.is_mute {
+        None
+    } else {
+        AudioOutput::open(&sdl_context)
+            .map_err(|e| eprintln!("Unable to open audio, continuing without sound: {}", e))
+            .ok()
+    };
+
+    let mut video = config.record_video.as_ref().and_then(|path| {
+        let scale = config.screenshot_scale.unwrap_or(config.scale);
+        let sample_rate = audio.as_ref().map_or(frontend::audio::SAMPLE_RATE as u32, AudioOutput::sample_rate);
+
+        match video::start_video(&config.ffmpeg, path, scale, (!config.is_mute).then_some(sample_rate)) {
+            Ok(capture) => {
+                println!("Recording video to {}", path.display());
+                Some(capture)
+            },
+            Err(message) => {
+                eprintln!("{}", message);
+                None
+            },
+        }
+    });
+
     let mut event_pump = sdl_context.event_pump().unwrap();
     let mut chip8 = Chip8::new();
-    let mut rom = File::open(&args[1]).expect("Unable to open file");
-    let mut buffer = Vec::new();
+    if let Err(error) = chip8.load_named(&buffer, &config.rom) {
+        eprintln!("Unable to load {}: {}", config.rom, error);
+        return false;
+    }
+    chip8.set_quirks(config.initial_quirks());
+
+    if let Some(seed) = config.seed {
+        chip8.set_seed(seed);
+    }
 
-    rom.read_to_end(&mut buffer).unwrap();
-    chip8.load(&buffer);
+    chip8.set_beep_config(config.beep);
+    chip8.set_paused(config.is_start_paused);
 
-    loop {
+    for &address in &config.breakpoints {
+        chip8.add_breakpoint(address);
+    }
+
+    chip8.set_rewind_capacity(REWIND_SECONDS * 60);
+    chip8.set_replay_capacity(config.replay_seconds as usize * 60);
+
+    if let Some(attract) = attract.as_mut() {
+        attract.start(&mut chip8);
+    }
+
+    if let Some(lockstep) = &netplay {
+        chip8.set_seed(lockstep.seed());
+        chip8.set_paused(false);
+    }
+
+    // what this player holds, during netplay the chip8 sees both players' keys
+    let mut keypad = [false; 16];
+
+    let mut input_recording = None;
+
+    // kept all the time, a dump is for the problems nobody planned for
+    chip8.set_trace_capacity(diagnostics::DEFAULT_TRACE_LENGTH);
+    frontend::signals::install();
+
+    if config.record_input.is_some() {
+        if netplay.is_some() {
+            eprintln!("--record-input doesn't work with netplay, ignoring");
+        } else {
+            // `headless --input-script` picks the seed up from the recording
+            let mut script = Script { seed: Some(chip8.seed()), ..Script::default() };
+            script.set_speed(0, config.ticks_per_frame);
+
+            for quirk in Quirk::all() {
+                script.set_quirk(0, quirk, chip8.quirks().get(quirk));
+            }
+
+            input_recording = Some(script);
+        }
+    }
+
+    let mut slots = save_slots::default_dir()
+        .zip(chip8.loaded_rom())
+        .map(|(dir, rom)| SaveSlots::new(dir, rom.sha256_hex()));
+
+    if config.record_audio.is_some() {
+        chip8.attach_audio_recorder(AudioRecorder::new());
+    }
+
+    // used to keep the recording going when there's no audio device
+    let mut silent_frame = vec![0.0; frontend::audio::samples_per_frame()];
+    let mut window_title = String::from(title::APP_NAME);
+    let mut rates = RateMeter::new();
+    let mut is_rewinding = false;
+    let mut gif: Option<GifCapture> = None;
+    let mut window_mode = WindowMode::new(config.is_fullscreen);
+    let mut palette = config.palette;
+    let mut is_crt = config.is_crt;
+    let mut is_overlay = false;
+    let mut is_grid = false;
+    let mut is_zoom = false;
+    let mut is_keypad = false;
+    // while the heatmap overlay is up
+    let mut memory_heat: Option<MemoryHeat> = None;
+    let mut quirk_menu = QuirkMenu::new();
+    // the last mouse position over the window, in window coordinates
+    let mut mouse: Option<(i32, i32)> = None;
+    let mut osd = Osd::new();
+    let mut crt = Crt::new(config.crt_persistence);
+
+    // only the CRT texture is filtered, set the hint just while creating it
+    let texture_creator = canvas.texture_creator();
+    sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "linear");
+    let mut crt_texture = texture_creator
+        .create_texture_streaming(PixelFormatEnum::RGBA32, crt::TEXTURE_WIDTH, crt::TEXTURE_HEIGHT)
+        .unwrap();
+    sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "nearest");
+    sdl_context.mouse().show_cursor(!window_mode.is_fullscreen());
+    let mut timestep = Timestep::new(Instant::now());
+    let mut dropped = Vec::new();
+    let mut watcher = None;
+
+    if config.is_watch {
+        if netplay.is_some() {
+            eprintln!("--watch doesn't work with netplay, ignoring");
+        } else if frontend::is_url(&config.rom) {
+            eprintln!("--watch only works with local files, ignoring");
+        } else {
+            watcher = Some(RomWatcher::new(PathBuf::from(&config.rom), Instant::now()));
+        }
+    }
+
+    // true when quitting should go back to the browser rather than exit
+    let mut is_back = false;
+
+    // key events are timed by when SDL queued them, not when they're read
+    let mut latency = config.is_latency_test.then(LatencyProbe::new);
+    let timer = sdl_context.timer().unwrap();
+
+    if latency.is_some() {
+        println!("Press any CHIP-8 key, let go and repeat, then close the window for the results");
+    }
+
+    'running: loop {
         for event in event_pump.poll_iter() {
             match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => return,
+                Event::Quit { .. } => break 'running,
+                Event::DropFile { filename, .. } => dropped.push(filename),
+                Event::MouseMotion { x, y, .. } => mouse = Some((x, y)),
+                Event::Window { win_event: WindowEvent::Leave, .. } => mouse = None,
+                // onto another monitor, maybe at another rate
+                Event::Window { win_event: WindowEvent::Moved(..), .. } => {
+                    let moved_to = canvas.window().display_index().ok();
+
+                    if moved_to != display {
+                        display = moved_to;
+                        let refresh_rate = display_refresh_rate(canvas.window());
+                        let pacing = Pacing::choose(refresh_rate).with_renderer(has_vsync);
+                        frame_repeat = FrameRepeat::new(pacing);
+                        println!("{}", pacing::describe(refresh_rate, pacing));
+                    }
+                },
+                // a paused screen can be clicked to find a pixel's coordinates for a trace
+                Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } if chip8.is_paused() => {
+                    if let Some((x, y)) = hovered_pixel(&canvas, (x, y)) {
+                        let message = inspect::describe(&chip8, x, y);
+                        println!("Pixel {}", message);
+                        osd.show(message, Instant::now());
+                    }
+                },
                 Event::KeyDown {
-                    keycode: Some(key), ..
+                    timestamp,
+                    keycode: Some(key),
+                    keymod,
+                    repeat,
+                    ..
                 } => {
-                    if let Some(key_index) = key_to_button(key) {
+                    if let Some(attract) = attract.as_mut() {
+                        attract.player_input(Instant::now(), &mut chip8, &keypad);
+                    }
+
+                    let is_shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                    let is_alt = keymod.intersects(Mod::LALTMOD | Mod::RALTMOD);
+
+                    if key == Keycode::Return && is_alt {
+                        toggle_fullscreen(&mut canvas, &mut window_mode, config.scale);
+                        sdl_context.mouse().show_cursor(!window_mode.is_fullscreen());
+                    // the open quirk menu keeps the arrows, Enter and Escape to itself
+                    } else if quirk_menu.is_open() && matches!(key, Keycode::Up | Keycode::Down | Keycode::Return | Keycode::Escape) {
+                        match key {
+                            Keycode::Up => quirk_menu.up(),
+                            Keycode::Down => quirk_menu.down(),
+                            Keycode::Return => {
+                                let quirk = quirk_menu.selected();
+                                let is_enabled = !chip8.quirks().get(quirk);
+                                change_quirk(&mut chip8, quirk, is_enabled, &mut osd, input_recording.as_mut());
+                            },
+                            _ => quirk_menu.close(),
+                        }
+                    // shift + 1-9 picks the save slot instead of pressing the keypad
+                    } else if let Some(slot) = slot_key(key).filter(|_| is_shift) {
+                        if let Some(slots) = slots.as_mut() {
+                            slots.select(slot).unwrap();
+                            println!("Save slot {}", slot);
+                        }
+                    } else if let Some(key_index) = config.bindings.button(key) {
+                        keypad[key_index] = true;
                         chip8.keypress(key_index, true);
+
+                        if let Some(probe) = latency.as_mut().filter(|_| !repeat) {
+                            let queued = Duration::from_millis(timer.ticks().saturating_sub(timestamp) as u64);
+                            let now = Instant::now();
+                            probe.key_pressed(now.checked_sub(queued).unwrap_or(now));
+                        }
+
+                        if let Some(script) = input_recording.as_mut() {
+                            script.press(chip8.frame_count() as usize, key_index, true);
+                        }
+                    } else {
+                        let action = config.bindings.action(key);
+
+                        match action.filter(|action| netplay.is_none() || !action.is_emulation_changing()) {
+                            Some(Action::Quit) => {
+                                is_back = is_browsing;
+                                break 'running;
+                            },
+                            Some(Action::Reset) => chip8.soft_reset(),
+                            Some(Action::Pause) => chip8.set_paused(!chip8.is_paused()),
+                            Some(Action::FrameAdvance) if chip8.is_paused() => {
+                                chip8.advance_frame(config.ticks_per_frame);
+
+                                if let Some(fault) = chip8.take_fault() {
+                                    show_crash(&RunError::from_fault(&chip8, &fault), &mut osd);
+                                }
+                            },
+                            Some(Action::Step) if chip8.is_paused() => {
+                                if let Err(fault) = chip8.tick() {
+                                    show_crash(&RunError::from_fault(&chip8, &fault), &mut osd);
+                                }
+                            },
+                            Some(Action::SaveState) => match slots.as_ref() {
+                                Some(slots) => match slots.save(&chip8) {
+                                    Ok(_) => println!("Saved slot {}", slots.slot()),
+                                    Err(message) => eprintln!("{}", message),
+                                },
+                                None => eprintln!("Unable to find a data directory for save states"),
+                            },
+                            Some(Action::LoadState) => match slots.as_ref() {
+                                Some(slots) => match slots.load(&mut chip8) {
+                                    Ok(_) => println!("Loaded slot {}", slots.slot()),
+                                    Err(message) => eprintln!("{}", message),
+                                },
+                                None => eprintln!("Unable to find a data directory for save states"),
+                            },
+                            Some(Action::Rewind) => is_rewinding = true,
+                            Some(Action::Screenshot) => {
+                                let scale = config.screenshot_scale.unwrap_or_else(|| viewport(&canvas).scale);
+
+                                match screenshot::save(&chip8, &config.screenshot_dir, &config.rom, scale, palette) {
+                                    Ok(path) => println!("Saved screenshot to {}", path.display()),
+                                    Err(message) => eprintln!("{}", message),
+                                }
+                            },
+                            Some(Action::RecordGif) => match gif.take() {
+                                Some(capture) => finish_gif(capture),
+                                None => {
+                                    let scale = config.screenshot_scale.unwrap_or_else(|| viewport(&canvas).scale);
+                                    let dir = &config.screenshot_dir;
+
+                                    match screenshot::start_gif(dir, &config.rom, scale, palette, config.gif_fps) {
+                                        Ok(capture) => {
+                                            println!("Recording GIF, press the record key again to stop");
+                                            gif = Some(capture);
+                                        },
+                                        Err(message) => eprintln!("{}", message),
+                                    }
+                                },
+                            },
+                            Some(Action::SaveReplay) => {
+                                let scale = config.screenshot_scale.unwrap_or_else(|| viewport(&canvas).scale);
+                                let screens = chip8.replay_screens();
+                                let dir = &config.screenshot_dir;
+
+                                match screenshot::save_replay(&screens, dir, &config.rom, scale, palette, config.gif_fps) {
+                                    Ok(path) => println!("Saved the last {:.0} seconds to {}", screens.len() as f64 / 60.0, path.display()),
+                                    Err(message) => eprintln!("{}", message),
+                                }
+                            },
+                            Some(Action::DumpState) => save_dump(&chip8, &config, "hotkey"),
+                            Some(Action::CyclePalette) => {
+                                palette = palette.next();
+                                println!("Palette: {}", palette);
+
+                                if let Err(message) = rom_overrides::remember(&chip8, |saved| saved.palette = Some(palette.to_string())) {
+                                    eprintln!("{}", message);
+                                }
+                            },
+                            Some(Action::ToggleCrt) => {
+                                is_crt = !is_crt;
+                                crt.clear();
+                            },
+                            Some(Action::ToggleOverlay) => is_overlay = !is_overlay,
+                            Some(Action::ToggleGrid) => is_grid = !is_grid,
+                            Some(Action::ToggleZoom) => is_zoom = !is_zoom,
+                            Some(Action::ToggleKeypad) => is_keypad = !is_keypad,
+                            Some(Action::ToggleHeatmap) => {
+                                memory_heat = if memory_heat.is_some() { None } else { Some(MemoryHeat::new()) };
+                                chip8.set_heatmap_enabled(memory_heat.is_some());
+                            },
+                            Some(Action::QuirkMenu) => quirk_menu.toggle(),
+                            Some(Action::Turbo) => chip8.set_speed_multiplier(config.turbo_speed as f64),
+                            Some(Action::SpeedUp) => {
+                                let ticks_per_frame = speed::faster(config.ticks_per_frame);
+                                change_speed(&mut config, ticks_per_frame, &chip8, &mut osd, input_recording.as_mut());
+                            },
+                            Some(Action::SpeedDown) => {
+                                let ticks_per_frame = speed::slower(config.ticks_per_frame);
+                                change_speed(&mut config, ticks_per_frame, &chip8, &mut osd, input_recording.as_mut());
+                            },
+                            Some(Action::SpeedReset) => {
+                                let ticks_per_frame = config.default_ticks_per_frame;
+                                change_speed(&mut config, ticks_per_frame, &chip8, &mut osd, input_recording.as_mut());
+                            },
+                            _ => (),
+                        }
                     }
                 },
                 Event::KeyUp {
                     keycode: Some(key), ..
                 } => {
-                    if let Some(key_index) = key_to_button(key) {
+                    if let Some(key_index) = config.bindings.button(key) {
+                        keypad[key_index] = false;
                         chip8.keypress(key_index, false);
-                    } else if key == Keycode::N {
-                        chip8.reset();
-                        chip8.load(&buffer);
+
+                        if let Some(script) = input_recording.as_mut() {
+                            script.press(chip8.frame_count() as usize, key_index, false);
+                        }
+                    } else {
+                        match config.bindings.action(key) {
+                            Some(Action::Rewind) => is_rewinding = false,
+                            Some(Action::Turbo) => chip8.set_speed_multiplier(1.0),
+                            _ => (),
+                        }
                     }
                 }
                 _ => (),
             }
         }
 
-        for _ in 0..TICKS_PER_FRAME {
-            chip8.tick();
+        if netplay.is_some() && !dropped.is_empty() {
+            eprintln!("Can't load another ROM during netplay");
+            dropped.clear();
         }
 
-        chip8.tick_timers();
-        draw_screen(&chip8, &mut canvas);
-    }
-}
+        // files dropped together arrive in the same batch of events, only the first one is played
+        let mut to_load = dropped.first().cloned();
 
-fn key_to_button(key: Keycode) -> Option<usize> {
-    match key {
-        Keycode::Num1 => Some(0x1),
-        Keycode::Num2 => Some(0x2),
-        Keycode::Num3 => Some(0x3),
-        Keycode::Num4 => Some(0xC),
-        Keycode::Q => Some(0x4),
-        Keycode::W => Some(0x5),
-        Keycode::E => Some(0x6),
-        Keycode::R => Some(0xD),
-        Keycode::A => Some(0x7),
-        Keycode::S => Some(0x8),
-        Keycode::D => Some(0x9),
-        Keycode::F => Some(0xE),
-        Keycode::Z => Some(0xA),
-        Keycode::X => Some(0x0),
-        Keycode::C => Some(0xB),
-        Keycode::V => Some(0xF),
-        _ => None,
-    }
-}
+        if dropped.len() > 1 {
+            eprintln!("Dropped {} files, loading only {}", dropped.len(), dropped[0]);
+        }
 
-fn draw_screen(chip8: &Chip8, canvas: &mut Canvas<Window>) {
-    // clear canvas as black
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
-    canvas.clear();
+        dropped.clear();
 
-    let screen_buffer = chip8.get_display();
+        if to_load.is_none() && watcher.as_mut().is_some_and(|watcher| watcher.poll(Instant::now())) {
+            to_load = Some(config.rom.clone());
+        }
 
-    // now set draw color to white, interate through each point and see if it should be drawn
-    canvas.set_draw_color(Color::RGB(50, 169, 86));
+        // a game that ends in a jump to itself has nothing more to show
+        let is_halted = HaltReason::of(&chip8) == HaltReason::SelfJump;
+        let mut is_rotating = false;
 
-    for (i, pixel) in screen_buffer.iter().enumerate() {
-        if *pixel {
-            // convert our 1d array's index into a 2D (x, y) position
-            let x = (i % SCREEN_WIDTH) as u32;
-            let y = (i / SCREEN_WIDTH) as u32;
+        if let Some(path) = attract.as_mut().and_then(|attract| attract.poll(Instant::now(), is_halted)) {
+            to_load = Some(path.display().to_string());
+            is_rotating = true;
+        }
 
-            // draw a rectangle at (x, y), scaled up by our SCALE value
-            let rect = Rect::new((x * SCALE) as i32, (y * SCALE) as i32, SCALE, SCALE);
-            canvas.fill_rect(rect).unwrap();
+        if let Some(path) = to_load {
+            let is_reload = path == config.rom;
+
+            match replace_rom(&mut chip8, &path) {
+                Ok(()) => {
+                    println!("{} {}", if is_reload { "Reloaded" } else { "Loaded" }, path);
+                    slots = save_slots::default_dir()
+                        .zip(chip8.loaded_rom())
+                        .map(|(dir, rom)| SaveSlots::new(dir, rom.sha256_hex()));
+                    crt.clear();
+
+                    if !is_reload && watcher.is_some() {
+                        watcher = Some(RomWatcher::new(PathBuf::from(&path), Instant::now()));
+                    }
+
+                    if let Some(attract) = attract.as_mut().filter(|_| is_rotating) {
+                        attract.start(&mut chip8);
+                    }
+
+                    config.rom = path;
+                },
+                Err(message) => eprintln!("{}", message),
+            }
         }
-    }
 
-    canvas.present();
+        if frontend::signals::take_dump_request() {
+            save_dump(&chip8, &config, "signal");
+        }
+
+        #[cfg(feature = "remote")]
+        if let Some(server) = &remote {
+            server.poll(&mut chip8);
+        }
+
+        #[cfg(feature = "viewer")]
+        if let Some(server) = &viewer {
+            server.poll(&mut chip8);
+        }
+
+        // emulate in whole 60 Hz frames for the time that passed, whatever the display's refresh rate.
+        // with vsync they're spread evenly over the presents
+        let due = timestep.frames(Instant::now());
+        let frames = match frame_repeat.as_mut() {
+            Some(repeat) => repeat.frames(due),
+            None => due,
+        };
+
+        for _ in 0..frames {
+            // one recorded frame per emulated frame keeps rewinding at real time
+            if let Some(lockstep) = netplay.as_mut() {
+                if let Err(e) = lockstep.advance(&mut chip8, &keypad, config.ticks_per_frame) {
+                    eprintln!("Netplay stopped: {}", e);
+                    netplay = None;
+                }
+            } else if is_rewinding {
+                chip8.rewind_frame();
+            } else {
+                let ticks_per_frame = match &attract {
+                    Some(attract) => attract.before_frame(&mut chip8, config.ticks_per_frame),
+                    None => config.ticks_per_frame,
+                };
+
+                // a fault pauses the game with the report on the console, instead of closing the window
+                chip8.run_frame(ticks_per_frame);
+
+                if let Some(fault) = chip8.take_fault() {
+                    show_crash(&RunError::from_fault(&chip8, &fault), &mut osd);
+                    break;
+                }
+            }
+
+            if let Some(probe) = latency.as_mut() {
+                probe.frame_ran(chip8.get_display(), Instant::now());
+            }
+
+            let samples: &[f32] = if let Some(audio) = audio.as_mut() {
+                audio.push_frame(&mut chip8)
+            } else if chip8.audio_recorder().is_some() || video.is_some() {
+                chip8.fill_audio(&mut silent_frame, frontend::audio::SAMPLE_RATE as u32);
+                &silent_frame
+            } else {
+                &[]
+            };
+
+            if let Some(capture) = gif.as_mut() {
+                if let Err(message) = capture.push_frame(&chip8) {
+                    eprintln!("{}, stopping the recording", message);
+                    gif = None;
+                }
+            }
+
+            // a failing ffmpeg only ends the capture, the game goes on
+            if let Some(capture) = video.as_mut() {
+                if let Err(message) = capture.push_frame(&chip8, palette, samples) {
+                    eprintln!("{}, stopping the recording", message);
+
+                    if let Some(capture) = video.take() {
+                        finish_video(capture);
+                    }
+                }
+            }
+        }
+
+        if let Some(heat) = memory_heat.as_mut() {
+            heat.update(chip8.heatmap(), frames);
+        }
+
+        if let Some(address) = chip8.take_breakpoint_hit() {
+            let message = format!("Breakpoint at {:03X}", address);
+            println!("{}", message);
+            osd.show(message, Instant::now());
+        }
+
+        #[cfg(feature = "viewer")]
+        if let Some(server) = viewer.as_mut() {
+            server.publish(&chip8, palette);
+        }
+
+        if is_crt {
+            let pixels = crt.frame(chip8.get_display(), palette);
+            draw_crt(&mut canvas, &mut crt_texture, pixels, config.is_scanlines);
+        } else {
+            draw_screen(&chip8, &mut canvas, palette);
+        }
+
+        if is_grid {
+            draw_grid(&mut canvas);
+        }
+
+        if is_overlay {
+            draw_overlay(&chip8, &mut canvas);
+        }
+
+        if is_keypad {
+            draw_keypad(&chip8, &mut canvas, &config.bindings.keypad_names());
+        }
+
+        if quirk_menu.is_open() {
+            draw_quirk_menu(&quirk_menu, &chip8, &mut canvas);
+        }
+
+        if let Some(heat) = &memory_heat {
+            draw_heatmap(&mut canvas, heat);
+
+            if let Some(point) = mouse {
+                let point = drawable_point(point, canvas.window().size(), canvas.output_size().unwrap());
+
+                if let Some((column, row)) = heatmap_viewport(&canvas).pixel_at(point) {
+                    let label = heatmap_view::describe(&chip8, heatmap_view::address(column, row));
+                    draw_tooltip(&mut canvas, &label, point);
+                }
+            }
+        }
+
+        if let Some(point) = mouse.filter(|_| is_zoom) {
+            if let Some((x, y)) = hovered_pixel(&canvas, point) {
+                let zoom = inspect::zoom(&chip8, x, y);
+                let point = drawable_point(point, canvas.window().size(), canvas.output_size().unwrap());
+                draw_zoom(&mut canvas, &zoom, &inspect::describe(&chip8, x, y), palette, point);
+            }
+        }
+
+        if let Some(message) = osd.message(Instant::now()) {
+            draw_osd(&mut canvas, message);
+        }
+
+        canvas.present();
+
+        if let Some(probe) = latency.as_mut() {
+            probe.presented(Instant::now());
+        }
+
+        let now = Instant::now();
+        rates.record(now, chip8.instruction_count());
+
+        if rates.is_due(now) {
+            let (fps, ips) = rates.rates();
+            let status = title::Status {
+                name: Some(title::display_name(&config.rom)),
+                fps,
+                ips,
+                is_paused: chip8.is_paused(),
+                is_turbo: chip8.speed_multiplier() > 1.0,
+                is_rewinding,
+                is_recording: gif.is_some(),
+            };
+            let new_title = title::format(&status);
+
+            if new_title != window_title {
+                canvas.window_mut().set_title(&new_title).unwrap();
+                window_title = new_title;
+            }
+        }
+
+        if let Some(limiter) = limiter.as_mut() {
+            limiter.wait(&SystemClock);
+        }
+    }
+
+    if let Some(capture) = gif {
+        finish_gif(capture);
+    }
+
+    if let Some(capture) = video {
+        finish_video(capture);
+    }
+
+    if let Some(probe) = &latency {
+        match probe.summary() {
+            Some(summary) => print!("{}", summary),
+            None => println!("No key presses were timed"),
+        }
+    }
+
+    if let (Some(path), Some(recorder)) = (&config.record_audio, chip8.detach_audio_recorder()) {
+        if let Err(e) = recorder.save_wav(path) {
+            eprintln!("Unable to save audio recording to {}: {}", path.display(), e);
+        }
+    }
+
+    if let (Some(path), Some(script)) = (&config.record_input, &input_recording) {
+        if let Err(e) = fs::write(path, script.to_string()) {
+            eprintln!("Unable to save input recording to {}: {}", path.display(), e);
+        }
+    }
+
+    if let Some(path) = &config.dump_state_on_exit {
+        if let Err(e) = fs::write(path, headless::state_json(&chip8)) {
+            eprintln!("Unable to save the state to {}: {}", path.display(), e);
+        }
+    }
+
+    is_back
+}
+
+fn show_crash(error: &RunError, osd: &mut Osd) {
+    eprintln!("{}", error.report);
+    osd.show(format!("Crashed at {:03X}: {}", error.pc, error.message), Instant::now());
+}
+
+fn save_dump(chip8: &Chip8, config: &Config, reason: &str) {
+    match screenshot::save_dump(chip8, &config.screenshot_dir, &config.rom, reason) {
+        Ok(path) => println!("Dumped the state to {}", path.display()),
+        Err(message) => eprintln!("{}", message),
+    }
+}
+
+// a speed picked with the speed keys: shown, recorded and saved for the ROM
+fn change_speed(config: &mut Config, ticks_per_frame: usize, chip8: &Chip8, osd: &mut Osd, recording: Option<&mut Script>) {
+    config.ticks_per_frame = ticks_per_frame;
+    osd.show(speed::describe(ticks_per_frame), Instant::now());
+
+    if let Some(script) = recording {
+        script.set_speed(chip8.frame_count() as usize, ticks_per_frame);
+    }
+
+    // back at the default there's nothing to restore next time
+    let saved = (ticks_per_frame != config.default_ticks_per_frame).then_some(ticks_per_frame);
+
+    if let Err(message) = rom_overrides::remember(chip8, |overrides| overrides.speed = saved) {
+        eprintln!("{}", message);
+    }
+}
+
+// a quirk flipped in the quirk menu. events are handled between frames, so the next frame is the
+// first to run with it. shown, recorded and saved for the ROM like a speed change
+fn change_quirk(chip8: &mut Chip8, quirk: Quirk, is_enabled: bool, osd: &mut Osd, recording: Option<&mut Script>) {
+    chip8.set_quirk(quirk, is_enabled);
+    let message = quirk_menu::describe(quirk, is_enabled);
+    println!("{}", message);
+    osd.show(message, Instant::now());
+
+    if let Some(script) = recording {
+        script.set_quirk(chip8.frame_count() as usize, quirk, is_enabled);
+    }
+
+    let saved = rom_overrides::remember(chip8, |overrides| {
+        overrides.quirks.insert(quirk.name().to_string(), is_enabled);
+    });
+
+    if let Err(message) = saved {
+        eprintln!("{}", message);
+    }
+}
+
+// the exit code, or why the run couldn't start
+fn run_headless(args: &HeadlessArgs) -> Result<i32, String> {
+    let rom = read_loadable_rom(&args.rom)?;
+
+    let mut script = match &args.input_script {
+        Some(path) => {
+            let text = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+            headless::parse_script(&text).map_err(|e| format!("{}: {}", path.display(), e))?
+        },
+        None => Script::default(),
+    };
+    script.start_with_quirks(args.quirks());
+
+    let seed = args.seed.or(script.seed).unwrap_or(headless::DEFAULT_SEED);
+
+    #[cfg(all(feature = "remote", feature = "viewer"))]
+    if args.remote.is_some() && args.viewer.is_some() {
+        return Err("--remote and --viewer can't be used together in headless runs".to_string());
+    }
+
+    #[cfg(all(feature = "scripting", feature = "remote"))]
+    if args.script.is_some() && args.remote.is_some() {
+        return Err("--script only runs with --frames, not --remote".to_string());
+    }
+
+    #[cfg(all(feature = "scripting", feature = "viewer"))]
+    if args.script.is_some() && args.viewer.is_some() {
+        return Err("--script only runs with --frames, not --viewer".to_string());
+    }
+
+    #[cfg(feature = "remote")]
+    if let Some(address) = &args.remote {
+        let server = frontend::listen_remote(address)?;
+        let mut chip8 = Chip8::with_seed(seed);
+        chip8.set_quirks(args.quirks());
+        chip8.load(&rom).map_err(|e| format!("Unable to load {}: {}", args.rom, e))?;
+        server.serve(&mut chip8);
+
+        write_dumps(args, &chip8)?;
+        if args.print_hash {
+            println!("{}", conformance::screen_hash(chip8.get_display()));
+        }
+        return Ok(report::EXIT_PASS);
+    }
+
+    #[cfg(feature = "viewer")]
+    if let Some(address) = &args.viewer {
+        let mut server = frontend::listen_viewer(address)?;
+        let mut chip8 = Chip8::with_seed(seed);
+        chip8.set_quirks(args.quirks());
+        let mut limiter = FrameLimiter::new(60);
+        chip8.load(&rom).map_err(|e| format!("Unable to load {}: {}", args.rom, e))?;
+
+        for _ in 0..args.frames {
+            server.poll(&mut chip8);
+            chip8.run_frame(args.speed);
+            server.publish(&chip8, Palette::default());
+            limiter.wait(&SystemClock);
+        }
+
+        write_dumps(args, &chip8)?;
+        if args.print_hash {
+            println!("{}", conformance::screen_hash(chip8.get_display()));
+        }
+        return Ok(report::EXIT_PASS);
+    }
+
+    let options = Options { frames: args.frames, ticks_per_frame: args.speed, seed, script };
+    let expectations = Expectations { screen_hash: args.expect_hash.clone(), halt: args.expect_halt };
+    #[cfg(feature = "scripting")]
+    let (chip8, report) = match &args.script {
+        Some(path) => {
+            let mut host = frontend::load_script(path)?;
+            let run = chip8_emu::scripting::run(&rom, &options, &expectations, &mut host);
+
+            // stdout is for the report and the hash
+            for line in host.take_output() {
+                eprintln!("{}", line);
+            }
+            run
+        },
+        None => report::run(&rom, &options, &expectations),
+    };
+    #[cfg(not(feature = "scripting"))]
+    let (chip8, report) = report::run(&rom, &options, &expectations);
+    write_dumps(args, &chip8)?;
+
+    if args.print_hash {
+        println!("{}", report.screen_hash);
+    }
+
+    match (args.report, &args.report_file) {
+        (Some(ReportFormat::Json), Some(path)) => write_file(path, report.to_json())?,
+        (Some(ReportFormat::Json), None) => print!("{}", report.to_json()),
+        // without a report the failures are all there is to go on
+        (None, _) => {
+            for failure in &report.failures {
+                eprintln!("{}", failure);
+            }
+        },
+    }
+
+    Ok(report.exit_code())
+}
+
+fn write_dumps(args: &HeadlessArgs, chip8: &Chip8) -> Result<(), String> {
+    if let Some(path) = &args.dump_screen {
+        write_file(path, headless::pbm(chip8.get_display()))?;
+    }
+
+    if let Some(path) = &args.dump_state {
+        write_file(path, headless::state_json(chip8))?;
+    }
+
+    Ok(())
+}
+
+fn write_file(path: &Path, contents: String) -> Result<(), String> {
+    fs::write(path, contents).map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+}
+
+fn run_bench(rom: Option<&str>, seconds: u64, is_json: bool) {
+    let buffer = match rom {
+        Some(rom) => read_rom_or_exit(rom),
+        None => bench::BENCH_ROM.to_vec(),
+    };
+
+    let settings = Settings { duration: Duration::from_secs(seconds), ..Settings::default() };
+    let measurements = bench::run(&bench::SystemClock, &buffer, &settings).unwrap_or_else(|error| {
+        eprintln!("Unable to load the ROM: {}", error);
+        process::exit(1);
+    });
+    let machine = Machine::current();
+
+    if is_json {
+        print!("{}", bench::format_json(&machine, &measurements));
+    } else {
+        print!("{}", bench::format_table(&machine, &measurements));
+    }
+}
+
+fn run_conformance(dir: &Path, is_bless: bool) {
+    let results = conformance::run_suite(dir, &Goldens::builtin());
+
+    if is_bless {
+        print!("{}", conformance::bless(&results));
+        return;
+    }
+
+    print!("{}", conformance::format_table(&results));
+
+    if results.iter().any(|result| result.is_failure()) {
+        process::exit(1);
+    }
+}
+
+// Ok(false) when a ROM regressed against the baseline, or crashed when there's none to compare with
+fn run_sweep(args: &SweepArgs) -> Result<bool, String> {
+    let mut roms = Vec::new();
+
+    for entry in browser::scan(&args.dir)? {
+        let name = entry.path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+
+        match fs::read(&entry.path) {
+            Ok(rom) => roms.push((name, rom)),
+            Err(e) => eprintln!("Skipping {}: {}", entry.path.display(), e),
+        }
+    }
+
+    let options = Options { frames: args.frames, ticks_per_frame: args.speed, seed: args.seed, script: Script::default() };
+    let started = Instant::now();
+    // crashes end up in the results, the default hook would print each one as it happened too
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let current = sweep::run(&roms, &options);
+    panic::set_hook(default_hook);
+    let crashed = current.roms.iter().filter(|result| result.is_failure()).count();
+    eprintln!("Ran {} ROMs in {:.1}s, {} crashed", roms.len(), started.elapsed().as_secs_f64(), crashed);
+
+    let Some(path) = &args.baseline else {
+        for result in &current.roms {
+            let outcome = result.error.clone().unwrap_or_else(|| format!("{} {}", result.halt, result.screen_hash));
+            println!("{}: {}", result.rom, outcome);
+        }
+
+        return Ok(crashed == 0);
+    };
+
+    if args.bless || !path.exists() {
+        write_file(path, current.to_json())?;
+        println!("Saved the results to {}", path.display());
+        return Ok(true);
+    }
+
+    let text = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+    let baseline = Sweep::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    for difference in current.setting_differences(&baseline) {
+        eprintln!("Warning: comparing against {} with {}", path.display(), difference);
+    }
+
+    let changes = sweep::compare(&baseline, &current);
+    print!("{}", sweep::format_changes(&changes, current.roms.len()));
+
+    Ok(!changes.iter().any(|change| change.is_regression()))
+}
+
+// the exit code, or why the audit couldn't start
+fn run_audit(args: &AuditArgs) -> Result<i32, String> {
+    let rom = read_loadable_rom(&args.rom)?;
+
+    let script = match &args.input_script {
+        Some(path) => {
+            let text = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+            headless::parse_script(&text).map_err(|e| format!("{}: {}", path.display(), e))?
+        },
+        None => Script::default(),
+    };
+
+    let seed = args.seed.or(script.seed).unwrap_or(headless::DEFAULT_SEED);
+    let options = Options { frames: args.frames, ticks_per_frame: args.speed, seed, script };
+
+    if let Some(path) = &args.record {
+        // a crash ends the log, the default hook would print it as well
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(|_| {}));
+        let hashes = audit::hash_log(&rom, &options);
+        panic::set_hook(default_hook);
+
+        write_file(path, audit::format_log(&hashes))?;
+        println!("Saved the state hashes of {} frames to {}", hashes.len(), path.display());
+        return Ok(report::EXIT_PASS);
+    }
+
+    let log = match &args.against {
+        Some(path) => {
+            let text = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+            Some(audit::parse_log(&text).map_err(|e| format!("{}: {}", path.display(), e))?)
+        },
+        None => None,
+    };
+
+    // a crash is part of the result, the default hook would print it as it happened too
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = match &log {
+        Some(log) => audit::compare_log(&rom, &options, log),
+        None => audit::audit(&rom, &options),
+    };
+    panic::set_hook(default_hook);
+
+    match result {
+        Ok(agreement) => {
+            println!("Deterministic over {} frames", agreement.frames);
+            if let Some(crash) = agreement.crash {
+                println!("Both runs crashed on the last one: {}", crash);
+            }
+            Ok(report::EXIT_PASS)
+        },
+        Err(divergence) => {
+            println!("{}", divergence);
+            Ok(report::EXIT_FAIL)
+        },
+    }
+}
+
+fn run_snapshot(args: &SnapshotArgs) -> Result<(), String> {
+    let rom = read_loadable_rom(&args.rom)?;
+
+    let script = match &args.input_script {
+        Some(path) => {
+            let text = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+            headless::parse_script(&text).map_err(|e| format!("{}: {}", path.display(), e))?
+        },
+        None => Script::default(),
+    };
+
+    let frames = golden::schedule(&args.at);
+    let existing: Vec<String> =
+        golden::file_names(&frames).into_iter().filter(|name| args.out.join(name).exists()).collect();
+    if !existing.is_empty() && !args.force {
+        return Err(format!("{} already has {}, --force replaces them", args.out.display(), existing.join(", ")));
+    }
+
+    let seed = args.seed.or(script.seed).unwrap_or(headless::DEFAULT_SEED);
+    let input_script = args.input_script.as_ref().map(|path| path.display().to_string());
+    let setup = Setup { seed, ticks_per_frame: args.speed, script, input_script };
+
+    // the crash is in the error, the default hook would print it as it happened too
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let captures = golden::capture(&rom, &setup, &frames);
+    panic::set_hook(default_hook);
+    let captures = captures.map_err(|e| e.to_string())?;
+
+    fs::create_dir_all(&args.out).map_err(|e| format!("Unable to create {}: {}", args.out.display(), e))?;
+    for capture in &captures {
+        write_file(&args.out.join(golden::pbm_file(capture.frame)), headless::pbm(&capture.screen))?;
+
+        let png = export_png(&capture.screen, Palette::default(), args.png_scale);
+        let path = args.out.join(golden::png_file(capture.frame));
+        fs::write(&path, png).map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+    }
+
+    let name = Path::new(&args.rom).file_name().map_or(args.rom.clone(), |name| name.to_string_lossy().into_owned());
+    let manifest = Manifest::new(&name, &rom, &setup, &captures);
+    write_file(&args.out.join(golden::MANIFEST_FILE), manifest.to_json())?;
+
+    let written: Vec<String> = captures.iter().map(|capture| capture.frame.to_string()).collect();
+    println!("Wrote the screens at frames {} to {}", written.join(", "), args.out.display());
+    Ok(())
+}
+
+fn analyze(path: &str, is_lints: bool) -> Result<i32, String> {
+    let rom = read_loadable_rom(path)?;
+    let analysis = analyze::analyze(&rom);
+
+    if !is_lints {
+        print!("{}", analysis.listing(&rom));
+        println!(
+            "{} instructions reached from 200, {} of the {} bytes are data",
+            analysis.code.len(),
+            analysis.data_bytes(),
+            rom.len()
+        );
+        return Ok(report::EXIT_PASS);
+    }
+
+    let findings = analysis.lints(&rom);
+    for finding in &findings {
+        println!("{}", finding);
+    }
+
+    Ok(if findings.is_empty() { report::EXIT_PASS } else { report::EXIT_FAIL })
+}
+
+fn run_compare(args: &CompareArgs) -> Result<i32, String> {
+    let rom = read_loadable_rom(&args.rom)?;
+
+    let script = match &args.input_script {
+        Some(path) => {
+            let text = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+            headless::parse_script(&text).map_err(|e| format!("{}: {}", path.display(), e))?
+        },
+        None => Script::default(),
+    };
+
+    let seed = args.seed.or(script.seed).unwrap_or(headless::DEFAULT_SEED);
+    let options = Options { frames: args.frames, ticks_per_frame: args.speed, seed, script };
+
+    // a crash is part of the result, the default hook would print it as it happened too
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = lockstep::compare(&rom, &options, [args.a.quirks, args.b.quirks]);
+    panic::set_hook(default_hook);
+
+    match result {
+        Ok(agreement) => {
+            println!("{} and {} agree over {} frames", args.a.name, args.b.name, agreement.frames);
+            if let Some(crash) = agreement.crash {
+                println!("Both crashed on the last one: {}", crash);
+            }
+            Ok(report::EXIT_PASS)
+        },
+        Err(divergence) => {
+            print!("{}", lockstep::report(&divergence, [&args.a.name, &args.b.name]));
+            Ok(report::EXIT_FAIL)
+        },
+    }
+}
+
+fn run_trace(args: &TraceArgs) -> Result<(), String> {
+    let rom = read_loadable_rom(&args.rom)?;
+
+    let script = match &args.input_script {
+        Some(path) => {
+            let text = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+            headless::parse_script(&text).map_err(|e| format!("{}: {}", path.display(), e))?
+        },
+        None => Script::default(),
+    };
+
+    let seed = args.seed.or(script.seed).unwrap_or(headless::DEFAULT_SEED);
+    let options = Options { frames: args.frames, ticks_per_frame: args.speed, seed, script };
+
+    let file = fs::File::create(&args.out).map_err(|e| format!("Unable to create {}: {}", args.out.display(), e))?;
+    let mut log = TraceLog::new(io::BufWriter::new(file), args.format.into(), args.filter.clone(), args.max_bytes);
+
+    // the crash is in the error, the default hook would print it as it happened too
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let run = trace_log::run(&rom, &options, &mut log);
+    panic::set_hook(default_hook);
+    run.map_err(|e| match e {
+        TraceError::Crashed(_) => format!("{}, the trace up to there is in {}", e, args.out.display()),
+        e => format!("{}: {}", args.out.display(), e),
+    })?;
+
+    if log.is_truncated() {
+        eprintln!("The trace reached {} bytes and was cut short, --max-bytes allows more", log.bytes());
+    }
+
+    println!("Wrote {} instructions to {}", log.lines(), args.out.display());
+    Ok(())
+}
+
+fn export_vectors(args: &VectorArgs, instructions: usize, output: Option<&Path>) -> Result<(), String> {
+    let rom = read_loadable_rom(&args.rom)?;
+
+    let (vectors, crash) = vectors::export(&rom, args.seed, args.speed, instructions);
+    let text: String = vectors.iter().map(|vector| vector.to_json() + "\n").collect();
+
+    match output {
+        Some(path) => write_file(path, text)?,
+        None => print!("{}", text),
+    }
+
+    match crash {
+        Some(message) => Err(format!("The emulator crashed after {} instructions: {}", vectors.len(), message)),
+        None => Ok(()),
+    }
+}
+
+fn verify_vectors(args: &VectorArgs, path: &Path) -> Result<usize, String> {
+    let rom = read_loadable_rom(&args.rom)?;
+    let text = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+
+    vectors::verify(&rom, args.seed, args.speed, &text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// swap the cartridge, the current game keeps running if the file isn't a usable rom
+fn replace_rom(chip8: &mut Chip8, path: &str) -> Result<(), String> {
+    let buffer = frontend::read_rom(path)?;
+
+    chip8.reset();
+    chip8.load_named(&buffer, path).map_err(|e| format!("Unable to load {}: {}", path, e))?;
+    chip8.clear_rewind();
+    chip8.clear_replay();
+
+    Ok(())
+}
+
+fn slot_key(key: Keycode) -> Option<u8> {
+    let digit = key as i32 - Keycode::Num0 as i32;
+
+    (1..=save_slots::SLOTS as i32).contains(&digit).then_some(digit as u8)
+}
+
+fn finish_gif(capture: GifCapture) {
+    match capture.finish() {
+        Ok(path) => println!("Saved GIF to {}", path.display()),
+        Err(message) => eprintln!("{}", message),
+    }
+}
+
+fn finish_video(capture: VideoCapture) {
+    match capture.finish() {
+        Ok(path) => println!("Saved video to {}", path.display()),
+        Err(message) => eprintln!("{}", message),
+    }
+}
+
+// of the display the window is on, None when SDL can't say. some drivers report 0 for that
+fn display_refresh_rate(window: &Window) -> Option<u32> {
+    let mode = window.subsystem().current_display_mode(window.display_index().ok()?).ok()?;
+
+    (mode.refresh_rate > 0).then_some(mode.refresh_rate as u32)
+}
+
+fn toggle_fullscreen(canvas: &mut Canvas<Window>, window_mode: &mut WindowMode, scale: u32) {
+    let window = canvas.window_mut();
+    let (x, y) = window.position();
+    let (width, height) = window.size();
+
+    let result = match window_mode.toggle(Geometry { x, y, width, height }) {
+        Transition::EnterFullscreen => window.set_fullscreen(FullscreenType::Desktop),
+        Transition::LeaveFullscreen(windowed) => window.set_fullscreen(FullscreenType::Off).and_then(|()| {
+            match windowed {
+                Some(geometry) => {
+                    window.set_position(WindowPos::Positioned(geometry.x), WindowPos::Positioned(geometry.y));
+                    window.set_size(geometry.width, geometry.height).map_err(|e| e.to_string())
+                },
+                // started fullscreen, fall back to the configured scale
+                None => {
+                    let size = window.set_size(SCREEN_WIDTH as u32 * scale, SCREEN_HEIGHT as u32 * scale);
+                    window.set_position(WindowPos::Centered, WindowPos::Centered);
+                    size.map_err(|e| e.to_string())
+                },
+            }
+        }),
+    };
+
+    if let Err(message) = result {
+        eprintln!("Unable to switch fullscreen: {}", message);
+    }
+}
+
+fn viewport(canvas: &Canvas<Window>) -> Viewport {
+    Viewport::fit(canvas.output_size().unwrap(), (SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32))
+}
+
+// the emulated pixel under a mouse position, None over the bars
+fn hovered_pixel(canvas: &Canvas<Window>, point: (i32, i32)) -> Option<(usize, usize)> {
+    let point = drawable_point(point, canvas.window().size(), canvas.output_size().unwrap());
+
+    viewport(canvas).pixel_at(point).map(|(x, y)| (x as usize, y as usize))
+}
+
+fn draw_crt(canvas: &mut Canvas<Window>, texture: &mut Texture, pixels: &[u8], is_scanlines: bool) {
+    let viewport = viewport(canvas);
+    let target = Rect::new(viewport.x, viewport.y, viewport.width, viewport.height);
+
+    texture.update(None, pixels, crt::TEXTURE_WIDTH as usize * 4).unwrap();
+
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+    canvas.copy(texture, None, target).unwrap();
+
+    if is_scanlines {
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, crt::SCANLINE_ALPHA));
+
+        for y in crt::scanline_rows(viewport.y, viewport.height) {
+            canvas.draw_line((target.left(), y), (target.right() - 1, y)).unwrap();
+        }
+
+        canvas.set_blend_mode(BlendMode::None);
+    }
+}
+
+fn draw_screen(chip8: &Chip8, canvas: &mut Canvas<Window>, palette: Palette) {
+    let (background, foreground) = (palette.background, palette.foreground);
+    let viewport = viewport(canvas);
+    let scale = viewport.scale;
+
+    // black bars around the image, then the background color under it
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+    canvas.set_draw_color(Color::RGB(background.0, background.1, background.2));
+    canvas
+        .fill_rect(Rect::new(viewport.x, viewport.y, viewport.width, viewport.height))
+        .unwrap();
+
+    let screen_buffer = chip8.get_display();
+
+    // now set draw color to the foreground, interate through each point and see if it should be drawn
+    canvas.set_draw_color(Color::RGB(foreground.0, foreground.1, foreground.2));
+
+    for (i, pixel) in screen_buffer.iter().enumerate() {
+        if *pixel {
+            // convert our 1d array's index into a 2D (x, y) position
+            let x = (i % SCREEN_WIDTH) as u32;
+            let y = (i / SCREEN_WIDTH) as u32;
+
+            // draw a rectangle at (x, y), scaled up and moved into the viewport
+            let (left, top) = viewport.pixel_origin(x, y);
+            let rect = Rect::new(left, top, scale, scale);
+            canvas.fill_rect(rect).unwrap();
+        }
+    }
+}
+
+// thin lines between the scaled pixels, left out when they'd cover most of the image
+fn draw_grid(canvas: &mut Canvas<Window>) {
+    let viewport = viewport(canvas);
+
+    if viewport.scale < inspect::MIN_GRID_SCALE {
+        return;
+    }
+
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(Color::RGBA(128, 128, 128, GRID_ALPHA));
+
+    for x in 1..SCREEN_WIDTH as u32 {
+        let (left, _) = viewport.pixel_origin(x, 0);
+        canvas.fill_rect(Rect::new(left, viewport.y, 1, viewport.height)).unwrap();
+    }
+
+    for y in 1..SCREEN_HEIGHT as u32 {
+        let (_, top) = viewport.pixel_origin(0, y);
+        canvas.fill_rect(Rect::new(viewport.x, top, viewport.width, 1)).unwrap();
+    }
+
+    canvas.set_blend_mode(BlendMode::None);
+}
+
+// the pixels around the mouse blown up next to it, with the hovered one outlined
+fn draw_zoom(canvas: &mut Canvas<Window>, zoom: &Zoom, label: &str, palette: Palette, point: (i32, i32)) {
+    let viewport = viewport(canvas);
+    let size = (viewport.scale / 6).max(2);
+    let margin = size as i32 * 2;
+    let cell = (viewport.scale * 2).clamp(8, 40);
+    let side = cell * inspect::ZOOM_SIZE as u32;
+    let width = side.max(font::text_width(label) * size) + margin as u32 * 2;
+    let height = side + font::LINE_HEIGHT * size + margin as u32 * 2;
+    let (left, top) = tooltip_origin(canvas, point, cell, (width, height));
+
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, OVERLAY_ALPHA));
+    canvas.fill_rect(Rect::new(left, top, width, height)).unwrap();
+    canvas.set_blend_mode(BlendMode::None);
+
+    for (row, line) in zoom.pixels.iter().enumerate() {
+        for (column, &pixel) in line.iter().enumerate() {
+            let (r, g, b) = if pixel { palette.foreground } else { palette.background };
+            let x = left + margin + (column as u32 * cell) as i32;
+            let y = top + margin + (row as u32 * cell) as i32;
+
+            // a pixel's gap on the right and bottom keeps the cells apart
+            canvas.set_draw_color(Color::RGB(r, g, b));
+            canvas.fill_rect(Rect::new(x, y, cell - 1, cell - 1)).unwrap();
+
+            if (zoom.left + column, zoom.top + row) == zoom.hovered {
+                canvas.set_draw_color(Color::RGB(255, 0, 0));
+                canvas.draw_rect(Rect::new(x, y, cell - 1, cell - 1)).unwrap();
+            }
+        }
+    }
+
+    canvas.set_draw_color(Color::RGB(255, 255, 255));
+    draw_text(canvas, label, left + margin, top + margin + side as i32 + size as i32, size);
+}
+
+// where a box `size` big goes for the mouse at `point`: below and right of it, `gap` away, and
+// flipped over it near the window's edges
+fn tooltip_origin(canvas: &Canvas<Window>, point: (i32, i32), gap: u32, size: (u32, u32)) -> (i32, i32) {
+    let (drawable_width, drawable_height) = canvas.output_size().unwrap();
+    let place = |at: i32, length: u32, limit: u32| {
+        if at + gap as i32 + length as i32 <= limit as i32 {
+            at + gap as i32
+        } else {
+            (at - gap as i32 - length as i32).max(0)
+        }
+    };
+
+    (place(point.0, size.0, drawable_width), place(point.1, size.1, drawable_height))
+}
+
+// a line of text by the mouse, where the zoom would go
+fn draw_tooltip(canvas: &mut Canvas<Window>, label: &str, point: (i32, i32)) {
+    let viewport = viewport(canvas);
+    let size = (viewport.scale / 6).max(2);
+    let margin = size as i32 * 2;
+    let width = font::text_width(label) * size + margin as u32 * 2;
+    let height = (font::LINE_HEIGHT - 1) * size + margin as u32 * 2;
+    let (left, top) = tooltip_origin(canvas, point, (viewport.scale * 2).clamp(8, 40), (width, height));
+
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, OVERLAY_ALPHA));
+    canvas.fill_rect(Rect::new(left, top, width, height)).unwrap();
+    canvas.set_blend_mode(BlendMode::None);
+    canvas.set_draw_color(Color::RGB(255, 255, 255));
+    draw_text(canvas, label, left + margin, top + margin, size);
+}
+
+// the 64x64 heatmap grid, as big as fits the window
+fn heatmap_viewport(canvas: &Canvas<Window>) -> Viewport {
+    Viewport::fit(canvas.output_size().unwrap(), (heatmap_view::GRID_SIZE, heatmap_view::GRID_SIZE))
+}
+
+// all of memory over the middle of the game, a cell a byte, lit where it was written lately
+fn draw_heatmap(canvas: &mut Canvas<Window>, heat: &MemoryHeat) {
+    let grid = heatmap_viewport(canvas);
+
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, OVERLAY_ALPHA));
+    canvas.fill_rect(Rect::new(grid.x, grid.y, grid.width, grid.height)).unwrap();
+    canvas.set_blend_mode(BlendMode::None);
+
+    for row in 0..heatmap_view::GRID_SIZE {
+        for column in 0..heatmap_view::GRID_SIZE {
+            if let Some((r, g, b)) = heatmap_view::color(heat.heat(heatmap_view::address(column, row))) {
+                let (left, top) = grid.pixel_origin(column, row);
+                canvas.set_draw_color(Color::RGB(r, g, b));
+                canvas.fill_rect(Rect::new(left, top, grid.scale, grid.scale)).unwrap();
+            }
+        }
+    }
+}
+
+// registers and upcoming instructions over the top left of the game
+fn draw_overlay(chip8: &Chip8, canvas: &mut Canvas<Window>) {
+    let viewport = viewport(canvas);
+    let lines = overlay::lines(chip8);
+    let size = (viewport.scale / 6).max(2);
+    let margin = size as i32 * 2;
+    let width = lines.iter().map(|line| font::text_width(line)).max().unwrap_or(0) * size + margin as u32 * 2;
+    let height = lines.len() as u32 * font::LINE_HEIGHT * size - size + margin as u32 * 2;
+
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, OVERLAY_ALPHA));
+    canvas.fill_rect(Rect::new(viewport.x, viewport.y, width, height)).unwrap();
+    canvas.set_blend_mode(BlendMode::None);
+    canvas.set_draw_color(Color::RGB(255, 255, 255));
+
+    for (row, line) in lines.iter().enumerate() {
+        let top = viewport.y + margin + (row as u32 * font::LINE_HEIGHT * size) as i32;
+        draw_text(canvas, line, viewport.x + margin, top, size);
+    }
+}
+
+// the quirk list over the middle of the game
+fn draw_quirk_menu(menu: &QuirkMenu, chip8: &Chip8, canvas: &mut Canvas<Window>) {
+    let viewport = viewport(canvas);
+    let lines = menu.lines(chip8.quirks());
+    let size = (viewport.scale / 6).max(2);
+    let margin = size as i32 * 2;
+    let width = lines.iter().map(|line| font::text_width(line)).max().unwrap_or(0) * size + margin as u32 * 2;
+    let height = lines.len() as u32 * font::LINE_HEIGHT * size - size + margin as u32 * 2;
+    let left = viewport.x + (viewport.width as i32 - width as i32) / 2;
+    let top = viewport.y + (viewport.height as i32 - height as i32) / 2;
+
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, OVERLAY_ALPHA));
+    canvas.fill_rect(Rect::new(left, top, width, height)).unwrap();
+    canvas.set_blend_mode(BlendMode::None);
+    canvas.set_draw_color(Color::RGB(255, 255, 255));
+
+    for (row, line) in lines.iter().enumerate() {
+        draw_text(canvas, line, left + margin, top + margin + (row as u32 * font::LINE_HEIGHT * size) as i32, size);
+    }
+}
+
+// the keypad over the top right of the game, lit for the keys the emulator sees as held
+fn draw_keypad(chip8: &Chip8, canvas: &mut Canvas<Window>, labels: &[&str; 16]) {
+    let viewport = viewport(canvas);
+    let size = (viewport.scale / 6).max(2);
+    let left = viewport.x + viewport.width as i32 - keypad_overlay::layout(labels, size).width as i32;
+
+    keypad_overlay::draw(canvas, chip8.keys(), labels, left, viewport.y, size);
+}
+
+// a one line message over the bottom left of the game
+fn draw_osd(canvas: &mut Canvas<Window>, message: &str) {
+    let viewport = viewport(canvas);
+    let size = (viewport.scale / 6).max(2);
+    let margin = size as i32 * 2;
+    let width = font::text_width(message) * size + margin as u32 * 2;
+    let height = (font::LINE_HEIGHT - 1) * size + margin as u32 * 2;
+    let top = viewport.y + viewport.height as i32 - height as i32;
+
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, OVERLAY_ALPHA));
+    canvas.fill_rect(Rect::new(viewport.x, top, width, height)).unwrap();
+    canvas.set_blend_mode(BlendMode::None);
+    canvas.set_draw_color(Color::RGB(255, 255, 255));
+    draw_text(canvas, message, viewport.x + margin, top + margin, size);
+}
+
+impl Painter for Canvas<Window> {
+    fn fill_rect(&mut self, left: i32, top: i32, width: u32, height: u32, color: Rgba) {
+        let (r, g, b, a) = color;
+
+        self.set_blend_mode(if a < 255 { BlendMode::Blend } else { BlendMode::None });
+        self.set_draw_color(Color::RGBA(r, g, b, a));
+        Canvas::fill_rect(self, Rect::new(left, top, width, height)).unwrap();
+        self.set_blend_mode(BlendMode::None);
+    }
+}
+
+// in the current draw color, `size` screen pixels per font pixel
+fn draw_text(canvas: &mut Canvas<Window>, text: &str, left: i32, top: i32, size: u32) {
+    for (x, y) in font::text_pixels(text) {
+        let rect = Rect::new(left + (x * size) as i32, top + (y * size) as i32, size, size);
+        canvas.fill_rect(rect).unwrap();
+    }
 }
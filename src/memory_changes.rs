@@ -0,0 +1,137 @@
+use crate::Chip8;
+
+// bytes written to memory, consecutive writes coalesced into one run, so a live hex view can
+// update what changed instead of diffing all of it every frame
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryChange {
+    pub address: u16,
+    // the new values from `address` on
+    pub values: Vec<u8>,
+}
+
+impl MemoryChange {
+    pub fn end(&self) -> usize {
+        self.address as usize + self.values.len()
+    }
+}
+
+impl Chip8 {
+    // off by default, turning it off drops whatever hasn't been taken
+    pub fn set_memory_change_tracking(&mut self, is_enabled: bool) {
+        self.memory_changes = is_enabled.then(Vec::new);
+    }
+
+    pub fn is_tracking_memory_changes(&self) -> bool {
+        self.memory_changes.is_some()
+    }
+
+    // the writes since the last call, oldest first. loading a ROM or a state, or a reset, rewrites
+    // everything and shows up as one change covering all of memory
+    pub fn take_memory_changes(&mut self) -> Vec<MemoryChange> {
+        self.memory_changes.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    pub(crate) fn write_ram(&mut self, address: usize, value: u8) {
+        self.ram[address] = value;
+        self.record_heatmap_write(address);
+        self.record_watchpoint_write(address);
+
+        let Some(changes) = self.memory_changes.as_mut() else {
+            return;
+        };
+
+        match changes.last_mut() {
+            // FX55 and FX33 write upwards a byte at a time
+            Some(last) if last.end() == address => last.values.push(value),
+            Some(last) if (last.address as usize..last.end()).contains(&address) => {
+                last.values[address - last.address as usize] = value;
+            },
+            _ => changes.push(MemoryChange { address: address as u16, values: vec![value] }),
+        }
+    }
+
+    pub(crate) fn mark_all_memory_changed(&mut self) {
+        if let Some(changes) = self.memory_changes.as_mut() {
+            *changes = vec![MemoryChange { address: 0, values: self.ram.clone() }];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn machine(rom: &[u8]) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load(rom).unwrap();
+        chip8.set_memory_change_tracking(true);
+        chip8
+    }
+
+    #[test]
+    fn coalesces_a_store_and_a_bcd() {
+        // V0-V3 = 1, 2, 3, 234, I = 300, store V0-V3, I = 308, BCD of V3
+        let mut chip8 = machine(&[
+            0x60, 0x01, 0x61, 0x02, 0x62, 0x03, 0x63, 0xEA, 0xA3, 0x00, 0xF3, 0x55, 0xA3, 0x08, 0xF3, 0x33,
+        ]);
+        chip8.run_frame(8);
+
+        assert_eq!(
+            chip8.take_memory_changes(),
+            [
+                MemoryChange { address: 0x300, values: vec![1, 2, 3, 234] },
+                MemoryChange { address: 0x308, values: vec![2, 3, 4] },
+            ]
+        );
+        assert_eq!(chip8.take_memory_changes(), []);
+    }
+
+    #[test]
+    fn joins_writes_that_continue_a_run() {
+        // V0 = 9, I = 300, store V0, I = 301, store V0, store V0 again
+        let mut chip8 = machine(&[0x60, 0x09, 0xA3, 0x00, 0xF0, 0x55, 0xA3, 0x01, 0xF0, 0x55, 0xF0, 0x55]);
+        chip8.run_frame(6);
+
+        assert_eq!(chip8.take_memory_changes(), [MemoryChange { address: 0x300, values: vec![9, 9] }]);
+    }
+
+    #[test]
+    fn reports_pokes() {
+        let mut chip8 = machine(&[0x12, 0x00]);
+
+        chip8.write_memory(0x400, 1);
+        chip8.write_memory(0x200, 2);
+        chip8.write_memory(0x201, 3);
+
+        assert_eq!(
+            chip8.take_memory_changes(),
+            [MemoryChange { address: 0x400, values: vec![1] }, MemoryChange { address: 0x200, values: vec![2, 3] }]
+        );
+    }
+
+    #[test]
+    fn a_load_changes_everything() {
+        let mut chip8 = machine(&[0x12, 0x00]);
+        let state = chip8.save_state();
+        chip8.write_memory(0x300, 1);
+        chip8.load_state(&state).unwrap();
+
+        let changes = chip8.take_memory_changes();
+        assert_eq!(changes.len(), 1);
+        assert_eq!((changes[0].address, changes[0].values.len()), (0, chip8.memory().len()));
+        assert_eq!(changes[0].values, chip8.memory());
+    }
+
+    #[test]
+    fn is_off_until_asked_for() {
+        let mut chip8 = Chip8::new();
+        chip8.write_memory(0x300, 1);
+        assert!(!chip8.is_tracking_memory_changes());
+        assert_eq!(chip8.take_memory_changes(), []);
+
+        chip8.set_memory_change_tracking(true);
+        chip8.write_memory(0x300, 1);
+        chip8.set_memory_change_tracking(false);
+        assert_eq!(chip8.take_memory_changes(), []);
+    }
+}
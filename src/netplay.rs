@@ -0,0 +1,473 @@
+// lockstep "pass the controller" netplay: both sides run the same ROM with the same seed, swap
+// each frame's keypad before running it, and now and then compare state hashes to catch desyncs
+
+use crate::{Chip8, NUM_KEYS};
+
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+pub const PROTOCOL_VERSION: u8 = 1;
+// once a second at normal speed
+pub const DEFAULT_HASH_INTERVAL: u64 = 60;
+
+const HELLO: u8 = 1;
+const INPUT: u8 = 2;
+const HASH: u8 = 3;
+
+// every message is a tag byte then fixed size big-endian fields
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Message {
+    Hello { version: u8, seed: u64, rom_hash: u64 },
+    Input { frame: u64, keys: u16 },
+    Hash { frame: u64, hash: u64 },
+}
+
+impl Message {
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(18);
+
+        match *self {
+            Message::Hello { version, seed, rom_hash } => {
+                bytes.extend([HELLO, version]);
+                bytes.extend(seed.to_be_bytes());
+                bytes.extend(rom_hash.to_be_bytes());
+            },
+            Message::Input { frame, keys } => {
+                bytes.push(INPUT);
+                bytes.extend(frame.to_be_bytes());
+                bytes.extend(keys.to_be_bytes());
+            },
+            Message::Hash { frame, hash } => {
+                bytes.push(HASH);
+                bytes.extend(frame.to_be_bytes());
+                bytes.extend(hash.to_be_bytes());
+            },
+        }
+
+        out.write_all(&bytes)
+    }
+
+    pub fn read_from<R: Read>(input: &mut R) -> io::Result<Message> {
+        let mut tag = [0; 1];
+        input.read_exact(&mut tag)?;
+
+        match tag[0] {
+            HELLO => {
+                let mut version = [0; 1];
+                input.read_exact(&mut version)?;
+
+                Ok(Message::Hello { version: version[0], seed: read_u64(input)?, rom_hash: read_u64(input)? })
+            },
+            INPUT => {
+                let frame = read_u64(input)?;
+                let mut keys = [0; 2];
+                input.read_exact(&mut keys)?;
+
+                Ok(Message::Input { frame, keys: u16::from_be_bytes(keys) })
+            },
+            HASH => Ok(Message::Hash { frame: read_u64(input)?, hash: read_u64(input)? }),
+            tag => Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown message type {}", tag))),
+        }
+    }
+}
+
+fn read_u64<R: Read>(input: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+#[derive(Debug)]
+pub enum NetplayError {
+    Io(io::Error),
+    Protocol(String),
+    RomMismatch,
+    Desync { frame: u64 },
+}
+
+impl fmt::Display for NetplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetplayError::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof => write!(f, "the other player left"),
+            NetplayError::Io(e) => write!(f, "connection error: {}", e),
+            NetplayError::Protocol(message) => write!(f, "protocol error: {}", message),
+            NetplayError::RomMismatch => write!(f, "the other player loaded a different ROM"),
+            NetplayError::Desync { frame } => write!(f, "desync at frame {}", frame),
+        }
+    }
+}
+
+impl Error for NetplayError {}
+
+impl From<io::Error> for NetplayError {
+    fn from(e: io::Error) -> Self {
+        NetplayError::Io(e)
+    }
+}
+
+pub fn keys_to_mask(keys: &[bool]) -> u16 {
+    keys.iter().enumerate().filter(|(_, &is_down)| is_down).fold(0, |mask, (index, _)| mask | (1 << index))
+}
+
+pub fn mask_to_keys(mask: u16) -> [bool; NUM_KEYS] {
+    let mut keys = [false; NUM_KEYS];
+
+    for (index, key) in keys.iter_mut().enumerate() {
+        *key = mask & (1 << index) != 0;
+    }
+
+    keys
+}
+
+// the first 8 bytes of a SHA-256, plenty to tell two states or ROMs apart
+fn digest(bytes: &[u8]) -> u64 {
+    let hash = Sha256::digest(bytes);
+    u64::from_be_bytes(hash[..8].try_into().unwrap())
+}
+
+pub fn state_hash(chip8: &Chip8) -> u64 {
+    digest(&chip8.save_state())
+}
+
+pub struct Lockstep<T: Read + Write> {
+    transport: T,
+    seed: u64,
+    frame: u64,
+    hash_interval: u64,
+    // hashes sent by one side that the other hasn't matched yet
+    our_hashes: VecDeque<(u64, u64)>,
+    their_hashes: VecDeque<(u64, u64)>,
+}
+
+impl<T: Read + Write> Lockstep<T> {
+    // the host picks the seed, both sides check they're running the same ROM
+    pub fn host(mut transport: T, rom: &[u8], seed: u64) -> Result<Self, NetplayError> {
+        let rom_hash = digest(rom);
+        send(&mut transport, Message::Hello { version: PROTOCOL_VERSION, seed, rom_hash })?;
+        check_hello(Message::read_from(&mut transport)?, rom_hash)?;
+
+        Ok(Self::new(transport, seed))
+    }
+
+    pub fn join(mut transport: T, rom: &[u8]) -> Result<Self, NetplayError> {
+        let rom_hash = digest(rom);
+        let seed = check_hello(Message::read_from(&mut transport)?, rom_hash)?;
+        send(&mut transport, Message::Hello { version: PROTOCOL_VERSION, seed, rom_hash })?;
+
+        Ok(Self::new(transport, seed))
+    }
+
+    fn new(transport: T, seed: u64) -> Self {
+        Self {
+            transport,
+            seed,
+            frame: 0,
+            hash_interval: DEFAULT_HASH_INTERVAL,
+            our_hashes: VecDeque::new(),
+            their_hashes: VecDeque::new(),
+        }
+    }
+
+    // both sides have to use the same interval
+    pub fn set_hash_interval(&mut self, frames: u64) {
+        self.hash_interval = frames.max(1);
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // the next frame to run, counting from 0 when the connection was made
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    // swaps keypads for the next frame, blocking until the other side's arrives. the ROM sees a
+    // key as down if either player holds it
+    pub fn exchange(&mut self, local: &[bool]) -> Result<[bool; NUM_KEYS], NetplayError> {
+        let keys = keys_to_mask(local);
+        send(&mut self.transport, Message::Input { frame: self.frame, keys })?;
+
+        loop {
+            match Message::read_from(&mut self.transport)? {
+                Message::Input { frame, keys: theirs } if frame == self.frame => {
+                    return Ok(mask_to_keys(keys | theirs));
+                },
+                Message::Input { frame, .. } => {
+                    return Err(NetplayError::Protocol(format!("got input for frame {} on frame {}", frame, self.frame)));
+                },
+                Message::Hash { frame, hash } => {
+                    self.their_hashes.push_back((frame, hash));
+                    self.compare_hashes()?;
+                },
+                Message::Hello { .. } => return Err(NetplayError::Protocol("unexpected hello".to_string())),
+            }
+        }
+    }
+
+    // runs one frame in lockstep, pause and the speed multiplier don't apply
+    pub fn advance(&mut self, chip8: &mut Chip8, local: &[bool], ticks_per_frame: usize) -> Result<(), NetplayError> {
+        for (index, is_down) in self.exchange(local)?.into_iter().enumerate() {
+            chip8.keypress(index, is_down);
+        }
+
+        chip8.advance_frame(ticks_per_frame);
+        self.frame += 1;
+
+        if self.frame.is_multiple_of(self.hash_interval) {
+            let hash = state_hash(chip8);
+            send(&mut self.transport, Message::Hash { frame: self.frame, hash })?;
+            self.our_hashes.push_back((self.frame, hash));
+            self.compare_hashes()?;
+        }
+
+        Ok(())
+    }
+
+    fn compare_hashes(&mut self) -> Result<(), NetplayError> {
+        while let (Some(&(ours_frame, ours)), Some(&(theirs_frame, theirs))) =
+            (self.our_hashes.front(), self.their_hashes.front())
+        {
+            if ours_frame != theirs_frame {
+                return Err(NetplayError::Protocol(format!(
+                    "hashes for frames {} and {} don't line up, are both sides using the same interval?",
+                    ours_frame, theirs_frame
+                )));
+            }
+
+            if ours != theirs {
+                return Err(NetplayError::Desync { frame: ours_frame });
+            }
+
+            self.our_hashes.pop_front();
+            self.their_hashes.pop_front();
+        }
+
+        Ok(())
+    }
+}
+
+fn send<T: Write>(transport: &mut T, message: Message) -> io::Result<()> {
+    message.write_to(transport)?;
+    transport.flush()
+}
+
+// the seed the host picked
+fn check_hello(message: Message, rom_hash: u64) -> Result<u64, NetplayError> {
+    match message {
+        Message::Hello { version, .. } if version != PROTOCOL_VERSION => Err(NetplayError::Protocol(format!(
+            "the other player speaks version {}, this build speaks {}",
+            version, PROTOCOL_VERSION
+        ))),
+        Message::Hello { rom_hash: theirs, .. } if theirs != rom_hash => Err(NetplayError::RomMismatch),
+        Message::Hello { seed, .. } => Ok(seed),
+        _ => Err(NetplayError::Protocol("expected a hello".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    // reads what the other side is scripted to send, keeps what this side sent
+    struct Scripted {
+        incoming: Cursor<Vec<u8>>,
+        outgoing: Vec<u8>,
+    }
+
+    impl Scripted {
+        fn new(messages: &[Message]) -> Self {
+            let mut incoming = Vec::new();
+            for message in messages {
+                message.write_to(&mut incoming).unwrap();
+            }
+
+            Self { incoming: Cursor::new(incoming), outgoing: Vec::new() }
+        }
+
+        fn sent(&self) -> Vec<Message> {
+            let mut sent = Cursor::new(&self.outgoing);
+            let mut messages = Vec::new();
+
+            while (sent.position() as usize) < self.outgoing.len() {
+                messages.push(Message::read_from(&mut sent).unwrap());
+            }
+
+            messages
+        }
+    }
+
+    impl Read for Scripted {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.incoming.read(buf)
+        }
+    }
+
+    impl Write for Scripted {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.outgoing.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // draws a random digit at a random spot every frame, so a wrong seed shows up fast
+    const ROM: &[u8] = &[0xC0, 0x3F, 0xC1, 0x1F, 0xC2, 0x0F, 0xF2, 0x29, 0xD0, 0x15, 0x12, 0x00];
+
+    fn hello(seed: u64, rom: &[u8]) -> Message {
+        Message::Hello { version: PROTOCOL_VERSION, seed, rom_hash: digest(rom) }
+    }
+
+    // what a well-behaved peer sends for `frames` frames with no keys down, including its hashes
+    fn peer(seed: u64, frames: u64, interval: u64, tamper_at: Option<u64>) -> Vec<Message> {
+        let mut chip8 = Chip8::new();
+        chip8.load(ROM).unwrap();
+        chip8.set_seed(seed);
+        let mut messages = vec![hello(seed, ROM)];
+
+        for frame in 0..frames {
+            messages.push(Message::Input { frame, keys: 0 });
+            chip8.advance_frame(10);
+
+            if tamper_at == Some(frame) {
+                chip8.tick().unwrap();
+            }
+
+            if (frame + 1) % interval == 0 {
+                messages.push(Message::Hash { frame: frame + 1, hash: state_hash(&chip8) });
+            }
+        }
+
+        messages
+    }
+
+    fn chip8() -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load(ROM).unwrap();
+        chip8
+    }
+
+    #[test]
+    fn round_trips_messages() {
+        let messages = [
+            Message::Hello { version: 1, seed: u64::MAX, rom_hash: 0x0123_4567_89AB_CDEF },
+            Message::Input { frame: 70_000, keys: 0x8001 },
+            Message::Hash { frame: 60, hash: 42 },
+        ];
+        let mut bytes = Vec::new();
+
+        for message in messages {
+            message.write_to(&mut bytes).unwrap();
+        }
+
+        assert_eq!(bytes.len(), 18 + 11 + 17);
+
+        let mut reader = Cursor::new(bytes);
+        for message in messages {
+            assert_eq!(Message::read_from(&mut reader).unwrap(), message);
+        }
+
+        let error = Message::read_from(&mut Cursor::new(vec![9, 0, 0])).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn converts_keypads_to_masks() {
+        let mut keys = [false; NUM_KEYS];
+        keys[0] = true;
+        keys[0xF] = true;
+
+        assert_eq!(keys_to_mask(&keys), 0x8001);
+        assert_eq!(mask_to_keys(0x8001), keys);
+    }
+
+    #[test]
+    fn joining_takes_the_hosts_seed() {
+        let lockstep = Lockstep::join(Scripted::new(&[hello(1234, ROM)]), ROM).unwrap();
+
+        assert_eq!(lockstep.seed(), 1234);
+        assert_eq!(lockstep.transport.sent(), [hello(1234, ROM)]);
+    }
+
+    #[test]
+    fn rejects_a_different_rom_or_version() {
+        let result = Lockstep::host(Scripted::new(&[hello(0, &[0x12, 0x00])]), ROM, 0);
+        assert!(matches!(result, Err(NetplayError::RomMismatch)));
+
+        let old = Message::Hello { version: PROTOCOL_VERSION + 1, seed: 0, rom_hash: digest(ROM) };
+        assert!(matches!(Lockstep::join(Scripted::new(&[old]), ROM), Err(NetplayError::Protocol(_))));
+    }
+
+    #[test]
+    fn combines_both_keypads() {
+        let script = [hello(0, ROM), Message::Input { frame: 0, keys: 0x0002 }];
+        let mut lockstep = Lockstep::host(Scripted::new(&script), ROM, 0).unwrap();
+        let mut local = [false; NUM_KEYS];
+        local[0xA] = true;
+
+        let keys = lockstep.exchange(&local).unwrap();
+
+        assert_eq!(keys_to_mask(&keys), 0x0402);
+        assert_eq!(lockstep.transport.sent()[1], Message::Input { frame: 0, keys: 0x0400 });
+    }
+
+    #[test]
+    fn stays_in_sync_with_the_same_seed() {
+        let mut lockstep = Lockstep::host(Scripted::new(&peer(99, 30, 10, None)), ROM, 99).unwrap();
+        lockstep.set_hash_interval(10);
+        let mut chip8 = chip8();
+        chip8.set_seed(lockstep.seed());
+
+        for _ in 0..30 {
+            lockstep.advance(&mut chip8, &[false; NUM_KEYS], 10).unwrap();
+        }
+
+        assert_eq!(lockstep.frame(), 30);
+        let hashes = lockstep.transport.sent().into_iter().filter(|m| matches!(m, Message::Hash { .. })).count();
+        assert_eq!(hashes, 3);
+        // frames 10 and 20 were checked, the peer's hash for 30 only arrives with its next input
+        assert_eq!(lockstep.our_hashes, [(30, state_hash(&chip8))]);
+    }
+
+    #[test]
+    fn reports_the_frame_of_a_desync() {
+        // the peer's state drifts during frame 15, its hash at frame 20 no longer matches
+        let mut lockstep = Lockstep::host(Scripted::new(&peer(5, 30, 10, Some(15))), ROM, 5).unwrap();
+        lockstep.set_hash_interval(10);
+        let mut chip8 = chip8();
+        chip8.set_seed(5);
+
+        let error = (0..30).find_map(|_| lockstep.advance(&mut chip8, &[false; NUM_KEYS], 10).err());
+
+        assert!(matches!(error, Some(NetplayError::Desync { frame: 20 })), "{:?}", error);
+        assert_eq!(error.unwrap().to_string(), "desync at frame 20");
+    }
+
+    #[test]
+    fn a_different_seed_desyncs() {
+        let mut lockstep = Lockstep::host(Scripted::new(&peer(1, 10, 10, None)), ROM, 2).unwrap();
+        lockstep.set_hash_interval(10);
+        let mut chip8 = chip8();
+        chip8.set_seed(2);
+
+        let error = (0..11).find_map(|_| lockstep.advance(&mut chip8, &[false; NUM_KEYS], 10).err());
+        assert!(matches!(error, Some(NetplayError::Desync { frame: 10 })), "{:?}", error);
+    }
+
+    #[test]
+    fn notices_the_peer_leaving() {
+        let mut lockstep = Lockstep::host(Scripted::new(&peer(0, 3, 60, None)), ROM, 0).unwrap();
+        let mut chip8 = chip8();
+
+        let error = (0..5).find_map(|_| lockstep.advance(&mut chip8, &[false; NUM_KEYS], 10).err()).unwrap();
+        assert_eq!(lockstep.frame(), 3);
+        assert_eq!(error.to_string(), "the other player left");
+    }
+}
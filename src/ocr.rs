@@ -0,0 +1,187 @@
+use crate::{Chip8, FONTSET, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+pub const GLYPH_HEIGHT: usize = 5;
+
+// wider gaps than this between two glyphs on a line are read as a space
+const LETTER_SPACING: usize = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Glyph {
+    pub ch: char,
+    pub width: usize,
+    // one byte per row, leftmost pixel in the highest bit
+    pub rows: [u8; GLYPH_HEIGHT],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    pub const SCREEN: Region = Region::new(0, 0, SCREEN_WIDTH, SCREEN_HEIGHT);
+
+    pub const fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self { x, y, width, height }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GlyphMatch {
+    pub ch: char,
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+}
+
+// the 4x5 hex digits the interpreter keeps at the bottom of ram
+pub fn font_glyphs() -> Vec<Glyph> {
+    FONTSET
+        .chunks(GLYPH_HEIGHT)
+        .zip("0123456789ABCDEF".chars())
+        .map(|(sprite, ch)| {
+            let mut rows = [0; GLYPH_HEIGHT];
+            rows.copy_from_slice(sprite);
+
+            Glyph { ch, width: 4, rows }
+        })
+        .collect()
+}
+
+// every place in `region` where one of `glyphs` is drawn pixel for pixel, in reading order
+pub fn find_glyphs(screen: &[bool], region: Region, glyphs: &[Glyph]) -> Vec<GlyphMatch> {
+    let right = (region.x + region.width).min(SCREEN_WIDTH);
+    let bottom = (region.y + region.height).min(SCREEN_HEIGHT);
+    let mut consumed = vec![false; SCREEN_WIDTH * SCREEN_HEIGHT];
+    let mut matches = Vec::new();
+
+    for y in region.y..bottom.saturating_sub(GLYPH_HEIGHT - 1) {
+        for x in region.x..right {
+            if consumed[x + SCREEN_WIDTH * y] {
+                continue;
+            }
+
+            let found = glyphs
+                .iter()
+                .find(|glyph| x + glyph.width <= right && glyph_at(screen, glyph, x, y));
+
+            if let Some(glyph) = found {
+                for row in y..y + GLYPH_HEIGHT {
+                    for column in x..x + glyph.width {
+                        consumed[column + SCREEN_WIDTH * row] = true;
+                    }
+                }
+
+                matches.push(GlyphMatch { ch: glyph.ch, x, y, width: glyph.width });
+            }
+        }
+    }
+
+    matches
+}
+
+// the text drawn in `region`, one line per row of glyphs
+pub fn read_glyphs(screen: &[bool], region: Region, glyphs: &[Glyph]) -> String {
+    let matches = find_glyphs(screen, region, glyphs);
+    let mut lines: Vec<String> = Vec::new();
+    let mut previous: Option<GlyphMatch> = None;
+
+    for found in matches {
+        match previous {
+            Some(last) if last.y == found.y => {
+                let line = lines.last_mut().unwrap();
+
+                if found.x > last.x + last.width + LETTER_SPACING {
+                    line.push(' ');
+                }
+
+                line.push(found.ch);
+            },
+            _ => lines.push(found.ch.to_string()),
+        }
+
+        previous = Some(found);
+    }
+
+    lines.join("\n")
+}
+
+fn glyph_at(screen: &[bool], glyph: &Glyph, x: usize, y: usize) -> bool {
+    glyph.rows.iter().enumerate().all(|(row, bits)| {
+        (0..glyph.width).all(|column| {
+            let expected = bits & (0b1000_0000 >> column) != 0;
+
+            screen[x + column + SCREEN_WIDTH * (y + row)] == expected
+        })
+    })
+}
+
+impl Chip8 {
+    pub fn read_glyphs(&self, region: Region, glyphs: &[Glyph]) -> String {
+        read_glyphs(self.get_display(), region, glyphs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // draws each digit with FX29 + DXY5, five pixels apart starting at (x, y)
+    fn draw_digits(digits: &[u8], x: u8, y: u8) -> Chip8 {
+        let mut rom = Vec::new();
+
+        for (i, &digit) in digits.iter().enumerate() {
+            rom.extend_from_slice(&[0x60, digit, 0xF0, 0x29, 0x61, x + 5 * i as u8, 0x62, y, 0xD1, 0x25]);
+        }
+
+        let spin = 0x200 + rom.len() as u16;
+        rom.extend_from_slice(&[0x10 | (spin >> 8) as u8, spin as u8]);
+
+        let mut chip8 = Chip8::new();
+        chip8.load(&rom).unwrap();
+
+        for _ in 0..digits.len() * 5 {
+            chip8.tick().unwrap();
+        }
+
+        chip8
+    }
+
+    #[test]
+    fn reads_font_digits() {
+        let chip8 = draw_digits(&[0x1, 0x2, 0x3, 0xA, 0xB, 0xF], 3, 7);
+
+        assert_eq!(chip8.read_glyphs(Region::SCREEN, &font_glyphs()), "123ABF");
+    }
+
+    #[test]
+    fn reads_all_digits_at_the_given_region() {
+        let digits: Vec<u8> = (0..12).collect();
+        let chip8 = draw_digits(&digits, 0, 20);
+
+        assert_eq!(chip8.read_glyphs(Region::SCREEN, &font_glyphs()), "0123456789AB");
+        assert_eq!(chip8.read_glyphs(Region::new(10, 20, 15, 5), &font_glyphs()), "234");
+        assert_eq!(chip8.read_glyphs(Region::new(0, 0, 64, 10), &font_glyphs()), "");
+    }
+
+    #[test]
+    fn separates_words_and_lines() {
+        let mut chip8 = draw_digits(&[0x4, 0x2], 0, 0);
+        let other = draw_digits(&[0x7], 20, 10);
+
+        for (pixel, &other_pixel) in chip8.screen.iter_mut().zip(other.get_display()) {
+            *pixel |= other_pixel;
+        }
+
+        let second = draw_digits(&[0x9], 30, 0);
+
+        for (pixel, &other_pixel) in chip8.screen.iter_mut().zip(second.get_display()) {
+            *pixel |= other_pixel;
+        }
+
+        assert_eq!(chip8.read_glyphs(Region::SCREEN, &font_glyphs()), "42 9\n7");
+    }
+}
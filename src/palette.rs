@@ -0,0 +1,187 @@
+use crate::{Chip8, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub background: (u8, u8, u8),
+    pub foreground: (u8, u8, u8),
+}
+
+pub const PALETTES: &[(&str, Palette)] = &[
+    ("green", Palette::new((0, 0, 0), (50, 169, 86))),
+    ("white", Palette::new((0, 0, 0), (255, 255, 255))),
+    ("amber", Palette::new((20, 12, 0), (255, 176, 0))),
+    ("gameboy", Palette::new((155, 188, 15), (15, 56, 15))),
+    ("inverted", Palette::new((255, 255, 255), (0, 0, 0))),
+];
+
+impl Palette {
+    pub const fn new(background: (u8, u8, u8), foreground: (u8, u8, u8)) -> Self {
+        Self { background, foreground }
+    }
+
+    pub fn named(name: &str) -> Option<Self> {
+        PALETTES
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(name))
+            .map(|&(_, palette)| palette)
+    }
+
+    // the built-in name, if this is one of them
+    pub fn name(&self) -> Option<&'static str> {
+        PALETTES
+            .iter()
+            .find(|(_, palette)| palette == self)
+            .map(|&(name, _)| name)
+    }
+
+    // the built-in after this one, custom palettes go back to the first
+    pub fn next(&self) -> Self {
+        let index = PALETTES.iter().position(|(_, palette)| palette == self);
+
+        match index {
+            Some(index) => PALETTES[(index + 1) % PALETTES.len()].1,
+            None => PALETTES[0].1,
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        PALETTES[0].1
+    }
+}
+
+// a built-in name, or "#rrggbb,#rrggbb" for background then foreground
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        if let Some(palette) = Palette::named(text.trim()) {
+            return Ok(palette);
+        }
+
+        let (background, foreground) = text.split_once(',').ok_or_else(|| {
+            let names: Vec<&str> = PALETTES.iter().map(|(name, _)| *name).collect();
+            format!("`{}` isn't a palette, use one of {} or two hex colors like #000000,#32a956", text, names.join(", "))
+        })?;
+
+        Ok(Palette::new(parse_hex(background)?, parse_hex(foreground)?))
+    }
+}
+
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{}", name),
+            None => {
+                let (bg, fg) = (self.background, self.foreground);
+                write!(f, "#{:02x}{:02x}{:02x},#{:02x}{:02x}{:02x}", bg.0, bg.1, bg.2, fg.0, fg.1, fg.2)
+            },
+        }
+    }
+}
+
+fn parse_hex(text: &str) -> Result<(u8, u8, u8), String> {
+    let digits = text.trim().trim_start_matches('#');
+
+    if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("`{}` isn't a hex color like #32a956", text.trim()));
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).unwrap();
+
+    Ok((channel(0), channel(2), channel(4)))
+}
+
+impl Chip8 {
+    // the screen as RGBA bytes, row by row, for frontends that upload a texture
+    pub fn render_rgba(&self, palette: Palette, out: &mut [u8]) {
+        assert_eq!(out.len(), SCREEN_WIDTH * SCREEN_HEIGHT * 4, "buffer must hold the whole screen");
+
+        for (pixel, &is_on) in out.chunks_exact_mut(4).zip(self.screen.iter()) {
+            let (r, g, b) = if is_on { palette.foreground } else { palette.background };
+            pixel.copy_from_slice(&[r, g, b, 0xFF]);
+        }
+    }
+
+    // the same, blown up to `scale` x `scale` blocks per pixel for frontends that can't scale
+    pub fn render_rgba_scaled(&self, palette: Palette, scale: usize, out: &mut [u8]) {
+        let width = SCREEN_WIDTH * scale;
+        assert_eq!(out.len(), width * SCREEN_HEIGHT * scale * 4, "buffer must hold the whole scaled screen");
+
+        for (y, row) in out.chunks_exact_mut(width * 4).enumerate() {
+            for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                let is_on = self.screen[y / scale * SCREEN_WIDTH + x / scale];
+                let (r, g, b) = if is_on { palette.foreground } else { palette.background };
+                pixel.copy_from_slice(&[r, g, b, 0xFF]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_names_and_hex_pairs() {
+        assert_eq!("amber".parse::<Palette>().unwrap(), Palette::named("amber").unwrap());
+        assert_eq!("GameBoy".parse::<Palette>().unwrap().name(), Some("gameboy"));
+
+        let custom: Palette = "#102030, 0a0B0c".parse().unwrap();
+        assert_eq!(custom, Palette::new((0x10, 0x20, 0x30), (0x0A, 0x0B, 0x0C)));
+        assert_eq!(custom.name(), None);
+        assert_eq!(custom.to_string(), "#102030,#0a0b0c");
+        assert_eq!(custom.to_string().parse::<Palette>().unwrap(), custom);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        let error = "purple".parse::<Palette>().unwrap_err();
+        assert!(error.contains("purple") && error.contains("amber"), "{}", error);
+
+        for bad in ["#12345,#000000", "#000000,#gggggg", "#000000,", "#0000000,#000000"] {
+            assert!(bad.parse::<Palette>().is_err(), "{} parsed", bad);
+        }
+    }
+
+    #[test]
+    fn cycles_through_the_built_ins() {
+        let mut palette = Palette::default();
+
+        for &(name, _) in PALETTES.iter().skip(1) {
+            palette = palette.next();
+            assert_eq!(palette.name(), Some(name));
+        }
+
+        assert_eq!(palette.next(), Palette::default());
+        assert_eq!(Palette::new((1, 2, 3), (4, 5, 6)).next(), Palette::default());
+    }
+
+    #[test]
+    fn renders_rgba() {
+        let mut chip8 = Chip8::new();
+        chip8.screen[1] = true;
+
+        let mut rgba = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT * 4];
+        chip8.render_rgba(Palette::named("amber").unwrap(), &mut rgba);
+
+        assert_eq!(&rgba[..8], &[20, 12, 0, 255, 255, 176, 0, 255]);
+    }
+
+    #[test]
+    fn renders_scaled_rgba() {
+        let mut chip8 = Chip8::new();
+        chip8.screen[SCREEN_WIDTH + 1] = true;
+
+        let mut rgba = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT * 9 * 4];
+        chip8.render_rgba_scaled(Palette::named("white").unwrap(), 3, &mut rgba);
+
+        let lit = |x: usize, y: usize| rgba[(y * SCREEN_WIDTH * 3 + x) * 4] == 255;
+        assert!(lit(3, 3) && lit(5, 5));
+        assert!(!lit(2, 3) && !lit(6, 5) && !lit(3, 6));
+    }
+}
@@ -0,0 +1,108 @@
+use crate::Palette;
+
+// a slow-fading copy of the screen: lit pixels jump to full brightness and dark ones decay,
+// which hides the flicker of games that erase and redraw sprites every frame
+#[derive(Clone, Debug)]
+pub struct Phosphor {
+    intensity: Vec<f32>,
+    persistence: f32,
+}
+
+impl Phosphor {
+    // `persistence` is how much brightness survives each update, 0.0 turns the effect off
+    pub fn new(pixels: usize, persistence: f32) -> Self {
+        Self {
+            intensity: vec![0.0; pixels],
+            persistence: persistence.clamp(0.0, 1.0),
+        }
+    }
+
+    pub fn persistence(&self) -> f32 {
+        self.persistence
+    }
+
+    pub fn update(&mut self, screen: &[bool]) {
+        for (intensity, &is_on) in self.intensity.iter_mut().zip(screen) {
+            *intensity = blend(*intensity, is_on, self.persistence);
+        }
+    }
+
+    pub fn intensity(&self) -> &[f32] {
+        &self.intensity
+    }
+
+    pub fn clear(&mut self) {
+        self.intensity.fill(0.0);
+    }
+
+    // RGBA bytes with each pixel faded between the palette colors by its intensity
+    pub fn render_rgba(&self, palette: Palette, out: &mut [u8]) {
+        for (pixel, &intensity) in out.chunks_exact_mut(4).zip(&self.intensity) {
+            let (r, g, b) = mix(palette.background, palette.foreground, intensity);
+            pixel.copy_from_slice(&[r, g, b, 0xFF]);
+        }
+    }
+}
+
+pub fn blend(previous: f32, is_on: bool, persistence: f32) -> f32 {
+    if is_on {
+        1.0
+    } else {
+        previous * persistence
+    }
+}
+
+pub fn mix(background: (u8, u8, u8), foreground: (u8, u8, u8), intensity: f32) -> (u8, u8, u8) {
+    let channel = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * intensity).round() as u8;
+
+    (
+        channel(background.0, foreground.0),
+        channel(background.1, foreground.1),
+        channel(background.2, foreground.2),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lit_pixels_fade_out() {
+        let mut phosphor = Phosphor::new(3, 0.5);
+
+        phosphor.update(&[true, false, true]);
+        assert_eq!(phosphor.intensity(), &[1.0, 0.0, 1.0]);
+
+        phosphor.update(&[false, false, true]);
+        assert_eq!(phosphor.intensity(), &[0.5, 0.0, 1.0]);
+
+        phosphor.update(&[false, true, false]);
+        assert_eq!(phosphor.intensity(), &[0.25, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn zero_persistence_is_the_plain_screen() {
+        let mut phosphor = Phosphor::new(2, 0.0);
+
+        phosphor.update(&[true, false]);
+        phosphor.update(&[false, true]);
+        assert_eq!(phosphor.intensity(), &[0.0, 1.0]);
+    }
+
+    #[test]
+    fn mixes_between_palette_colors() {
+        let palette = Palette::new((0, 0, 100), (200, 100, 0));
+
+        assert_eq!(mix(palette.background, palette.foreground, 0.0), (0, 0, 100));
+        assert_eq!(mix(palette.background, palette.foreground, 1.0), (200, 100, 0));
+        assert_eq!(mix(palette.background, palette.foreground, 0.5), (100, 50, 50));
+
+        let mut phosphor = Phosphor::new(2, 0.5);
+        phosphor.update(&[true, false]);
+        phosphor.update(&[false, false]);
+
+        let mut rgba = [0; 8];
+        phosphor.render_rgba(palette, &mut rgba);
+        assert_eq!(rgba, [100, 50, 50, 255, 0, 0, 100, 255]);
+    }
+}
@@ -0,0 +1,352 @@
+use crate::{Chip8, PackedScreen, Palette, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+// largest block deflate can store uncompressed
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+impl Chip8 {
+    // a 1-bit indexed PNG of the screen, each CHIP-8 pixel drawn as a `scale` x `scale` block
+    pub fn write_png<W: Write>(&self, writer: &mut W, scale: u32, palette: Palette) -> io::Result<()> {
+        assert!(scale > 0, "scale must be at least 1");
+
+        writer.write_all(&SIGNATURE)?;
+        write_header(writer, scale, palette)?;
+        write_chunk(writer, b"IDAT", &zlib_stored(&png_rows(&self.screen, scale)))?;
+        write_chunk(writer, b"IEND", &[])
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P, scale: u32, palette: Palette) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        self.write_png(&mut writer, scale, palette)?;
+        writer.flush()
+    }
+}
+
+// an animated PNG of captured frames shown `fps` times a second, looping forever. frames that
+// repeat the one before are folded into its delay, which APNG keeps as an exact fraction
+pub fn export_apng(frames: &[PackedScreen], palette: Palette, scale: u32, fps: u32) -> Vec<u8> {
+    assert!(!frames.is_empty(), "an animation needs at least one frame");
+    assert!(scale > 0, "scale must be at least 1");
+    assert!(fps > 0 && fps <= u16::MAX as u32, "fps must be between 1 and {}", u16::MAX);
+
+    let mut runs: Vec<(PackedScreen, u16)> = Vec::new();
+
+    for &frame in frames {
+        match runs.last_mut() {
+            Some((last, count)) if *last == frame && *count < u16::MAX => *count += 1,
+            _ => runs.push((frame, 1)),
+        }
+    }
+
+    let mut apng = SIGNATURE.to_vec();
+    write_apng(&mut apng, &runs, palette, scale, fps as u16).expect("writing to a Vec doesn't fail");
+
+    apng
+}
+
+// a still of `screen`, as write_png draws it
+pub fn export_png(screen: &[bool], palette: Palette, scale: u32) -> Vec<u8> {
+    assert!(scale > 0, "scale must be at least 1");
+
+    let mut png = SIGNATURE.to_vec();
+    write_header(&mut png, scale, palette).expect("writing to a Vec doesn't fail");
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&png_rows(screen, scale))).expect("writing to a Vec doesn't fail");
+    write_chunk(&mut png, b"IEND", &[]).expect("writing to a Vec doesn't fail");
+
+    png
+}
+
+fn write_apng<W: Write>(
+    writer: &mut W,
+    runs: &[(PackedScreen, u16)],
+    palette: Palette,
+    scale: u32,
+    fps: u16,
+) -> io::Result<()> {
+    write_header(writer, scale, palette)?;
+
+    let mut animation = Vec::with_capacity(8);
+    animation.extend_from_slice(&(runs.len() as u32).to_be_bytes());
+    // plays, 0 loops forever
+    animation.extend_from_slice(&0u32.to_be_bytes());
+    write_chunk(writer, b"acTL", &animation)?;
+
+    // fcTL and fdAT share one sequence, IDAT has none
+    let mut sequence = 0u32;
+
+    for (index, (frame, count)) in runs.iter().enumerate() {
+        let mut control = Vec::with_capacity(26);
+        control.extend_from_slice(&sequence.to_be_bytes());
+        control.extend_from_slice(&(SCREEN_WIDTH as u32 * scale).to_be_bytes());
+        control.extend_from_slice(&(SCREEN_HEIGHT as u32 * scale).to_be_bytes());
+        // x and y offsets
+        control.extend_from_slice(&[0; 8]);
+        // delay as a fraction of a second
+        control.extend_from_slice(&count.to_be_bytes());
+        control.extend_from_slice(&fps.to_be_bytes());
+        // every frame covers the whole image, dispose none, blend source
+        control.extend_from_slice(&[0, 0]);
+        write_chunk(writer, b"fcTL", &control)?;
+        sequence += 1;
+
+        let data = zlib_stored(&png_rows(&frame.unpack(), scale));
+
+        // the first frame doubles as the still image for viewers without APNG support
+        if index == 0 {
+            write_chunk(writer, b"IDAT", &data)?;
+        } else {
+            let mut frame_data = Vec::with_capacity(4 + data.len());
+            frame_data.extend_from_slice(&sequence.to_be_bytes());
+            frame_data.extend_from_slice(&data);
+            write_chunk(writer, b"fdAT", &frame_data)?;
+            sequence += 1;
+        }
+    }
+
+    write_chunk(writer, b"IEND", &[])
+}
+
+// IHDR and PLTE for a 1-bit indexed image of the screen at `scale`
+fn write_header<W: Write>(writer: &mut W, scale: u32, palette: Palette) -> io::Result<()> {
+    let width = SCREEN_WIDTH as u32 * scale;
+    let height = SCREEN_HEIGHT as u32 * scale;
+    let (background, foreground) = (palette.background, palette.foreground);
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // bit depth 1, color type 3 (indexed), deflate, no filter, no interlace
+    header.extend_from_slice(&[1, 3, 0, 0, 0]);
+    write_chunk(writer, b"IHDR", &header)?;
+
+    let colors = [background.0, background.1, background.2, foreground.0, foreground.1, foreground.2];
+    write_chunk(writer, b"PLTE", &colors)
+}
+
+fn png_rows(screen: &[bool], scale: u32) -> Vec<u8> {
+    let scale = scale as usize;
+    let row_bytes = (SCREEN_WIDTH * scale).div_ceil(8);
+    let mut rows = Vec::with_capacity((row_bytes + 1) * SCREEN_HEIGHT * scale);
+
+    for y in 0..SCREEN_HEIGHT {
+        let mut row = vec![0; row_bytes];
+
+        for x in 0..SCREEN_WIDTH * scale {
+            if screen[y * SCREEN_WIDTH + x / scale] {
+                row[x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+
+        for _ in 0..scale {
+            // filter type 0, none
+            rows.push(0);
+            rows.extend_from_slice(&row);
+        }
+    }
+
+    rows
+}
+
+fn write_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+
+    let crc = crc32(crc32(!0, kind), data);
+    writer.write_all(&(!crc).to_be_bytes())
+}
+
+// 1-bit rows are small enough that skipping compression keeps this simple without bloating files much
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let blocks = data.len() / MAX_STORED_BLOCK + 1;
+    let mut out = Vec::with_capacity(data.len() + blocks * 5 + 6);
+
+    // deflate, 32k window, no preset dictionary, fastest
+    out.extend_from_slice(&[0x78, 0x01]);
+
+    let mut chunks = data.chunks(MAX_STORED_BLOCK).peekable();
+
+    while let Some(chunk) = chunks.next() {
+        let is_last = chunks.peek().is_none();
+        let len = chunk.len() as u16;
+
+        out.push(is_last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+
+    out
+}
+
+fn crc32(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= byte as u32;
+
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+
+    crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLORS: Palette = Palette::new((0, 0, 0), (255, 255, 255));
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn checksums_match_known_values() {
+        assert_eq!(!crc32(!0, b"123456789"), 0xCBF4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn writes_a_scaled_indexed_png() {
+        let mut chip8 = Chip8::new();
+        chip8.screen[0] = true;
+
+        let mut png = Vec::new();
+        chip8.write_png(&mut png, 3, COLORS).unwrap();
+
+        assert_eq!(&png[..8], &SIGNATURE);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(u32_at(&png, 16), 64 * 3);
+        assert_eq!(u32_at(&png, 20), 32 * 3);
+        assert_eq!(&png[24..26], &[1, 3]);
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+
+        // top left pixel is a 3x3 foreground block
+        let rows = png_rows(&chip8.screen, 3);
+        let row_len = 1 + 24;
+        assert_eq!(rows.len(), row_len * 96);
+        assert_eq!(rows[1], 0b1110_0000);
+        assert_eq!(rows[row_len * 2 + 1], 0b1110_0000);
+        assert_eq!(rows[row_len * 3 + 1], 0);
+    }
+
+    #[test]
+    fn splits_large_images_into_stored_blocks() {
+        let data = vec![7; MAX_STORED_BLOCK + 10];
+        let zlib = zlib_stored(&data);
+
+        assert_eq!(zlib.len(), 2 + 2 * 5 + data.len() + 4);
+        assert_eq!(zlib[2], 0);
+        assert_eq!(zlib[2 + 5 + MAX_STORED_BLOCK], 1);
+    }
+
+    // (kind, data) of every chunk after the signature
+    fn chunks(png: &[u8]) -> Vec<(&[u8], &[u8])> {
+        let mut chunks = Vec::new();
+        let mut offset = 8;
+
+        while offset < png.len() {
+            let len = u32_at(png, offset) as usize;
+            let kind = &png[offset + 4..offset + 8];
+            let data = &png[offset + 8..offset + 8 + len];
+            assert_eq!(u32_at(png, offset + 8 + len), !crc32(crc32(!0, kind), data));
+
+            chunks.push((kind, data));
+            offset += 12 + len;
+        }
+
+        chunks
+    }
+
+    fn frame(pixel: usize) -> PackedScreen {
+        let mut screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        screen[pixel] = true;
+        PackedScreen::pack(&screen)
+    }
+
+    #[test]
+    fn exports_a_frame_control_per_frame_with_exact_delays() {
+        let apng = export_apng(&[frame(0), frame(1), frame(2)], COLORS, 2, 60);
+        let chunks = chunks(&apng);
+        let kinds: Vec<&[u8]> = chunks.iter().map(|(kind, _)| *kind).collect();
+
+        assert_eq!(&apng[..8], &SIGNATURE);
+        assert_eq!(
+            kinds,
+            [&b"IHDR"[..], b"PLTE", b"acTL", b"fcTL", b"IDAT", b"fcTL", b"fdAT", b"fcTL", b"fdAT", b"IEND"]
+        );
+
+        let animation = chunks[2].1;
+        assert_eq!((u32_at(animation, 0), u32_at(animation, 4)), (3, 0));
+
+        let controls: Vec<&[u8]> = chunks.iter().filter(|(kind, _)| *kind == b"fcTL").map(|(_, data)| *data).collect();
+        for control in &controls {
+            assert_eq!((u32_at(control, 4), u32_at(control, 8)), (128, 64));
+            assert_eq!(&control[20..24], &[0, 1, 0, 60]);
+        }
+
+        // fcTL and fdAT count up together
+        let sequences: Vec<u32> = chunks
+            .iter()
+            .filter(|(kind, _)| *kind == b"fcTL" || *kind == b"fdAT")
+            .map(|(_, data)| u32_at(data, 0))
+            .collect();
+        assert_eq!(sequences, [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn frames_share_the_screenshot_rows() {
+        let mut chip8 = Chip8::new();
+        chip8.screen[1] = true;
+
+        let mut png = Vec::new();
+        chip8.write_png(&mut png, 2, COLORS).unwrap();
+        let apng = export_apng(&[frame(0), frame(1)], COLORS, 2, 60);
+
+        let idat = chunks(&png).into_iter().find(|(kind, _)| *kind == b"IDAT").unwrap().1;
+        let fdat = chunks(&apng).into_iter().find(|(kind, _)| *kind == b"fdAT").unwrap().1;
+        assert_eq!(&fdat[4..], idat);
+        assert_eq!(chunks(&apng)[..2], chunks(&png)[..2]);
+    }
+
+    #[test]
+    fn exports_the_same_png_as_a_screenshot() {
+        let mut chip8 = Chip8::new();
+        chip8.screen[70] = true;
+
+        let mut png = Vec::new();
+        chip8.write_png(&mut png, 2, COLORS).unwrap();
+        assert_eq!(export_png(&chip8.screen, COLORS, 2), png);
+    }
+
+    #[test]
+    fn folds_repeated_frames_into_longer_delays() {
+        let apng = export_apng(&[frame(0), frame(0), frame(0), frame(5)], COLORS, 1, 30);
+        let chunks = chunks(&apng);
+
+        assert_eq!(u32_at(chunks[2].1, 0), 2);
+
+        let delays: Vec<&[u8]> =
+            chunks.iter().filter(|(kind, _)| *kind == b"fcTL").map(|(_, data)| &data[20..24]).collect();
+        assert_eq!(delays, [&[0, 3, 0, 30][..], &[0, 1, 0, 30]]);
+    }
+}
@@ -0,0 +1,254 @@
+// the core as a Python module through PyO3. `maturin develop` builds and installs it (see
+// pyproject.toml), then
+//
+//   import chip8_emu
+//   chip8 = chip8_emu.Chip8(seed=42, shift_uses_vy=True)
+//   chip8.load(open("pong.ch8", "rb").read())
+//   chip8.run_frame()
+//   numpy.asarray(chip8.display)  # (32, 64) of 0 and 1
+//
+// tests/python holds the pytest tests for the installed module
+
+use crate::{Quirk, StateError, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyMemoryView};
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+const TICKS_PER_FRAME: usize = 10;
+
+create_exception!(
+    chip8_emu,
+    Chip8Error,
+    PyException,
+    "A ROM, key or save state the emulator can't take, or an instruction it can't run."
+);
+
+impl From<StateError> for PyErr {
+    fn from(err: StateError) -> Self {
+        Chip8Error::new_err(err.to_string())
+    }
+}
+
+impl From<crate::Chip8Error> for PyErr {
+    fn from(err: crate::Chip8Error) -> Self {
+        Chip8Error::new_err(err.to_string())
+    }
+}
+
+// Python can hand the object to any thread, and hooks aren't Sync, so the machine sits behind a
+// lock that's never contended with the GIL held
+#[pyclass(name = "Chip8")]
+pub struct PyChip8 {
+    chip8: Mutex<crate::Chip8>,
+}
+
+impl PyChip8 {
+    fn machine(&self) -> MutexGuard<'_, crate::Chip8> {
+        // a panic mid-instruction leaves the machine usable, the next load resets it
+        self.chip8.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[pymethods]
+impl PyChip8 {
+    // quirks go by their names, like shift_uses_vy=True
+    #[new]
+    #[pyo3(signature = (*, seed = None, **quirks))]
+    fn new(seed: Option<u64>, quirks: Option<&Bound<'_, PyDict>>) -> PyResult<Self> {
+        let mut chip8 = match seed {
+            Some(seed) => crate::Chip8::with_seed(seed),
+            None => crate::Chip8::new(),
+        };
+
+        for (name, is_enabled) in quirks.into_iter().flatten() {
+            let name: String = name.extract()?;
+            let quirk = Quirk::from_name(&name).ok_or_else(|| {
+                let names: Vec<&str> = Quirk::all().map(Quirk::name).collect();
+                Chip8Error::new_err(format!("`{}` isn't a quirk, use one of {}", name, names.join(", ")))
+            })?;
+
+            chip8.set_quirk(quirk, is_enabled.extract()?);
+        }
+
+        Ok(Self { chip8: Mutex::new(chip8) })
+    }
+
+    // resets the machine first, keeping the seed and quirks
+    fn load(&self, rom: &[u8]) -> PyResult<()> {
+        // checked before the reset, a ROM that doesn't load leaves the machine as it was
+        let mut chip8 = self.machine();
+        chip8.check_rom(rom)?;
+        chip8.reset();
+        chip8.load(rom)?;
+
+        Ok(())
+    }
+
+    fn tick(&self) -> PyResult<()> {
+        self.machine().tick()?;
+
+        Ok(())
+    }
+
+    // a fault raises, the machine stays paused just before the instruction
+    #[pyo3(signature = (ticks_per_frame = TICKS_PER_FRAME))]
+    fn run_frame(&self, ticks_per_frame: usize) -> PyResult<()> {
+        let mut chip8 = self.machine();
+        chip8.run_frame(ticks_per_frame);
+
+        match chip8.take_fault() {
+            Some(error) => Err(error.into()),
+            None => Ok(()),
+        }
+    }
+
+    fn keypress(&self, key: usize, is_pressed: bool) -> PyResult<()> {
+        if key >= 16 {
+            return Err(Chip8Error::new_err(format!("{} isn't a key, they go 0 to 15", key)));
+        }
+
+        self.machine().keypress(key, is_pressed);
+
+        Ok(())
+    }
+
+    // a copy of the screen as a (32, 64) memoryview of 0 and 1 bytes, numpy.asarray takes it as is
+    #[getter]
+    fn display<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let pixels: Vec<u8> = self.machine().get_display().iter().map(|&is_on| is_on as u8).collect();
+        let view = PyMemoryView::from(&PyBytes::new(py, &pixels))?;
+
+        view.call_method1("cast", ("B", (SCREEN_HEIGHT, SCREEN_WIDTH)))
+    }
+
+    // V0 to VF as bytes
+    #[getter]
+    fn registers<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.machine().registers())
+    }
+
+    #[getter]
+    fn i(&self) -> u16 {
+        self.machine().register_i()
+    }
+
+    #[getter]
+    fn pc(&self) -> u16 {
+        self.machine().program_counter()
+    }
+
+    #[getter]
+    fn seed(&self) -> u64 {
+        self.machine().seed()
+    }
+
+    fn save_state<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.machine().save_state())
+    }
+
+    fn load_state(&self, state: &[u8]) -> PyResult<()> {
+        Ok(self.machine().load_state(state)?)
+    }
+}
+
+#[pymodule]
+fn chip8_emu(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyChip8>()?;
+    module.add("Chip8Error", module.py().get_type::<Chip8Error>())?;
+
+    Ok(())
+}
+
+// runs Python against the module in an embedded interpreter, so `cargo test --features python`
+// covers it without maturin
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::ffi::CString;
+
+    fn run(code: &str) -> PyResult<()> {
+        Python::initialize();
+
+        Python::attach(|py| {
+            let module = PyModule::new(py, "chip8_emu")?;
+            chip8_emu(&module)?;
+
+            let globals = PyDict::new(py);
+            globals.set_item("chip8_emu", module)?;
+            globals.set_item("rom", PyBytes::new(py, include_bytes!("../tests/fixtures/random-digit.ch8")))?;
+
+            py.run(&CString::new(code).unwrap(), Some(&globals), None)
+        })
+    }
+
+    #[test]
+    fn runs_the_bundled_rom() {
+        run(r#"
+chip8 = chip8_emu.Chip8(seed=42)
+chip8.load(rom)
+for _ in range(5):
+    chip8.run_frame()
+
+display = chip8.display
+assert display.shape == (32, 64), display.shape
+assert any(display.tobytes()), "nothing was drawn"
+assert len(chip8.registers) == 16
+assert chip8.pc >= 0x200 and chip8.seed == 42
+"#)
+        .unwrap();
+    }
+
+    #[test]
+    fn the_seed_decides_the_run() {
+        run(r#"
+def screen(seed):
+    chip8 = chip8_emu.Chip8(seed=seed)
+    chip8.load(rom)
+    for _ in range(5):
+        chip8.run_frame()
+    return chip8.display.tobytes()
+
+assert screen(1) == screen(1)
+assert chip8_emu.Chip8().seed != chip8_emu.Chip8().seed
+"#)
+        .unwrap();
+    }
+
+    #[test]
+    fn round_trips_state() {
+        run(r#"
+chip8 = chip8_emu.Chip8(seed=1, shift_uses_vy=True)
+chip8.load(rom)
+state = chip8.save_state()
+chip8.run_frame()
+chip8.keypress(15, True)
+chip8.load_state(state)
+assert not any(chip8.display.tobytes())
+"#)
+        .unwrap();
+    }
+
+    #[test]
+    fn raises_chip8_errors() {
+        run(r#"
+def raises(call):
+    try:
+        call()
+    except chip8_emu.Chip8Error:
+        return True
+    return False
+
+chip8 = chip8_emu.Chip8()
+assert raises(lambda: chip8.load(bytes(4000)))
+assert raises(lambda: chip8.keypress(16, True))
+assert raises(lambda: chip8.load_state(b"nope"))
+assert raises(lambda: chip8_emu.Chip8(shift=True))
+"#)
+        .unwrap();
+    }
+}
@@ -0,0 +1,419 @@
+// behavior that differs between the interpreters CHIP-8 programs were written for.
+//
+// every quirk is read as its instruction runs and nothing about one carries over from an earlier
+// instruction, so changing one takes effect immediately, from the next instruction on. whether a
+// draw already ran this frame, which display_wait looks at, is kept with the quirk off too.
+// frontends change them between frames so a frame never runs with two sets
+
+use crate::Chip8;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quirks {
+    // 8XY6/8XYE shift VY into VX like the COSMAC VIP, instead of shifting VX in place
+    pub shift_uses_vy: bool,
+    // FX55/FX65 leave I just past the last register like the COSMAC VIP, instead of unchanged
+    pub memory_increment_i: bool,
+    // BNNN jumps to XNN + VX like CHIP-48 and SUPER-CHIP, instead of NNN + V0
+    pub jump_uses_vx: bool,
+    // DXYN cuts sprites off at the screen edges, only wrapping where they start, instead of
+    // wrapping every pixel around
+    pub clip_sprites: bool,
+    // 8XY1/8XY2/8XY3 clear VF like the COSMAC VIP, instead of leaving it
+    pub vf_reset: bool,
+    // DXYN draws once a frame like the COSMAC VIP waiting for the vertical blank, a second one in
+    // the same frame waits for the next, instead of drawing straight away
+    pub display_wait: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Quirk {
+    ShiftUsesVy,
+    MemoryIncrementI,
+    JumpUsesVx,
+    ClipSprites,
+    VfReset,
+    DisplayWait,
+}
+
+// name, quirk, what it does
+const QUIRKS: &[(&str, Quirk, &str)] = &[
+    ("shift_uses_vy", Quirk::ShiftUsesVy, "8XY6/8XYE shift VY into VX"),
+    ("memory_increment_i", Quirk::MemoryIncrementI, "FX55/FX65 move I past the registers"),
+    ("jump_uses_vx", Quirk::JumpUsesVx, "BXNN jumps to XNN + VX"),
+    ("clip_sprites", Quirk::ClipSprites, "DXYN clips at the screen edges"),
+    ("vf_reset", Quirk::VfReset, "8XY1/8XY2/8XY3 clear VF"),
+    ("display_wait", Quirk::DisplayWait, "DXYN draws once a frame"),
+];
+
+impl Quirk {
+    pub fn all() -> impl Iterator<Item = Quirk> {
+        QUIRKS.iter().map(|&(_, quirk, _)| quirk)
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        QUIRKS.iter().find(|&&(known, _, _)| known == name).map(|&(_, quirk, _)| quirk)
+    }
+
+    pub fn name(self) -> &'static str {
+        QUIRKS.iter().find(|&&(_, known, _)| known == self).map_or("?", |&(name, _, _)| name)
+    }
+
+    pub fn description(self) -> &'static str {
+        QUIRKS.iter().find(|&&(_, known, _)| known == self).map_or("?", |&(_, _, description)| description)
+    }
+}
+
+impl Quirks {
+    // the quirks of the interpreter a ROM was written for, as far as they're supported: the COSMAC
+    // VIP's CHIP-8 and XO-CHIP shift VY and move I past what FX55/FX65 touch, SUPER-CHIP does
+    // neither but jumps with VX. XO-CHIP is the one that wraps sprites, and only the VIP clears VF
+    // after the logical instructions and waits for the vertical blank to draw
+    pub fn preset(name: &str) -> Option<Self> {
+        let vip = Self { shift_uses_vy: true, memory_increment_i: true, ..Self::default() };
+
+        match name {
+            "chip8" => Some(Self { clip_sprites: true, vf_reset: true, display_wait: true, ..vip }),
+            "xochip" => Some(vip),
+            "schip" => Some(Self { jump_uses_vx: true, clip_sprites: true, ..Self::default() }),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, quirk: Quirk) -> bool {
+        match quirk {
+            Quirk::ShiftUsesVy => self.shift_uses_vy,
+            Quirk::MemoryIncrementI => self.memory_increment_i,
+            Quirk::JumpUsesVx => self.jump_uses_vx,
+            Quirk::ClipSprites => self.clip_sprites,
+            Quirk::VfReset => self.vf_reset,
+            Quirk::DisplayWait => self.display_wait,
+        }
+    }
+
+    pub fn set(&mut self, quirk: Quirk, is_enabled: bool) {
+        match quirk {
+            Quirk::ShiftUsesVy => self.shift_uses_vy = is_enabled,
+            Quirk::MemoryIncrementI => self.memory_increment_i = is_enabled,
+            Quirk::JumpUsesVx => self.jump_uses_vx = is_enabled,
+            Quirk::ClipSprites => self.clip_sprites = is_enabled,
+            Quirk::VfReset => self.vf_reset = is_enabled,
+            Quirk::DisplayWait => self.display_wait = is_enabled,
+        }
+    }
+}
+
+impl Chip8 {
+    // a new machine with `quirks` from the first instruction, the builder for anything more
+    pub fn with_quirks(quirks: Quirks) -> Self {
+        let mut chip8 = Self::new();
+        chip8.quirks = quirks;
+
+        chip8
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    // safe mid-run, see the top of this file
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+        self.step_history.clear();
+    }
+
+    pub fn set_quirk(&mut self, quirk: Quirk, is_enabled: bool) {
+        self.quirks.set(quirk, is_enabled);
+        self.step_history.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{Instruction, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+    // V1 = 0x81, then forever: V0 = 0x10, V0 = V1 >> 1, jump back
+    const SHIFT: [u8; 8] = [0x61, 0x81, 0x60, 0x10, 0x80, 0x16, 0x12, 0x02];
+
+    #[test]
+    fn shifts_vx_in_place_by_default() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&SHIFT).unwrap();
+        chip8.run_frame(3);
+
+        assert_eq!(chip8.registers()[0], 0x08);
+        assert_eq!(chip8.registers()[0xF], 0);
+    }
+
+    #[test]
+    fn a_shift_flip_mid_run_applies_from_the_next_shift() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&SHIFT).unwrap();
+
+        // up to the first shift
+        chip8.run_frame(3);
+        assert_eq!((chip8.registers()[0], chip8.registers()[0xF]), (0x08, 0));
+
+        chip8.set_quirk(Quirk::ShiftUsesVy, true);
+        assert!(chip8.quirks().shift_uses_vy);
+
+        // jump back, V0 = 0x10, shift again, now out of V1
+        chip8.run_frame(3);
+        assert_eq!((chip8.registers()[0], chip8.registers()[0xF]), (0x40, 1));
+        assert_eq!(chip8.registers()[1], 0x81);
+
+        chip8.set_quirks(Quirks::default());
+        chip8.run_frame(3);
+        assert_eq!((chip8.registers()[0], chip8.registers()[0xF]), (0x08, 0));
+    }
+
+    // V1 = 0x81, V2 = 0xC1, then V0 = V1 >> 1, V3 = V2 << 1 and stop
+    const BOTH_SHIFTS: [u8; 12] = [0x61, 0x81, 0x62, 0xC1, 0x60, 0x10, 0x80, 0x16, 0x83, 0x2E, 0x12, 0x0A];
+
+    // V0, V3 and VF after both shifts, the flag being the one SHL left
+    fn shifted(quirks: Quirks, is_debug: bool) -> (u8, u8, u8) {
+        let mut chip8 = Chip8::with_quirks(quirks);
+        chip8.load(&BOTH_SHIFTS).unwrap();
+        chip8.is_debug = is_debug;
+        chip8.run_frame(5);
+
+        (chip8.registers()[0], chip8.registers()[3], chip8.registers()[0xF])
+    }
+
+    #[test]
+    fn both_shifts_follow_the_quirk() {
+        let vip = Quirks { shift_uses_vy: true, ..Quirks::default() };
+
+        // V0 = 0x81 >> 1 with bit 0 out, V3 = 0xC1 << 1 with bit 7 out
+        assert_eq!(shifted(vip, false), (0x40, 0x82, 1));
+        // V0 = 0x10 >> 1, V3 = 0x00 << 1
+        assert_eq!(shifted(Quirks::default(), false), (0x08, 0x00, 0));
+
+        // the debug path takes the same branches
+        assert_eq!(shifted(vip, true), shifted(vip, false));
+        assert_eq!(shifted(Quirks::default(), true), shifted(Quirks::default(), false));
+    }
+
+    #[test]
+    fn a_new_machine_starts_with_its_quirks() {
+        assert!(Chip8::with_quirks(Quirks { shift_uses_vy: true, ..Quirks::default() }).quirks().shift_uses_vy);
+        assert_eq!(Chip8::with_quirks(Quirks::default()).quirks(), Chip8::new().quirks());
+    }
+
+    // V0-V3 = 1, 2, 3, 4, I = 0x300, store them, I = 0x300, load them back into V0-V3 cleared, stop
+    const STORE_LOAD: [u8; 30] = [
+        0x60, 0x01, 0x61, 0x02, 0x62, 0x03, 0x63, 0x04, 0xA3, 0x00, 0xF3, 0x55, // store
+        0x60, 0x00, 0x61, 0x00, 0x62, 0x00, 0x63, 0x00, 0xA3, 0x00, 0xF3, 0x65, // load
+        0x12, 0x18, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    // RAM at 0x300 and I after the store, then V0-V4 and I after the load
+    fn store_and_load(memory_increment_i: bool) -> ([u8; 5], u16, [u8; 5], u16) {
+        let mut chip8 = Chip8::with_quirks(Quirks { memory_increment_i, ..Quirks::default() });
+        chip8.load(&STORE_LOAD).unwrap();
+
+        chip8.run_frame(6);
+        let stored = (chip8.memory()[0x300..0x305].try_into().unwrap(), chip8.register_i());
+        chip8.run_frame(6);
+
+        (stored.0, stored.1, chip8.registers()[..5].try_into().unwrap(), chip8.register_i())
+    }
+
+    #[test]
+    fn loads_and_stores_move_i_only_with_the_quirk() {
+        assert_eq!(store_and_load(false), ([1, 2, 3, 4, 0], 0x300, [1, 2, 3, 4, 0], 0x300));
+        // past V3 both times, each from the 0x300 set just before it
+        assert_eq!(store_and_load(true), ([1, 2, 3, 4, 0], 0x304, [1, 2, 3, 4, 0], 0x304));
+    }
+
+    // V0 = 0x10, V3 = 0x20, then B304
+    const JUMP: [u8; 6] = [0x60, 0x10, 0x63, 0x20, 0xB3, 0x04];
+
+    fn jumped(jump_uses_vx: bool, is_debug: bool) -> u16 {
+        let mut chip8 = Chip8::with_quirks(Quirks { jump_uses_vx, ..Quirks::default() });
+        chip8.load(&JUMP).unwrap();
+        chip8.is_debug = is_debug;
+        chip8.run_frame(3);
+
+        chip8.program_counter()
+    }
+
+    #[test]
+    fn jumps_with_v0_unless_the_quirk_says_vx() {
+        // 0x304 + V0
+        assert_eq!(jumped(false, false), 0x314);
+        // 0x304 + V3, X being the 3 of 304
+        assert_eq!(jumped(true, false), 0x324);
+
+        assert_eq!(jumped(false, true), 0x314);
+        assert_eq!(jumped(true, true), 0x324);
+    }
+
+    // V0 = 62, V1 = 30, I = 0x210, draw the 3 rows there and stop
+    const CORNER: [u8; 19] = [
+        0x60, 0x3E, 0x61, 0x1E, 0xA2, 0x10, 0xD0, 0x13, 0x12, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // code
+        0xF0, 0x90, 0xF0, // 1111, 1001, 1111
+    ];
+
+    // the lit pixels as (x, y) and VF after drawing the corner sprite, over (0, 0) already lit when
+    // `is_covered`
+    fn corner(clip_sprites: bool, is_covered: bool, is_debug: bool) -> (Vec<(usize, usize)>, u8) {
+        let mut chip8 = Chip8::with_quirks(Quirks { clip_sprites, ..Quirks::default() });
+        chip8.load(&CORNER).unwrap();
+        chip8.screen[0] = is_covered;
+        chip8.is_debug = is_debug;
+        chip8.run_frame(5);
+
+        let lit = (0..SCREEN_HEIGHT)
+            .flat_map(|y| (0..SCREEN_WIDTH).map(move |x| (x, y)))
+            .filter(|&(x, y)| chip8.get_display()[x + y * SCREEN_WIDTH])
+            .collect();
+
+        (lit, chip8.registers()[0xF])
+    }
+
+    #[test]
+    fn sprites_wrap_or_clip_at_the_edges() {
+        let (wrapped, _) = corner(false, false, false);
+        assert_eq!(
+            wrapped,
+            [(0, 0), (1, 0), (62, 0), (63, 0), (0, 30), (1, 30), (62, 30), (63, 30), (1, 31), (62, 31)]
+        );
+
+        let (clipped, _) = corner(true, false, false);
+        assert_eq!(clipped, [(62, 30), (63, 30), (62, 31)]);
+
+        for clip_sprites in [false, true] {
+            assert_eq!(corner(clip_sprites, false, true), corner(clip_sprites, false, false));
+        }
+    }
+
+    #[test]
+    fn only_pixels_that_land_collide() {
+        // the wrapped sprite turns (0, 0) off, the clipped one never reaches it
+        let (wrapped, collided) = corner(false, true, false);
+        assert_eq!((wrapped.contains(&(0, 0)), collided), (false, 1));
+
+        let (clipped, collided) = corner(true, true, false);
+        assert_eq!((clipped.contains(&(0, 0)), collided), (true, 0));
+    }
+
+    // VF = 1, V1 = 0x0C, V2 = 0x0A, then the logical instruction
+    fn logic(opcode: u16, vf_reset: bool, is_debug: bool) -> (u8, u8) {
+        let [high, low] = opcode.to_be_bytes();
+        let mut chip8 = Chip8::with_quirks(Quirks { vf_reset, ..Quirks::default() });
+        chip8.load(&[0x6F, 0x01, 0x61, 0x0C, 0x62, 0x0A, high, low]).unwrap();
+        chip8.is_debug = is_debug;
+        chip8.run_frame(4);
+
+        (chip8.registers()[1], chip8.registers()[0xF])
+    }
+
+    #[test]
+    fn logical_instructions_clear_vf_only_with_the_quirk() {
+        for (opcode, value) in [(0x8121, 0x0E), (0x8122, 0x08), (0x8123, 0x06)] {
+            for is_debug in [false, true] {
+                assert_eq!(logic(opcode, false, is_debug), (value, 1), "{:04X}", opcode);
+                assert_eq!(logic(opcode, true, is_debug), (value, 0), "{:04X}", opcode);
+            }
+        }
+    }
+
+    #[test]
+    fn the_debug_output_says_when_vf_is_cleared() {
+        let mut chip8 = Chip8::new();
+        assert_eq!(chip8.vf_reset_note(), "");
+
+        chip8.set_quirk(Quirk::VfReset, true);
+        assert_eq!(chip8.vf_reset_note(), " (vf_reset: VF = 0)");
+    }
+
+    // the font's 0 at (0, 0), then the font's 1 at (8, 0), then stop. the 1's top row starts two
+    // pixels in
+    const TWO_DRAWS: [u8; 10] = [0x61, 0x08, 0xD0, 0x05, 0xA0, 0x05, 0xD1, 0x05, 0x12, 0x08];
+
+    // whether each digit shows after each of two frames
+    fn draws(display_wait: bool, is_debug: bool) -> [(bool, bool); 2] {
+        let mut chip8 = Chip8::with_quirks(Quirks { display_wait, ..Quirks::default() });
+        chip8.load(&TWO_DRAWS).unwrap();
+        chip8.is_debug = is_debug;
+
+        [0, 1].map(|_| {
+            chip8.run_frame(10);
+            (chip8.get_display()[0], chip8.get_display()[10])
+        })
+    }
+
+    #[test]
+    fn draws_once_a_frame_with_the_quirk() {
+        for is_debug in [false, true] {
+            assert_eq!(draws(false, is_debug), [(true, true), (true, true)]);
+            assert_eq!(draws(true, is_debug), [(true, false), (true, true)]);
+        }
+    }
+
+    #[test]
+    fn a_held_draw_spins_until_the_timers_tick() {
+        let mut chip8 = Chip8::with_quirks(Quirks { display_wait: true, ..Quirks::default() });
+        chip8.load(&TWO_DRAWS).unwrap();
+
+        for _ in 0..5 {
+            chip8.tick().unwrap();
+        }
+        assert_eq!((chip8.program_counter(), chip8.draw_count()), (0x206, 1));
+        assert_eq!(chip8.display_wait_note(), " (display_wait: waits for the next frame)");
+
+        chip8.tick_timers();
+        chip8.tick().unwrap();
+        assert_eq!((chip8.program_counter(), chip8.draw_count()), (0x208, 2));
+    }
+
+    // execute_instruction doesn't fetch, so at pc 0 the draw held back is "at" 0xFFFE
+    #[test]
+    fn a_held_draw_at_the_bottom_of_memory_wraps() {
+        let mut chip8 = Chip8::with_quirks(Quirks { display_wait: true, ..Quirks::default() });
+        chip8.program_counter = 0;
+
+        chip8.execute_instruction(Instruction::Draw(0, 0, 5)).unwrap();
+        chip8.execute_instruction(Instruction::Draw(0, 0, 5)).unwrap();
+        assert_eq!((chip8.program_counter(), chip8.draw_count()), (0xFFFE, 1));
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for quirk in Quirk::all() {
+            assert_eq!(Quirk::from_name(quirk.name()), Some(quirk));
+            assert_ne!(quirk.description(), "?");
+        }
+
+        assert_eq!(Quirk::from_name("shift"), None);
+        assert_eq!(
+            Quirks::preset("chip8"),
+            Some(Quirks {
+                shift_uses_vy: true,
+                memory_increment_i: true,
+                jump_uses_vx: false,
+                clip_sprites: true,
+                vf_reset: true,
+                display_wait: true
+            })
+        );
+        assert_eq!(Quirks::preset("xochip").map(|quirks| quirks.clip_sprites), Some(false));
+        assert_eq!(
+            Quirks::preset("schip"),
+            Some(Quirks { jump_uses_vx: true, clip_sprites: true, ..Quirks::default() })
+        );
+        assert_eq!(Quirks::preset("megachip"), None);
+    }
+
+    #[test]
+    fn sets_single_quirks() {
+        let mut quirks = Quirks::default();
+
+        for quirk in Quirk::all() {
+            quirks.set(quirk, true);
+            assert!(quirks.get(quirk));
+        }
+    }
+}
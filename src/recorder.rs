@@ -0,0 +1,136 @@
+use crate::Chip8;
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+const BITS_PER_SAMPLE: u16 = 16;
+const CHANNELS: u16 = 1;
+
+// collects everything fill_audio generates so it can be written out as a WAV file
+#[derive(Clone, Debug, Default)]
+pub struct AudioRecorder {
+    samples: Vec<f32>,
+    sample_rate: u32,
+}
+
+impl AudioRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub(crate) fn record(&mut self, samples: &[f32], sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.samples.extend_from_slice(samples);
+    }
+
+    pub fn write_wav<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+        let byte_rate = self.sample_rate * block_align as u32;
+        let data_size = (self.samples.len() * block_align as usize) as u32;
+
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(36 + data_size).to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        // 1 = integer PCM
+        writer.write_all(&1u16.to_le_bytes())?;
+        writer.write_all(&CHANNELS.to_le_bytes())?;
+        writer.write_all(&self.sample_rate.to_le_bytes())?;
+        writer.write_all(&byte_rate.to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+
+        writer.write_all(b"data")?;
+        writer.write_all(&data_size.to_le_bytes())?;
+
+        for sample in &self.samples {
+            let pcm = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            writer.write_all(&pcm.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn save_wav<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        self.write_wav(&mut writer)?;
+        writer.flush()
+    }
+}
+
+impl Chip8 {
+    // from now on every fill_audio buffer is also appended to the recorder
+    pub fn attach_audio_recorder(&mut self, recorder: AudioRecorder) {
+        self.audio_recorder = Some(recorder);
+    }
+
+    pub fn detach_audio_recorder(&mut self) -> Option<AudioRecorder> {
+        self.audio_recorder.take()
+    }
+
+    pub fn audio_recorder(&self) -> Option<&AudioRecorder> {
+        self.audio_recorder.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn records_a_second_of_beeping_as_wav() {
+        let mut chip8 = Chip8::new();
+        // V0 = 120, ST = V0, spin
+        chip8.load(&[0x60, 0x78, 0xF0, 0x18, 0x12, 0x04]).unwrap();
+        chip8.tick().unwrap();
+        chip8.tick().unwrap();
+        chip8.attach_audio_recorder(AudioRecorder::new());
+
+        let mut frame = [0.0; 735];
+
+        for _ in 0..60 {
+            chip8.fill_audio(&mut frame, 44100);
+            chip8.tick_timers();
+        }
+
+        let recorder = chip8.detach_audio_recorder().unwrap();
+        assert_eq!(recorder.samples().len(), 44100);
+        assert!(recorder.samples().iter().any(|&sample| sample != 0.0));
+
+        let mut wav = Vec::new();
+        recorder.write_wav(&mut wav).unwrap();
+
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(u32_at(&wav, 4) as usize, wav.len() - 8);
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(u16_at(&wav, 20), 1);
+        assert_eq!(u16_at(&wav, 22), 1);
+        assert_eq!(u32_at(&wav, 24), 44100);
+        assert_eq!(u32_at(&wav, 28), 88200);
+        assert_eq!(u16_at(&wav, 32), 2);
+        assert_eq!(u16_at(&wav, 34), 16);
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(u32_at(&wav, 40), 88200);
+        assert_eq!(wav.len(), 44 + 88200);
+    }
+}
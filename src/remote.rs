@@ -0,0 +1,496 @@
+// a line-delimited JSON control protocol, for driving the emulator from test rigs and other
+// languages without the C API. each request is one line, each gets one line back
+//
+//   -> {"id": 1, "cmd": "load_rom", "rom": "<base64>"}
+//   <- {"id":1,"ok":true,"result":{}}
+//   -> {"id": 2, "cmd": "run_frames", "frames": 60}
+//   <- {"id":2,"ok":true,"result":{"frames":60,"pc":520,"paused":false}}
+//   -> {"id": 3, "cmd": "key", "key": 99, "pressed": true}
+//   <- {"id":3,"ok":false,"error":"99 isn't a key, they go 0 to 15"}
+//
+// the id is anything JSON and comes back as is, null when the request had none or wasn't JSON.
+// the commands:
+//
+//   load_rom {rom}                 resets the machine, keeping the seed, and loads the ROM
+//   tick {count = 1}               runs instructions, past breakpoints and pauses
+//   run_frames {frames, ticks_per_frame = 10}
+//                                  60 Hz frames, stopping at a breakpoint
+//   pause {paused}                 a breakpoint pauses, run_frames does nothing until this resumes
+//   key {key, pressed}
+//   registers                      pc, i, v, sp, stack, timers and counts
+//   read_memory {address, length}  the bytes in base64
+//   add_breakpoint {address}, remove_breakpoint {address}, breakpoints
+//   display {format = "hash"}      "hash" is --print-hash's, "pbm" is a base64 plain PBM
+//   save_state                     the state in base64
+//   load_state {state}
+//
+// handle is all of it over the library. Server puts it on a socket, connections each get a thread
+// but the requests all come back to whoever owns the Chip8, so a frontend answers them between
+// frames and a headless run just waits for them
+
+use crate::conformance::screen_hash;
+use crate::headless::{panic_message, pbm};
+use crate::report::json_string;
+use crate::{Chip8, Chip8Error, NUM_KEYS};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::Value;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const TICKS_PER_FRAME: usize = 10;
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+enum Command {
+    LoadRom {
+        rom: String,
+    },
+    Tick {
+        #[serde(default = "one")]
+        count: usize,
+    },
+    RunFrames {
+        frames: usize,
+        #[serde(default = "ticks_per_frame")]
+        ticks_per_frame: usize,
+    },
+    Pause {
+        paused: bool,
+    },
+    Key {
+        key: usize,
+        pressed: bool,
+    },
+    Registers,
+    ReadMemory {
+        address: usize,
+        length: usize,
+    },
+    AddBreakpoint {
+        address: u16,
+    },
+    RemoveBreakpoint {
+        address: u16,
+    },
+    Breakpoints,
+    Display {
+        #[serde(default)]
+        format: DisplayFormat,
+    },
+    SaveState,
+    LoadState {
+        state: String,
+    },
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+enum DisplayFormat {
+    #[default]
+    Hash,
+    Pbm,
+}
+
+fn one() -> usize {
+    1
+}
+
+fn ticks_per_frame() -> usize {
+    TICKS_PER_FRAME
+}
+
+// one request line in, one response line out, without the newline
+pub fn handle(chip8: &mut Chip8, request: &str) -> String {
+    let request: Value = match serde_json::from_str(request) {
+        Ok(request) => request,
+        Err(err) => return response(&Value::Null, Err(format!("Not a JSON request: {}", err))),
+    };
+
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let result = match Command::deserialize(&request) {
+        Ok(command) => panic::catch_unwind(AssertUnwindSafe(|| run(chip8, command))).unwrap_or_else(|cause| {
+            // the program counter moves past an instruction before running it
+            let pc = chip8.program_counter().wrapping_sub(2);
+            Err(format!("The emulator crashed at {:03X}: {}", pc, panic_message(cause)))
+        }),
+        Err(err) => Err(err.to_string()),
+    };
+
+    response(&id, result)
+}
+
+// an instruction that couldn't run, the machine is left just before it
+fn fault_message(chip8: &Chip8, error: Chip8Error) -> String {
+    format!("The emulator crashed at {:03X}: {}", chip8.program_counter(), error)
+}
+
+fn load_message(error: Chip8Error) -> String {
+    format!("Unable to load the ROM: {}", error)
+}
+
+fn response(id: &Value, result: Result<String, String>) -> String {
+    match result {
+        Ok(result) => format!("{{\"id\":{},\"ok\":true,\"result\":{}}}", id, result),
+        Err(message) => format!("{{\"id\":{},\"ok\":false,\"error\":{}}}", id, json_string(&message)),
+    }
+}
+
+// the result object as JSON
+fn run(chip8: &mut Chip8, command: Command) -> Result<String, String> {
+    let result = match command {
+        Command::LoadRom { rom } => {
+            let rom = decode(&rom)?;
+
+            // checked before the reset, a ROM that doesn't load leaves the machine as it was
+            chip8.check_rom(&rom).map_err(load_message)?;
+            chip8.reset();
+            chip8.load(&rom).map_err(load_message)?;
+            "{}".to_string()
+        },
+        Command::Tick { count } => {
+            for _ in 0..count {
+                chip8.tick().map_err(|error| fault_message(chip8, error))?;
+            }
+
+            format!("{{\"pc\":{}}}", chip8.program_counter())
+        },
+        Command::RunFrames { frames, ticks_per_frame } => {
+            let mut breakpoint = None;
+
+            for _ in 0..frames {
+                chip8.run_frame(ticks_per_frame);
+
+                if let Some(error) = chip8.take_fault() {
+                    return Err(fault_message(chip8, error));
+                }
+
+                breakpoint = chip8.take_breakpoint_hit();
+
+                if breakpoint.is_some() || chip8.is_paused() {
+                    break;
+                }
+            }
+
+            let breakpoint = breakpoint.map_or("null".to_string(), |address| address.to_string());
+            format!(
+                "{{\"frames\":{},\"pc\":{},\"paused\":{},\"breakpoint\":{}}}",
+                chip8.frame_count(),
+                chip8.program_counter(),
+                chip8.is_paused(),
+                breakpoint
+            )
+        },
+        Command::Pause { paused } => {
+            chip8.set_paused(paused);
+            "{}".to_string()
+        },
+        Command::Key { key, pressed } => {
+            if key >= NUM_KEYS {
+                return Err(format!("{} isn't a key, they go 0 to 15", key));
+            }
+
+            chip8.keypress(key, pressed);
+            "{}".to_string()
+        },
+        Command::Registers => {
+            let list = |values: Vec<String>| values.join(",");
+
+            format!(
+                "{{\"pc\":{},\"i\":{},\"v\":[{}],\"sp\":{},\"stack\":[{}],\"delay_timer\":{},\"sound_timer\":{},\"frames\":{},\"instructions\":{}}}",
+                chip8.program_counter(),
+                chip8.register_i(),
+                list(chip8.registers().iter().map(u8::to_string).collect()),
+                chip8.stack_pointer(),
+                list(chip8.stack()[..chip8.stack_pointer() as usize].iter().map(u16::to_string).collect()),
+                chip8.delay_timer(),
+                chip8.sound_timer(),
+                chip8.frame_count(),
+                chip8.instruction_count()
+            )
+        },
+        Command::ReadMemory { address, length } => {
+            let memory = chip8.memory();
+            let bytes = address
+                .checked_add(length)
+                .and_then(|end| memory.get(address..end))
+                .ok_or_else(|| format!("{} bytes from {:#05X} run past the end of memory", length, address))?;
+
+            format!("{{\"bytes\":\"{}\"}}", BASE64.encode(bytes))
+        },
+        Command::AddBreakpoint { address } => {
+            chip8.add_breakpoint(address);
+            "{}".to_string()
+        },
+        Command::RemoveBreakpoint { address } => {
+            chip8.remove_breakpoint(address);
+            "{}".to_string()
+        },
+        Command::Breakpoints => {
+            let addresses: Vec<String> = chip8.breakpoints().map(|address| address.to_string()).collect();
+            format!("{{\"addresses\":[{}]}}", addresses.join(","))
+        },
+        Command::Display { format: DisplayFormat::Hash } => {
+            format!("{{\"hash\":\"{}\"}}", screen_hash(chip8.get_display()))
+        },
+        Command::Display { format: DisplayFormat::Pbm } => {
+            format!("{{\"pbm\":\"{}\"}}", BASE64.encode(pbm(chip8.get_display())))
+        },
+        Command::SaveState => format!("{{\"state\":\"{}\"}}", BASE64.encode(chip8.save_state())),
+        Command::LoadState { state } => {
+            chip8.load_state(&decode(&state)?).map_err(|err| err.to_string())?;
+            "{}".to_string()
+        },
+    };
+
+    Ok(result)
+}
+
+fn decode(text: &str) -> Result<Vec<u8>, String> {
+    BASE64.decode(text).map_err(|err| format!("Not base64: {}", err))
+}
+
+enum Event {
+    Request(String, Sender<String>),
+    Closed,
+}
+
+pub struct Server {
+    events: Receiver<Event>,
+    address: SocketAddr,
+    is_closed: Arc<AtomicBool>,
+    acceptor: Option<JoinHandle<()>>,
+}
+
+impl Server {
+    // accepts connections in the background, nothing is answered until poll or serve
+    pub fn bind(address: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        // polled, so dropping the server can stop it and free the port for the next one
+        listener.set_nonblocking(true)?;
+
+        let (sender, events) = mpsc::channel();
+        let is_closed = Arc::new(AtomicBool::new(false));
+        let acceptor = {
+            let is_closed = is_closed.clone();
+
+            thread::spawn(move || {
+                while !is_closed.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, _)) if stream.set_nonblocking(false).is_ok() => {
+                            let sender = sender.clone();
+                            thread::spawn(move || connection(stream, sender));
+                        },
+                        Ok(_) => {},
+                        Err(_) => thread::sleep(ACCEPT_INTERVAL),
+                    }
+                }
+            })
+        };
+
+        Ok(Self { events, address, is_closed, acceptor: Some(acceptor) })
+    }
+
+    // the real port when bound to port 0
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    // answers what has come in without waiting, once a frame from a frontend
+    pub fn poll(&self, chip8: &mut Chip8) {
+        while let Ok(event) = self.events.try_recv() {
+            if let Event::Request(request, reply) = event {
+                let _ = reply.send(handle(chip8, &request));
+            }
+        }
+    }
+
+    // answers until a client disconnects
+    pub fn serve(&self, chip8: &mut Chip8) {
+        while let Ok(event) = self.events.recv() {
+            match event {
+                Event::Request(request, reply) => {
+                    let _ = reply.send(handle(chip8, &request));
+                },
+                Event::Closed => break,
+            }
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.is_closed.store(true, Ordering::Relaxed);
+
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+    }
+}
+
+fn connection(stream: TcpStream, sender: Sender<Event>) {
+    let Ok(reader) = stream.try_clone() else {
+        return;
+    };
+    let mut writer = stream;
+
+    for line in BufReader::new(reader).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (reply, response) = mpsc::channel();
+
+        if sender.send(Event::Request(line, reply)).is_err() {
+            return;
+        }
+
+        match response.recv() {
+            Ok(response) if writeln!(writer, "{}", response).is_ok() => {},
+            _ => break,
+        }
+    }
+
+    let _ = sender.send(Event::Closed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // draw "0" at (0, 0) then loop
+    const ROM: [u8; 4] = [0xD0, 0x05, 0x12, 0x02];
+
+    fn loaded() -> Chip8 {
+        let mut chip8 = Chip8::with_seed(1);
+        chip8.load(&ROM).unwrap();
+        chip8
+    }
+
+    fn json(response: &str) -> Value {
+        serde_json::from_str(response).unwrap()
+    }
+
+    #[test]
+    fn echoes_the_id() {
+        let mut chip8 = loaded();
+
+        let response = json(&handle(&mut chip8, r#"{"id": "a", "cmd": "tick"}"#));
+        assert_eq!(response["id"], "a");
+        assert_eq!(response["ok"], true);
+        assert_eq!(response["result"]["pc"], 0x202);
+
+        let response = json(&handle(&mut chip8, r#"{"cmd": "tick", "count": 2}"#));
+        assert_eq!(response["id"], Value::Null);
+        assert_eq!(response["result"]["pc"], 0x202);
+    }
+
+    #[test]
+    fn loads_and_runs_a_rom() {
+        let mut chip8 = Chip8::with_seed(1);
+        let request = format!(r#"{{"id": 1, "cmd": "load_rom", "rom": "{}"}}"#, BASE64.encode(ROM));
+        assert_eq!(handle(&mut chip8, &request), r#"{"id":1,"ok":true,"result":{}}"#);
+
+        let response = json(&handle(&mut chip8, r#"{"id": 2, "cmd": "run_frames", "frames": 3}"#));
+        assert_eq!(response["result"]["frames"], 3);
+        assert_eq!(response["result"]["paused"], false);
+
+        let response = json(&handle(&mut chip8, r#"{"id": 3, "cmd": "display"}"#));
+        assert_eq!(response["result"]["hash"], screen_hash(chip8.get_display()));
+
+        let response = json(&handle(&mut chip8, r#"{"id": 4, "cmd": "display", "format": "pbm"}"#));
+        let pbm = BASE64.decode(response["result"]["pbm"].as_str().unwrap()).unwrap();
+        assert!(pbm.starts_with(b"P1\n64 32\n1 1 1 1 0"));
+    }
+
+    #[test]
+    fn stops_at_breakpoints() {
+        let mut chip8 = loaded();
+
+        handle(&mut chip8, r#"{"cmd": "add_breakpoint", "address": 514}"#);
+        let response = json(&handle(&mut chip8, r#"{"cmd": "breakpoints"}"#));
+        assert_eq!(response["result"]["addresses"], serde_json::json!([514]));
+
+        let response = json(&handle(&mut chip8, r#"{"cmd": "run_frames", "frames": 10}"#));
+        assert_eq!(response["result"]["breakpoint"], 514);
+        assert_eq!(response["result"]["paused"], true);
+
+        handle(&mut chip8, r#"{"cmd": "remove_breakpoint", "address": 514}"#);
+        handle(&mut chip8, r#"{"cmd": "pause", "paused": false}"#);
+        let response = json(&handle(&mut chip8, r#"{"cmd": "run_frames", "frames": 2}"#));
+        assert_eq!(response["result"]["breakpoint"], Value::Null);
+    }
+
+    #[test]
+    fn reads_registers_and_memory() {
+        let mut chip8 = loaded();
+        handle(&mut chip8, r#"{"cmd": "key", "key": 15, "pressed": true}"#);
+        assert!(chip8.keys()[15]);
+
+        let response = json(&handle(&mut chip8, r#"{"cmd": "registers"}"#));
+        assert_eq!(response["result"]["pc"], 0x200);
+        assert_eq!(response["result"]["v"].as_array().unwrap().len(), 16);
+
+        let response = json(&handle(&mut chip8, r#"{"cmd": "read_memory", "address": 512, "length": 4}"#));
+        assert_eq!(BASE64.decode(response["result"]["bytes"].as_str().unwrap()).unwrap(), ROM);
+    }
+
+    #[test]
+    fn round_trips_state() {
+        let mut chip8 = loaded();
+
+        let response = json(&handle(&mut chip8, r#"{"cmd": "save_state"}"#));
+        let state = response["result"]["state"].as_str().unwrap().to_string();
+        chip8.run_frame(10);
+
+        let request = format!(r#"{{"cmd": "load_state", "state": "{}"}}"#, state);
+        assert_eq!(json(&handle(&mut chip8, &request))["ok"], true);
+        assert!(!chip8.get_display()[0]);
+    }
+
+    #[test]
+    fn reports_errors() {
+        let mut chip8 = loaded();
+        let error = |chip8: &mut Chip8, request: &str| {
+            let response = json(&handle(chip8, request));
+            assert_eq!(response["ok"], false, "{}", request);
+            response["error"].as_str().unwrap().to_string()
+        };
+
+        assert!(error(&mut chip8, "nope").starts_with("Not a JSON request"));
+        assert!(error(&mut chip8, r#"{"cmd": "fly"}"#).contains("unknown variant"));
+        assert!(error(&mut chip8, r#"{"cmd": "key", "key": 16, "pressed": true}"#).contains("isn't a key"));
+        assert!(error(&mut chip8, r#"{"cmd": "read_memory", "address": 4000, "length": 100}"#).contains("past the end"));
+        assert!(error(&mut chip8, r#"{"cmd": "load_rom", "rom": "!!"}"#).starts_with("Not base64"));
+        assert!(!error(&mut chip8, r#"{"cmd": "load_state", "state": "AQID"}"#).is_empty());
+
+        let response = json(&handle(&mut chip8, r#"{"id": [7], "cmd": "key"}"#));
+        assert_eq!(response["id"], serde_json::json!([7]));
+        assert_eq!(response["ok"], false);
+    }
+
+    #[test]
+    fn survives_a_crash() {
+        // return with an empty stack
+        let mut chip8 = Chip8::with_seed(1);
+        chip8.load(&[0x00, 0xEE]).unwrap();
+
+        let response = json(&handle(&mut chip8, r#"{"cmd": "tick"}"#));
+        assert_eq!(response["ok"], false);
+        assert!(response["error"].as_str().unwrap().starts_with("The emulator crashed at 200"));
+    }
+}
@@ -0,0 +1,307 @@
+use crate::{Chip8, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use std::collections::VecDeque;
+use std::mem;
+
+pub const PACKED_SCREEN_BYTES: usize = SCREEN_WIDTH * SCREEN_HEIGHT / 8;
+
+// the screen at one bit per pixel, most significant bit first
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PackedScreen([u8; PACKED_SCREEN_BYTES]);
+
+impl PackedScreen {
+    pub fn pack(screen: &[bool]) -> Self {
+        let mut bytes = [0; PACKED_SCREEN_BYTES];
+
+        for (byte, pixels) in bytes.iter_mut().zip(screen.chunks(8)) {
+            *byte = pixels.iter().fold(0, |byte, &pixel| byte << 1 | pixel as u8);
+        }
+
+        Self(bytes)
+    }
+
+    pub fn unpack(&self) -> [bool; SCREEN_WIDTH * SCREEN_HEIGHT] {
+        let mut screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+
+        for (pixels, byte) in screen.chunks_mut(8).zip(self.0) {
+            for (bit, pixel) in pixels.iter_mut().enumerate() {
+                *pixel = byte & 0x80 >> bit != 0;
+            }
+        }
+
+        screen
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Chip8 {
+    // the screen without allocating, for small displays that take one bit a pixel
+    pub fn packed_display(&self) -> PackedScreen {
+        PackedScreen::pack(&self.screen)
+    }
+}
+
+// the screen after each of the last `capacity` frames. a screen that stays the same is kept once
+// with a count, so a mostly still game costs next to nothing
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ReplayBuffer {
+    runs: VecDeque<(PackedScreen, usize)>,
+    frames: usize,
+    capacity: usize,
+}
+
+impl ReplayBuffer {
+    pub(crate) fn push(&mut self, screen: &[bool]) {
+        let packed = PackedScreen::pack(screen);
+
+        match self.runs.back_mut() {
+            Some((last, count)) if *last == packed => *count += 1,
+            _ => self.runs.push_back((packed, 1)),
+        }
+
+        self.frames += 1;
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.frames > self.capacity {
+            let Some((_, count)) = self.runs.front_mut() else {
+                break;
+            };
+
+            let dropped = (*count).min(self.frames - self.capacity);
+            *count -= dropped;
+            self.frames -= dropped;
+
+            if *count == 0 {
+                self.runs.pop_front();
+            }
+        }
+    }
+
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    fn clear(&mut self) {
+        self.runs.clear();
+        self.frames = 0;
+    }
+
+    fn screens(&self) -> impl Iterator<Item = PackedScreen> + '_ {
+        self.runs.iter().flat_map(|&(screen, count)| std::iter::repeat_n(screen, count))
+    }
+
+    fn memory(&self) -> usize {
+        self.runs.len() * mem::size_of::<(PackedScreen, usize)>()
+    }
+}
+
+impl Chip8 {
+    // keep the screens of the last `frames` frames for replay_screens, 0 (the default) turns it off
+    pub fn set_replay_capacity(&mut self, frames: usize) {
+        self.replay.set_capacity(frames);
+    }
+
+    pub fn replay_capacity(&self) -> usize {
+        self.replay.capacity
+    }
+
+    // the rewind buffer has a snapshot, screen included, of every frame it covers. when it reaches
+    // back at least as far the screens come from there instead of being stored twice
+    pub(crate) fn is_replay_from_rewind(&self) -> bool {
+        self.rewind_capacity() >= self.replay.capacity
+    }
+
+    pub(crate) fn record_replay_frame(&mut self) {
+        if self.replay.capacity > 0 && !self.is_replay_from_rewind() {
+            self.replay.push(&self.screen);
+        }
+    }
+
+    pub fn clear_replay(&mut self) {
+        self.replay.clear();
+    }
+
+    // the screen after each of the last replay_capacity frames that were run, oldest first
+    pub fn replay_screens(&self) -> Vec<PackedScreen> {
+        if self.replay.capacity == 0 {
+            return Vec::new();
+        }
+
+        if !self.is_replay_from_rewind() {
+            return self.replay.screens().collect();
+        }
+
+        // a snapshot is taken as a frame starts, so each one after the oldest shows the frame
+        // before it, and the current screen is the last frame's
+        let mut screens: Vec<PackedScreen> = self
+            .rewind_screens()
+            .skip(1)
+            .map(PackedScreen::pack)
+            .chain(self.rewind_len().checked_sub(1).map(|_| PackedScreen::pack(&self.screen)))
+            .collect();
+
+        let excess = screens.len().saturating_sub(self.replay.capacity);
+        screens.drain(..excess);
+        screens
+    }
+
+    // bytes held for replays, nothing extra while the rewind buffer covers them
+    pub fn replay_memory(&self) -> usize {
+        if self.is_replay_from_rewind() {
+            0
+        } else {
+            self.replay.memory()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // V0 += 1, draw the font digit for V0 & 0xF at (0, 0) over the last one, forever
+    const ROM: [u8; 12] = [0x70, 0x01, 0x61, 0x0F, 0x81, 0x02, 0xF1, 0x29, 0xD2, 0x25, 0x12, 0x00];
+
+    fn screen(lit: &[usize]) -> Vec<bool> {
+        let mut screen = vec![false; SCREEN_WIDTH * SCREEN_HEIGHT];
+
+        for &pixel in lit {
+            screen[pixel] = true;
+        }
+
+        screen
+    }
+
+    fn buffer(capacity: usize) -> ReplayBuffer {
+        ReplayBuffer { capacity, ..ReplayBuffer::default() }
+    }
+
+    fn lit(screen: &PackedScreen) -> Vec<usize> {
+        screen.unpack().iter().enumerate().filter(|(_, &pixel)| pixel).map(|(index, _)| index).collect()
+    }
+
+    #[test]
+    fn packs_eight_pixels_a_byte() {
+        let screen = screen(&[0, 7, 9, 2047]);
+        let packed = PackedScreen::pack(&screen);
+
+        assert_eq!(packed.bytes()[..2], [0b1000_0001, 0b0100_0000]);
+        assert_eq!(packed.bytes()[255], 0b0000_0001);
+        assert_eq!(packed.unpack()[..], screen[..]);
+    }
+
+    #[test]
+    fn packs_the_live_screen() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ROM).unwrap();
+        chip8.run_frame(5);
+
+        // "1" has a 0x20 top row, drawn at (0, 0)
+        assert_eq!(chip8.packed_display().bytes()[0], 0x20);
+        assert_eq!(chip8.packed_display(), PackedScreen::pack(chip8.get_display()));
+    }
+
+    #[test]
+    fn keeps_the_latest_frames() {
+        let mut buffer = buffer(3);
+
+        for pixel in 0..5 {
+            buffer.push(&screen(&[pixel]));
+        }
+
+        let screens: Vec<Vec<usize>> = buffer.screens().map(|screen| lit(&screen)).collect();
+        assert_eq!(screens, [[2], [3], [4]]);
+    }
+
+    #[test]
+    fn a_still_screen_is_stored_once() {
+        let mut buffer = buffer(100);
+
+        for _ in 0..50 {
+            buffer.push(&screen(&[1]));
+        }
+        buffer.push(&screen(&[2]));
+
+        assert_eq!(buffer.screens().count(), 51);
+        assert_eq!(buffer.runs.len(), 2);
+        assert_eq!(buffer.memory(), 2 * mem::size_of::<(PackedScreen, usize)>());
+    }
+
+    #[test]
+    fn evicts_part_of_a_run() {
+        let mut buffer = buffer(4);
+
+        for _ in 0..3 {
+            buffer.push(&screen(&[1]));
+        }
+        for _ in 0..3 {
+            buffer.push(&screen(&[2]));
+        }
+
+        // one frame of the first run is still in
+        assert_eq!(buffer.runs.iter().map(|&(_, count)| count).collect::<Vec<_>>(), [1, 3]);
+        assert_eq!(buffer.frames, 4);
+
+        buffer.set_capacity(2);
+        assert_eq!(buffer.runs.len(), 1);
+        assert_eq!(buffer.screens().count(), 2);
+    }
+
+    #[test]
+    fn stores_nothing_while_off() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ROM).unwrap();
+
+        for _ in 0..5 {
+            chip8.run_frame(5);
+        }
+
+        assert!(chip8.replay_screens().is_empty());
+        assert_eq!(chip8.replay_memory(), 0);
+    }
+
+    #[test]
+    fn records_what_each_frame_showed() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ROM).unwrap();
+        chip8.set_replay_capacity(3);
+        let mut shown = Vec::new();
+
+        for _ in 0..5 {
+            chip8.run_frame(5);
+            shown.push(PackedScreen::pack(chip8.get_display()));
+        }
+
+        assert_eq!(chip8.replay_screens(), shown[2..]);
+        assert!(chip8.replay_memory() > 0);
+    }
+
+    #[test]
+    fn reuses_the_rewind_buffer() {
+        let mut stored = Chip8::new();
+        stored.load(&ROM).unwrap();
+        stored.set_replay_capacity(4);
+
+        let mut shared = Chip8::new();
+        shared.load(&ROM).unwrap();
+        shared.set_replay_capacity(4);
+        shared.set_rewind_capacity(10);
+
+        for frames in 0..8 {
+            // the same screens whether or not the rewind buffer is full yet
+            assert_eq!(shared.replay_screens(), stored.replay_screens(), "after {} frames", frames);
+            stored.run_frame(5);
+            shared.run_frame(5);
+        }
+
+        assert_eq!(shared.replay_screens().len(), 4);
+        assert_eq!(shared.replay_memory(), 0);
+        assert!(shared.replay.runs.is_empty());
+    }
+}
@@ -0,0 +1,376 @@
+// a headless run summed up for CI: what happened, and whether it's what the caller expected.
+//
+// the JSON is schema version REPORT_VERSION. adding a field keeps the version, renaming or
+// removing one or changing what a value means bumps it
+//
+// {
+//   "version": 1,
+//   "verdict": "pass" | "fail",
+//   "frames": frames run, fewer than asked for after a crash or a failing --script,
+//   "halt": "frames"       every frame ran and the ROM was still going
+//           "self_jump"    stopped on a 1NNN jumping to itself, how most test ROMs end
+//           "key_wait"     waiting for a key in FX0A
+//           "crash",
+//   "screen_hash": the final screen, the same hash as --print-hash and the conformance goldens,
+//   "seed": what RND was seeded with, --seed repeats the run,
+//   "stats": { "instructions": N, "beeps": times the sound started },
+//   "errors": [{ "pc": address of the instruction, "opcode": "00EE", "message": "...",
+//                "report": diagnostics::report_fault's plain text }],
+//   "failures": ["why the verdict is fail", ...]
+// }
+//
+// the process exits with EXIT_PASS, EXIT_FAIL, or EXIT_ERROR when the run couldn't start
+
+use crate::conformance::screen_hash;
+use crate::diagnostics::{opcode_at, report_fault, FAULT_TRACE_LENGTH};
+use crate::headless::{self, Options};
+use crate::{BeepEdge, Chip8, Chip8Error, FrameResult};
+
+use std::any::Any;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+pub const REPORT_VERSION: u32 = 1;
+
+pub const EXIT_PASS: i32 = 0;
+pub const EXIT_FAIL: i32 = 1;
+// the same code clap exits with for bad arguments
+pub const EXIT_ERROR: i32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HaltReason {
+    Frames,
+    SelfJump,
+    KeyWait,
+    Crash,
+}
+
+const HALT_NAMES: &[(&str, HaltReason)] = &[
+    ("frames", HaltReason::Frames),
+    ("self_jump", HaltReason::SelfJump),
+    ("key_wait", HaltReason::KeyWait),
+    ("crash", HaltReason::Crash),
+];
+
+impl HaltReason {
+    pub fn from_name(name: &str) -> Option<Self> {
+        HALT_NAMES.iter().find(|(known, _)| *known == name).map(|&(_, halt)| halt)
+    }
+
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        HALT_NAMES.iter().map(|&(name, _)| name)
+    }
+
+    // how a machine that didn't crash is left, from the instruction it's about to run
+    pub fn of(chip8: &Chip8) -> Self {
+        let pc = chip8.program_counter();
+        let memory = chip8.memory();
+        let opcode = match (memory.get(pc as usize), memory.get(pc as usize + 1)) {
+            (Some(&high), Some(&low)) => u16::from_be_bytes([high, low]),
+            _ => return HaltReason::Frames,
+        };
+
+        if opcode == 0x1000 | pc {
+            HaltReason::SelfJump
+        } else if opcode & 0xF0FF == 0xF00A {
+            HaltReason::KeyWait
+        } else {
+            HaltReason::Frames
+        }
+    }
+}
+
+impl fmt::Display for HaltReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = HALT_NAMES.iter().find(|&&(_, halt)| halt == *self).map_or("?", |&(name, _)| name);
+        f.write_str(name)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Expectations {
+    pub screen_hash: Option<String>,
+    pub halt: Option<HaltReason>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunError {
+    pub pc: u16,
+    pub opcode: u16,
+    pub message: String,
+    pub report: String,
+}
+
+impl RunError {
+    // from what a panic inside `chip8` left behind
+    pub fn from_panic(chip8: &Chip8, cause: Box<dyn Any + Send>) -> Self {
+        // the program counter moves past an instruction before running it
+        let pc = chip8.program_counter().wrapping_sub(2);
+        let mut error = RunError {
+            pc,
+            opcode: opcode_at(chip8, pc),
+            message: headless::panic_message(cause),
+            report: String::new(),
+        };
+        error.report = report_fault(chip8, &error);
+
+        error
+    }
+
+    // from an instruction `chip8` couldn't run, tick leaves the program counter on it
+    pub fn from_fault(chip8: &Chip8, fault: &Chip8Error) -> Self {
+        let pc = chip8.program_counter();
+        let mut error =
+            RunError { pc, opcode: opcode_at(chip8, pc), message: fault.to_string(), report: String::new() };
+        error.report = report_fault(chip8, &error);
+
+        error
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    pub frames: u64,
+    pub halt: HaltReason,
+    pub screen_hash: String,
+    pub seed: u64,
+    pub instructions: u64,
+    pub beeps: u64,
+    pub errors: Vec<RunError>,
+    pub failures: Vec<String>,
+}
+
+// like headless::run, but a crash still leaves the machine to report on
+pub fn run(rom: &[u8], options: &Options, expectations: &Expectations) -> (Chip8, Report) {
+    run_with(rom, options, expectations, |chip8, frame| {
+        Ok(headless::play_frame(chip8, frame, options.ticks_per_frame, &options.script))
+    })
+}
+
+// run, with `play` running each frame. an error from it stops the run and fails it
+pub(crate) fn run_with(
+    rom: &[u8],
+    options: &Options,
+    expectations: &Expectations,
+    mut play: impl FnMut(&mut Chip8, usize) -> Result<FrameResult, String>,
+) -> (Chip8, Report) {
+    let mut chip8 = Chip8::new();
+    chip8.set_seed(options.seed);
+    // for the crash report
+    chip8.set_trace_capacity(FAULT_TRACE_LENGTH);
+    let mut beeps = 0;
+    // a ROM that doesn't load fails the run without running a frame
+    let mut stopped = chip8.load(rom).err().map(|error| format!("unable to load the ROM: {}", error));
+    let mut fault = None;
+    let frames = if stopped.is_some() { 0 } else { options.frames };
+
+    let run = panic::catch_unwind(AssertUnwindSafe(|| {
+        for frame in 0..frames {
+            let result = match play(&mut chip8, frame) {
+                Ok(result) => result,
+                Err(message) => {
+                    stopped = Some(message);
+                    break;
+                },
+            };
+
+            if result.beep == Some(BeepEdge::Start) {
+                beeps += 1;
+            }
+
+            if let Some(error) = chip8.take_fault() {
+                fault = Some(error);
+                break;
+            }
+        }
+    }));
+
+    let errors = match (run, fault) {
+        (Ok(()), None) => Vec::new(),
+        (Ok(()), Some(fault)) => vec![RunError::from_fault(&chip8, &fault)],
+        (Err(cause), _) => vec![RunError::from_panic(&chip8, cause)],
+    };
+
+    let halt = if errors.is_empty() { HaltReason::of(&chip8) } else { HaltReason::Crash };
+    let mut report = Report {
+        frames: chip8.frame_count(),
+        halt,
+        screen_hash: screen_hash(chip8.get_display()),
+        seed: chip8.seed(),
+        instructions: chip8.instruction_count(),
+        beeps,
+        errors,
+        failures: Vec::new(),
+    };
+    report.check(expectations);
+    report.failures.extend(stopped);
+
+    (chip8, report)
+}
+
+impl Report {
+    // a crash fails the run unless it was what the caller expected
+    fn check(&mut self, expectations: &Expectations) {
+        match expectations.halt {
+            Some(halt) if halt != self.halt => {
+                self.failures.push(format!("expected the run to end with {}, it ended with {}", halt, self.halt));
+            },
+            None if self.halt == HaltReason::Crash => {
+                let failures = self.errors.iter().map(|error| {
+                    format!("the emulator crashed at {:03X} running {:04X}: {}", error.pc, error.opcode, error.message)
+                });
+                self.failures.extend(failures);
+            },
+            _ => (),
+        }
+
+        if let Some(hash) = &expectations.screen_hash {
+            if !hash.eq_ignore_ascii_case(&self.screen_hash) {
+                self.failures.push(format!("expected screen hash {}, got {}", hash, self.screen_hash));
+            }
+        }
+    }
+
+    pub fn is_pass(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        if self.is_pass() {
+            EXIT_PASS
+        } else {
+            EXIT_FAIL
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let errors: Vec<String> = self
+            .errors
+            .iter()
+            .map(|error| {
+                format!(
+                    "{{ \"pc\": {}, \"opcode\": \"{:04X}\", \"message\": {}, \"report\": {} }}",
+                    error.pc,
+                    error.opcode,
+                    json_string(&error.message),
+                    json_string(&error.report)
+                )
+            })
+            .collect();
+        let failures: Vec<String> = self.failures.iter().map(|failure| json_string(failure)).collect();
+
+        format!(
+            "{{\n  \"version\": {},\n  \"verdict\": \"{}\",\n  \"frames\": {},\n  \"halt\": \"{}\",\n  \"screen_hash\": \"{}\",\n  \"seed\": {},\n  \"stats\": {{ \"instructions\": {}, \"beeps\": {} }},\n  \"errors\": [{}],\n  \"failures\": [{}]\n}}\n",
+            REPORT_VERSION,
+            if self.is_pass() { "pass" } else { "fail" },
+            self.frames,
+            self.halt,
+            self.screen_hash,
+            self.seed,
+            self.instructions,
+            self.beeps,
+            errors.join(", "),
+            failures.join(", ")
+        )
+    }
+}
+
+pub(crate) fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::headless::{Script, DEFAULT_SEED};
+
+    fn options(frames: usize) -> Options {
+        Options { frames, ticks_per_frame: 10, seed: DEFAULT_SEED, script: Script::default() }
+    }
+
+    #[test]
+    fn tells_how_a_run_ended() {
+        // draw "0" then jump to the jump
+        let (_, report) = run(&[0xD0, 0x05, 0x12, 0x02], &options(5), &Expectations::default());
+        assert_eq!(report.halt, HaltReason::SelfJump);
+
+        // wait for a key
+        assert_eq!(run(&[0xF0, 0x0A], &options(5), &Expectations::default()).1.halt, HaltReason::KeyWait);
+
+        // count up in V0 forever
+        assert_eq!(run(&[0x70, 0x01, 0x12, 0x00], &options(5), &Expectations::default()).1.halt, HaltReason::Frames);
+    }
+
+    #[test]
+    fn keeps_the_crashing_instruction() {
+        // count to 20, then return with an empty stack. that's the 60th instruction, in the 6th frame
+        let rom = [0x70, 0x01, 0x30, 0x14, 0x12, 0x00, 0x00, 0xEE];
+        let (chip8, report) = run(&rom, &options(10), &Expectations::default());
+
+        assert_eq!(report.halt, HaltReason::Crash);
+        assert_eq!(report.frames, 5);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!((report.errors[0].pc, report.errors[0].opcode), (0x206, 0x00EE));
+        assert_eq!(report.failures.len(), 1);
+        assert!(report.failures[0].starts_with("the emulator crashed at 206 running 00EE"), "{}", report.failures[0]);
+        assert_eq!(report.exit_code(), EXIT_FAIL);
+        assert_eq!(chip8.registers()[0], 20);
+    }
+
+    #[test]
+    fn fails_a_rom_that_doesnt_load() {
+        let (_, report) = run(&[], &options(10), &Expectations::default());
+
+        assert_eq!((report.frames, report.instructions), (0, 0));
+        assert_eq!(report.failures, ["unable to load the ROM: the ROM is empty"]);
+        assert_eq!(report.exit_code(), EXIT_FAIL);
+    }
+
+    #[test]
+    fn checks_expectations() {
+        let rom = [0xD0, 0x05, 0x12, 0x02];
+        let (_, report) = run(&rom, &options(5), &Expectations::default());
+        assert!(report.is_pass());
+
+        let expected =
+            Expectations { screen_hash: Some(report.screen_hash.to_uppercase()), halt: Some(HaltReason::SelfJump) };
+        assert_eq!(run(&rom, &options(5), &expected).1.exit_code(), EXIT_PASS);
+
+        let wrong = Expectations { screen_hash: Some("0000".to_string()), halt: Some(HaltReason::KeyWait) };
+        let (_, report) = run(&rom, &options(5), &wrong);
+        assert_eq!(report.failures.len(), 2);
+        assert_eq!(report.exit_code(), EXIT_FAIL);
+
+        // an expected crash passes
+        let crash = Expectations { halt: Some(HaltReason::Crash), ..Expectations::default() };
+        assert!(run(&[0x00, 0xEE], &options(1), &crash).1.is_pass());
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for name in HaltReason::names() {
+            assert_eq!(HaltReason::from_name(name).unwrap().to_string(), name);
+        }
+
+        assert_eq!(HaltReason::from_name("done"), None);
+    }
+
+    #[test]
+    fn escapes_json_strings() {
+        assert_eq!(json_string("a \"b\"\\\n\u{1}"), "\"a \\\"b\\\"\\\\\\n\\u0001\"");
+    }
+}
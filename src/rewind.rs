@@ -0,0 +1,176 @@
+use crate::state::Snapshot;
+use crate::Chip8;
+
+use std::collections::VecDeque;
+
+// the state at the start of each of the last `capacity` frames, oldest first
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RewindBuffer {
+    snapshots: VecDeque<Snapshot>,
+    capacity: usize,
+}
+
+impl RewindBuffer {
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    pub(crate) fn push(&mut self, snapshot: Snapshot) {
+        while self.snapshots.len() >= self.capacity {
+            self.snapshots.pop_front();
+        }
+
+        self.snapshots.push_back(snapshot);
+    }
+
+    // popping is what truncates the timeline, whatever runs next records over the frames taken back
+    pub(crate) fn pop(&mut self) -> Option<Snapshot> {
+        self.snapshots.pop_back()
+    }
+
+    // drops the frames that start after `frame`, for when the machine steps back into an earlier one
+    pub(crate) fn forget_after(&mut self, frame: u64) {
+        while self.snapshots.back().is_some_and(|snapshot| snapshot.frame_count() > frame) {
+            self.snapshots.pop_back();
+        }
+    }
+
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+
+        while self.snapshots.len() > capacity {
+            self.snapshots.pop_front();
+        }
+    }
+}
+
+impl Chip8 {
+    // how many frames rewind_frame can go back, 0 (the default) turns recording off
+    pub fn set_rewind_capacity(&mut self, frames: usize) {
+        self.rewind.set_capacity(frames);
+    }
+
+    // forget the history, e.g. when another game is loaded
+    pub fn clear_rewind(&mut self) {
+        self.rewind.snapshots.clear();
+    }
+
+    pub fn rewind_len(&self) -> usize {
+        self.rewind.snapshots.len()
+    }
+
+    pub fn rewind_capacity(&self) -> usize {
+        self.rewind.capacity
+    }
+
+    // the screen as each recorded frame started, oldest first
+    pub(crate) fn rewind_screens(&self) -> impl Iterator<Item = &[bool]> {
+        self.rewind.snapshots.iter().map(|snapshot| snapshot.screen())
+    }
+
+    // undo the last frame, false once the start of the buffer is reached
+    pub fn rewind_frame(&mut self) -> bool {
+        match self.rewind.pop() {
+            Some(snapshot) => {
+                self.restore(&snapshot);
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // V0 = 0x2A, then V0 += 1 forever
+    const ROM: [u8; 6] = [0x60, 0x2A, 0x70, 0x01, 0x12, 0x02];
+
+    fn rewindable(capacity: usize) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ROM).unwrap();
+        chip8.set_rewind_capacity(capacity);
+
+        chip8
+    }
+
+    #[test]
+    fn rewinds_one_frame_at_a_time() {
+        let mut chip8 = rewindable(10);
+        let start = chip8.snapshot();
+
+        chip8.run_frame(2);
+        let after_one = chip8.snapshot();
+        chip8.run_frame(2);
+
+        assert!(chip8.rewind_frame());
+        assert_eq!(chip8.snapshot(), after_one);
+        assert!(chip8.rewind_frame());
+        assert_eq!(chip8.snapshot(), start);
+    }
+
+    #[test]
+    fn stops_at_the_start_of_the_buffer() {
+        let mut chip8 = rewindable(3);
+
+        for _ in 0..5 {
+            chip8.run_frame(2);
+        }
+
+        assert_eq!(chip8.rewind_len(), 3);
+
+        for _ in 0..3 {
+            assert!(chip8.rewind_frame());
+        }
+
+        let oldest = chip8.snapshot();
+        assert!(!chip8.rewind_frame());
+        assert_eq!(chip8.snapshot(), oldest);
+        assert_eq!(chip8.frame_count(), 2);
+    }
+
+    #[test]
+    fn running_after_a_rewind_starts_a_new_timeline() {
+        let mut chip8 = rewindable(10);
+
+        for _ in 0..4 {
+            chip8.run_frame(2);
+        }
+
+        chip8.rewind_frame();
+        chip8.rewind_frame();
+        assert_eq!(chip8.rewind_len(), 2);
+
+        // a different input from here on, the two frames taken back are gone for good
+        chip8.keypress(5, true);
+        chip8.run_frame(2);
+        assert_eq!(chip8.rewind_len(), 3);
+
+        chip8.rewind_frame();
+        assert_eq!(chip8.frame_count(), 2);
+        chip8.rewind_frame();
+        assert_eq!(chip8.frame_count(), 1);
+    }
+
+    #[test]
+    fn clearing_keeps_the_capacity() {
+        let mut chip8 = rewindable(10);
+        chip8.run_frame(2);
+        chip8.clear_rewind();
+
+        assert!(!chip8.rewind_frame());
+        chip8.run_frame(2);
+        assert_eq!(chip8.rewind_len(), 1);
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ROM).unwrap();
+        chip8.run_frame(2);
+
+        assert_eq!(chip8.rewind_len(), 0);
+        assert!(!chip8.rewind_frame());
+    }
+}
@@ -0,0 +1,27 @@
+use sha2::{Digest, Sha256};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoadedRom {
+    pub bytes: Vec<u8>,
+    pub size: usize,
+    pub sha256: [u8; 32],
+    pub source_name: Option<String>,
+    pub loaded_at_address: u16,
+}
+
+impl LoadedRom {
+    pub fn new(bytes: &[u8], source_name: Option<&str>, loaded_at_address: u16) -> Self {
+        Self {
+            bytes: bytes.to_vec(),
+            size: bytes.len(),
+            sha256: Sha256::digest(bytes).into(),
+            source_name: source_name.map(String::from),
+            loaded_at_address,
+        }
+    }
+
+    // lowercase hex, the form used to key rom databases and save slots
+    pub fn sha256_hex(&self) -> String {
+        self.sha256.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
@@ -0,0 +1,404 @@
+// rhai scripts that run alongside a ROM, for ROM-specific checks, playing through an intro, or
+// trying things without a rebuild. a script defines whichever callbacks it wants:
+//
+//   fn on_frame(chip) { if chip.frame == 30 { chip.press(5) } }   before every frame
+//   fn on_draw(chip) { ... }                                       after a frame that ran DXYN
+//   fn on_halt(chip, reason) { ... }                               after the frame that stopped on
+//                                                                  a "self_jump" or "key_wait"
+//
+// `chip` reads the machine as it was when the callback started:
+//
+//   chip.v(x)  chip.i  chip.pc  chip.delay_timer  chip.sound_timer
+//   chip.frame  chip.instructions  chip.draws  chip.collisions
+//   chip.peek(address)  chip.pixel(x, y)  chip.is_key_down(key)
+//
+// and chip.press(key) and chip.release(key) take effect when it returns. print() goes to
+// take_output, `throw` fails the run, and `this` is a map that lasts from one callback to the next.
+// each callback gets time_limit to finish before it's stopped, so a runaway loop fails the run
+// instead of hanging it
+
+use crate::headless::{self, Options};
+use crate::report::{self, Expectations, HaltReason, Report};
+use crate::{Chip8, FrameResult, NUM_KEYS, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+
+use std::cell::{Cell, RefCell};
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_TIME_LIMIT: Duration = Duration::from_millis(20);
+
+// the callbacks and how many arguments each takes
+const CALLBACKS: &[(&str, usize)] = &[("on_frame", 1), ("on_draw", 1), ("on_halt", 2)];
+// looking at the clock every operation would slow scripts down for nothing
+const OPERATIONS_PER_CLOCK_CHECK: u64 = 256;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScriptError {
+    Parse(String),
+    // `callback` is "load" for the script's top level
+    Runtime { callback: &'static str, message: String },
+    TimedOut { callback: &'static str, limit: Duration },
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScriptError::Parse(message) => write!(f, "script doesn't parse: {}", message),
+            ScriptError::Runtime { callback, message } => write!(f, "script failed in {}: {}", callback, message),
+            ScriptError::TimedOut { callback, limit } => {
+                write!(f, "script's {} ran longer than {} ms", callback, limit.as_millis())
+            },
+        }
+    }
+}
+
+impl Error for ScriptError {}
+
+// what a callback sees, copied out of the machine before the call
+#[derive(Clone)]
+struct ScriptChip {
+    registers: [u8; 16],
+    register_i: u16,
+    program_counter: u16,
+    delay_timer: u8,
+    sound_timer: u8,
+    frame: u64,
+    instructions: u64,
+    draws: u64,
+    collisions: u64,
+    keys: [bool; NUM_KEYS],
+    memory: Rc<[u8]>,
+    screen: Rc<[bool]>,
+    // presses and releases to apply afterwards, shared by every copy rhai makes
+    presses: Rc<RefCell<Vec<(usize, bool)>>>,
+}
+
+impl ScriptChip {
+    fn of(chip8: &Chip8) -> Self {
+        let mut registers = [0; 16];
+        registers.copy_from_slice(chip8.registers());
+        let mut keys = [false; NUM_KEYS];
+        keys.copy_from_slice(chip8.keys());
+
+        Self {
+            registers,
+            register_i: chip8.register_i(),
+            program_counter: chip8.program_counter(),
+            delay_timer: chip8.delay_timer(),
+            sound_timer: chip8.sound_timer(),
+            frame: chip8.frame_count(),
+            instructions: chip8.instruction_count(),
+            draws: chip8.draw_count(),
+            collisions: chip8.collision_count(),
+            keys,
+            memory: chip8.memory().into(),
+            screen: chip8.get_display().into(),
+            presses: Rc::default(),
+        }
+    }
+
+    fn set_key(&mut self, key: i64, is_pressed: bool) -> Result<(), Box<EvalAltResult>> {
+        let index = key_index(key)?;
+        self.keys[index] = is_pressed;
+        self.presses.borrow_mut().push((index, is_pressed));
+
+        Ok(())
+    }
+}
+
+fn key_index(key: i64) -> Result<usize, Box<EvalAltResult>> {
+    usize::try_from(key).ok().filter(|&key| key < NUM_KEYS).ok_or_else(|| format!("no key {}", key).into())
+}
+
+fn register_api(engine: &mut Engine) {
+    engine
+        .register_type_with_name::<ScriptChip>("Chip")
+        .register_get("i", |chip: &mut ScriptChip| chip.register_i as i64)
+        .register_get("pc", |chip: &mut ScriptChip| chip.program_counter as i64)
+        .register_get("delay_timer", |chip: &mut ScriptChip| chip.delay_timer as i64)
+        .register_get("sound_timer", |chip: &mut ScriptChip| chip.sound_timer as i64)
+        .register_get("frame", |chip: &mut ScriptChip| chip.frame as i64)
+        .register_get("instructions", |chip: &mut ScriptChip| chip.instructions as i64)
+        .register_get("draws", |chip: &mut ScriptChip| chip.draws as i64)
+        .register_get("collisions", |chip: &mut ScriptChip| chip.collisions as i64)
+        .register_fn("v", |chip: &mut ScriptChip, x: i64| -> Result<i64, Box<EvalAltResult>> {
+            let register = usize::try_from(x).ok().and_then(|x| chip.registers.get(x));
+            register.map(|&value| value as i64).ok_or_else(|| format!("no register V{}", x).into())
+        })
+        .register_fn("peek", |chip: &mut ScriptChip, address: i64| -> Result<i64, Box<EvalAltResult>> {
+            let byte = usize::try_from(address).ok().and_then(|address| chip.memory.get(address));
+            byte.map(|&byte| byte as i64).ok_or_else(|| format!("address {:#X} is outside RAM", address).into())
+        })
+        .register_fn("pixel", |chip: &mut ScriptChip, x: i64, y: i64| -> Result<bool, Box<EvalAltResult>> {
+            match (usize::try_from(x), usize::try_from(y)) {
+                (Ok(x), Ok(y)) if x < SCREEN_WIDTH && y < SCREEN_HEIGHT => Ok(chip.screen[y * SCREEN_WIDTH + x]),
+                _ => Err(format!("({}, {}) is off the screen", x, y).into()),
+            }
+        })
+        .register_fn("is_key_down", |chip: &mut ScriptChip, key: i64| -> Result<bool, Box<EvalAltResult>> {
+            Ok(chip.keys[key_index(key)?])
+        })
+        .register_fn("press", |chip: &mut ScriptChip, key: i64| chip.set_key(key, true))
+        .register_fn("release", |chip: &mut ScriptChip, key: i64| chip.set_key(key, false));
+}
+
+pub struct ScriptHost {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    // `this` in every callback
+    state: Dynamic,
+    callbacks: Vec<&'static str>,
+    time_limit: Duration,
+    deadline: Rc<Cell<Option<Instant>>>,
+    output: Rc<RefCell<Vec<String>>>,
+    // where the last frame stopped, so on_halt runs once per stop
+    halt: Option<HaltReason>,
+}
+
+impl ScriptHost {
+    // parses `source` and runs its top level
+    pub fn new(source: &str) -> Result<Self, ScriptError> {
+        let mut engine = Engine::new();
+        let deadline: Rc<Cell<Option<Instant>>> = Rc::default();
+        let output: Rc<RefCell<Vec<String>>> = Rc::default();
+
+        register_api(&mut engine);
+
+        let progress_deadline = deadline.clone();
+        engine.on_progress(move |operations| {
+            let is_late = operations % OPERATIONS_PER_CLOCK_CHECK == 0
+                && progress_deadline.get().is_some_and(|deadline| Instant::now() >= deadline);
+            is_late.then_some(Dynamic::UNIT)
+        });
+
+        let print_output = output.clone();
+        engine.on_print(move |text| print_output.borrow_mut().push(text.to_string()));
+        let debug_output = output.clone();
+        engine.on_debug(move |text, _, _| debug_output.borrow_mut().push(text.to_string()));
+
+        let ast = engine.compile(source).map_err(|e| ScriptError::Parse(e.to_string()))?;
+        let callbacks = CALLBACKS
+            .iter()
+            .filter(|&&(name, params)| ast.iter_functions().any(|f| f.name == name && f.params.len() == params))
+            .map(|&(name, _)| name)
+            .collect();
+
+        let mut host = Self {
+            engine,
+            ast,
+            scope: Scope::new(),
+            state: Map::new().into(),
+            callbacks,
+            time_limit: DEFAULT_TIME_LIMIT,
+            deadline,
+            output,
+            halt: None,
+        };
+
+        host.deadline.set(Some(Instant::now() + host.time_limit));
+        let run = host.engine.run_ast_with_scope(&mut host.scope, &host.ast);
+        host.deadline.set(None);
+        run.map_err(|e| host.error("load", *e))?;
+
+        Ok(host)
+    }
+
+    pub fn set_time_limit(&mut self, limit: Duration) {
+        self.time_limit = limit;
+    }
+
+    pub fn has_callback(&self, name: &str) -> bool {
+        self.callbacks.contains(&name)
+    }
+
+    // what the script printed since the last call
+    pub fn take_output(&mut self) -> Vec<String> {
+        self.output.take()
+    }
+
+    // a frame with the callbacks around it: `run` runs the frame itself
+    pub fn frame(
+        &mut self,
+        chip8: &mut Chip8,
+        run: impl FnOnce(&mut Chip8) -> FrameResult,
+    ) -> Result<FrameResult, ScriptError> {
+        self.call("on_frame", chip8, None)?;
+
+        let draws = chip8.draw_count();
+        let result = run(chip8);
+
+        if chip8.draw_count() != draws {
+            self.call("on_draw", chip8, None)?;
+        }
+
+        let halt = Some(HaltReason::of(chip8)).filter(|&halt| halt != HaltReason::Frames);
+        if halt.is_some() && halt != self.halt {
+            self.call("on_halt", chip8, halt)?;
+        }
+        self.halt = halt;
+
+        Ok(result)
+    }
+
+    fn call(&mut self, callback: &'static str, chip8: &mut Chip8, halt: Option<HaltReason>) -> Result<(), ScriptError> {
+        if !self.has_callback(callback) {
+            return Ok(());
+        }
+
+        let chip = ScriptChip::of(chip8);
+        let presses = chip.presses.clone();
+        let mut args = vec![Dynamic::from(chip)];
+        args.extend(halt.map(|halt| Dynamic::from(halt.to_string())));
+
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut self.state);
+        self.deadline.set(Some(Instant::now() + self.time_limit));
+        let call = self.engine.call_fn_with_options::<Dynamic>(options, &mut self.scope, &self.ast, callback, args);
+        self.deadline.set(None);
+
+        // the presses up to an error still happen
+        for (key, is_pressed) in presses.take() {
+            chip8.keypress(key, is_pressed);
+        }
+
+        call.map(|_| ()).map_err(|e| self.error(callback, *e))
+    }
+
+    fn error(&self, callback: &'static str, error: EvalAltResult) -> ScriptError {
+        match error {
+            EvalAltResult::ErrorTerminated(..) => ScriptError::TimedOut { callback, limit: self.time_limit },
+            // a `throw` reads better without rhai's position
+            EvalAltResult::ErrorRuntime(value, position) => {
+                ScriptError::Runtime { callback, message: format!("{} ({})", value, position) }
+            },
+            error => ScriptError::Runtime { callback, message: error.to_string() },
+        }
+    }
+}
+
+// report::run with the script's callbacks around every frame. a script error fails the report
+pub fn run(rom: &[u8], options: &Options, expectations: &Expectations, host: &mut ScriptHost) -> (Chip8, Report) {
+    report::run_with(rom, options, expectations, |chip8, frame| {
+        host.frame(chip8, |chip8| headless::play_frame(chip8, frame, options.ticks_per_frame, &options.script))
+            .map_err(|e| e.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::headless::Script;
+
+    // V0 = the key pressed, drawn as a digit, then a self-jump
+    const KEY_ROM: &[u8] = &[0xF0, 0x0A, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x06];
+
+    fn options(frames: usize) -> Options {
+        Options { frames, ticks_per_frame: 10, seed: headless::DEFAULT_SEED, script: Script::default() }
+    }
+
+    fn run_script(source: &str, frames: usize) -> (Chip8, Report, ScriptHost) {
+        let mut host = ScriptHost::new(source).unwrap();
+        let (chip8, report) = run(KEY_ROM, &options(frames), &Expectations::default(), &mut host);
+
+        (chip8, report, host)
+    }
+
+    #[test]
+    fn presses_a_key_at_a_frame_and_checks_a_register() {
+        let source = r#"
+            fn on_frame(chip) {
+                if chip.frame == 10 { chip.press(7) }
+                if chip.frame == 11 { chip.release(7) }
+            }
+
+            fn on_halt(chip, reason) {
+                if reason == "self_jump" && chip.v(0) != 7 { throw `V0 is ${chip.v(0)}` }
+                print(`${reason} at frame ${chip.frame}, V0 = ${chip.v(0)}`);
+            }
+        "#;
+        let (chip8, report, mut host) = run_script(source, 20);
+
+        assert!(report.is_pass(), "{:?}", report.failures);
+        assert_eq!(chip8.registers()[0], 7);
+        assert_eq!(host.take_output(), ["key_wait at frame 1, V0 = 0", "self_jump at frame 12, V0 = 7"]);
+        assert!(host.take_output().is_empty());
+    }
+
+    #[test]
+    fn a_throw_fails_the_run_where_it_happened() {
+        let source = r#"
+            fn on_draw(chip) {
+                if !chip.pixel(5, 0) { throw "no digit" }
+            }
+            fn on_frame(chip) {
+                if chip.frame == 1 { chip.press(0) } else { chip.release(0) }
+            }
+        "#;
+        let (chip8, report, _) = run_script(source, 20);
+
+        assert!(!report.is_pass());
+        assert!(report.failures[0].starts_with("script failed in on_draw: no digit"), "{:?}", report.failures);
+        // it stopped on the frame that drew
+        assert_eq!(chip8.frame_count(), 3);
+    }
+
+    #[test]
+    fn keeps_state_in_this() {
+        let source = r#"
+            fn on_frame(chip) {
+                if this.frames == () { this.frames = 0 }
+                this.frames += 1;
+                if this.frames == 5 { print(this.frames) }
+            }
+        "#;
+        let (_, report, mut host) = run_script(source, 5);
+
+        assert!(report.is_pass());
+        assert_eq!(host.take_output(), ["5"]);
+    }
+
+    #[test]
+    fn stops_a_callback_that_runs_too_long() {
+        let source = "fn on_frame(chip) { if chip.frame == 3 { loop {} } }";
+        let mut host = ScriptHost::new(source).unwrap();
+        host.set_time_limit(Duration::from_millis(5));
+
+        let started = Instant::now();
+        let (chip8, report) = run(KEY_ROM, &options(10), &Expectations::default(), &mut host);
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(chip8.frame_count(), 3);
+        assert_eq!(report.failures, ["script's on_frame ran longer than 5 ms"]);
+
+        // the top level too
+        assert_eq!(
+            ScriptHost::new("loop {}").err(),
+            Some(ScriptError::TimedOut { callback: "load", limit: DEFAULT_TIME_LIMIT })
+        );
+    }
+
+    #[test]
+    fn refuses_what_it_cant_run() {
+        assert!(matches!(ScriptHost::new("fn on_frame(chip) {"), Err(ScriptError::Parse(_))));
+
+        let host = ScriptHost::new("fn on_frame(chip) {} fn on_halt(chip) {}").unwrap();
+        assert!(host.has_callback("on_frame"));
+        // the wrong number of arguments
+        assert!(!host.has_callback("on_halt"));
+
+        for (source, message) in [
+            ("fn on_frame(chip) { chip.press(16) }", "no key 16"),
+            ("fn on_frame(chip) { chip.v(-1) }", "no register V-1"),
+            ("fn on_frame(chip) { chip.peek(4096) }", "address 0x1000 is outside RAM"),
+            ("fn on_frame(chip) { chip.pixel(64, 0) }", "(64, 0) is off the screen"),
+        ] {
+            let (_, report, _) = run_script(source, 1);
+            assert!(report.failures[0].contains(message), "{:?}", report.failures);
+        }
+    }
+}
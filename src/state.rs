@@ -0,0 +1,544 @@
+use crate::audio::AUDIO_PATTERN_SIZE;
+use crate::key_wait::KeyWait;
+use crate::{Chip8, DirtyRegion, NUM_REGISTER_V, RAM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH, STACK_SIZE};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+use std::error::Error;
+use std::fmt;
+
+const MAGIC: &[u8; 4] = b"C8ST";
+// 2 added the seed and how far RND had got, 3 how long FX0A has been waiting, 4 whether a key went
+// down during the wait and whether the frame has drawn yet
+const VERSION: u8 = 4;
+
+// what encode writes with a ROM and an audio pattern loaded, nothing bigger. decode stops at the
+// end of the state, so hosts that want one fixed size can pad to this
+pub const MAX_STATE_SIZE: usize = MAGIC.len()
+    + 1
+    + 1
+    + 32
+    + SCREEN_WIDTH * SCREEN_HEIGHT
+    + RAM_SIZE
+    + 2
+    + NUM_REGISTER_V
+    + 2
+    + 1
+    + 1
+    + 2
+    + STACK_SIZE * 2
+    + 1
+    + AUDIO_PATTERN_SIZE
+    + 1
+    + 8
+    + 8
+    + 16
+    + 4
+    + 1
+    + 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateError {
+    NotAState,
+    UnsupportedVersion(u8),
+    Truncated,
+    RomMismatch,
+    // more return addresses than the stack holds, or than the machine loading it allows
+    StackPointer(u16),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::NotAState => write!(f, "not a chip8-emu save state"),
+            StateError::UnsupportedVersion(version) => write!(f, "unsupported save state version {}", version),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::RomMismatch => write!(f, "save state belongs to a different ROM"),
+            StateError::StackPointer(stack_pointer) => {
+                write!(f, "save state has {} return addresses on the stack, more than fit", stack_pointer)
+            },
+        }
+    }
+}
+
+impl Error for StateError {}
+
+// everything the program can observe, the host side (audio, hooks, pause) stays put
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Snapshot {
+    rom_sha256: Option<[u8; 32]>,
+    screen: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+    ram: [u8; RAM_SIZE],
+    program_counter: u16,
+    register_v: [u8; NUM_REGISTER_V],
+    register_i: u16,
+    delay_timer: u8,
+    sound_timer: u8,
+    stack_pointer: u16,
+    stack: [u16; STACK_SIZE],
+    audio_pattern: Option<[u8; AUDIO_PATTERN_SIZE]>,
+    audio_pitch: u8,
+    frame_count: u64,
+    // the seed and the generator's word position, None from a version 1 state
+    rng: Option<(u64, u128)>,
+    key_wait_frames: u32,
+    key_wait: KeyWait,
+    has_drawn_this_frame: bool,
+}
+
+impl Snapshot {
+    pub(crate) fn screen(&self) -> &[bool] {
+        &self.screen
+    }
+
+    pub(crate) fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(RAM_SIZE + SCREEN_WIDTH * SCREEN_HEIGHT + 128);
+
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+
+        match &self.rom_sha256 {
+            Some(hash) => {
+                out.push(1);
+                out.extend_from_slice(hash);
+            },
+            None => out.push(0),
+        }
+
+        out.extend(self.screen.iter().map(|&pixel| pixel as u8));
+        out.extend_from_slice(&self.ram);
+        out.extend_from_slice(&self.program_counter.to_le_bytes());
+        out.extend_from_slice(&self.register_v);
+        out.extend_from_slice(&self.register_i.to_le_bytes());
+        out.push(self.delay_timer);
+        out.push(self.sound_timer);
+        out.extend_from_slice(&self.stack_pointer.to_le_bytes());
+
+        for address in &self.stack {
+            out.extend_from_slice(&address.to_le_bytes());
+        }
+
+        match &self.audio_pattern {
+            Some(pattern) => {
+                out.push(1);
+                out.extend_from_slice(pattern);
+            },
+            None => out.push(0),
+        }
+
+        out.push(self.audio_pitch);
+        out.extend_from_slice(&self.frame_count.to_le_bytes());
+
+        // always there in what we write, only old states leave it out
+        let (seed, position) = self.rng.unwrap_or_default();
+        out.extend_from_slice(&seed.to_le_bytes());
+        out.extend_from_slice(&position.to_le_bytes());
+        out.extend_from_slice(&self.key_wait_frames.to_le_bytes());
+        out.push(self.key_wait.encode());
+        out.push(self.has_drawn_this_frame as u8);
+
+        out
+    }
+
+    fn decode(data: &[u8]) -> Result<Self, StateError> {
+        if data.len() < MAGIC.len() || &data[..MAGIC.len()] != MAGIC {
+            return Err(StateError::NotAState);
+        }
+
+        let mut reader = Reader { data, position: MAGIC.len() };
+
+        let version = reader.byte()?;
+        if !(1..=VERSION).contains(&version) {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+
+        let rom_sha256 = match reader.byte()? {
+            0 => None,
+            _ => Some(reader.array()?),
+        };
+
+        let mut screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        for (pixel, &byte) in screen.iter_mut().zip(reader.bytes(SCREEN_WIDTH * SCREEN_HEIGHT)?) {
+            *pixel = byte != 0;
+        }
+
+        let ram = reader.array()?;
+        let program_counter = reader.word()?;
+        let register_v = reader.array()?;
+        let register_i = reader.word()?;
+        let delay_timer = reader.byte()?;
+        let sound_timer = reader.byte()?;
+        let stack_pointer = reader.word()?;
+        if stack_pointer as usize > STACK_SIZE {
+            return Err(StateError::StackPointer(stack_pointer));
+        }
+
+        let mut stack = [0; STACK_SIZE];
+        for address in stack.iter_mut() {
+            *address = reader.word()?;
+        }
+
+        let audio_pattern = match reader.byte()? {
+            0 => None,
+            _ => Some(reader.array()?),
+        };
+
+        let audio_pitch = reader.byte()?;
+        let frame_count = u64::from_le_bytes(reader.array()?);
+        let rng = match version {
+            1 => None,
+            _ => Some((u64::from_le_bytes(reader.array()?), u128::from_le_bytes(reader.array()?))),
+        };
+        let key_wait_frames = match version {
+            1 | 2 => 0,
+            _ => u32::from_le_bytes(reader.array()?),
+        };
+        // an older state caught mid-wait waits again from the start, in a frame that hasn't drawn
+        let (key_wait, has_drawn_this_frame) = match version {
+            1..=3 => (KeyWait::Idle, false),
+            _ => (KeyWait::decode(reader.byte()?), reader.byte()? != 0),
+        };
+
+        Ok(Self {
+            rom_sha256,
+            screen,
+            ram,
+            program_counter,
+            register_v,
+            register_i,
+            delay_timer,
+            sound_timer,
+            stack_pointer,
+            stack,
+            audio_pattern,
+            audio_pitch,
+            frame_count,
+            rng,
+            key_wait_frames,
+            key_wait,
+            has_drawn_this_frame,
+        })
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], StateError> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + count)
+            .ok_or(StateError::Truncated)?;
+        self.position += count;
+
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], StateError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+
+        Ok(array)
+    }
+
+    fn byte(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn word(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+}
+
+impl Chip8 {
+    pub(crate) fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            rom_sha256: self.loaded_rom.as_ref().map(|rom| rom.sha256),
+            screen: self.screen,
+            ram: self.padded_ram(),
+            program_counter: self.program_counter,
+            register_v: self.register_v,
+            register_i: self.register_i,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            stack_pointer: self.stack_pointer,
+            stack: self.stack,
+            audio_pattern: self.audio_pattern,
+            audio_pitch: self.audio_pitch,
+            frame_count: self.frame_count,
+            rng: Some((self.seed, self.rng.get_word_pos())),
+            key_wait_frames: self.key_wait_frames,
+            key_wait: self.key_wait,
+            has_drawn_this_frame: self.has_drawn_this_frame,
+        }
+    }
+
+    // states are always RAM_SIZE, a smaller machine from the builder reads as zeros past its end
+    fn padded_ram(&self) -> [u8; RAM_SIZE] {
+        let mut ram = [0; RAM_SIZE];
+        ram[..self.ram.len()].copy_from_slice(&self.ram);
+
+        ram
+    }
+
+    pub(crate) fn restore(&mut self, snapshot: &Snapshot) {
+        self.step_history.clear();
+        self.screen = snapshot.screen;
+        self.mark_dirty(DirtyRegion::FULL);
+        let size = self.ram.len();
+        self.ram.copy_from_slice(&snapshot.ram[..size]);
+        self.mark_all_memory_changed();
+        self.program_counter = snapshot.program_counter;
+        self.register_v = snapshot.register_v;
+        self.register_i = snapshot.register_i;
+        self.delay_timer = snapshot.delay_timer;
+        self.sound_timer = snapshot.sound_timer;
+        self.stack_pointer = snapshot.stack_pointer;
+        self.stack = snapshot.stack;
+        self.audio_pattern = snapshot.audio_pattern;
+        self.audio_pitch = snapshot.audio_pitch;
+        self.frame_count = snapshot.frame_count;
+        self.key_wait_frames = snapshot.key_wait_frames;
+        self.key_wait = snapshot.key_wait;
+        self.has_drawn_this_frame = snapshot.has_drawn_this_frame;
+        self.is_waiting_for_key = false;
+
+        // seeking is cheap, so rewinding brings RND back too
+        if let Some((seed, position)) = snapshot.rng {
+            self.rng = ChaCha12Rng::seed_from_u64(seed);
+            self.rng.set_word_pos(position);
+            self.seed = seed;
+        }
+    }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        self.snapshot().encode()
+    }
+
+    // the state has to come from the same ROM that's loaded now
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let snapshot = Snapshot::decode(data)?;
+
+        if snapshot.rom_sha256 != self.loaded_rom.as_ref().map(|rom| rom.sha256) {
+            return Err(StateError::RomMismatch);
+        }
+
+        if snapshot.stack_pointer as usize > self.stack_limit {
+            return Err(StateError::StackPointer(snapshot.stack_pointer));
+        }
+
+        self.restore(&snapshot);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Quirks;
+
+    // V0 = 0x2A, then V0 += 1 forever
+    const ROM: [u8; 6] = [0x60, 0x2A, 0x70, 0x01, 0x12, 0x02];
+
+    #[test]
+    fn round_trips_machine_state() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ROM).unwrap();
+        chip8.run_frame(5);
+
+        let state = chip8.save_state();
+        let saved = chip8.snapshot();
+        chip8.run_frame(5);
+        assert_ne!(chip8.snapshot(), saved);
+
+        chip8.load_state(&state).unwrap();
+        assert_eq!(chip8.snapshot(), saved);
+        assert_eq!(chip8.frame_count(), 1);
+    }
+
+    #[test]
+    fn restores_the_random_numbers() {
+        // V0 = random, forever
+        let rom = [0xC0, 0xFF, 0x12, 0x00];
+        let mut chip8 = Chip8::new();
+        chip8.load(&rom).unwrap();
+        chip8.set_seed(7);
+        chip8.run_frame(10);
+
+        let state = chip8.save_state();
+        chip8.run_frame(10);
+        let after = chip8.registers()[0];
+
+        let mut other = Chip8::new();
+        other.load(&rom).unwrap();
+        other.load_state(&state).unwrap();
+        assert_eq!(other.seed(), 7);
+        other.run_frame(10);
+        assert_eq!(other.registers()[0], after);
+    }
+
+    #[test]
+    fn loads_version_1_states() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ROM).unwrap();
+        chip8.set_seed(7);
+        chip8.run_frame(5);
+
+        // version 1 ended at the frame count
+        let mut state = chip8.save_state();
+        state.truncate(state.len() - 30);
+        state[MAGIC.len()] = 1;
+
+        let mut other = Chip8::new();
+        other.load(&ROM).unwrap();
+        other.set_seed(9);
+        other.load_state(&state).unwrap();
+        assert_eq!(other.registers(), chip8.registers());
+        assert_eq!(other.seed(), 9);
+    }
+
+    #[test]
+    fn loads_version_2_states() {
+        // waits for a key forever
+        let rom = [0xF0, 0x0A];
+        let mut chip8 = Chip8::new();
+        chip8.load(&rom).unwrap();
+        chip8.run_frame(5);
+        chip8.run_frame(5);
+
+        // version 2 ended at the generator's position
+        let mut state = chip8.save_state();
+        state.truncate(state.len() - 6);
+        state[MAGIC.len()] = 2;
+
+        let mut other = Chip8::new();
+        other.load(&rom).unwrap();
+        other.load_state(&state).unwrap();
+        assert_eq!(other.frame_count(), 2);
+        assert_eq!(other.key_wait_frames(), 0);
+    }
+
+    #[test]
+    fn loads_version_3_states() {
+        let rom = [0xF0, 0x0A, 0x12, 0x02];
+        let mut chip8 = Chip8::new();
+        chip8.load(&rom).unwrap();
+        chip8.run_frame(5);
+        chip8.keypress(4, true);
+
+        // version 3 ended at how long FX0A had been waiting
+        let mut state = chip8.save_state();
+        state.truncate(state.len() - 2);
+        state[MAGIC.len()] = 3;
+
+        let mut other = Chip8::new();
+        other.load(&rom).unwrap();
+        other.load_state(&state).unwrap();
+        assert_eq!(other.key_wait_key(), None);
+
+        // a state from now brings the key that went down back
+        other.load_state(&chip8.save_state()).unwrap();
+        assert_eq!(other.key_wait_key(), Some(4));
+        other.run_frame(5);
+        assert_eq!(other.program_counter(), 0x202);
+        assert_eq!(other.registers()[0], 4);
+    }
+
+    #[test]
+    fn version_3_states_havent_drawn_this_frame() {
+        // the font's 0 drawn twice
+        let rom = [0xD0, 0x05, 0xD0, 0x05];
+        let mut chip8 = Chip8::with_quirks(Quirks { display_wait: true, ..Quirks::default() });
+        chip8.load(&rom).unwrap();
+        chip8.tick().unwrap();
+
+        let mut state = chip8.save_state();
+        state.truncate(state.len() - 2);
+        state[MAGIC.len()] = 3;
+
+        let mut other = Chip8::with_quirks(chip8.quirks());
+        other.load(&rom).unwrap();
+        other.load_state(&state).unwrap();
+        other.tick().unwrap();
+        assert_eq!(other.program_counter(), 0x204);
+
+        // a state from now remembers the frame has drawn
+        other.load_state(&chip8.save_state()).unwrap();
+        other.tick().unwrap();
+        assert_eq!(other.program_counter(), 0x202);
+    }
+
+    #[test]
+    fn fits_in_the_max_size_with_padding() {
+        let mut chip8 = Chip8::new();
+        assert!(chip8.save_state().len() < MAX_STATE_SIZE);
+
+        chip8.load(&ROM).unwrap();
+        chip8.audio_pattern = Some([0xAA; AUDIO_PATTERN_SIZE]);
+        let mut state = chip8.save_state();
+        assert_eq!(state.len(), MAX_STATE_SIZE);
+
+        chip8.run_frame(5);
+        state.resize(MAX_STATE_SIZE + 100, 0);
+        chip8.load_state(&state).unwrap();
+        assert_eq!(chip8.frame_count(), 0);
+    }
+
+    #[test]
+    fn rejects_other_roms() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ROM).unwrap();
+        let state = chip8.save_state();
+
+        let mut other = Chip8::new();
+        other.load(&[0x12, 0x00]).unwrap();
+
+        assert_eq!(other.load_state(&state), Err(StateError::RomMismatch));
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ROM).unwrap();
+        let state = chip8.save_state();
+
+        assert_eq!(chip8.load_state(b"not a state"), Err(StateError::NotAState));
+        assert_eq!(chip8.load_state(&state[..100]), Err(StateError::Truncated));
+
+        let mut future = state.clone();
+        future[MAGIC.len()] = VERSION + 1;
+        assert_eq!(chip8.load_state(&future), Err(StateError::UnsupportedVersion(VERSION + 1)));
+    }
+
+    #[test]
+    fn rejects_a_stack_pointer_past_the_stack() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ROM).unwrap();
+        let mut state = chip8.save_state();
+
+        // the stack pointer follows the ROM's hash, the screen, RAM, PC, V, I and the timers
+        let at = MAGIC.len() + 2 + 32 + SCREEN_WIDTH * SCREEN_HEIGHT + RAM_SIZE + 2 + NUM_REGISTER_V + 2 + 2;
+        state[at..at + 2].copy_from_slice(&999u16.to_le_bytes());
+        assert_eq!(chip8.load_state(&state), Err(StateError::StackPointer(999)));
+        assert_eq!(chip8.stack_pointer, 0);
+
+        // a full stack loads, unless the machine loading it has a lower limit
+        state[at..at + 2].copy_from_slice(&(STACK_SIZE as u16).to_le_bytes());
+        chip8.load_state(&state).unwrap();
+        assert_eq!(chip8.stack_pointer as usize, STACK_SIZE);
+
+        let mut limited = Chip8::builder().stack_limit(4).build().unwrap();
+        limited.load(&ROM).unwrap();
+        assert_eq!(limited.load_state(&state), Err(StateError::StackPointer(STACK_SIZE as u16)));
+    }
+}
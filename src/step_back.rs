@@ -0,0 +1,327 @@
+use crate::audio::Beeper;
+use crate::state::Snapshot;
+use crate::{Chip8, NUM_KEYS};
+
+use std::collections::VecDeque;
+use std::mem;
+
+// how many checkpoints step_back keeps, so it reaches back interval × this many instructions
+pub const STEP_BACK_CHECKPOINTS: usize = 64;
+// a checkpoint every this many instructions, what the debuggers use
+pub const DEFAULT_STEP_BACK_INTERVAL: usize = 1000;
+
+// what happened after a checkpoint, in order, enough to run it again exactly
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Event {
+    Instruction,
+    Timers,
+    NextFrame,
+    Key(u8, bool),
+}
+
+#[derive(Clone, Debug)]
+struct Checkpoint {
+    snapshot: Snapshot,
+    // what a save state leaves out but running again needs
+    keys: [bool; NUM_KEYS],
+    instruction_count: u64,
+    draw_count: u64,
+    collision_count: u64,
+    events: Vec<Event>,
+}
+
+// a checkpoint every `interval` instructions and everything since, for stepping backwards. anything
+// that changes the machine from outside (a debugger edit, a load, a new seed) clears it, the
+// checkpoints couldn't get back to the state it made
+#[derive(Clone, Debug, Default)]
+pub(crate) struct StepHistory {
+    checkpoints: VecDeque<Checkpoint>,
+    interval: usize,
+    // since the last checkpoint
+    instructions: usize,
+}
+
+impl StepHistory {
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.interval > 0
+    }
+
+    // nothing before the first checkpoint can be run again, so it isn't kept
+    fn push(&mut self, event: Event) {
+        if let Some(checkpoint) = self.checkpoints.back_mut() {
+            checkpoint.events.push(event);
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.checkpoints.clear();
+        self.instructions = 0;
+    }
+}
+
+impl Chip8 {
+    // a checkpoint every `instructions` instructions for step_back, 0 (the default) turns it off.
+    // smaller is a faster step back for more memory
+    pub fn set_step_back_interval(&mut self, instructions: usize) {
+        self.step_history.interval = instructions;
+        self.step_history.clear();
+    }
+
+    pub fn step_back_interval(&self) -> usize {
+        self.step_history.interval
+    }
+
+    // called by tick before each instruction
+    pub(crate) fn record_instruction(&mut self) {
+        if self.step_history.checkpoints.is_empty() || self.step_history.instructions >= self.step_history.interval {
+            if self.step_history.checkpoints.len() == STEP_BACK_CHECKPOINTS {
+                self.step_history.checkpoints.pop_front();
+            }
+
+            let checkpoint = Checkpoint {
+                snapshot: self.snapshot(),
+                keys: self.keys,
+                instruction_count: self.instruction_count,
+                draw_count: self.draw_count,
+                collision_count: self.collision_count,
+                events: Vec::new(),
+            };
+            self.step_history.checkpoints.push_back(checkpoint);
+            self.step_history.instructions = 0;
+        }
+
+        self.step_history.instructions += 1;
+        self.step_history.push(Event::Instruction);
+    }
+
+    pub(crate) fn record_timers(&mut self) {
+        self.step_history.push(Event::Timers);
+    }
+
+    pub(crate) fn record_next_frame(&mut self) {
+        self.step_history.push(Event::NextFrame);
+    }
+
+    pub(crate) fn record_key(&mut self, key_index: usize, is_pressed: bool) {
+        if self.keys[key_index] != is_pressed {
+            self.step_history.push(Event::Key(key_index as u8, is_pressed));
+        }
+    }
+
+    // undo the last instruction: back to the checkpoint before it, then everything up to it again.
+    // breakpoints stay, the trace loses the instruction, and a frame it ended is undone too. false
+    // when there's nothing recorded to go back to
+    pub fn step_back(&mut self) -> bool {
+        let mut history = mem::take(&mut self.step_history);
+
+        // the last instruction, dropping checkpoints that were stepped all the way back to
+        let index = loop {
+            let Some(checkpoint) = history.checkpoints.back() else {
+                self.step_history = history;
+                return false;
+            };
+
+            match checkpoint.events.iter().rposition(|&event| event == Event::Instruction) {
+                Some(index) => break index,
+                None => {
+                    history.checkpoints.pop_back();
+                },
+            }
+        };
+
+        let checkpoint = history.checkpoints.back_mut().expect("the loop found one");
+        checkpoint.events.truncate(index);
+        history.instructions = checkpoint.events.iter().filter(|&&event| event == Event::Instruction).count();
+
+        // none of running it again should show: no trace, hooks, sounds or debug output
+        let trace = mem::take(&mut self.trace);
+        let hooks = self.hooks.take();
+        let beeper = mem::replace(&mut self.beeper, Beeper::new());
+        let is_debug = mem::replace(&mut self.is_debug, false);
+        let (is_paused, stop_reason) = (self.is_paused, self.stop_reason.take());
+
+        self.restore(&checkpoint.snapshot);
+        self.keys = checkpoint.keys;
+        self.instruction_count = checkpoint.instruction_count;
+        self.draw_count = checkpoint.draw_count;
+        self.collision_count = checkpoint.collision_count;
+
+        for &event in &checkpoint.events {
+            match event {
+                // one that faulted faults the same way again, leaving the machine where it did
+                Event::Instruction => {
+                    let _ = self.tick();
+                },
+                Event::Timers => {
+                    self.tick_timers();
+                },
+                Event::NextFrame => {
+                    self.check_key_wait();
+                    self.frame_count += 1;
+                },
+                Event::Key(key, is_pressed) => {
+                    self.see_key_for_wait(key as usize, is_pressed);
+                    self.keys[key as usize] = is_pressed;
+                },
+            }
+        }
+
+        self.trace = trace;
+        self.trace.pop();
+        self.hooks = hooks;
+        self.beeper = beeper;
+        self.is_debug = is_debug;
+        self.is_paused = is_paused;
+        self.stop_reason = stop_reason;
+        self.step_history = history;
+
+        // rewinding can't go to frames that haven't started yet
+        self.rewind.forget_after(self.frame_count);
+        // continuing runs the instruction here rather than stopping on its breakpoint again
+        self.resume_from(self.program_counter);
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // V0 = random, V1 += 1, V2 += V0, a digit drawn, then loop after the first instruction
+    const ROM: [u8; 12] = [0xC0, 0xFF, 0x71, 0x01, 0x82, 0x04, 0xF1, 0x29, 0xD3, 0x45, 0x12, 0x00];
+
+    fn machine(interval: usize) -> Chip8 {
+        let mut chip8 = Chip8::with_seed(9);
+        chip8.load(&ROM).unwrap();
+        chip8.set_step_back_interval(interval);
+        chip8.set_trace_capacity(100);
+
+        chip8
+    }
+
+    fn state(chip8: &Chip8) -> (Snapshot, [bool; NUM_KEYS], u64, u64, u64) {
+        (chip8.snapshot(), chip8.keys, chip8.instruction_count, chip8.draw_count, chip8.collision_count)
+    }
+
+    #[test]
+    fn steps_back_to_where_fewer_steps_got() {
+        for interval in [1, 3, 4, 100] {
+            let mut reference = machine(interval);
+            for _ in 0..7 {
+                reference.tick().unwrap();
+            }
+
+            let mut chip8 = machine(interval);
+            for _ in 0..10 {
+                chip8.tick().unwrap();
+            }
+            for _ in 0..3 {
+                assert!(chip8.step_back());
+            }
+
+            assert_eq!(state(&chip8), state(&reference), "every {} instructions", interval);
+            assert_eq!(chip8.trace(), reference.trace());
+
+            // and forwards again is the same as it ever was
+            chip8.tick().unwrap();
+            reference.tick().unwrap();
+            assert_eq!(state(&chip8), state(&reference));
+        }
+    }
+
+    #[test]
+    fn replays_frames_and_keys() {
+        let mut reference = machine(5);
+        let mut chip8 = machine(5);
+
+        for chip8 in [&mut reference, &mut chip8] {
+            chip8.keypress(3, true);
+            chip8.advance_frame(4);
+            chip8.keypress(3, false);
+            chip8.keypress(7, true);
+            chip8.tick().unwrap();
+        }
+
+        chip8.tick().unwrap();
+        chip8.advance_frame(4);
+        chip8.keypress(7, false);
+        for _ in 0..5 {
+            assert!(chip8.step_back());
+        }
+        assert_eq!(state(&chip8), state(&reference));
+
+        // past the key changes, into the middle of the first frame before its timers
+        assert!(chip8.step_back());
+        assert!(chip8.step_back());
+        assert!(chip8.keys()[3] && !chip8.keys()[7]);
+
+        let mut expected = machine(5);
+        expected.keypress(3, true);
+        for _ in 0..3 {
+            expected.tick().unwrap();
+        }
+        assert_eq!(state(&chip8), state(&expected));
+    }
+
+    #[test]
+    fn stops_at_the_oldest_checkpoint() {
+        let mut chip8 = machine(2);
+        let start = chip8.snapshot();
+
+        for _ in 0..(STEP_BACK_CHECKPOINTS * 2 + 1) {
+            chip8.tick().unwrap();
+        }
+
+        let mut steps = 0;
+        while chip8.step_back() {
+            steps += 1;
+        }
+
+        // the first checkpoint fell off the front
+        assert_eq!(steps, (STEP_BACK_CHECKPOINTS - 1) * 2 + 1);
+        assert_ne!(chip8.snapshot(), start);
+        assert_eq!(chip8.instruction_count(), 2);
+    }
+
+    #[test]
+    fn an_edit_forgets_the_history() {
+        let mut chip8 = machine(4);
+        chip8.tick().unwrap();
+        chip8.tick().unwrap();
+        chip8.set_register(5, 1);
+
+        assert!(!chip8.step_back());
+        chip8.tick().unwrap();
+        assert!(chip8.step_back());
+        assert!(!chip8.step_back());
+        assert_eq!(chip8.registers()[5], 1);
+    }
+
+    #[test]
+    fn off_by_default() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ROM).unwrap();
+        chip8.tick().unwrap();
+
+        assert_eq!(chip8.step_back_interval(), 0);
+        assert!(!chip8.step_back());
+    }
+
+    #[test]
+    fn continuing_runs_the_breakpoint_stepped_back_to() {
+        let mut chip8 = machine(10);
+        chip8.add_breakpoint(0x202);
+        chip8.advance_frame(2);
+        assert_eq!(chip8.take_breakpoint_hit(), Some(0x202));
+
+        chip8.tick().unwrap();
+        assert!(chip8.step_back());
+        assert_eq!(chip8.program_counter(), 0x202);
+
+        chip8.advance_frame(1);
+        assert_eq!(chip8.program_counter(), 0x204);
+        assert_eq!(chip8.take_breakpoint_hit(), None);
+        assert!(chip8.has_breakpoint(0x202));
+    }
+}
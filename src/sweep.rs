@@ -0,0 +1,462 @@
+// a folder of ROMs run headlessly side by side, to find which ones a change broke. a sweep saved
+// as JSON is the baseline the next one is compared against:
+//
+// {
+//   "version": 1,
+//   "frames": 1200, "ticks_per_frame": 10, "seed": 1,
+//   "roms": [
+//     { "rom": "pong.ch8", "halt": "frames", "error": null, "screen_hash": "...", "frames": 1200, "instructions": 12000, "beeps": 3 },
+//     ...
+//   ]
+// }
+//
+// one ROM per line so a baseline kept in git diffs by ROM. halt is one of report::HaltReason's names
+
+use crate::headless::{panic_message, Options};
+use crate::report::{self, json_string, Expectations, HaltReason, Report};
+use crate::Chip8;
+
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+pub const SWEEP_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct RomResult {
+    pub rom: String,
+    #[serde(deserialize_with = "halt_from_name")]
+    pub halt: HaltReason,
+    // why it crashed, None unless halt is Crash
+    pub error: Option<String>,
+    pub screen_hash: String,
+    pub frames: u64,
+    pub instructions: u64,
+    pub beeps: u64,
+}
+
+impl RomResult {
+    fn of(rom: &str, report: &Report) -> Self {
+        let error = report
+            .errors
+            .first()
+            .map(|error| format!("crashed at {:03X} running {:04X}: {}", error.pc, error.opcode, error.message));
+
+        Self {
+            rom: rom.to_string(),
+            halt: report.halt,
+            error,
+            screen_hash: report.screen_hash.clone(),
+            frames: report.frames,
+            instructions: report.instructions,
+            beeps: report.beeps,
+        }
+    }
+
+    // a ROM that doesn't load, or a panic outside the run itself
+    fn crashed(rom: &str, message: String) -> Self {
+        Self {
+            rom: rom.to_string(),
+            halt: HaltReason::Crash,
+            error: Some(message),
+            screen_hash: String::new(),
+            frames: 0,
+            instructions: 0,
+            beeps: 0,
+        }
+    }
+
+    pub fn is_failure(&self) -> bool {
+        self.halt == HaltReason::Crash
+    }
+
+    fn to_json(&self) -> String {
+        format!(
+            "{{ \"rom\": {}, \"halt\": \"{}\", \"error\": {}, \"screen_hash\": \"{}\", \"frames\": {}, \"instructions\": {}, \"beeps\": {} }}",
+            json_string(&self.rom),
+            self.halt,
+            self.error.as_deref().map_or("null".to_string(), json_string),
+            self.screen_hash,
+            self.frames,
+            self.instructions,
+            self.beeps
+        )
+    }
+}
+
+fn halt_from_name<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HaltReason, D::Error> {
+    let name = String::deserialize(deserializer)?;
+
+    HaltReason::from_name(&name).ok_or_else(|| serde::de::Error::custom(format!("unknown halt `{}`", name)))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct Sweep {
+    pub version: u32,
+    pub frames: usize,
+    pub ticks_per_frame: usize,
+    pub seed: u64,
+    // in the order they were given
+    pub roms: Vec<RomResult>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SweepError {
+    Parse(String),
+    Version(u32),
+}
+
+impl fmt::Display for SweepError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SweepError::Parse(message) => write!(f, "not a sweep: {}", message),
+            SweepError::Version(version) => {
+                write!(f, "sweep version {} isn't one this build reads ({})", version, SWEEP_VERSION)
+            },
+        }
+    }
+}
+
+impl Error for SweepError {}
+
+impl Sweep {
+    pub fn parse(text: &str) -> Result<Self, SweepError> {
+        let sweep: Sweep = serde_json::from_str(text).map_err(|err| SweepError::Parse(err.to_string()))?;
+
+        if sweep.version != SWEEP_VERSION {
+            return Err(SweepError::Version(sweep.version));
+        }
+
+        Ok(sweep)
+    }
+
+    pub fn to_json(&self) -> String {
+        let roms: Vec<String> = self.roms.iter().map(|rom| format!("    {}", rom.to_json())).collect();
+
+        format!(
+            "{{\n  \"version\": {},\n  \"frames\": {}, \"ticks_per_frame\": {}, \"seed\": {},\n  \"roms\": [\n{}\n  ]\n}}\n",
+            self.version,
+            self.frames,
+            self.ticks_per_frame,
+            self.seed,
+            roms.join(",\n")
+        )
+    }
+
+    // the settings that differ from `other`'s, a comparison between them shows changes nobody made
+    pub fn setting_differences(&self, other: &Sweep) -> Vec<String> {
+        let settings = [
+            ("frames", self.frames as u64, other.frames as u64),
+            ("ticks per frame", self.ticks_per_frame as u64, other.ticks_per_frame as u64),
+            ("seed", self.seed, other.seed),
+        ];
+
+        settings
+            .iter()
+            .filter(|(_, mine, theirs)| mine != theirs)
+            .map(|(name, mine, theirs)| format!("{} {} instead of {}", name, mine, theirs))
+            .collect()
+    }
+}
+
+// every ROM with `options`, as many at once as there are cores. each runs on its own machine and a
+// panic in one is its result, the rest carry on
+pub fn run(roms: &[(String, Vec<u8>)], options: &Options) -> Sweep {
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get()).min(roms.len()).max(1);
+    let next = AtomicUsize::new(0);
+
+    let mut results: Vec<(usize, RomResult)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some((name, rom)) = roms.get(index) else {
+                            break;
+                        };
+
+                        done.push((index, run_rom(name, rom, options)));
+                    }
+
+                    done
+                })
+            })
+            .collect();
+
+        workers.into_iter().flat_map(|worker| worker.join().expect("sweep workers catch their panics")).collect()
+    });
+
+    // workers finish in any order, the results go back in the ROMs'
+    results.sort_by_key(|&(index, _)| index);
+
+    Sweep {
+        version: SWEEP_VERSION,
+        frames: options.frames,
+        ticks_per_frame: options.ticks_per_frame,
+        seed: options.seed,
+        roms: results.into_iter().map(|(_, result)| result).collect(),
+    }
+}
+
+fn run_rom(name: &str, rom: &[u8], options: &Options) -> RomResult {
+    if let Err(error) = Chip8::new_with_rom(rom) {
+        return RomResult::crashed(name, format!("unable to load the ROM: {}", error));
+    }
+
+    let run = panic::catch_unwind(AssertUnwindSafe(|| report::run(rom, options, &Expectations::default()).1));
+
+    match run {
+        Ok(report) => RomResult::of(name, &report),
+        Err(cause) => RomResult::crashed(name, panic_message(cause)),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    // crashed now, didn't in the baseline
+    Failing { rom: String, was: HaltReason, error: String },
+    Fixed { rom: String, halt: HaltReason },
+    ScreenChanged { rom: String, was: String, now: String },
+    HaltChanged { rom: String, was: HaltReason, now: HaltReason },
+    Added(String),
+    Removed(String),
+}
+
+impl Change {
+    pub fn rom(&self) -> &str {
+        match self {
+            Change::Failing { rom, .. }
+            | Change::Fixed { rom, .. }
+            | Change::ScreenChanged { rom, .. }
+            | Change::HaltChanged { rom, .. }
+            | Change::Added(rom)
+            | Change::Removed(rom) => rom,
+        }
+    }
+
+    // what a sweep is for finding, the rest is worth a line but not a failure
+    pub fn is_regression(&self) -> bool {
+        matches!(self, Change::Failing { .. } | Change::ScreenChanged { .. } | Change::HaltChanged { .. })
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Failing { rom, was, error } => write!(f, "FAIL    {}: {} (was {})", rom, error, was),
+            Change::Fixed { rom, halt } => write!(f, "FIXED   {}: no longer crashes, ends with {}", rom, halt),
+            Change::ScreenChanged { rom, was, now } => write!(f, "SCREEN  {}: {} -> {}", rom, was, now),
+            Change::HaltChanged { rom, was, now } => write!(f, "HALT    {}: {} -> {}", rom, was, now),
+            Change::Added(rom) => write!(f, "NEW     {}", rom),
+            Change::Removed(rom) => write!(f, "GONE    {}", rom),
+        }
+    }
+}
+
+// what's different about each ROM in `current`, then the ROMs only the baseline has. a crash hides
+// the screen and halt changes that come with it
+pub fn compare(baseline: &Sweep, current: &Sweep) -> Vec<Change> {
+    let before: BTreeMap<&str, &RomResult> = baseline.roms.iter().map(|result| (result.rom.as_str(), result)).collect();
+    let mut changes = Vec::new();
+
+    for now in &current.roms {
+        let rom = now.rom.clone();
+        let Some(was) = before.get(now.rom.as_str()) else {
+            changes.push(Change::Added(rom));
+            continue;
+        };
+
+        match (was.is_failure(), now.is_failure()) {
+            (false, true) => {
+                let error = now.error.clone().unwrap_or_default();
+                changes.push(Change::Failing { rom, was: was.halt, error });
+            },
+            (true, false) => changes.push(Change::Fixed { rom, halt: now.halt }),
+            (true, true) => (),
+            (false, false) => {
+                if was.halt != now.halt {
+                    changes.push(Change::HaltChanged { rom: rom.clone(), was: was.halt, now: now.halt });
+                }
+
+                if was.screen_hash != now.screen_hash {
+                    let (was, now) = (was.screen_hash.clone(), now.screen_hash.clone());
+                    changes.push(Change::ScreenChanged { rom, was, now });
+                }
+            },
+        }
+    }
+
+    let after: BTreeMap<&str, &RomResult> = current.roms.iter().map(|result| (result.rom.as_str(), result)).collect();
+    let removed = baseline.roms.iter().filter(|result| !after.contains_key(result.rom.as_str()));
+    changes.extend(removed.map(|result| Change::Removed(result.rom.clone())));
+
+    changes
+}
+
+// a line per change then a summary, like
+//   SCREEN  brix.ch8: 1f2e... -> 9a8b...
+//   1 of 200 ROMs regressed
+pub fn format_changes(changes: &[Change], roms: usize) -> String {
+    let mut out: String = changes.iter().map(|change| format!("{}\n", change)).collect();
+    // a ROM's changes are next to each other
+    let mut regressed: Vec<&str> = changes.iter().filter(|change| change.is_regression()).map(Change::rom).collect();
+    regressed.dedup();
+
+    if changes.is_empty() {
+        out.push_str(&format!("all {} ROMs match the baseline\n", roms));
+    } else {
+        out.push_str(&format!("{} of {} ROMs regressed\n", regressed.len(), roms));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::headless::{Script, DEFAULT_SEED};
+
+    fn result(rom: &str, halt: HaltReason, screen_hash: &str) -> RomResult {
+        let error = (halt == HaltReason::Crash).then(|| "crashed at 200 running 00EE: stack underflow".to_string());
+
+        RomResult {
+            rom: rom.to_string(),
+            halt,
+            error,
+            screen_hash: screen_hash.to_string(),
+            frames: 1200,
+            instructions: 12000,
+            beeps: 0,
+        }
+    }
+
+    fn sweep(roms: Vec<RomResult>) -> Sweep {
+        Sweep { version: SWEEP_VERSION, frames: 1200, ticks_per_frame: 10, seed: 1, roms }
+    }
+
+    #[test]
+    fn an_unchanged_sweep_matches() {
+        let baseline =
+            sweep(vec![result("pong.ch8", HaltReason::Frames, "aa"), result("bad.ch8", HaltReason::Crash, "")]);
+
+        assert_eq!(compare(&baseline, &baseline.clone()), []);
+        assert_eq!(format_changes(&[], 2), "all 2 ROMs match the baseline\n");
+    }
+
+    #[test]
+    fn finds_new_failures_and_changed_screens() {
+        let baseline = sweep(vec![
+            result("pong.ch8", HaltReason::Frames, "aa"),
+            result("brix.ch8", HaltReason::Frames, "bb"),
+            result("maze.ch8", HaltReason::SelfJump, "cc"),
+            result("bad.ch8", HaltReason::Crash, ""),
+            result("gone.ch8", HaltReason::Frames, "dd"),
+        ]);
+        let current = sweep(vec![
+            result("pong.ch8", HaltReason::Crash, "ee"),
+            result("brix.ch8", HaltReason::Frames, "ff"),
+            result("maze.ch8", HaltReason::KeyWait, "11"),
+            result("bad.ch8", HaltReason::Frames, "22"),
+            result("new.ch8", HaltReason::Frames, "33"),
+        ]);
+
+        let changes = compare(&baseline, &current);
+        let error = "crashed at 200 running 00EE: stack underflow".to_string();
+        assert_eq!(
+            changes,
+            [
+                Change::Failing { rom: "pong.ch8".to_string(), was: HaltReason::Frames, error },
+                Change::ScreenChanged { rom: "brix.ch8".to_string(), was: "bb".to_string(), now: "ff".to_string() },
+                Change::HaltChanged {
+                    rom: "maze.ch8".to_string(),
+                    was: HaltReason::SelfJump,
+                    now: HaltReason::KeyWait
+                },
+                Change::ScreenChanged { rom: "maze.ch8".to_string(), was: "cc".to_string(), now: "11".to_string() },
+                Change::Fixed { rom: "bad.ch8".to_string(), halt: HaltReason::Frames },
+                Change::Added("new.ch8".to_string()),
+                Change::Removed("gone.ch8".to_string()),
+            ]
+        );
+
+        // maze counts once
+        let text = format_changes(&changes, current.roms.len());
+        assert!(text.ends_with("\n3 of 5 ROMs regressed\n"), "{}", text);
+        assert!(
+            text.starts_with("FAIL    pong.ch8: crashed at 200 running 00EE: stack underflow (was frames)\n"),
+            "{}",
+            text
+        );
+        assert!(text.contains("SCREEN  brix.ch8: bb -> ff\n"), "{}", text);
+        assert!(text.contains("HALT    maze.ch8: self_jump -> key_wait\n"), "{}", text);
+    }
+
+    #[test]
+    fn only_new_and_fixed_roms_arent_regressions() {
+        let baseline = sweep(vec![result("bad.ch8", HaltReason::Crash, "")]);
+        let current =
+            sweep(vec![result("bad.ch8", HaltReason::SelfJump, "aa"), result("new.ch8", HaltReason::Crash, "")]);
+
+        let changes = compare(&baseline, &current);
+        assert!(changes.iter().all(|change| !change.is_regression()), "{:?}", changes);
+        assert!(format_changes(&changes, 2).ends_with("0 of 2 ROMs regressed\n"));
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let mut crashed = result("quote\".ch8", HaltReason::Crash, "");
+        crashed.error = Some("line\nbreak".to_string());
+        let saved = sweep(vec![result("pong.ch8", HaltReason::Frames, "0123456789abcdef"), crashed]);
+
+        let json = saved.to_json();
+        assert_eq!(json.lines().filter(|line| line.contains("\"rom\"")).count(), 2);
+        assert_eq!(Sweep::parse(&json), Ok(saved.clone()));
+        assert_eq!(Sweep::parse(&sweep(Vec::new()).to_json()).unwrap().roms, []);
+
+        assert_eq!(Sweep::parse(&json.replace("\"version\": 1", "\"version\": 9")), Err(SweepError::Version(9)));
+        assert!(matches!(Sweep::parse(&json.replace("\"frames\"", "\"halted\"")), Err(SweepError::Parse(_))));
+        assert!(matches!(Sweep::parse(&json.replace("\"crash\"", "\"melted\"")), Err(SweepError::Parse(_))));
+    }
+
+    #[test]
+    fn notices_different_settings() {
+        let baseline = sweep(Vec::new());
+        let mut current = sweep(Vec::new());
+        assert_eq!(current.setting_differences(&baseline), Vec::<String>::new());
+
+        current.seed = 2;
+        current.frames = 600;
+        assert_eq!(current.setting_differences(&baseline), ["frames 600 instead of 1200", "seed 2 instead of 1"]);
+    }
+
+    #[test]
+    fn a_crashing_rom_doesnt_stop_the_rest() {
+        // return with an empty stack, a ROM too big for memory, and one that stops
+        let roms: Vec<(String, Vec<u8>)> = vec![
+            ("underflow.ch8".to_string(), vec![0x00, 0xEE]),
+            ("huge.ch8".to_string(), vec![0; 8192]),
+            ("stop.ch8".to_string(), vec![0x12, 0x00]),
+        ];
+        let options = Options { frames: 10, ticks_per_frame: 10, seed: DEFAULT_SEED, script: Script::default() };
+
+        let sweep = run(&roms, &options);
+        let names: Vec<&str> = sweep.roms.iter().map(|result| result.rom.as_str()).collect();
+
+        assert_eq!(names, ["underflow.ch8", "huge.ch8", "stop.ch8"]);
+        assert!(sweep.roms[0].error.as_deref().unwrap().starts_with("crashed at 200 running 00EE"));
+        assert_eq!(sweep.roms[1].halt, HaltReason::Crash);
+        assert!(sweep.roms[1].error.as_deref().unwrap().starts_with("unable to load the ROM: 8192 bytes"));
+        assert_eq!(
+            (sweep.roms[2].halt, sweep.roms[2].frames, sweep.roms[2].error.as_deref()),
+            (HaltReason::SelfJump, 10, None)
+        );
+        assert_eq!(run(&roms, &options), sweep);
+    }
+}
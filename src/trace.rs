@@ -0,0 +1,94 @@
+use crate::Chip8;
+
+use std::collections::VecDeque;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub pc: u16,
+    pub opcode: u16,
+}
+
+// the last `capacity` instructions run, kept so a dump can show what led up to it
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TraceBuffer {
+    entries: VecDeque<TraceEntry>,
+    capacity: usize,
+}
+
+impl TraceBuffer {
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    pub(crate) fn push(&mut self, entry: TraceEntry) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+
+        self.entries.push_back(entry);
+    }
+
+    pub(crate) fn pop(&mut self) {
+        self.entries.pop_back();
+    }
+
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        let excess = self.entries.len().saturating_sub(capacity);
+        self.entries.drain(..excess);
+    }
+}
+
+impl Chip8 {
+    // keep the last `instructions` instructions for trace, 0 (the default) turns it off
+    pub fn set_trace_capacity(&mut self, instructions: usize) {
+        self.trace.set_capacity(instructions);
+    }
+
+    pub fn trace_capacity(&self) -> usize {
+        self.trace.capacity
+    }
+
+    // oldest first
+    pub fn trace(&self) -> Vec<TraceEntry> {
+        self.trace.entries.iter().copied().collect()
+    }
+
+    pub fn clear_trace(&mut self) {
+        self.trace.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // V0 += 1, jump back
+    const ROM: [u8; 4] = [0x70, 0x01, 0x12, 0x00];
+
+    #[test]
+    fn keeps_nothing_while_off() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ROM).unwrap();
+        chip8.run_frame(10);
+
+        assert!(chip8.trace().is_empty());
+    }
+
+    #[test]
+    fn keeps_the_latest_instructions() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ROM).unwrap();
+        chip8.set_trace_capacity(3);
+        chip8.run_frame(5);
+
+        let add = TraceEntry { pc: 0x200, opcode: 0x7001 };
+        let jump = TraceEntry { pc: 0x202, opcode: 0x1200 };
+        assert_eq!(chip8.trace(), [add, jump, add]);
+
+        chip8.set_trace_capacity(1);
+        assert_eq!(chip8.trace(), [add]);
+        chip8.clear_trace();
+        assert!(chip8.trace().is_empty());
+    }
+}
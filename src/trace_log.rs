@@ -0,0 +1,295 @@
+// a whole run's instructions written out as they execute, for reading a ROM's behaviour after the
+// fact or diffing two runs. the run is headless, so the seed, speed and input script make it the
+// same every time
+//
+// a long run is millions of instructions, so the log stops at a size cap with a line saying so,
+// and filters keep only the part that matters: an address range, an opcode pattern, some frames
+
+use crate::disasm::disassemble;
+use crate::headless::{crash_message, play_frame, Options};
+use crate::{Chip8, Chip8Error, TraceEntry};
+
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::mem;
+use std::ops::RangeInclusive;
+
+pub const DEFAULT_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TraceFormat {
+    // `FRAME PC OPCODE assembly` a line
+    #[default]
+    Text,
+    // a JSON object a line
+    Json,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TraceFilter {
+    // instructions at these addresses
+    Pc(RangeInclusive<u16>),
+    // opcodes that are `value` where `mask` is set
+    Opcode { value: u16, mask: u16 },
+    Frames(RangeInclusive<usize>),
+}
+
+impl TraceFilter {
+    // `pc=200-2FF` or `pc=2A4` in hex, `op=DXYN` with X, Y, N and K matching any digit, or
+    // `frames=60-120`
+    pub fn parse(text: &str) -> Option<Self> {
+        let (kind, value) = text.split_once('=')?;
+
+        match kind.trim() {
+            "pc" => {
+                let range = parse_range(value, |bound| u16::from_str_radix(bound, 16).ok())?;
+                Some(TraceFilter::Pc(range))
+            },
+            "op" => parse_pattern(value.trim()),
+            "frames" => Some(TraceFilter::Frames(parse_range(value, |bound| bound.parse().ok())?)),
+            _ => None,
+        }
+    }
+
+    pub fn matches(&self, frame: usize, entry: TraceEntry) -> bool {
+        match self {
+            TraceFilter::Pc(range) => range.contains(&entry.pc),
+            TraceFilter::Opcode { value, mask } => entry.opcode & mask == *value,
+            TraceFilter::Frames(range) => range.contains(&frame),
+        }
+    }
+}
+
+fn parse_range<T: PartialOrd + Copy>(text: &str, parse: impl Fn(&str) -> Option<T>) -> Option<RangeInclusive<T>> {
+    let (start, end) = match text.split_once('-') {
+        Some((start, end)) => (parse(start.trim())?, parse(end.trim())?),
+        None => {
+            let at = parse(text.trim())?;
+            (at, at)
+        },
+    };
+
+    (start <= end).then_some(start..=end)
+}
+
+fn parse_pattern(text: &str) -> Option<TraceFilter> {
+    if text.chars().count() != 4 {
+        return None;
+    }
+
+    let (mut value, mut mask) = (0, 0);
+
+    for c in text.chars() {
+        let digit = match c.to_ascii_uppercase() {
+            'X' | 'Y' | 'N' | 'K' => None,
+            c => Some(c.to_digit(16)? as u16),
+        };
+
+        value = value << 4 | digit.unwrap_or(0);
+        mask = mask << 4 | if digit.is_some() { 0xF } else { 0 };
+    }
+
+    Some(TraceFilter::Opcode { value, mask })
+}
+
+// filters of the same kind are alternatives, each kind given has to match
+pub fn keeps(filters: &[TraceFilter], frame: usize, entry: TraceEntry) -> bool {
+    filters.iter().all(|filter| {
+        filters
+            .iter()
+            .filter(|other| mem::discriminant(*other) == mem::discriminant(filter))
+            .any(|other| other.matches(frame, entry))
+    })
+}
+
+// the lines of a trace going to `out`, up to `max_bytes` of them
+pub struct TraceLog<W: Write> {
+    out: W,
+    format: TraceFormat,
+    filters: Vec<TraceFilter>,
+    max_bytes: u64,
+    bytes: u64,
+    lines: usize,
+    is_truncated: bool,
+}
+
+impl<W: Write> TraceLog<W> {
+    pub fn new(out: W, format: TraceFormat, filters: Vec<TraceFilter>, max_bytes: u64) -> Self {
+        Self { out, format, filters, max_bytes, bytes: 0, lines: 0, is_truncated: false }
+    }
+
+    // the instruction as a line, unless a filter leaves it out or the log is full. the first line
+    // that doesn't fit writes the truncation marker instead, and nothing goes in after it
+    pub fn record(&mut self, frame: usize, entry: TraceEntry) -> io::Result<()> {
+        if self.is_truncated || !keeps(&self.filters, frame, entry) {
+            return Ok(());
+        }
+
+        let line = self.line(frame, entry);
+
+        if self.bytes + line.len() as u64 > self.max_bytes {
+            self.is_truncated = true;
+            let marker = self.marker();
+            return self.out.write_all(marker.as_bytes());
+        }
+
+        self.out.write_all(line.as_bytes())?;
+        self.bytes += line.len() as u64;
+        self.lines += 1;
+
+        Ok(())
+    }
+
+    fn line(&self, frame: usize, entry: TraceEntry) -> String {
+        let assembly = disassemble(entry.opcode);
+
+        match self.format {
+            TraceFormat::Text => format!("{} {:03X} {:04X} {}\n", frame, entry.pc, entry.opcode, assembly),
+            TraceFormat::Json => format!(
+                "{{\"frame\":{},\"pc\":{},\"opcode\":{},\"asm\":\"{}\"}}\n",
+                frame, entry.pc, entry.opcode, assembly
+            ),
+        }
+    }
+
+    fn marker(&self) -> String {
+        match self.format {
+            TraceFormat::Text => format!("# truncated after {} lines, {} bytes\n", self.lines, self.bytes),
+            TraceFormat::Json => {
+                format!("{{\"truncated\":true,\"lines\":{},\"bytes\":{}}}\n", self.lines, self.bytes)
+            },
+        }
+    }
+
+    // instructions written, the marker not counted
+    pub fn lines(&self) -> usize {
+        self.lines
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.is_truncated
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+#[derive(Debug)]
+pub enum TraceError {
+    Write(io::Error),
+    // the ROM is empty or too big to load, nothing ran
+    Rom(Chip8Error),
+    // the trace up to the crash is in the log
+    Crashed(String),
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TraceError::Write(e) => write!(f, "unable to write the trace: {}", e),
+            TraceError::Rom(e) => write!(f, "unable to load the ROM: {}", e),
+            TraceError::Crashed(message) => write!(f, "the emulator crashed: {}", message),
+        }
+    }
+}
+
+impl Error for TraceError {}
+
+impl From<io::Error> for TraceError {
+    fn from(e: io::Error) -> Self {
+        TraceError::Write(e)
+    }
+}
+
+// runs `options.frames` frames like headless::run into `log`, stopping early once the log is full
+pub fn run<W: Write>(rom: &[u8], options: &Options, log: &mut TraceLog<W>) -> Result<Chip8, TraceError> {
+    let mut chip8 = Chip8::new();
+    chip8.load(rom).map_err(TraceError::Rom)?;
+    chip8.set_seed(options.seed);
+
+    for frame in 0..options.frames {
+        if log.is_truncated() {
+            break;
+        }
+
+        // a frame's worth of instructions at a time, however fast the script makes it
+        chip8.set_trace_capacity(options.script.speed_at(frame, options.ticks_per_frame));
+        let crash = crash_message(&mut chip8, |chip8| {
+            play_frame(chip8, frame, options.ticks_per_frame, &options.script);
+        });
+
+        for entry in chip8.trace() {
+            log.record(frame, entry)?;
+        }
+        chip8.clear_trace();
+
+        if let Some(message) = crash {
+            log.flush()?;
+            return Err(TraceError::Crashed(message));
+        }
+    }
+
+    log.flush()?;
+    chip8.set_trace_capacity(0);
+
+    Ok(chip8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(pc: u16, opcode: u16) -> TraceEntry {
+        TraceEntry { pc, opcode }
+    }
+
+    #[test]
+    fn parses_filters() {
+        assert_eq!(TraceFilter::parse("pc=200-2ff"), Some(TraceFilter::Pc(0x200..=0x2FF)));
+        assert_eq!(TraceFilter::parse("pc=2A4"), Some(TraceFilter::Pc(0x2A4..=0x2A4)));
+        assert_eq!(TraceFilter::parse("op=DXYN"), Some(TraceFilter::Opcode { value: 0xD000, mask: 0xF000 }));
+        assert_eq!(TraceFilter::parse("op=fx65"), Some(TraceFilter::Opcode { value: 0xF065, mask: 0xF0FF }));
+        assert_eq!(TraceFilter::parse("frames=60-120"), Some(TraceFilter::Frames(60..=120)));
+
+        for bad in ["pc=2FF-200", "pc=G00", "op=DXY", "op=DXYNN", "op=DZYN", "frames=1-x", "200-2FF", "sp=1"] {
+            assert_eq!(TraceFilter::parse(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn kinds_narrow_and_alternatives_widen() {
+        let draws_or_clears = [
+            TraceFilter::parse("op=DXYN").unwrap(),
+            TraceFilter::parse("op=00E0").unwrap(),
+            TraceFilter::parse("pc=200-2FF").unwrap(),
+        ];
+
+        assert!(keeps(&draws_or_clears, 0, entry(0x204, 0xD125)));
+        assert!(keeps(&draws_or_clears, 0, entry(0x2FE, 0x00E0)));
+        assert!(!keeps(&draws_or_clears, 0, entry(0x300, 0xD125)));
+        assert!(!keeps(&draws_or_clears, 0, entry(0x204, 0x7001)));
+        assert!(keeps(&[], 9, entry(0, 0)));
+    }
+
+    #[test]
+    fn stops_at_the_cap_with_a_marker() {
+        let mut log = TraceLog::new(Vec::new(), TraceFormat::Text, Vec::new(), 40);
+
+        for pc in [0x200, 0x202, 0x204, 0x206] {
+            log.record(3, entry(pc, 0x7001)).unwrap();
+        }
+
+        let text = String::from_utf8(log.into_inner()).unwrap();
+        assert_eq!(text, "3 200 7001 ADD V0, 0x01\n# truncated after 1 lines, 24 bytes\n");
+    }
+}
@@ -0,0 +1,248 @@
+// CHIP-8 test vectors: the machine before each instruction, one JSON object per line, for comparing
+// against other emulators step by step
+//
+//   {"pc":512,"opcode":24576,"v":[0,...],"i":0,"sp":0,"dt":0,"st":0,"screen_hash":"..."}
+//
+// the screen hash is conformance::screen_hash. the timers tick after every `ticks_per_frame`
+// instructions, like a frame would, and RND comes from the seed, so the same ROM, seed and speed
+// always give the same file
+
+use crate::conformance::screen_hash;
+use crate::headless::panic_message;
+use crate::{Chip8, Chip8Error};
+
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct Vector {
+    pub pc: u16,
+    pub opcode: u16,
+    pub v: [u8; 16],
+    pub i: u16,
+    pub sp: u16,
+    pub dt: u8,
+    pub st: u8,
+    pub screen_hash: String,
+}
+
+impl Vector {
+    pub fn of(chip8: &Chip8) -> Self {
+        let pc = chip8.program_counter();
+        let byte = |address: u16| chip8.memory().get(address as usize).copied().unwrap_or(0);
+        let mut v = [0; 16];
+        v.copy_from_slice(chip8.registers());
+
+        Self {
+            pc,
+            opcode: u16::from_be_bytes([byte(pc), byte(pc.wrapping_add(1))]),
+            v,
+            i: chip8.register_i(),
+            sp: chip8.stack_pointer(),
+            dt: chip8.delay_timer(),
+            st: chip8.sound_timer(),
+            screen_hash: screen_hash(chip8.get_display()),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let v: Vec<String> = self.v.iter().map(u8::to_string).collect();
+
+        format!(
+            "{{\"pc\":{},\"opcode\":{},\"v\":[{}],\"i\":{},\"sp\":{},\"dt\":{},\"st\":{},\"screen_hash\":\"{}\"}}",
+            self.pc,
+            self.opcode,
+            v.join(","),
+            self.i,
+            self.sp,
+            self.dt,
+            self.st,
+            self.screen_hash
+        )
+    }
+
+    // "pc 204, expected 206" for each field that's off
+    fn differences(&self, expected: &Vector) -> Vec<String> {
+        let mut differences = Vec::new();
+        let mut check = |name: &str, actual: String, expected: String| {
+            if actual != expected {
+                differences.push(format!("{} {}, expected {}", name, actual, expected));
+            }
+        };
+
+        check("pc", format!("{:03X}", self.pc), format!("{:03X}", expected.pc));
+        check("opcode", format!("{:04X}", self.opcode), format!("{:04X}", expected.opcode));
+        for (register, (actual, wanted)) in self.v.iter().zip(expected.v).enumerate() {
+            check(&format!("V{:X}", register), actual.to_string(), wanted.to_string());
+        }
+        check("I", format!("{:03X}", self.i), format!("{:03X}", expected.i));
+        check("sp", self.sp.to_string(), expected.sp.to_string());
+        check("dt", self.dt.to_string(), expected.dt.to_string());
+        check("st", self.st.to_string(), expected.st.to_string());
+        check("screen", self.screen_hash.clone(), expected.screen_hash.clone());
+
+        differences
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VectorError {
+    Parse { line: usize, message: String },
+    Mismatch { line: usize, differences: Vec<String> },
+    Crashed { line: usize, message: String },
+}
+
+impl fmt::Display for VectorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VectorError::Parse { line, message } => write!(f, "line {} isn't a test vector: {}", line, message),
+            VectorError::Mismatch { line, differences } => write!(f, "line {} differs: {}", line, differences.join(", ")),
+            VectorError::Crashed { line, message } => write!(f, "the emulator crashed before line {}: {}", line, message),
+        }
+    }
+}
+
+impl Error for VectorError {}
+
+// up to `instructions` vectors, fewer and what went wrong if the ROM crashes the emulator
+pub fn export(rom: &[u8], seed: u64, ticks_per_frame: usize, instructions: usize) -> (Vec<Vector>, Option<String>) {
+    let mut chip8 = Chip8::with_seed(seed);
+    if let Err(error) = chip8.load(rom) {
+        return (Vec::new(), Some(format!("unable to load the ROM: {}", error)));
+    }
+
+    let mut vectors = Vec::with_capacity(instructions);
+    let run = panic::catch_unwind(AssertUnwindSafe(|| -> Result<(), Chip8Error> {
+        for index in 0..instructions {
+            vectors.push(Vector::of(&chip8));
+            step(&mut chip8, index, ticks_per_frame)?;
+        }
+
+        Ok(())
+    }));
+
+    let crash = match run {
+        Ok(Ok(())) => None,
+        Ok(Err(error)) => Some(error.to_string()),
+        Err(cause) => Some(panic_message(cause)),
+    };
+
+    (vectors, crash)
+}
+
+// replays the ROM against every line of `text`, Ok is how many lines matched
+pub fn verify(rom: &[u8], seed: u64, ticks_per_frame: usize, text: &str) -> Result<usize, VectorError> {
+    let expected = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str::<Vector>(line)
+                .map(|vector| (index + 1, vector))
+                .map_err(|err| VectorError::Parse { line: index + 1, message: err.to_string() })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (actual, crash) = export(rom, seed, ticks_per_frame, expected.len());
+
+    for (&(line, ref expected), actual) in expected.iter().zip(&actual) {
+        let differences = actual.differences(expected);
+
+        if !differences.is_empty() {
+            return Err(VectorError::Mismatch { line, differences });
+        }
+    }
+
+    match (crash, expected.get(actual.len())) {
+        (Some(message), Some(&(line, _))) => Err(VectorError::Crashed { line, message }),
+        _ => Ok(expected.len()),
+    }
+}
+
+fn step(chip8: &mut Chip8, index: usize, ticks_per_frame: usize) -> Result<(), Chip8Error> {
+    chip8.tick()?;
+
+    if (index + 1).is_multiple_of(ticks_per_frame) {
+        chip8.tick_timers();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // V0 = 3, DT = V0, draw "0" at (0, 0), V1 = RND, loop
+    const ROM: [u8; 10] = [0x60, 0x03, 0xF0, 0x15, 0xD0, 0x05, 0xC1, 0xFF, 0x12, 0x06];
+
+    fn exported(instructions: usize) -> String {
+        let (vectors, crash) = export(&ROM, 7, 2, instructions);
+        assert_eq!(crash, None);
+
+        vectors.iter().map(|vector| vector.to_json() + "\n").collect()
+    }
+
+    #[test]
+    fn records_the_state_before_each_instruction() {
+        let (vectors, _) = export(&ROM, 7, 2, 4);
+
+        assert_eq!(vectors[0].pc, 0x200);
+        assert_eq!(vectors[0].opcode, 0x6003);
+        assert_eq!(vectors[1].v[0], 3);
+        // the timers ticked once after the first two instructions
+        assert_eq!(vectors[2].dt, 2);
+        assert_ne!(vectors[3].screen_hash, vectors[0].screen_hash);
+    }
+
+    #[test]
+    fn verifies_its_own_export() {
+        let text = exported(40);
+
+        assert_eq!(text.lines().count(), 40);
+        assert_eq!(verify(&ROM, 7, 2, &text), Ok(40));
+        assert_eq!(verify(&ROM, 7, 2, &format!("\n{}\n\n", text)), Ok(40));
+    }
+
+    #[test]
+    fn finds_the_first_corrupted_line() {
+        let mut lines: Vec<String> = exported(20).lines().map(String::from).collect();
+        lines[5] = lines[5].replacen("\"i\":", "\"i\":1", 1);
+        lines[9] = lines[9].replacen("\"sp\":0", "\"sp\":3", 1);
+
+        match verify(&ROM, 7, 2, &lines.join("\n")) {
+            Err(VectorError::Mismatch { line, differences }) => {
+                assert_eq!(line, 6);
+                assert_eq!(differences.len(), 1);
+                assert!(differences[0].starts_with("I "), "{:?}", differences);
+            },
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn a_different_seed_is_a_mismatch() {
+        // RND is the fourth instruction, so V1 first differs before the fifth
+        match verify(&ROM, 8, 2, &exported(10)) {
+            Err(VectorError::Mismatch { line, differences }) => {
+                assert_eq!(line, 5);
+                assert!(differences[0].starts_with("V1 "), "{:?}", differences);
+            },
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_bad_lines_and_crashes() {
+        let error = verify(&ROM, 7, 2, "{\"pc\": 512}").unwrap_err();
+        assert!(matches!(error, VectorError::Parse { line: 1, .. }), "{:?}", error);
+
+        // return with an empty stack
+        let (vectors, _) = export(&[0x00, 0xEE], 7, 2, 1);
+        let text = format!("{}\n{}", vectors[0].to_json(), vectors[0].to_json());
+        let error = verify(&[0x00, 0xEE], 7, 2, &text).unwrap_err();
+        assert!(matches!(error, VectorError::Crashed { line: 2, .. }), "{:?}", error);
+    }
+}
@@ -0,0 +1,561 @@
+// streams the screen to browsers over WebSocket and takes their keys, so a machine running headless
+// on a server can be watched and played from anywhere. examples/web/viewer.html is a viewer.
+//
+// every message is binary. the server sends
+//
+//   FRAME  [1, width u16, height u16, background rgb, foreground rgb, the screen at 1 bit per pixel]
+//   BEEP   [2, 1 while beeping or 0]
+//
+// a frame only when the screen or palette changed, a beep only when it starts or stops. the
+// client sends [key, 1 pressed or 0 released] for keys 0 to 15.
+//
+// each client gets a mailbox that holds the newest frame and beep, and a thread that sends what's
+// in it at most 60 times a second. a slow client just misses the frames that came in meanwhile,
+// nothing queues up behind it and the emulator never waits on a socket
+
+use crate::{Chip8, Palette, PackedScreen, NUM_KEYS, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+pub const FRAME: u8 = 1;
+pub const BEEP: u8 = 2;
+
+const FRAME_INTERVAL: Duration = Duration::from_nanos(1_000_000_000 / 60);
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+
+// RFC 6455, hashed with the client's key to prove the server speaks WebSocket
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+// clients only ever send keys, anything bigger is a broken or hostile client
+const MAX_CLIENT_PAYLOAD: usize = 125;
+
+pub fn frame_message(screen: &PackedScreen, palette: Palette) -> Vec<u8> {
+    let (background, foreground) = (palette.background, palette.foreground);
+    let mut message = vec![FRAME];
+
+    message.extend((SCREEN_WIDTH as u16).to_be_bytes());
+    message.extend((SCREEN_HEIGHT as u16).to_be_bytes());
+    message.extend([background.0, background.1, background.2, foreground.0, foreground.1, foreground.2]);
+    message.extend(screen.bytes());
+    message
+}
+
+pub fn beep_message(is_beeping: bool) -> Vec<u8> {
+    vec![BEEP, is_beeping as u8]
+}
+
+// a server's frame, never masked
+fn write_frame<W: Write>(out: &mut W, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut header = vec![0x80 | opcode];
+
+    match payload.len() {
+        len if len < 126 => header.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            header.push(126);
+            header.extend((len as u16).to_be_bytes());
+        },
+        len => {
+            header.push(127);
+            header.extend((len as u64).to_be_bytes());
+        },
+    }
+
+    out.write_all(&header)?;
+    out.write_all(payload)
+}
+
+struct Frame {
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+// a client's frame, unmasked
+fn read_frame<R: Read>(input: &mut R) -> io::Result<Frame> {
+    let mut header = [0; 2];
+    input.read_exact(&mut header)?;
+
+    let opcode = header[0] & 0x0F;
+    let is_masked = header[1] & 0x80 != 0;
+    let len = (header[1] & 0x7F) as usize;
+
+    if !is_masked || len > MAX_CLIENT_PAYLOAD {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a frame a viewer sends"));
+    }
+
+    let mut mask = [0; 4];
+    input.read_exact(&mut mask)?;
+
+    let mut payload = vec![0; len];
+    input.read_exact(&mut payload)?;
+
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+
+    Ok(Frame { opcode, payload })
+}
+
+fn accept_key(key: &str) -> String {
+    BASE64.encode(sha1(format!("{}{}", key.trim(), HANDSHAKE_GUID).as_bytes()))
+}
+
+// reads the HTTP upgrade request and answers it, the reader is left at the first frame
+fn handshake<R: BufRead, W: Write>(input: &mut R, out: &mut W) -> io::Result<()> {
+    let mut key = None;
+    let mut is_upgrade = false;
+
+    loop {
+        let mut line = String::new();
+
+        if input.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let line = line.trim_end();
+
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            match name.trim().to_ascii_lowercase().as_str() {
+                "sec-websocket-key" => key = Some(value.trim().to_string()),
+                "upgrade" => is_upgrade = value.trim().eq_ignore_ascii_case("websocket"),
+                _ => {},
+            }
+        }
+    }
+
+    match key.filter(|_| is_upgrade) {
+        Some(key) => write!(
+            out,
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            accept_key(&key)
+        ),
+        None => {
+            write!(out, "HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")?;
+            Err(io::Error::new(io::ErrorKind::InvalidData, "not a WebSocket upgrade"))
+        },
+    }
+}
+
+// only for the handshake, sha2 has no SHA-1
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend((data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (h, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (bytes, word) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+#[derive(Default)]
+struct Outbox {
+    frame: Option<Vec<u8>>,
+    beep: Option<bool>,
+    is_closed: bool,
+}
+
+// what's waiting for one client, the newest of each kind of message
+#[derive(Default)]
+struct Mailbox {
+    outbox: Mutex<Outbox>,
+    is_ready: Condvar,
+}
+
+impl Mailbox {
+    fn lock(&self) -> MutexGuard<'_, Outbox> {
+        self.outbox.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn post_frame(&self, message: Vec<u8>) {
+        // a frame the client hasn't been sent yet is just replaced
+        self.lock().frame = Some(message);
+        self.is_ready.notify_one();
+    }
+
+    fn post_beep(&self, is_beeping: bool) {
+        self.lock().beep = Some(is_beeping);
+        self.is_ready.notify_one();
+    }
+
+    fn close(&self) {
+        self.lock().is_closed = true;
+        self.is_ready.notify_one();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.lock().is_closed
+    }
+
+    // blocks until there's something to send, None once closed and empty
+    fn take(&self) -> Option<Vec<Vec<u8>>> {
+        let mut outbox = self.lock();
+
+        while outbox.frame.is_none() && outbox.beep.is_none() {
+            if outbox.is_closed {
+                return None;
+            }
+            outbox = self.is_ready.wait(outbox).unwrap_or_else(PoisonError::into_inner);
+        }
+
+        let beep = outbox.beep.take().map(beep_message);
+        Some(beep.into_iter().chain(outbox.frame.take()).collect())
+    }
+}
+
+// sends whatever is in the mailbox, no faster than 60 Hz, until it's closed or the sink fails
+fn pump<W: Write>(mailbox: &Mailbox, sink: &mut W) {
+    while let Some(messages) = mailbox.take() {
+        let sent = Instant::now();
+        let result = messages.iter().try_for_each(|message| write_frame(sink, OP_BINARY, message));
+
+        if result.and_then(|_| sink.flush()).is_err() {
+            mailbox.close();
+            return;
+        }
+
+        thread::sleep(FRAME_INTERVAL.saturating_sub(sent.elapsed()));
+    }
+
+    let _ = write_frame(sink, OP_CLOSE, &[]);
+}
+
+// what a client that connects now needs to catch up
+#[derive(Default)]
+struct Shared {
+    clients: Vec<Arc<Mailbox>>,
+    frame: Option<Vec<u8>>,
+    is_beeping: bool,
+}
+
+pub struct Server {
+    address: SocketAddr,
+    shared: Arc<Mutex<Shared>>,
+    keys: Receiver<(usize, bool)>,
+    last: Option<(PackedScreen, Palette)>,
+    is_closed: Arc<AtomicBool>,
+    acceptor: Option<JoinHandle<()>>,
+}
+
+impl Server {
+    pub fn bind(address: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        // polled, so dropping the server can stop it and free the port for the next one
+        listener.set_nonblocking(true)?;
+
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let (sender, keys) = mpsc::channel();
+        let is_closed = Arc::new(AtomicBool::new(false));
+        let acceptor = {
+            let (shared, is_closed) = (shared.clone(), is_closed.clone());
+
+            thread::spawn(move || {
+                while !is_closed.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, _)) if stream.set_nonblocking(false).is_ok() => {
+                            let (shared, sender) = (shared.clone(), sender.clone());
+                            thread::spawn(move || connection(stream, &shared, &sender));
+                        },
+                        Ok(_) => {},
+                        Err(_) => thread::sleep(ACCEPT_INTERVAL),
+                    }
+                }
+            })
+        };
+
+        Ok(Self { address, shared, keys, last: None, is_closed, acceptor: Some(acceptor) })
+    }
+
+    // the real port when bound to port 0
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    // presses the keys viewers sent since the last call
+    pub fn poll(&self, chip8: &mut Chip8) {
+        while let Ok((key, is_pressed)) = self.keys.try_recv() {
+            chip8.keypress(key, is_pressed);
+        }
+    }
+
+    // call after each frame, only changes go out
+    pub fn publish(&mut self, chip8: &Chip8, palette: Palette) {
+        let mut shared = lock(&self.shared);
+        shared.clients.retain(|client| !client.is_closed());
+
+        let screen = chip8.packed_display();
+        if self.last != Some((screen, palette)) {
+            let message = frame_message(&screen, palette);
+
+            for client in &shared.clients {
+                client.post_frame(message.clone());
+            }
+            shared.frame = Some(message);
+            self.last = Some((screen, palette));
+        }
+
+        let is_beeping = chip8.is_beeping();
+        if is_beeping != shared.is_beeping {
+            for client in &shared.clients {
+                client.post_beep(is_beeping);
+            }
+            shared.is_beeping = is_beeping;
+        }
+    }
+
+    pub fn client_count(&self) -> usize {
+        lock(&self.shared).clients.iter().filter(|client| !client.is_closed()).count()
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.is_closed.store(true, Ordering::Relaxed);
+
+        for client in &lock(&self.shared).clients {
+            client.close();
+        }
+
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+    }
+}
+
+fn lock(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+    shared.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn connection(stream: TcpStream, shared: &Mutex<Shared>, keys: &Sender<(usize, bool)>) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let mut reader = BufReader::new(stream);
+
+    if handshake(&mut reader, &mut writer).is_err() {
+        return;
+    }
+
+    let mailbox = Arc::new(Mailbox::default());
+    {
+        let mut shared = lock(shared);
+
+        if let Some(frame) = &shared.frame {
+            mailbox.post_frame(frame.clone());
+        }
+        if shared.is_beeping {
+            mailbox.post_beep(true);
+        }
+        shared.clients.push(mailbox.clone());
+    }
+
+    let sender = {
+        let mailbox = mailbox.clone();
+        thread::spawn(move || pump(&mailbox, &mut writer))
+    };
+
+    while let Ok(frame) = read_frame(&mut reader) {
+        let is_open = match (frame.opcode, frame.payload.as_slice()) {
+            (OP_CLOSE, _) => false,
+            (OP_BINARY, &[key, is_pressed]) if (key as usize) < NUM_KEYS => {
+                keys.send((key as usize, is_pressed != 0)).is_ok()
+            },
+            // browsers don't ping, and nothing else is expected from a viewer
+            _ => true,
+        };
+
+        if !is_open {
+            break;
+        }
+    }
+
+    mailbox.close();
+    let _ = sender.join();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    // unmasks what pump wrote
+    fn server_frames(mut bytes: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        let mut frames = Vec::new();
+
+        while !bytes.is_empty() {
+            let opcode = bytes[0] & 0x0F;
+            let (len, start) = match bytes[1] {
+                126 => (u16::from_be_bytes([bytes[2], bytes[3]]) as usize, 4),
+                len => (len as usize, 2),
+            };
+
+            frames.push((opcode, bytes[start..start + len].to_vec()));
+            bytes = &bytes[start + len..];
+        }
+
+        frames
+    }
+
+    fn masked(opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [0x12, 0x34, 0x56, 0x78];
+        let mut frame = vec![0x80 | opcode, 0x80 | payload.len() as u8];
+
+        frame.extend(mask);
+        frame.extend(payload.iter().enumerate().map(|(i, byte)| byte ^ mask[i % 4]));
+        frame
+    }
+
+    #[test]
+    fn hashes_sha1() {
+        let hex = |digest: [u8; 20]| digest.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+
+        assert_eq!(hex(sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(hex(sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    }
+
+    #[test]
+    fn accepts_the_rfc_example_key() {
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn answers_the_handshake() {
+        let request = "GET / HTTP/1.1\r\nHost: x\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n";
+        let mut input = Cursor::new([request.as_bytes(), &masked(OP_BINARY, &[5, 1])].concat());
+        let mut out = Vec::new();
+
+        handshake(&mut input, &mut out).unwrap();
+
+        let response = String::from_utf8(out).unwrap();
+        assert!(response.starts_with("HTTP/1.1 101"), "{}", response);
+        assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+        assert_eq!(read_frame(&mut input).unwrap().payload, [5, 1]);
+
+        let mut out = Vec::new();
+        assert!(handshake(&mut Cursor::new("GET / HTTP/1.1\r\n\r\n"), &mut out).is_err());
+        assert!(out.starts_with(b"HTTP/1.1 400"));
+    }
+
+    #[test]
+    fn encodes_frames() {
+        let mut chip8 = Chip8::with_seed(1);
+        chip8.load(&[0xD0, 0x05]).unwrap();
+        chip8.tick().unwrap();
+
+        let message = frame_message(&chip8.packed_display(), Palette::default());
+        assert_eq!(message.len(), 11 + SCREEN_WIDTH * SCREEN_HEIGHT / 8);
+        assert_eq!(message[..5], [FRAME, 0, 64, 0, 32]);
+        assert_eq!(message[5..11], [0, 0, 0, 50, 169, 86]);
+        // the top of the "0" glyph
+        assert_eq!(message[11], 0xF0);
+
+        let mut out = Vec::new();
+        write_frame(&mut out, OP_BINARY, &message).unwrap();
+        assert_eq!(out[..4], [0x82, 126, 1, 11]);
+        assert_eq!(server_frames(&out), [(OP_BINARY, message)]);
+    }
+
+    #[test]
+    fn rejects_unmasked_and_big_client_frames() {
+        assert!(read_frame(&mut Cursor::new([0x82, 0x02, 5, 1])).is_err());
+
+        let mut big = masked(OP_BINARY, &[0; 125]);
+        big[1] = 0x80 | 126;
+        assert!(read_frame(&mut Cursor::new(big)).is_err());
+    }
+
+    #[test]
+    fn a_slow_client_only_gets_the_newest_frame() {
+        let mailbox = Mailbox::default();
+
+        // nothing is sent while the client is busy, so these pile up
+        mailbox.post_frame(vec![FRAME, 1]);
+        mailbox.post_frame(vec![FRAME, 2]);
+        mailbox.post_beep(true);
+        mailbox.post_frame(vec![FRAME, 3]);
+
+        let mut sink = Vec::new();
+        mailbox.close();
+        pump(&mailbox, &mut sink);
+
+        assert_eq!(
+            server_frames(&sink),
+            [(OP_BINARY, beep_message(true)), (OP_BINARY, vec![FRAME, 3]), (OP_CLOSE, vec![])]
+        );
+    }
+
+    #[test]
+    fn pumps_until_the_sink_fails() {
+        struct Broken;
+
+        impl Write for Broken {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mailbox = Mailbox::default();
+        mailbox.post_frame(vec![FRAME]);
+        pump(&mailbox, &mut Broken);
+
+        assert!(mailbox.is_closed());
+    }
+}
@@ -0,0 +1,213 @@
+// the core for the browser through wasm-bindgen, examples/web is a whole page built on it. build
+// with `wasm-pack build --target web -- --no-default-features --features wasm`, then from JS
+//
+//   const emulator = new Emulator(romBytes, 42n);
+//   emulator.runFrame();
+//   context.putImageData(new ImageData(emulator.displayRgba(), 64, 32), 0, 0);
+//
+// the RNG is the core's own ChaCha, seeded from the constructor, so nothing here asks the browser
+// for randomness unless the seed is left out
+
+use crate::{Chip8, Palette, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use js_sys::Uint8ClampedArray;
+use wasm_bindgen::prelude::*;
+
+const TICKS_PER_FRAME: usize = 10;
+
+#[wasm_bindgen]
+pub struct Emulator {
+    chip8: Chip8,
+    // reused by every displayRgba
+    rgba: Vec<u8>,
+    palette: Palette,
+    ticks_per_frame: usize,
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    // without a seed RND is seeded from Math.random
+    #[wasm_bindgen(constructor)]
+    pub fn new(rom: &[u8], seed: Option<u64>) -> Result<Emulator, String> {
+        let seed = seed.unwrap_or_else(|| (js_sys::Math::random() * u64::MAX as f64) as u64);
+        let mut chip8 = Chip8::with_seed(seed);
+        chip8.load(rom).map_err(|error| format!("Unable to load the ROM: {}", error))?;
+
+        Ok(Self {
+            chip8,
+            rgba: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT * 4],
+            palette: Palette::default(),
+            ticks_per_frame: TICKS_PER_FRAME,
+        })
+    }
+
+    // an instruction that can't run throws, and stays where it is
+    pub fn tick(&mut self) -> Result<(), String> {
+        self.chip8.tick().map(|_| ()).map_err(|error| error.to_string())
+    }
+
+    // one 60 Hz frame, throwing like tick
+    #[wasm_bindgen(js_name = runFrame)]
+    pub fn run_frame(&mut self) -> Result<(), String> {
+        self.chip8.run_frame(self.ticks_per_frame);
+
+        match self.chip8.take_fault() {
+            Some(error) => Err(error.to_string()),
+            None => Ok(()),
+        }
+    }
+
+    #[wasm_bindgen(js_name = setTicksPerFrame)]
+    pub fn set_ticks_per_frame(&mut self, ticks_per_frame: usize) {
+        self.ticks_per_frame = ticks_per_frame;
+    }
+
+    // keys past F are ignored
+    #[wasm_bindgen(js_name = keyDown)]
+    pub fn key_down(&mut self, key: u8) {
+        if key < 16 {
+            self.chip8.keypress(key as usize, true);
+        }
+    }
+
+    #[wasm_bindgen(js_name = keyUp)]
+    pub fn key_up(&mut self, key: u8) {
+        if key < 16 {
+            self.chip8.keypress(key as usize, false);
+        }
+    }
+
+    // a view straight into wasm memory, good until the next call into the emulator. copy it to
+    // keep it
+    #[wasm_bindgen(js_name = displayRgba)]
+    pub fn display_rgba(&mut self) -> Uint8ClampedArray {
+        self.render();
+
+        unsafe { Uint8ClampedArray::view(&self.rgba) }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> usize {
+        SCREEN_WIDTH
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> usize {
+        SCREEN_HEIGHT
+    }
+
+    // "#rrggbb,#rrggbb" or a built-in name, like --palette
+    #[wasm_bindgen(js_name = setPalette)]
+    pub fn set_palette(&mut self, palette: &str) -> Result<(), String> {
+        self.palette = palette.parse()?;
+
+        Ok(())
+    }
+
+    // the beep for one audio callback, in consecutive buffers. the Float32Array is written in place
+    #[wasm_bindgen(js_name = fillAudio)]
+    pub fn fill_audio(&mut self, out: &mut [f32], sample_rate: u32) {
+        self.chip8.fill_audio(out, sample_rate);
+    }
+
+    #[wasm_bindgen(js_name = isBeeping)]
+    pub fn is_beeping(&self) -> bool {
+        self.chip8.is_beeping()
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.chip8.seed()
+    }
+
+    #[wasm_bindgen(js_name = setSeed)]
+    pub fn set_seed(&mut self, seed: u64) {
+        self.chip8.set_seed(seed);
+    }
+
+    #[wasm_bindgen(js_name = saveState)]
+    pub fn save_state(&self) -> Vec<u8> {
+        self.chip8.save_state()
+    }
+
+    #[wasm_bindgen(js_name = loadState)]
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), String> {
+        self.chip8.load_state(state).map_err(|err| err.to_string())
+    }
+}
+
+impl Emulator {
+    fn render(&mut self) -> &[u8] {
+        self.chip8.render_rgba(self.palette, &mut self.rgba);
+
+        &self.rgba
+    }
+}
+
+// the JS side is covered by tests/wasm.rs under wasm-pack, these run natively
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::MAX_ROM_SIZE;
+
+    // draw "0" at (0, 0) then loop
+    const ROM: [u8; 4] = [0xD0, 0x05, 0x12, 0x02];
+
+    #[test]
+    fn renders_into_the_same_buffer() {
+        let mut emulator = Emulator::new(&ROM, Some(1)).unwrap();
+        let before = emulator.render().as_ptr();
+
+        emulator.run_frame().unwrap();
+        let rgba = emulator.render();
+
+        assert_eq!(rgba.len(), SCREEN_WIDTH * SCREEN_HEIGHT * 4);
+        assert_eq!(rgba.as_ptr(), before);
+        assert_eq!(rgba[..4], [50, 169, 86, 0xFF]);
+    }
+
+    #[test]
+    fn fills_audio_while_beeping() {
+        // ST = V0 = 30, then loop
+        let mut emulator = Emulator::new(&[0x60, 0x1E, 0xF0, 0x18, 0x12, 0x04], Some(1)).unwrap();
+        let mut out = [0.0; 512];
+
+        emulator.fill_audio(&mut out, 44100);
+        assert!(out.iter().all(|&sample| sample == 0.0));
+
+        emulator.run_frame().unwrap();
+        assert!(emulator.is_beeping());
+        emulator.fill_audio(&mut out, 44100);
+        assert!(out.iter().any(|&sample| sample != 0.0));
+    }
+
+    #[test]
+    fn rejects_big_roms() {
+        assert!(Emulator::new(&vec![0; MAX_ROM_SIZE + 1], Some(1)).is_err());
+    }
+
+    #[test]
+    fn ignores_keys_past_f() {
+        let mut emulator = Emulator::new(&ROM, Some(1)).unwrap();
+
+        emulator.key_down(0xF);
+        emulator.key_down(0x10);
+        assert!(emulator.chip8.keys()[0xF]);
+        emulator.key_up(0xF);
+        assert!(!emulator.chip8.keys()[0xF]);
+    }
+
+    #[test]
+    fn round_trips_state_and_seed() {
+        let mut emulator = Emulator::new(&ROM, Some(7)).unwrap();
+        assert_eq!(emulator.seed(), 7);
+
+        let state = emulator.save_state();
+        emulator.run_frame().unwrap();
+        emulator.load_state(&state).unwrap();
+
+        assert!(!emulator.chip8.get_display()[0]);
+        assert!(emulator.load_state(&[1, 2, 3]).is_err());
+        assert!(emulator.set_palette("nope").is_err());
+    }
+}
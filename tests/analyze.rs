@@ -0,0 +1,43 @@
+// lints.ch8 has one of each pitfall the lints look for:
+//
+//   200 6F05  LD VF, 0x05       VF as a value...
+//   202 8014  ADD V0, V1        ...then overwritten with the carry
+//   204 A200  LD I, 0x200
+//   206 F033  LD B, V0          the digits go over the first instructions
+//   208 D010  DRW V0, V1, 0
+//   20A 3000  SE V0, 0x00       skips into the middle of...
+//   20C F000  0300              ...a long I load (XO-CHIP)
+//   210 2215  CALL 0x215        an odd address
+//   212 1212  JMP 0x212
+
+use chip8_emu::analyze::{self, Lint};
+
+use std::fs;
+use std::path::Path;
+
+fn fixture(name: &str) -> Vec<u8> {
+    fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)).unwrap()
+}
+
+#[test]
+fn finds_each_lint_once() {
+    let rom = fixture("lints.ch8");
+    let findings = analyze::analyze(&rom).lints(&rom);
+    let found: Vec<(u16, Lint)> = findings.iter().map(|finding| (finding.address, finding.lint)).collect();
+
+    assert_eq!(
+        found,
+        [
+            (0x200, Lint::VfClobbered),
+            (0x206, Lint::BcdIntoCode),
+            (0x208, Lint::ZeroHeightSprite),
+            (0x20A, Lint::SkipIntoLongLoad),
+            (0x210, Lint::OddJump),
+        ]
+    );
+    assert_eq!(
+        findings[0].to_string(),
+        "200 vf_clobbered: VF is set here but ADD V0, V1 at 202 overwrites it with a flag"
+    );
+    assert_eq!(findings[4].to_string(), "210 odd_jump: 215 is odd, so every instruction from there is misaligned");
+}
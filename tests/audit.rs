@@ -0,0 +1,102 @@
+// the features that reach into a running machine shouldn't change how it runs: each of these plays
+// random-digit.ch8 next to a plain run of it and has the audit compare them frame by frame
+
+use chip8_emu::audit::{self, Agreement};
+use chip8_emu::headless::{parse_script, Options, Script, DEFAULT_SEED};
+use chip8_emu::{Chip8, Quirk};
+
+use std::fs;
+use std::path::Path;
+
+const FRAMES: usize = 60;
+const TICKS_PER_FRAME: usize = 10;
+
+fn fixture(name: &str) -> Vec<u8> {
+    fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)).unwrap()
+}
+
+fn script() -> Script {
+    parse_script(&String::from_utf8(fixture("random-digit.keys")).unwrap()).unwrap()
+}
+
+fn machine() -> Chip8 {
+    let mut chip8 = Chip8::with_seed(DEFAULT_SEED);
+    chip8.load(&fixture("random-digit.ch8")).unwrap();
+
+    chip8
+}
+
+fn play(chip8: &mut Chip8, frame: usize, script: &Script) {
+    for key in script.keys.iter().filter(|key| key.frame == frame) {
+        chip8.keypress(key.key, key.is_pressed);
+    }
+
+    chip8.run_frame(TICKS_PER_FRAME);
+}
+
+// `meddle` runs on the second machine before each of its frames
+fn audit_against_plain(mut meddle: impl FnMut(&mut Chip8, usize)) {
+    let script = script();
+    let (mut plain, mut meddled) = (machine(), machine());
+    let mut is_meddled = false;
+
+    let agreement = audit::compare(FRAMES, &mut plain, &mut meddled, |chip8, frame| {
+        // compare plays the first machine's frame first
+        if is_meddled {
+            meddle(chip8, frame);
+        }
+        is_meddled = !is_meddled;
+
+        play(chip8, frame, &script);
+    });
+
+    assert_eq!(agreement, Ok(Agreement { frames: FRAMES, crash: None }));
+}
+
+#[test]
+fn the_headless_run_is_deterministic() {
+    let options = Options { frames: FRAMES, ticks_per_frame: TICKS_PER_FRAME, seed: DEFAULT_SEED, script: script() };
+
+    assert_eq!(audit::audit(&fixture("random-digit.ch8"), &options), Ok(Agreement { frames: FRAMES, crash: None }));
+}
+
+#[test]
+fn recording_rewind_changes_nothing() {
+    audit_against_plain(|chip8, frame| {
+        if frame == 0 {
+            chip8.set_rewind_capacity(16);
+        }
+    });
+}
+
+#[test]
+fn saving_and_loading_mid_run_changes_nothing() {
+    audit_against_plain(|chip8, _| {
+        let state = chip8.save_state();
+        chip8.tick().unwrap();
+        chip8.load_state(&state).unwrap();
+    });
+}
+
+#[test]
+fn toggling_a_quirk_back_changes_nothing() {
+    audit_against_plain(|chip8, frame| {
+        if frame % 7 == 0 {
+            chip8.set_quirk(Quirk::ShiftUsesVy, true);
+            chip8.set_quirk(Quirk::ShiftUsesVy, false);
+        }
+    });
+}
+
+#[test]
+fn stepping_back_and_forward_changes_nothing() {
+    audit_against_plain(|chip8, frame| {
+        // a few instructions into the frame, and back to its start
+        for _ in 0..frame % 4 {
+            chip8.tick().unwrap();
+        }
+        for _ in 0..frame % 4 {
+            assert!(chip8.step_back());
+        }
+    });
+}
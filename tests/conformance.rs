@@ -0,0 +1,31 @@
+use chip8_emu::conformance::{self, Goldens, Outcome};
+
+use std::env;
+use std::path::PathBuf;
+
+// the logos don't draw marks and nobody's blessed their screens yet, take them out of here when
+// goldens.txt has them
+const UNBLESSED_LOGOS: &[&str] = &["chip8-logo", "ibm-logo"];
+
+// the suite is GPL licensed so it isn't vendored, point CHIP8_TEST_SUITE_DIR at its bin/ directory
+#[test]
+fn timendus_suite() {
+    let dir = match env::var_os("CHIP8_TEST_SUITE_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            eprintln!("CHIP8_TEST_SUITE_DIR not set, skipping conformance tests");
+            return;
+        }
+    };
+
+    let results = conformance::run_suite(&dir, &Goldens::builtin());
+    println!("{}", conformance::format_table(&results));
+
+    assert!(results.iter().all(|result| result.outcome != Outcome::Missing));
+    // a test without a golden didn't check anything, bless one
+    assert!(results
+        .iter()
+        .filter(|result| !UNBLESSED_LOGOS.contains(&result.test))
+        .all(|result| !matches!(result.outcome, Outcome::NoGolden { .. })));
+    assert!(!results.iter().any(|result| result.is_failure()));
+}
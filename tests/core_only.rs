@@ -0,0 +1,36 @@
+// `cargo add chip8-emu --no-default-features` is meant to be the core alone. this builds it, cdylib
+// and all, with nothing on the library path, so an SDL or other C dependency sneaking back in fails
+// here instead of on a user's machine
+
+use std::path::Path;
+use std::process::Command;
+
+fn cargo(args: &[&str]) -> String {
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("core-only");
+    let output = Command::new(env!("CARGO"))
+        .args(args)
+        .args(["--no-default-features", "--manifest-path", concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml")])
+        .env("CARGO_TARGET_DIR", target_dir)
+        // the stub or real SDL the tests were linked with
+        .env_remove("RUSTFLAGS")
+        .env_remove("LIBRARY_PATH")
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "cargo {} failed:\n{}", args.join(" "), stderr);
+
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn builds_without_the_frontend() {
+    let tree = cargo(&["tree", "--edges", "normal,build", "--prefix", "none", "--offline"]);
+    let crates: Vec<&str> = tree.lines().filter_map(|line| line.split_whitespace().next()).collect();
+
+    for frontend in ["sdl2", "sdl2-sys", "clap", "toml", "chrono"] {
+        assert!(!crates.contains(&frontend), "{} is in the core's dependencies:\n{}", frontend, tree);
+    }
+
+    cargo(&["build", "--lib", "--offline"]);
+}
@@ -0,0 +1,57 @@
+use chip8_emu::env::{Env, EnvConfig, KeyBitmask, Observation, StepInfo};
+
+use std::fs;
+use std::path::Path;
+
+const STEPS: usize = 40;
+const FRAMES_PER_STEP: u32 = 3;
+
+fn rom() -> Vec<u8> {
+    fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/random-digit.ch8")).unwrap()
+}
+
+// answers the key wait with a key picked from how many pixels the random digit lit, let go the step
+// after so the wait finishes
+fn policy(observation: &Observation, info: &StepInfo, last: KeyBitmask) -> KeyBitmask {
+    if !info.is_waiting_for_key || last != 0 {
+        return 0;
+    }
+
+    let lit = observation.screen.unpack().iter().filter(|&&pixel| pixel).count();
+    1 << (lit % 16)
+}
+
+fn episode(seed: u64) -> Vec<(Observation, StepInfo)> {
+    let mut env = Env::new(&rom(), EnvConfig { seed, ram_window: Some(0x200..0x220), ..EnvConfig::default() }).unwrap();
+    let mut observation = env.reset();
+    let mut info = StepInfo::default();
+    let mut keys = 0;
+    let mut steps = Vec::new();
+
+    for _ in 0..STEPS {
+        keys = policy(&observation, &info, keys);
+        (observation, info) = env.step(keys, FRAMES_PER_STEP);
+        steps.push((observation.clone(), info));
+    }
+
+    steps
+}
+
+#[test]
+fn replays_an_episode_the_same() {
+    let first = episode(42);
+    assert_eq!(first, episode(42));
+
+    // it waited, got its key and drew it
+    assert!(first.iter().any(|(_, info)| info.is_waiting_for_key));
+    assert!(!first.last().unwrap().1.is_waiting_for_key);
+    assert_eq!(first.iter().map(|(_, info)| info.draws).sum::<u64>(), 2);
+    assert!(first.iter().all(|(observation, info)| observation.ram.len() == 0x20 && !info.is_crashed));
+}
+
+#[test]
+fn another_seed_plays_another_episode() {
+    let screens = |seed| episode(seed).into_iter().map(|(observation, _)| observation.screen).collect::<Vec<_>>();
+
+    assert_ne!(screens(42), screens(7));
+}
@@ -0,0 +1,117 @@
+use chip8_emu::harness::{Scenario, Stat};
+use chip8_emu::headless::{self, Options, Script};
+use chip8_emu::report::{self, Expectations, HaltReason};
+
+use std::fs;
+use std::path::Path;
+
+const FRAMES: usize = 120;
+const SEED: u64 = 42;
+
+fn fixture(name: &str) -> String {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name).display().to_string()
+}
+
+// draws a random digit, waits for a key and draws that too. regenerate the .pbm with
+// chip8-emu headless tests/fixtures/random-digit.ch8 --frames 120 --seed 42
+//   --input-script tests/fixtures/random-digit.keys --dump-screen tests/fixtures/random-digit.pbm
+#[test]
+fn matches_the_fixture_screen() {
+    let rom = fs::read(fixture("random-digit.ch8")).unwrap();
+    let script = fs::read_to_string(fixture("random-digit.keys")).unwrap();
+    let scenario = Scenario::new(&rom).seed(SEED).frames(FRAMES).input_script(&script);
+
+    let report =
+        scenario.clone().expect_display(fixture("random-digit.pbm")).expect_halt(HaltReason::SelfJump).run().unwrap();
+
+    // and again, the same
+    scenario
+        .expect_screen_hash(&report.screen_hash)
+        .expect_stat(Stat::Instructions, report.instructions)
+        .run()
+        .unwrap();
+}
+
+#[test]
+fn waits_without_the_script() {
+    let rom = fs::read(fixture("random-digit.ch8")).unwrap();
+
+    // still on FX0A
+    Scenario::new(&rom)
+        .seed(SEED)
+        .frames(FRAMES)
+        .expect_halt(HaltReason::KeyWait)
+        .expect_stat(Stat::ProgramCounter, 0x20A)
+        .run()
+        .unwrap();
+}
+
+// the fields a CI script reads, in schema version 1
+fn json_field<'a>(json: &'a str, name: &str) -> &'a str {
+    let start = json.find(&format!("\"{}\": ", name)).unwrap_or_else(|| panic!("no {} in {}", name, json)) + name.len() + 4;
+    let rest = &json[start..];
+
+    &rest[..rest.find([',', '\n']).unwrap()]
+}
+
+#[test]
+fn reports_a_passing_run() {
+    let rom = fs::read(fixture("random-digit.ch8")).unwrap();
+    let script = headless::parse_script(&fs::read_to_string(fixture("random-digit.keys")).unwrap()).unwrap();
+    let options = Options { frames: FRAMES, ticks_per_frame: 10, seed: SEED, script };
+    let (chip8, first) = report::run(&rom, &options, &Expectations::default());
+
+    let expected = Expectations { screen_hash: Some(first.screen_hash.clone()), halt: Some(first.halt) };
+    let (_, report) = report::run(&rom, &options, &expected);
+    let json = report.to_json();
+
+    assert_eq!(report.exit_code(), report::EXIT_PASS);
+    assert_eq!(json_field(&json, "version"), report::REPORT_VERSION.to_string());
+    assert_eq!(json_field(&json, "verdict"), "\"pass\"");
+    assert_eq!(json_field(&json, "frames"), FRAMES.to_string());
+    assert_eq!(json_field(&json, "screen_hash"), format!("\"{}\"", chip8_emu::conformance::screen_hash(chip8.get_display())));
+    assert_eq!(json_field(&json, "instructions"), chip8.instruction_count().to_string());
+    assert!(json.contains("\"errors\": []") && json.contains("\"failures\": []"), "{}", json);
+}
+
+#[test]
+fn reports_failures_and_crashes() {
+    let rom = fs::read(fixture("random-digit.ch8")).unwrap();
+    let options = Options { frames: FRAMES, ticks_per_frame: 10, seed: SEED, script: Script::default() };
+
+    // without the script it never gets past FX0A
+    let expected = Expectations { halt: Some(HaltReason::SelfJump), ..Expectations::default() };
+    let (_, report) = report::run(&rom, &options, &expected);
+    let json = report.to_json();
+
+    assert_eq!(report.halt, HaltReason::KeyWait);
+    assert_eq!(report.exit_code(), report::EXIT_FAIL);
+    assert_eq!(json_field(&json, "verdict"), "\"fail\"");
+    assert_eq!(json_field(&json, "halt"), "\"key_wait\"");
+    assert!(json.contains("\"failures\": [\"expected the run to end with self_jump, it ended with key_wait\"]"), "{}", json);
+
+    // return with an empty stack
+    let (_, report) = report::run(&[0x00, 0xEE], &options, &Expectations::default());
+    let json = report.to_json();
+
+    assert_eq!(report.exit_code(), report::EXIT_FAIL);
+    assert_eq!(json_field(&json, "halt"), "\"crash\"");
+    assert_eq!(json_field(&json, "frames"), "0");
+    assert!(json.contains("\"errors\": [{ \"pc\": 512, \"opcode\": \"00EE\", \"message\": "), "{}", json);
+}
+
+#[test]
+fn the_seed_decides_the_run() {
+    let rom = fs::read(fixture("random-digit.ch8")).unwrap();
+    let run = |seed| {
+        let options = Options { frames: FRAMES, ticks_per_frame: 10, seed, script: Script::default() };
+        report::run(&rom, &options, &Expectations::default()).1
+    };
+
+    let first = run(SEED);
+    assert_eq!(run(SEED).screen_hash, first.screen_hash);
+    assert_eq!(json_field(&first.to_json(), "seed"), SEED.to_string());
+
+    // a different random digit
+    assert_ne!(run(SEED + 1).screen_hash, first.screen_hash);
+}
@@ -0,0 +1,74 @@
+use chip8_emu::netplay::{Lockstep, NetplayError};
+use chip8_emu::Chip8;
+
+use std::fs;
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::thread;
+
+const FRAMES: u64 = 120;
+const TICKS_PER_FRAME: usize = 10;
+
+fn rom() -> Vec<u8> {
+    fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/random-digit.ch8")).unwrap()
+}
+
+// plays FRAMES frames, holding `key` from frame 30 to 40. `drift_at` resets on one side only
+fn play(lockstep: &mut Lockstep<TcpStream>, key: Option<usize>, drift_at: Option<u64>) -> Result<Chip8, NetplayError> {
+    let mut chip8 = Chip8::new();
+    chip8.load(&rom()).unwrap();
+    chip8.set_seed(lockstep.seed());
+    lockstep.set_hash_interval(20);
+
+    for frame in 0..FRAMES {
+        let mut keys = [false; 16];
+        if let Some(key) = key.filter(|_| (30..40).contains(&frame)) {
+            keys[key] = true;
+        }
+
+        lockstep.advance(&mut chip8, &keys, TICKS_PER_FRAME)?;
+
+        if drift_at == Some(frame) {
+            chip8.soft_reset();
+        }
+    }
+
+    Ok(chip8)
+}
+
+fn connect(host_drift: Option<u64>) -> (Result<Chip8, NetplayError>, Result<Chip8, NetplayError>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let host = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut lockstep = Lockstep::host(stream, &rom(), 42).unwrap();
+        play(&mut lockstep, None, host_drift)
+    });
+
+    let mut lockstep = Lockstep::join(TcpStream::connect(address).unwrap(), &rom()).unwrap();
+    assert_eq!(lockstep.seed(), 42);
+    let guest = play(&mut lockstep, Some(0xA), None);
+    drop(lockstep);
+
+    (host.join().unwrap(), guest)
+}
+
+#[test]
+fn both_sides_see_the_same_game() {
+    let (host, guest) = connect(None);
+    let (host, guest) = (host.unwrap(), guest.unwrap());
+
+    // the guest's key press reached the host, and the seeded random digit matches
+    assert_eq!(host.registers()[3], 0xA);
+    assert_eq!(host.get_display(), guest.get_display());
+    assert_eq!(host.save_state(), guest.save_state());
+}
+
+#[test]
+fn reports_desyncs_over_tcp() {
+    let (host, guest) = connect(Some(50));
+
+    assert!(matches!(host, Err(NetplayError::Desync { frame: 60 })), "{:?}", host.err());
+    assert!(matches!(guest, Err(NetplayError::Desync { frame: 60 }) | Err(NetplayError::Io(_))), "{:?}", guest.err());
+}
@@ -0,0 +1,66 @@
+# pytest tests for the installed module, see pyproject.toml. the same checks run inside
+# `cargo test --features python` through an embedded interpreter
+
+from pathlib import Path
+
+import pytest
+
+import chip8_emu
+
+# draws a random digit and waits for a key
+ROM = (Path(__file__).parent.parent / "fixtures" / "random-digit.ch8").read_bytes()
+
+
+def running(seed=42, frames=5, **quirks):
+    chip8 = chip8_emu.Chip8(seed=seed, **quirks)
+    chip8.load(ROM)
+    for _ in range(frames):
+        chip8.run_frame()
+    return chip8
+
+
+def test_runs_the_bundled_rom():
+    chip8 = running()
+
+    assert chip8.display.shape == (32, 64)
+    assert any(chip8.display.tobytes())
+    assert len(chip8.registers) == 16
+    assert chip8.pc >= 0x200
+    assert chip8.seed == 42
+
+
+def test_the_display_works_with_numpy():
+    numpy = pytest.importorskip("numpy")
+    screen = numpy.asarray(running().display)
+
+    assert screen.shape == (32, 64)
+    assert set(numpy.unique(screen)) <= {0, 1}
+
+
+def test_the_seed_decides_the_run():
+    assert running(seed=1).display.tobytes() == running(seed=1).display.tobytes()
+
+
+def test_round_trips_state():
+    chip8 = chip8_emu.Chip8(seed=1, shift_uses_vy=True)
+    chip8.load(ROM)
+    state = chip8.save_state()
+
+    chip8.run_frame()
+    chip8.keypress(15, True)
+    chip8.load_state(state)
+
+    assert not any(chip8.display.tobytes())
+
+
+def test_raises_chip8_errors():
+    chip8 = chip8_emu.Chip8()
+
+    with pytest.raises(chip8_emu.Chip8Error):
+        chip8.load(bytes(4000))
+    with pytest.raises(chip8_emu.Chip8Error):
+        chip8.keypress(16, True)
+    with pytest.raises(chip8_emu.Chip8Error):
+        chip8.load_state(b"nope")
+    with pytest.raises(chip8_emu.Chip8Error):
+        chip8_emu.Chip8(shift=True)
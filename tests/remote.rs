@@ -0,0 +1,41 @@
+#![cfg(feature = "remote")]
+
+use chip8_emu::remote::Server;
+use chip8_emu::Chip8;
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::thread;
+
+// draw "0" at (0, 0) then loop, as base64
+const ROM: &str = "0AUSAg==";
+
+#[test]
+fn answers_over_tcp_until_the_client_leaves() {
+    let server = Server::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr();
+
+    let client = thread::spawn(move || {
+        let mut stream = TcpStream::connect(address).unwrap();
+        let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+        let mut ask = |request: &str| {
+            writeln!(stream, "{}", request).unwrap();
+            lines.next().unwrap().unwrap()
+        };
+
+        vec![
+            ask(&format!(r#"{{"id": 1, "cmd": "load_rom", "rom": "{}"}}"#, ROM)),
+            ask(r#"{"id": 2, "cmd": "run_frames", "frames": 2}"#),
+            ask(r#"{"id": 3, "cmd": "key", "key": 20, "pressed": true}"#),
+        ]
+    });
+
+    let mut chip8 = Chip8::with_seed(1);
+    server.serve(&mut chip8);
+    let responses = client.join().unwrap();
+
+    assert_eq!(responses[0], r#"{"id":1,"ok":true,"result":{}}"#);
+    assert_eq!(responses[1], r#"{"id":2,"ok":true,"result":{"frames":2,"pc":514,"paused":false,"breakpoint":null}}"#);
+    assert!(responses[2].starts_with(r#"{"id":3,"ok":false,"error":"#), "{}", responses[2]);
+    assert!(chip8.get_display()[0]);
+}
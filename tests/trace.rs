@@ -0,0 +1,107 @@
+// traces written to real files, the way `chip8-emu trace` writes them
+
+use chip8_emu::headless::{parse_script, Options, Script, DEFAULT_SEED};
+use chip8_emu::trace_log::{self, TraceFilter, TraceFormat, TraceLog, DEFAULT_MAX_BYTES};
+
+use std::env;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+// V0 += 1, jump back
+const COUNTER: [u8; 4] = [0x70, 0x01, 0x12, 0x00];
+
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("chip8-emu-trace-{}-{}", name, std::process::id()))
+}
+
+fn options(frames: usize) -> Options {
+    Options { frames, ticks_per_frame: 10, seed: DEFAULT_SEED, script: Script::default() }
+}
+
+// the file's lines, and whether the log was cut short
+fn trace(
+    name: &str,
+    rom: &[u8],
+    options: &Options,
+    format: TraceFormat,
+    filters: &[&str],
+    max_bytes: u64,
+) -> (Vec<String>, bool) {
+    let path = temp_path(name);
+    let filters = filters.iter().map(|filter| TraceFilter::parse(filter).unwrap()).collect();
+    let mut log = TraceLog::new(BufWriter::new(File::create(&path).unwrap()), format, filters, max_bytes);
+
+    trace_log::run(rom, options, &mut log).unwrap();
+    let is_truncated = log.is_truncated();
+    drop(log);
+
+    let text = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    (text.lines().map(str::to_string).collect(), is_truncated)
+}
+
+#[test]
+fn writes_a_line_per_instruction() {
+    let (lines, is_truncated) = trace("text", &COUNTER, &options(5), TraceFormat::Text, &[], DEFAULT_MAX_BYTES);
+
+    assert!(!is_truncated);
+    assert_eq!(lines.len(), 50);
+    assert_eq!(lines[0], "0 200 7001 ADD V0, 0x01");
+    assert_eq!(lines[1], "0 202 1200 JMP 0x200");
+    assert_eq!(lines[49], "4 202 1200 JMP 0x200");
+}
+
+#[test]
+fn writes_json_lines() {
+    let (lines, _) = trace("json", &COUNTER, &options(3), TraceFormat::Json, &[], DEFAULT_MAX_BYTES);
+    assert_eq!(lines.len(), 30);
+
+    for (index, line) in lines.iter().enumerate() {
+        let value: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert_eq!(value["frame"], index / 10);
+        assert_eq!(value["pc"], if index.is_multiple_of(2) { 0x200 } else { 0x202 });
+    }
+
+    let last: serde_json::Value = serde_json::from_str(&lines[29]).unwrap();
+    assert_eq!((last["opcode"].as_u64(), last["asm"].as_str()), (Some(0x1200), Some("JMP 0x200")));
+}
+
+#[test]
+fn keeps_only_what_the_filters_match() {
+    let filters = ["op=7XNN", "frames=1-2"];
+    let (lines, _) = trace("filtered", &COUNTER, &options(5), TraceFormat::Text, &filters, DEFAULT_MAX_BYTES);
+
+    assert_eq!(lines.len(), 10);
+    assert!(lines.iter().all(|line| line.ends_with("200 7001 ADD V0, 0x01")));
+    assert!(lines[0].starts_with("1 ") && lines[9].starts_with("2 "));
+}
+
+#[test]
+fn a_tiny_cap_truncates_with_a_marker() {
+    // "0 200 7001 ADD V0, 0x01\n" is 24 bytes, "0 202 1200 JMP 0x200\n" 21
+    let (lines, is_truncated) = trace("capped", &COUNTER, &options(1000), TraceFormat::Text, &[], 100);
+
+    assert!(is_truncated);
+    assert_eq!(lines.len(), 5);
+    assert_eq!(lines[4], "# truncated after 4 lines, 90 bytes");
+
+    let (lines, is_truncated) = trace("capped-json", &COUNTER, &options(1000), TraceFormat::Json, &[], 10);
+    assert!(is_truncated);
+    assert_eq!(lines, ["{\"truncated\":true,\"lines\":0,\"bytes\":0}"]);
+}
+
+#[test]
+fn follows_the_seed_and_input_script() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let rom = fs::read(fixtures.join("random-digit.ch8")).unwrap();
+    let script = parse_script(&fs::read_to_string(fixtures.join("random-digit.keys")).unwrap()).unwrap();
+    let options = Options { frames: 120, ticks_per_frame: 10, seed: 42, script };
+
+    let (first, _) = trace("seeded-1", &rom, &options, TraceFormat::Text, &[], DEFAULT_MAX_BYTES);
+    let (second, _) = trace("seeded-2", &rom, &options, TraceFormat::Text, &[], DEFAULT_MAX_BYTES);
+
+    assert!(!first.is_empty());
+    assert_eq!(first, second);
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "viewer")]
+
+use chip8_emu::viewer::{Server, FRAME};
+use chip8_emu::{Chip8, Palette};
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn streams_to_a_websocket_client() {
+    let mut server = Server::bind("127.0.0.1:0").unwrap();
+    let mut chip8 = Chip8::with_seed(1);
+    chip8.load(&[0xD0, 0x05, 0x12, 0x02]).unwrap();
+
+    let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+    write!(
+        stream,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n"
+    )
+    .unwrap();
+
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut response = String::new();
+    while !response.ends_with("\r\n\r\n") {
+        assert!(reader.read_line(&mut response).unwrap() > 0, "{}", response);
+    }
+    assert!(response.starts_with("HTTP/1.1 101"), "{}", response);
+    assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo="), "{}", response);
+
+    chip8.run_frame(10);
+    server.publish(&chip8, Palette::default());
+
+    // a binary frame too big for the short length
+    let mut header = [0; 4];
+    reader.read_exact(&mut header).unwrap();
+    assert_eq!(header[..2], [0x82, 126]);
+    let mut message = vec![0; u16::from_be_bytes([header[2], header[3]]) as usize];
+    reader.read_exact(&mut message).unwrap();
+    assert_eq!(message[0], FRAME);
+    assert_eq!(message[11], 0xF0);
+
+    // key 5 down, masked like every client frame
+    let mask = [1, 2, 3, 4];
+    stream.write_all(&[0x82, 0x82, mask[0], mask[1], mask[2], mask[3], 5 ^ mask[0], 1 ^ mask[1]]).unwrap();
+
+    let start = Instant::now();
+    while !chip8.keys()[5] {
+        assert!(start.elapsed() < Duration::from_secs(5), "the key never arrived");
+        thread::sleep(Duration::from_millis(10));
+        server.poll(&mut chip8);
+    }
+    assert_eq!(server.client_count(), 1);
+}
@@ -0,0 +1,48 @@
+// run with `wasm-pack test --node -- --no-default-features --features wasm`, natively this is empty
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+use chip8_emu::wasm::Emulator;
+use chip8_emu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use wasm_bindgen_test::wasm_bindgen_test;
+
+// draw "0" at (0, 0) then loop
+const ROM: [u8; 4] = [0xD0, 0x05, 0x12, 0x02];
+
+#[wasm_bindgen_test]
+fn loads_and_runs_frames() {
+    let mut emulator = Emulator::new(&ROM, Some(1)).unwrap();
+
+    for _ in 0..3 {
+        emulator.run_frame();
+    }
+
+    let rgba = emulator.display_rgba();
+    assert_eq!(rgba.length() as usize, SCREEN_WIDTH * SCREEN_HEIGHT * 4);
+    assert_eq!(rgba.to_vec()[..4], [50, 169, 86, 0xFF]);
+    assert!(!emulator.is_beeping());
+}
+
+#[wasm_bindgen_test]
+fn saves_and_loads_state() {
+    let mut emulator = Emulator::new(&ROM, None).unwrap();
+    let state = emulator.save_state();
+
+    emulator.run_frame();
+    emulator.load_state(&state).unwrap();
+
+    assert_eq!(emulator.display_rgba().to_vec()[..4], [0, 0, 0, 0xFF]);
+}
+
+#[wasm_bindgen_test]
+fn fills_audio_while_beeping() {
+    // ST = V0 = 30, then loop
+    let mut emulator = Emulator::new(&[0x60, 0x1E, 0xF0, 0x18, 0x12, 0x04], Some(1)).unwrap();
+    let mut out = vec![0.0; 512];
+
+    emulator.run_frame();
+    emulator.fill_audio(&mut out, 44100);
+
+    assert!(emulator.is_beeping());
+    assert!(out.iter().any(|&sample| sample != 0.0));
+}